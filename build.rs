@@ -131,6 +131,46 @@ fn make_add_watermark_callback(
     Ok(())
 }
 
+/// Exposes the commit from which erdos is built as `ERDOS_GIT_HASH`, if built from a git checkout.
+fn set_git_hash() {
+    let output = Command::new("git")
+        .args(&["rev-parse", "--short", "HEAD"])
+        .output();
+    if let Ok(output) = output {
+        if output.status.success() {
+            let git_hash = String::from_utf8_lossy(&output.stdout);
+            println!("cargo:rustc-env=ERDOS_GIT_HASH={}", git_hash.trim());
+        }
+    }
+    watch_git_head();
+}
+
+/// Re-runs build.rs when HEAD moves, either by switching branches or by committing on the
+/// current branch, whose ref may be loose or packed.
+fn watch_git_head() {
+    let git_dir = match Command::new("git")
+        .args(&["rev-parse", "--git-dir"])
+        .output()
+    {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        }
+        _ => ".git".to_string(),
+    };
+    let git_dir = Path::new(&git_dir);
+    let head = git_dir.join("HEAD");
+    let mut watched = vec![head.clone(), git_dir.join("packed-refs")];
+    if let Ok(contents) = std::fs::read_to_string(&head) {
+        if let Some(head_ref) = contents.trim().strip_prefix("ref: ") {
+            watched.push(git_dir.join(head_ref));
+        }
+    }
+    // Cargo re-runs build.rs on every build if a watched file does not exist.
+    for path in watched.iter().filter(|path| path.exists()) {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+}
+
 fn main() -> Result<(), String> {
     let logger = Logger::root(Mutex::new(slog_term::term_full()).fuse(), slog::o!());
     let bundle_max_read_streams: usize = parse_env_variable(
@@ -158,6 +198,8 @@ fn main() -> Result<(), String> {
         "Done generating code for adding callbacks over vectors of streams."
     );
 
+    set_git_hash();
+
     // Re-run build.rs if the following files are changed.
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-changed=scripts/make_callback_builder.py");
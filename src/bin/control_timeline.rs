@@ -0,0 +1,22 @@
+//! Merges the control message logs written by nodes which run with `--control-log-directory`,
+//! and prints the cluster-wide timeline of control messages.
+//!
+//! Usage: `erdos-control-timeline control-0.log control-1.log ...`
+use std::{env, process};
+
+use erdos::communication::{merge_control_logs, render_control_timeline};
+
+fn main() {
+    let paths: Vec<String> = env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("Usage: erdos-control-timeline <control log>...");
+        process::exit(2);
+    }
+    match merge_control_logs(&paths) {
+        Ok(records) => print!("{}", render_control_timeline(&records)),
+        Err(e) => {
+            eprintln!("Unable to read the control message logs: {}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use byteorder::{ByteOrder, NetworkEndian, WriteBytesExt};
+#[cfg(feature = "auth")]
+use hmac::{Hmac, Mac, NewMac};
+use rand::{OsRng, Rng};
+#[cfg(feature = "auth")]
+use sha2::Sha256;
+#[cfg(feature = "auth")]
+use std::{fs, path::Path};
+use std::{io, time::Duration};
+use tokio::prelude::*;
+
+use crate::node::NodeId;
+
+/// Size in bytes of the random challenges which the nodes send each other upon a connection.
+const CHALLENGE_SIZE: usize = 32;
+/// Maximum size in bytes of the response to a challenge, which bounds the memory that the other
+/// end of a connection makes the node allocate.
+const MAX_RESPONSE_SIZE: usize = 1024;
+/// Sent after the response to a challenge is verified. Rejected connections are closed instead.
+const ACCEPTED: u8 = 1;
+/// Duration after which a node which connected to the node without completing the handshake is
+/// rejected.
+pub(crate) const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Authenticates the nodes which connect to a node with the TCP transport, so that a process
+/// without the credentials of the dataflow cannot join the control plane and send control
+/// messages, e.g. to run operators or to shut down the nodes.
+///
+/// Upon a connection, the node which accepted the connection sends a random challenge, which
+/// the connecting node answers with [`respond`](Authenticator::respond). Once
+/// [`verify`](Authenticator::verify) accepts the response, the connecting node challenges the
+/// other node in turn, so that a process listening on the address of a node cannot impersonate
+/// it. The connection is closed unless both nodes are authenticated. All nodes of a dataflow
+/// must use the same authenticator, and nodes which communicate with the Zenoh transports cannot
+/// use one.
+pub trait Authenticator: Send + Sync {
+    /// Returns the response of the node `node_id` to the challenge.
+    fn respond(&self, node_id: NodeId, challenge: &[u8]) -> Vec<u8>;
+
+    /// Checks the response of the node `node_id` to the challenge.
+    fn verify(&self, node_id: NodeId, challenge: &[u8], response: &[u8]) -> bool;
+}
+
+/// An [`Authenticator`] which accepts the nodes holding a secret shared by all nodes.
+///
+/// Nodes respond to challenges with an HMAC-SHA256 of the challenge and of their ID, keyed with
+/// the secret, so the secret is never sent over the network. Requires the `auth` feature.
+#[cfg(feature = "auth")]
+pub struct SharedSecretAuthenticator {
+    secret: Vec<u8>,
+}
+
+#[cfg(feature = "auth")]
+impl SharedSecretAuthenticator {
+    pub fn new<S: AsRef<[u8]>>(secret: S) -> Self {
+        Self {
+            secret: secret.as_ref().to_vec(),
+        }
+    }
+
+    /// Reads the secret from a file, ignoring the whitespace at its ends, so that the secret
+    /// does not show in the arguments of the process.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let contents = fs::read(path)?;
+        let start = contents
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "The secret is empty"))?;
+        let end = contents
+            .iter()
+            .rposition(|b| !b.is_ascii_whitespace())
+            .unwrap();
+        Ok(Self::new(&contents[start..=end]))
+    }
+
+    fn mac(&self, node_id: NodeId, challenge: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_varkey(&self.secret).expect("HMAC accepts keys of any size");
+        mac.update(&(node_id as u32).to_be_bytes());
+        mac.update(challenge);
+        mac
+    }
+}
+
+#[cfg(feature = "auth")]
+impl Authenticator for SharedSecretAuthenticator {
+    fn respond(&self, node_id: NodeId, challenge: &[u8]) -> Vec<u8> {
+        self.mac(node_id, challenge)
+            .finalize()
+            .into_bytes()
+            .to_vec()
+    }
+
+    fn verify(&self, node_id: NodeId, challenge: &[u8], response: &[u8]) -> bool {
+        // Compares the MACs in constant time.
+        self.mac(node_id, challenge).verify(response).is_ok()
+    }
+}
+
+/// Authenticates the node `node_id` to the node `peer_id` to which it connected, then
+/// authenticates the other node.
+///
+/// Returns a [`PermissionDenied`](io::ErrorKind::PermissionDenied) error if either node rejects
+/// the response of the other, in which case the connection must be closed.
+pub(crate) async fn connect_handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    node_id: NodeId,
+    peer_id: NodeId,
+    authenticator: &dyn Authenticator,
+) -> io::Result<()> {
+    answer_challenge(stream, node_id, authenticator).await?;
+    challenge_peer(stream, peer_id, authenticator).await
+}
+
+/// Authenticates the node `peer_id` which connected to the node `node_id`, then authenticates
+/// the node to the other node.
+///
+/// Returns a [`PermissionDenied`](io::ErrorKind::PermissionDenied) error if either node rejects
+/// the response of the other, in which case the connection must be closed.
+pub(crate) async fn accept_handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    node_id: NodeId,
+    peer_id: NodeId,
+    authenticator: &dyn Authenticator,
+) -> io::Result<()> {
+    challenge_peer(stream, peer_id, authenticator).await?;
+    answer_challenge(stream, node_id, authenticator).await
+}
+
+/// Answers the challenge of the other node with the response of the node `node_id`.
+///
+/// Returns a [`PermissionDenied`](io::ErrorKind::PermissionDenied) error if the other node
+/// rejects the response.
+async fn answer_challenge<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    node_id: NodeId,
+    authenticator: &dyn Authenticator,
+) -> io::Result<()> {
+    let mut challenge = [0u8; CHALLENGE_SIZE];
+    stream.read_exact(&mut challenge).await?;
+    let response = authenticator.respond(node_id, &challenge);
+    let mut buffer = Vec::with_capacity(4 + response.len());
+    WriteBytesExt::write_u32::<NetworkEndian>(&mut buffer, response.len() as u32)?;
+    buffer.extend(response);
+    stream.write_all(&buffer).await?;
+    let mut accepted = [0u8; 1];
+    match stream.read_exact(&mut accepted).await {
+        Ok(_) if accepted[0] == ACCEPTED => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("Node {} was not authenticated", node_id),
+        )),
+    }
+}
+
+/// Challenges the node `node_id` at the other end of the connection, and accepts it if the
+/// authenticator verifies the response.
+///
+/// Returns a [`PermissionDenied`](io::ErrorKind::PermissionDenied) error if the response is
+/// rejected.
+async fn challenge_peer<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    node_id: NodeId,
+    authenticator: &dyn Authenticator,
+) -> io::Result<()> {
+    let mut challenge = [0u8; CHALLENGE_SIZE];
+    OsRng::new()?.fill_bytes(&mut challenge);
+    stream.write_all(&challenge).await?;
+    let mut size = [0u8; 4];
+    stream.read_exact(&mut size).await?;
+    let size = NetworkEndian::read_u32(&size) as usize;
+    let authenticated = if size <= MAX_RESPONSE_SIZE {
+        let mut response = vec![0u8; size];
+        stream.read_exact(&mut response).await?;
+        authenticator.verify(node_id, &challenge, &response)
+    } else {
+        false
+    };
+    if !authenticated {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("Node {} failed to authenticate", node_id),
+        ));
+    }
+    stream.write_all(&[ACCEPTED]).await
+}
+
+#[cfg(all(test, feature = "auth"))]
+mod tests {
+    use super::*;
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Accepts any response, but does not hold the secret.
+    struct ImpostorAuthenticator;
+
+    impl Authenticator for ImpostorAuthenticator {
+        fn respond(&self, _node_id: NodeId, _challenge: &[u8]) -> Vec<u8> {
+            vec![0; 32]
+        }
+
+        fn verify(&self, _node_id: NodeId, _challenge: &[u8], _response: &[u8]) -> bool {
+            true
+        }
+    }
+
+    /// Test that the nodes holding the shared secret authenticate each other, and that the
+    /// connection is rejected if either node does not hold the secret.
+    #[test]
+    fn test_shared_secret_handshake() {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let authenticator = SharedSecretAuthenticator::new("secret");
+            let cases: Vec<(Box<dyn Authenticator>, Box<dyn Authenticator>, bool)> = vec![
+                (
+                    Box::new(SharedSecretAuthenticator::new("secret")),
+                    Box::new(SharedSecretAuthenticator::new("secret")),
+                    true,
+                ),
+                (
+                    Box::new(SharedSecretAuthenticator::new("guess")),
+                    Box::new(SharedSecretAuthenticator::new("secret")),
+                    false,
+                ),
+                (
+                    Box::new(SharedSecretAuthenticator::new("secret")),
+                    Box::new(ImpostorAuthenticator),
+                    false,
+                ),
+            ];
+            for (connector, acceptor, expect_authenticated) in cases.iter() {
+                let (connected, accepted) =
+                    futures::join!(TcpStream::connect(addr), listener.accept());
+                let (mut connected, (mut accepted, _)) = (connected.unwrap(), accepted.unwrap());
+                let (connect_result, accept_result) = futures::join!(
+                    async move {
+                        let result =
+                            connect_handshake(&mut connected, 1, 0, connector.as_ref()).await;
+                        // Rejected connections are closed.
+                        drop(connected);
+                        result
+                    },
+                    async move {
+                        let result = accept_handshake(&mut accepted, 0, 1, acceptor.as_ref()).await;
+                        drop(accepted);
+                        result
+                    }
+                );
+                assert_eq!(connect_result.is_ok(), *expect_authenticated);
+                assert_eq!(accept_result.is_ok(), *expect_authenticated);
+            }
+
+            // The response is bound to the ID of the node.
+            let response = authenticator.respond(1, b"challenge");
+            assert!(authenticator.verify(1, b"challenge", &response));
+            assert!(!authenticator.verify(2, b"challenge", &response));
+        });
+    }
+}
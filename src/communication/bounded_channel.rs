@@ -0,0 +1,516 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    task::{Context, Poll},
+};
+
+use futures::task::AtomicWaker;
+use tokio::sync::Notify;
+
+use crate::{
+    communication::{CommunicationError, TryRecvError},
+    dataflow::stream::StreamId,
+    node::NodeId,
+};
+
+thread_local! {
+    /// Whether the current thread runs tasks of a runtime, which must not wait for the channels
+    /// to have room.
+    static DEFERRING: Cell<bool> = Cell::new(false);
+    /// The full channels on which messages were sent since the last [`defer_blocking`].
+    static BLOCKED: RefCell<Vec<Arc<dyn Capacity>>> = RefCell::new(Vec::new());
+}
+
+/// What a bounded channel does with a message sent while it is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// Blocks the sending operator until the receiving operator reads a message. Nodes hold the
+    /// messages they send on the stream to another node until the other node grants them
+    /// credits, which it does as its operators read the messages, so the policy is not
+    /// supported with the Zenoh transports.
+    Block,
+    /// Drops the oldest message queued, e.g. for sensor streams of which only the latest
+    /// messages matter.
+    DropOldest,
+    /// Drops the message sent.
+    DropNewest,
+}
+
+/// Bounds the number of messages queued for each operator which reads a stream, so that a slow
+/// operator cannot exhaust the memory of the node.
+///
+/// Only messages which carry data count towards the capacity, and watermarks are never dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChannelBound {
+    /// Number of messages queued for an operator, which is at least 1.
+    pub capacity: usize,
+    pub policy: BackpressurePolicy,
+}
+
+impl ChannelBound {
+    pub fn new(capacity: usize, policy: BackpressurePolicy) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            policy,
+        }
+    }
+}
+
+struct QueueState<D> {
+    /// The queued messages, and whether each carries data.
+    messages: VecDeque<(D, bool)>,
+    /// Number of queued messages which carry data.
+    num_data: usize,
+    num_senders: usize,
+    receiver_dropped: bool,
+}
+
+struct Queue<D> {
+    bound: ChannelBound,
+    state: Mutex<QueueState<D>>,
+    /// Notifies the blocked senders that a message was read.
+    not_full: Condvar,
+    /// Notifies one of the senders waiting asynchronously that a message was read.
+    not_full_notify: Notify,
+    /// Wakes the receiver once a message is queued, or all senders are dropped.
+    receiver_waker: AtomicWaker,
+}
+
+/// Sends messages on a bounded channel, and applies its [`BackpressurePolicy`] once it is full.
+pub(crate) struct BoundedSender<D> {
+    queue: Arc<Queue<D>>,
+    /// Whether the sender blocks with [`BackpressurePolicy::Block`]. Data receivers do not
+    /// block, as the credits granted to the sending node bound the messages they receive.
+    blocking: bool,
+}
+
+/// Receives the messages of a bounded channel.
+pub(crate) struct BoundedReceiver<D> {
+    queue: Arc<Queue<D>>,
+    /// Counts the messages read if they are received from another node, with the index of the
+    /// receiver.
+    credits: Option<(Arc<CreditCounter>, usize)>,
+}
+
+/// Creates a bounded channel between operators of the current process.
+pub(crate) fn channel<D>(bound: ChannelBound) -> (BoundedSender<D>, BoundedReceiver<D>) {
+    let queue = Arc::new(Queue {
+        bound,
+        state: Mutex::new(QueueState {
+            messages: VecDeque::new(),
+            num_data: 0,
+            num_senders: 1,
+            receiver_dropped: false,
+        }),
+        not_full: Condvar::new(),
+        not_full_notify: Notify::new(),
+        receiver_waker: AtomicWaker::new(),
+    });
+    let sender = BoundedSender {
+        queue: Arc::clone(&queue),
+        blocking: true,
+    };
+    (
+        sender,
+        BoundedReceiver {
+            queue,
+            credits: None,
+        },
+    )
+}
+
+/// Creates a bounded channel on which a data receiver passes the messages of the stream
+/// received from the node to an operator. With [`BackpressurePolicy::Block`], a credit is
+/// granted to the node for each message which all operators reading the stream read, which is
+/// counted by `credits`.
+pub(crate) fn channel_from_node<D>(
+    bound: ChannelBound,
+    node_id: NodeId,
+    stream_id: StreamId,
+    credits: &CreditCounters,
+) -> (BoundedSender<D>, BoundedReceiver<D>) {
+    let (mut sender, mut receiver) = channel(bound);
+    sender.blocking = false;
+    if bound.policy == BackpressurePolicy::Block {
+        let counter = credits.counter(node_id, stream_id);
+        let index = counter.add_reader();
+        receiver.credits = Some((counter, index));
+    }
+    (sender, receiver)
+}
+
+impl<D: 'static + Send> BoundedSender<D> {
+    /// Queues the message, which carries data if `is_data` is set.
+    ///
+    /// With [`BackpressurePolicy::Block`], blocks the thread while the channel is full. Threads
+    /// which run tasks of a runtime do not block: the message is queued past the capacity, and
+    /// the caller of [`defer_blocking`] waits for the channel to have room.
+    pub fn send(&self, msg: D, is_data: bool) -> Result<(), CommunicationError> {
+        let capacity = self.queue.bound.capacity;
+        let mut state = self.queue.state.lock().unwrap();
+        if state.receiver_dropped {
+            return Err(CommunicationError::Disconnected);
+        }
+        if is_data && state.num_data >= capacity {
+            match self.queue.bound.policy {
+                BackpressurePolicy::DropNewest => return Ok(()),
+                BackpressurePolicy::DropOldest => {
+                    if let Some(i) = state.messages.iter().position(|(_, is_data)| *is_data) {
+                        state.messages.remove(i);
+                        state.num_data -= 1;
+                    }
+                }
+                BackpressurePolicy::Block if self.blocking && DEFERRING.with(Cell::get) => {
+                    let queue: Arc<dyn Capacity> = Arc::clone(&self.queue) as _;
+                    BLOCKED.with(|blocked| blocked.borrow_mut().push(queue));
+                }
+                BackpressurePolicy::Block if self.blocking => {
+                    while state.num_data >= capacity && !state.receiver_dropped {
+                        state = self.queue.not_full.wait(state).unwrap();
+                    }
+                    if state.receiver_dropped {
+                        return Err(CommunicationError::Disconnected);
+                    }
+                }
+                BackpressurePolicy::Block => (),
+            }
+        }
+        state.messages.push_back((msg, is_data));
+        if is_data {
+            state.num_data += 1;
+        }
+        drop(state);
+        self.queue.receiver_waker.wake();
+        Ok(())
+    }
+}
+
+impl<D> Clone for BoundedSender<D> {
+    fn clone(&self) -> Self {
+        self.queue.state.lock().unwrap().num_senders += 1;
+        Self {
+            queue: Arc::clone(&self.queue),
+            blocking: self.blocking,
+        }
+    }
+}
+
+impl<D> Drop for BoundedSender<D> {
+    fn drop(&mut self) {
+        let mut state = self.queue.state.lock().unwrap();
+        state.num_senders -= 1;
+        if state.num_senders == 0 {
+            drop(state);
+            self.queue.receiver_waker.wake();
+        }
+    }
+}
+
+impl<D> BoundedReceiver<D> {
+    /// Reads the next message. Returns [`TryRecvError::Disconnected`] once all senders are
+    /// dropped and all messages were read.
+    pub fn try_recv(&mut self) -> Result<D, TryRecvError> {
+        let mut state = self.queue.state.lock().unwrap();
+        match state.messages.pop_front() {
+            Some((msg, is_data)) => {
+                if is_data {
+                    state.num_data -= 1;
+                    drop(state);
+                    self.queue.not_full.notify_one();
+                    self.queue.not_full_notify.notify();
+                    if let Some((counter, index)) = &self.credits {
+                        counter.read(*index);
+                    }
+                }
+                Ok(msg)
+            }
+            None if state.num_senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Polls for the next message. Returns `None` once all senders are dropped and all messages
+    /// were read.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<D>> {
+        match self.try_recv() {
+            Ok(msg) => return Poll::Ready(Some(msg)),
+            Err(TryRecvError::Empty) => (),
+            Err(_) => return Poll::Ready(None),
+        }
+        self.queue.receiver_waker.register(cx.waker());
+        // A message may have been queued before the waker was registered.
+        match self.try_recv() {
+            Ok(msg) => Poll::Ready(Some(msg)),
+            Err(TryRecvError::Empty) => Poll::Pending,
+            Err(_) => Poll::Ready(None),
+        }
+    }
+
+    pub async fn recv(&mut self) -> Option<D> {
+        futures::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+}
+
+impl<D> Drop for BoundedReceiver<D> {
+    fn drop(&mut self) {
+        self.queue.state.lock().unwrap().receiver_dropped = true;
+        self.queue.not_full.notify_all();
+        // The woken sender wakes the next one.
+        self.queue.not_full_notify.notify();
+        if let Some((counter, index)) = &self.credits {
+            counter.remove_reader(*index);
+        }
+    }
+}
+
+/// The capacity of a channel, on which senders wait without knowing the type of its messages.
+trait Capacity: Send + Sync {
+    /// Whether the channel is full, and its receiver is not dropped.
+    fn is_full(&self) -> bool;
+
+    fn not_full_notify(&self) -> &Notify;
+}
+
+impl<D: Send> Capacity for Queue<D> {
+    fn is_full(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.num_data >= self.bound.capacity && !state.receiver_dropped
+    }
+
+    fn not_full_notify(&self) -> &Notify {
+        &self.not_full_notify
+    }
+}
+
+/// The full channels on which messages were sent by a function run with [`defer_blocking`].
+#[must_use]
+pub(crate) struct BlockedSends(Vec<Arc<dyn Capacity>>);
+
+impl BlockedSends {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Waits until the channels have room.
+    pub async fn wait(self) {
+        for queue in self.0 {
+            loop {
+                let notified = queue.not_full_notify().notified();
+                if !queue.is_full() {
+                    break;
+                }
+                notified.await;
+            }
+            // Passes the notification on, in case other senders wait on the channel.
+            queue.not_full_notify().notify();
+        }
+    }
+}
+
+/// Runs the function on a thread which runs tasks of a runtime, so that the messages the
+/// function sends on full channels with [`BackpressurePolicy::Block`] are queued instead of
+/// blocking the thread. Returns the full channels, on which the task waits with
+/// [`BlockedSends::wait`] before the function runs again.
+pub(crate) fn defer_blocking<R, F: FnOnce() -> R>(f: F) -> (R, BlockedSends) {
+    let deferring = set_deferring(true);
+    let result = f();
+    set_deferring(deferring);
+    let blocked = BLOCKED.with(|blocked| blocked.replace(Vec::new()));
+    (result, BlockedSends(blocked))
+}
+
+/// Sets whether the current thread runs tasks of a runtime, and returns the previous setting.
+/// Core threads always do, while threads of the threaded runtime may block inside
+/// `block_in_place`.
+pub(crate) fn set_deferring(deferring: bool) -> bool {
+    DEFERRING.with(|cell| cell.replace(deferring))
+}
+
+#[derive(Default)]
+struct CreditState {
+    /// Number of messages read by each receiver, or `None` once the receiver is dropped.
+    reads: Vec<Option<u64>>,
+    /// Number of credits granted to the sending node.
+    granted: u64,
+}
+
+impl CreditState {
+    /// The number of messages which all receivers read.
+    fn min_reads(&self) -> Option<u64> {
+        self.reads.iter().filter_map(|reads| *reads).min()
+    }
+}
+
+/// Counts the messages of a stream received from a node which the local operators read.
+#[derive(Default)]
+struct CreditCounter {
+    state: Mutex<CreditState>,
+}
+
+impl CreditCounter {
+    /// Adds a receiver, which starts at the messages read by the other receivers so that it does
+    /// not hold back the credits. Returns its index.
+    fn add_reader(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let reads = state.min_reads().unwrap_or(state.granted);
+        state.reads.push(Some(reads));
+        state.reads.len() - 1
+    }
+
+    fn read(&self, index: usize) {
+        if let Some(reads) = &mut self.state.lock().unwrap().reads[index] {
+            *reads += 1;
+        }
+    }
+
+    fn remove_reader(&self, index: usize) {
+        self.state.lock().unwrap().reads[index] = None;
+    }
+
+    /// Returns the credits to grant since the last call.
+    fn take_grant(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        match state.min_reads() {
+            Some(reads) if reads > state.granted => {
+                let grant = reads - state.granted;
+                state.granted = reads;
+                grant
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// Counts the messages which the operators of a node read from the bounded streams received
+/// from other nodes.
+#[derive(Default)]
+pub(crate) struct CreditCounters {
+    /// Indexed by the sending node and the stream.
+    counters: Mutex<HashMap<(NodeId, StreamId), Arc<CreditCounter>>>,
+    /// Avoids locking the counters if no blocking stream is received from other nodes.
+    granting: AtomicBool,
+}
+
+impl CreditCounters {
+    fn counter(&self, node_id: NodeId, stream_id: StreamId) -> Arc<CreditCounter> {
+        self.granting.store(true, Ordering::SeqCst);
+        Arc::clone(
+            self.counters
+                .lock()
+                .unwrap()
+                .entry((node_id, stream_id))
+                .or_insert_with(Default::default),
+        )
+    }
+
+    /// Returns the credits to grant to the other nodes for the messages of their blocking
+    /// streams which the operators read since the last call, grouped by node.
+    pub fn take_grants(&self) -> HashMap<NodeId, Vec<(StreamId, u64)>> {
+        let mut grants: HashMap<NodeId, Vec<(StreamId, u64)>> = HashMap::new();
+        if !self.granting.load(Ordering::Relaxed) {
+            return grants;
+        }
+        for (&(node_id, stream_id), counter) in self.counters.lock().unwrap().iter() {
+            let grant = counter.take_grant();
+            if grant > 0 {
+                grants.entry(node_id).or_default().push((stream_id, grant));
+            }
+        }
+        grants
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use futures::executor::block_on;
+
+    use super::*;
+
+    /// Test that full channels drop the oldest or the newest message, and never drop the
+    /// messages which carry no data.
+    #[test]
+    fn test_drop_policies() {
+        for &(policy, expected) in &[
+            (BackpressurePolicy::DropOldest, [0, 2, 3]),
+            (BackpressurePolicy::DropNewest, [0, 1, 2]),
+        ] {
+            let (sender, mut receiver) = channel(ChannelBound::new(2, policy));
+            sender.send(0, false).unwrap();
+            for i in 1..4 {
+                sender.send(i, true).unwrap();
+            }
+            drop(sender);
+            let mut received = Vec::new();
+            while let Some(msg) = block_on(receiver.recv()) {
+                received.push(msg);
+            }
+            assert_eq!(received, expected);
+        }
+    }
+
+    /// Test that senders on threads which run tasks queue the messages sent on full channels,
+    /// and wait until the receiver reads them.
+    #[test]
+    fn test_deferred_block_policy() {
+        let (sender, mut receiver) = channel(ChannelBound::new(1, BackpressurePolicy::Block));
+        let ((), blocked) = defer_blocking(|| {
+            for i in 0..2 {
+                sender.send(i, true).unwrap();
+            }
+        });
+        let handle = thread::spawn(move || block_on(blocked.wait()));
+        for i in 0..2 {
+            assert_eq!(block_on(receiver.recv()), Some(i));
+        }
+        handle.join().unwrap();
+    }
+
+    /// Test that senders block until the receiver reads a message, and that the credits of the
+    /// messages received from another node are granted once all receivers read them.
+    #[test]
+    fn test_block_policy() {
+        let bound = ChannelBound::new(1, BackpressurePolicy::Block);
+        let (sender, mut receiver) = channel(bound);
+        let handle = thread::spawn(move || {
+            for i in 0..3 {
+                sender.send(i, true).unwrap();
+            }
+        });
+        for i in 0..3 {
+            assert_eq!(block_on(receiver.recv()), Some(i));
+        }
+        handle.join().unwrap();
+        assert_eq!(block_on(receiver.recv()), None);
+
+        let stream_id = StreamId::new_deterministic();
+        let credits = CreditCounters::default();
+        let (first_sender, mut first_receiver) = channel_from_node(bound, 1, stream_id, &credits);
+        let (second_sender, mut second_receiver) = channel_from_node(bound, 1, stream_id, &credits);
+        // Data receivers do not block, as the credits bound the messages sent.
+        for i in 0..2 {
+            first_sender.send(i, true).unwrap();
+            second_sender.send(i, true).unwrap();
+        }
+        let granted = |grants: HashMap<NodeId, Vec<(StreamId, u64)>>| {
+            grants
+                .get(&1)
+                .and_then(|grants| grants.iter().find(|(id, _)| *id == stream_id))
+                .map_or(0, |(_, grant)| *grant)
+        };
+        first_receiver.try_recv().unwrap();
+        first_receiver.try_recv().unwrap();
+        assert_eq!(granted(credits.take_grants()), 0);
+        second_receiver.try_recv().unwrap();
+        assert_eq!(granted(credits.take_grants()), 1);
+        drop(second_receiver);
+        assert_eq!(granted(credits.take_grants()), 1);
+        // Nodes which run in the same process count their credits separately.
+        assert_eq!(granted(CreditCounters::default().take_grants()), 0);
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "tcp_transport")]
+use bytes::BytesMut;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    communication::{CodecError, InterProcessMessage, MessageMetadata},
+    dataflow::stream::StreamId,
+};
+
+/// Bytes of a serialized message, as used by the transport.
+#[cfg(feature = "tcp_transport")]
+pub(crate) type SerializedBytes = BytesMut;
+#[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
+pub(crate) type SerializedBytes = zenoh::net::protocol::io::ArcSlice;
+
+#[cfg(feature = "tcp_transport")]
+pub(crate) fn to_serialized_bytes(buf: &[u8]) -> SerializedBytes {
+    BytesMut::from(buf)
+}
+
+#[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
+pub(crate) fn to_serialized_bytes(buf: &[u8]) -> SerializedBytes {
+    zenoh::net::protocol::io::ArcSlice::from(buf)
+}
+
+#[cfg(feature = "tcp_transport")]
+pub(crate) fn as_slice(bytes: &SerializedBytes) -> &[u8] {
+    &bytes[..]
+}
+
+#[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
+pub(crate) fn as_slice(bytes: &SerializedBytes) -> &[u8] {
+    bytes.as_slice()
+}
+
+/// Position of a chunk within a message that was split by a [`Chunker`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkMetadata {
+    /// Identifies the message to which the chunk belongs. Unique per sender.
+    pub message_id: u64,
+    /// Index of the chunk within the message.
+    pub index: u32,
+    /// Total number of chunks the message was split into.
+    pub num_chunks: u32,
+    /// Set on an empty chunk to cancel the transfer of the message.
+    pub cancelled: bool,
+}
+
+/// Splits messages whose serialized data is larger than the maximum chunk size into
+/// several [`InterProcessMessage`]s, which are reassembled by the [`Reassembler`].
+pub(crate) struct Chunker {
+    /// The maximum size of a chunk. Messages are never split if `None`.
+    max_chunk_size: Option<usize>,
+    next_message_id: u64,
+}
+
+impl Chunker {
+    pub fn new(max_chunk_size: Option<usize>) -> Self {
+        if let Some(max_chunk_size) = max_chunk_size {
+            assert!(max_chunk_size > 0, "Chunks must be at least 1 byte large.");
+        }
+        Self {
+            max_chunk_size,
+            next_message_id: 0,
+        }
+    }
+
+    /// Splits the message into chunks if it is larger than the maximum chunk size.
+    /// Otherwise, returns the message unchanged.
+    pub fn split(
+        &mut self,
+        msg: InterProcessMessage,
+    ) -> Result<Vec<InterProcessMessage>, CodecError> {
+        let max_chunk_size = match self.max_chunk_size {
+            Some(max_chunk_size) => max_chunk_size,
+            None => return Ok(vec![msg]),
+        };
+        let (metadata, bytes) = match &msg {
+            InterProcessMessage::Deserialized { metadata, data } => {
+                let data_size = data.serialized_size().map_err(|e| {
+                    CodecError::BincodeError(Box::new(bincode::ErrorKind::Custom(format!(
+                        "{:?}",
+                        e
+                    ))))
+                })?;
+                if data_size <= max_chunk_size {
+                    return Ok(vec![msg]);
+                }
+                (metadata.clone(), data.encode_into_vec()?)
+            }
+            InterProcessMessage::Serialized { metadata, bytes } => {
+                if bytes.len() <= max_chunk_size {
+                    return Ok(vec![msg]);
+                }
+                (metadata.clone(), as_slice(bytes).to_vec())
+            }
+        };
+
+        let message_id = self.next_message_id;
+        self.next_message_id += 1;
+        let num_chunks = ((bytes.len() + max_chunk_size - 1) / max_chunk_size) as u32;
+        let chunks = bytes
+            .chunks(max_chunk_size)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let metadata = MessageMetadata {
+                    chunk: Some(ChunkMetadata {
+                        message_id,
+                        index: index as u32,
+                        num_chunks,
+                        cancelled: false,
+                    }),
+                    ..metadata.clone()
+                };
+                InterProcessMessage::new_serialized(to_serialized_bytes(chunk), metadata)
+            })
+            .collect();
+        Ok(chunks)
+    }
+
+    /// Returns a message which cancels the transfer of the message to which `chunk` belongs.
+    /// Returns `None` if `chunk` is not part of a chunked message.
+    pub fn cancellation(chunk: &InterProcessMessage) -> Option<InterProcessMessage> {
+        let metadata = match chunk {
+            InterProcessMessage::Deserialized { metadata, data: _ } => metadata,
+            InterProcessMessage::Serialized { metadata, bytes: _ } => metadata,
+        };
+        metadata.chunk.as_ref().map(|chunk_metadata| {
+            let metadata = MessageMetadata {
+                chunk: Some(ChunkMetadata {
+                    cancelled: true,
+                    ..chunk_metadata.clone()
+                }),
+                ..metadata.clone()
+            };
+            InterProcessMessage::new_serialized(to_serialized_bytes(&[]), metadata)
+        })
+    }
+}
+
+/// Error raised when a chunked message cannot be reassembled.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ReassemblyError {
+    /// The sender cancelled the transfer of the message.
+    Cancelled { message_id: u64 },
+    /// A chunk was lost or arrived out of order.
+    MissingChunk {
+        message_id: u64,
+        expected_index: u32,
+        received_index: u32,
+    },
+}
+
+/// A message for which only some chunks were received.
+struct PartialMessage {
+    message_id: u64,
+    num_chunks: u32,
+    next_index: u32,
+    bytes: Vec<u8>,
+}
+
+/// Reassembles messages split by a [`Chunker`].
+///
+/// Chunks of a message must be received in order. A stream only has one message in
+/// transfer at a time, as senders forward all chunks of a message before the next message.
+pub(crate) struct Reassembler {
+    partial_messages: HashMap<StreamId, PartialMessage>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self {
+            partial_messages: HashMap::new(),
+        }
+    }
+
+    /// Adds a chunk of a message. Returns the serialized message once all its chunks are
+    /// received.
+    ///
+    /// On error, the partially received message is discarded.
+    pub fn add_chunk(
+        &mut self,
+        stream_id: StreamId,
+        chunk: &ChunkMetadata,
+        bytes: &[u8],
+    ) -> Result<Option<SerializedBytes>, ReassemblyError> {
+        if chunk.cancelled {
+            self.partial_messages.remove(&stream_id);
+            return Err(ReassemblyError::Cancelled {
+                message_id: chunk.message_id,
+            });
+        }
+        if chunk.index == 0 {
+            let previous = self.partial_messages.insert(
+                stream_id,
+                PartialMessage {
+                    message_id: chunk.message_id,
+                    num_chunks: chunk.num_chunks,
+                    next_index: 0,
+                    bytes: Vec::new(),
+                },
+            );
+            // The previous message will never complete.
+            if let Some(previous) = previous {
+                slog::warn!(
+                    crate::TERMINAL_LOGGER,
+                    "Discarding incomplete message {} on stream {} ({} of {} chunks received)",
+                    previous.message_id,
+                    stream_id,
+                    previous.next_index,
+                    previous.num_chunks
+                );
+            }
+        }
+        let partial = match self.partial_messages.get_mut(&stream_id) {
+            Some(partial)
+                if partial.message_id == chunk.message_id && partial.next_index == chunk.index =>
+            {
+                partial
+            }
+            Some(partial) => {
+                let expected_index = partial.next_index;
+                self.partial_messages.remove(&stream_id);
+                return Err(ReassemblyError::MissingChunk {
+                    message_id: chunk.message_id,
+                    expected_index,
+                    received_index: chunk.index,
+                });
+            }
+            None => {
+                return Err(ReassemblyError::MissingChunk {
+                    message_id: chunk.message_id,
+                    expected_index: 0,
+                    received_index: chunk.index,
+                })
+            }
+        };
+        partial.bytes.extend_from_slice(bytes);
+        partial.next_index += 1;
+        if partial.next_index < partial.num_chunks {
+            return Ok(None);
+        }
+        let message = self.partial_messages.remove(&stream_id).unwrap();
+        Ok(Some(to_serialized_bytes(&message.bytes)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::dataflow::{Message, Timestamp};
+
+    fn make_message(stream_id: StreamId, size: usize) -> InterProcessMessage {
+        let msg = Message::new_message(Timestamp::new(vec![1]), vec![7u8; size]);
+        InterProcessMessage::new_deserialized(Arc::new(msg), stream_id)
+    }
+
+    fn chunk_parts(chunk: &InterProcessMessage) -> (ChunkMetadata, Vec<u8>) {
+        match chunk {
+            InterProcessMessage::Serialized { metadata, bytes } => {
+                (metadata.chunk.clone().unwrap(), as_slice(bytes).to_vec())
+            }
+            InterProcessMessage::Deserialized { .. } => panic!("Chunks must be serialized"),
+        }
+    }
+
+    /// Test that small messages are not split.
+    #[test]
+    fn test_no_split() {
+        let mut chunker = Chunker::new(Some(1024));
+        let chunks = chunker
+            .split(make_message(StreamId::new_deterministic(), 10))
+            .unwrap();
+        assert_eq!(chunks.len(), 1);
+        match &chunks[0] {
+            InterProcessMessage::Deserialized { metadata, .. } => assert!(metadata.chunk.is_none()),
+            InterProcessMessage::Serialized { .. } => panic!("The message must not be serialized"),
+        }
+    }
+
+    /// Test that a split message is reassembled into the original serialized message.
+    #[test]
+    fn test_split_and_reassemble() {
+        let stream_id = StreamId::new_deterministic();
+        let msg = make_message(stream_id, 1000);
+        let expected = match &msg {
+            InterProcessMessage::Deserialized { data, .. } => data.encode_into_vec().unwrap(),
+            InterProcessMessage::Serialized { .. } => unreachable!(),
+        };
+
+        let mut chunker = Chunker::new(Some(64));
+        let chunks = chunker.split(msg).unwrap();
+        assert_eq!(chunks.len(), (expected.len() + 63) / 64);
+
+        let mut reassembler = Reassembler::new();
+        let mut result = None;
+        for chunk in chunks.iter() {
+            assert!(
+                result.is_none(),
+                "Message reassembled before the last chunk"
+            );
+            let (chunk_metadata, bytes) = chunk_parts(chunk);
+            result = reassembler
+                .add_chunk(stream_id, &chunk_metadata, &bytes)
+                .unwrap();
+        }
+        assert_eq!(as_slice(&result.unwrap()), expected.as_slice());
+    }
+
+    /// Test that the reassembler discards a message when a chunk is lost.
+    #[test]
+    fn test_missing_chunk() {
+        let stream_id = StreamId::new_deterministic();
+        let mut chunker = Chunker::new(Some(64));
+        let chunks = chunker.split(make_message(stream_id, 1000)).unwrap();
+
+        let mut reassembler = Reassembler::new();
+        let (first, bytes) = chunk_parts(&chunks[0]);
+        assert!(matches!(
+            reassembler.add_chunk(stream_id, &first, &bytes),
+            Ok(None)
+        ));
+        let (third, bytes) = chunk_parts(&chunks[2]);
+        assert!(matches!(
+            reassembler.add_chunk(stream_id, &third, &bytes),
+            Err(e) if e == ReassemblyError::MissingChunk {
+                message_id: first.message_id,
+                expected_index: 1,
+                received_index: 2,
+            }
+        ));
+    }
+
+    /// Test that a cancellation discards the partially received message.
+    #[test]
+    fn test_cancellation() {
+        let stream_id = StreamId::new_deterministic();
+        let mut chunker = Chunker::new(Some(64));
+        let chunks = chunker.split(make_message(stream_id, 1000)).unwrap();
+
+        let mut reassembler = Reassembler::new();
+        let (first, bytes) = chunk_parts(&chunks[0]);
+        assert!(matches!(
+            reassembler.add_chunk(stream_id, &first, &bytes),
+            Ok(None)
+        ));
+        let (cancellation, bytes) = chunk_parts(&Chunker::cancellation(&chunks[1]).unwrap());
+        assert!(matches!(
+            reassembler.add_chunk(stream_id, &cancellation, &bytes),
+            Err(e) if e == ReassemblyError::Cancelled {
+                message_id: first.message_id
+            }
+        ));
+        // The remaining chunks of the cancelled message are discarded.
+        let (second, bytes) = chunk_parts(&chunks[1]);
+        assert!(reassembler.add_chunk(stream_id, &second, &bytes).is_err());
+    }
+}
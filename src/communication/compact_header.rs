@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use bytes::{Buf, BufMut, BytesMut};
+
+use crate::{
+    communication::{
+        watermark_frame::{self, TICK_FLAG, TOP_FLAG},
+        CodecError, MessageMetadata,
+    },
+    dataflow::{stream::StreamId, Timestamp},
+    Uuid,
+};
+
+/// First byte of the frames with compact headers. Frames with full headers start with the size
+/// of their metadata, whose first byte is lower, and watermark frames start with `0xFF`.
+///
+/// Defines the index of a stream and run on the connection.
+const DEFINE_STREAM: u8 = 0xFC;
+/// Precedes the data of a message.
+const COMPACT_DATA: u8 = 0xFD;
+/// A watermark or a tick, whose flags and coordinates follow.
+const COMPACT_WATERMARK: u8 = 0xFE;
+
+/// Upper bound of the size of the header of a message, including the definition of its stream.
+pub(crate) const MAX_HEADER_SIZE: usize = (1 + 10 + 32) + (1 + 10 + 10 + 10);
+
+/// Returns whether the frame starting with the byte has a compact header.
+pub(crate) fn is_compact(first_byte: u8) -> bool {
+    match first_byte {
+        DEFINE_STREAM | COMPACT_DATA | COMPACT_WATERMARK => true,
+        _ => false,
+    }
+}
+
+/// Returns whether a message with the metadata can be sent with a compact header, which only
+/// carries its stream ID, run ID, and sequence number.
+pub(crate) fn fits(metadata: &MessageMetadata) -> bool {
+    metadata.chunk.is_none()
+        && !metadata.compressed
+        && metadata.timings.is_none()
+        && metadata.provenance.is_none()
+}
+
+fn put_varint<B: BufMut>(buf: &mut B, mut value: u64) {
+    while value >= 0x80 {
+        buf.put_u8(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.put_u8(value as u8);
+}
+
+/// Writes compact headers on a connection, which refer to streams by an index instead of their
+/// stream ID and run ID.
+///
+/// The first message of each stream and run is preceded by the definition of its index, so the
+/// receiving codec learns the indices from the connection itself.
+#[derive(Debug, Default)]
+pub(crate) struct CompactEncoder {
+    indices: HashMap<(StreamId, Uuid), u64>,
+}
+
+impl CompactEncoder {
+    /// Writes the header of a message whose data has `data_size` bytes.
+    pub fn encode_header(
+        &mut self,
+        metadata: &MessageMetadata,
+        data_size: usize,
+        buf: &mut BytesMut,
+    ) {
+        let index = self.index(metadata, buf);
+        buf.put_u8(COMPACT_DATA);
+        put_varint(buf, index);
+        put_varint(buf, metadata.sequence);
+        put_varint(buf, data_size as u64);
+    }
+
+    /// Writes a watermark or a tick, whose coordinates are written as varints.
+    pub fn encode_watermark(
+        &mut self,
+        metadata: &MessageMetadata,
+        timestamp: &Timestamp,
+        buf: &mut BytesMut,
+    ) {
+        let index = self.index(metadata, buf);
+        buf.put_u8(COMPACT_WATERMARK);
+        put_varint(buf, index);
+        put_varint(buf, metadata.sequence);
+        buf.put_u8(watermark_frame::flags(metadata, timestamp));
+        put_varint(buf, timestamp.time.len() as u64);
+        for coordinate in timestamp.time.iter() {
+            put_varint(buf, *coordinate);
+        }
+    }
+
+    fn index(&mut self, metadata: &MessageMetadata, buf: &mut BytesMut) -> u64 {
+        let key = (metadata.stream_id, metadata.run_id);
+        if let Some(index) = self.indices.get(&key) {
+            return *index;
+        }
+        let index = self.indices.len() as u64;
+        self.indices.insert(key, index);
+        buf.put_u8(DEFINE_STREAM);
+        put_varint(buf, index);
+        buf.put_slice(metadata.stream_id.as_bytes());
+        buf.put_slice(metadata.run_id.as_bytes());
+        index
+    }
+}
+
+/// A frame with a compact header.
+pub(crate) enum CompactFrame {
+    /// The definition of the index of a stream.
+    Stream,
+    /// The header of a message whose data follows.
+    Data {
+        metadata: MessageMetadata,
+        data_size: usize,
+    },
+    Watermark(MessageMetadata),
+}
+
+enum ParseError {
+    Incomplete,
+    Malformed,
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, ParseError> {
+        let byte = *self.buf.get(self.pos).ok_or(ParseError::Incomplete)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u64, ParseError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ParseError::Malformed)
+    }
+
+    fn id(&mut self) -> Result<Uuid, ParseError> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + 16)
+            .ok_or(ParseError::Incomplete)?;
+        let mut id = [0; 16];
+        id.copy_from_slice(bytes);
+        self.pos += 16;
+        Ok(Uuid::from_bytes(id))
+    }
+}
+
+/// Reads the compact headers written by the [`CompactEncoder`] of the other end of a
+/// connection.
+#[derive(Debug, Default)]
+pub(crate) struct CompactDecoder {
+    streams: Vec<(StreamId, Uuid)>,
+}
+
+impl CompactDecoder {
+    /// Removes the frame at the start of the buffer, or returns `None` if the buffer does not
+    /// contain the whole frame yet.
+    pub fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<CompactFrame>, CodecError> {
+        match self.parse(buf) {
+            Ok((frame, size)) => {
+                buf.advance(size);
+                Ok(Some(frame))
+            }
+            Err(ParseError::Incomplete) => Ok(None),
+            Err(ParseError::Malformed) => Err(CodecError::BincodeError(Box::new(
+                bincode::ErrorKind::Custom("Malformed compact header".to_string()),
+            ))),
+        }
+    }
+
+    fn parse(&mut self, buf: &[u8]) -> Result<(CompactFrame, usize), ParseError> {
+        let mut reader = Reader { buf, pos: 0 };
+        let kind = reader.byte()?;
+        if kind == DEFINE_STREAM {
+            let index = reader.varint()?;
+            let stream_id = StreamId::from(reader.id()?);
+            let run_id = reader.id()?;
+            if index != self.streams.len() as u64 {
+                return Err(ParseError::Malformed);
+            }
+            self.streams.push((stream_id, run_id));
+            return Ok((CompactFrame::Stream, reader.pos));
+        }
+        let (stream_id, run_id) = *self
+            .streams
+            .get(reader.varint()? as usize)
+            .ok_or(ParseError::Malformed)?;
+        let metadata = MessageMetadata {
+            stream_id,
+            chunk: None,
+            compressed: false,
+            run_id,
+            conflation_key: None,
+            timings: None,
+            watermark: None,
+            tick: false,
+            sequence: reader.varint()?,
+            provenance: None,
+        };
+        let frame = match kind {
+            COMPACT_DATA => CompactFrame::Data {
+                metadata,
+                data_size: reader.varint()? as usize,
+            },
+            COMPACT_WATERMARK => {
+                let flags = reader.byte()?;
+                let num_coordinates = reader.varint()?;
+                let mut time = Vec::new();
+                for _ in 0..num_coordinates {
+                    time.push(reader.varint()?);
+                }
+                let timestamp = if flags & TOP_FLAG != 0 {
+                    Timestamp::top()
+                } else {
+                    Timestamp::new(time)
+                };
+                CompactFrame::Watermark(MessageMetadata {
+                    watermark: Some(timestamp),
+                    tick: flags & TICK_FLAG != 0,
+                    ..metadata
+                })
+            }
+            _ => return Err(ParseError::Malformed),
+        };
+        Ok((frame, reader.pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that compact headers refer to the streams defined earlier on the connection.
+    #[test]
+    fn test_compact_header() {
+        let metadata = MessageMetadata {
+            stream_id: StreamId::new_v4(),
+            chunk: None,
+            compressed: false,
+            run_id: Uuid::new_v4(),
+            conflation_key: None,
+            timings: None,
+            watermark: None,
+            tick: false,
+            sequence: 130,
+            provenance: None,
+        };
+        let mut encoder = CompactEncoder::default();
+        let mut buf = BytesMut::new();
+        encoder.encode_header(&metadata, 300, &mut buf);
+        let defined_size = buf.len();
+        encoder.encode_header(&metadata, 300, &mut buf);
+        // The tag, the index, and two bytes each for the sequence number and the size of the data.
+        assert_eq!(buf.len() - defined_size, 6);
+        let timestamp = Timestamp::new(vec![5, 1 << 20]);
+        encoder.encode_watermark(&metadata, &timestamp, &mut buf);
+
+        let mut decoder = CompactDecoder::default();
+        let mut partial = BytesMut::from(&buf[..10]);
+        assert!(decoder.decode(&mut partial).unwrap().is_none());
+        assert!(matches!(
+            decoder.decode(&mut buf).unwrap(),
+            Some(CompactFrame::Stream)
+        ));
+        for _ in 0..2 {
+            match decoder.decode(&mut buf).unwrap() {
+                Some(CompactFrame::Data {
+                    metadata: decoded,
+                    data_size,
+                }) => {
+                    assert_eq!(decoded.stream_id, metadata.stream_id);
+                    assert_eq!(decoded.run_id, metadata.run_id);
+                    assert_eq!(decoded.sequence, 130);
+                    assert_eq!(data_size, 300);
+                }
+                _ => panic!("Expected the header of a message"),
+            }
+        }
+        match decoder.decode(&mut buf).unwrap() {
+            Some(CompactFrame::Watermark(decoded)) => {
+                assert_eq!(decoded.watermark, Some(timestamp))
+            }
+            _ => panic!("Expected a watermark"),
+        }
+        assert!(buf.is_empty());
+        assert!(decoder
+            .decode(&mut BytesMut::from(&[COMPACT_DATA, 1][..]))
+            .is_err());
+    }
+}
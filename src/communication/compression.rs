@@ -0,0 +1,694 @@
+use std::{collections::HashMap, fmt, io};
+
+use byteorder::{ByteOrder, NetworkEndian};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    communication::{
+        chunking::{as_slice, to_serialized_bytes, SerializedBytes},
+        CodecError, InterProcessMessage, MessageMetadata,
+    },
+    dataflow::stream::StreamId,
+    node::NodeId,
+};
+
+/// Size of the prefix which stores the uncompressed size of a message.
+const SIZE_PREFIX: usize = 4;
+/// Maximum number of compressed messages of a stream buffered until the dictionary of the stream
+/// arrives. Later messages are dropped.
+const MAX_PENDING_MESSAGES: usize = 1024;
+
+/// Compresses the messages of a stream sent to other nodes without a dictionary, e.g. for
+/// high-bandwidth streams of camera frames.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CompressionCodec {
+    /// zstd with the compression level, from 1 to 22. Requires the `compression` feature.
+    Zstd(i32),
+    /// LZ4, which compresses less than zstd but is faster. Requires the `lz4` feature.
+    Lz4,
+}
+
+impl fmt::Display for CompressionCodec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompressionCodec::Zstd(level) => write!(f, "zstd level {}", level),
+            CompressionCodec::Lz4 => write!(f, "lz4"),
+        }
+    }
+}
+
+impl CompressionCodec {
+    /// Returns the compressed data, prefixed with the size of the data.
+    fn compress(&self, bytes: &[u8]) -> Result<Vec<u8>, CodecError> {
+        let mut buf = vec![0; SIZE_PREFIX];
+        NetworkEndian::write_u32(&mut buf, bytes.len() as u32);
+        match self {
+            #[cfg(feature = "compression")]
+            CompressionCodec::Zstd(level) => buf.extend(
+                zstd::block::compress(bytes, *level).map_err(CodecError::CompressionError)?,
+            ),
+            #[cfg(not(feature = "compression"))]
+            CompressionCodec::Zstd(_) => return Err(zstd_unavailable()),
+            #[cfg(feature = "lz4")]
+            CompressionCodec::Lz4 => buf.extend(lz4_flex::compress(bytes)),
+            #[cfg(not(feature = "lz4"))]
+            CompressionCodec::Lz4 => return Err(lz4_unavailable()),
+        }
+        Ok(buf)
+    }
+
+    /// Returns the decompressed data, unless its size is larger than `max_message_size`.
+    fn decompress(
+        &self,
+        buf: &[u8],
+        max_message_size: Option<usize>,
+    ) -> Result<SerializedBytes, CodecError> {
+        let size = read_size(buf, max_message_size)?;
+        let bytes = match self {
+            #[cfg(feature = "compression")]
+            CompressionCodec::Zstd(_) => zstd::block::decompress(&buf[SIZE_PREFIX..], size)
+                .map_err(CodecError::CompressionError)?,
+            #[cfg(not(feature = "compression"))]
+            CompressionCodec::Zstd(_) => return Err(zstd_unavailable()),
+            #[cfg(feature = "lz4")]
+            CompressionCodec::Lz4 => {
+                lz4_flex::decompress(&buf[SIZE_PREFIX..], size).map_err(|e| {
+                    CodecError::CompressionError(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        e.to_string(),
+                    ))
+                })?
+            }
+            #[cfg(not(feature = "lz4"))]
+            CompressionCodec::Lz4 => return Err(lz4_unavailable()),
+        };
+        Ok(to_serialized_bytes(&bytes))
+    }
+}
+
+#[cfg(not(feature = "lz4"))]
+fn lz4_unavailable() -> CodecError {
+    CodecError::CompressionError(io::Error::new(
+        io::ErrorKind::Other,
+        "LZ4 compression requires the lz4 feature",
+    ))
+}
+
+#[cfg(not(feature = "compression"))]
+fn zstd_unavailable() -> CodecError {
+    CodecError::CompressionError(io::Error::new(
+        io::ErrorKind::Other,
+        "zstd compression requires the compression feature",
+    ))
+}
+
+/// Reads the uncompressed size of the message, which is checked against the maximum message
+/// size before the buffer for the message is allocated, as the size is sent by the peer.
+fn read_size(buf: &[u8], max_message_size: Option<usize>) -> Result<usize, CodecError> {
+    if buf.len() < SIZE_PREFIX {
+        return Err(malformed_message());
+    }
+    let size = NetworkEndian::read_u32(&buf[..SIZE_PREFIX]) as usize;
+    match max_message_size {
+        Some(limit) if size > limit => Err(CodecError::MessageTooLarge { size, limit }),
+        _ => Ok(size),
+    }
+}
+
+fn malformed_message() -> CodecError {
+    CodecError::BincodeError(Box::new(bincode::ErrorKind::Custom(
+        "Malformed message".to_string(),
+    )))
+}
+
+/// Panics if the codec with which the stream is compressed is not available in this build.
+pub(crate) fn check_codec_available(stream_id: StreamId, codec: CompressionCodec) {
+    if codec == CompressionCodec::Lz4 && cfg!(not(feature = "lz4")) {
+        panic!(
+            "Unable to compress stream {} with LZ4: the lz4 feature is disabled",
+            stream_id
+        );
+    }
+    if let (CompressionCodec::Zstd(_), false) = (codec, cfg!(feature = "compression")) {
+        panic!(
+            "Unable to compress stream {} with zstd: the compression feature is disabled",
+            stream_id
+        );
+    }
+}
+
+/// Returns the codecs of the streams, sorted by stream ID, which all nodes must agree on.
+pub(crate) fn sorted_codecs(
+    codecs: &HashMap<StreamId, CompressionCodec>,
+) -> Vec<(StreamId, CompressionCodec)> {
+    let mut codecs: Vec<_> = codecs
+        .iter()
+        .map(|(stream_id, codec)| (*stream_id, *codec))
+        .collect();
+    codecs.sort_by_key(|(stream_id, _)| *stream_id);
+    codecs
+}
+
+/// Checks that the node compresses the streams with the same codecs as the current node, so
+/// that messages do not fail to decompress once the dataflow runs.
+pub(crate) fn check_codecs(
+    node_id: NodeId,
+    local_codecs: &[(StreamId, CompressionCodec)],
+    node_codecs: &[(StreamId, CompressionCodec)],
+) -> Result<(), String> {
+    let describe = |codecs: &[(StreamId, CompressionCodec)], stream_id: StreamId| {
+        codecs
+            .iter()
+            .find(|(id, _)| *id == stream_id)
+            .map_or("no codec".to_string(), |(_, codec)| codec.to_string())
+    };
+    let mismatch = local_codecs
+        .iter()
+        .chain(node_codecs.iter())
+        .map(|(stream_id, _)| *stream_id)
+        .find(|stream_id| describe(local_codecs, *stream_id) != describe(node_codecs, *stream_id));
+    match mismatch {
+        Some(stream_id) => Err(format!(
+            "stream {} is compressed with {} locally but with {} on node {}",
+            stream_id,
+            describe(local_codecs, stream_id),
+            describe(node_codecs, stream_id),
+            node_id
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Configures the zstd dictionary which compresses the messages of a stream sent to other nodes.
+///
+/// The first `num_samples` messages are sent uncompressed, and used to train the dictionary.
+/// Dictionaries significantly improve the compression ratio of small, repetitive messages (e.g.,
+/// JSON telemetry). Requires the `compression` feature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DictionaryConfig {
+    /// Number of messages used to train the dictionary.
+    pub num_samples: usize,
+    /// Maximum size in bytes of the dictionary.
+    pub max_dictionary_size: usize,
+    /// The zstd compression level.
+    pub compression_level: i32,
+}
+
+impl Default for DictionaryConfig {
+    fn default() -> Self {
+        Self {
+            num_samples: 1000,
+            max_dictionary_size: 16 * 1024,
+            compression_level: 3,
+        }
+    }
+}
+
+/// A zstd dictionary trained on the messages a node sends on a stream to another node.
+///
+/// Distributed to the receiving node via the control plane.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamDictionary {
+    /// The node which trained the dictionary.
+    pub from_node: NodeId,
+    /// The node which receives the compressed messages.
+    pub to_node: NodeId,
+    pub stream_id: StreamId,
+    pub dictionary: Vec<u8>,
+}
+
+enum CompressionState {
+    Training {
+        config: DictionaryConfig,
+        samples: Vec<Vec<u8>>,
+    },
+    #[cfg(feature = "compression")]
+    Trained {
+        compression_level: i32,
+        compressor: zstd::block::Compressor,
+    },
+    /// Training failed, so messages are sent uncompressed.
+    Disabled,
+}
+
+/// Compresses the messages a data sender sends to another node with the codecs or the
+/// dictionaries of their streams.
+pub(crate) struct StreamCompressor {
+    from_node: NodeId,
+    to_node: NodeId,
+    streams: HashMap<StreamId, CompressionState>,
+    /// The codecs of the streams compressed without a dictionary, which take precedence over
+    /// the dictionaries.
+    codecs: HashMap<StreamId, CompressionCodec>,
+}
+
+impl StreamCompressor {
+    pub fn new(
+        from_node: NodeId,
+        to_node: NodeId,
+        configs: &HashMap<StreamId, DictionaryConfig>,
+    ) -> Self {
+        let streams = configs
+            .iter()
+            .map(|(stream_id, config)| {
+                let state = CompressionState::Training {
+                    config: config.clone(),
+                    samples: Vec::with_capacity(config.num_samples),
+                };
+                (*stream_id, state)
+            })
+            .collect();
+        Self {
+            from_node,
+            to_node,
+            streams,
+            codecs: HashMap::new(),
+        }
+    }
+
+    /// Compresses the messages of the streams with their codecs.
+    pub fn with_codecs(mut self, codecs: HashMap<StreamId, CompressionCodec>) -> Self {
+        self.codecs = codecs;
+        self
+    }
+
+    /// Compresses the message if the dictionary of its stream is trained.
+    ///
+    /// Also returns the dictionary once it is trained. The dictionary must be sent to the
+    /// receiving node, which buffers the compressed messages until the dictionary arrives.
+    pub fn compress(
+        &mut self,
+        msg: InterProcessMessage,
+    ) -> Result<(InterProcessMessage, Option<StreamDictionary>), CodecError> {
+        // Watermarks are sent as watermark frames, which are not worth compressing.
+        if msg.metadata().watermark_frame().is_some() {
+            return Ok((msg, None));
+        }
+        let stream_id = match &msg {
+            InterProcessMessage::Deserialized { metadata, data: _ } => metadata.stream_id,
+            InterProcessMessage::Serialized { metadata, bytes: _ } => metadata.stream_id,
+        };
+        if let Some(codec) = self.codecs.get(&stream_id) {
+            let (mut metadata, bytes) = serialize(msg)?;
+            let compressed = codec.compress(&bytes)?;
+            metadata.compressed = true;
+            let msg =
+                InterProcessMessage::new_serialized(to_serialized_bytes(&compressed), metadata);
+            return Ok((msg, None));
+        }
+        let state = match self.streams.get_mut(&stream_id) {
+            Some(state) => state,
+            None => return Ok((msg, None)),
+        };
+        match state {
+            CompressionState::Disabled => Ok((msg, None)),
+            #[cfg(feature = "compression")]
+            CompressionState::Trained {
+                compression_level,
+                compressor,
+            } => {
+                let (mut metadata, bytes) = serialize(msg)?;
+                let compressed = compressor
+                    .compress(&bytes, *compression_level)
+                    .map_err(CodecError::CompressionError)?;
+                let mut buf = vec![0; SIZE_PREFIX];
+                NetworkEndian::write_u32(&mut buf, bytes.len() as u32);
+                buf.extend_from_slice(&compressed);
+                metadata.compressed = true;
+                let msg = InterProcessMessage::new_serialized(to_serialized_bytes(&buf), metadata);
+                Ok((msg, None))
+            }
+            CompressionState::Training { config, samples } => {
+                let (metadata, bytes) = serialize(msg)?;
+                samples.push(bytes.clone());
+                let msg =
+                    InterProcessMessage::new_serialized(to_serialized_bytes(&bytes), metadata);
+                if samples.len() < config.num_samples {
+                    return Ok((msg, None));
+                }
+                match train(config, samples) {
+                    Ok((dictionary, trained)) => {
+                        *state = trained;
+                        let dictionary = StreamDictionary {
+                            from_node: self.from_node,
+                            to_node: self.to_node,
+                            stream_id,
+                            dictionary,
+                        };
+                        Ok((msg, Some(dictionary)))
+                    }
+                    Err(e) => {
+                        slog::warn!(
+                            crate::TERMINAL_LOGGER,
+                            "Unable to train a dictionary for stream {}, sending it uncompressed: {}",
+                            stream_id,
+                            e
+                        );
+                        *state = CompressionState::Disabled;
+                        Ok((msg, None))
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Trains the dictionary of a stream on its first messages, and returns it with the state which
+/// compresses the next messages with it.
+#[cfg(feature = "compression")]
+fn train(
+    config: &DictionaryConfig,
+    samples: &[Vec<u8>],
+) -> io::Result<(Vec<u8>, CompressionState)> {
+    let dictionary = zstd::dict::from_samples(samples, config.max_dictionary_size)?;
+    let state = CompressionState::Trained {
+        compression_level: config.compression_level,
+        compressor: zstd::block::Compressor::with_dict(dictionary.clone()),
+    };
+    Ok((dictionary, state))
+}
+
+#[cfg(not(feature = "compression"))]
+fn train(
+    _config: &DictionaryConfig,
+    _samples: &[Vec<u8>],
+) -> io::Result<(Vec<u8>, CompressionState)> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "dictionaries require the compression feature",
+    ))
+}
+
+/// Decompresses the messages of a stream with its zstd dictionary.
+#[cfg(feature = "compression")]
+struct DictionaryDecompressor(zstd::block::Decompressor);
+
+/// Fails to decompress the messages, as dictionaries require the `compression` feature.
+#[cfg(not(feature = "compression"))]
+struct DictionaryDecompressor;
+
+impl DictionaryDecompressor {
+    #[cfg(feature = "compression")]
+    fn new(dictionary: Vec<u8>) -> Self {
+        Self(zstd::block::Decompressor::with_dict(dictionary))
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn new(_dictionary: Vec<u8>) -> Self {
+        Self
+    }
+
+    /// Decompresses the message, unless its size is larger than `max_message_size`.
+    #[cfg(feature = "compression")]
+    fn decompress(
+        &mut self,
+        buf: &[u8],
+        max_message_size: Option<usize>,
+    ) -> Result<SerializedBytes, CodecError> {
+        let size = read_size(buf, max_message_size)?;
+        let bytes = self
+            .0
+            .decompress(&buf[SIZE_PREFIX..], size)
+            .map_err(CodecError::CompressionError)?;
+        Ok(to_serialized_bytes(&bytes))
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn decompress(
+        &mut self,
+        buf: &[u8],
+        max_message_size: Option<usize>,
+    ) -> Result<SerializedBytes, CodecError> {
+        read_size(buf, max_message_size)?;
+        Err(zstd_unavailable())
+    }
+}
+
+/// Decompresses the messages a data receiver receives from another node.
+pub(crate) struct StreamDecompressor {
+    decompressors: HashMap<StreamId, DictionaryDecompressor>,
+    /// Compressed messages received before the dictionary of their stream.
+    pending: HashMap<StreamId, Vec<(MessageMetadata, SerializedBytes)>>,
+    /// Messages which are larger once decompressed are rejected.
+    max_message_size: Option<usize>,
+    /// The codecs of the streams compressed without a dictionary.
+    codecs: HashMap<StreamId, CompressionCodec>,
+}
+
+impl StreamDecompressor {
+    pub fn new() -> Self {
+        Self::with_max_message_size(None)
+    }
+
+    pub fn with_max_message_size(max_message_size: Option<usize>) -> Self {
+        Self {
+            decompressors: HashMap::new(),
+            pending: HashMap::new(),
+            max_message_size,
+            codecs: HashMap::new(),
+        }
+    }
+
+    /// Decompresses the messages of the streams with their codecs.
+    pub fn with_codecs(mut self, codecs: HashMap<StreamId, CompressionCodec>) -> Self {
+        self.codecs = codecs;
+        self
+    }
+
+    /// Returns the uncompressed data of the message, or `None` if the dictionary of the stream
+    /// has not arrived yet. In that case, the message is returned by
+    /// [`add_dictionary`](StreamDecompressor::add_dictionary).
+    ///
+    /// Returns a [`MessageTooLarge`](CodecError::MessageTooLarge) error if the message is
+    /// larger than the maximum message size once decompressed, and an error if too many
+    /// messages of the stream wait for the dictionary.
+    pub fn decompress(
+        &mut self,
+        metadata: &MessageMetadata,
+        bytes: SerializedBytes,
+    ) -> Result<Option<SerializedBytes>, CodecError> {
+        if !metadata.compressed {
+            return Ok(Some(bytes));
+        }
+        if let Some(codec) = self.codecs.get(&metadata.stream_id) {
+            return codec
+                .decompress(as_slice(&bytes), self.max_message_size)
+                .map(Some);
+        }
+        match self.decompressors.get_mut(&metadata.stream_id) {
+            Some(decompressor) => decompressor
+                .decompress(as_slice(&bytes), self.max_message_size)
+                .map(Some),
+            None => {
+                let pending = self.pending.entry(metadata.stream_id).or_default();
+                if pending.len() >= MAX_PENDING_MESSAGES {
+                    return Err(CodecError::CompressionError(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!(
+                            "{} messages already wait for the dictionary of the stream",
+                            pending.len()
+                        ),
+                    )));
+                }
+                pending.push((metadata.clone(), bytes));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Installs the dictionary of a stream, and decompresses the messages which waited for it.
+    pub fn add_dictionary(
+        &mut self,
+        dictionary: StreamDictionary,
+    ) -> Vec<(MessageMetadata, Result<SerializedBytes, CodecError>)> {
+        let mut decompressor = DictionaryDecompressor::new(dictionary.dictionary);
+        let max_message_size = self.max_message_size;
+        let pending = self
+            .pending
+            .remove(&dictionary.stream_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(metadata, bytes)| {
+                let result = decompressor.decompress(as_slice(&bytes), max_message_size);
+                (metadata, result)
+            })
+            .collect();
+        self.decompressors
+            .insert(dictionary.stream_id, decompressor);
+        pending
+    }
+}
+
+/// Returns the metadata and the serialized data of the message.
+fn serialize(msg: InterProcessMessage) -> Result<(MessageMetadata, Vec<u8>), CodecError> {
+    match msg {
+        InterProcessMessage::Deserialized { metadata, data } => {
+            Ok((metadata, data.encode_into_vec()?))
+        }
+        InterProcessMessage::Serialized { metadata, bytes } => {
+            Ok((metadata, as_slice(&bytes).to_vec()))
+        }
+    }
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::dataflow::{Message, Timestamp};
+
+    fn make_message(stream_id: StreamId, i: usize) -> InterProcessMessage {
+        let data = format!(
+            "{{\"sensor\": \"lidar\", \"status\": \"ok\", \"seq\": {}}}",
+            i
+        );
+        let msg = Message::new_message(Timestamp::new(vec![i as u64]), data);
+        InterProcessMessage::new_deserialized(Arc::new(msg), stream_id)
+    }
+
+    /// Test that messages compressed with a trained dictionary are decompressed by the receiver,
+    /// including messages received before the dictionary, unless they are larger than the
+    /// maximum message size.
+    #[test]
+    fn test_compress_with_dictionary() {
+        let stream_id = StreamId::new_deterministic();
+        let mut configs = HashMap::new();
+        configs.insert(
+            stream_id,
+            DictionaryConfig {
+                num_samples: 1000,
+                max_dictionary_size: 1024,
+                ..DictionaryConfig::default()
+            },
+        );
+        let mut compressor = StreamCompressor::new(0, 1, &configs);
+
+        let mut dictionary = None;
+        for i in 0..1000 {
+            let (_, trained) = compressor.compress(make_message(stream_id, i)).unwrap();
+            dictionary = dictionary.or(trained);
+        }
+        let dictionary = dictionary.expect("The dictionary must be trained after 1000 samples");
+
+        let original = make_message(stream_id, 1000);
+        let expected = serialize(original.clone()).unwrap().1;
+        let (compressed, _) = compressor.compress(original).unwrap();
+        let (metadata, bytes) = match compressed {
+            InterProcessMessage::Serialized { metadata, bytes } => (metadata, bytes),
+            InterProcessMessage::Deserialized { .. } => panic!("Message must be compressed"),
+        };
+        assert!(metadata.compressed);
+
+        let mut decompressor = StreamDecompressor::new();
+        assert!(decompressor
+            .decompress(&metadata, bytes.clone())
+            .unwrap()
+            .is_none());
+        let pending = decompressor.add_dictionary(dictionary.clone());
+        assert_eq!(pending.len(), 1);
+        let decompressed = pending.into_iter().next().unwrap().1.unwrap();
+        assert_eq!(as_slice(&decompressed), expected.as_slice());
+
+        let mut decompressor = StreamDecompressor::with_max_message_size(Some(expected.len() - 1));
+        assert!(decompressor
+            .decompress(&metadata, bytes.clone())
+            .unwrap()
+            .is_none());
+        let pending = decompressor.add_dictionary(dictionary);
+        assert!(matches!(
+            pending[0].1,
+            Err(CodecError::MessageTooLarge { .. })
+        ));
+        assert!(matches!(
+            decompressor.decompress(&metadata, bytes),
+            Err(CodecError::MessageTooLarge { .. })
+        ));
+    }
+
+    /// Test that the messages of streams with a codec are compressed without a dictionary, and
+    /// that nodes which compress a stream differently are detected.
+    #[test]
+    fn test_compress_with_codec() {
+        let stream_id = StreamId::new_v4();
+        let mut codecs = HashMap::new();
+        codecs.insert(stream_id, CompressionCodec::Zstd(3));
+        let mut compressor =
+            StreamCompressor::new(0, 1, &HashMap::new()).with_codecs(codecs.clone());
+        let original = make_message(stream_id, 0);
+        let expected = serialize(original.clone()).unwrap().1;
+        let (metadata, bytes) = match compressor.compress(original).unwrap() {
+            (InterProcessMessage::Serialized { metadata, bytes }, None) => (metadata, bytes),
+            _ => panic!("Message must be compressed without a dictionary"),
+        };
+        assert!(metadata.compressed);
+        let bytes = StreamDecompressor::new()
+            .with_codecs(codecs.clone())
+            .decompress(&metadata, bytes)
+            .unwrap()
+            .unwrap();
+        assert_eq!(as_slice(&bytes), expected.as_slice());
+
+        let local_codecs = sorted_codecs(&codecs);
+        assert_eq!(check_codecs(1, &local_codecs, &local_codecs), Ok(()));
+        assert_eq!(
+            check_codecs(1, &local_codecs, &[]),
+            Err(format!(
+                "stream {} is compressed with zstd level 3 locally but with no codec on node 1",
+                stream_id
+            ))
+        );
+    }
+
+    /// Test that messages larger than the maximum message size once decompressed are rejected
+    /// before their buffer is allocated, and that the messages waiting for a dictionary are
+    /// bounded.
+    #[test]
+    fn test_decompress_limits() {
+        let stream_id = StreamId::new_v4();
+        let mut codecs = HashMap::new();
+        codecs.insert(stream_id, CompressionCodec::Zstd(3));
+        let mut compressor =
+            StreamCompressor::new(0, 1, &HashMap::new()).with_codecs(codecs.clone());
+        let original = make_message(stream_id, 0);
+        let size = serialize(original.clone()).unwrap().1.len();
+        let (metadata, bytes) = match compressor.compress(original).unwrap() {
+            (InterProcessMessage::Serialized { metadata, bytes }, None) => (metadata, bytes),
+            _ => panic!("Message must be compressed without a dictionary"),
+        };
+        let mut decompressor =
+            StreamDecompressor::with_max_message_size(Some(size - 1)).with_codecs(codecs.clone());
+        match decompressor.decompress(&metadata, bytes.clone()) {
+            Err(CodecError::MessageTooLarge {
+                size: too_large,
+                limit,
+            }) => {
+                assert_eq!((too_large, limit), (size, size - 1))
+            }
+            result => panic!("Unexpected result {:?}", result.map(|_| ())),
+        }
+
+        // The size prefix is sent by the peer.
+        let mut forged = as_slice(&bytes).to_vec();
+        NetworkEndian::write_u32(&mut forged, u32::MAX);
+        let mut decompressor =
+            StreamDecompressor::with_max_message_size(Some(1024)).with_codecs(codecs);
+        assert!(matches!(
+            decompressor.decompress(&metadata, to_serialized_bytes(&forged)),
+            Err(CodecError::MessageTooLarge { .. })
+        ));
+
+        let metadata = MessageMetadata {
+            stream_id: StreamId::new_v4(),
+            ..metadata
+        };
+        let mut decompressor = StreamDecompressor::new();
+        for _ in 0..MAX_PENDING_MESSAGES {
+            assert!(decompressor
+                .decompress(&metadata, bytes.clone())
+                .unwrap()
+                .is_none());
+        }
+        assert!(decompressor.decompress(&metadata, bytes).is_err());
+    }
+}
@@ -0,0 +1,308 @@
+//! Checks that a [`Transport`] carries messages between nodes correctly.
+//!
+//! Each check links endpoints with the transport, exchanges messages, and returns a description
+//! of the first misbehavior it observes. The checks must run on a Tokio runtime with the time
+//! driver enabled, and fail instead of waiting forever if a message does not arrive.
+//!
+//! ```ignore
+//! let mut runtime = erdos::tokio::runtime::Runtime::new().unwrap();
+//! runtime
+//!     .block_on(conformance::check_transport(&mut MyTransport::new()))
+//!     .unwrap();
+//! ```
+use futures_util::{sink::SinkExt, stream::StreamExt};
+use std::time::Duration;
+
+use crate::{
+    communication::{
+        chunking::as_slice, to_serialized_bytes, InterProcessMessage, MessageMetadata, Transport,
+        TransportLink,
+    },
+    dataflow::{stream::StreamId, Timestamp},
+    Uuid,
+};
+
+/// Time after which a message which was not received is considered lost.
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Number of messages sent by the ordering check.
+const NUM_ORDERED_MESSAGES: usize = 1000;
+/// Size of the message sent by the large message check.
+const LARGE_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Runs all the checks of the suite on the transport.
+pub async fn check_transport<T: Transport>(transport: &mut T) -> Result<(), String> {
+    check_ordering(transport).await?;
+    check_watermark_delivery(transport).await?;
+    check_large_messages(transport).await?;
+    check_shutdown(transport).await?;
+    check_reconnection(transport).await
+}
+
+/// Checks that messages are received in the order in which they are sent in both directions,
+/// with their stream IDs and sequence numbers.
+pub async fn check_ordering<T: Transport>(transport: &mut T) -> Result<(), String> {
+    let (mut first, mut second) = connect(transport).await?;
+    let stream_ids = [StreamId::new_v4(), StreamId::new_v4()];
+    let messages: Vec<_> = (0..NUM_ORDERED_MESSAGES)
+        .map(|i| {
+            let payload = (i as u32).to_be_bytes();
+            data_message(stream_ids[i % 2], (i / 2 + 1) as u64, &payload)
+        })
+        .collect();
+    let received = exchange(&mut first, &mut second, messages.clone()).await?;
+    expect_same(&messages, &received)?;
+    let received = exchange(&mut second, &mut first, messages.clone()).await?;
+    expect_same(&messages, &received)
+}
+
+/// Checks that watermarks and ticks are delivered with their timestamps, in order with the
+/// messages which carry data.
+pub async fn check_watermark_delivery<T: Transport>(transport: &mut T) -> Result<(), String> {
+    let (mut first, mut second) = connect(transport).await?;
+    let stream_id = StreamId::new_v4();
+    let messages = vec![
+        data_message(stream_id, 1, b"data"),
+        watermark_message(stream_id, 2, Timestamp::new(vec![1, 2]), false),
+        watermark_message(stream_id, 3, Timestamp::new(vec![3]), true),
+        data_message(stream_id, 4, b"more data"),
+        watermark_message(stream_id, 5, Timestamp::top(), false),
+    ];
+    let received = exchange(&mut first, &mut second, messages.clone()).await?;
+    for (index, (sent, received)) in messages.iter().zip(received.iter()).enumerate() {
+        let (sent, received) = (sent.metadata(), received.metadata());
+        if sent.watermark != received.watermark || sent.tick != received.tick {
+            return Err(format!(
+                "Message {} was sent with watermark {:?} (tick: {}), but received with \
+                 watermark {:?} (tick: {})",
+                index, sent.watermark, sent.tick, received.watermark, received.tick
+            ));
+        }
+    }
+    expect_same(&messages, &received)
+}
+
+/// Checks that a message of several megabytes is received unmodified.
+pub async fn check_large_messages<T: Transport>(transport: &mut T) -> Result<(), String> {
+    let (mut first, mut second) = connect(transport).await?;
+    let payload: Vec<u8> = (0..LARGE_MESSAGE_SIZE).map(|i| (i % 251) as u8).collect();
+    let messages = vec![
+        data_message(StreamId::new_v4(), 1, &payload),
+        data_message(StreamId::new_v4(), 1, b"after the large message"),
+    ];
+    let received = exchange(&mut first, &mut second, messages.clone()).await?;
+    expect_same(&messages, &received)
+}
+
+/// Checks that the messages sent before a sink is closed are delivered, and that the stream of
+/// the other end then ends.
+pub async fn check_shutdown<T: Transport>(transport: &mut T) -> Result<(), String> {
+    let (mut first, mut second) = connect(transport).await?;
+    let stream_id = StreamId::new_v4();
+    let messages: Vec<_> = (1..=10)
+        .map(|i| data_message(stream_id, i, b"before shutdown"))
+        .collect();
+    let received = exchange(&mut first, &mut second, messages.clone()).await?;
+    expect_same(&messages, &received)?;
+    first
+        .sink
+        .close()
+        .await
+        .map_err(|e| format!("Failed to close the sink: {:?}", e))?;
+    match tokio::time::timeout(RECEIVE_TIMEOUT, second.stream.next()).await {
+        Ok(None) => Ok(()),
+        Ok(Some(msg)) => Err(format!(
+            "Received {:?} after the sink was closed",
+            msg.map(|msg| msg.metadata().clone())
+        )),
+        Err(_) => Err("The stream did not end after the sink was closed".to_string()),
+    }
+}
+
+/// Checks that the stream of an end fails or ends once the other end drops its link, and that
+/// the transport then links endpoints again.
+pub async fn check_reconnection<T: Transport>(transport: &mut T) -> Result<(), String> {
+    let (first, mut second) = connect(transport).await?;
+    drop(first);
+    match tokio::time::timeout(RECEIVE_TIMEOUT, second.stream.next()).await {
+        Ok(None) | Ok(Some(Err(_))) => (),
+        Ok(Some(Ok(msg))) => {
+            return Err(format!("Received {:?} from a dropped link", msg.metadata()))
+        }
+        Err(_) => return Err("The stream did not end after the link was dropped".to_string()),
+    }
+    drop(second);
+
+    let (mut first, mut second) = connect(transport).await?;
+    let messages = vec![data_message(StreamId::new_v4(), 1, b"after reconnecting")];
+    let received = exchange(&mut first, &mut second, messages.clone()).await?;
+    expect_same(&messages, &received)?;
+    let received = exchange(&mut second, &mut first, messages.clone()).await?;
+    expect_same(&messages, &received)
+}
+
+async fn connect<T: Transport>(
+    transport: &mut T,
+) -> Result<(TransportLink, TransportLink), String> {
+    transport
+        .connect()
+        .await
+        .map_err(|e| format!("Failed to connect: {:?}", e))
+}
+
+/// Sends the messages on one end of a link while receiving them on the other end.
+async fn exchange(
+    from: &mut TransportLink,
+    to: &mut TransportLink,
+    messages: Vec<InterProcessMessage>,
+) -> Result<Vec<InterProcessMessage>, String> {
+    let num_messages = messages.len();
+    let send = async {
+        for msg in messages {
+            from.sink
+                .send(msg)
+                .await
+                .map_err(|e| format!("Failed to send a message: {:?}", e))?;
+        }
+        Ok::<_, String>(())
+    };
+    let receive = async {
+        let mut received = Vec::with_capacity(num_messages);
+        while received.len() < num_messages {
+            match tokio::time::timeout(RECEIVE_TIMEOUT, to.stream.next()).await {
+                Ok(Some(Ok(msg))) => received.push(msg),
+                Ok(Some(Err(e))) => return Err(format!("Failed to receive a message: {:?}", e)),
+                Ok(None) => {
+                    return Err(format!(
+                        "The stream ended after {} of {} messages",
+                        received.len(),
+                        num_messages
+                    ))
+                }
+                Err(_) => {
+                    return Err(format!(
+                        "Timed out after receiving {} of {} messages",
+                        received.len(),
+                        num_messages
+                    ))
+                }
+            }
+        }
+        Ok(received)
+    };
+    let (sent, received) = futures::join!(send, receive);
+    sent?;
+    received
+}
+
+/// Checks that the received messages have the stream IDs, the sequence numbers, and the data of
+/// the sent messages.
+fn expect_same(
+    sent: &[InterProcessMessage],
+    received: &[InterProcessMessage],
+) -> Result<(), String> {
+    for (index, (sent, received)) in sent.iter().zip(received.iter()).enumerate() {
+        let (sent_metadata, received_metadata) = (sent.metadata(), received.metadata());
+        if sent_metadata.stream_id != received_metadata.stream_id
+            || sent_metadata.sequence != received_metadata.sequence
+        {
+            return Err(format!(
+                "Message {} was sent as message {} of stream {}, but received as message {} of \
+                 stream {}",
+                index,
+                sent_metadata.sequence,
+                sent_metadata.stream_id,
+                received_metadata.sequence,
+                received_metadata.stream_id
+            ));
+        }
+        // The data of watermarks is carried by their metadata.
+        if sent_metadata.watermark.is_none() && payload(sent)? != payload(received)? {
+            return Err(format!("The data of message {} was modified", index));
+        }
+    }
+    Ok(())
+}
+
+fn payload(msg: &InterProcessMessage) -> Result<&[u8], String> {
+    match msg {
+        InterProcessMessage::Serialized { bytes, .. } => Ok(as_slice(bytes)),
+        InterProcessMessage::Deserialized { .. } => {
+            Err("Received a message which is not serialized".to_string())
+        }
+    }
+}
+
+fn metadata(stream_id: StreamId, sequence: u64) -> MessageMetadata {
+    MessageMetadata {
+        stream_id,
+        chunk: None,
+        compressed: false,
+        run_id: Uuid::nil(),
+        conflation_key: None,
+        timings: None,
+        watermark: None,
+        tick: false,
+        sequence,
+        provenance: None,
+    }
+}
+
+fn data_message(stream_id: StreamId, sequence: u64, payload: &[u8]) -> InterProcessMessage {
+    InterProcessMessage::new_serialized(to_serialized_bytes(payload), metadata(stream_id, sequence))
+}
+
+fn watermark_message(
+    stream_id: StreamId,
+    sequence: u64,
+    timestamp: Timestamp,
+    tick: bool,
+) -> InterProcessMessage {
+    let metadata = MessageMetadata {
+        watermark: Some(timestamp),
+        tick,
+        ..metadata(stream_id, sequence)
+    };
+    InterProcessMessage::new_serialized(to_serialized_bytes(&[]), metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::communication::MockTransport;
+
+    /// Test that the mock transport passes the conformance suite.
+    #[test]
+    fn test_mock_transport_conformance() {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut transport = MockTransport::new();
+        runtime.block_on(check_transport(&mut transport)).unwrap();
+        assert_eq!(transport.num_connections(), 6);
+    }
+
+    /// Test that TCP connections pass the conformance suite, with and without compact headers.
+    #[cfg(feature = "tcp_transport")]
+    #[test]
+    fn test_tcp_transport_conformance() {
+        use crate::communication::TcpTransport;
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        for &compact_headers in &[false, true] {
+            let mut transport = TcpTransport::new().with_compact_headers(compact_headers);
+            runtime.block_on(check_transport(&mut transport)).unwrap();
+        }
+    }
+
+    /// Test that Unix domain sockets pass the conformance suite, with and without compact
+    /// headers.
+    #[cfg(feature = "uds_transport")]
+    #[test]
+    fn test_unix_transport_conformance() {
+        use crate::communication::UnixTransport;
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        for &compact_headers in &[false, true] {
+            let mut transport = UnixTransport::new().with_compact_headers(compact_headers);
+            runtime.block_on(check_transport(&mut transport)).unwrap();
+        }
+    }
+}
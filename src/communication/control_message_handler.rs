@@ -1,11 +1,14 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
 
 use slog::{self, Logger};
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
 use crate::node::NodeId;
 
-use super::{CommunicationError, ControlMessage};
+use super::{CommunicationError, ControlDirection, ControlMessage, ControlRecorder};
 
 // TODO: update `channels_to_nodes` for fault tolerance in case nodes to go down.
 pub struct ControlMessageHandler {
@@ -20,11 +23,18 @@ pub struct ControlMessageHandler {
     channels_to_data_senders: HashMap<NodeId, UnboundedSender<ControlMessage>>,
     channels_to_data_receivers: HashMap<NodeId, UnboundedSender<ControlMessage>>,
     channels_to_nodes: HashMap<NodeId, UnboundedSender<ControlMessage>>,
+    /// Logs the control messages exchanged with other nodes.
+    recorder: ControlRecorder,
+    /// Time at which each node last sent a heartbeat, or at which the heartbeats of the node were
+    /// first checked.
+    last_heartbeats: HashMap<NodeId, Instant>,
+    /// Nodes reported as failed, which are not reported again.
+    failed_nodes: HashSet<NodeId>,
 }
 
 #[allow(dead_code)]
 impl ControlMessageHandler {
-    pub fn new(logger: Logger) -> Self {
+    pub fn new(logger: Logger, recorder: ControlRecorder) -> Self {
         let (tx, rx) = mpsc::unbounded_channel();
         Self {
             logger,
@@ -35,9 +45,17 @@ impl ControlMessageHandler {
             channels_to_data_senders: HashMap::new(),
             channels_to_data_receivers: HashMap::new(),
             channels_to_nodes: HashMap::new(),
+            recorder,
+            last_heartbeats: HashMap::new(),
+            failed_nodes: HashSet::new(),
         }
     }
 
+    /// Returns the recorder with which control receivers log the messages they receive.
+    pub fn recorder(&self) -> ControlRecorder {
+        self.recorder.clone()
+    }
+
     pub fn add_channel_to_control_sender(
         &mut self,
         node_id: NodeId,
@@ -58,7 +76,10 @@ impl ControlMessageHandler {
         msg: ControlMessage,
     ) -> Result<(), CommunicationError> {
         match self.channels_to_control_senders.get_mut(&node_id) {
-            Some(tx) => tx.send(msg).map_err(CommunicationError::from),
+            Some(tx) => {
+                self.recorder.record(ControlDirection::Sent, node_id, &msg);
+                tx.send(msg).map_err(CommunicationError::from)
+            }
             None => Err(CommunicationError::Disconnected),
         }
     }
@@ -67,7 +88,8 @@ impl ControlMessageHandler {
         &mut self,
         msg: ControlMessage,
     ) -> Result<(), CommunicationError> {
-        for tx in self.channels_to_control_senders.values_mut() {
+        for (&node_id, tx) in self.channels_to_control_senders.iter_mut() {
+            self.recorder.record(ControlDirection::Sent, node_id, &msg);
             tx.send(msg.clone()).map_err(CommunicationError::from)?;
         }
         Ok(())
@@ -204,24 +226,52 @@ impl ControlMessageHandler {
         self.tx.clone()
     }
 
+    /// Reads the next control message. Heartbeats are recorded instead of returned.
     pub async fn read(&mut self) -> Result<ControlMessage, CommunicationError> {
-        self.rx.recv().await.ok_or(CommunicationError::Disconnected)
+        loop {
+            match self.rx.recv().await {
+                Some(ControlMessage::Heartbeat(node_id)) => {
+                    self.last_heartbeats.insert(node_id, Instant::now());
+                }
+                Some(msg) => return Ok(msg),
+                None => return Err(CommunicationError::Disconnected),
+            }
+        }
+    }
+
+    /// Sends a heartbeat of the node to all other nodes.
+    pub fn send_heartbeats(&mut self, node_id: NodeId) -> Result<(), CommunicationError> {
+        self.broadcast_to_nodes(ControlMessage::Heartbeat(node_id))
+    }
+
+    /// Returns the nodes which have not sent a heartbeat within the timeout, and which were not
+    /// returned before. The timeout of a node which never sent a heartbeat starts at the first
+    /// check.
+    pub fn check_heartbeats(&mut self, timeout: Duration) -> Vec<NodeId> {
+        let now = Instant::now();
+        let mut failed_nodes = Vec::new();
+        for &node_id in self.channels_to_nodes.keys() {
+            let last_heartbeat = *self.last_heartbeats.entry(node_id).or_insert(now);
+            if now.duration_since(last_heartbeat) > timeout && self.failed_nodes.insert(node_id) {
+                failed_nodes.push(node_id);
+            }
+        }
+        failed_nodes.sort_unstable();
+        failed_nodes
     }
 
     // TODO: try to implement this via a generic
-    /// Reads messages until a `ControlMessage::AllOperatorsInitializedOnNode` is received
-    /// without consuming any other messages types.
+    /// Reads messages until a `ControlMessage::AllOperatorsInitializedOnNode` or a
+    /// `ControlMessage::NodeSetupFailed` is received without consuming any other messages types.
     /// Note: this may affect message order.
-    pub async fn read_all_operators_initialized_on_node_msg(
-        &mut self,
-    ) -> Result<NodeId, CommunicationError> {
+    pub async fn read_node_setup_msg(&mut self) -> Result<ControlMessage, CommunicationError> {
         let mut read_msgs = Vec::new();
         let mut result = None;
         while result.is_none() {
             match self.read().await {
-                Ok(ControlMessage::AllOperatorsInitializedOnNode(node_id)) => {
-                    result = Some(Ok(node_id))
-                }
+                Ok(msg @ ControlMessage::AllOperatorsInitializedOnNode(..))
+                | Ok(msg @ ControlMessage::NodeSetupFailed(_, _))
+                | Ok(msg @ ControlMessage::StreamCodecs(_, _)) => result = Some(Ok(msg)),
                 Ok(msg) => read_msgs.push(msg),
                 Err(e) => result = Some(Err(e)),
             };
@@ -0,0 +1,200 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, LineWriter, Write},
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::node::NodeId;
+
+use super::ControlMessage;
+
+/// Whether a node sent a control message to a peer or received it from the peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlDirection {
+    Sent,
+    Received,
+}
+
+/// A control message sent or received by a node, as logged by a [`ControlRecorder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlRecord {
+    /// Time at which the message was sent or received, in microseconds since the UNIX epoch on
+    /// the clock of the node.
+    pub time_micros: u128,
+    pub node_id: NodeId,
+    pub direction: ControlDirection,
+    pub peer: NodeId,
+    /// The debug representation of the message.
+    pub message: String,
+}
+
+impl fmt::Display for ControlRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let direction = match self.direction {
+            ControlDirection::Sent => "sent",
+            ControlDirection::Received => "received",
+        };
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}",
+            self.time_micros, self.node_id, direction, self.peer, self.message
+        )
+    }
+}
+
+impl FromStr for ControlRecord {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid control message record: {}", line);
+        let fields: Vec<&str> = line.splitn(5, '\t').collect();
+        if fields.len() != 5 {
+            return Err(invalid());
+        }
+        let direction = match fields[2] {
+            "sent" => ControlDirection::Sent,
+            "received" => ControlDirection::Received,
+            _ => return Err(invalid()),
+        };
+        Ok(Self {
+            time_micros: fields[0].parse().map_err(|_| invalid())?,
+            node_id: fields[1].parse().map_err(|_| invalid())?,
+            direction,
+            peer: fields[3].parse().map_err(|_| invalid())?,
+            message: fields[4].to_string(),
+        })
+    }
+}
+
+/// Logs the control messages which a node exchanges with other nodes to a file, with one
+/// [`ControlRecord`] per line, so that hangs during initialization can be debugged by merging
+/// the logs of all nodes with [`merge_control_logs`].
+#[derive(Clone)]
+pub(crate) struct ControlRecorder {
+    node_id: NodeId,
+    file: Option<Arc<Mutex<LineWriter<File>>>>,
+}
+
+impl ControlRecorder {
+    /// Returns a recorder which does not log messages.
+    pub fn disabled(node_id: NodeId) -> Self {
+        Self {
+            node_id,
+            file: None,
+        }
+    }
+
+    /// Logs the messages of the node to `control-<node ID>.log` in the directory.
+    pub fn new(node_id: NodeId, directory: &Path) -> io::Result<Self> {
+        fs::create_dir_all(directory)?;
+        let file = File::create(directory.join(format!("control-{}.log", node_id)))?;
+        Ok(Self {
+            node_id,
+            file: Some(Arc::new(Mutex::new(LineWriter::new(file)))),
+        })
+    }
+
+    pub fn record(&self, direction: ControlDirection, peer: NodeId, msg: &ControlMessage) {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return,
+        };
+        let record = ControlRecord {
+            time_micros: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_micros()),
+            node_id: self.node_id,
+            direction,
+            peer,
+            message: format!("{:?}", msg),
+        };
+        // Lines are flushed as they are written, so the log is complete if the node hangs.
+        writeln!(file.lock().unwrap(), "{}", record).ok();
+    }
+}
+
+/// Reads the control message logs of several nodes, and returns their records ordered by time.
+pub fn merge_control_logs<P: AsRef<Path>>(paths: &[P]) -> io::Result<Vec<ControlRecord>> {
+    let mut records = Vec::new();
+    for path in paths {
+        for line in fs::read_to_string(path)?.lines() {
+            if line.is_empty() {
+                continue;
+            }
+            let record = line
+                .parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            records.push(record);
+        }
+    }
+    records.sort_by_key(|record: &ControlRecord| (record.time_micros, record.node_id));
+    Ok(records)
+}
+
+/// Renders merged records as a cluster-wide timeline, with one line per record and times
+/// relative to the first record.
+///
+/// The clocks of the nodes may be skewed, so a message may appear to be received before it was
+/// sent.
+pub fn render_control_timeline(records: &[ControlRecord]) -> String {
+    let start = records.first().map_or(0, |record| record.time_micros);
+    let mut timeline = String::new();
+    for record in records {
+        let arrow = match record.direction {
+            ControlDirection::Sent => "->",
+            ControlDirection::Received => "<-",
+        };
+        let elapsed = record.time_micros.saturating_sub(start);
+        timeline.push_str(&format!(
+            "{:>10}.{:03} ms  node {} {} node {}  {}\n",
+            elapsed / 1000,
+            elapsed % 1000,
+            record.node_id,
+            arrow,
+            record.peer,
+            record.message
+        ));
+    }
+    timeline
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the logs of several nodes are merged into one timeline.
+    #[test]
+    fn test_control_timeline() {
+        let directory = std::env::temp_dir().join(format!("erdos-control-{}", std::process::id()));
+        let sender = ControlRecorder::new(0, &directory).unwrap();
+        let receiver = ControlRecorder::new(1, &directory).unwrap();
+        let msg = ControlMessage::ControlSenderInitialized(0);
+        sender.record(ControlDirection::Sent, 1, &msg);
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        receiver.record(ControlDirection::Received, 0, &msg);
+        ControlRecorder::disabled(2).record(ControlDirection::Sent, 0, &msg);
+
+        let records = merge_control_logs(&[
+            directory.join("control-1.log"),
+            directory.join("control-0.log"),
+        ])
+        .unwrap();
+        fs::remove_dir_all(&directory).ok();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            (records[0].node_id, records[0].direction, records[0].peer),
+            (0, ControlDirection::Sent, 1)
+        );
+        assert_eq!(records[1].direction, ControlDirection::Received);
+        assert_eq!(records[0].to_string().parse(), Ok(records[0].clone()));
+
+        let timeline = render_control_timeline(&records);
+        let lines: Vec<&str> = timeline.lines().collect();
+        assert!(lines[0].ends_with("0.000 ms  node 0 -> node 1  ControlSenderInitialized(0)"));
+        assert!(lines[1].contains("node 1 <- node 0"));
+    }
+}
@@ -1,20 +1,47 @@
-use std::{fmt::Debug, sync::Arc};
+use std::{
+    fmt::Debug,
+    sync::Arc,
+    task::{Context, Poll},
+};
 use tokio::sync::mpsc;
 
 use crate::{
-    communication::{CommunicationError, InterProcessMessage, Serializable, TryRecvError},
+    communication::{
+        bounded_channel::{BoundedReceiver, BoundedSender},
+        CommunicationError, InterProcessMessage, Serializable, TryRecvError, WatermarkFrame,
+    },
     dataflow::stream::StreamId,
+    node::{latency, run_id::RunId, NodeContext},
 };
 
+/// Computes the key of a message sent on a conflated stream, or `None` if the message must not
+/// be replaced, e.g. because it is a watermark.
+pub(crate) type ConflationKey<D> = Arc<dyn Fn(&D) -> Option<u64> + Send + Sync>;
+
 /// Endpoint to be used to send messages between operators.
+///
+/// Each endpoint holds the [`NodeContext`] of the node which sends on it, on which the sent
+/// messages are counted.
 #[derive(Clone)]
 pub enum SendEndpoint<D: Clone + Send + Debug> {
     /// Send messages to an operator running in the same process.
-    InterThread(mpsc::UnboundedSender<D>),
+    InterThread(mpsc::UnboundedSender<D>, Arc<NodeContext>),
+    /// Send messages to an operator running in the same process on a stream with a
+    /// [`ChannelBound`](crate::communication::ChannelBound).
+    Bounded(BoundedSender<D>, Arc<NodeContext>),
     /// Send messages to operators running on a different node.
     /// Data is first sended to [`DataSender`](crate::communication::senders::DataSender)
     /// which encodes and sends the message on a TCP stream.
-    InterProcess(StreamId, mpsc::UnboundedSender<InterProcessMessage>),
+    /// Messages larger than the optional maximum message size are rejected. Messages of
+    /// conflated streams are tagged with their key.
+    InterProcess(
+        StreamId,
+        mpsc::UnboundedSender<InterProcessMessage>,
+        Option<usize>,
+        RunId,
+        Option<ConflationKey<D>>,
+        Arc<NodeContext>,
+    ),
 }
 
 /// Zero-copy implementation of the endpoint.
@@ -22,34 +49,111 @@ pub enum SendEndpoint<D: Clone + Send + Debug> {
 impl<D: 'static + Serializable + Send + Sync + Debug> SendEndpoint<Arc<D>> {
     pub fn send(&mut self, msg: Arc<D>) -> Result<(), CommunicationError> {
         match self {
-            Self::InterThread(sender) => sender.send(msg).map_err(CommunicationError::from),
-            Self::InterProcess(stream_id, sender) => sender
-                .send(InterProcessMessage::new_deserialized(msg, *stream_id))
-                .map_err(CommunicationError::from),
+            Self::InterThread(sender, context) => {
+                sender.send(msg).map_err(CommunicationError::from)?;
+                context.counters.message_sent();
+                Ok(())
+            }
+            Self::Bounded(sender, context) => {
+                let is_data = WatermarkFrame::as_watermark(msg.as_ref()).is_none()
+                    && WatermarkFrame::as_tick(msg.as_ref()).is_none();
+                sender.send(msg, is_data)?;
+                context.counters.message_sent();
+                Ok(())
+            }
+            Self::InterProcess(
+                stream_id,
+                sender,
+                max_message_size,
+                run_id,
+                conflation_key,
+                context,
+            ) => {
+                if let Some(limit) = *max_message_size {
+                    let size = Serializable::serialized_size(msg.as_ref())?;
+                    if size > limit {
+                        return Err(CommunicationError::MessageTooLarge { size, limit });
+                    }
+                }
+                let key = conflation_key.as_ref().and_then(|key| key(&msg));
+                let watermark = WatermarkFrame::as_watermark(msg.as_ref()).cloned();
+                let tick = WatermarkFrame::as_tick(msg.as_ref()).cloned();
+                sender
+                    .send(
+                        InterProcessMessage::new_deserialized(msg, *stream_id)
+                            .with_run_id(run_id.get())
+                            .with_conflation_key(key)
+                            .with_watermark(watermark)
+                            .with_tick(tick)
+                            .with_timings(latency::sent_timings(*stream_id))
+                            .with_provenance(context.provenance.sent_provenance(*stream_id)),
+                    )
+                    .map_err(CommunicationError::from)?;
+                context.counters.message_sent_to_node();
+                Ok(())
+            }
         }
     }
 }
 
-/// Endpoint to be used to receive messages.
+/// Endpoint to be used to receive messages, which holds the [`NodeContext`] of the node which
+/// reads from it.
 pub enum RecvEndpoint<D: Clone + Send + Debug> {
-    InterThread(mpsc::UnboundedReceiver<D>),
+    InterThread(mpsc::UnboundedReceiver<D>, Arc<NodeContext>),
+    Bounded(BoundedReceiver<D>, Arc<NodeContext>),
 }
 
 impl<D: Clone + Send + Debug> RecvEndpoint<D> {
+    /// The context of the node which reads from the endpoint.
+    pub(crate) fn context(&self) -> &Arc<NodeContext> {
+        match self {
+            Self::InterThread(_, context) | Self::Bounded(_, context) => context,
+        }
+    }
+
     /// Aync read of a new message.
     pub async fn read(&mut self) -> Result<D, CommunicationError> {
-        match self {
-            Self::InterThread(receiver) => receiver
+        let msg = match self {
+            Self::InterThread(receiver, _) => receiver
+                .recv()
+                .await
+                .ok_or(CommunicationError::Disconnected),
+            Self::Bounded(receiver, _) => receiver
                 .recv()
                 .await
                 .ok_or(CommunicationError::Disconnected),
+        }?;
+        self.context().counters.message_read();
+        Ok(msg)
+    }
+
+    /// Polls for a new message.
+    pub fn poll_read(&mut self, cx: &mut Context<'_>) -> Poll<Result<D, CommunicationError>> {
+        let result = self
+            .poll_recv(cx)
+            .map(|msg| msg.ok_or(CommunicationError::Disconnected));
+        if let Poll::Ready(Ok(_)) = result {
+            self.context().counters.message_read();
         }
+        result
     }
 
-    /// Non-blocking read of a new message. Returns `TryRecvError::Empty` if no message is available.
-    pub fn try_read(&mut self) -> Result<D, TryRecvError> {
+    /// Polls for a new message without counting it as read by the quiescence detection.
+    /// Returns `None` once the sending side is dropped.
+    pub(crate) fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<D>> {
         match self {
-            Self::InterThread(receiver) => receiver.try_recv().map_err(TryRecvError::from),
+            Self::InterThread(receiver, _) => receiver.poll_recv(cx),
+            Self::Bounded(receiver, _) => receiver.poll_recv(cx),
         }
     }
+
+    /// Non-blocking read of a new message. Returns `TryRecvError::Empty` if no message is available.
+    pub fn try_read(&mut self) -> Result<D, TryRecvError> {
+        let msg = match self {
+            Self::InterThread(receiver, _) => receiver.try_recv().map_err(TryRecvError::from),
+            Self::Bounded(receiver, _) => receiver.try_recv(),
+        }?;
+        self.context().counters.message_read();
+        Ok(msg)
+    }
 }
@@ -1,6 +1,8 @@
 use std::io;
 use tokio::sync::mpsc;
 
+use crate::dataflow::stream::StreamId;
+
 /// Error raised by the communication layer.
 #[derive(Debug)]
 pub enum CommunicationError {
@@ -26,6 +28,23 @@ pub enum CommunicationError {
     SharedMemoryError(shared_memory::ShmemError),
     #[cfg(feature = "zenoh_zerocopy_transport")]
     ZenohSharedMemoryError(String),
+    /// The serialized message is larger than the configured maximum message size.
+    MessageTooLarge { size: usize, limit: usize },
+    /// Failed to compress/decompress a message with zstd.
+    CompressionError(io::Error),
+}
+
+impl CommunicationError {
+    /// Returns true if the error was raised while decoding a message.
+    pub(crate) fn is_decode_error(&self) -> bool {
+        match self {
+            CommunicationError::BincodeError(_)
+            | CommunicationError::AbomonationError(_)
+            | CommunicationError::DeserializeNotImplemented
+            | CommunicationError::CompressionError(_) => true,
+            _ => false,
+        }
+    }
 }
 
 impl From<bincode::Error> for CommunicationError {
@@ -74,6 +93,11 @@ impl From<CodecError> for CommunicationError {
             CodecError::ZenohSharedMemoryError(zshm_error) => {
                 CommunicationError::ZenohSharedMemoryError(zshm_error)
             }
+            CodecError::MessageTooLarge { size, limit }
+            | CodecError::SkippedFrame { size, limit, .. } => {
+                CommunicationError::MessageTooLarge { size, limit }
+            }
+            CodecError::CompressionError(e) => CommunicationError::CompressionError(e),
         }
     }
 }
@@ -104,6 +128,21 @@ pub enum CodecError {
     SharedMemoryError(shared_memory::ShmemError),
     #[cfg(feature = "zenoh_zerocopy_transport")]
     ZenohSharedMemoryError(String),
+    /// The frame is larger than the maximum frame size of the codec.
+    MessageTooLarge {
+        size: usize,
+        limit: usize,
+    },
+    /// A received frame was larger than the maximum frame size of the codec, and was skipped.
+    /// The codec decodes the next frame, so the stream can still be read.
+    SkippedFrame {
+        /// The stream of the frame, if its metadata was received.
+        stream_id: Option<StreamId>,
+        size: usize,
+        limit: usize,
+    },
+    /// Failed to compress/decompress a message with zstd.
+    CompressionError(io::Error),
 }
 
 impl From<io::Error> for CodecError {
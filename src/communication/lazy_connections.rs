@@ -0,0 +1,201 @@
+use futures::{
+    channel::{
+        mpsc::{self, UnboundedReceiver},
+        oneshot,
+    },
+    future, stream,
+};
+use futures_util::{sink::SinkExt, stream::StreamExt};
+use std::{collections::HashMap, io, net::SocketAddr, sync::Arc};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+use crate::{
+    communication::{
+        receivers::DataStream, senders::DataSink, Authenticator, CodecError, CommunicationError,
+        InterProcessMessage, MessageCodec,
+    },
+    node::NodeId,
+};
+
+/// Returns a sink which connects to the data address of the node `peer_id` upon the first
+/// message, unless `warm_up` is set in which case it connects immediately.
+///
+/// Messages sent before the connection completes are buffered, and written in order once it
+/// completes. The connection only carries messages from the node to the other node.
+pub(crate) fn lazy_data_sink(
+    addr: SocketAddr,
+    node_id: NodeId,
+    peer_id: NodeId,
+    warm_up: bool,
+    codec: MessageCodec,
+    authenticator: Option<Arc<dyn Authenticator>>,
+    logger: slog::Logger,
+) -> DataSink {
+    let (tx, rx) = mpsc::unbounded();
+    tokio::spawn(async move {
+        let authenticator = authenticator.as_deref();
+        let peer = (peer_id, addr);
+        if let Err(e) =
+            write_lazily(peer, node_id, warm_up, rx, codec, authenticator, &logger).await
+        {
+            slog::error!(
+                logger,
+                "Node {}: lazy connection to {} errored with {:?}",
+                node_id,
+                addr,
+                e
+            );
+        }
+    });
+    Box::pin(tx.sink_map_err(|_| {
+        CodecError::from(io::Error::new(
+            io::ErrorKind::BrokenPipe,
+            "The lazy connection closed",
+        ))
+    }))
+}
+
+async fn write_lazily(
+    (peer_id, addr): (NodeId, SocketAddr),
+    node_id: NodeId,
+    warm_up: bool,
+    mut rx: UnboundedReceiver<InterProcessMessage>,
+    codec: MessageCodec,
+    authenticator: Option<&dyn Authenticator>,
+    logger: &slog::Logger,
+) -> Result<(), CommunicationError> {
+    let first_msg = if warm_up {
+        None
+    } else {
+        match rx.next().await {
+            Some(msg) => Some(msg),
+            None => return Ok(()),
+        }
+    };
+    slog::debug!(logger, "Node {}: connecting to {}", node_id, addr);
+    let stream = super::connect_to_node(&addr, node_id, peer_id, authenticator, logger).await?;
+    let mut sink = FramedWrite::new(stream, codec);
+    if let Some(msg) = first_msg {
+        sink.send(msg).await?;
+    }
+    while let Some(msg) = rx.next().await {
+        sink.send(msg).await?;
+    }
+    Ok(())
+}
+
+/// Accepts the lazy connections of other nodes, and hands them to the corresponding
+/// [`DataStream`]s.
+pub(crate) struct LazyAcceptor {
+    node_id: NodeId,
+    pending_streams: HashMap<NodeId, oneshot::Sender<TcpStream>>,
+}
+
+impl LazyAcceptor {
+    /// Returns the acceptor of the connections to the node `node_id`.
+    pub fn new(node_id: NodeId) -> Self {
+        Self {
+            node_id,
+            pending_streams: HashMap::new(),
+        }
+    }
+
+    /// Returns a stream of the messages which the node receives from another node once the
+    /// other node connects.
+    pub fn data_stream(&mut self, node_id: NodeId, max_frame_size: Option<usize>) -> DataStream {
+        let (tx, rx) = oneshot::channel();
+        self.pending_streams.insert(node_id, tx);
+        Box::pin(
+            stream::once(rx)
+                .filter_map(|result| future::ready(result.ok()))
+                .map(move |tcp_stream| {
+                    FramedRead::new(
+                        tcp_stream,
+                        MessageCodec::with_max_frame_size(max_frame_size),
+                    )
+                })
+                .flatten(),
+        )
+    }
+
+    /// Accepts connections until all other nodes connected. The connections of the nodes which
+    /// fail to authenticate are closed if an authenticator is passed.
+    pub async fn run(
+        mut self,
+        mut listener: TcpListener,
+        authenticator: Option<Arc<dyn Authenticator>>,
+        logger: slog::Logger,
+    ) -> Result<(), CommunicationError> {
+        while !self.pending_streams.is_empty() {
+            let (stream, _) = listener.accept().await?;
+            stream.set_nodelay(true).expect("couldn't disable Nagle");
+            let accepted =
+                super::accept_node(stream, self.node_id, authenticator.as_deref(), &logger).await;
+            let (node_id, stream) = match accepted {
+                Ok(accepted) => accepted,
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                    slog::warn!(logger, "Rejecting lazy connection: {}", e);
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            match self.pending_streams.remove(&node_id) {
+                Some(tx) => {
+                    let _ = tx.send(stream);
+                }
+                None => slog::warn!(
+                    logger,
+                    "Rejecting unexpected lazy connection from node {}",
+                    node_id
+                ),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    use crate::dataflow::{stream::StreamId, Message, Timestamp};
+
+    /// Test that messages sent before the lazy connection completes are received.
+    #[test]
+    fn test_lazy_connection() {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let logger = crate::get_terminal_logger();
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let mut acceptor = LazyAcceptor::new(0);
+            let mut data_stream = acceptor.data_stream(1, None);
+            tokio::spawn(acceptor.run(listener, None, logger.clone()));
+
+            let mut data_sink =
+                lazy_data_sink(addr, 1, 0, false, MessageCodec::new(), None, logger);
+            let stream_id = StreamId::new_deterministic();
+            for t in 0..3 {
+                let msg = Message::new_message(Timestamp::new(vec![t]), t);
+                data_sink
+                    .send(InterProcessMessage::new_deserialized(
+                        Arc::new(msg),
+                        stream_id,
+                    ))
+                    .await
+                    .unwrap();
+            }
+            for _ in 0..3 {
+                match data_stream.next().await {
+                    Some(Ok(InterProcessMessage::Serialized { metadata, .. })) => {
+                        assert_eq!(metadata.stream_id, stream_id)
+                    }
+                    _ => panic!("Expected a message"),
+                }
+            }
+        });
+    }
+}
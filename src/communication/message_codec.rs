@@ -3,7 +3,12 @@ use bytes::{buf::ext::BufMutExt, BytesMut};
 use std::fmt::Debug;
 use tokio_util::codec::{Decoder, Encoder};
 
-use crate::communication::{CodecError, InterProcessMessage, MessageMetadata};
+use crate::communication::{
+    compact_header::{self, CompactDecoder, CompactEncoder, CompactFrame},
+    watermark_frame::{self, WATERMARK_FRAME},
+    CodecError, InterProcessMessage, MessageMetadata,
+};
+use crate::dataflow::stream::StreamId;
 
 const HEADER_SIZE: usize = 8;
 
@@ -17,17 +22,41 @@ enum DecodeStatus {
     Data {
         data_size: usize,
     },
+    Watermark {
+        num_coordinates: usize,
+    },
+    /// Skip the rest of a frame larger than the maximum frame size.
+    Skip {
+        stream_id: Option<StreamId>,
+        size: usize,
+        remaining: usize,
+    },
 }
 
 /// Encodes messages into bytes, and decodes bytes into an [`InterProcessMessage`].
 ///
 /// For each message, the codec first writes the size of its message header,
-/// then the message header, and finally the content of the message.
+/// then the message header, and finally the content of the message. Watermarks are
+/// written as compact watermark frames instead.
+///
+/// If compact headers are enabled, the codec instead writes an index of the stream and the size
+/// of the message as varints for the messages which are not chunked, compressed, or timed. The
+/// codec always decodes both kinds of headers, so each end of a connection enables compact
+/// headers for the messages it sends.
+///
+/// Received frames larger than the maximum frame size are skipped without buffering them, and
+/// decoding fails with [`CodecError::SkippedFrame`] once the frame is skipped. The codec then
+/// decodes the next frame.
 #[derive(Debug)]
 pub struct MessageCodec {
     /// Current part of the message to decode.
     status: DecodeStatus,
     msg_metadata: Option<MessageMetadata>,
+    /// Maximum size of the data of a frame. Unlimited if `None`.
+    max_frame_size: Option<usize>,
+    /// Set if the codec writes compact headers.
+    compact_encoder: Option<CompactEncoder>,
+    compact_decoder: CompactDecoder,
 }
 
 impl MessageCodec {
@@ -35,6 +64,107 @@ impl MessageCodec {
         MessageCodec {
             status: DecodeStatus::Header,
             msg_metadata: None,
+            max_frame_size: None,
+            compact_encoder: None,
+            compact_decoder: CompactDecoder::default(),
+        }
+    }
+
+    /// Creates a codec which rejects frames larger than `max_frame_size` bytes.
+    pub fn with_max_frame_size(max_frame_size: Option<usize>) -> MessageCodec {
+        MessageCodec {
+            max_frame_size,
+            ..MessageCodec::new()
+        }
+    }
+
+    /// Makes the codec write compact headers.
+    pub fn with_compact_headers(mut self, compact_headers: bool) -> MessageCodec {
+        self.compact_encoder = if compact_headers {
+            Some(CompactEncoder::default())
+        } else {
+            None
+        };
+        self
+    }
+
+    fn check_frame_size(&self, size: usize) -> Result<(), CodecError> {
+        match self.max_frame_size {
+            Some(limit) if size > limit => Err(CodecError::MessageTooLarge { size, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    fn is_too_large(&self, size: usize) -> bool {
+        self.check_frame_size(size).is_err()
+    }
+
+    /// Skips the next `size` bytes of the frame, which is too large.
+    fn skip(
+        &mut self,
+        stream_id: Option<StreamId>,
+        size: usize,
+        buf: &mut BytesMut,
+    ) -> Result<Option<InterProcessMessage>, CodecError> {
+        self.status = DecodeStatus::Skip {
+            stream_id,
+            size,
+            remaining: size,
+        };
+        self.decode(buf)
+    }
+
+    /// Writes the header of a message whose data has `data_size` bytes, and reserves memory for
+    /// the data.
+    fn encode_header(
+        &mut self,
+        metadata: &MessageMetadata,
+        data_size: usize,
+        buf: &mut BytesMut,
+    ) -> Result<(), CodecError> {
+        self.check_frame_size(data_size)?;
+        if let Some(encoder) = self
+            .compact_encoder
+            .as_mut()
+            .filter(|_| compact_header::fits(metadata))
+        {
+            buf.reserve(compact_header::MAX_HEADER_SIZE + data_size);
+            encoder.encode_header(metadata, data_size, buf);
+            return Ok(());
+        }
+        let metadata_size = bincode::serialized_size(metadata).map_err(CodecError::from)?;
+        buf.reserve(HEADER_SIZE + metadata_size as usize + data_size);
+
+        let mut writer = buf.writer();
+        writer.write_u32::<NetworkEndian>(metadata_size as u32)?;
+        writer.write_u32::<NetworkEndian>(data_size as u32)?;
+        bincode::serialize_into(&mut writer, metadata).map_err(CodecError::from)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "tcp_transport")]
+    fn decode_compact(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> Result<Option<InterProcessMessage>, CodecError> {
+        match self.compact_decoder.decode(buf)? {
+            None => Ok(None),
+            Some(CompactFrame::Stream) => self.decode(buf),
+            Some(CompactFrame::Data {
+                metadata,
+                data_size,
+            }) => {
+                if self.is_too_large(data_size) {
+                    return self.skip(Some(metadata.stream_id), data_size, buf);
+                }
+                self.msg_metadata = Some(metadata);
+                self.status = DecodeStatus::Data { data_size };
+                buf.reserve(data_size + HEADER_SIZE);
+                self.decode(buf)
+            }
+            Some(CompactFrame::Watermark(metadata)) => Ok(Some(
+                InterProcessMessage::new_serialized(BytesMut::new(), metadata),
+            )),
         }
     }
 }
@@ -53,16 +183,37 @@ impl Decoder for MessageCodec {
         match self.status {
             // Decode the header and reserve
             DecodeStatus::Header => {
+                if buf
+                    .first()
+                    .map_or(false, |byte| compact_header::is_compact(*byte))
+                {
+                    return self.decode_compact(buf);
+                }
                 if buf.len() >= HEADER_SIZE {
                     let header = buf.split_to(HEADER_SIZE);
+                    if NetworkEndian::read_u32(&header[0..4]) == WATERMARK_FRAME {
+                        let num_coordinates = NetworkEndian::read_u32(&header[4..8]) as usize;
+                        let body_size = watermark_frame::body_size(num_coordinates);
+                        if self.is_too_large(body_size) {
+                            return self.skip(None, body_size, buf);
+                        }
+                        self.status = DecodeStatus::Watermark { num_coordinates };
+                        return self.decode(buf);
+                    }
                     let metadata_size = NetworkEndian::read_u32(&header[0..4]) as usize;
                     let data_size = NetworkEndian::read_u32(&header[4..8]) as usize;
                     self.status = DecodeStatus::Metadata {
                         metadata_size,
                         data_size,
                     };
-                    // Reserve space in the buffer for the rest of the message and the next header.
-                    buf.reserve(metadata_size + data_size + HEADER_SIZE);
+                    // Reserve space in the buffer for the rest of the message and the next header,
+                    // except for the data of oversized frames, which is skipped.
+                    let reserved_data_size = if self.is_too_large(data_size) {
+                        0
+                    } else {
+                        data_size
+                    };
+                    buf.reserve(metadata_size + reserved_data_size + HEADER_SIZE);
                     self.decode(buf)
                 } else {
                     Ok(None)
@@ -77,6 +228,9 @@ impl Decoder for MessageCodec {
                     let metadata_bytes = buf.split_to(metadata_size);
                     let metadata: MessageMetadata =
                         bincode::deserialize(&metadata_bytes).map_err(CodecError::BincodeError)?;
+                    if self.is_too_large(data_size) {
+                        return self.skip(Some(metadata.stream_id), data_size, buf);
+                    }
                     self.msg_metadata = Some(metadata);
                     self.status = DecodeStatus::Data { data_size };
                     self.decode(buf)
@@ -98,6 +252,44 @@ impl Decoder for MessageCodec {
                     Ok(None)
                 }
             }
+            // Decode a watermark frame, which carries no serialized message.
+            DecodeStatus::Watermark { num_coordinates } => {
+                let body_size = watermark_frame::body_size(num_coordinates);
+                if buf.len() >= body_size {
+                    let body = buf.split_to(body_size);
+                    let metadata = watermark_frame::decode_body(&body, num_coordinates)?;
+                    self.status = DecodeStatus::Header;
+                    Ok(Some(InterProcessMessage::new_serialized(
+                        BytesMut::new(),
+                        metadata,
+                    )))
+                } else {
+                    Ok(None)
+                }
+            }
+            // Drop the bytes of an oversized frame as they are received.
+            DecodeStatus::Skip {
+                stream_id,
+                size,
+                remaining,
+            } => {
+                let num_bytes = remaining.min(buf.len());
+                let _ = buf.split_to(num_bytes);
+                if num_bytes < remaining {
+                    self.status = DecodeStatus::Skip {
+                        stream_id,
+                        size,
+                        remaining: remaining - num_bytes,
+                    };
+                    return Ok(None);
+                }
+                self.status = DecodeStatus::Header;
+                Err(CodecError::SkippedFrame {
+                    stream_id,
+                    size,
+                    limit: self.max_frame_size.unwrap_or_default(),
+                })
+            }
         }
     }
 }
@@ -109,29 +301,32 @@ impl Encoder<InterProcessMessage> for MessageCodec {
     /// Encodes a InterProcessMessage into a buffer.
     ///
     /// First writes the header_size, then the header, and finally the
-    /// serialized message.
+    /// serialized message. Serialized messages are copied into the buffer as-is.
+    /// Watermarks skip the serialization of the message and its metadata.
     fn encode(&mut self, msg: InterProcessMessage, buf: &mut BytesMut) -> Result<(), CodecError> {
-        // Serialize and write the header.
-        let (metadata, data) = match msg {
-            InterProcessMessage::Deserialized { metadata, data } => (metadata, data),
-            InterProcessMessage::Serialized {
-                metadata: _,
-                bytes: _,
-            } => unreachable!(),
-        };
-
-        // Allocate memory in the buffer for serialized metadata and data
-        // to reduce memory allocations.
-        let metadata_size = bincode::serialized_size(&metadata).map_err(CodecError::from)?;
-        let data_size = data.serialized_size().unwrap();
-        buf.reserve(HEADER_SIZE + metadata_size as usize + data_size);
-
-        // Serialize directly into the buffer.
-        let mut writer = buf.writer();
-        writer.write_u32::<NetworkEndian>(metadata_size as u32)?;
-        writer.write_u32::<NetworkEndian>(data_size as u32)?;
-        bincode::serialize_into(&mut writer, &metadata).map_err(CodecError::from)?;
-        data.encode_into(buf).unwrap();
+        if let Some(timestamp) = msg.metadata().watermark_frame() {
+            buf.reserve(watermark_frame::frame_size(timestamp));
+            match self.compact_encoder.as_mut() {
+                Some(encoder) => encoder.encode_watermark(msg.metadata(), timestamp, buf),
+                None => watermark_frame::encode(msg.metadata(), timestamp, buf),
+            }
+            return Ok(());
+        }
+        match msg {
+            InterProcessMessage::Deserialized { metadata, data } => {
+                // Allocate memory in the buffer for serialized metadata and data
+                // to reduce memory allocations.
+                let data_size = data.serialized_size().unwrap();
+                self.encode_header(&metadata, data_size, buf)?;
+                // Serialize directly into the buffer.
+                data.encode_into(buf).unwrap();
+            }
+            // Chunks of split messages are already serialized.
+            InterProcessMessage::Serialized { metadata, bytes } => {
+                self.encode_header(&metadata, bytes.len(), buf)?;
+                buf.extend_from_slice(&bytes);
+            }
+        }
 
         Ok(())
     }
@@ -142,3 +337,48 @@ impl Default for MessageCodec {
         Self::new()
     }
 }
+
+#[cfg(all(test, feature = "tcp_transport"))]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// Test that frames larger than the maximum frame size are skipped, and that the next frame
+    /// is decoded.
+    #[test]
+    fn test_skip_oversized_frame() {
+        let large_stream_id = StreamId::new_deterministic();
+        let small_stream_id = StreamId::new_deterministic();
+        let mut encoder = MessageCodec::new();
+        let mut buf = BytesMut::new();
+        let large_msg =
+            InterProcessMessage::new_deserialized(Arc::new(vec![0u8; 1024]), large_stream_id);
+        encoder.encode(large_msg, &mut buf).unwrap();
+        let small_msg =
+            InterProcessMessage::new_deserialized(Arc::new(vec![0u8; 16]), small_stream_id);
+        encoder.encode(small_msg, &mut buf).unwrap();
+
+        // The oversized frame is skipped as its bytes are received.
+        let rest = buf.split_off(512);
+        let mut decoder = MessageCodec::with_max_frame_size(Some(256));
+        assert!(matches!(decoder.decode(&mut buf), Ok(None)));
+        assert!(buf.is_empty());
+        buf.extend_from_slice(&rest);
+        match decoder.decode(&mut buf) {
+            Err(CodecError::SkippedFrame {
+                stream_id, limit, ..
+            }) => {
+                assert_eq!(stream_id, Some(large_stream_id));
+                assert_eq!(limit, 256);
+            }
+            _ => panic!("Expected the oversized frame to be skipped"),
+        }
+        match decoder.decode(&mut buf) {
+            Ok(Some(InterProcessMessage::Serialized { metadata, .. })) => {
+                assert_eq!(metadata.stream_id, small_stream_id)
+            }
+            _ => panic!("Expected the next frame to be decoded"),
+        }
+    }
+}
@@ -5,10 +5,20 @@ use std::{
     time::{Duration, Instant},
 };
 
-use crate::{dataflow::stream::StreamId, node::NodeId, OperatorId};
+use crate::{
+    dataflow::{
+        stream::{demand::DemandChange, StreamId},
+        Timestamp,
+    },
+    node::{quiescence::QuiescenceToken, HopTimings, NodeId, NodeInfo, Provenance},
+    OperatorId, Uuid,
+};
 use byteorder::{ByteOrder, NetworkEndian, WriteBytesExt};
 use bytes::BytesMut;
-use futures::future;
+use futures::{
+    future,
+    stream::{FuturesUnordered, StreamExt},
+};
 use serde::{Deserialize, Serialize};
 use slog;
 use std::boxed::Box;
@@ -20,14 +30,39 @@ use tokio::{
 };
 
 // Private submodules
+mod authentication;
+mod chunking;
+mod compact_header;
+mod compression;
 mod control_message_codec;
 mod control_message_handler;
+mod control_recorder;
 mod endpoints;
 mod errors;
+#[cfg(feature = "tcp_transport")]
+mod lazy_connections;
 mod message_codec;
+#[cfg(feature = "tcp_transport")]
+mod multiplex_codec;
 mod serializable;
+mod sequence;
+mod serialization_pool;
+mod traffic_shaping;
+mod transport;
+#[cfg(feature = "uds_transport")]
+mod uds;
+mod unregistered;
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+mod uring;
+mod watermark_frame;
+#[cfg(feature = "zenoh_zerocopy_transport")]
+mod zenoh_shm_pool;
+
+// Public submodules
+pub mod conformance;
 
 // Crate-wide visible submodules
+pub(crate) mod bounded_channel;
 pub(crate) mod pusher;
 
 #[cfg(feature = "tcp_transport")]
@@ -47,7 +82,6 @@ pub(crate) mod zenoh_shm_senders;
 
 #[cfg(feature = "zenoh_zerocopy_transport")]
 pub(crate) mod zenoh_shm_receivers;
-
 // Private imports
 use serializable::Serializable;
 
@@ -55,24 +89,111 @@ use serializable::Serializable;
 #[cfg(feature = "tcp_transport")]
 pub(crate) use control_message_codec::ControlMessageCodec;
 #[cfg(feature = "tcp_transport")]
+pub(crate) use lazy_connections::{lazy_data_sink, LazyAcceptor};
+#[cfg(feature = "tcp_transport")]
 pub(crate) use message_codec::MessageCodec;
+#[cfg(feature = "tcp_transport")]
+pub(crate) use multiplex_codec::{multiplex, MultiplexedConnection};
+#[cfg(feature = "uds_transport")]
+pub(crate) use uds::create_unix_streams_to_peers;
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+pub(crate) use uring::UringFramed;
+#[cfg(feature = "zenoh_zerocopy_transport")]
+pub(crate) use zenoh_shm_pool::{
+    metrics as shm_pool_metrics, remove_stale_segments as remove_stale_shm_segments,
+    set_shm_directory, ShmPool, ShmPoolConfig,
+};
 
+pub(crate) use chunking::{to_serialized_bytes, Chunker, Reassembler};
+pub(crate) use compression::{
+    check_codec_available, check_codecs, sorted_codecs, StreamCompressor, StreamDecompressor,
+};
 pub(crate) use control_message_handler::ControlMessageHandler;
-pub(crate) use errors::{CodecError, CommunicationError, TryRecvError};
+pub(crate) use control_recorder::ControlRecorder;
+pub(crate) use errors::{CommunicationError, TryRecvError};
 pub(crate) use pusher::{Pusher, PusherT};
+pub(crate) use sequence::{lost_messages, GapDetector, SequenceNumbers};
+pub(crate) use serialization_pool::SerializationPool;
+pub(crate) use traffic_shaping::{
+    limit_knob_value, parse_limit_knob, ShapedReceiver, TrafficShaper,
+};
+pub(crate) use unregistered::UnregisteredMessages;
+pub(crate) use watermark_frame::WatermarkFrame;
 
 // Crate-wide exports
-pub(crate) use endpoints::{RecvEndpoint, SendEndpoint};
+pub(crate) use endpoints::{ConflationKey, RecvEndpoint, SendEndpoint};
+
+// Public exports
+pub use authentication::Authenticator;
+#[cfg(feature = "auth")]
+pub use authentication::SharedSecretAuthenticator;
+pub use bounded_channel::{BackpressurePolicy, ChannelBound};
+pub use chunking::ChunkMetadata;
+pub use compression::{CompressionCodec, DictionaryConfig, StreamDictionary};
+pub use control_recorder::{
+    merge_control_logs, render_control_timeline, ControlDirection, ControlRecord,
+};
+pub use errors::CodecError;
+pub use traffic_shaping::{BandwidthLimit, StreamShaping};
+#[cfg(feature = "tcp_transport")]
+pub use transport::TcpTransport;
+#[cfg(feature = "uds_transport")]
+pub use transport::UnixTransport;
+pub use transport::{MessageSink, MessageStream, MockTransport, Transport, TransportLink};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ControlMessage {
-    AllOperatorsInitializedOnNode(NodeId),
+    /// Contains the run ID proposed by the node.
+    AllOperatorsInitializedOnNode(NodeId, Uuid),
+    /// A node failed to set up its operators, e.g. because an operator's setup timed out.
+    NodeSetupFailed(NodeId, String),
     OperatorInitialized(OperatorId),
     RunOperator(OperatorId),
+    /// The [`Operator::run`](crate::dataflow::Operator::run) method of an operator completed, or
+    /// the operator called [`RunContext::ready`](crate::dataflow::RunContext::ready).
+    OperatorRan(OperatorId),
+    /// The operators added to the running dataflow finished setting up on the node.
+    OperatorsAdded(NodeId, Vec<OperatorId>),
     DataSenderInitialized(NodeId),
     DataReceiverInitialized(NodeId),
     ControlSenderInitialized(NodeId),
     ControlReceiverInitialized(NodeId),
+    /// An operator failed and its error policy requires tearing down the dataflow.
+    OperatorFailed(OperatorId, String),
+    /// A compression dictionary for the messages of a stream sent between two nodes.
+    StreamDictionary(StreamDictionary),
+    /// The codecs with which the node compresses the messages of streams, which all nodes must
+    /// agree on. Sent before the node tells that its operators are initialized.
+    StreamCodecs(NodeId, Vec<(StreamId, CompressionCodec)>),
+    /// Asks the stateful operators of a node to take the checkpoint with the given ID.
+    TakeCheckpoint(u64),
+    /// Tells that the node is alive. Sent periodically to all other nodes.
+    Heartbeat(NodeId),
+    /// The node did not send a heartbeat within the timeout, or did not reply to the liveliness
+    /// checks. Sent by the node which detected the failure to all other nodes, which tear down
+    /// the dataflow.
+    NodeFailed(NodeId),
+    /// The node did not reply to consecutive liveliness checks with the Zenoh transports. Sent
+    /// to the control handler of the node which checked, which reports the node as failed.
+    PeerUnreachable(NodeId),
+    /// The highest watermarks sent by the operators of a node on the watched streams since the
+    /// last report.
+    WatermarkProgress(Vec<(StreamId, Timestamp)>),
+    /// Grants the node credits to send more messages on the blocking bounded streams which the
+    /// granting node receives, as its operators read the messages.
+    StreamCredits(NodeId, Vec<(StreamId, u64)>),
+    /// Asks a node for its build information, which it sends back to the given node.
+    NodeInfoRequest(NodeId),
+    NodeInfo(NodeInfo),
+    /// Updates a runtime knob to a value. Contains the node on which the update was requested.
+    SetKnob(String, String, NodeId),
+    /// A consumer subscribed to or unsubscribed from a stream.
+    StreamDemand(DemandChange),
+    /// A user-defined message with its type tag. Contains the node which forwards the message
+    /// from the process on which it was sent to the other nodes.
+    User(Vec<u8>, String, NodeId),
+    /// Token passed along the ring of nodes to detect whether the dataflow is quiescent.
+    QuiescenceToken(QuiescenceToken),
 }
 
 impl ControlMessage {
@@ -93,6 +214,51 @@ impl ControlMessage {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageMetadata {
     pub stream_id: StreamId,
+    /// Set if the message is a chunk of a larger message.
+    pub chunk: Option<ChunkMetadata>,
+    /// Whether the data is compressed with the codec or the zstd dictionary of the stream.
+    pub compressed: bool,
+    /// The ID of the run of the dataflow in which the message was sent.
+    pub run_id: Uuid,
+    /// Set if the message is sent on a conflated stream. Queued messages of the stream with the
+    /// same key are replaced by the newest one. Not sent to the other node.
+    #[serde(skip)]
+    pub conflation_key: Option<u64>,
+    /// Set if the latency of the stream is tracked.
+    pub timings: Option<HopTimings>,
+    /// Set if the message is a watermark, which is sent as a watermark frame. Not serialized
+    /// with the metadata.
+    #[serde(skip)]
+    pub watermark: Option<Timestamp>,
+    /// Whether the timestamp of the watermark frame is the timestamp of a tick, i.e. of a
+    /// message without data, instead of a watermark.
+    #[serde(skip)]
+    pub tick: bool,
+    /// Number of the message among the messages of its stream sent to the receiving node,
+    /// starting from 1, with which the receiver detects lost messages. 0 if the message is not
+    /// numbered.
+    pub sequence: u64,
+    /// Set if provenance is tracked and the message was sent by a callback, in order to record
+    /// the origins of the message on the receiving node.
+    pub provenance: Option<Provenance>,
+}
+
+impl MessageMetadata {
+    /// Returns the timestamp of the message if it is sent as a watermark frame, which only
+    /// carries the stream ID and the run ID of the metadata.
+    pub fn watermark_frame(&self) -> Option<&Timestamp> {
+        match self {
+            Self {
+                chunk: None,
+                compressed: false,
+                timings: None,
+                watermark: Some(timestamp),
+                provenance: None,
+                ..
+            } => Some(timestamp),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -129,14 +295,138 @@ impl InterProcessMessage {
         stream_id: StreamId,
     ) -> Self {
         Self::Deserialized {
-            metadata: MessageMetadata { stream_id },
+            metadata: MessageMetadata {
+                stream_id,
+                chunk: None,
+                compressed: false,
+                run_id: Uuid::nil(),
+                conflation_key: None,
+                timings: None,
+                watermark: None,
+                tick: false,
+                sequence: 0,
+                provenance: None,
+            },
             data,
         }
     }
 
+    /// Sets the ID of the run of the dataflow in which the message is sent.
+    pub fn with_run_id(mut self, id: Uuid) -> Self {
+        match &mut self {
+            Self::Serialized { metadata, .. } | Self::Deserialized { metadata, .. } => {
+                metadata.run_id = id
+            }
+        }
+        self
+    }
+
+    /// Sets the key with which the message replaces queued messages of a conflated stream.
+    pub fn with_conflation_key(mut self, key: Option<u64>) -> Self {
+        match &mut self {
+            Self::Serialized { metadata, .. } | Self::Deserialized { metadata, .. } => {
+                metadata.conflation_key = key
+            }
+        }
+        self
+    }
+
+    /// Sets the timestamp of the message if it is a watermark.
+    pub fn with_watermark(mut self, timestamp: Option<Timestamp>) -> Self {
+        match &mut self {
+            Self::Serialized { metadata, .. } | Self::Deserialized { metadata, .. } => {
+                metadata.watermark = timestamp
+            }
+        }
+        self
+    }
+
+    /// Sets the timestamp of the message if it is a tick, which is sent as a watermark frame.
+    pub fn with_tick(mut self, timestamp: Option<Timestamp>) -> Self {
+        if let Some(timestamp) = timestamp {
+            match &mut self {
+                Self::Serialized { metadata, .. } | Self::Deserialized { metadata, .. } => {
+                    metadata.watermark = Some(timestamp);
+                    metadata.tick = true;
+                }
+            }
+        }
+        self
+    }
+
+    /// Sets the number of the message among the messages of its stream sent to a node.
+    pub fn with_sequence(mut self, sequence: u64) -> Self {
+        match &mut self {
+            Self::Serialized { metadata, .. } | Self::Deserialized { metadata, .. } => {
+                metadata.sequence = sequence
+            }
+        }
+        self
+    }
+
+    /// Sets the timings with which the latency of the message is tracked.
+    pub fn with_timings(mut self, timings: Option<HopTimings>) -> Self {
+        match &mut self {
+            Self::Serialized { metadata, .. } | Self::Deserialized { metadata, .. } => {
+                metadata.timings = timings
+            }
+        }
+        self
+    }
+
+    /// Sets the origins of the message if its provenance is tracked.
+    pub fn with_provenance(mut self, provenance: Option<Provenance>) -> Self {
+        match &mut self {
+            Self::Serialized { metadata, .. } | Self::Deserialized { metadata, .. } => {
+                metadata.provenance = provenance
+            }
+        }
+        self
+    }
+
+    pub fn metadata(&self) -> &MessageMetadata {
+        match self {
+            Self::Serialized { metadata, .. } | Self::Deserialized { metadata, .. } => metadata,
+        }
+    }
+
+    /// Returns the size of the serialized data of the message.
+    pub fn data_size(&self) -> Result<usize, CodecError> {
+        match self {
+            InterProcessMessage::Deserialized { metadata: _, data } => {
+                data.serialized_size().map_err(|e| {
+                    CodecError::BincodeError(Box::new(bincode::ErrorKind::Custom(format!(
+                        "{:?}",
+                        e
+                    ))))
+                })
+            }
+            InterProcessMessage::Serialized { metadata: _, bytes } => Ok(bytes.len()),
+        }
+    }
+
+    /// Returns an error if the serialized data of the message is larger than `max_size`.
+    #[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
+    pub fn check_size(&self, max_size: Option<usize>) -> Result<(), CodecError> {
+        let limit = match max_size {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let size = self.data_size()?;
+        if size > limit {
+            return Err(CodecError::MessageTooLarge { size, limit });
+        }
+        Ok(())
+    }
+
     #[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
     pub fn into_rbuf(&self) -> Result<zenoh::net::RBuf, CodecError> {
         const HEADER_SIZE: usize = 8;
+        if let Some(timestamp) = self.metadata().watermark_frame() {
+            let mut buf = Vec::with_capacity(watermark_frame::frame_size(timestamp));
+            watermark_frame::encode(self.metadata(), timestamp, &mut buf);
+            return Ok(buf.into());
+        }
         match self {
             InterProcessMessage::Deserialized { metadata, data } => {
                 let mut buf = Vec::new();
@@ -175,7 +465,7 @@ impl InterProcessMessage {
     #[cfg(feature = "zenoh_zerocopy_transport")]
     pub fn into_sbuf(
         &self,
-        shm: &mut zenoh::net::SharedMemoryManager,
+        shm: &mut ShmPool,
     ) -> Result<zenoh::net::SharedMemoryBuf, CodecError> {
         const HEADER_SIZE: usize = 8;
         match self {
@@ -273,6 +563,9 @@ impl InterProcessMessage {
     #[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
     pub fn from_slice(buf : &[u8]) -> Result<Self, CodecError> {
         const HEADER_SIZE: usize = 8;
+        if let Some(metadata) = watermark_frame::decode(buf)? {
+            return Ok(InterProcessMessage::new_serialized(to_serialized_bytes(&[]), metadata));
+        }
         if buf.len() >= HEADER_SIZE {
 
             let header = &buf[0..HEADER_SIZE];
@@ -342,17 +635,40 @@ impl std::fmt::Debug for InterProcessMessage {
 /// Returns a vec of TCPStreams; one for each node pair.
 ///
 /// The function creates a TCPStream to each node address. The node address vector stores
-/// the network address of each node, and is indexed by node id.
+/// the network address of each node, and is indexed by node id. If an authenticator is passed,
+/// the nodes which connect to the node must authenticate.
 pub async fn create_tcp_streams(
     node_addrs: Vec<SocketAddr>,
     node_id: NodeId,
+    authenticator: Option<&dyn Authenticator>,
+    logger: &slog::Logger,
+) -> Vec<(NodeId, TcpStream)> {
+    let peers: Vec<NodeId> = (0..node_addrs.len()).filter(|&id| id != node_id).collect();
+    create_tcp_streams_to_peers(node_addrs, node_id, &peers, authenticator, logger).await
+}
+
+/// Returns a vec of TCPStreams; one for each peer of the node.
+///
+/// The peers must be symmetric, i.e. each peer must also create a TCPStream to the node.
+pub async fn create_tcp_streams_to_peers(
+    node_addrs: Vec<SocketAddr>,
+    node_id: NodeId,
+    peers: &[NodeId],
+    authenticator: Option<&dyn Authenticator>,
     logger: &slog::Logger,
 ) -> Vec<(NodeId, TcpStream)> {
     let node_addr = node_addrs[node_id].clone();
-    // Connect to the nodes that have a lower id than the node.
-    let connect_streams_fut = connect_to_nodes(node_addrs[..node_id].to_vec(), node_id, logger);
-    // Wait for connections from the nodes that have a higher id than the node.
-    let stream_fut = await_node_connections(node_addr, node_addrs.len() - node_id - 1, logger);
+    // Connect to the peers that have a lower id than the node.
+    let lower_peers = peers
+        .iter()
+        .filter(|&&id| id < node_id)
+        .map(|&id| (id, node_addrs[id]))
+        .collect();
+    let connect_streams_fut = connect_to_nodes(lower_peers, node_id, authenticator, logger);
+    // Wait for connections from the peers that have a higher id than the node.
+    let num_higher_peers = peers.iter().filter(|&&id| id > node_id).count();
+    let stream_fut =
+        await_node_connections(node_addr, node_id, num_higher_peers, authenticator, logger);
     // Wait until all connections are established.
     match future::try_join(connect_streams_fut, stream_fut).await {
         Ok((mut streams, await_streams)) => {
@@ -379,27 +695,35 @@ pub async fn create_tcp_streams(
 ///
 /// The function returns a vector of `(NodeId, TcpStream)` for each connection.
 async fn connect_to_nodes(
-    addrs: Vec<SocketAddr>,
+    addrs: Vec<(NodeId, SocketAddr)>,
     node_id: NodeId,
+    authenticator: Option<&dyn Authenticator>,
     logger: &slog::Logger,
 ) -> Result<Vec<(NodeId, TcpStream)>, std::io::Error> {
     let mut connect_futures = Vec::new();
     // For each node address, launch a task that tries to create a TCP stream to the node.
-    for addr in addrs.iter() {
-        connect_futures.push(connect_to_node(addr, node_id, logger));
+    for (peer_id, addr) in addrs.iter() {
+        connect_futures.push(connect_to_node(addr, node_id, *peer_id, authenticator, logger));
     }
     // Wait for all tasks to complete successfully.
     let tcp_results = future::try_join_all(connect_futures).await?;
-    let streams: Vec<(NodeId, TcpStream)> = (0..tcp_results.len()).zip(tcp_results).collect();
+    let streams: Vec<(NodeId, TcpStream)> = addrs
+        .iter()
+        .map(|(id, _)| *id)
+        .zip(tcp_results)
+        .collect();
     Ok(streams)
 }
 
 /// Creates TCP stream connection to an address and writes the node id on the TCP stream.
 ///
-/// The function keeps on retrying until it connects successfully.
+/// The function keeps on retrying until it connects successfully, and then authenticates the
+/// node and the node `peer_id` to each other if an authenticator is passed.
 async fn connect_to_node(
     dst_addr: &SocketAddr,
     node_id: NodeId,
+    peer_id: NodeId,
+    authenticator: Option<&dyn Authenticator>,
     logger: &slog::Logger,
 ) -> Result<TcpStream, std::io::Error> {
     // Keeps on reatying to connect to `dst_addr` until it succeeds.
@@ -414,7 +738,18 @@ async fn connect_to_node(
                 WriteBytesExt::write_u32::<NetworkEndian>(&mut buffer, node_id as u32)?;
                 loop {
                     match stream.write(&buffer[..]).await {
-                        Ok(_) => return Ok(stream),
+                        Ok(_) => {
+                            if let Some(authenticator) = authenticator {
+                                authentication::connect_handshake(
+                                    &mut stream,
+                                    node_id,
+                                    peer_id,
+                                    authenticator,
+                                )
+                                .await?;
+                            }
+                            return Ok(stream);
+                        }
                         Err(e) => {
                             slog::error!(
                                 logger,
@@ -447,35 +782,79 @@ async fn connect_to_node(
     }
 }
 
-/// Awaiting for connections from `expected_conns` other nodes.
+/// Awaiting for connections from `expected_conns` other nodes to the node `node_id`.
 ///
 /// Upon a new connection, the function reads from the stream the id of the node that initiated
-/// the connection.
+/// the connection. If an authenticator is passed, the connections of the nodes which fail to
+/// authenticate are closed, and do not count towards the expected connections.
 async fn await_node_connections(
     addr: SocketAddr,
+    node_id: NodeId,
     expected_conns: usize,
+    authenticator: Option<&dyn Authenticator>,
     logger: &slog::Logger,
 ) -> Result<Vec<(NodeId, TcpStream)>, std::io::Error> {
-    let mut await_futures = Vec::new();
+    let mut await_futures = FuturesUnordered::new();
+    let mut streams = Vec::with_capacity(expected_conns);
     let mut listener = TcpListener::bind(&addr).await?;
     // Awaiting for `expected_conns` conections.
-    for _ in 0..expected_conns {
-        let (stream, _) = listener.accept().await?;
-        stream.set_nodelay(true).expect("couldn't disable Nagle");
-        // Launch a task that reads the node id from the TCP stream.
-        await_futures.push(read_node_id(stream, logger));
+    while streams.len() < expected_conns {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted?;
+                stream.set_nodelay(true).expect("couldn't disable Nagle");
+                // Launch a task that reads the node id from the TCP stream.
+                await_futures.push(accept_node(stream, node_id, authenticator, logger));
+            }
+            Some(result) = await_futures.next(), if !await_futures.is_empty() => match result {
+                Ok(stream) => streams.push(stream),
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                    slog::warn!(logger, "Rejecting connection to {}: {}", addr, e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Ok(streams)
+}
+
+/// Reads the id of the node which initiated the connection to the node `node_id` and, if an
+/// authenticator is passed, authenticates the nodes to each other.
+///
+/// Returns a [`PermissionDenied`](std::io::ErrorKind::PermissionDenied) error if the nodes do
+/// not authenticate within [`HANDSHAKE_TIMEOUT`](authentication::HANDSHAKE_TIMEOUT).
+async fn accept_node<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    node_id: NodeId,
+    authenticator: Option<&dyn Authenticator>,
+    logger: &slog::Logger,
+) -> Result<(NodeId, S), std::io::Error> {
+    let authenticator = match authenticator {
+        Some(authenticator) => authenticator,
+        None => return read_node_id(stream, logger).await,
+    };
+    let handshake = async {
+        let (peer_id, mut stream) = read_node_id(stream, logger).await?;
+        authentication::accept_handshake(&mut stream, node_id, peer_id, authenticator).await?;
+        Ok::<_, std::io::Error>((peer_id, stream))
+    };
+    match tokio::time::timeout(authentication::HANDSHAKE_TIMEOUT, handshake).await {
+        Ok(Ok(accepted)) => Ok(accepted),
+        Ok(Err(e)) => Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, e)),
+        Err(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "The connecting node did not authenticate in time",
+        )),
     }
-    // Await until we've received `expected_conns` node ids.
-    Ok(future::try_join_all(await_futures).await?)
 }
 
-/// Reads a node id from a TCP stream.
+/// Reads a node id from a stream.
 ///
 /// The method is used to discover the id of the node that initiated the connection.
-async fn read_node_id(
-    mut stream: TcpStream,
+async fn read_node_id<S: AsyncRead + Unpin>(
+    mut stream: S,
     logger: &slog::Logger,
-) -> Result<(NodeId, TcpStream), std::io::Error> {
+) -> Result<(NodeId, S), std::io::Error> {
     let mut buffer = [0u8; 4];
     match stream.read_exact(&mut buffer).await {
         Ok(n) => n,
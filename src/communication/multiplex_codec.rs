@@ -0,0 +1,253 @@
+use byteorder::WriteBytesExt;
+use bytes::BytesMut;
+use futures::{
+    channel::mpsc::{self, UnboundedReceiver},
+    stream::SplitStream,
+};
+use futures_util::{sink::SinkExt, stream::StreamExt};
+use std::io;
+use tokio::net::TcpStream;
+use tokio_util::codec::{Decoder, Encoder, Framed};
+
+use crate::communication::{
+    receivers::{ControlStream, DataStream},
+    senders::{ControlSink, DataSink},
+    CodecError, CommunicationError, ControlMessage, ControlMessageCodec, InterProcessMessage,
+    MessageCodec,
+};
+
+const CONTROL_FRAME: u8 = 0;
+const DATA_FRAME: u8 = 1;
+
+/// A frame sent on a connection shared by the control and the data planes.
+#[derive(Debug)]
+pub(crate) enum Frame {
+    Control(ControlMessage),
+    Data(InterProcessMessage),
+}
+
+/// Encodes control and data messages on a single connection, and decodes them into [`Frame`]s.
+///
+/// For each frame, the codec first writes its type, and then the message encoded with the
+/// [`ControlMessageCodec`] or the [`MessageCodec`].
+#[derive(Debug)]
+pub(crate) struct MultiplexCodec {
+    /// Type of the frame being decoded.
+    frame_type: Option<u8>,
+    control_codec: ControlMessageCodec,
+    data_codec: MessageCodec,
+}
+
+impl MultiplexCodec {
+    pub fn new(data_codec: MessageCodec) -> Self {
+        Self {
+            frame_type: None,
+            control_codec: ControlMessageCodec::new(),
+            data_codec,
+        }
+    }
+}
+
+impl Decoder for MultiplexCodec {
+    type Item = Frame;
+    type Error = CodecError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Frame>, CodecError> {
+        if self.frame_type.is_none() {
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            self.frame_type = Some(buf.split_to(1)[0]);
+        }
+        let frame = match self.frame_type.unwrap() {
+            CONTROL_FRAME => self.control_codec.decode(buf)?.map(Frame::Control),
+            DATA_FRAME => match self.data_codec.decode(buf) {
+                // The skipped frame ends the data frame.
+                Err(e @ CodecError::SkippedFrame { .. }) => {
+                    self.frame_type = None;
+                    return Err(e);
+                }
+                result => result?.map(Frame::Data),
+            },
+            frame_type => {
+                return Err(CodecError::from(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unknown frame type {}", frame_type),
+                )))
+            }
+        };
+        if frame.is_some() {
+            self.frame_type = None;
+        }
+        Ok(frame)
+    }
+}
+
+impl Encoder<Frame> for MultiplexCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, frame: Frame, buf: &mut BytesMut) -> Result<(), CodecError> {
+        let mut frame_type = Vec::with_capacity(1);
+        match frame {
+            Frame::Control(msg) => {
+                frame_type.write_u8(CONTROL_FRAME)?;
+                buf.extend(frame_type);
+                self.control_codec.encode(msg, buf)
+            }
+            Frame::Data(msg) => {
+                frame_type.write_u8(DATA_FRAME)?;
+                buf.extend(frame_type);
+                self.data_codec.encode(msg, buf)
+            }
+        }
+    }
+}
+
+/// The halves of the control and the data planes sharing a multiplexed connection.
+pub(crate) struct MultiplexedConnection {
+    pub control_sink: ControlSink,
+    pub control_stream: ControlStream,
+    pub data_sink: DataSink,
+    pub data_stream: DataStream,
+}
+
+/// Splits a TCP stream into the halves of the control and the data planes.
+///
+/// Spawns tasks which write the frames of both planes on the stream, and route the frames read
+/// from the stream to their plane. Control frames have strict priority over data frames, so
+/// that large data messages do not delay the control plane.
+pub(crate) fn multiplex(
+    stream: TcpStream,
+    data_codec: MessageCodec,
+    logger: slog::Logger,
+) -> MultiplexedConnection {
+    let framed = Framed::new(stream, MultiplexCodec::new(data_codec));
+    let (mut sink, stream) = framed.split();
+
+    let (control_sink_tx, control_sink_rx) = mpsc::unbounded();
+    let (data_sink_tx, data_sink_rx) = mpsc::unbounded();
+    let (control_stream_tx, control_stream_rx) = mpsc::unbounded();
+    let (data_stream_tx, data_stream_rx) = mpsc::unbounded();
+
+    let write_logger = logger.clone();
+    tokio::spawn(async move {
+        if let Err(e) = write_frames(&mut sink, control_sink_rx, data_sink_rx).await {
+            slog::debug!(
+                write_logger,
+                "Writing multiplexed frames errored with {:?}",
+                e
+            );
+        }
+    });
+    tokio::spawn(async move {
+        if let Err(e) = route_frames(stream, control_stream_tx, data_stream_tx).await {
+            slog::debug!(logger, "Reading multiplexed frames errored with {:?}", e);
+        }
+    });
+
+    MultiplexedConnection {
+        control_sink: Box::pin(control_sink_tx.sink_map_err(|_| disconnected())),
+        control_stream: Box::pin(control_stream_rx),
+        data_sink: Box::pin(data_sink_tx.sink_map_err(|_| disconnected())),
+        data_stream: Box::pin(data_stream_rx),
+    }
+}
+
+async fn write_frames(
+    sink: &mut futures::stream::SplitSink<Framed<TcpStream, MultiplexCodec>, Frame>,
+    mut control_rx: UnboundedReceiver<ControlMessage>,
+    mut data_rx: UnboundedReceiver<InterProcessMessage>,
+) -> Result<(), CommunicationError> {
+    loop {
+        // Send all pending control frames before the next data frame.
+        while let Ok(Some(msg)) = control_rx.try_next() {
+            sink.send(Frame::Control(msg)).await?;
+        }
+        let frame = tokio::select! {
+            Some(msg) = control_rx.next() => Frame::Control(msg),
+            Some(msg) = data_rx.next() => Frame::Data(msg),
+            else => return Ok(()),
+        };
+        sink.send(frame).await?;
+    }
+}
+
+async fn route_frames(
+    mut stream: SplitStream<Framed<TcpStream, MultiplexCodec>>,
+    control_tx: mpsc::UnboundedSender<Result<ControlMessage, CodecError>>,
+    data_tx: mpsc::UnboundedSender<Result<InterProcessMessage, CodecError>>,
+) -> Result<(), CommunicationError> {
+    while let Some(frame) = stream.next().await {
+        match frame {
+            Ok(Frame::Control(msg)) => control_tx
+                .unbounded_send(Ok(msg))
+                .map_err(|_| CommunicationError::Disconnected)?,
+            Ok(Frame::Data(msg)) => data_tx
+                .unbounded_send(Ok(msg))
+                .map_err(|_| CommunicationError::Disconnected)?,
+            Err(e @ CodecError::SkippedFrame { .. }) => data_tx
+                .unbounded_send(Err(e))
+                .map_err(|_| CommunicationError::Disconnected)?,
+            Err(e) => {
+                // Both planes fail with the connection.
+                let _ = control_tx.unbounded_send(Err(disconnected()));
+                let _ = data_tx.unbounded_send(Err(disconnected()));
+                return Err(CommunicationError::from(e));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn disconnected() -> CodecError {
+    CodecError::from(io::Error::new(
+        io::ErrorKind::BrokenPipe,
+        "The multiplexed connection closed",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dataflow::{stream::StreamId, Message, Timestamp};
+
+    /// Test that interleaved control and data frames decode into their plane.
+    #[test]
+    fn test_multiplex_codec() {
+        let mut codec = MultiplexCodec::new(MessageCodec::new());
+        let mut buf = BytesMut::new();
+        let stream_id = StreamId::new_deterministic();
+        let data_msg = InterProcessMessage::new_deserialized(
+            std::sync::Arc::new(Message::new_message(Timestamp::new(vec![1]), 1u32)),
+            stream_id,
+        );
+        codec
+            .encode(
+                Frame::Control(ControlMessage::AllOperatorsInitializedOnNode(
+                    0,
+                    crate::Uuid::nil(),
+                )),
+                &mut buf,
+            )
+            .unwrap();
+        codec.encode(Frame::Data(data_msg), &mut buf).unwrap();
+
+        // Partial frames are buffered until they are complete.
+        let mut partial = buf.split_to(3);
+        assert!(codec.decode(&mut partial).unwrap().is_none());
+        partial.unsplit(buf);
+        let mut buf = partial;
+        match codec.decode(&mut buf).unwrap() {
+            Some(Frame::Control(ControlMessage::AllOperatorsInitializedOnNode(0, _))) => (),
+            frame => panic!("Expected a control frame, got {:?}", frame),
+        }
+        match codec.decode(&mut buf).unwrap() {
+            Some(Frame::Data(InterProcessMessage::Serialized { metadata, .. })) => {
+                assert_eq!(metadata.stream_id, stream_id)
+            }
+            frame => panic!("Expected a data frame, got {:?}", frame),
+        }
+        assert!(buf.is_empty());
+    }
+}
@@ -10,28 +10,59 @@ use serde::Deserialize;
 use crate::{
     communication::{
         serializable::{Deserializable, DeserializedMessage, Serializable},
-        CommunicationError, SendEndpoint,
+        CommunicationError, SendEndpoint, WatermarkFrame,
+    },
+    dataflow::{
+        buffer::{self, BufferAllocator},
+        Data, Message, Timestamp,
     },
-    dataflow::Data,
 };
 
+/// Records the size of the frame in which a message was received, which counts against the
+/// memory limits of the operators reading the message.
+pub(crate) trait FrameSize {
+    fn set_frame_size(&mut self, size: usize);
+}
+
+impl<T> FrameSize for T {
+    default fn set_frame_size(&mut self, _size: usize) {}
+}
+
+impl<D: Data> FrameSize for Message<D> {
+    fn set_frame_size(&mut self, size: usize) {
+        if let Message::TimestampedData(msg) = self {
+            msg.frame_size = Some(size);
+        }
+    }
+}
+
 /// Trait used to deserialize a message and send it on a collection of [`SendEndpoint`]s
 /// without exposing the message's type to owner of the [`PusherT`] trait object.
 pub trait PusherT: Send {
     fn as_any(&mut self) -> &mut dyn Any;
     /// To be used to clone a boxed pusher.
     fn box_clone(&self) -> Box<dyn PusherT>;
-    /// Creates message from bytes and sends it to endpoints.
+    /// Creates message from bytes and sends it to endpoints. Hands the bytes back with the error
+    /// on failure, so that they are only copied into a dead letter if the message is dropped.
     #[cfg(feature = "tcp_transport")]
-    fn send_from_bytes(&mut self, buf: BytesMut) -> Result<(), CommunicationError>;
+    fn send_from_bytes(&mut self, buf: BytesMut) -> Result<(), (CommunicationError, BytesMut)>;
     #[cfg(any(feature = "zenoh_zerocopy_transport", feature = "zenoh_transport"))]
-    fn send_from_bytes(&mut self, buf: zenoh::net::protocol::io::ArcSlice) -> Result<(), CommunicationError>;
+    fn send_from_bytes(
+        &mut self,
+        buf: zenoh::net::protocol::io::ArcSlice,
+    ) -> Result<(), (CommunicationError, zenoh::net::protocol::io::ArcSlice)>;
+    /// Creates a watermark received as a watermark frame and sends it to endpoints.
+    fn send_watermark(&mut self, timestamp: Timestamp) -> Result<(), CommunicationError>;
+    /// Creates a tick received as a watermark frame and sends it to endpoints.
+    fn send_tick(&mut self, timestamp: Timestamp) -> Result<(), CommunicationError>;
 }
 
 /// Internal structure used to send data on a collection of [`SendEndpoint`]s.
 #[derive(Clone)]
 pub struct Pusher<D: Debug + Clone + Send> {
     endpoints: Vec<SendEndpoint<D>>,
+    /// Allocates the [`Buffer`](crate::dataflow::Buffer)s of received messages, if set.
+    allocator: Option<Arc<dyn BufferAllocator>>,
 }
 
 /// Zero-copy implementation of the pusher.
@@ -39,13 +70,23 @@ impl<D: 'static + Serializable + Send + Sync + Debug> Pusher<Arc<D>> {
     pub fn new() -> Self {
         Self {
             endpoints: Vec::new(),
+            allocator: None,
         }
     }
 
+    pub fn set_allocator(&mut self, allocator: Arc<dyn BufferAllocator>) {
+        self.allocator = Some(allocator);
+    }
+
     pub fn add_endpoint(&mut self, endpoint: SendEndpoint<Arc<D>>) {
         self.endpoints.push(endpoint);
     }
 
+    /// Replaces the endpoints on which the pusher sends.
+    pub fn set_endpoints(&mut self, endpoints: Vec<SendEndpoint<Arc<D>>>) {
+        self.endpoints = endpoints;
+    }
+
     pub fn send(&mut self, msg: Arc<D>) -> Result<(), CommunicationError> {
         for endpoint in self.endpoints.iter_mut() {
             endpoint.send(Arc::clone(&msg))?;
@@ -75,28 +116,67 @@ where
     }
 
     #[cfg(feature = "tcp_transport")]
-    fn send_from_bytes(&mut self, mut buf: BytesMut) -> Result<(), CommunicationError> {
+    fn send_from_bytes(&mut self, mut buf: BytesMut) -> Result<(), (CommunicationError, BytesMut)> {
         if !self.endpoints.is_empty() {
-            let msg = match Deserializable::decode(&mut buf)? {
-                DeserializedMessage::<D>::Owned(msg) => msg,
-                DeserializedMessage::<D>::Ref(msg) => msg.clone(),
+            let frame_size = buf.len();
+            let decoded = buffer::deserialize_with(self.allocator.as_ref(), || {
+                Deserializable::decode(&mut buf).map(|decoded| match decoded {
+                    DeserializedMessage::<D>::Owned(msg) => msg,
+                    DeserializedMessage::<D>::Ref(msg) => msg.clone(),
+                })
+            });
+            let mut msg = match decoded {
+                Ok(msg) => msg,
+                Err(e) => return Err((e, buf)),
             };
+            msg.set_frame_size(frame_size);
             let msg_arc = Arc::new(msg);
-            self.send(msg_arc)?;
+            if let Err(e) = self.send(msg_arc) {
+                return Err((e, buf));
+            }
         }
         Ok(())
     }
 
     #[cfg(any(feature = "zenoh_zerocopy_transport", feature = "zenoh_transport"))]
-    fn send_from_bytes(&mut self, buf: zenoh::net::protocol::io::ArcSlice) -> Result<(), CommunicationError> {
-        let buf = buf.as_slice();
+    fn send_from_bytes(
+        &mut self,
+        buf: zenoh::net::protocol::io::ArcSlice,
+    ) -> Result<(), (CommunicationError, zenoh::net::protocol::io::ArcSlice)> {
         if !self.endpoints.is_empty() {
-            let msg = match Deserializable::decode_from_vec(&buf)? {
-                DeserializedMessage::<D>::Owned(msg) => msg,
-                DeserializedMessage::<D>::Ref(msg) => msg.clone(),
+            let slice = buf.as_slice();
+            let decoded = buffer::deserialize_with(self.allocator.as_ref(), || {
+                Deserializable::decode_from_vec(&slice).map(|decoded| match decoded {
+                    DeserializedMessage::<D>::Owned(msg) => msg,
+                    DeserializedMessage::<D>::Ref(msg) => msg.clone(),
+                })
+            });
+            let mut msg = match decoded {
+                Ok(msg) => msg,
+                Err(e) => return Err((CommunicationError::from(e), buf)),
             };
+            msg.set_frame_size(slice.len());
             let msg_arc = Arc::new(msg);
-            self.send(msg_arc)?;
+            if let Err(e) = self.send(msg_arc) {
+                return Err((e, buf));
+            }
+        }
+        Ok(())
+    }
+
+    fn send_watermark(&mut self, timestamp: Timestamp) -> Result<(), CommunicationError> {
+        if !self.endpoints.is_empty() {
+            let msg = D::from_watermark(timestamp)
+                .ok_or(CommunicationError::DeserializeNotImplemented)?;
+            self.send(Arc::new(msg))?;
+        }
+        Ok(())
+    }
+
+    fn send_tick(&mut self, timestamp: Timestamp) -> Result<(), CommunicationError> {
+        if !self.endpoints.is_empty() {
+            let msg = D::from_tick(timestamp).ok_or(CommunicationError::DeserializeNotImplemented)?;
+            self.send(Arc::new(msg))?;
         }
         Ok(())
     }
@@ -1,35 +1,40 @@
-use futures::future;
+use bytes::BytesMut;
+use futures::{future, Stream};
 use futures_util::stream::StreamExt;
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, pin::Pin, sync::Arc};
 use tokio::sync::{
     mpsc::{self, UnboundedReceiver, UnboundedSender},
     Mutex,
 };
 
-use futures::stream::SplitStream;
-
-use tokio::net::TcpStream;
-
-use tokio_util::codec::Framed;
-
-use crate::communication::{ControlMessageCodec, MessageCodec};
-
 use crate::{
     communication::{
-        CommunicationError, ControlMessage, ControlMessageHandler, InterProcessMessage, PusherT,
+        CodecError, CommunicationError, ControlDirection, ControlMessage, ControlMessageHandler,
+        ControlRecorder, GapDetector, InterProcessMessage, MessageMetadata, PusherT, Reassembler,
+        StreamDecompressor, UnregisteredMessages,
     },
     dataflow::stream::StreamId,
-    node::NodeId,
+    node::{
+        latency, stream_metrics, DeadLetter, DeadLetterQueue, DeadLetterReason, NodeContext, NodeId,
+    },
     scheduler::endpoints_manager::ChannelsToReceivers,
 };
 
+/// Read half of a framed TCP stream, or of the data plane of a multiplexed connection.
+pub(crate) type DataStream =
+    Pin<Box<dyn Stream<Item = Result<InterProcessMessage, CodecError>> + Send>>;
+
+/// Read half of a framed TCP stream, or of the control plane of a multiplexed connection.
+pub(crate) type ControlStream =
+    Pin<Box<dyn Stream<Item = Result<ControlMessage, CodecError>> + Send>>;
+
 /// Listens on a TCP stream, and pushes messages it receives to operator executors.
 #[allow(dead_code)]
 pub(crate) struct DataReceiver {
     /// The id of the node the stream is receiving data from.
     node_id: NodeId,
     /// Framed TCP read stream.
-    stream: SplitStream<Framed<TcpStream, MessageCodec>>,
+    stream: DataStream,
     /// Channel receiver on which new pusher updates are received.
     rx: UnboundedReceiver<(StreamId, Box<dyn PusherT>)>,
     /// Mapping between stream id to [`PusherT`] trait objects.
@@ -40,14 +45,28 @@ pub(crate) struct DataReceiver {
     control_tx: UnboundedSender<ControlMessage>,
     /// Tokio channel receiver from `ControlMessageHandler`.
     control_rx: UnboundedReceiver<ControlMessage>,
+    /// Queue to which undeliverable messages are sent.
+    dead_letters: DeadLetterQueue,
+    /// The state of the node, on which the received messages are counted and traced.
+    context: Arc<NodeContext>,
+    /// Reassembles messages which the sender split into chunks.
+    reassembler: Reassembler,
+    /// Decompresses messages compressed with the zstd dictionary of their stream.
+    decompressor: StreamDecompressor,
+    /// Detects the messages lost on the channel from the sequence numbers of each stream.
+    gaps: GapDetector,
+    /// Messages received before the pusher of their stream was registered.
+    unregistered: UnregisteredMessages<BytesMut>,
 }
 
 impl DataReceiver {
     pub(crate) async fn new(
         node_id: NodeId,
-        stream: SplitStream<Framed<TcpStream, MessageCodec>>,
+        stream: DataStream,
         channels_to_receivers: Arc<Mutex<ChannelsToReceivers>>,
         control_handler: &mut ControlMessageHandler,
+        dead_letters: DeadLetterQueue,
+        context: Arc<NodeContext>,
     ) -> Self {
         // Create a channel for this stream.
         let (tx, rx) = mpsc::unbounded_channel();
@@ -63,54 +82,224 @@ impl DataReceiver {
             stream_id_to_pusher: HashMap::new(),
             control_tx: control_handler.get_channel_to_handler(),
             control_rx,
+            dead_letters,
+            context,
+            reassembler: Reassembler::new(),
+            decompressor: StreamDecompressor::new(),
+            gaps: GapDetector::default(),
+            unregistered: UnregisteredMessages::default(),
         }
     }
 
+    /// Decompresses the received messages with the decompressor, e.g. to drop the compressed
+    /// messages which are too large once decompressed.
+    pub(crate) fn with_decompressor(mut self, decompressor: StreamDecompressor) -> Self {
+        self.decompressor = decompressor;
+        self
+    }
+
+    /// Returns the id of the node from which the receiver receives data.
+    pub(crate) fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
     pub(crate) async fn run(&mut self) -> Result<(), CommunicationError> {
         // Notify `ControlMessageHandler` that receiver is initialized.
         self.control_tx
             .send(ControlMessage::DataReceiverInitialized(self.node_id))
             .map_err(CommunicationError::from)?;
 
-        while let Some(res) = self.stream.next().await {
-            match res {
-                // Push the message to the listening operator executors.
-                Ok(msg) => {
-                    // Update pushers before we send the message.
-                    // Note: we may want to update the pushers less frequently.
-                    self.update_pushers();
-                    // Send the message.
-                    let (metadata, bytes) = match msg {
-                        InterProcessMessage::Serialized { metadata, bytes } => (metadata, bytes),
-                        InterProcessMessage::Deserialized {
-                            metadata: _,
-                            data: _,
-                        } => unreachable!(),
-                    };
-                    match self.stream_id_to_pusher.get_mut(&metadata.stream_id) {
-                        Some(pusher) => {
-                            if let Err(e) = pusher.send_from_bytes(bytes) {
-                                return Err(e);
-                            }
-                        }
-                        None => panic!(
-                            "Receiver does not have any pushers. \
-                                Race condition during data-flow reconfiguration."
-                        ),
+        loop {
+            tokio::select! {
+                res = self.stream.next() => match res {
+                    // Push the message to the listening operator executors.
+                    Some(Ok(msg)) => self.handle_message(msg)?,
+                    Some(Err(CodecError::SkippedFrame { stream_id, size, limit })) => {
+                        self.dead_letters.send(DeadLetter {
+                            from_node: self.node_id,
+                            stream_id,
+                            reason: DeadLetterReason::MessageTooLarge { size, limit },
+                            bytes: Vec::new(),
+                        })
                     }
+                    Some(Err(e)) => return Err(CommunicationError::from(e)),
+                    None => return Ok(()),
+                },
+                Some(msg) = self.control_rx.recv() => self.handle_control_message(msg)?,
+                // Deliver the messages which wait for the pusher as soon as it is registered.
+                Some((stream_id, pusher)) = self.rx.recv() => self.add_pusher(stream_id, pusher)?,
+            }
+        }
+    }
+
+    fn handle_message(&mut self, msg: InterProcessMessage) -> Result<(), CommunicationError> {
+        // Update pushers before we send the message.
+        // Note: we may want to update the pushers less frequently.
+        self.update_pushers()?;
+        let (metadata, bytes) = match msg {
+            InterProcessMessage::Serialized { metadata, bytes } => (metadata, bytes),
+            InterProcessMessage::Deserialized {
+                metadata: _,
+                data: _,
+            } => unreachable!(),
+        };
+        let lost = self.gaps.observe(&metadata);
+        if lost > 0 {
+            slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "Lost {} messages on stream {} from node {}",
+                lost,
+                metadata.stream_id,
+                self.node_id
+            );
+            self.dead_letters.send(DeadLetter {
+                from_node: self.node_id,
+                stream_id: Some(metadata.stream_id),
+                reason: DeadLetterReason::Lost(lost),
+                bytes: Vec::new(),
+            });
+        }
+        // Wait for all the chunks of split messages.
+        let bytes = match &metadata.chunk {
+            None => bytes,
+            Some(chunk) => match self
+                .reassembler
+                .add_chunk(metadata.stream_id, chunk, &bytes)
+            {
+                Ok(Some(bytes)) => bytes,
+                Ok(None) => return Ok(()),
+                Err(e) => {
+                    self.dead_letters.send(DeadLetter {
+                        from_node: self.node_id,
+                        stream_id: Some(metadata.stream_id),
+                        reason: DeadLetterReason::Incomplete(format!("{:?}", e)),
+                        bytes: Vec::new(),
+                    });
+                    return Ok(());
+                }
+            },
+        };
+        // Wait for the dictionary of compressed messages.
+        match self.decompressor.decompress(&metadata, bytes) {
+            Ok(Some(bytes)) => self.push(&metadata, bytes),
+            Ok(None) => Ok(()),
+            Err(e) => {
+                self.dead_letters.send(DeadLetter {
+                    from_node: self.node_id,
+                    stream_id: Some(metadata.stream_id),
+                    reason: DeadLetterReason::decompression_failed(&e),
+                    bytes: Vec::new(),
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /// Delivers the compressed messages which waited for the dictionary of their stream.
+    fn handle_control_message(&mut self, msg: ControlMessage) -> Result<(), CommunicationError> {
+        if let ControlMessage::StreamDictionary(dictionary) = msg {
+            self.update_pushers()?;
+            for (metadata, result) in self.decompressor.add_dictionary(dictionary) {
+                match result {
+                    Ok(bytes) => self.push(&metadata, bytes)?,
+                    Err(e) => self.dead_letters.send(DeadLetter {
+                        from_node: self.node_id,
+                        stream_id: Some(metadata.stream_id),
+                        reason: DeadLetterReason::decompression_failed(&e),
+                        bytes: Vec::new(),
+                    }),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the message to the operators which read from its stream.
+    fn push(
+        &mut self,
+        metadata: &MessageMetadata,
+        bytes: BytesMut,
+    ) -> Result<(), CommunicationError> {
+        self.context.counters.message_received_from_node();
+        stream_metrics::message_received(metadata.stream_id);
+        self.deliver(metadata, bytes)
+    }
+
+    /// Sends the message to the pusher of its stream, or buffers it until the pusher is
+    /// registered.
+    fn deliver(
+        &mut self,
+        metadata: &MessageMetadata,
+        bytes: BytesMut,
+    ) -> Result<(), CommunicationError> {
+        match self.stream_id_to_pusher.get_mut(&metadata.stream_id) {
+            Some(pusher) => {
+                let deliver = || match &metadata.watermark {
+                    // Watermark frames carry no serialized message.
+                    Some(timestamp) if metadata.tick => {
+                        pusher.send_tick(timestamp.clone()).map_err(|e| (e, None))
+                    }
+                    Some(timestamp) => pusher
+                        .send_watermark(timestamp.clone())
+                        .map_err(|e| (e, None)),
+                    None => pusher
+                        .send_from_bytes(bytes)
+                        .map_err(|(e, bytes)| (e, Some(bytes))),
+                };
+                self.context.provenance.record_received(metadata);
+                match latency::deliver_timed(metadata, deliver) {
+                    Ok(()) => (),
+                    Err((e, bytes)) if e.is_decode_error() => self.dead_letters.send(DeadLetter {
+                        from_node: self.node_id,
+                        stream_id: Some(metadata.stream_id),
+                        reason: DeadLetterReason::Undecodable(format!("{:?}", e)),
+                        // Only copy the message if somebody reads the dead letters.
+                        bytes: match bytes {
+                            Some(bytes) if self.dead_letters.has_subscribers() => bytes.to_vec(),
+                            _ => Vec::new(),
+                        },
+                    }),
+                    Err((e, _)) => return Err(e),
+                }
+            }
+            None => {
+                if let Some((metadata, bytes)) = self.unregistered.push(metadata.clone(), bytes) {
+                    self.dead_letters.send(DeadLetter {
+                        from_node: self.node_id,
+                        stream_id: Some(metadata.stream_id),
+                        reason: DeadLetterReason::NoReceiver,
+                        bytes: if self.dead_letters.has_subscribers() {
+                            bytes.to_vec()
+                        } else {
+                            Vec::new()
+                        },
+                    })
                 }
-                Err(e) => return Err(CommunicationError::from(e)),
             }
         }
         Ok(())
     }
 
     // TODO: update this method.
-    fn update_pushers(&mut self) {
+    fn update_pushers(&mut self) -> Result<(), CommunicationError> {
         // Execute while we still have pusher updates.
         while let Ok((stream_id, pusher)) = self.rx.try_recv() {
-            self.stream_id_to_pusher.insert(stream_id, pusher);
+            self.add_pusher(stream_id, pusher)?;
         }
+        Ok(())
+    }
+
+    /// Registers the pusher of the stream, and delivers the messages received before it.
+    fn add_pusher(
+        &mut self,
+        stream_id: StreamId,
+        pusher: Box<dyn PusherT>,
+    ) -> Result<(), CommunicationError> {
+        self.stream_id_to_pusher.insert(stream_id, pusher);
+        for (metadata, bytes) in self.unregistered.take(stream_id) {
+            self.deliver(&metadata, bytes)?;
+        }
+        Ok(())
     }
 }
 
@@ -131,17 +320,19 @@ pub(crate) struct ControlReceiver {
     /// The id of the node the stream is receiving data from.
     node_id: NodeId,
     /// Framed TCP read stream.
-    stream: SplitStream<Framed<TcpStream, ControlMessageCodec>>,
+    stream: ControlStream,
     /// Tokio channel sender to `ControlMessageHandler`.
     control_tx: UnboundedSender<ControlMessage>,
     /// Tokio channel receiver from `ControlMessageHandler`.
     control_rx: UnboundedReceiver<ControlMessage>,
+    /// Logs the received control messages.
+    recorder: ControlRecorder,
 }
 
 impl ControlReceiver {
     pub(crate) fn new(
         node_id: NodeId,
-        stream: SplitStream<Framed<TcpStream, ControlMessageCodec>>,
+        stream: ControlStream,
         control_handler: &mut ControlMessageHandler,
     ) -> Self {
         // Set up control channel.
@@ -152,6 +343,7 @@ impl ControlReceiver {
             stream,
             control_tx: control_handler.get_channel_to_handler(),
             control_rx,
+            recorder: control_handler.recorder(),
         }
     }
 
@@ -167,6 +359,8 @@ impl ControlReceiver {
         while let Some(res) = self.stream.next().await {
             match res {
                 Ok(msg) => {
+                    self.recorder
+                        .record(ControlDirection::Received, self.node_id, &msg);
                     self.control_tx
                         .send(msg)
                         .map_err(CommunicationError::from)?;
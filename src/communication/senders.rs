@@ -14,25 +14,31 @@ use tokio::{
 };
 
 #[cfg(feature = "tcp_transport")]
-use crate::communication::{ControlMessageCodec, MessageCodec};
-#[cfg(feature = "tcp_transport")]
-use futures::stream::SplitSink;
+use futures::Sink;
 #[cfg(feature = "tcp_transport")]
 use futures_util::sink::SinkExt;
 #[cfg(feature = "tcp_transport")]
-use tokio::net::TcpStream;
-#[cfg(feature = "tcp_transport")]
-use tokio_util::codec::Framed;
+use std::pin::Pin;
 
 #[cfg(feature = "tcp_transport")]
 use crate::communication::{
-    CommunicationError, ControlMessage, ControlMessageHandler, InterProcessMessage,
+    Chunker, CodecError, CommunicationError, ControlMessage, ControlMessageHandler,
+    InterProcessMessage, SequenceNumbers, SerializationPool, ShapedReceiver, StreamCompressor,
+    TrafficShaper,
 };
 #[cfg(feature = "tcp_transport")]
-use crate::node::NodeId;
+use crate::node::{latency, stream_metrics, NodeId};
 #[cfg(feature = "tcp_transport")]
 use crate::scheduler::endpoints_manager::ChannelsToSenders;
 
+/// Write half of a framed TCP stream, or of the data plane of a multiplexed connection.
+#[cfg(feature = "tcp_transport")]
+pub(crate) type DataSink = Pin<Box<dyn Sink<InterProcessMessage, Error = CodecError> + Send>>;
+
+/// Write half of a framed TCP stream, or of the control plane of a multiplexed connection.
+#[cfg(feature = "tcp_transport")]
+pub(crate) type ControlSink = Pin<Box<dyn Sink<ControlMessage, Error = CodecError> + Send>>;
+
 #[cfg(feature = "tcp_transport")]
 #[allow(dead_code)]
 /// The [`DataSender`] pulls messages from a FIFO inter-thread channel.
@@ -42,22 +48,33 @@ pub(crate) struct DataSender {
     /// The id of the node the sink is sending data to.
     node_id: NodeId,
     /// Framed TCP write sink.
-    sink: SplitSink<Framed<TcpStream, MessageCodec>, InterProcessMessage>,
-    /// Tokio channel receiver on which to receive data from worker threads.
-    rx: UnboundedReceiver<InterProcessMessage>,
+    sink: DataSink,
+    /// Receives the data from worker threads at the rate allowed by the traffic shaper.
+    rx: ShapedReceiver,
     /// Tokio channel sender to `ControlMessageHandler`.
     control_tx: UnboundedSender<ControlMessage>,
     /// Tokio channel receiver from `ControlMessageHandler`.
     control_rx: UnboundedReceiver<ControlMessage>,
+    /// Splits messages larger than the maximum chunk size.
+    chunker: Chunker,
+    /// Compresses the messages of streams with a zstd dictionary.
+    compressor: StreamCompressor,
+    /// Numbers the messages of each stream, so that the receiver detects lost messages.
+    sequences: SequenceNumbers,
+    /// Serializes large messages off the event loop, if set.
+    serialization_pool: Option<SerializationPool>,
 }
 
 #[cfg(feature = "tcp_transport")]
 impl DataSender {
     pub(crate) async fn new(
         node_id: NodeId,
-        sink: SplitSink<Framed<TcpStream, MessageCodec>, InterProcessMessage>,
+        sink: DataSink,
         channels_to_senders: Arc<Mutex<ChannelsToSenders>>,
         control_handler: &mut ControlMessageHandler,
+        max_chunk_size: Option<usize>,
+        compressor: StreamCompressor,
+        shaper: TrafficShaper,
     ) -> Self {
         // Create a channel for this stream.
         let (tx, rx) = mpsc::unbounded_channel();
@@ -69,12 +86,22 @@ impl DataSender {
         Self {
             node_id,
             sink,
-            rx,
+            rx: ShapedReceiver::new(node_id, rx, shaper),
             control_tx: control_handler.get_channel_to_handler(),
             control_rx,
+            chunker: Chunker::new(max_chunk_size),
+            compressor,
+            sequences: SequenceNumbers::default(),
+            serialization_pool: None,
         }
     }
 
+    /// Makes the sender serialize large messages on the threads of the pool.
+    pub(crate) fn with_serialization_pool(mut self, pool: Option<SerializationPool>) -> Self {
+        self.serialization_pool = pool;
+        self
+    }
+
     pub(crate) async fn run(&mut self) -> Result<(), CommunicationError> {
         // Notify [`ControlMessageHandler`] that sender is initialized.
         self.control_tx
@@ -85,8 +112,43 @@ impl DataSender {
         loop {
             match self.rx.recv().await {
                 Some(msg) => {
-                    if let Err(e) = self.sink.send(msg).await.map_err(CommunicationError::from) {
-                        return Err(e);
+                    let msg = self.sequences.number(msg);
+                    let msg = latency::serialize_timed(msg).map_err(CommunicationError::from)?;
+                    let msg = match &self.serialization_pool {
+                        Some(pool) => pool
+                            .serialize(msg)
+                            .await
+                            .map_err(CommunicationError::from)?,
+                        None => msg,
+                    };
+                    let msg =
+                        stream_metrics::message_sent(msg).map_err(CommunicationError::from)?;
+                    let (msg, dictionary) = self
+                        .compressor
+                        .compress(msg)
+                        .map_err(CommunicationError::from)?;
+                    if let Some(dictionary) = dictionary {
+                        // The receiver buffers compressed messages until the dictionary arrives.
+                        self.control_tx
+                            .send(ControlMessage::StreamDictionary(dictionary))
+                            .map_err(CommunicationError::from)?;
+                    }
+                    let chunks = self.chunker.split(msg).map_err(CommunicationError::from)?;
+                    for chunk in chunks {
+                        let cancellation = Chunker::cancellation(&chunk);
+                        if let Err(e) = self
+                            .sink
+                            .send(chunk)
+                            .await
+                            .map_err(CommunicationError::from)
+                        {
+                            // Best effort: the receiver also discards the message if the next
+                            // chunk is missing.
+                            if let Some(cancellation) = cancellation {
+                                let _ = self.sink.send(cancellation).await;
+                            }
+                            return Err(e);
+                        }
                     }
                 }
                 None => return Err(CommunicationError::Disconnected),
@@ -118,7 +180,7 @@ pub(crate) struct ControlSender {
     /// The id of the node the sink is sending data to.
     node_id: NodeId,
     /// Framed TCP write sink.
-    sink: SplitSink<Framed<TcpStream, ControlMessageCodec>, ControlMessage>,
+    sink: ControlSink,
     /// Tokio channel receiver on which to receive data from worker threads.
     rx: UnboundedReceiver<ControlMessage>,
     /// Tokio channel sender to `ControlMessageHandler`.
@@ -131,7 +193,7 @@ pub(crate) struct ControlSender {
 impl ControlSender {
     pub(crate) fn new(
         node_id: NodeId,
-        sink: SplitSink<Framed<TcpStream, ControlMessageCodec>, ControlMessage>,
+        sink: ControlSink,
         control_handler: &mut ControlMessageHandler,
     ) -> Self {
         // Set up channel to other node.
@@ -0,0 +1,104 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::dataflow::stream::StreamId;
+
+use super::{InterProcessMessage, MessageMetadata};
+
+/// Number of messages which the data receivers of the current process detected as lost.
+static LOST_MESSAGES: AtomicU64 = AtomicU64::new(0);
+
+/// Numbers the messages which a data sender sends on each stream, so that the receiving node
+/// detects lost messages.
+///
+/// Messages are numbered when the data sender dequeues them, so that messages replaced on
+/// conflated streams are not reported as lost.
+#[derive(Debug, Default)]
+pub(crate) struct SequenceNumbers {
+    last: HashMap<StreamId, u64>,
+}
+
+impl SequenceNumbers {
+    pub fn number(&mut self, msg: InterProcessMessage) -> InterProcessMessage {
+        let last = self.last.entry(msg.metadata().stream_id).or_insert(0);
+        *last += 1;
+        msg.with_sequence(*last)
+    }
+}
+
+/// Detects the messages lost on a channel from the gaps in the sequence numbers of each stream.
+#[derive(Debug, Default)]
+pub(crate) struct GapDetector {
+    /// The highest sequence number received on each stream.
+    last: HashMap<StreamId, u64>,
+}
+
+impl GapDetector {
+    /// Returns the number of messages lost before the message, which is 0 unless messages are
+    /// missing since the last message of the stream.
+    ///
+    /// The chunks of a message share its sequence number, and messages which are not numbered
+    /// are ignored.
+    pub fn observe(&mut self, metadata: &MessageMetadata) -> u64 {
+        if metadata.sequence == 0 {
+            return 0;
+        }
+        let last = self.last.entry(metadata.stream_id).or_insert(0);
+        if metadata.sequence <= *last {
+            return 0;
+        }
+        let lost = metadata.sequence - *last - 1;
+        *last = metadata.sequence;
+        if lost > 0 {
+            LOST_MESSAGES.fetch_add(lost, Ordering::Relaxed);
+        }
+        lost
+    }
+}
+
+/// Returns the number of messages lost by the data receivers of the current process, exported
+/// as a runtime metric.
+pub(crate) fn lost_messages() -> u64 {
+    LOST_MESSAGES.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that gaps in the sequence numbers of a stream are reported as lost messages.
+    #[test]
+    fn test_gap_detection() {
+        let (stream_id, other_stream_id) = (StreamId::new_v4(), StreamId::new_v4());
+        let mut numbers = SequenceNumbers::default();
+        let mut sent: Vec<MessageMetadata> = [stream_id, stream_id, other_stream_id, stream_id]
+            .iter()
+            .map(|&id| {
+                let msg = InterProcessMessage::new_deserialized(std::sync::Arc::new(()), id);
+                numbers.number(msg).metadata().clone()
+            })
+            .collect();
+        assert_eq!(
+            sent.iter().map(|m| m.sequence).collect::<Vec<_>>(),
+            vec![1, 2, 1, 3]
+        );
+
+        let mut detector = GapDetector::default();
+        let lost_before = lost_messages();
+        assert_eq!(detector.observe(&sent[0]), 0);
+        // The second message of the stream is lost.
+        sent.remove(1);
+        assert_eq!(detector.observe(&sent[1]), 0);
+        assert_eq!(detector.observe(&sent[2]), 1);
+        // Duplicate chunks and messages which are not numbered are ignored.
+        assert_eq!(detector.observe(&sent[2]), 0);
+        let unnumbered = MessageMetadata {
+            sequence: 0,
+            ..sent[2].clone()
+        };
+        assert_eq!(detector.observe(&unnumbered), 0);
+        assert!(lost_messages() >= lost_before + 1);
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    future::Future,
+    io,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use tokio::sync::oneshot;
+
+use crate::communication::{to_serialized_bytes, CodecError, InterProcessMessage};
+
+/// Messages whose data is smaller are serialized by the data sender, because handing them to a
+/// worker costs more than serializing them.
+const MIN_OFFLOADED_SIZE: usize = 16 * 1024;
+
+type Job = (
+    InterProcessMessage,
+    oneshot::Sender<Result<InterProcessMessage, CodecError>>,
+);
+
+/// Threads which serialize the large messages that data senders send to other nodes, so that
+/// serializing them does not block the event loop running the network tasks.
+///
+/// A data sender waits for each message to be serialized before it sends the next message, so
+/// the messages of each stream keep their order.
+#[derive(Clone)]
+pub(crate) struct SerializationPool {
+    jobs: Arc<Mutex<mpsc::Sender<Job>>>,
+}
+
+impl SerializationPool {
+    /// Creates the workers, which stop once the pool and its clones are dropped.
+    pub fn new(num_threads: usize) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let rx = Arc::new(Mutex::new(rx));
+        for i in 0..num_threads.max(1) {
+            let rx = Arc::clone(&rx);
+            thread::Builder::new()
+                .name(format!("erdos-serialization-{}", i))
+                .spawn(move || Self::run_worker(rx))
+                .expect("Unable to spawn a serialization thread");
+        }
+        Self {
+            jobs: Arc::new(Mutex::new(tx)),
+        }
+    }
+
+    fn run_worker(jobs: Arc<Mutex<mpsc::Receiver<Job>>>) {
+        loop {
+            let job = jobs.lock().unwrap().recv();
+            match job {
+                Ok((msg, result_tx)) => {
+                    let _ = result_tx.send(serialize_data(msg));
+                }
+                Err(_) => return,
+            }
+        }
+    }
+
+    /// Returns the message with its data serialized by a worker if the data is large, or the
+    /// message unchanged.
+    pub fn serialize(
+        &self,
+        msg: InterProcessMessage,
+    ) -> impl Future<Output = Result<InterProcessMessage, CodecError>> {
+        let offloaded = match &msg {
+            // Watermark frames do not need a serialized message.
+            InterProcessMessage::Deserialized { metadata, data }
+                if metadata.watermark_frame().is_none() =>
+            {
+                data.serialized_size()
+                    .map_or(true, |size| size >= MIN_OFFLOADED_SIZE)
+            }
+            _ => false,
+        };
+        let result_rx = if offloaded {
+            let (result_tx, result_rx) = oneshot::channel();
+            match self.jobs.lock().unwrap().send((msg, result_tx)) {
+                Ok(()) => Ok(result_rx),
+                Err(mpsc::SendError((msg, _))) => Err(msg),
+            }
+        } else {
+            Err(msg)
+        };
+        async move {
+            match result_rx {
+                Ok(result_rx) => result_rx.await.unwrap_or_else(|_| {
+                    Err(CodecError::from(io::Error::new(
+                        io::ErrorKind::Other,
+                        "A serialization thread stopped",
+                    )))
+                }),
+                Err(msg) => Ok(msg),
+            }
+        }
+    }
+}
+
+fn serialize_data(msg: InterProcessMessage) -> Result<InterProcessMessage, CodecError> {
+    match msg {
+        InterProcessMessage::Deserialized { metadata, data } => {
+            let bytes = to_serialized_bytes(&data.encode_into_vec()?);
+            Ok(InterProcessMessage::new_serialized(bytes, metadata))
+        }
+        msg => Ok(msg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dataflow::{stream::StreamId, Message, Timestamp};
+
+    /// Test that large messages are serialized by the workers, and small messages are not.
+    #[test]
+    fn test_serialization_pool() {
+        let pool = SerializationPool::new(2);
+        let stream_id = StreamId::new_deterministic();
+        let large = Message::new_message(Timestamp::new(vec![1]), vec![7u8; MIN_OFFLOADED_SIZE]);
+        let small = Message::new_message(Timestamp::new(vec![2]), vec![7u8; 8]);
+        let large = InterProcessMessage::new_deserialized(Arc::new(large), stream_id);
+        let small = InterProcessMessage::new_deserialized(Arc::new(small), stream_id);
+
+        let large = futures::executor::block_on(pool.serialize(large)).unwrap();
+        let small = futures::executor::block_on(pool.serialize(small)).unwrap();
+        match large {
+            InterProcessMessage::Serialized { bytes, .. } => {
+                assert!(bytes.len() > MIN_OFFLOADED_SIZE)
+            }
+            InterProcessMessage::Deserialized { .. } => panic!("Expected a serialized message"),
+        }
+        assert!(matches!(small, InterProcessMessage::Deserialized { .. }));
+    }
+}
@@ -0,0 +1,504 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::{communication::InterProcessMessage, dataflow::stream::StreamId, node::NodeId};
+
+/// Duration after which a data sender checks again whether it received credits for a blocking
+/// bounded stream.
+const CREDIT_WAIT: Duration = Duration::from_millis(10);
+
+/// Caps the bandwidth of a stream, or of the data sent to a node, with a token bucket.
+///
+/// Parsed from and formatted as `<bytes per second>` or `<bytes per second>/<burst bytes>`,
+/// e.g. when updating the limit with a runtime knob.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BandwidthLimit {
+    /// Sustained bandwidth in bytes per second.
+    pub bytes_per_sec: u64,
+    /// Number of bytes which can be sent at once after the bandwidth was not used for a while.
+    /// Messages larger than the burst are sent once the bucket is full.
+    pub burst_bytes: u64,
+}
+
+impl BandwidthLimit {
+    /// Creates a limit which allows a burst of one second of bandwidth.
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec,
+            burst_bytes: bytes_per_sec,
+        }
+    }
+
+    pub fn with_burst(mut self, burst_bytes: u64) -> Self {
+        self.burst_bytes = burst_bytes;
+        self
+    }
+}
+
+impl fmt::Display for BandwidthLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.bytes_per_sec, self.burst_bytes)
+    }
+}
+
+impl FromStr for BandwidthLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |value: &str| {
+            value
+                .trim()
+                .parse::<u64>()
+                .map_err(|_| format!("Invalid bandwidth limit {}", s))
+        };
+        match s.find('/') {
+            Some(i) => Ok(Self::new(parse(&s[..i])?).with_burst(parse(&s[i + 1..])?)),
+            None => Ok(Self::new(parse(s)?)),
+        }
+    }
+}
+
+/// Parses the value of a bandwidth knob, where `unlimited` removes the limit.
+pub(crate) fn parse_limit_knob(value: &str) -> Result<Option<BandwidthLimit>, String> {
+    match value {
+        "unlimited" => Ok(None),
+        value => value.parse().map(Some),
+    }
+}
+
+/// Formats the value of a bandwidth knob.
+pub(crate) fn limit_knob_value(limit: Option<BandwidthLimit>) -> String {
+    match limit {
+        Some(limit) => limit.to_string(),
+        None => "unlimited".to_string(),
+    }
+}
+
+/// Configures how the messages of a stream sent to other nodes share the bandwidth.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StreamShaping {
+    /// Caps the bandwidth of the stream summed over all receiving nodes.
+    pub limit: Option<BandwidthLimit>,
+    /// Messages of streams with a higher priority are sent first when the bandwidth to a node
+    /// is exhausted. Streams have priority 0 by default.
+    pub priority: u8,
+}
+
+impl StreamShaping {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn limit(mut self, limit: BandwidthLimit) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+struct TokenBucket {
+    limit: BandwidthLimit,
+    /// Becomes negative when a message larger than the burst is sent.
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: BandwidthLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst_bytes as f64,
+            last_refill: now,
+        }
+    }
+
+    fn set_limit(&mut self, limit: BandwidthLimit) {
+        self.limit = limit;
+        self.tokens = self.tokens.min(limit.burst_bytes as f64);
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.bytes_per_sec as f64)
+            .min(self.limit.burst_bytes as f64);
+        self.last_refill = now;
+    }
+
+    /// Returns how long to wait until the message can be sent.
+    fn wait_time(&self, size: usize) -> Option<Duration> {
+        let required = (size as f64).min(self.limit.burst_bytes as f64);
+        if self.tokens >= required {
+            None
+        } else if self.limit.bytes_per_sec == 0 {
+            // Paused until the limit is raised.
+            Some(Duration::from_secs(1))
+        } else {
+            Some(Duration::from_secs_f64(
+                (required - self.tokens) / self.limit.bytes_per_sec as f64,
+            ))
+        }
+    }
+
+    fn consume(&mut self, size: usize) {
+        self.tokens -= size as f64;
+    }
+}
+
+#[derive(Default)]
+struct ShaperState {
+    stream_buckets: HashMap<StreamId, TokenBucket>,
+    priorities: HashMap<StreamId, u8>,
+    peer_buckets: HashMap<NodeId, TokenBucket>,
+    /// The capacity of each blocking bounded stream, which is the number of credits each
+    /// receiving node initially grants.
+    credit_streams: HashMap<StreamId, u64>,
+    /// The credits left to send messages with data on the blocking bounded streams to each node.
+    credits: HashMap<(NodeId, StreamId), u64>,
+}
+
+impl ShaperState {
+    fn is_active(&self) -> bool {
+        !(self.stream_buckets.is_empty()
+            && self.priorities.is_empty()
+            && self.peer_buckets.is_empty()
+            && self.credit_streams.is_empty())
+    }
+
+    /// Returns the credits left to send on the stream to the node, if the stream is blocking.
+    fn credits(&mut self, node_id: NodeId, stream_id: StreamId) -> Option<&mut u64> {
+        let capacity = *self.credit_streams.get(&stream_id)?;
+        Some(self.credits.entry((node_id, stream_id)).or_insert(capacity))
+    }
+}
+
+/// Why a message cannot be sent yet.
+enum Throttled {
+    Stream(Duration),
+    Peer(Duration),
+}
+
+/// Shares the bandwidth of the data senders of a node between the streams and the receiving
+/// nodes, with per-stream and per-node token buckets and per-stream priorities.
+///
+/// Limits can be changed while the node runs, and apply to the next messages sent.
+#[derive(Clone, Default)]
+pub(crate) struct TrafficShaper {
+    state: Arc<Mutex<ShaperState>>,
+}
+
+impl TrafficShaper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_stream_limit(&self, stream_id: StreamId, limit: Option<BandwidthLimit>) {
+        let mut state = self.state.lock().unwrap();
+        match limit {
+            Some(limit) => match state.stream_buckets.get_mut(&stream_id) {
+                Some(bucket) => bucket.set_limit(limit),
+                None => {
+                    state
+                        .stream_buckets
+                        .insert(stream_id, TokenBucket::new(limit, Instant::now()));
+                }
+            },
+            None => {
+                state.stream_buckets.remove(&stream_id);
+            }
+        }
+    }
+
+    pub fn set_stream_priority(&self, stream_id: StreamId, priority: u8) {
+        self.state
+            .lock()
+            .unwrap()
+            .priorities
+            .insert(stream_id, priority);
+    }
+
+    pub fn set_peer_limit(&self, node_id: NodeId, limit: Option<BandwidthLimit>) {
+        let mut state = self.state.lock().unwrap();
+        match limit {
+            Some(limit) => match state.peer_buckets.get_mut(&node_id) {
+                Some(bucket) => bucket.set_limit(limit),
+                None => {
+                    state
+                        .peer_buckets
+                        .insert(node_id, TokenBucket::new(limit, Instant::now()));
+                }
+            },
+            None => {
+                state.peer_buckets.remove(&node_id);
+            }
+        }
+    }
+
+    /// Makes the messages with data sent on the stream to each node consume a credit granted by
+    /// the node, which initially grants `capacity` credits.
+    pub fn set_stream_credits(&self, stream_id: StreamId, capacity: u64) {
+        self.state
+            .lock()
+            .unwrap()
+            .credit_streams
+            .insert(stream_id, capacity);
+    }
+
+    /// Adds the credits granted by the node to send on the stream.
+    pub fn add_credits(&self, node_id: NodeId, stream_id: StreamId, credits: u64) {
+        if let Some(left) = self.state.lock().unwrap().credits(node_id, stream_id) {
+            *left += credits;
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.state.lock().unwrap().is_active()
+    }
+
+    /// Consumes the bandwidth for a message of the size sent on the stream to the node, if the
+    /// limits of both the stream and the node allow it, and a credit if the message carries data
+    /// on a blocking bounded stream.
+    fn try_acquire(
+        state: &mut ShaperState,
+        node_id: NodeId,
+        stream_id: StreamId,
+        size: usize,
+        is_data: bool,
+        now: Instant,
+    ) -> Result<(), Throttled> {
+        if is_data && matches!(state.credits(node_id, stream_id), Some(&mut 0)) {
+            return Err(Throttled::Stream(CREDIT_WAIT));
+        }
+        if let Some(bucket) = state.peer_buckets.get_mut(&node_id) {
+            bucket.refill(now);
+            if let Some(wait) = bucket.wait_time(size) {
+                return Err(Throttled::Peer(wait));
+            }
+        }
+        if let Some(bucket) = state.stream_buckets.get_mut(&stream_id) {
+            bucket.refill(now);
+            if let Some(wait) = bucket.wait_time(size) {
+                return Err(Throttled::Stream(wait));
+            }
+            bucket.consume(size);
+        }
+        if let Some(bucket) = state.peer_buckets.get_mut(&node_id) {
+            bucket.consume(size);
+        }
+        if is_data {
+            if let Some(left) = state.credits(node_id, stream_id) {
+                *left -= 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Selects the next message to send to the node among the pending messages.
+    ///
+    /// The oldest message of each stream is considered, from the highest to the lowest
+    /// priority. Streams which exceed their own limit are skipped, but no message overtakes a
+    /// higher-priority message which waits for the bandwidth of the node. Returns how long to
+    /// wait if no message can be sent.
+    fn select(
+        &self,
+        node_id: NodeId,
+        pending: &VecDeque<InterProcessMessage>,
+        now: Instant,
+    ) -> Result<usize, Duration> {
+        let mut state = self.state.lock().unwrap();
+        let mut seen = HashSet::new();
+        let mut candidates: Vec<(Reverse<u8>, usize)> = pending
+            .iter()
+            .enumerate()
+            .filter(|(_, msg)| seen.insert(msg.metadata().stream_id))
+            .map(|(i, msg)| {
+                let priority = state
+                    .priorities
+                    .get(&msg.metadata().stream_id)
+                    .copied()
+                    .unwrap_or(0);
+                (Reverse(priority), i)
+            })
+            .collect();
+        candidates.sort();
+
+        let mut wait = Duration::from_secs(1);
+        for (_, i) in candidates {
+            let msg = &pending[i];
+            // Messages whose size is unknown fail to serialize later on, so they are not throttled.
+            let size = msg.data_size().unwrap_or(0);
+            let metadata = msg.metadata();
+            let is_data = metadata.watermark.is_none();
+            match Self::try_acquire(&mut state, node_id, metadata.stream_id, size, is_data, now) {
+                Ok(()) => return Ok(i),
+                Err(Throttled::Stream(stream_wait)) => wait = wait.min(stream_wait),
+                Err(Throttled::Peer(peer_wait)) => return Err(wait.min(peer_wait)),
+            }
+        }
+        Err(wait)
+    }
+}
+
+/// Receives the messages a data sender sends to a node in the order and at the rate allowed by
+/// the [`TrafficShaper`].
+///
+/// Messages queue up while the data sender waits for the bandwidth or for the connection. A
+/// queued message of a conflated stream is replaced by a newer message with the same
+/// conflation key, which bounds the latency and the memory of state-like streams.
+pub(crate) struct ShapedReceiver {
+    node_id: NodeId,
+    rx: UnboundedReceiver<InterProcessMessage>,
+    shaper: TrafficShaper,
+    /// Messages received from the operators which were not sent yet.
+    pending: VecDeque<InterProcessMessage>,
+    closed: bool,
+}
+
+impl ShapedReceiver {
+    pub fn new(
+        node_id: NodeId,
+        rx: UnboundedReceiver<InterProcessMessage>,
+        shaper: TrafficShaper,
+    ) -> Self {
+        Self {
+            node_id,
+            rx,
+            shaper,
+            pending: VecDeque::new(),
+            closed: false,
+        }
+    }
+
+    /// Returns the next message to send, or `None` once all senders were dropped and all
+    /// messages were sent.
+    pub async fn recv(&mut self) -> Option<InterProcessMessage> {
+        loop {
+            // Collect the queued messages so that higher-priority messages can overtake them.
+            while let Ok(msg) = self.rx.try_recv() {
+                self.enqueue(msg);
+            }
+            if self.pending.is_empty() {
+                if self.closed {
+                    return None;
+                }
+                match self.rx.recv().await {
+                    // Avoid computing the size of the messages if no limits are set.
+                    Some(msg) if !self.shaper.is_active() => return Some(msg),
+                    Some(msg) => self.enqueue(msg),
+                    None => return None,
+                }
+                continue;
+            }
+            if !self.shaper.is_active() {
+                return self.pending.pop_front();
+            }
+            match self
+                .shaper
+                .select(self.node_id, &self.pending, Instant::now())
+            {
+                Ok(i) => return self.pending.remove(i),
+                Err(wait) => {
+                    let closed = self.closed;
+                    tokio::select! {
+                        msg = self.rx.recv(), if !closed => match msg {
+                            Some(msg) => self.enqueue(msg),
+                            None => self.closed = true,
+                        },
+                        _ = tokio::time::delay_for(wait) => (),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Queues the message, and removes the queued message it replaces if its stream is
+    /// conflated.
+    fn enqueue(&mut self, msg: InterProcessMessage) {
+        let metadata = msg.metadata();
+        if let Some(key) = metadata.conflation_key {
+            let stream_id = metadata.stream_id;
+            // Removing the older message instead of replacing it in place keeps the newer
+            // message behind the watermarks sent in between.
+            self.pending.retain(|queued| {
+                queued.metadata().stream_id != stream_id
+                    || queued.metadata().conflation_key != Some(key)
+            });
+        }
+        self.pending.push_back(msg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::dataflow::{Message, Timestamp};
+
+    /// Test that the bucket allows a burst, and then throttles messages to the bandwidth.
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(BandwidthLimit::new(1000).with_burst(500), start);
+        assert_eq!(bucket.wait_time(500), None);
+        bucket.consume(500);
+        assert_eq!(bucket.wait_time(100), Some(Duration::from_millis(100)));
+        bucket.refill(start + Duration::from_millis(100));
+        assert_eq!(bucket.wait_time(100), None);
+
+        // Messages larger than the burst are sent once the bucket is full.
+        bucket.refill(start + Duration::from_secs(10));
+        assert_eq!(bucket.wait_time(2000), None);
+        bucket.consume(2000);
+        assert!(bucket.wait_time(1).unwrap() > Duration::from_millis(1500));
+
+        assert_eq!(
+            "1000/500".parse::<BandwidthLimit>(),
+            Ok(BandwidthLimit::new(1000).with_burst(500))
+        );
+        assert_eq!(parse_limit_knob("unlimited"), Ok(None));
+    }
+
+    /// Test that queued messages of a conflated stream are replaced by the newest message with
+    /// the same key.
+    #[test]
+    fn test_conflation() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut receiver = ShapedReceiver::new(1, rx, TrafficShaper::new());
+        let stream_id = StreamId::new_deterministic();
+        let send = |msg: Message<u32>, key: Option<u64>| {
+            tx.send(
+                InterProcessMessage::new_deserialized(Arc::new(msg), stream_id)
+                    .with_conflation_key(key),
+            )
+            .unwrap();
+        };
+        send(Message::new_message(Timestamp::new(vec![1]), 1), Some(1));
+        send(Message::new_message(Timestamp::new(vec![1]), 2), Some(2));
+        send(Message::new_message(Timestamp::new(vec![2]), 1), Some(1));
+        send(Message::new_watermark(Timestamp::new(vec![2])), None);
+        send(Message::new_message(Timestamp::new(vec![3]), 1), Some(1));
+        drop(send);
+        drop(tx);
+
+        let mut keys = Vec::new();
+        while let Some(msg) = block_on(receiver.recv()) {
+            keys.push(msg.metadata().conflation_key);
+        }
+        assert_eq!(keys, vec![Some(2), None, Some(1)]);
+    }
+}
@@ -0,0 +1,177 @@
+use async_trait::async_trait;
+use futures::{channel::mpsc, Sink, Stream};
+use futures_util::{sink::SinkExt, stream::StreamExt};
+use std::{io, pin::Pin};
+
+#[cfg(feature = "uds_transport")]
+use tokio::net::UnixStream;
+#[cfg(feature = "tcp_transport")]
+use tokio::net::{TcpListener, TcpStream};
+#[cfg(feature = "tcp_transport")]
+use tokio_util::codec::Framed;
+
+#[cfg(feature = "tcp_transport")]
+use crate::communication::MessageCodec;
+use crate::communication::{CodecError, InterProcessMessage};
+
+/// Half of a link on which messages are sent to the other end of the link.
+pub type MessageSink = Pin<Box<dyn Sink<InterProcessMessage, Error = CodecError> + Send>>;
+
+/// Half of a link on which the messages sent by the other end of the link are received.
+pub type MessageStream =
+    Pin<Box<dyn Stream<Item = Result<InterProcessMessage, CodecError>> + Send>>;
+
+/// One end of a bidirectional link between two nodes, as created by a [`Transport`].
+pub struct TransportLink {
+    /// Sends messages to the other end of the link.
+    pub sink: MessageSink,
+    /// Receives the messages sent by the other end of the link. Ends once the other end closes
+    /// its sink, or drops the link.
+    pub stream: MessageStream,
+}
+
+/// A transport which links pairs of nodes, and carries the serialized messages of the data
+/// plane between them.
+///
+/// The sink and the stream of a [`TransportLink`] are the halves which the data senders and
+/// receivers of the TCP transport use, so new transports can be checked for correctness with
+/// the [`conformance`](crate::communication::conformance) suite before they are used by nodes.
+/// The Zenoh transports publish on Zenoh sessions instead, and are not [`Transport`]s.
+#[async_trait]
+pub trait Transport: Send {
+    /// Links two endpoints, and returns the end of the link of each endpoint.
+    ///
+    /// Messages sent on the sink of an end must be received in order, and unmodified, on the
+    /// stream of the other end.
+    async fn connect(&mut self) -> Result<(TransportLink, TransportLink), CodecError>;
+}
+
+/// A [`Transport`] which links endpoints with in-memory channels.
+///
+/// Messages, including their metadata, are moved to the other end of the link without being
+/// encoded, so the mock behaves as an ideal transport against which others are compared.
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    num_connections: usize,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of links created by the transport.
+    pub fn num_connections(&self) -> usize {
+        self.num_connections
+    }
+
+    fn link(
+        tx: mpsc::UnboundedSender<InterProcessMessage>,
+        rx: mpsc::UnboundedReceiver<InterProcessMessage>,
+    ) -> TransportLink {
+        TransportLink {
+            sink: Box::pin(
+                tx.sink_map_err(|e| CodecError::from(io::Error::new(io::ErrorKind::BrokenPipe, e))),
+            ),
+            stream: Box::pin(rx.map(Ok)),
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for MockTransport {
+    async fn connect(&mut self) -> Result<(TransportLink, TransportLink), CodecError> {
+        self.num_connections += 1;
+        let (first_tx, first_rx) = mpsc::unbounded();
+        let (second_tx, second_rx) = mpsc::unbounded();
+        Ok((
+            Self::link(second_tx, first_rx),
+            Self::link(first_tx, second_rx),
+        ))
+    }
+}
+
+/// A [`Transport`] which links endpoints with TCP connections on the loopback interface,
+/// framed with the codec of the messages which nodes send to each other.
+#[cfg(feature = "tcp_transport")]
+#[derive(Debug, Default)]
+pub struct TcpTransport {
+    compact_headers: bool,
+}
+
+#[cfg(feature = "tcp_transport")]
+impl TcpTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes both ends of the links write compact headers, as nodes do if
+    /// [`Configuration::compact_headers`](crate::Configuration::compact_headers) is set.
+    pub fn with_compact_headers(mut self, compact_headers: bool) -> Self {
+        self.compact_headers = compact_headers;
+        self
+    }
+
+    fn link(&self, stream: TcpStream) -> Result<TransportLink, CodecError> {
+        stream.set_nodelay(true)?;
+        let codec = MessageCodec::new().with_compact_headers(self.compact_headers);
+        let (sink, stream) = Framed::new(stream, codec).split();
+        Ok(TransportLink {
+            sink: Box::pin(sink),
+            stream: Box::pin(stream),
+        })
+    }
+}
+
+#[cfg(feature = "tcp_transport")]
+#[async_trait]
+impl Transport for TcpTransport {
+    async fn connect(&mut self) -> Result<(TransportLink, TransportLink), CodecError> {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let (connected, accepted) = futures::join!(TcpStream::connect(addr), listener.accept());
+        let (accepted, _) = accepted?;
+        Ok((self.link(connected?)?, self.link(accepted)?))
+    }
+}
+
+/// A [`Transport`] which links endpoints with Unix domain sockets, framed with the codec of the
+/// messages which nodes send to each other, as nodes on the same host do with the
+/// `uds_transport` feature.
+#[cfg(feature = "uds_transport")]
+#[derive(Debug, Default)]
+pub struct UnixTransport {
+    compact_headers: bool,
+}
+
+#[cfg(feature = "uds_transport")]
+impl UnixTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes both ends of the links write compact headers, as nodes do if
+    /// [`Configuration::compact_headers`](crate::Configuration::compact_headers) is set.
+    pub fn with_compact_headers(mut self, compact_headers: bool) -> Self {
+        self.compact_headers = compact_headers;
+        self
+    }
+
+    fn link(&self, stream: UnixStream) -> TransportLink {
+        let codec = MessageCodec::new().with_compact_headers(self.compact_headers);
+        let (sink, stream) = Framed::new(stream, codec).split();
+        TransportLink {
+            sink: Box::pin(sink),
+            stream: Box::pin(stream),
+        }
+    }
+}
+
+#[cfg(feature = "uds_transport")]
+#[async_trait]
+impl Transport for UnixTransport {
+    async fn connect(&mut self) -> Result<(TransportLink, TransportLink), CodecError> {
+        let (first, second) = UnixStream::pair()?;
+        Ok((self.link(first), self.link(second)))
+    }
+}
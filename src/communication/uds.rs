@@ -0,0 +1,200 @@
+use std::{
+    fs, io,
+    os::unix::fs::FileTypeExt,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use byteorder::{NetworkEndian, WriteBytesExt};
+use futures::{
+    future,
+    stream::{FuturesUnordered, StreamExt},
+};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{UnixListener, UnixStream},
+    time::delay_for,
+};
+
+use crate::{
+    communication::{authentication, Authenticator},
+    node::NodeId,
+};
+
+/// Returns a Unix domain socket stream to each peer of the node, like
+/// [`create_tcp_streams_to_peers`](super::create_tcp_streams_to_peers) does with TCP streams.
+///
+/// The paths are the sockets on which the nodes listen, indexed by node id. The node removes the
+/// socket left at its path by a previous process before it listens.
+pub(crate) async fn create_unix_streams_to_peers(
+    paths: &[PathBuf],
+    node_id: NodeId,
+    peers: &[NodeId],
+    authenticator: Option<&dyn Authenticator>,
+    logger: &slog::Logger,
+) -> Vec<(NodeId, UnixStream)> {
+    // Connect to the peers that have a lower id than the node.
+    let connect_streams_fut = future::try_join_all(peers.iter().filter(|&&id| id < node_id).map(
+        |&id| async move {
+            let stream = connect_to_path(&paths[id], node_id, id, authenticator, logger).await?;
+            Ok::<_, io::Error>((id, stream))
+        },
+    ));
+    // Wait for connections from the peers that have a higher id than the node.
+    let num_higher_peers = peers.iter().filter(|&&id| id > node_id).count();
+    let stream_fut = await_path_connections(
+        &paths[node_id],
+        node_id,
+        num_higher_peers,
+        authenticator,
+        logger,
+    );
+    match future::try_join(connect_streams_fut, stream_fut).await {
+        Ok((mut streams, await_streams)) => {
+            streams.extend(await_streams);
+            streams
+        }
+        Err(e) => {
+            slog::error!(
+                logger,
+                "Node {}: creating Unix domain socket streams errored with {:?}",
+                node_id,
+                e
+            );
+            panic!(
+                "Node {}: creating Unix domain socket streams errored with {:?}",
+                node_id, e
+            )
+        }
+    }
+}
+
+/// Connects to the socket at the path, retrying until it succeeds, and writes the node id on the
+/// stream. Then authenticates the node and the node `peer_id` to each other if an authenticator
+/// is passed.
+async fn connect_to_path(
+    path: &Path,
+    node_id: NodeId,
+    peer_id: NodeId,
+    authenticator: Option<&dyn Authenticator>,
+    logger: &slog::Logger,
+) -> io::Result<UnixStream> {
+    let mut last_err_msg_time = Instant::now();
+    loop {
+        match UnixStream::connect(path).await {
+            Ok(mut stream) => {
+                let mut buffer: Vec<u8> = Vec::new();
+                WriteBytesExt::write_u32::<NetworkEndian>(&mut buffer, node_id as u32)?;
+                stream.write_all(&buffer[..]).await?;
+                if let Some(authenticator) = authenticator {
+                    authentication::connect_handshake(&mut stream, node_id, peer_id, authenticator)
+                        .await?;
+                }
+                return Ok(stream);
+            }
+            Err(e) => {
+                // Only print connection errors every 1s.
+                let now = Instant::now();
+                if now.duration_since(last_err_msg_time) >= Duration::from_secs(1) {
+                    slog::error!(
+                        logger,
+                        "Node {}: could not connect to {}; error {}; retrying",
+                        node_id,
+                        path.display(),
+                        e
+                    );
+                    last_err_msg_time = now;
+                }
+                delay_for(Duration::from_millis(100)).await;
+            }
+        }
+    }
+}
+
+/// Listens on the path for connections from `expected_conns` other nodes, like
+/// [`await_node_connections`](super::await_node_connections) does on a socket address.
+async fn await_path_connections(
+    path: &Path,
+    node_id: NodeId,
+    expected_conns: usize,
+    authenticator: Option<&dyn Authenticator>,
+    logger: &slog::Logger,
+) -> io::Result<Vec<(NodeId, UnixStream)>> {
+    let mut await_futures = FuturesUnordered::new();
+    let mut streams = Vec::with_capacity(expected_conns);
+    remove_stale_socket(path)?;
+    let mut listener = UnixListener::bind(path)?;
+    while streams.len() < expected_conns {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted?;
+                await_futures.push(super::accept_node(stream, node_id, authenticator, logger));
+            }
+            Some(result) = await_futures.next(), if !await_futures.is_empty() => match result {
+                Ok(stream) => streams.push(stream),
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                    slog::warn!(logger, "Rejecting connection to {}: {}", path.display(), e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Ok(streams)
+}
+
+/// Removes the socket at the path, which a crashed process may have left. Fails if the path is
+/// not a socket, so that other files are not removed.
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path),
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        )),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the nodes connect to each other with one stream per peer, and that the socket
+    /// left by a previous process is replaced.
+    #[test]
+    fn test_create_unix_streams() {
+        let directory = std::env::temp_dir().join(format!("erdos-uds-{}", crate::Uuid::new_v4()));
+        fs::create_dir_all(&directory).unwrap();
+        let paths: Vec<PathBuf> = (0..3)
+            .map(|id| directory.join(format!("node-{}.sock", id)))
+            .collect();
+        // A socket left by a crashed process.
+        drop(std::os::unix::net::UnixListener::bind(&paths[2]).unwrap());
+
+        let logger = crate::get_terminal_logger();
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let all_streams = runtime.block_on(future::join_all((0..3).map(|node_id| {
+            let peers: Vec<NodeId> = (0..3).filter(|&id| id != node_id).collect();
+            let paths = &paths;
+            let logger = &logger;
+            async move {
+                create_unix_streams_to_peers(paths, node_id, &peers, None, logger)
+                    .await
+                    .into_iter()
+                    .map(|(peer, _)| peer)
+                    .collect::<Vec<_>>()
+            }
+        })));
+        for (node_id, mut peers) in all_streams.into_iter().enumerate() {
+            peers.sort_unstable();
+            let expected: Vec<NodeId> = (0..3).filter(|&id| id != node_id).collect();
+            assert_eq!(peers, expected);
+        }
+
+        let file = directory.join("not-a-socket");
+        fs::write(&file, b"data").unwrap();
+        assert!(remove_stale_socket(&file).is_err());
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::dataflow::stream::StreamId;
+
+use super::MessageMetadata;
+
+/// Maximum number of messages buffered per stream until an operator reads from the stream.
+pub(crate) const MAX_UNREGISTERED_MESSAGES: usize = 1024;
+
+/// Buffers the messages which data receivers receive before the pusher of their stream is
+/// registered, e.g. because the other node started sending before the operators of the current
+/// node were set up.
+#[derive(Debug)]
+pub(crate) struct UnregisteredMessages<B> {
+    messages: HashMap<StreamId, VecDeque<(MessageMetadata, B)>>,
+}
+
+impl<B> Default for UnregisteredMessages<B> {
+    fn default() -> Self {
+        Self {
+            messages: HashMap::new(),
+        }
+    }
+}
+
+impl<B> UnregisteredMessages<B> {
+    /// Buffers the message until the pusher of its stream is registered.
+    ///
+    /// Returns the oldest message of the stream if more than [`MAX_UNREGISTERED_MESSAGES`] are
+    /// buffered, which is dropped as no operator reads from the stream.
+    pub fn push(&mut self, metadata: MessageMetadata, bytes: B) -> Option<(MessageMetadata, B)> {
+        let messages = self.messages.entry(metadata.stream_id).or_default();
+        messages.push_back((metadata, bytes));
+        if messages.len() > MAX_UNREGISTERED_MESSAGES {
+            messages.pop_front()
+        } else {
+            None
+        }
+    }
+
+    /// Removes the messages of the stream, in the order in which they were received.
+    pub fn take(&mut self, stream_id: StreamId) -> VecDeque<(MessageMetadata, B)> {
+        self.messages.remove(&stream_id).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::communication::InterProcessMessage;
+
+    fn make_metadata(stream_id: StreamId) -> MessageMetadata {
+        InterProcessMessage::new_deserialized(Arc::new(()), stream_id)
+            .metadata()
+            .clone()
+    }
+
+    /// Test that messages are buffered per stream in order, and that the oldest messages are
+    /// returned once a stream buffers too many messages.
+    #[test]
+    fn test_unregistered_messages() {
+        let (stream_id, other_stream_id) = (StreamId::new_v4(), StreamId::new_v4());
+        let mut unregistered = UnregisteredMessages::default();
+        for i in 0..MAX_UNREGISTERED_MESSAGES {
+            assert!(unregistered.push(make_metadata(stream_id), i).is_none());
+        }
+        assert!(unregistered
+            .push(make_metadata(other_stream_id), 0)
+            .is_none());
+        let (_, oldest) = unregistered
+            .push(make_metadata(stream_id), MAX_UNREGISTERED_MESSAGES)
+            .unwrap();
+        assert_eq!(oldest, 0);
+
+        let messages: Vec<usize> = unregistered
+            .take(stream_id)
+            .into_iter()
+            .map(|(_, i)| i)
+            .collect();
+        assert_eq!(
+            messages,
+            (1..=MAX_UNREGISTERED_MESSAGES).collect::<Vec<_>>()
+        );
+        assert!(unregistered.take(stream_id).is_empty());
+        assert_eq!(unregistered.take(other_stream_id).len(), 1);
+    }
+}
@@ -0,0 +1,481 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    io,
+    os::unix::io::{AsRawFd, RawFd},
+    pin::Pin,
+    sync::{
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::{ready, Sink, Stream};
+use io_uring::{opcode, squeue, types, IoUring};
+use lazy_static::lazy_static;
+use tokio::{net::TcpStream, sync::oneshot};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::communication::{CodecError, InterProcessMessage, MessageCodec};
+
+/// Number of entries of the submission queue of the ring.
+const RING_ENTRIES: u32 = 256;
+/// Number of bytes for which room is reserved in the read buffer before each read.
+const READ_CAPACITY: usize = 64 * 1024;
+/// Number of encoded bytes after which the sink flushes before accepting more messages.
+const BACKPRESSURE_BOUNDARY: usize = 64 * 1024;
+/// User data of the read of the eventfd which wakes up the driver upon new requests.
+const WAKE_UP: u64 = 0;
+/// User data of the polls which are linked to the reads and writes, whose completions are
+/// ignored.
+const LINKED_POLL: u64 = 1;
+
+lazy_static! {
+    /// The driver shared by the data streams of the process, or `None` if the kernel does not
+    /// support io_uring.
+    static ref DRIVER: Option<Arc<UringDriver>> = match UringDriver::start() {
+        Ok(driver) => Some(driver),
+        Err(e) => {
+            slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "io_uring is unavailable, sending data with epoll: {:?}",
+                e
+            );
+            None
+        }
+    };
+}
+
+/// The buffer of a read or a write, which is a buffer of the codec. The request holds it until
+/// the operation completes, so that the kernel reads into and writes from the codec's memory
+/// without copies.
+enum Buffer {
+    /// Bytes are read into the spare capacity of the buffer.
+    Read(BytesMut),
+    Write(Bytes),
+}
+
+impl Buffer {
+    /// Builds the entry which reads into the spare capacity of the buffer, or writes the buffer.
+    fn entry(&mut self, fd: RawFd) -> squeue::Entry {
+        let fd = types::Fd(fd);
+        match self {
+            Buffer::Read(buf) => {
+                let spare = buf.bytes_mut();
+                opcode::Read::new(fd, spare.as_mut_ptr() as *mut u8, spare.len() as u32).build()
+            }
+            Buffer::Write(bytes) => {
+                opcode::Write::new(fd, bytes.as_ptr(), bytes.len() as u32).build()
+            }
+        }
+    }
+
+    /// Returns the events once which the socket is ready for the operation.
+    fn poll_events(&self) -> u32 {
+        match self {
+            Buffer::Read(_) => libc::POLLIN as u32,
+            Buffer::Write(_) => libc::POLLOUT as u32,
+        }
+    }
+}
+
+/// The result of a read or a write, which is the number of bytes transferred or a negated
+/// `errno`, with its buffer.
+type Completion = (i32, Buffer);
+
+enum Request {
+    Io {
+        fd: RawFd,
+        buffer: Buffer,
+        reply: oneshot::Sender<Completion>,
+    },
+    /// Closes the socket once its operations in flight complete.
+    Close(RawFd),
+}
+
+/// Submits the reads and writes of the data streams to an io_uring on a dedicated thread, and
+/// owns their sockets, which it closes once no operation on them is in flight.
+pub(crate) struct UringDriver {
+    requests: Mutex<Sender<Request>>,
+    /// Wakes up the driver while it waits for completions.
+    eventfd: RawFd,
+}
+
+impl UringDriver {
+    fn start() -> io::Result<Arc<Self>> {
+        let ring = IoUring::new(RING_ENTRIES)?;
+        let eventfd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+        if eventfd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let (tx, rx) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("erdos-io-uring".to_string())
+            .spawn(move || {
+                if let Err(e) = Self::run(ring, rx, eventfd) {
+                    slog::error!(crate::TERMINAL_LOGGER, "io_uring driver failed: {:?}", e);
+                }
+            })?;
+        Ok(Arc::new(Self {
+            requests: Mutex::new(tx),
+            eventfd,
+        }))
+    }
+
+    fn submit(&self, fd: RawFd, buffer: Buffer) -> oneshot::Receiver<Completion> {
+        let (reply, rx) = oneshot::channel();
+        // The reply is dropped, which fails the operation, if the driver stopped.
+        self.send(Request::Io { fd, buffer, reply });
+        rx
+    }
+
+    /// Closes the socket after the operations submitted before.
+    fn close(&self, fd: RawFd) {
+        self.send(Request::Close(fd));
+    }
+
+    fn send(&self, request: Request) {
+        if self.requests.lock().unwrap().send(request).is_ok() {
+            let wake_up: u64 = 1;
+            unsafe {
+                libc::write(
+                    self.eventfd,
+                    &wake_up as *const u64 as *const libc::c_void,
+                    8,
+                )
+            };
+        }
+    }
+
+    fn run(mut ring: IoUring, rx: Receiver<Request>, eventfd: RawFd) -> io::Result<()> {
+        let mut in_flight: HashMap<u64, (RawFd, Buffer, oneshot::Sender<Completion>)> =
+            HashMap::new();
+        // The number of operations in flight on each socket, and whether the socket is closed
+        // once they complete.
+        let mut sockets: HashMap<RawFd, (usize, bool)> = HashMap::new();
+        let mut next_id = LINKED_POLL + 1;
+        let mut wake_up_buf = [0u8; 8];
+        let mut wake_up_armed = false;
+        loop {
+            if !wake_up_armed {
+                let entry = opcode::Read::new(types::Fd(eventfd), wake_up_buf.as_mut_ptr(), 8)
+                    .build()
+                    .user_data(WAKE_UP);
+                Self::push(&mut ring, &[entry])?;
+                wake_up_armed = true;
+            }
+            loop {
+                match rx.try_recv() {
+                    Ok(Request::Io {
+                        fd,
+                        mut buffer,
+                        reply,
+                    }) => {
+                        // The socket is non-blocking, so the operation waits for the socket to be
+                        // ready instead of failing with `EAGAIN`.
+                        let poll = opcode::PollAdd::new(types::Fd(fd), buffer.poll_events())
+                            .build()
+                            .flags(squeue::Flags::IO_LINK)
+                            .user_data(LINKED_POLL);
+                        let entry = buffer.entry(fd).user_data(next_id);
+                        Self::push(&mut ring, &[poll, entry])?;
+                        in_flight.insert(next_id, (fd, buffer, reply));
+                        sockets.entry(fd).or_default().0 += 1;
+                        next_id += 1;
+                    }
+                    Ok(Request::Close(fd)) => match sockets.get_mut(&fd) {
+                        Some((_, closing)) => *closing = true,
+                        None => unsafe {
+                            libc::close(fd);
+                        },
+                    },
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                }
+            }
+            match ring.submit_and_wait(1) {
+                Ok(_) => (),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+            let completions: Vec<(u64, i32)> = ring
+                .completion()
+                .map(|cqe| (cqe.user_data(), cqe.result()))
+                .collect();
+            for (id, result) in completions {
+                match id {
+                    WAKE_UP => wake_up_armed = false,
+                    LINKED_POLL => (),
+                    _ => {
+                        if let Some((fd, buffer, reply)) = in_flight.remove(&id) {
+                            // The stream may have been dropped.
+                            let _ = reply.send((result, buffer));
+                            Self::complete(&mut sockets, fd);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Records that an operation on the socket completed, and closes the socket if it was the
+    /// last operation in flight on a dropped stream.
+    fn complete(sockets: &mut HashMap<RawFd, (usize, bool)>, fd: RawFd) {
+        if let Some((num_in_flight, closing)) = sockets.get_mut(&fd) {
+            *num_in_flight -= 1;
+            if *num_in_flight == 0 {
+                if *closing {
+                    unsafe { libc::close(fd) };
+                }
+                sockets.remove(&fd);
+            }
+        }
+    }
+
+    /// Pushes the entries, which are linked if more than one, in the same submission.
+    fn push(ring: &mut IoUring, entries: &[squeue::Entry]) -> io::Result<()> {
+        while ring.submission().capacity() - ring.submission().len() < entries.len() {
+            ring.submit()?;
+        }
+        for entry in entries {
+            // The buffers of the entries live in the requests in flight.
+            unsafe { ring.submission().push(entry) }.map_err(|_| {
+                io::Error::new(io::ErrorKind::Other, "The submission queue is full")
+            })?;
+        }
+        Ok(())
+    }
+}
+
+fn driver_stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "The io_uring driver stopped")
+}
+
+/// A TCP stream framed by the message codec, whose reads and writes are submitted to io_uring.
+///
+/// The ring reads into the buffer which the codec decodes, and writes the frames the codec
+/// encodes, so the bytes are not copied between the codec and the ring.
+pub(crate) struct UringFramed {
+    fd: RawFd,
+    driver: Arc<UringDriver>,
+    codec: MessageCodec,
+    /// The bytes read which the codec did not decode yet, or `None` while a read is in flight.
+    read_buf: Option<BytesMut>,
+    read: Option<oneshot::Receiver<Completion>>,
+    /// Whether the other side closed the stream.
+    eof: bool,
+    /// The frames encoded since the last write was submitted.
+    write_buf: BytesMut,
+    write: Option<oneshot::Receiver<Completion>>,
+}
+
+impl UringFramed {
+    /// Moves the stream to io_uring, or returns it with the codec if io_uring is unavailable.
+    pub(crate) fn new(
+        stream: TcpStream,
+        codec: MessageCodec,
+    ) -> Result<Self, (TcpStream, MessageCodec)> {
+        let driver = match DRIVER.as_ref() {
+            Some(driver) => Arc::clone(driver),
+            None => return Err((stream, codec)),
+        };
+        // Take over the socket from tokio. The socket stays non-blocking, as blocking reads would
+        // be punted to the workers of io_uring.
+        let fd = unsafe { libc::dup(stream.as_raw_fd()) };
+        if fd < 0 {
+            return Err((stream, codec));
+        }
+        drop(stream);
+        Ok(Self::from_raw_fd(fd, driver, codec))
+    }
+
+    fn from_raw_fd(fd: RawFd, driver: Arc<UringDriver>, codec: MessageCodec) -> Self {
+        Self {
+            fd,
+            driver,
+            codec,
+            read_buf: Some(BytesMut::new()),
+            read: None,
+            eof: false,
+            write_buf: BytesMut::new(),
+            write: None,
+        }
+    }
+
+    /// Polls the operation in progress, and returns its result and buffer.
+    fn poll_completion(
+        operation: &mut Option<oneshot::Receiver<Completion>>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<Completion>> {
+        let rx = operation.as_mut().expect("No operation in progress");
+        let result = ready!(Pin::new(rx).poll(cx));
+        *operation = None;
+        Poll::Ready(result.map_err(|_| driver_stopped()))
+    }
+
+    /// Completes the read in flight, if any.
+    fn poll_read(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.read.is_none() {
+            return Poll::Ready(Ok(()));
+        }
+        let (result, buffer) = ready!(Self::poll_completion(&mut self.read, cx))?;
+        let mut buf = match buffer {
+            Buffer::Read(buf) => buf,
+            Buffer::Write(_) => unreachable!(),
+        };
+        let result = match result {
+            // The read is submitted again.
+            result if result == -libc::EAGAIN => Ok(()),
+            result if result < 0 => Err(io::Error::from_raw_os_error(-result)),
+            0 => {
+                self.eof = true;
+                Ok(())
+            }
+            num_bytes => {
+                unsafe { buf.advance_mut(num_bytes as usize) };
+                Ok(())
+            }
+        };
+        self.read_buf = Some(buf);
+        Poll::Ready(result)
+    }
+}
+
+impl Stream for UringFramed {
+    type Item = Result<InterProcessMessage, CodecError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Err(e) = ready!(this.poll_read(cx)) {
+                return Poll::Ready(Some(Err(e.into())));
+            }
+            let buf = this.read_buf.as_mut().expect("No read buffer");
+            match this.codec.decode(buf) {
+                Ok(Some(msg)) => return Poll::Ready(Some(Ok(msg))),
+                Ok(None) => (),
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+            if this.eof {
+                return Poll::Ready(None);
+            }
+            let mut buf = this.read_buf.take().unwrap();
+            buf.reserve(READ_CAPACITY);
+            this.read = Some(this.driver.submit(this.fd, Buffer::Read(buf)));
+        }
+    }
+}
+
+impl Sink<InterProcessMessage> for UringFramed {
+    type Error = CodecError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), CodecError>> {
+        if self.write_buf.len() >= BACKPRESSURE_BOUNDARY {
+            self.poll_flush(cx)
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: InterProcessMessage) -> Result<(), CodecError> {
+        let this = self.get_mut();
+        this.codec.encode(msg, &mut this.write_buf)
+    }
+
+    /// Writes the encoded frames, one write in flight at a time.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), CodecError>> {
+        let this = self.get_mut();
+        loop {
+            if this.write.is_some() {
+                let (result, buffer) = ready!(Self::poll_completion(&mut this.write, cx))?;
+                let mut bytes = match buffer {
+                    Buffer::Write(bytes) => bytes,
+                    Buffer::Read(_) => unreachable!(),
+                };
+                match result {
+                    // The write is submitted again.
+                    result if result == -libc::EAGAIN => (),
+                    result if result < 0 => {
+                        return Poll::Ready(Err(io::Error::from_raw_os_error(-result).into()))
+                    }
+                    0 => return Poll::Ready(Err(io::Error::from(io::ErrorKind::WriteZero).into())),
+                    num_bytes => bytes.advance(num_bytes as usize),
+                }
+                // Writes the rest of the bytes before the frames encoded since.
+                if !bytes.is_empty() {
+                    this.write = Some(this.driver.submit(this.fd, Buffer::Write(bytes)));
+                    continue;
+                }
+            }
+            if this.write_buf.is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+            let bytes = this.write_buf.split().freeze();
+            this.write = Some(this.driver.submit(this.fd, Buffer::Write(bytes)));
+        }
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), CodecError>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        if unsafe { libc::shutdown(self.fd, libc::SHUT_WR) } < 0 {
+            return Poll::Ready(Err(io::Error::last_os_error().into()));
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for UringFramed {
+    fn drop(&mut self) {
+        // Completes the operations in flight, after which the driver closes the socket.
+        unsafe { libc::shutdown(self.fd, libc::SHUT_RDWR) };
+        self.driver.close(self.fd);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::{io::IntoRawFd, net::UnixStream};
+
+    use futures::{SinkExt, StreamExt};
+
+    use crate::dataflow::stream::StreamId;
+
+    use super::*;
+
+    /// Test that messages written through io_uring are read back.
+    #[test]
+    fn test_uring_framed() {
+        let driver = match DRIVER.as_ref() {
+            Some(driver) => Arc::clone(driver),
+            // The kernel does not support io_uring.
+            None => return,
+        };
+        let (left, right) = UnixStream::pair().unwrap();
+        left.set_nonblocking(true).unwrap();
+        right.set_nonblocking(true).unwrap();
+        let mut left =
+            UringFramed::from_raw_fd(left.into_raw_fd(), Arc::clone(&driver), MessageCodec::new());
+        let mut right = UringFramed::from_raw_fd(right.into_raw_fd(), driver, MessageCodec::new());
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let stream_id = StreamId::new_deterministic();
+            let data: Vec<u8> = (0..3 * READ_CAPACITY).map(|i| i as u8).collect();
+            let expected = bincode::serialize(&data).unwrap();
+            let writer = tokio::spawn(async move {
+                let msg = InterProcessMessage::new_deserialized(Arc::new(data), stream_id);
+                left.send(msg).await.unwrap();
+                left
+            });
+            match right.next().await {
+                Some(Ok(InterProcessMessage::Serialized { metadata, bytes })) => {
+                    assert_eq!(metadata.stream_id, stream_id);
+                    assert_eq!(&bytes[..], &expected[..]);
+                }
+                _ => panic!("Expected a serialized message"),
+            }
+            writer.await.unwrap();
+        });
+    }
+}
@@ -0,0 +1,245 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use bytes::BufMut;
+
+use crate::{
+    communication::{CodecError, MessageMetadata},
+    dataflow::{stream::StreamId, Data, Message, Timestamp, TimestampedData},
+    Uuid,
+};
+
+/// Written in place of the size of the metadata to mark a watermark frame.
+pub(crate) const WATERMARK_FRAME: u32 = u32::MAX;
+
+/// Size of a watermark frame whose timestamp has no coordinates: the marker, the number of
+/// coordinates, the stream ID, the run ID, the sequence number, and the flags.
+const WATERMARK_HEADER_SIZE: usize = 4 + 4 + 16 + 16 + 8 + 1;
+
+/// Flag set if the timestamp is the top timestamp.
+pub(crate) const TOP_FLAG: u8 = 1;
+/// Flag set if the frame carries a tick instead of a watermark.
+pub(crate) const TICK_FLAG: u8 = 2;
+
+/// Finds the watermarks and ticks among the messages sent to other nodes, and creates the
+/// watermarks and ticks received from other nodes, without knowing the type of the messages.
+///
+/// Ticks are messages of a [`TickWriteStream`](crate::dataflow::stream::TickWriteStream), which
+/// only carry their timestamp and are sent as watermark frames.
+pub(crate) trait WatermarkFrame: Sized {
+    fn as_watermark(&self) -> Option<&Timestamp>;
+    fn from_watermark(timestamp: Timestamp) -> Option<Self>;
+    fn as_tick(&self) -> Option<&Timestamp>;
+    fn from_tick(timestamp: Timestamp) -> Option<Self>;
+}
+
+impl<T> WatermarkFrame for T {
+    default fn as_watermark(&self) -> Option<&Timestamp> {
+        None
+    }
+
+    default fn from_watermark(_timestamp: Timestamp) -> Option<Self> {
+        None
+    }
+
+    default fn as_tick(&self) -> Option<&Timestamp> {
+        None
+    }
+
+    default fn from_tick(_timestamp: Timestamp) -> Option<Self> {
+        None
+    }
+}
+
+impl<D: Data> WatermarkFrame for Message<D> {
+    fn as_watermark(&self) -> Option<&Timestamp> {
+        match self {
+            Message::Watermark(timestamp) => Some(timestamp),
+            Message::TimestampedData(_) => None,
+        }
+    }
+
+    fn from_watermark(timestamp: Timestamp) -> Option<Self> {
+        Some(Message::Watermark(timestamp))
+    }
+
+    default fn as_tick(&self) -> Option<&Timestamp> {
+        None
+    }
+
+    default fn from_tick(_timestamp: Timestamp) -> Option<Self> {
+        None
+    }
+}
+
+impl WatermarkFrame for Message<()> {
+    /// Ticks which carry other times or are backfilled are serialized like other messages.
+    fn as_tick(&self) -> Option<&Timestamp> {
+        match self {
+            Message::TimestampedData(TimestampedData {
+                timestamp,
+                backfill: false,
+                event_time: None,
+                ingestion_time: None,
+                ..
+            }) => Some(timestamp),
+            _ => None,
+        }
+    }
+
+    fn from_tick(timestamp: Timestamp) -> Option<Self> {
+        Some(Message::new_message(timestamp, ()))
+    }
+}
+
+/// Returns the size of the watermark frame of the timestamp.
+pub(crate) fn frame_size(timestamp: &Timestamp) -> usize {
+    8 + body_size(timestamp.time.len())
+}
+
+/// Returns the size of the part of a watermark frame after the marker and the number of
+/// coordinates.
+pub(crate) fn body_size(num_coordinates: usize) -> usize {
+    WATERMARK_HEADER_SIZE - 8 + 8 * num_coordinates
+}
+
+/// Writes a watermark frame, which carries the IDs and the sequence number of the metadata and
+/// the coordinates of the timestamp in a fixed layout instead of serializing the message.
+pub(crate) fn encode<B: BufMut>(metadata: &MessageMetadata, timestamp: &Timestamp, buf: &mut B) {
+    buf.put_u32(WATERMARK_FRAME);
+    buf.put_u32(timestamp.time.len() as u32);
+    buf.put_slice(metadata.stream_id.as_bytes());
+    buf.put_slice(metadata.run_id.as_bytes());
+    buf.put_u64(metadata.sequence);
+    buf.put_u8(flags(metadata, timestamp));
+    for coordinate in timestamp.time.iter() {
+        buf.put_u64(*coordinate);
+    }
+}
+
+/// Returns the flags of the watermark frame.
+pub(crate) fn flags(metadata: &MessageMetadata, timestamp: &Timestamp) -> u8 {
+    let mut flags = 0;
+    if timestamp.is_top() {
+        flags |= TOP_FLAG;
+    }
+    if metadata.tick {
+        flags |= TICK_FLAG;
+    }
+    flags
+}
+
+/// Reads the part of a watermark frame after the marker and the number of coordinates, and
+/// returns the metadata of the watermark.
+pub(crate) fn decode_body(
+    body: &[u8],
+    num_coordinates: usize,
+) -> Result<MessageMetadata, CodecError> {
+    if body.len() < body_size(num_coordinates) {
+        return Err(malformed());
+    }
+    let mut stream_id = [0; 16];
+    stream_id.copy_from_slice(&body[0..16]);
+    let mut run_id = [0; 16];
+    run_id.copy_from_slice(&body[16..32]);
+    let flags = body[40];
+    let timestamp = if flags & TOP_FLAG != 0 {
+        Timestamp::top()
+    } else {
+        Timestamp::new(
+            body[41..41 + 8 * num_coordinates]
+                .chunks(8)
+                .map(NetworkEndian::read_u64)
+                .collect(),
+        )
+    };
+    Ok(MessageMetadata {
+        stream_id: StreamId::from(Uuid::from_bytes(stream_id)),
+        chunk: None,
+        compressed: false,
+        run_id: Uuid::from_bytes(run_id),
+        conflation_key: None,
+        timings: None,
+        watermark: Some(timestamp),
+        tick: flags & TICK_FLAG != 0,
+        sequence: NetworkEndian::read_u64(&body[32..40]),
+        provenance: None,
+    })
+}
+
+/// Returns the metadata of the watermark if the whole frame is a watermark frame.
+#[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
+pub(crate) fn decode(frame: &[u8]) -> Result<Option<MessageMetadata>, CodecError> {
+    if frame.len() < 8 || NetworkEndian::read_u32(&frame[0..4]) != WATERMARK_FRAME {
+        return Ok(None);
+    }
+    let num_coordinates = NetworkEndian::read_u32(&frame[4..8]) as usize;
+    decode_body(&frame[8..], num_coordinates).map(Some)
+}
+
+fn malformed() -> CodecError {
+    CodecError::BincodeError(Box::new(bincode::ErrorKind::Custom(
+        "Malformed watermark frame".to_string(),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that watermark frames are decoded into the metadata of the encoded watermark.
+    #[test]
+    fn test_watermark_frame() {
+        let msg = Message::<usize>::new_watermark(Timestamp::new(vec![3, 7]));
+        let timestamp = msg.as_watermark().unwrap();
+        let metadata = MessageMetadata {
+            stream_id: StreamId::new_v4(),
+            chunk: None,
+            compressed: false,
+            run_id: Uuid::new_v4(),
+            conflation_key: None,
+            timings: None,
+            watermark: None,
+            tick: false,
+            sequence: 9,
+            provenance: None,
+        };
+        let mut buf = Vec::new();
+        encode(&metadata, timestamp, &mut buf);
+        assert_eq!(buf.len(), frame_size(timestamp));
+
+        assert_eq!(NetworkEndian::read_u32(&buf[0..4]), WATERMARK_FRAME);
+        assert_eq!(NetworkEndian::read_u32(&buf[4..8]), 2);
+
+        let decoded = decode_body(&buf[8..], 2).unwrap();
+        assert_eq!(decoded.stream_id, metadata.stream_id);
+        assert_eq!(decoded.run_id, metadata.run_id);
+        assert_eq!(decoded.watermark.as_ref(), Some(timestamp));
+        assert_eq!(decoded.sequence, 9);
+        assert!(decode_body(&buf[8..buf.len() - 1], 2).is_err());
+
+        let mut buf = Vec::new();
+        encode(&metadata, &Timestamp::top(), &mut buf);
+        let decoded = decode_body(&buf[8..], 0).unwrap();
+        assert!(decoded.watermark.unwrap().is_top());
+        assert!(!decoded.tick);
+
+        let tick = Message::new_message(Timestamp::new(vec![4]), ());
+        let mut buf = Vec::new();
+        let metadata = MessageMetadata {
+            tick: true,
+            ..metadata
+        };
+        encode(&metadata, tick.as_tick().unwrap(), &mut buf);
+        let decoded = decode_body(&buf[8..], 1).unwrap();
+        assert!(decoded.tick);
+        assert_eq!(
+            Message::<()>::from_tick(decoded.watermark.unwrap())
+                .unwrap()
+                .timestamp(),
+            tick.timestamp()
+        );
+        assert!(Message::new_message(Timestamp::new(vec![1]), 1usize)
+            .as_watermark()
+            .is_none());
+        assert!(usize::from_watermark(Timestamp::top()).is_none());
+    }
+}
@@ -7,14 +7,18 @@ use tokio::sync::{
 };
 
 #[cfg(feature = "zenoh_transport")]
-use zenoh::net;
+use zenoh::net::{self, protocol::io::ArcSlice};
 
 use crate::{
     communication::{
-        CommunicationError, ControlMessage, ControlMessageHandler, InterProcessMessage, PusherT,
+        CommunicationError, ControlDirection, ControlMessage, ControlMessageHandler,
+        ControlRecorder, GapDetector, InterProcessMessage, MessageMetadata, PusherT, Reassembler,
+        StreamDecompressor, UnregisteredMessages,
     },
     dataflow::stream::StreamId,
-    node::NodeId,
+    node::{
+        latency, stream_metrics, DeadLetter, DeadLetterQueue, DeadLetterReason, NodeContext, NodeId,
+    },
     scheduler::endpoints_manager::ChannelsToReceivers,
 };
 
@@ -38,6 +42,18 @@ pub(crate) struct ZenohDataReceiver {
     control_tx: UnboundedSender<ControlMessage>,
     /// Tokio channel receiver from `ControlMessageHandler`.
     control_rx: UnboundedReceiver<ControlMessage>,
+    /// Queue to which undeliverable messages are sent.
+    dead_letters: DeadLetterQueue,
+    /// The state of the node, on which the received messages are counted and traced.
+    context: Arc<NodeContext>,
+    /// Reassembles messages which the sender split into chunks.
+    reassembler: Reassembler,
+    /// Decompresses messages compressed with the zstd dictionary of their stream.
+    decompressor: StreamDecompressor,
+    /// Detects the messages lost on the channel from the sequence numbers of each stream.
+    gaps: GapDetector,
+    /// Messages received before the pusher of their stream was registered.
+    unregistered: UnregisteredMessages<ArcSlice>,
 }
 
 #[cfg(feature = "zenoh_transport")]
@@ -48,6 +64,8 @@ impl ZenohDataReceiver {
         zsession: Arc<net::Session>,
         channels_to_receivers: Arc<Mutex<ChannelsToReceivers>>,
         control_handler: &mut ControlMessageHandler,
+        dead_letters: DeadLetterQueue,
+        context: Arc<NodeContext>,
     ) -> Self {
         // Create a channel for this stream.
         let (tx, rx) = mpsc::unbounded_channel();
@@ -64,9 +82,27 @@ impl ZenohDataReceiver {
             stream_id_to_pusher: HashMap::new(),
             control_tx: control_handler.get_channel_to_handler(),
             control_rx,
+            dead_letters,
+            context,
+            reassembler: Reassembler::new(),
+            decompressor: StreamDecompressor::new(),
+            gaps: GapDetector::default(),
+            unregistered: UnregisteredMessages::default(),
         }
     }
 
+    /// Decompresses the received messages with the decompressor, e.g. to drop the compressed
+    /// messages which are too large once decompressed.
+    pub(crate) fn with_decompressor(mut self, decompressor: StreamDecompressor) -> Self {
+        self.decompressor = decompressor;
+        self
+    }
+
+    /// Returns the id of the node from which the receiver receives data.
+    pub(crate) fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
     pub(crate) async fn run(&mut self) -> Result<(), CommunicationError> {
         //Create the Zenoh subscription
         let sub_info = zenoh::net::SubInfo {
@@ -91,62 +127,204 @@ impl ZenohDataReceiver {
             .map_err(CommunicationError::from)?;
 
         let z_sub_stream = subscriber.stream();
-        while let Some(zres) = z_sub_stream.next().await {
-            // println!("ZenohReceiver, rbuf received {:?}", zres.payload);
-            let m = InterProcessMessage::from_rbuf(&zres.payload);
-            // println!("ZenohReceiver, msg received {:?}", m);
-
-            match m {
-                // Push the message to the listening operator executors.
-                Ok(msg) => {
-                    // Update pushers before we send the message.
-                    // Note: we may want to update the pushers less frequently.
-                    self.update_pushers();
-                    // Send the message.
-                    let (metadata, bytes) = match msg {
-                        InterProcessMessage::Serialized { metadata, bytes } => (metadata, bytes),
-                        InterProcessMessage::Deserialized {
-                            metadata: _,
-                            data: _,
-                        } => unreachable!(),
+        loop {
+            tokio::select! {
+                zres = z_sub_stream.next() => {
+                    let zres = match zres {
+                        Some(zres) => zres,
+                        None => return Ok(()),
                     };
+                    match InterProcessMessage::from_rbuf(&zres.payload) {
+                        // Push the message to the listening operator executors.
+                        Ok(msg) => self.handle_message(msg)?,
+                        // The metadata could not be decoded, so the stream is unknown.
+                        Err(e) => self.dead_letters.send(DeadLetter {
+                            from_node: self.node_id,
+                            stream_id: None,
+                            reason: DeadLetterReason::Undecodable(format!("{:?}", e)),
+                            bytes: zres.payload.to_vec(),
+                        }),
+                    }
+                }
+                Some(msg) = self.control_rx.recv() => self.handle_control_message(msg)?,
+                // Deliver the messages which wait for the pusher as soon as it is registered.
+                Some((stream_id, pusher)) = self.rx.recv() => self.add_pusher(stream_id, pusher)?,
+            }
+        }
+    }
 
-                    match self.stream_id_to_pusher.get_mut(&metadata.stream_id) {
-                        Some(pusher) => {
-                            // println!("Sending to pusher {:?}", bytes);
-                            if let Err(e) = pusher.send_from_bytes(bytes) {
-                                // println!("Got error from pusher {:?}", e);
-                                return Err(e);
-                            }
-                        }
-                        None => (),
-                        //println!("Receiver does not have any pushers. {:?}", metadata),
-                        // panic!(
-                        //     "Receiver does not have any pushers. \
-                        //         Race condition during data-flow reconfiguration."
-                        // ),
+    fn handle_message(&mut self, msg: InterProcessMessage) -> Result<(), CommunicationError> {
+        // Update pushers before we send the message.
+        // Note: we may want to update the pushers less frequently.
+        self.update_pushers()?;
+        let (metadata, bytes) = match msg {
+            InterProcessMessage::Serialized { metadata, bytes } => (metadata, bytes),
+            InterProcessMessage::Deserialized {
+                metadata: _,
+                data: _,
+            } => unreachable!(),
+        };
+        let lost = self.gaps.observe(&metadata);
+        if lost > 0 {
+            slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "Lost {} messages on stream {} from node {}",
+                lost,
+                metadata.stream_id,
+                self.node_id
+            );
+            self.dead_letters.send(DeadLetter {
+                from_node: self.node_id,
+                stream_id: Some(metadata.stream_id),
+                reason: DeadLetterReason::Lost(lost),
+                bytes: Vec::new(),
+            });
+        }
+        // Wait for all the chunks of split messages.
+        let bytes = match &metadata.chunk {
+            None => bytes,
+            Some(chunk) => {
+                match self
+                    .reassembler
+                    .add_chunk(metadata.stream_id, chunk, bytes.as_slice())
+                {
+                    Ok(Some(bytes)) => bytes,
+                    Ok(None) => return Ok(()),
+                    Err(e) => {
+                        self.dead_letters.send(DeadLetter {
+                            from_node: self.node_id,
+                            stream_id: Some(metadata.stream_id),
+                            reason: DeadLetterReason::Incomplete(format!("{:?}", e)),
+                            bytes: Vec::new(),
+                        });
+                        return Ok(());
                     }
                 }
-                Err(e) => {
-                    slog::warn!(
-                        crate::get_terminal_logger(),
-                        "DataReceiver got error from Zenoh {:?}",
-                        e
-                    );
-                    return Err(CommunicationError::from(e));
+            }
+        };
+        // Wait for the dictionary of compressed messages.
+        match self.decompressor.decompress(&metadata, bytes) {
+            Ok(Some(bytes)) => self.push(&metadata, bytes),
+            Ok(None) => Ok(()),
+            Err(e) => {
+                self.dead_letters.send(DeadLetter {
+                    from_node: self.node_id,
+                    stream_id: Some(metadata.stream_id),
+                    reason: DeadLetterReason::decompression_failed(&e),
+                    bytes: Vec::new(),
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /// Delivers the compressed messages which waited for the dictionary of their stream.
+    fn handle_control_message(&mut self, msg: ControlMessage) -> Result<(), CommunicationError> {
+        if let ControlMessage::StreamDictionary(dictionary) = msg {
+            self.update_pushers()?;
+            for (metadata, result) in self.decompressor.add_dictionary(dictionary) {
+                match result {
+                    Ok(bytes) => self.push(&metadata, bytes)?,
+                    Err(e) => self.dead_letters.send(DeadLetter {
+                        from_node: self.node_id,
+                        stream_id: Some(metadata.stream_id),
+                        reason: DeadLetterReason::decompression_failed(&e),
+                        bytes: Vec::new(),
+                    }),
                 }
             }
         }
+        Ok(())
+    }
 
+    /// Sends the message to the operators which read from its stream.
+    fn push(
+        &mut self,
+        metadata: &MessageMetadata,
+        bytes: ArcSlice,
+    ) -> Result<(), CommunicationError> {
+        self.context.counters.message_received_from_node();
+        stream_metrics::message_received(metadata.stream_id);
+        self.deliver(metadata, bytes)
+    }
+
+    /// Sends the message to the pusher of its stream, or buffers it until the pusher is
+    /// registered.
+    fn deliver(
+        &mut self,
+        metadata: &MessageMetadata,
+        bytes: ArcSlice,
+    ) -> Result<(), CommunicationError> {
+        match self.stream_id_to_pusher.get_mut(&metadata.stream_id) {
+            Some(pusher) => {
+                let deliver = || match &metadata.watermark {
+                    // Watermark frames carry no serialized message.
+                    Some(timestamp) if metadata.tick => {
+                        pusher.send_tick(timestamp.clone()).map_err(|e| (e, None))
+                    }
+                    Some(timestamp) => pusher
+                        .send_watermark(timestamp.clone())
+                        .map_err(|e| (e, None)),
+                    None => pusher
+                        .send_from_bytes(bytes)
+                        .map_err(|(e, bytes)| (e, Some(bytes))),
+                };
+                self.context.provenance.record_received(metadata);
+                match latency::deliver_timed(metadata, deliver) {
+                    Ok(()) => (),
+                    Err((e, bytes)) if e.is_decode_error() => self.dead_letters.send(DeadLetter {
+                        from_node: self.node_id,
+                        stream_id: Some(metadata.stream_id),
+                        reason: DeadLetterReason::Undecodable(format!("{:?}", e)),
+                        // Only copy the message if somebody reads the dead letters.
+                        bytes: match bytes {
+                            Some(bytes) if self.dead_letters.has_subscribers() => {
+                                bytes.as_slice().to_vec()
+                            }
+                            _ => Vec::new(),
+                        },
+                    }),
+                    Err((e, _)) => return Err(e),
+                }
+            }
+            None => {
+                if let Some((metadata, bytes)) = self.unregistered.push(metadata.clone(), bytes) {
+                    self.dead_letters.send(DeadLetter {
+                        from_node: self.node_id,
+                        stream_id: Some(metadata.stream_id),
+                        reason: DeadLetterReason::NoReceiver,
+                        bytes: if self.dead_letters.has_subscribers() {
+                            bytes.as_slice().to_vec()
+                        } else {
+                            Vec::new()
+                        },
+                    })
+                }
+            }
+        }
         Ok(())
     }
 
     // TODO: update this method.
-    fn update_pushers(&mut self) {
+    fn update_pushers(&mut self) -> Result<(), CommunicationError> {
         // Execute while we still have pusher updates.
         while let Ok((stream_id, pusher)) = self.rx.try_recv() {
-            self.stream_id_to_pusher.insert(stream_id, pusher);
+            self.add_pusher(stream_id, pusher)?;
         }
+        Ok(())
+    }
+
+    /// Registers the pusher of the stream, and delivers the messages received before it.
+    fn add_pusher(
+        &mut self,
+        stream_id: StreamId,
+        pusher: Box<dyn PusherT>,
+    ) -> Result<(), CommunicationError> {
+        self.stream_id_to_pusher.insert(stream_id, pusher);
+        for (metadata, bytes) in self.unregistered.take(stream_id) {
+            self.deliver(&metadata, bytes)?;
+        }
+        Ok(())
     }
 }
 
@@ -178,6 +356,8 @@ pub(crate) struct ZenohControlReceiver {
     control_tx: UnboundedSender<ControlMessage>,
     /// Tokio channel receiver from `ControlMessageHandler`.
     control_rx: UnboundedReceiver<ControlMessage>,
+    /// Logs the received control messages.
+    recorder: ControlRecorder,
 }
 
 #[cfg(feature = "zenoh_transport")]
@@ -198,6 +378,7 @@ impl ZenohControlReceiver {
             zsession,
             control_tx: control_handler.get_channel_to_handler(),
             control_rx,
+            recorder: control_handler.recorder(),
         }
     }
 
@@ -235,6 +416,8 @@ impl ZenohControlReceiver {
             match ControlMessage::from_rbuf(&zres.payload) {
                 // Push the message to the listening operator executors.
                 Ok(msg) => {
+                    self.recorder
+                        .record(ControlDirection::Received, self.node_id, &msg);
                     self.control_tx
                         .send(msg)
                         .map_err(CommunicationError::from)?;
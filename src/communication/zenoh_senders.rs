@@ -13,9 +13,10 @@ use tokio::{
 use zenoh::net;
 
 use crate::communication::{
-    CommunicationError, ControlMessage, ControlMessageHandler, InterProcessMessage,
+    Chunker, CommunicationError, ControlMessage, ControlMessageHandler, InterProcessMessage,
+    SequenceNumbers, SerializationPool, ShapedReceiver, StreamCompressor, TrafficShaper,
 };
-use crate::node::NodeId;
+use crate::node::{latency, stream_metrics, NodeId};
 use crate::scheduler::endpoints_manager::ChannelsToSenders;
 
 #[allow(dead_code)]
@@ -30,12 +31,22 @@ pub(crate) struct ZenohDataSender {
     self_node_id: NodeId,
     /// Zenoh Session
     zsession: Arc<net::Session>,
-    /// Tokio channel receiver on which to receive data from worker threads.
-    rx: UnboundedReceiver<InterProcessMessage>,
+    /// Receives the data from worker threads at the rate allowed by the traffic shaper.
+    rx: ShapedReceiver,
     /// Tokio channel sender to `ControlMessageHandler`.
     control_tx: UnboundedSender<ControlMessage>,
     /// Tokio channel receiver from `ControlMessageHandler`.
     control_rx: UnboundedReceiver<ControlMessage>,
+    /// Maximum size of a message sent to the other node. Unlimited if `None`.
+    max_message_size: Option<usize>,
+    /// Splits messages larger than the maximum chunk size.
+    chunker: Chunker,
+    /// Compresses the messages of streams with a zstd dictionary.
+    compressor: StreamCompressor,
+    /// Numbers the messages of each stream, so that the receiver detects lost messages.
+    sequences: SequenceNumbers,
+    /// Serializes large messages off the event loop, if set.
+    serialization_pool: Option<SerializationPool>,
 }
 
 #[cfg(feature = "zenoh_transport")]
@@ -46,6 +57,10 @@ impl ZenohDataSender {
         zsession: Arc<net::Session>,
         channels_to_senders: Arc<Mutex<ChannelsToSenders>>,
         control_handler: &mut ControlMessageHandler,
+        max_message_size: Option<usize>,
+        max_chunk_size: Option<usize>,
+        compressor: StreamCompressor,
+        shaper: TrafficShaper,
     ) -> Self {
         // Create a channel for this stream.
         let (tx, rx) = mpsc::unbounded_channel();
@@ -59,9 +74,51 @@ impl ZenohDataSender {
             self_node_id,
             // sink,
             zsession,
-            rx,
+            rx: ShapedReceiver::new(node_id, rx, shaper),
             control_tx: control_handler.get_channel_to_handler(),
             control_rx,
+            max_message_size,
+            chunker: Chunker::new(max_chunk_size),
+            compressor,
+            sequences: SequenceNumbers::default(),
+            serialization_pool: None,
+        }
+    }
+
+    /// Makes the sender serialize large messages on the threads of the pool.
+    pub(crate) fn with_serialization_pool(mut self, pool: Option<SerializationPool>) -> Self {
+        self.serialization_pool = pool;
+        self
+    }
+
+    /// Writes a message to the other node.
+    async fn write(
+        &self,
+        reskey: &zenoh::net::protocol::core::ResKey,
+        msg: &InterProcessMessage,
+    ) -> Result<(), CommunicationError> {
+        let rbf: zenoh::net::RBuf = msg.into_rbuf().map_err(CommunicationError::from)?;
+        self.zsession
+            .write_ext(
+                reskey,
+                rbf,
+                zenoh::net::encoding::DEFAULT,
+                zenoh::net::data_kind::DEFAULT,
+                zenoh::net::protocol::core::CongestionControl::Block,
+            )
+            .await
+            .map_err(CommunicationError::from)
+    }
+
+    /// Notifies the other node that the remaining chunks of `chunk`'s message will not be sent.
+    async fn cancel(
+        &self,
+        reskey: &zenoh::net::protocol::core::ResKey,
+        chunk: &InterProcessMessage,
+    ) {
+        if let Some(cancellation) = Chunker::cancellation(chunk) {
+            // Best effort: the receiver also discards the message if the next chunk is missing.
+            let _ = self.write(reskey, &cancellation).await;
         }
     }
 
@@ -90,24 +147,44 @@ impl ZenohDataSender {
                         .await
                         .map_err(CommunicationError::from)?;
 
-                    let rbf: zenoh::net::RBuf =
-                        msg.into_rbuf().map_err(CommunicationError::from)?;
-
-                    if let Err(e) = self
-                        .zsession
-                        .write_ext(
-                            &reskey,
-                            rbf,
-                            zenoh::net::encoding::DEFAULT,
-                            zenoh::net::data_kind::DEFAULT,
-                            zenoh::net::protocol::core::CongestionControl::Block,
-                        )
-                        .await
-                        .map_err(CommunicationError::from)
-                    {
-                        return Err(e);
+                    let msg = self.sequences.number(msg);
+                    let msg = latency::serialize_timed(msg).map_err(CommunicationError::from)?;
+                    let msg = match &self.serialization_pool {
+                        Some(pool) => pool
+                            .serialize(msg)
+                            .await
+                            .map_err(CommunicationError::from)?,
+                        None => msg,
+                    };
+                    let msg =
+                        stream_metrics::message_sent(msg).map_err(CommunicationError::from)?;
+                    let (msg, dictionary) = self
+                        .compressor
+                        .compress(msg)
+                        .map_err(CommunicationError::from)?;
+                    if let Some(dictionary) = dictionary {
+                        // The receiver buffers compressed messages until the dictionary arrives.
+                        self.control_tx
+                            .send(ControlMessage::StreamDictionary(dictionary))
+                            .map_err(CommunicationError::from)?;
+                    }
+                    let chunks = self.chunker.split(msg).map_err(CommunicationError::from)?;
+                    for chunk in chunks {
+                        if let Err(e) = chunk.check_size(self.max_message_size) {
+                            slog::error!(
+                                crate::TERMINAL_LOGGER,
+                                "ZenohDataSender to node {}: dropping message: {:?}",
+                                self.node_id,
+                                e
+                            );
+                            self.cancel(&reskey, &chunk).await;
+                            break;
+                        }
+                        if let Err(e) = self.write(&reskey, &chunk).await {
+                            self.cancel(&reskey, &chunk).await;
+                            return Err(e);
+                        }
                     }
-                    //
                 }
                 None => return Err(CommunicationError::Disconnected),
             }
@@ -0,0 +1,367 @@
+use std::{
+    fs,
+    path::Path,
+    process,
+    sync::atomic::{AtomicI64, AtomicU64, Ordering},
+};
+
+use zenoh::net::{SharedMemoryBuf, SharedMemoryManager};
+
+use crate::{
+    communication::CommunicationError, configuration::DEFAULT_SHM_SEGMENT_SIZE,
+    node::ResourceLimits, Configuration, ShmOverflowPolicy,
+};
+
+/// Size of the transparent huge pages backing the shared memory.
+const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+/// Size below which segments are not shrunk to fit the memory available to the process.
+const MIN_SEGMENT_SIZE: usize = 16 * 1024 * 1024;
+/// Prefix of the names of the segments, which is followed by the ID of the creating process so
+/// that the segments of crashed processes can be removed.
+const SEGMENT_PREFIX: &str = "erdos-p";
+
+/// Bytes allocated in the shared memory pools of the current process and not released yet.
+static USED_BYTES: AtomicI64 = AtomicI64::new(0);
+static CAPACITY_BYTES: AtomicI64 = AtomicI64::new(0);
+/// Number of allocations which did not fit in the shared memory pools.
+static OVERFLOWS: AtomicU64 = AtomicU64::new(0);
+/// Number of messages sent as serialized copies because the shared memory pools were full.
+static FALLBACKS: AtomicU64 = AtomicU64::new(0);
+
+/// Settings of the shared memory pools of the zero-copy transport.
+#[derive(Clone, Debug)]
+pub(crate) struct ShmPoolConfig {
+    pub segment_size: usize,
+    pub num_segments: usize,
+    pub overflow_policy: ShmOverflowPolicy,
+    pub huge_pages: bool,
+}
+
+impl ShmPoolConfig {
+    /// Returns the settings of the pools of the node, whose segments are shrunk so that the
+    /// pools for all other nodes take at most half of the memory available to the process, as
+    /// shared memory counts against the memory limit of its cgroup.
+    pub fn from_configuration(config: &Configuration) -> Self {
+        let num_pools = config.data_addresses.len().saturating_sub(1).max(1);
+        let num_segments = config.shm_num_segments.max(1);
+        let segment_size = match ResourceLimits::detect().memory_bytes {
+            Some(memory_bytes) => {
+                let max_segment_size = memory_bytes as usize / 2 / num_pools / num_segments;
+                config
+                    .shm_segment_size
+                    .min(max_segment_size.max(MIN_SEGMENT_SIZE))
+            }
+            None => config.shm_segment_size,
+        };
+        Self {
+            segment_size,
+            num_segments: config.shm_num_segments,
+            overflow_policy: config.shm_overflow_policy,
+            huge_pages: config.shm_huge_pages,
+        }
+    }
+}
+
+impl Default for ShmPoolConfig {
+    fn default() -> Self {
+        Self {
+            segment_size: DEFAULT_SHM_SEGMENT_SIZE,
+            num_segments: 1,
+            overflow_policy: ShmOverflowPolicy::Backpressure,
+            huge_pages: false,
+        }
+    }
+}
+
+fn round_up(len: usize, multiple: usize) -> usize {
+    (len + multiple - 1) / multiple * multiple
+}
+
+/// Segments of shared memory from which a data sender allocates the messages it sends to
+/// another node.
+pub(crate) struct ShmPool {
+    segments: Vec<SharedMemoryManager>,
+    overflow_policy: ShmOverflowPolicy,
+    /// Whether the buffers are advised to use huge pages, until the kernel rejects the advice.
+    huge_pages: bool,
+    capacity: usize,
+    /// Bytes allocated in the segments and not garbage collected yet.
+    used_bytes: usize,
+}
+
+impl ShmPool {
+    /// Creates the segments, whose names contain `id` and the ID of the process.
+    pub fn new(id: &str, config: &ShmPoolConfig) -> Result<Self, CommunicationError> {
+        let huge_pages = config.huge_pages && cfg!(target_os = "linux");
+        if config.huge_pages && !huge_pages {
+            slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "Huge pages are only supported on Linux, using regular pages for shared memory"
+            );
+        }
+        let segment_size = if huge_pages {
+            round_up(config.segment_size, HUGE_PAGE_SIZE)
+        } else {
+            config.segment_size
+        };
+        let segments = (0..config.num_segments.max(1))
+            .map(|i| {
+                let name = format!("{}{}-{}-{}", SEGMENT_PREFIX, process::id(), id, i);
+                SharedMemoryManager::new(name, segment_size).map_err(CommunicationError::from)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let capacity = segments.len() * segment_size;
+        CAPACITY_BYTES.fetch_add(capacity as i64, Ordering::SeqCst);
+        Ok(Self {
+            segments,
+            overflow_policy: config.overflow_policy,
+            huge_pages,
+            capacity,
+            used_bytes: 0,
+        })
+    }
+
+    pub fn overflow_policy(&self) -> ShmOverflowPolicy {
+        self.overflow_policy
+    }
+
+    /// Allocates a buffer from the first segment with enough free memory, or returns `None` if
+    /// the buffer does not fit in any segment after releasing the buffers the other node freed.
+    ///
+    /// With huge pages, buffers spanning huge pages are rounded up to whole huge pages so that
+    /// the next buffers start on a huge page.
+    pub fn alloc(&mut self, len: usize) -> Option<SharedMemoryBuf> {
+        let len = if self.huge_pages && len >= HUGE_PAGE_SIZE {
+            round_up(len, HUGE_PAGE_SIZE)
+        } else {
+            len
+        };
+        let mut buf = self.try_alloc(len).or_else(|| {
+            self.garbage_collect();
+            self.try_alloc(len)
+        });
+        match buf.as_mut() {
+            Some(buf) => {
+                self.used_bytes += len;
+                USED_BYTES.fetch_add(len as i64, Ordering::SeqCst);
+                if self.huge_pages && len >= HUGE_PAGE_SIZE {
+                    self.advise_huge_pages(buf);
+                }
+            }
+            None => {
+                OVERFLOWS.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        buf
+    }
+
+    fn try_alloc(&mut self, len: usize) -> Option<SharedMemoryBuf> {
+        self.segments
+            .iter_mut()
+            .find_map(|segment| segment.alloc(len))
+    }
+
+    /// Advises the kernel to back the huge pages within the buffer with transparent huge pages,
+    /// which requires `shmem_enabled` to allow them.
+    #[cfg(target_os = "linux")]
+    fn advise_huge_pages(&mut self, buf: &mut SharedMemoryBuf) {
+        let slice = unsafe { buf.as_mut_slice() };
+        let start = round_up(slice.as_ptr() as usize, HUGE_PAGE_SIZE);
+        let end = (slice.as_ptr() as usize + slice.len()) / HUGE_PAGE_SIZE * HUGE_PAGE_SIZE;
+        if start >= end {
+            return;
+        }
+        let result =
+            unsafe { libc::madvise(start as *mut libc::c_void, end - start, libc::MADV_HUGEPAGE) };
+        if result != 0 {
+            slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "Huge pages are unavailable, using regular pages for shared memory: {}",
+                std::io::Error::last_os_error()
+            );
+            self.huge_pages = false;
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn advise_huge_pages(&mut self, _buf: &mut SharedMemoryBuf) {}
+
+    /// Releases the buffers which the other node freed.
+    pub fn garbage_collect(&mut self) {
+        let freed: usize = self
+            .segments
+            .iter_mut()
+            .map(|segment| segment.garbage_collect())
+            .sum();
+        let freed = freed.min(self.used_bytes);
+        self.used_bytes -= freed;
+        USED_BYTES.fetch_sub(freed as i64, Ordering::SeqCst);
+    }
+
+    /// A message was sent as a serialized copy because the pool was full.
+    pub fn record_fallback(&self) {
+        FALLBACKS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Drop for ShmPool {
+    fn drop(&mut self) {
+        USED_BYTES.fetch_sub(self.used_bytes as i64, Ordering::SeqCst);
+        CAPACITY_BYTES.fetch_sub(self.capacity as i64, Ordering::SeqCst);
+    }
+}
+
+/// Makes the zero-copy transport create the files through which other nodes open its shared
+/// memory in the directory.
+///
+/// Zenoh creates these files in the temporary directory of the process, which is changed for
+/// the whole process.
+pub(crate) fn set_shm_directory(directory: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(directory)?;
+    if cfg!(windows) {
+        // Windows takes the temporary directory from `TMP`, and then from `TEMP`.
+        std::env::set_var("TMP", directory);
+        std::env::set_var("TEMP", directory);
+    } else {
+        std::env::set_var("TMPDIR", directory);
+    }
+    Ok(())
+}
+
+/// Removes the shared memory which processes that exited without dropping their pools left in
+/// the temporary directory, and returns the number of removed segments.
+///
+/// Segments are named objects which outlive their creator on Linux and macOS until they are
+/// unlinked, so the objects are unlinked along with the files through which other nodes open
+/// them. On Windows, the system frees the named mappings with their last handle, and only the
+/// files are left behind.
+pub(crate) fn remove_stale_segments() -> usize {
+    let entries = match fs::read_dir(std::env::temp_dir()) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    let mut num_removed = 0;
+    for entry in entries.filter_map(Result::ok) {
+        let file_name = entry.file_name();
+        let pid = match file_name.to_str().and_then(segment_pid) {
+            Some(pid) => pid,
+            None => continue,
+        };
+        if pid == process::id() || is_running(pid) {
+            continue;
+        }
+        // The file stores the OS identifier of the segment.
+        #[cfg(unix)]
+        if let Ok(os_id) = fs::read_to_string(entry.path()) {
+            if let Ok(os_id) = std::ffi::CString::new(os_id.trim()) {
+                unsafe { libc::shm_unlink(os_id.as_ptr()) };
+            }
+        }
+        if fs::remove_file(entry.path()).is_ok() {
+            num_removed += 1;
+        }
+    }
+    num_removed
+}
+
+/// Returns the ID of the process which created the segment with the file, if it is a segment.
+fn segment_pid(file_name: &str) -> Option<u32> {
+    let start = file_name.find(SEGMENT_PREFIX)? + SEGMENT_PREFIX.len();
+    let pid = &file_name[start..];
+    pid[..pid.find('-')?].parse().ok()
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    // Signal 0 only checks whether the process exists, which it does if it belongs to another
+    // user.
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+    || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+fn is_running(pid: u32) -> bool {
+    use winapi::{
+        shared::winerror::ERROR_ACCESS_DENIED,
+        um::{
+            handleapi::CloseHandle,
+            minwinbase::STILL_ACTIVE,
+            processthreadsapi::{GetExitCodeProcess, OpenProcess},
+            winnt::PROCESS_QUERY_LIMITED_INFORMATION,
+        },
+    };
+
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            // The process exited, or belongs to another user, whose segments are kept.
+            return std::io::Error::last_os_error().raw_os_error()
+                == Some(ERROR_ACCESS_DENIED as i32);
+        }
+        let mut exit_code = 0;
+        let running = GetExitCodeProcess(handle, &mut exit_code) == 0 || exit_code == STILL_ACTIVE;
+        CloseHandle(handle);
+        running
+    }
+}
+
+/// Returns the name, type, and value of the utilization metrics of the shared memory pools.
+pub(crate) fn metrics() -> Vec<(&'static str, &'static str, i64)> {
+    vec![
+        (
+            "erdos_shm_used_bytes",
+            "gauge",
+            USED_BYTES.load(Ordering::SeqCst),
+        ),
+        (
+            "erdos_shm_capacity_bytes",
+            "gauge",
+            CAPACITY_BYTES.load(Ordering::SeqCst),
+        ),
+        (
+            "erdos_shm_overflows_total",
+            "counter",
+            OVERFLOWS.load(Ordering::SeqCst) as i64,
+        ),
+        (
+            "erdos_shm_fallbacks_total",
+            "counter",
+            FALLBACKS.load(Ordering::SeqCst) as i64,
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that buffers are allocated from the next segment once a segment is full.
+    #[test]
+    fn test_shm_pool_overflow() {
+        let config = ShmPoolConfig {
+            segment_size: 1024,
+            num_segments: 2,
+            overflow_policy: ShmOverflowPolicy::Serialize,
+            huge_pages: false,
+        };
+        let mut pool = ShmPool::new("erdos-test-shm-pool", &config).unwrap();
+        let first = pool.alloc(1000);
+        let second = pool.alloc(1000);
+        assert!(first.is_some() && second.is_some());
+        assert_eq!(pool.used_bytes, 2000);
+        assert!(pool.alloc(1000).is_none());
+        assert_eq!(pool.overflow_policy(), ShmOverflowPolicy::Serialize);
+    }
+
+    /// Test that the process which created a segment is parsed from the name of its file.
+    #[test]
+    fn test_segment_pid() {
+        assert_eq!(
+            segment_pid("zenoh_shm_erdos-p4242-from-0-to-1-data-af01-0"),
+            Some(4242)
+        );
+        assert_eq!(segment_pid("zenoh_shm_other-segment"), None);
+        assert_eq!(segment_pid("erdos-p12"), None);
+        assert!(is_running(process::id()));
+    }
+}
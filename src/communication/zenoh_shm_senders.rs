@@ -13,10 +13,12 @@ use tokio::{
 use zenoh::net;
 
 use crate::communication::{
-    CodecError, CommunicationError, ControlMessage, ControlMessageHandler, InterProcessMessage,
+    Chunker, CodecError, CommunicationError, ControlMessage, ControlMessageHandler, InterProcessMessage,
+    SequenceNumbers, SerializationPool, ShapedReceiver, ShmPool, ShmPoolConfig, StreamCompressor, TrafficShaper,
 };
-use crate::node::NodeId;
+use crate::node::{latency, stream_metrics, NodeId};
 use crate::scheduler::endpoints_manager::ChannelsToSenders;
+use crate::ShmOverflowPolicy;
 
 #[allow(dead_code)]
 /// The [`ZenohDataSender`] pulls messages from a FIFO inter-thread channel.
@@ -30,12 +32,24 @@ pub(crate) struct ZenohShmDataSender {
     self_node_id: NodeId,
     /// Zenoh Session
     zsession: Arc<net::Session>,
-    /// Tokio channel receiver on which to receive data from worker threads.
-    rx: UnboundedReceiver<InterProcessMessage>,
+    /// Receives the data from worker threads at the rate allowed by the traffic shaper.
+    rx: ShapedReceiver,
     /// Tokio channel sender to `ControlMessageHandler`.
     control_tx: UnboundedSender<ControlMessage>,
     /// Tokio channel receiver from `ControlMessageHandler`.
     control_rx: UnboundedReceiver<ControlMessage>,
+    /// Maximum size of a message sent to the other node. Unlimited if `None`.
+    max_message_size: Option<usize>,
+    /// Splits messages larger than the maximum chunk size.
+    chunker: Chunker,
+    /// Compresses the messages of streams with a zstd dictionary.
+    compressor: StreamCompressor,
+    /// Numbers the messages of each stream, so that the receiver detects lost messages.
+    sequences: SequenceNumbers,
+    /// Serializes large messages off the event loop, if set.
+    serialization_pool: Option<SerializationPool>,
+    /// Sizes the shared memory from which messages are allocated.
+    shm_config: ShmPoolConfig,
 }
 
 #[cfg(feature = "zenoh_zerocopy_transport")]
@@ -46,6 +60,10 @@ impl ZenohShmDataSender {
         zsession: Arc<net::Session>,
         channels_to_senders: Arc<Mutex<ChannelsToSenders>>,
         control_handler: &mut ControlMessageHandler,
+        max_message_size: Option<usize>,
+        max_chunk_size: Option<usize>,
+        compressor: StreamCompressor,
+        shaper: TrafficShaper,
     ) -> Self {
         // Create a channel for this stream.
         let (tx, rx) = mpsc::unbounded_channel();
@@ -59,9 +77,55 @@ impl ZenohShmDataSender {
             self_node_id,
             // sink,
             zsession,
-            rx,
+            rx: ShapedReceiver::new(node_id, rx, shaper),
             control_tx: control_handler.get_channel_to_handler(),
             control_rx,
+            max_message_size,
+            chunker: Chunker::new(max_chunk_size),
+            compressor,
+            sequences: SequenceNumbers::default(),
+            serialization_pool: None,
+            shm_config: ShmPoolConfig::default(),
+        }
+    }
+
+    /// Sets the size of the shared memory and what happens when it is full.
+    pub(crate) fn with_shm_config(mut self, shm_config: ShmPoolConfig) -> Self {
+        self.shm_config = shm_config;
+        self
+    }
+
+    /// Makes the sender serialize large messages on the threads of the pool.
+    pub(crate) fn with_serialization_pool(mut self, pool: Option<SerializationPool>) -> Self {
+        self.serialization_pool = pool;
+        self
+    }
+
+    /// Notifies the other node that the remaining chunks of `chunk`'s message will not be sent.
+    async fn cancel(
+        &self,
+        reskey: &zenoh::net::protocol::core::ResKey,
+        chunk: &InterProcessMessage,
+        shm: &mut Option<ShmPool>,
+    ) {
+        if let Some(cancellation) = Chunker::cancellation(chunk) {
+            let rbf = match shm {
+                Some(shm) => cancellation.into_sbuf(shm).map(zenoh::net::RBuf::from),
+                None => cancellation.into_rbuf(),
+            };
+            if let Ok(rbf) = rbf {
+                // Best effort: the receiver also discards the message if the next chunk is missing.
+                let _ = self
+                    .zsession
+                    .write_ext(
+                        reskey,
+                        rbf,
+                        zenoh::net::encoding::DEFAULT,
+                        zenoh::net::data_kind::DEFAULT,
+                        zenoh::net::protocol::core::CongestionControl::Block,
+                    )
+                    .await;
+            }
         }
     }
 
@@ -69,8 +133,19 @@ impl ZenohShmDataSender {
 
         let id = format!("from-{}-to-{}-data-{}", self.self_node_id, self.node_id, self.zsession.id().await);
 
-        let mut shm =
-            zenoh::net::SharedMemoryManager::new(id, crate::SHM_SIZE).map_err(CommunicationError::from)?;
+        // Send serialized copies if the shared memory cannot be created, e.g. in another container.
+        let mut shm = match ShmPool::new(&id, &self.shm_config) {
+            Ok(shm) => Some(shm),
+            Err(e) => {
+                slog::warn!(
+                    crate::TERMINAL_LOGGER,
+                    "ZenohShmDataSender to node {}: unable to create shared memory, sending copies: {:?}",
+                    self.node_id,
+                    e
+                );
+                None
+            }
+        };
 
         let res_name = format!("/from/{}/to/{}/data", self.self_node_id, self.node_id);
 
@@ -102,45 +177,84 @@ impl ZenohShmDataSender {
                     k += 1;
 
                     if k % 256 == 0 {
-                        shm.garbage_collect();
+                        if let Some(shm) = shm.as_mut() {
+                            shm.garbage_collect();
+                        }
                     }
-                    // Sending over Zenoh-net
-
-                    let sbuf = loop {
-                        match msg.into_sbuf(&mut shm) {
-                            Ok(buf) => break Ok(buf),
-                            Err(CodecError::ZenohSharedMemoryError(e)) => {
-                                // println!(
-                                //     "### Unable to allocate on DataSender - Manager: {:?} {}",
-                                //     shm, e
-                                // );
-                                tokio::time::delay_for(tokio::time::Duration::from_millis(500))
-                                    .await;
-                                shm.garbage_collect();
-                            }
-                            Err(e) => break Err(e),
+                    let msg = self.sequences.number(msg);
+                    let msg = latency::serialize_timed(msg).map_err(CommunicationError::from)?;
+                    let msg = match &self.serialization_pool {
+                        Some(pool) => pool.serialize(msg).await.map_err(CommunicationError::from)?,
+                        None => msg,
+                    };
+                    let msg = stream_metrics::message_sent(msg).map_err(CommunicationError::from)?;
+                    let (msg, dictionary) =
+                        self.compressor.compress(msg).map_err(CommunicationError::from)?;
+                    if let Some(dictionary) = dictionary {
+                        // The receiver buffers compressed messages until the dictionary arrives.
+                        self.control_tx
+                            .send(ControlMessage::StreamDictionary(dictionary))
+                            .map_err(CommunicationError::from)?;
+                    }
+                    let chunks = self.chunker.split(msg).map_err(CommunicationError::from)?;
+                    for chunk in chunks {
+                        if let Err(e) = chunk.check_size(self.max_message_size) {
+                            slog::error!(
+                                crate::TERMINAL_LOGGER,
+                                "ZenohShmDataSender to node {}: dropping message: {:?}",
+                                self.node_id,
+                                e
+                            );
+                            self.cancel(&reskey, &chunk, &mut shm).await;
+                            break;
                         }
-                    }?;
+                        // Sending over Zenoh-net
 
-                    // let sbuf = msg.into_sbuf(&mut shm).map_err(CommunicationError::from)?;
-
-                    let rbf = zenoh::net::RBuf::from(sbuf);
+                        // Watermark frames are too small to be worth allocating in shared memory.
+                        let rbf = match shm.as_mut() {
+                            Some(shm) if chunk.metadata().watermark_frame().is_none() => loop {
+                                match chunk.into_sbuf(shm) {
+                                    Ok(buf) => break Ok(zenoh::net::RBuf::from(buf)),
+                                    Err(CodecError::ZenohSharedMemoryError(_)) => match shm.overflow_policy() {
+                                        ShmOverflowPolicy::Backpressure => {
+                                            // Wait for the other node to release buffers.
+                                            tokio::time::delay_for(tokio::time::Duration::from_millis(500))
+                                                .await;
+                                        }
+                                        ShmOverflowPolicy::Serialize => {
+                                            shm.record_fallback();
+                                            break chunk.into_rbuf();
+                                        }
+                                    },
+                                    Err(e) => break Err(e),
+                                }
+                            },
+                            _ => chunk.into_rbuf(),
+                        };
+                        let rbf = match rbf {
+                            Ok(rbf) => rbf,
+                            Err(e) => {
+                                self.cancel(&reskey, &chunk, &mut shm).await;
+                                return Err(CommunicationError::from(e));
+                            }
+                        };
 
-                    if let Err(e) = self
-                        .zsession
-                        .write_ext(
-                            &reskey,
-                            rbf,
-                            zenoh::net::encoding::DEFAULT,
-                            zenoh::net::data_kind::DEFAULT,
-                            zenoh::net::protocol::core::CongestionControl::Block,
-                        )
-                        .await
-                        .map_err(CommunicationError::from)
-                    {
-                        return Err(e);
+                        if let Err(e) = self
+                            .zsession
+                            .write_ext(
+                                &reskey,
+                                rbf,
+                                zenoh::net::encoding::DEFAULT,
+                                zenoh::net::data_kind::DEFAULT,
+                                zenoh::net::protocol::core::CongestionControl::Block,
+                            )
+                            .await
+                            .map_err(CommunicationError::from)
+                        {
+                            self.cancel(&reskey, &chunk, &mut shm).await;
+                            return Err(e);
+                        }
                     }
-                    //
                 }
                 None => return Err(CommunicationError::Disconnected),
             }
@@ -1,22 +1,226 @@
-use std::net::SocketAddr;
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use crate::node::NodeId;
+#[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
+use zenoh::net::config::{self, ConfigProperties};
+
+use crate::{
+    communication::Authenticator,
+    node::{AdminRole, CheckpointBackend, FileSystemBackend, NodeId, ResourceLimits},
+    scheduler::{self, Scheduler},
+    Deployment,
+};
+
+/// Size in bytes of the shared memory segments of the zero-copy transport by default.
+pub(crate) const DEFAULT_SHM_SEGMENT_SIZE: usize = 512 * 1024 * 1024;
+
+/// What the zero-copy transport does when its shared memory is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShmOverflowPolicy {
+    /// Waits until the other node releases enough shared memory for the message, which slows
+    /// down the operators sending to the node.
+    Backpressure,
+    /// Sends the message as a serialized copy, like the Zenoh transport.
+    Serialize,
+}
+
+/// How a node runs its operators.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecutorModel {
+    /// Operators run as tasks of a runtime whose worker threads steal tasks from each other.
+    WorkStealing,
+    /// Each worker thread runs a single-threaded runtime, and operators are assigned to the
+    /// threads in turn, so that an operator never moves to another thread. Avoids the jitter of
+    /// work stealing for real-time pipelines, at the cost of balancing the load.
+    ///
+    /// Only the placement of the operators changes: operators on different threads exchange
+    /// messages on the same channels as with [`WorkStealing`](ExecutorModel::WorkStealing). Each
+    /// channel has a single sending and a single receiving operator, but the executor does not
+    /// replace the channels with dedicated single-producer single-consumer queues.
+    ThreadPerCore,
+}
+
+/// Whether the Zenoh session of a node discovers and connects to other peers, or connects
+/// through routers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZenohMode {
+    /// Connects to the peers it scouts and to the configured peers.
+    Peer,
+    /// Only connects to the configured routers.
+    Client,
+}
 
 /// Stores the configuration parameters of a [`node`](crate::node::Node).
 #[derive(Clone)]
 pub struct Configuration {
     /// The index of the node.
     pub index: NodeId,
-    /// The number of worker threads the node has.
+    /// The number of worker threads the node has. Uses the CPUs available to the process, e.g.
+    /// the CPU quota of its container, if 0.
     pub num_worker_threads: usize,
     /// Mapping between node indices and data socket addresses.
     pub data_addresses: Vec<SocketAddr>,
+    /// Mapping between node indices and the paths of the Unix domain sockets on which the nodes
+    /// receive data messages, when all nodes run on the same host. Used instead of the data
+    /// addresses with the `uds_transport` feature, which avoids the overhead of TCP on the
+    /// loopback interface, unless connections are multiplexed or lazy. Set from the command line
+    /// if the data addresses are paths.
+    pub data_socket_paths: Option<Vec<PathBuf>>,
     /// Mapping between node indices and control socket addresses.
     pub control_addresses: Vec<SocketAddr>,
     /// System-level logger.
     pub logger: slog::Logger,
-    /// DOT file to export dataflow graph.
+    /// DOT file to export dataflow graph, or JSON file if the filename ends with `.json`.
     pub graph_filename: Option<String>,
+    /// JSON file to which the node writes the placement of the dataflow on nodes when it plans
+    /// the dataflow with [`Node::plan`](crate::node::Node::plan).
+    pub plan_filename: Option<String>,
+    /// Whether [`Node::run`](crate::node::Node::run) only plans the dataflow, prints the
+    /// placement of its operators, and returns without running the dataflow, e.g. to validate
+    /// the dataflow in CI.
+    pub dry_run: bool,
+    /// Maximum size in bytes of a serialized message sent to another node.
+    /// Larger messages are rejected when sent, and compressed messages which are larger once
+    /// decompressed are dropped when received, unless messages are split into chunks.
+    /// Unlimited if `None`.
+    pub max_message_size: Option<usize>,
+    /// Maximum size in bytes of a chunk sent to another node. Larger messages are split into
+    /// chunks and reassembled by the receiving node. Messages are never split if `None`.
+    pub max_chunk_size: Option<usize>,
+    /// File which the node updates periodically while it is healthy, so that process
+    /// supervisors can restart wedged nodes. Disabled if `None`.
+    pub liveness_file: Option<String>,
+    /// Interval at which the node updates the liveness file and notifies the systemd watchdog.
+    /// Shortened to half of the systemd watchdog timeout if it is set.
+    pub watchdog_interval: Duration,
+    /// Address on which the node serves the `/healthz`, `/readyz`, and `/metrics` endpoints over
+    /// HTTP, and the `POST /knobs/<name>` and `POST /shutdown` control commands.
+    /// Disabled if `None`.
+    pub admin_address: Option<SocketAddr>,
+    /// Roles of the tokens which clients of the admin address pass in an
+    /// `Authorization: Bearer <token>` header. If empty, the endpoints are served to anyone and
+    /// the control commands are disabled.
+    pub admin_tokens: HashMap<String, AdminRole>,
+    /// Address on which the node serves only the `/metrics` endpoint over HTTP, without
+    /// authentication, e.g. for a Prometheus server. The messages of the streams sent between
+    /// nodes are counted and timed if it or the admin address is set. Disabled if `None`.
+    pub metrics_address: Option<SocketAddr>,
+    /// Duration after which an operator running a callback is considered stalled, which makes
+    /// the node unhealthy.
+    pub stall_timeout: Duration,
+    /// Interval at which the node checks that the other nodes are alive with the Zenoh
+    /// transports.
+    pub peer_check_interval: Duration,
+    /// Duration within which a peer must reply to a liveliness check.
+    pub peer_check_timeout: Duration,
+    /// Number of consecutive liveliness checks to which a peer does not reply after which the
+    /// peer is considered failed, which tears down the dataflow on all nodes.
+    pub peer_check_misses: usize,
+    /// Interval at which the node sends heartbeats to the other nodes, which must not be zero.
+    pub heartbeat_interval: Duration,
+    /// Duration after which a node which sent no heartbeat is considered failed, which tears
+    /// down the dataflow on all nodes.
+    pub heartbeat_timeout: Duration,
+    /// Duration after which the node fails if it has not discovered all other nodes with the
+    /// Zenoh transports.
+    pub discovery_timeout: Duration,
+    /// Whether control and data messages share a single connection to each other node with the
+    /// TCP transport. Control messages take priority over data messages on shared connections.
+    pub multiplex_connections: bool,
+    /// Whether the node connects to another node with the TCP transport upon the first message
+    /// it sends to the node, instead of at startup. Messages sent before the connection completes
+    /// are buffered. The streams passed to
+    /// [`default_graph::warm_up_stream`](crate::dataflow::graph::default_graph::warm_up_stream)
+    /// still connect at startup.
+    pub lazy_connections: bool,
+    /// Whether the node sends messages to other nodes with the TCP transport with compact
+    /// headers, which refer to the stream by an index defined on the connection and encode the
+    /// sizes as varints. Reduces the overhead of small messages, e.g. poses or booleans.
+    pub compact_headers: bool,
+    /// Number of threads which serialize the large messages sent to other nodes, so that
+    /// serializing them does not delay the network tasks. Messages are serialized on the
+    /// network tasks if 0.
+    pub serialization_threads: usize,
+    /// Duration after which the node fails if an operator has not finished setting up, unless
+    /// the operator sets its own [setup timeout](crate::dataflow::OperatorConfig::setup_timeout).
+    pub setup_timeout: Duration,
+    /// Duration after which a callback is slow, which logs a warning with the timestamp of the
+    /// callback, unless the operator sets its own
+    /// [threshold](crate::dataflow::OperatorConfig::slow_callback_threshold). The durations of
+    /// the callbacks are exported as metrics.
+    pub slow_callback_threshold: Duration,
+    /// Size in bytes of each segment of shared memory the zero-copy transport allocates
+    /// messages from, which is shrunk if the segments for all nodes would take more than half of
+    /// the memory available to the process. The utilization of the segments is exported as
+    /// metrics. The transport
+    /// sends serialized copies to the nodes for which the shared memory cannot be created.
+    pub shm_segment_size: usize,
+    /// Number of segments of shared memory the zero-copy transport allocates for each other
+    /// node.
+    pub shm_num_segments: usize,
+    /// What the zero-copy transport does when all segments are full.
+    pub shm_overflow_policy: ShmOverflowPolicy,
+    /// Directory in which the zero-copy transport creates the files through which other nodes
+    /// open its shared memory. Nodes in different containers on the same host share memory if
+    /// they mount the same directory and share `/dev/shm`, e.g. by bind-mounting it. Uses the
+    /// temporary directory of the process if `None`. The shared memory left in the directory by
+    /// crashed processes is removed when the node starts, on Linux, macOS, and Windows.
+    pub shm_directory: Option<PathBuf>,
+    /// Directory to which the node logs the control messages it exchanges with other nodes, with
+    /// their direction, peer, and time, in `control-<index>.log`. The logs of all nodes can be
+    /// merged into a timeline with the `erdos-control-timeline` tool to debug hangs.
+    pub control_log_directory: Option<PathBuf>,
+    /// Whether the zero-copy transport backs its shared memory with transparent huge pages,
+    /// which reduces TLB misses when sending large messages. Segments are rounded up to a
+    /// multiple of the huge page size. Regular pages are used if huge pages are unavailable.
+    pub shm_huge_pages: bool,
+    /// How the node runs its operators on its worker threads.
+    pub executor: ExecutorModel,
+    /// Whether the threads of the [`ExecutorModel::ThreadPerCore`] executor are pinned to CPUs,
+    /// and each data receiver runs on the thread of the operators which read the streams it
+    /// receives, so large payloads stay in the cache of one core. Pinning requires Linux and the
+    /// `core_affinity` feature.
+    pub core_affinity: bool,
+    /// Labels of each node, indexed by node ID, e.g. `gpu=true` or `zone=vehicle`, which
+    /// [node selectors](crate::dataflow::OperatorConfig::with_node_selector) match to place
+    /// operators. Every node must have the same labels for all nodes.
+    pub node_labels: Vec<HashMap<String, String>>,
+    /// Places the movable operators on nodes, e.g. a [`LoadAwareScheduler`]. Operators stay on
+    /// their [node](crate::dataflow::OperatorConfig::node) if `None`. Every node must use the same
+    /// scheduler.
+    ///
+    /// [`LoadAwareScheduler`]: crate::scheduler::LoadAwareScheduler
+    pub scheduler: Option<Arc<dyn Scheduler>>,
+    /// Authenticates the nodes which connect to the node with the TCP transport, so that other
+    /// processes cannot send control messages to the node. Every node must use the same
+    /// authenticator. Connections are not authenticated if `None`. The Zenoh transports do not
+    /// authenticate their data or control messages, so [`Node::new`](crate::node::Node::new)
+    /// panics if an authenticator is set with them.
+    pub authenticator: Option<Arc<dyn Authenticator>>,
+    /// Stores the checkpoints of the [stateful operators](crate::dataflow::StatefulOperator) of
+    /// the node, which are restored from their latest checkpoint when they start. Operators are
+    /// not checkpointed if `None`.
+    pub checkpoint_backend: Option<Arc<dyn CheckpointBackend>>,
+    /// Interval at which the leader node, with index 0, asks all nodes to take a checkpoint,
+    /// which must not be zero.
+    pub checkpoint_interval: Duration,
+    /// Mode of the Zenoh session with which the node communicates with the Zenoh transports.
+    pub zenoh_mode: ZenohMode,
+    /// Locators of the Zenoh routers or peers to which the Zenoh session connects, e.g.
+    /// `tcp/10.0.0.1:7447`. Required by [`ZenohMode::Client`].
+    pub zenoh_peers: Vec<String>,
+    /// Network interface on which the Zenoh session scouts for peers. Chosen by Zenoh if
+    /// `None`.
+    pub zenoh_multicast_interface: Option<String>,
+    /// Duration during which the Zenoh session scouts for peers when it opens. Uses the default
+    /// of Zenoh if `None`.
+    pub zenoh_scouting_delay: Option<Duration>,
 }
 
 impl Configuration {
@@ -28,30 +232,225 @@ impl Configuration {
         num_worker_threads: usize,
         graph_filename: Option<String>,
     ) -> Self {
+        let num_nodes = data_addresses.len();
         Self {
             index: node_index,
             num_worker_threads,
             data_addresses,
+            data_socket_paths: None,
             control_addresses,
             logger: crate::get_terminal_logger(),
             graph_filename,
+            plan_filename: None,
+            dry_run: false,
+            max_message_size: None,
+            max_chunk_size: None,
+            liveness_file: None,
+            watchdog_interval: Duration::from_secs(1),
+            admin_address: None,
+            admin_tokens: HashMap::new(),
+            metrics_address: None,
+            stall_timeout: Duration::from_secs(30),
+            peer_check_interval: Duration::from_millis(500),
+            peer_check_timeout: Duration::from_millis(500),
+            peer_check_misses: 3,
+            heartbeat_interval: Duration::from_secs(1),
+            heartbeat_timeout: Duration::from_secs(10),
+            discovery_timeout: Duration::from_secs(300),
+            multiplex_connections: false,
+            lazy_connections: false,
+            compact_headers: false,
+            serialization_threads: 0,
+            setup_timeout: Duration::from_secs(300),
+            slow_callback_threshold: Duration::from_secs(1),
+            shm_segment_size: DEFAULT_SHM_SEGMENT_SIZE,
+            shm_num_segments: 1,
+            shm_overflow_policy: ShmOverflowPolicy::Backpressure,
+            shm_directory: None,
+            control_log_directory: None,
+            shm_huge_pages: false,
+            executor: ExecutorModel::WorkStealing,
+            core_affinity: false,
+            node_labels: vec![HashMap::new(); num_nodes],
+            scheduler: None,
+            authenticator: None,
+            checkpoint_backend: None,
+            checkpoint_interval: Duration::from_secs(10),
+            zenoh_mode: ZenohMode::Peer,
+            zenoh_peers: Vec::new(),
+            zenoh_multicast_interface: None,
+            zenoh_scouting_delay: None,
+        }
+    }
+
+    /// Returns the number of worker threads of the node, which are detected from the
+    /// [resources](ResourceLimits) available to the process if `num_worker_threads` is 0.
+    pub fn worker_threads(&self) -> usize {
+        match self.num_worker_threads {
+            0 => ResourceLimits::detect().worker_threads(),
+            num_threads => num_threads,
         }
     }
 
+    /// Sets how the node runs its operators.
+    pub fn with_executor(mut self, executor: ExecutorModel) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// Makes the nodes send data messages to each other on the Unix domain sockets at the paths,
+    /// indexed by node ID, with the `uds_transport` feature.
+    pub fn with_data_socket_paths<P: AsRef<Path>>(mut self, paths: &[P]) -> Self {
+        assert_eq!(
+            paths.len(),
+            self.data_addresses.len(),
+            "Each node must have 1 data socket path"
+        );
+        self.data_socket_paths = Some(paths.iter().map(|p| p.as_ref().to_path_buf()).collect());
+        self
+    }
+
+    /// Sets how the node places the movable operators on nodes.
+    pub fn with_scheduler<S: 'static + Scheduler>(mut self, scheduler: S) -> Self {
+        self.scheduler = Some(Arc::new(scheduler));
+        self
+    }
+
+    /// Makes the node authenticate the nodes which connect to it, and authenticate itself to the
+    /// nodes to which it connects.
+    pub fn with_authenticator<A: 'static + Authenticator>(mut self, authenticator: A) -> Self {
+        self.authenticator = Some(Arc::new(authenticator));
+        self
+    }
+
+    /// Makes the node checkpoint its stateful operators in the backend at the interval, and
+    /// restore them from their latest checkpoint when they start.
+    pub fn with_checkpoints<B: 'static + CheckpointBackend>(
+        mut self,
+        backend: B,
+        interval: Duration,
+    ) -> Self {
+        self.checkpoint_backend = Some(Arc::new(backend));
+        self.checkpoint_interval = interval;
+        self
+    }
+
+    /// Allows the clients of the admin address which pass the token to send the requests of the
+    /// role.
+    pub fn with_admin_token(mut self, token: &str, role: AdminRole) -> Self {
+        self.admin_tokens.insert(token.to_string(), role);
+        self
+    }
+
+    /// Makes the Zenoh session of the node connect to a router or peer, e.g.
+    /// `tcp/10.0.0.1:7447`.
+    pub fn with_zenoh_peer(mut self, locator: &str) -> Self {
+        self.zenoh_peers.push(locator.to_string());
+        self
+    }
+
+    /// Returns the configuration of the Zenoh session of the node.
+    #[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
+    pub(crate) fn zenoh_config(&self) -> ConfigProperties {
+        let mut zconfig = match self.zenoh_mode {
+            ZenohMode::Peer => config::peer(),
+            ZenohMode::Client => config::client(None),
+        };
+        if !self.zenoh_peers.is_empty() {
+            zconfig.insert(config::ZN_PEER_KEY, self.zenoh_peers.join(","));
+        }
+        if let Some(interface) = &self.zenoh_multicast_interface {
+            zconfig.insert(config::ZN_MULTICAST_INTERFACE_KEY, interface.clone());
+        }
+        if let Some(delay) = self.zenoh_scouting_delay {
+            // Zenoh expects the delay in seconds.
+            zconfig.insert(
+                config::ZN_SCOUTING_DELAY_KEY,
+                delay.as_secs_f64().to_string(),
+            );
+        }
+        zconfig
+    }
+
+    /// Adds a label to the node, e.g. `gpu` with the value `true`.
+    pub fn with_node_label(mut self, node_id: NodeId, key: &str, value: &str) -> Self {
+        self.node_labels[node_id].insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Adds the comma-separated labels to the node, e.g. `gpu=true,zone=vehicle`.
+    pub fn with_node_labels(mut self, node_id: NodeId, labels: &str) -> Self {
+        self.node_labels[node_id].extend(scheduler::parse_labels(labels));
+        self
+    }
+
+    /// Loads the configuration of the node with the index from a [`Deployment`] file, which
+    /// describes every node, so that a single binary can run any node.
+    #[cfg(feature = "cli")]
+    pub fn from_file<P: AsRef<Path>>(path: P, index: NodeId) -> io::Result<Self> {
+        Deployment::load(path)?
+            .configuration(index)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Creates a node configuration from the command line arguments of the process, which are
+    /// defined by [`new_app`](crate::new_app).
+    #[cfg(feature = "cli")]
+    pub fn from_command_line() -> Self {
+        Self::from_args(&crate::new_app("erdos").get_matches())
+    }
+
     /// Creates a node configuration from command line arguments.
+    ///
+    /// Options which are not passed on the command line are read from the [`Deployment`] file
+    /// passed with `--config`, if any.
+    #[cfg(feature = "cli")]
     pub fn from_args(args: &clap::ArgMatches) -> Self {
-        let num_threads = args
+        let file = args.value_of("config").map(|path| {
+            let index = args
+                .value_of("index")
+                .unwrap()
+                .parse()
+                .expect("Unable to parse node index");
+            let deployment = Deployment::load(path)
+                .unwrap_or_else(|e| panic!("Unable to load the deployment {}: {}", path, e));
+            crate::new_app("erdos")
+                .get_matches_from_safe(deployment.args(index))
+                .unwrap_or_else(|e| panic!("Invalid deployment {}: {}", path, e.message))
+        });
+        let options = Options { args, file };
+        let num_threads = options
             .value_of("threads")
             .unwrap()
             .parse()
             .expect("Unable to parse number of worker threads");
 
-        let data_addrs = args.value_of("data-addresses").unwrap();
+        let data_addrs = options.value_of("data-addresses").unwrap();
         let mut data_addresses: Vec<SocketAddr> = Vec::new();
+        let mut data_socket_paths: Vec<PathBuf> = Vec::new();
         for addr in data_addrs.split(",") {
-            data_addresses.push(addr.parse().expect("Unable to parse socket address"));
+            if addr.contains('/') {
+                data_socket_paths.push(PathBuf::from(addr));
+            } else {
+                data_addresses.push(addr.parse().expect("Unable to parse socket address"));
+            }
         }
-        let control_addrs = args.value_of("control-addresses").unwrap();
+        let data_socket_paths = if data_socket_paths.is_empty() {
+            None
+        } else {
+            assert!(
+                data_addresses.is_empty(),
+                "The data addresses must be all socket addresses or all paths"
+            );
+            assert!(
+                cfg!(feature = "uds_transport"),
+                "Data addresses which are paths require the uds_transport feature"
+            );
+            // The data addresses only count the nodes, as the data planes use the paths.
+            data_addresses = vec![SocketAddr::from(([127, 0, 0, 1], 0)); data_socket_paths.len()];
+            Some(data_socket_paths)
+        };
+        let control_addrs = options.value_of("control-addresses").unwrap();
         let mut control_addresses: Vec<SocketAddr> = Vec::new();
         for addr in control_addrs.split(",") {
             control_addresses.push(addr.parse().expect("Unable to parse socket address"));
@@ -61,7 +460,7 @@ impl Configuration {
             control_addresses.len(),
             "Each node must have 1 data address and 1 control address"
         );
-        let node_index = args
+        let node_index = options
             .value_of("index")
             .unwrap()
             .parse()
@@ -70,19 +469,294 @@ impl Configuration {
             node_index < data_addresses.len(),
             "Node index is larger than number of available nodes"
         );
-        let graph_filename_arg = args.value_of("graph-filename").unwrap();
+        let graph_filename_arg = options.value_of("graph-filename").unwrap();
         let graph_filename = if graph_filename_arg == "" {
             None
         } else {
             Some(graph_filename_arg.to_string())
         };
+        let plan_filename = options.value_of("plan-filename").map(String::from);
+        let dry_run = options.is_present("dry-run");
+        let max_message_size = options.value_of("max-message-size").map(|size| {
+            size.parse()
+                .expect("Unable to parse the maximum message size")
+        });
+        let max_chunk_size = options.value_of("max-chunk-size").map(|size| {
+            size.parse()
+                .expect("Unable to parse the maximum chunk size")
+        });
+        if let (Some(chunk_size), Some(message_size)) = (max_chunk_size, max_message_size) {
+            assert!(
+                chunk_size <= message_size,
+                "The maximum chunk size must not exceed the maximum message size"
+            );
+        }
+        let liveness_file = options.value_of("liveness-file").map(String::from);
+        let watchdog_interval = Duration::from_millis(
+            options
+                .value_of("watchdog-interval")
+                .unwrap()
+                .parse()
+                .expect("Unable to parse the watchdog interval"),
+        );
+        let admin_address = options.value_of("admin-address").map(|addr| {
+            addr.parse()
+                .expect("Unable to parse the admin socket address")
+        });
+        let metrics_address = options.value_of("metrics-address").map(|addr| {
+            addr.parse()
+                .expect("Unable to parse the metrics socket address")
+        });
+        let admin_tokens = options
+            .value_of("admin-tokens-file")
+            .map(|path| {
+                crate::node::read_admin_tokens(path)
+                    .unwrap_or_else(|e| panic!("Unable to read the admin tokens {}: {}", path, e))
+            })
+            .unwrap_or_default();
+        let stall_timeout = Duration::from_secs(
+            options
+                .value_of("stall-timeout")
+                .unwrap()
+                .parse()
+                .expect("Unable to parse the stall timeout"),
+        );
+        let peer_check_interval = Duration::from_millis(
+            options
+                .value_of("peer-check-interval")
+                .unwrap()
+                .parse()
+                .expect("Unable to parse the peer check interval"),
+        );
+        let peer_check_timeout = Duration::from_millis(
+            options
+                .value_of("peer-check-timeout")
+                .unwrap()
+                .parse()
+                .expect("Unable to parse the peer check timeout"),
+        );
+        let peer_check_misses = options
+            .value_of("peer-check-misses")
+            .unwrap()
+            .parse()
+            .expect("Unable to parse the number of peer check misses");
+        let heartbeat_interval = Duration::from_millis(
+            options
+                .value_of("heartbeat-interval")
+                .unwrap()
+                .parse()
+                .expect("Unable to parse the heartbeat interval"),
+        );
+        let heartbeat_timeout = Duration::from_millis(
+            options
+                .value_of("heartbeat-timeout")
+                .unwrap()
+                .parse()
+                .expect("Unable to parse the heartbeat timeout"),
+        );
+        let discovery_timeout = Duration::from_secs(
+            options
+                .value_of("discovery-timeout")
+                .unwrap()
+                .parse()
+                .expect("Unable to parse the discovery timeout"),
+        );
+        let setup_timeout = Duration::from_secs(
+            options
+                .value_of("setup-timeout")
+                .unwrap()
+                .parse()
+                .expect("Unable to parse the setup timeout"),
+        );
+        let slow_callback_threshold = Duration::from_millis(
+            options
+                .value_of("slow-callback-threshold")
+                .unwrap()
+                .parse()
+                .expect("Unable to parse the slow callback threshold"),
+        );
+        let shm_segment_size = options
+            .value_of("shm-segment-size")
+            .unwrap()
+            .parse()
+            .expect("Unable to parse the shared memory segment size");
+        let shm_num_segments = options
+            .value_of("shm-num-segments")
+            .unwrap()
+            .parse()
+            .expect("Unable to parse the number of shared memory segments");
+        assert!(
+            shm_num_segments > 0,
+            "At least 1 shared memory segment is required"
+        );
+        let shm_overflow_policy = match options.value_of("shm-overflow-policy").unwrap() {
+            "serialize" => ShmOverflowPolicy::Serialize,
+            _ => ShmOverflowPolicy::Backpressure,
+        };
+        let shm_directory = options.value_of("shm-directory").map(PathBuf::from);
+        let control_log_directory = options.value_of("control-log-directory").map(PathBuf::from);
+        let shm_huge_pages = options.is_present("shm-huge-pages");
+        let executor = match options.value_of("executor").unwrap() {
+            "thread-per-core" => ExecutorModel::ThreadPerCore,
+            _ => ExecutorModel::WorkStealing,
+        };
+        let core_affinity = options.is_present("core-affinity");
+        let multiplex_connections = options.is_present("multiplex-connections");
+        let lazy_connections = options.is_present("lazy-connections");
+        assert!(
+            !(multiplex_connections && lazy_connections),
+            "Multiplexed connections cannot be established lazily"
+        );
+        let compact_headers = options.is_present("compact-headers");
+        let serialization_threads = options
+            .value_of("serialization-threads")
+            .unwrap()
+            .parse()
+            .expect("Unable to parse the number of serialization threads");
+        let scheduler = options.value_of("scheduler").map(|name| match name {
+            "round-robin" => Arc::new(scheduler::RoundRobinScheduler) as Arc<dyn Scheduler>,
+            "load-aware" => Arc::new(scheduler::LoadAwareScheduler::new()),
+            "constraint" => Arc::new(scheduler::ConstraintScheduler),
+            _ => panic!("Unknown scheduler {}", name),
+        });
+        #[cfg(feature = "auth")]
+        let authenticator = options.value_of("shared-secret-file").map(|path| {
+            let authenticator = crate::communication::SharedSecretAuthenticator::from_file(path)
+                .unwrap_or_else(|e| panic!("Unable to read the shared secret {}: {}", path, e));
+            Arc::new(authenticator) as Arc<dyn Authenticator>
+        });
+        #[cfg(not(feature = "auth"))]
+        let authenticator: Option<Arc<dyn Authenticator>> =
+            match options.value_of("shared-secret-file") {
+                Some(path) => panic!(
+                    "Unable to use the shared secret {}: the auth feature is disabled",
+                    path
+                ),
+                None => None,
+            };
+        let checkpoint_backend = options.value_of("checkpoint-directory").map(|directory| {
+            Arc::new(FileSystemBackend::new(directory)) as Arc<dyn CheckpointBackend>
+        });
+        let checkpoint_interval = Duration::from_millis(
+            options
+                .value_of("checkpoint-interval")
+                .unwrap()
+                .parse()
+                .expect("Unable to parse the checkpoint interval"),
+        );
+        let zenoh_mode = match options.value_of("zenoh-mode").unwrap() {
+            "client" => ZenohMode::Client,
+            _ => ZenohMode::Peer,
+        };
+        let zenoh_peers = options
+            .value_of("zenoh-peers")
+            .map(|peers| {
+                peers
+                    .split(',')
+                    .map(|peer| peer.trim().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let zenoh_multicast_interface = options
+            .value_of("zenoh-multicast-interface")
+            .map(String::from);
+        let zenoh_scouting_delay = options.value_of("zenoh-scouting-delay").map(|delay| {
+            Duration::from_millis(
+                delay
+                    .parse()
+                    .expect("Unable to parse the Zenoh scouting delay"),
+            )
+        });
+        assert!(
+            zenoh_mode == ZenohMode::Peer || !zenoh_peers.is_empty(),
+            "Zenoh clients require a router with --zenoh-peers"
+        );
+        // The labels of the nodes are separated by semicolons.
+        let mut node_labels = vec![HashMap::new(); data_addresses.len()];
+        if let Some(labels) = options.value_of("node-labels") {
+            let labels: Vec<&str> = labels.split(';').collect();
+            assert!(
+                labels.len() <= data_addresses.len(),
+                "More node labels than nodes"
+            );
+            for (node_id, labels) in labels.into_iter().enumerate() {
+                node_labels[node_id] = scheduler::parse_labels(labels);
+            }
+        }
         Self {
             index: node_index,
             num_worker_threads: num_threads,
             data_addresses,
+            data_socket_paths,
             control_addresses,
             logger: crate::get_terminal_logger(),
             graph_filename,
+            plan_filename,
+            dry_run,
+            max_message_size,
+            max_chunk_size,
+            liveness_file,
+            watchdog_interval,
+            admin_address,
+            admin_tokens,
+            metrics_address,
+            stall_timeout,
+            peer_check_interval,
+            peer_check_timeout,
+            peer_check_misses,
+            heartbeat_interval,
+            heartbeat_timeout,
+            discovery_timeout,
+            multiplex_connections,
+            lazy_connections,
+            compact_headers,
+            serialization_threads,
+            setup_timeout,
+            slow_callback_threshold,
+            shm_segment_size,
+            shm_num_segments,
+            shm_overflow_policy,
+            shm_directory,
+            control_log_directory,
+            shm_huge_pages,
+            executor,
+            core_affinity,
+            node_labels,
+            scheduler,
+            authenticator,
+            checkpoint_backend,
+            checkpoint_interval,
+            zenoh_mode,
+            zenoh_peers,
+            zenoh_multicast_interface,
+            zenoh_scouting_delay,
+        }
+    }
+}
+
+/// Command line arguments which take precedence over the options of a deployment file.
+#[cfg(feature = "cli")]
+struct Options<'a, 'b> {
+    args: &'a clap::ArgMatches<'b>,
+    file: Option<clap::ArgMatches<'static>>,
+}
+
+#[cfg(feature = "cli")]
+impl<'a, 'b> Options<'a, 'b> {
+    fn value_of(&self, name: &str) -> Option<&str> {
+        match &self.file {
+            Some(file) if self.args.occurrences_of(name) == 0 && file.occurrences_of(name) > 0 => {
+                file.value_of(name)
+            }
+            _ => self.args.value_of(name),
         }
     }
+
+    fn is_present(&self, name: &str) -> bool {
+        self.args.is_present(name)
+            || self
+                .file
+                .as_ref()
+                .map_or(false, |file| file.is_present(name))
+    }
 }
@@ -77,10 +77,7 @@ macro_rules! make_operator_executor {
                 };
             )*
             $(
-                let $ws = {
-                    let send_endpoints = channel_manager.lock().unwrap().get_send_endpoints($ws).unwrap();
-                    WriteStream::from_endpoints(send_endpoints, $ws)
-                };
+                let $ws = channel_manager.lock().unwrap().get_write_stream($ws).unwrap();
             )*
             // After: $rs is an identifier pointing to ReadStream
             // $ws is an identifier pointing to WriteStream
@@ -89,15 +86,27 @@ macro_rules! make_operator_executor {
             let flow_watermarks = config.flow_watermarks;
             // TODO: set operator name?
             let mut op = $crate::make_operator!($t, config.clone(), ($($rs),*), ($($ws),*));
+            // Closed by the executor if the operator fails.
+            let failure_write_streams: Vec<Box<dyn OperatorExecutorWriteStreamT>> =
+                vec![$(Box::new($ws.clone()) as Box<dyn OperatorExecutorWriteStreamT>),*];
             // Pass on watermarks
             if flow_watermarks {
                 $crate::flow_watermarks!(($($rs),*), ($($ws),*));
             }
+            // Stream on which the operator reports callback errors.
+            let error_stream = config.error_stream_id.map(|error_stream_id| {
+                channel_manager
+                    .lock()
+                    .unwrap()
+                    .get_write_stream::<OperatorErrorReport>(error_stream_id)
+                    .unwrap()
+            });
             // Notify node that operator is done setting up
             if let Err(e) = control_sender.send(ControlMessage::OperatorInitialized(config.id)) {
                 panic!("Error sending OperatorInitialized message to control handler: {:?}", e);
             }
-            let mut op_executor = OperatorExecutor::new(op, config, op_ex_streams, control_receiver);
+            let mut op_executor = OperatorExecutor::new(op, config, op_ex_streams, control_sender, control_receiver, error_stream);
+            op_executor.set_write_streams(failure_write_streams);
             op_executor
         }
     }};
@@ -124,9 +133,10 @@ macro_rules! imports {
             communication::ControlMessage,
             dataflow::graph::default_graph,
             dataflow::stream::{InternalReadStream, WriteStreamT},
-            dataflow::{Message, Operator, ReadStream, WriteStream},
+            dataflow::{Message, Operator, OperatorErrorReport, ReadStream, WriteStream},
             node::operator_executor::{
                 OperatorExecutor, OperatorExecutorStream, OperatorExecutorStreamT,
+                OperatorExecutorWriteStreamT,
             },
             scheduler::channel_manager::ChannelManager,
             OperatorId,
@@ -146,7 +156,11 @@ macro_rules! register {
         $crate::imports!();
 
         let mut config = $config.clone();
-        config.id = OperatorId::new_deterministic();
+        config.id = match &config.name {
+            Some(name) => OperatorId::new_named(name)
+                .unwrap_or_else(|e| panic!("Unable to connect the operator {}: {}", name, e)),
+            None => OperatorId::new_deterministic(),
+        };
         let config_copy = config.clone();
 
         // No-op that throws compile-time error if types in `new` and `connect` don't match.
@@ -157,12 +171,37 @@ macro_rules! register {
 
         // Add operator to dataflow graph.
         let read_stream_ids = vec![$($rs.get_id()),*];
-        let write_stream_ids = vec![$($ws.get_id()),*];
+        let mut write_stream_ids = vec![$($ws.get_id()),*];
+        if let Some(error_stream_id) = config.error_stream_id {
+            write_stream_ids.push(error_stream_id);
+        }
+        for (index, description) in config.stream_descriptions.iter() {
+            let stream_id = *write_stream_ids.get(*index).unwrap_or_else(|| {
+                panic!("Unable to describe write stream {} of operator {:?}", index, config.id)
+            });
+            default_graph::describe_stream(stream_id, description.clone());
+        }
         let op_runner = $crate::make_operator_executor!($t, config_copy, ($($rs),*), ($($ws),*));
         default_graph::add_operator(config.id, config.name.clone(), config.node_id, read_stream_ids, write_stream_ids, op_runner);
+        default_graph::set_operator_dependencies(config.id, config.dependencies.clone());
+        default_graph::set_operator_partitioning(config.id, config.movable, config.cpu_demand);
+        default_graph::set_operator_constraints(config.id, config.colocated_with.clone(), config.separated_from.clone());
+        if let Some(node_selector) = &config.node_selector {
+            default_graph::set_operator_node_selector(config.id, node_selector.clone());
+        }
+        default_graph::set_operator_node_affinity(config.id, config.node_affinity.clone());
+        if let Some(setup_timeout) = config.setup_timeout {
+            default_graph::set_operator_setup_timeout(config.id, setup_timeout);
+        }
         $(
             default_graph::add_operator_stream(config.id, &$ws);
         )*
+        if let Some(error_stream_id) = config.error_stream_id {
+            default_graph::add_operator_stream(
+                config.id,
+                &WriteStream::<OperatorErrorReport>::new_with_id(error_stream_id),
+            );
+        }
         // Register streams with stream manager.
         ($(ReadStream::from(&$ws)),*)
     }};
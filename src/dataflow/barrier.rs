@@ -0,0 +1,167 @@
+//! Barriers which coordinate operators and drivers across nodes.
+//!
+//! Like the barriers with which the nodes wait for each other while setting up, the
+//! participants announce their arrival on the control plane, and are released once all
+//! participants arrived. Barriers are reusable: the `n`-th call to [`Barrier::wait`] of each
+//! participant waits for the `n`-th call of the other participants.
+//!
+//! # Example
+//! The below example shows how two operators cut over to a new map at the same time.
+//!
+//! ```
+//! # use erdos::dataflow::barrier;
+//! # use futures::{executor::block_on, future::join};
+//! let mut left = barrier::barrier("map-update", 2);
+//! let mut right = barrier::barrier("map-update", 2);
+//! block_on(join(left.wait(), right.wait()));
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+
+use futures::channel::oneshot;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::{dataflow::control, Uuid};
+
+const BARRIER_TYPE_TAG: &str = "erdos::barrier";
+
+lazy_static! {
+    static ref BARRIERS: Barriers = {
+        control::subscribe_handler(BARRIER_TYPE_TAG, |bytes| {
+            if let Ok(arrival) = bincode::deserialize::<Arrival>(bytes) {
+                BARRIERS.arrive(arrival);
+            }
+        });
+        Barriers::new()
+    };
+}
+
+/// A participant arrived at a barrier.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Arrival {
+    name: String,
+    /// The number of times the participant waited on the barrier, including this arrival.
+    generation: u64,
+    participant: Uuid,
+    participants: usize,
+}
+
+#[derive(Default)]
+struct BarrierState {
+    arrived: HashSet<Uuid>,
+    /// The participants on the current process waiting for the barrier.
+    waiters: Vec<oneshot::Sender<()>>,
+}
+
+/// Tracks the arrivals at the barriers of the current process.
+struct Barriers {
+    states: Mutex<HashMap<(String, u64), BarrierState>>,
+}
+
+impl Barriers {
+    fn new() -> Self {
+        Self {
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn wait(&self, name: &str, generation: u64) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        self.states
+            .lock()
+            .unwrap()
+            .entry((name.to_string(), generation))
+            .or_default()
+            .waiters
+            .push(tx);
+        rx
+    }
+
+    /// Records the arrival of a participant, and releases the waiting participants once all
+    /// participants arrived.
+    fn arrive(&self, arrival: Arrival) {
+        let mut states = self.states.lock().unwrap();
+        let key = (arrival.name, arrival.generation);
+        let state = states.entry(key.clone()).or_default();
+        state.arrived.insert(arrival.participant);
+        if state.arrived.len() >= arrival.participants {
+            for waiter in states.remove(&key).unwrap().waiters {
+                let _ = waiter.send(());
+            }
+        }
+    }
+}
+
+/// Starts tracking the arrivals at barriers, so that arrivals received from other nodes before
+/// a participant on the current process waits are not missed.
+pub(crate) fn register_node() {
+    lazy_static::initialize(&BARRIERS);
+}
+
+/// A participant in a barrier.
+///
+/// Created by [`barrier`].
+pub struct Barrier {
+    name: String,
+    participants: usize,
+    participant: Uuid,
+    generation: u64,
+}
+
+impl Barrier {
+    /// Returns the name of the barrier.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Waits until all participants of the barrier arrived.
+    pub async fn wait(&mut self) {
+        self.generation += 1;
+        let released = BARRIERS.wait(&self.name, self.generation);
+        let arrival = Arrival {
+            name: self.name.clone(),
+            generation: self.generation,
+            participant: self.participant,
+            participants: self.participants,
+        };
+        control::send(BARRIER_TYPE_TAG, &arrival).expect("Unable to serialize barrier arrival");
+        // The sender is only dropped once the barrier released its participants.
+        let _ = released.await;
+    }
+}
+
+/// Joins the barrier with the name, which releases its participants once `participants`
+/// participants on any nodes wait on it.
+pub fn barrier(name: &str, participants: usize) -> Barrier {
+    Barrier {
+        name: name.to_string(),
+        participants,
+        participant: Uuid::new_v4(),
+        generation: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{executor::block_on, future::FutureExt};
+
+    use super::*;
+
+    /// Test that participants are only released once all participants arrived, and that
+    /// barriers can be reused.
+    #[test]
+    fn test_barrier() {
+        let mut first = barrier("test-barrier", 2);
+        let mut second = barrier("test-barrier", 2);
+        for _ in 0..2 {
+            let mut first_wait = Box::pin(first.wait());
+            assert!((&mut first_wait).now_or_never().is_none());
+            block_on(second.wait());
+            block_on(first_wait);
+        }
+    }
+}
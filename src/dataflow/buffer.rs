@@ -0,0 +1,189 @@
+//! Buffers whose memory is provided by the operators which receive them.
+use std::{cell::RefCell, fmt, ops::Deref, sync::Arc};
+
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+thread_local!(static ALLOCATOR: RefCell<Option<Arc<dyn BufferAllocator>>> = RefCell::new(None));
+
+/// Memory which holds the bytes of a [`Buffer`], e.g. pinned host memory from which a GPU copies
+/// the bytes.
+pub trait BufferMemory: Send + Sync {
+    fn as_slice(&self) -> &[u8];
+    fn as_mut_slice(&mut self) -> &mut [u8];
+}
+
+impl BufferMemory for Vec<u8> {
+    fn as_slice(&self) -> &[u8] {
+        self
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+/// Provides the memory of the [`Buffer`]s received from other nodes on a stream.
+///
+/// The bytes of a buffer are copied into the memory once, straight from the bytes received from
+/// the network or from the shared memory mapped by the zero-copy transport.
+pub trait BufferAllocator: Send + Sync {
+    /// Returns memory which holds exactly `len` bytes.
+    fn allocate(&self, len: usize) -> Box<dyn BufferMemory>;
+}
+
+/// Bytes which are deserialized into the memory of the [`BufferAllocator`] registered for the
+/// stream on which they are received, or into a `Vec<u8>` otherwise.
+///
+/// Cloning a buffer shares its memory.
+#[derive(Clone)]
+pub struct Buffer {
+    memory: Arc<dyn BufferMemory>,
+}
+
+impl Buffer {
+    pub fn new(memory: Box<dyn BufferMemory>) -> Self {
+        Self {
+            memory: Arc::from(memory),
+        }
+    }
+
+    /// Returns the memory of the buffer.
+    pub fn memory(&self) -> &dyn BufferMemory {
+        &*self.memory
+    }
+
+    /// Returns the bytes of the buffer for writing, unless its memory is shared with clones.
+    pub fn get_mut(&mut self) -> Option<&mut [u8]> {
+        Arc::get_mut(&mut self.memory).map(|memory| memory.as_mut_slice())
+    }
+}
+
+impl From<Vec<u8>> for Buffer {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::new(Box::new(bytes))
+    }
+}
+
+impl Deref for Buffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.memory.as_slice()
+    }
+}
+
+impl fmt::Debug for Buffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Buffer {{ len: {} }}", self.len())
+    }
+}
+
+impl PartialEq for Buffer {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Serialize for Buffer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Buffer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_bytes(BufferVisitor)
+    }
+}
+
+struct BufferVisitor;
+
+impl<'de> Visitor<'de> for BufferVisitor {
+    type Value = Buffer;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Buffer, E> {
+        let memory = ALLOCATOR.with(|allocator| match &*allocator.borrow() {
+            Some(allocator) => {
+                let mut memory = allocator.allocate(bytes.len());
+                memory.as_mut_slice()[..bytes.len()].copy_from_slice(bytes);
+                memory
+            }
+            None => Box::new(bytes.to_vec()),
+        });
+        Ok(Buffer::new(memory))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<Buffer, E> {
+        if ALLOCATOR.with(|allocator| allocator.borrow().is_some()) {
+            self.visit_bytes(&bytes)
+        } else {
+            Ok(Buffer::from(bytes))
+        }
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Buffer, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        self.visit_byte_buf(bytes)
+    }
+}
+
+/// Runs `f`, which deserializes a message, so that its [`Buffer`]s are allocated by the
+/// allocator.
+pub(crate) fn deserialize_with<R>(
+    allocator: Option<&Arc<dyn BufferAllocator>>,
+    f: impl FnOnce() -> R,
+) -> R {
+    let allocator = match allocator {
+        Some(allocator) => Arc::clone(allocator),
+        None => return f(),
+    };
+    let previous = ALLOCATOR.with(|current| current.replace(Some(allocator)));
+    let result = f();
+    ALLOCATOR.with(|current| current.replace(previous));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    struct CountingAllocator(AtomicUsize);
+
+    impl BufferAllocator for CountingAllocator {
+        fn allocate(&self, len: usize) -> Box<dyn BufferMemory> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Box::new(vec![0; len])
+        }
+    }
+
+    /// Test that buffers are deserialized into the memory of the allocator, if one is set.
+    #[test]
+    fn test_buffer_allocator() {
+        let buffer = Buffer::from(vec![1, 2, 3]);
+        let bytes = bincode::serialize(&(7u32, buffer.clone())).unwrap();
+        let counting = Arc::new(CountingAllocator(AtomicUsize::new(0)));
+        let allocator: Arc<dyn BufferAllocator> = counting.clone();
+
+        let (_, decoded): (u32, Buffer) =
+            deserialize_with(Some(&allocator), || bincode::deserialize(&bytes)).unwrap();
+        assert_eq!(decoded, buffer);
+        assert_eq!(counting.0.load(Ordering::SeqCst), 1);
+
+        let (_, decoded): (u32, Buffer) =
+            deserialize_with(None, || bincode::deserialize(&bytes)).unwrap();
+        assert_eq!(&*decoded, &[1, 2, 3]);
+        assert_eq!(counting.0.load(Ordering::SeqCst), 1);
+    }
+}
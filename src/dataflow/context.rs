@@ -0,0 +1,259 @@
+use std::{
+    any::Any,
+    cell::{RefCell, RefMut},
+    collections::{BTreeSet, HashMap},
+    rc::Rc,
+};
+
+use slog::Logger;
+
+use crate::{
+    dataflow::{stream::StreamId, Data, OperatorConfig, ReadStream, Timestamp},
+    node::{memory_limit, OperatorMetrics},
+    OperatorId,
+};
+
+/// Handed to the callbacks registered with
+/// [`ReadStream::add_callback_with_context`] and
+/// [`ReadStream::add_watermark_callback_with_context`].
+///
+/// Exposes the configuration and a logger of the operator, state shared by its callbacks,
+/// metrics, timers, and the watermarks received on its inputs, so that callbacks need not
+/// capture these in the operator's fields. The context is created from the config passed to the
+/// operator, and cloning it returns a handle to the same context.
+///
+/// ```
+/// # use erdos::dataflow::{OperatorConfig, OperatorContext, ReadStream, Timestamp};
+/// fn new(config: OperatorConfig<()>, read_stream: ReadStream<u32>) {
+///     let ctx = OperatorContext::new(&config);
+///     ctx.set_state(0u32);
+///     read_stream.add_callback_with_context(&ctx, |ctx, _t: &Timestamp, data: &u32| {
+///         *ctx.state::<u32>() += data;
+///         ctx.metrics().counter("messages_total").increment();
+///     });
+/// }
+/// ```
+pub struct OperatorContext<T: Clone = ()> {
+    inner: Rc<ContextInner<T>>,
+}
+
+struct ContextInner<T: Clone> {
+    config: OperatorConfig<T>,
+    name: String,
+    logger: Logger,
+    state: RefCell<Option<Box<dyn Any>>>,
+    metrics: OperatorMetrics,
+    /// The last watermark received on each tracked input.
+    watermarks: RefCell<HashMap<StreamId, Option<Timestamp>>>,
+    timers: RefCell<BTreeSet<Timestamp>>,
+    timer_cbs: RefCell<Vec<Rc<dyn Fn(&OperatorContext<T>, &Timestamp)>>>,
+}
+
+impl<T: 'static + Clone> OperatorContext<T> {
+    pub fn new(config: &OperatorConfig<T>) -> Self {
+        let name = match &config.name {
+            Some(name) => name.clone(),
+            None => config.id.to_string(),
+        };
+        let metrics = OperatorMetrics::new(&name);
+        let logger = crate::get_terminal_logger()
+            .new(slog::o!("operator" => name.clone(), "operator_id" => config.id.to_string()));
+        Self {
+            inner: Rc::new(ContextInner {
+                config: config.clone(),
+                name,
+                logger,
+                state: RefCell::new(None),
+                metrics,
+                watermarks: RefCell::new(HashMap::new()),
+                timers: RefCell::new(BTreeSet::new()),
+                timer_cbs: RefCell::new(Vec::new()),
+            }),
+        }
+    }
+
+    pub fn id(&self) -> OperatorId {
+        self.inner.config.id
+    }
+
+    /// Returns the name of the operator, or its ID if the operator has no name.
+    pub fn name(&self) -> &str {
+        &self.inner.name[..]
+    }
+
+    pub fn config(&self) -> &OperatorConfig<T> {
+        &self.inner.config
+    }
+
+    /// Returns the argument with which the operator was configured.
+    pub fn arg(&self) -> Option<&T> {
+        self.inner.config.arg.as_ref()
+    }
+
+    /// Returns a logger which tags the messages with the name and the ID of the operator.
+    pub fn logger(&self) -> &Logger {
+        &self.inner.logger
+    }
+
+    /// Sets the state shared by the callbacks of the operator.
+    pub fn set_state<S: 'static>(&self, state: S) {
+        self.inner.state.replace(Some(Box::new(state)));
+    }
+
+    /// Returns the state set with [`set_state`](Self::set_state).
+    ///
+    /// Panics if the state has another type, or if a callback already accesses the state.
+    pub fn state<S: 'static>(&self) -> RefMut<S> {
+        RefMut::map(self.inner.state.borrow_mut(), |state| {
+            state
+                .as_mut()
+                .and_then(|state| state.downcast_mut::<S>())
+                .expect("The operator has no state of the requested type")
+        })
+    }
+
+    /// Reports the size in bytes of the state of the operator, which counts against its
+    /// [memory limit](OperatorConfig::with_memory_limit).
+    pub fn report_state_size(&self, bytes: u64) {
+        memory_limit::set_state_bytes(self.inner.config.id, bytes);
+    }
+
+    /// Returns the registry of the metrics of the operator, which the node exports.
+    pub fn metrics(&self) -> &OperatorMetrics {
+        &self.inner.metrics
+    }
+
+    /// Adds to the [counter](OperatorMetrics::counter) with the given name.
+    pub fn increment_counter(&self, name: &str, amount: u64) {
+        self.inner.metrics.counter(name).add(amount);
+    }
+
+    /// Returns the value of the [counter](OperatorMetrics::counter) with the given name, which
+    /// is 0 if it was never incremented.
+    pub fn counter(&self, name: &str) -> u64 {
+        self.inner.metrics.counter(name).get()
+    }
+
+    /// Tracks the watermarks received on the stream, which becomes an input of
+    /// [`low_watermark`](Self::low_watermark) and of the timers.
+    ///
+    /// The streams on which callbacks are registered with the context are tracked
+    /// automatically. The watermarks are updated by a watermark callback, so the watermark
+    /// callbacks registered before the stream is tracked do not observe the update.
+    pub fn track_watermarks<D: Data>(&self, read_stream: &ReadStream<D>) {
+        let stream_id = read_stream.get_id();
+        if self.inner.watermarks.borrow().contains_key(&stream_id) {
+            return;
+        }
+        self.inner.watermarks.borrow_mut().insert(stream_id, None);
+        let ctx = self.clone();
+        read_stream.add_watermark_callback(move |t: &Timestamp| {
+            ctx.update_watermark(stream_id, t);
+        });
+    }
+
+    /// Returns the last watermark received on the input.
+    pub fn watermark(&self, stream_id: StreamId) -> Option<Timestamp> {
+        self.inner
+            .watermarks
+            .borrow()
+            .get(&stream_id)
+            .cloned()
+            .flatten()
+    }
+
+    /// Returns the lowest watermark across the tracked inputs, or `None` until all inputs
+    /// received a watermark.
+    pub fn low_watermark(&self) -> Option<Timestamp> {
+        let watermarks = self.inner.watermarks.borrow();
+        if watermarks.is_empty() {
+            return None;
+        }
+        let mut low_watermark: Option<Timestamp> = None;
+        for watermark in watermarks.values() {
+            let watermark = watermark.as_ref()?;
+            if low_watermark.as_ref().map_or(true, |low| watermark < low) {
+                low_watermark = Some(watermark.clone());
+            }
+        }
+        low_watermark
+    }
+
+    /// Sets a timer which fires once the low watermark reaches the timestamp.
+    pub fn set_timer(&self, timestamp: Timestamp) {
+        self.inner.timers.borrow_mut().insert(timestamp);
+    }
+
+    /// Adds a callback invoked with the timestamp of every timer which fires.
+    pub fn add_timer_callback<F: 'static + Fn(&OperatorContext<T>, &Timestamp)>(
+        &self,
+        callback: F,
+    ) {
+        self.inner.timer_cbs.borrow_mut().push(Rc::new(callback));
+    }
+
+    fn update_watermark(&self, stream_id: StreamId, timestamp: &Timestamp) {
+        self.inner
+            .watermarks
+            .borrow_mut()
+            .insert(stream_id, Some(timestamp.clone()));
+        let low_watermark = match self.low_watermark() {
+            Some(low_watermark) => low_watermark,
+            None => return,
+        };
+        let fired: Vec<Timestamp> = {
+            let mut timers = self.inner.timers.borrow_mut();
+            let fired: Vec<Timestamp> = timers
+                .iter()
+                .take_while(|t| **t <= low_watermark)
+                .cloned()
+                .collect();
+            for t in fired.iter() {
+                timers.remove(t);
+            }
+            fired
+        };
+        // Clone the callbacks so that they can set timers and add callbacks.
+        let timer_cbs = self.inner.timer_cbs.borrow().clone();
+        for t in fired.iter() {
+            for cb in timer_cbs.iter() {
+                (cb)(self, t);
+            }
+        }
+    }
+}
+
+impl<T: Clone> Clone for OperatorContext<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Rc::clone(&self.inner),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that timers fire once the watermarks of all tracked inputs reach their timestamp.
+    #[test]
+    fn test_context_timers() {
+        let ctx = OperatorContext::new(&OperatorConfig::<()>::new().name("ContextTestOp"));
+        let left: ReadStream<u32> = ReadStream::new();
+        let right: ReadStream<u32> = ReadStream::new();
+        ctx.track_watermarks(&left);
+        ctx.track_watermarks(&right);
+        ctx.set_timer(Timestamp::new(vec![2]));
+        ctx.add_timer_callback(|ctx, _t| ctx.increment_counter("test_timers_total", 1));
+
+        ctx.update_watermark(left.get_id(), &Timestamp::new(vec![3]));
+        assert_eq!(ctx.low_watermark(), None);
+        ctx.update_watermark(right.get_id(), &Timestamp::new(vec![1]));
+        assert_eq!(ctx.counter("test_timers_total"), 0);
+        ctx.update_watermark(right.get_id(), &Timestamp::new(vec![2]));
+        assert_eq!(ctx.counter("test_timers_total"), 1);
+        assert_eq!(ctx.low_watermark(), Some(Timestamp::new(vec![2])));
+        assert_eq!(ctx.watermark(left.get_id()), Some(Timestamp::new(vec![3])));
+        assert_eq!(ctx.name(), "ContextTestOp");
+    }
+}
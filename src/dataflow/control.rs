@@ -0,0 +1,204 @@
+//! User-defined messages sent on the control plane.
+//!
+//! Operators and drivers can exchange small coordination signals which should not travel
+//! through the data streams, e.g. to notify all operators that a model was reloaded. Messages
+//! are identified by a type tag, and are delivered reliably to all subscriptions to the tag on
+//! all nodes, including the sender's node.
+//!
+//! # Example
+//! ```
+//! # use erdos::dataflow::control;
+//! let subscription = control::subscribe::<String>("model-reloaded");
+//! control::send("model-reloaded", &"v2".to_string()).unwrap();
+//! assert_eq!(subscription.read(), Ok("v2".to_string()));
+//! ```
+
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{mpsc, Mutex},
+};
+
+use lazy_static::lazy_static;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    communication::ControlMessage,
+    dataflow::stream::errors::{ReadError, TryReadError, WriteStreamError},
+    node::NodeId,
+};
+
+lazy_static! {
+    static ref USER_CONTROL: UserControl = UserControl::new();
+}
+
+/// Receives the messages with a type tag on the current process.
+enum Subscriber {
+    Channel(mpsc::Sender<Vec<u8>>),
+    /// Used by the runtime to build primitives such as barriers on top of the control messages.
+    Handler(Box<dyn Fn(&[u8]) + Send>),
+}
+
+impl Subscriber {
+    /// Returns false if the subscription was dropped.
+    fn deliver(&self, bytes: &[u8]) -> bool {
+        match self {
+            Subscriber::Channel(tx) => tx.send(bytes.to_vec()).is_ok(),
+            Subscriber::Handler(handler) => {
+                handler(bytes);
+                true
+            }
+        }
+    }
+}
+
+/// Routes the user-defined control messages sent and received on the current process.
+struct UserControl {
+    subscriptions: Mutex<HashMap<String, Vec<Subscriber>>>,
+    channels_to_nodes: Mutex<Vec<(NodeId, UnboundedSender<ControlMessage>)>>,
+    /// Messages sent before any node started running.
+    pending: Mutex<Vec<(Vec<u8>, String)>>,
+}
+
+impl UserControl {
+    fn new() -> Self {
+        Self {
+            subscriptions: Mutex::new(HashMap::new()),
+            channels_to_nodes: Mutex::new(Vec::new()),
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn deliver(&self, type_tag: &str, bytes: &[u8]) {
+        if let Some(subscriptions) = self.subscriptions.lock().unwrap().get_mut(type_tag) {
+            subscriptions.retain(|subscriber| subscriber.deliver(bytes));
+        }
+    }
+
+    /// Delivers the message on the current process, and sends it to one of the nodes running
+    /// on the process, which forwards it to the other nodes.
+    fn subscribe(&self, type_tag: &str, subscriber: Subscriber) {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .entry(type_tag.to_string())
+            .or_default()
+            .push(subscriber);
+    }
+
+    fn send(&self, type_tag: String, bytes: Vec<u8>) {
+        self.deliver(&type_tag, &bytes);
+        let mut channels_to_nodes = self.channels_to_nodes.lock().unwrap();
+        while let Some((node_id, tx)) = channels_to_nodes.first() {
+            let msg = ControlMessage::User(bytes.clone(), type_tag.clone(), *node_id);
+            if tx.send(msg).is_ok() {
+                return;
+            }
+            // The node stopped running.
+            channels_to_nodes.remove(0);
+        }
+        self.pending.lock().unwrap().push((bytes, type_tag));
+    }
+}
+
+/// Receives the user-defined control messages with a type tag.
+///
+/// Only receives the messages sent after the subscription was created.
+pub struct ControlSubscription<T> {
+    rx: mpsc::Receiver<Vec<u8>>,
+    phantom: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> ControlSubscription<T> {
+    /// Blocks until a message is received.
+    pub fn read(&self) -> Result<T, ReadError> {
+        let bytes = self.rx.recv().map_err(|_| ReadError::Disconnected)?;
+        bincode::deserialize(&bytes).map_err(|_| ReadError::SerializationError)
+    }
+
+    /// Returns a message if one was received, or [`TryReadError::Empty`] otherwise.
+    pub fn try_read(&self) -> Result<T, TryReadError> {
+        let bytes = self.rx.try_recv().map_err(|e| match e {
+            mpsc::TryRecvError::Empty => TryReadError::Empty,
+            mpsc::TryRecvError::Disconnected => TryReadError::Disconnected,
+        })?;
+        bincode::deserialize(&bytes).map_err(|_| TryReadError::SerializationError)
+    }
+}
+
+/// Subscribes to the user-defined control messages with a type tag.
+pub fn subscribe<T: DeserializeOwned>(type_tag: &str) -> ControlSubscription<T> {
+    let (tx, rx) = mpsc::channel();
+    USER_CONTROL.subscribe(type_tag, Subscriber::Channel(tx));
+    ControlSubscription {
+        rx,
+        phantom: PhantomData,
+    }
+}
+
+/// Sends a user-defined control message to the subscriptions to its type tag on all nodes.
+///
+/// Messages sent before the nodes start running are sent to the other nodes once a node starts
+/// running on the current process.
+pub fn send<T: Serialize>(type_tag: &str, msg: &T) -> Result<(), WriteStreamError> {
+    let bytes = bincode::serialize(msg).map_err(|_| WriteStreamError::SerializationError)?;
+    USER_CONTROL.send(type_tag.to_string(), bytes);
+    Ok(())
+}
+
+/// Calls the handler with the messages with a type tag received on the current process.
+pub(crate) fn subscribe_handler<F: 'static + Fn(&[u8]) + Send>(type_tag: &str, handler: F) {
+    USER_CONTROL.subscribe(type_tag, Subscriber::Handler(Box::new(handler)));
+}
+
+/// Forwards the user-defined control messages sent on the current process to the node's control
+/// message handler, including the messages sent before the node started running.
+pub(crate) fn register_node(node_id: NodeId, tx: UnboundedSender<ControlMessage>) {
+    let mut channels_to_nodes = USER_CONTROL.channels_to_nodes.lock().unwrap();
+    if channels_to_nodes.is_empty() {
+        for (bytes, type_tag) in USER_CONTROL.pending.lock().unwrap().drain(..) {
+            let _ = tx.send(ControlMessage::User(bytes, type_tag, node_id));
+        }
+    }
+    channels_to_nodes.push((node_id, tx));
+}
+
+/// Returns whether the node runs on the current process, e.g. with a
+/// [`MultiNodeRunner`](crate::node::MultiNodeRunner).
+pub(crate) fn is_local_node(node_id: NodeId) -> bool {
+    USER_CONTROL
+        .channels_to_nodes
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|(local_node_id, _)| *local_node_id == node_id)
+}
+
+/// Delivers a user-defined control message received from another node.
+pub(crate) fn deliver(type_tag: &str, bytes: &[u8]) {
+    USER_CONTROL.deliver(type_tag, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that messages are only delivered to the subscriptions to their type tag.
+    #[test]
+    fn test_user_control_messages() {
+        let subscription = subscribe::<u32>("test-user-control");
+        let other_subscription = subscribe::<u32>("test-user-control-other");
+        send("test-user-control", &7u32).unwrap();
+        assert_eq!(subscription.try_read(), Ok(7));
+        assert_eq!(subscription.try_read(), Err(TryReadError::Empty));
+        assert_eq!(other_subscription.try_read(), Err(TryReadError::Empty));
+
+        // Messages which do not deserialize into the type of the subscription are reported.
+        send("test-user-control", &7u8).unwrap();
+        assert_eq!(
+            subscription.try_read(),
+            Err(TryReadError::SerializationError)
+        );
+    }
+}
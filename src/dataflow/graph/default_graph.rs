@@ -5,7 +5,7 @@
 //! The dataflow graph is thread-local; therefore, drivers should not be
 //! multi-threaded and this module should never be used from an asynchronous
 //! context.
-use std::cell::RefCell;
+use std::{cell::RefCell, time::Duration};
 
 use serde::Deserialize;
 
@@ -15,10 +15,11 @@ use crate::{
         Data,
     },
     node::NodeId,
-    OperatorId,
+    scheduler::NodeSelector,
+    OperatorId, Uuid,
 };
 
-use super::{Graph, OperatorRunner, StreamSetupHook};
+use super::{DriverTeardownHook, Graph, OperatorRunner, StreamDescription, StreamSetupHook};
 
 thread_local!(static DEFAULT_GRAPH: RefCell<Graph> = RefCell::new(Graph::new()));
 
@@ -39,6 +40,70 @@ pub fn add_operator<F: OperatorRunner>(
     });
 }
 
+pub fn set_operator_dependencies(operator_id: OperatorId, dependencies: Vec<String>) {
+    DEFAULT_GRAPH.with(|g| {
+        g.borrow_mut()
+            .set_operator_dependencies(operator_id, dependencies);
+    });
+}
+
+pub fn set_operator_setup_timeout(operator_id: OperatorId, timeout: Duration) {
+    DEFAULT_GRAPH.with(|g| {
+        g.borrow_mut()
+            .set_operator_setup_timeout(operator_id, timeout);
+    });
+}
+
+/// Documents the messages sent on the stream, e.g. on an
+/// [`IngestStream`](crate::dataflow::stream::IngestStream).
+pub fn describe_stream(stream_id: StreamId, description: StreamDescription) {
+    DEFAULT_GRAPH.with(|g| {
+        g.borrow_mut().describe_stream(stream_id, description);
+    });
+}
+
+pub fn set_operator_partitioning(operator_id: OperatorId, movable: bool, cpu_demand: f64) {
+    DEFAULT_GRAPH.with(|g| {
+        g.borrow_mut()
+            .set_operator_partitioning(operator_id, movable, cpu_demand);
+    });
+}
+
+pub fn set_operator_constraints(
+    operator_id: OperatorId,
+    colocated_with: Vec<String>,
+    separated_from: Vec<String>,
+) {
+    DEFAULT_GRAPH.with(|g| {
+        g.borrow_mut()
+            .set_operator_constraints(operator_id, colocated_with, separated_from);
+    });
+}
+
+pub fn set_operator_node_selector(operator_id: OperatorId, selector: NodeSelector) {
+    DEFAULT_GRAPH.with(|g| {
+        g.borrow_mut()
+            .set_operator_node_selector(operator_id, selector);
+    });
+}
+
+pub fn set_operator_node_affinity(operator_id: OperatorId, node_ids: Vec<NodeId>) {
+    DEFAULT_GRAPH.with(|g| {
+        g.borrow_mut()
+            .set_operator_node_affinity(operator_id, node_ids);
+    });
+}
+
+/// Connects the nodes which exchange the messages of the stream at startup, instead of upon the
+/// first message, when nodes connect lazily.
+///
+/// See [`Configuration::lazy_connections`](crate::Configuration::lazy_connections).
+pub fn warm_up_stream(stream_id: StreamId) {
+    DEFAULT_GRAPH.with(|g| {
+        g.borrow_mut().warm_up_stream(stream_id);
+    });
+}
+
 pub fn add_operator_stream<D>(operator_id: OperatorId, write_stream: &WriteStream<D>)
 where
     for<'a> D: Data + Deserialize<'a>,
@@ -68,6 +133,34 @@ where
     });
 }
 
+/// Adds the driver hooks and the ingest and extract streams created by `f` to the driver section
+/// `name`, so that different components of the application can each install their own driver
+/// plumbing.
+pub fn with_driver_section<T, F: FnOnce() -> T>(name: &str, f: F) -> T {
+    let previous = DEFAULT_GRAPH.with(|g| g.borrow_mut().set_driver_section(name));
+    let result = f();
+    DEFAULT_GRAPH.with(|g| g.borrow_mut().set_driver_section(&previous));
+    result
+}
+
+/// Adds a hook called on the node once its operators are set up, before the dataflow runs.
+pub fn add_driver_setup_hook<F: StreamSetupHook>(node_id: NodeId, setup_hook: F) {
+    DEFAULT_GRAPH.with(|g| {
+        g.borrow_mut().add_driver_setup_hook(node_id, setup_hook);
+    });
+}
+
+/// Adds a hook called on the node when the dataflow shuts down.
+///
+/// Teardown hooks run after the node stops, in the reverse order of the driver sections, so that
+/// a section can rely on the sections set up before it.
+pub fn add_driver_teardown_hook<F: DriverTeardownHook>(node_id: NodeId, teardown_hook: F) {
+    DEFAULT_GRAPH.with(|g| {
+        g.borrow_mut()
+            .add_driver_teardown_hook(node_id, teardown_hook);
+    });
+}
+
 pub fn add_loop_stream<D>(loop_stream: &LoopStream<D>)
 where
     for<'a> D: Data + Deserialize<'a>,
@@ -82,6 +175,21 @@ pub fn add_stream_alias(from_id: StreamId, to_id: StreamId) -> Result<(), String
     DEFAULT_GRAPH.with(|g| g.borrow_mut().add_stream_alias(from_id, to_id))
 }
 
+/// Sets the name of the default graph, from which the IDs of named operators and streams are
+/// derived.
+pub fn set_name(name: &str) {
+    DEFAULT_GRAPH.with(|g| g.borrow_mut().set_name(name));
+}
+
+pub fn name() -> String {
+    DEFAULT_GRAPH.with(|g| g.borrow().name().to_string())
+}
+
+/// Derives the ID of an operator or a stream of the default graph from its name.
+pub(crate) fn derive_id(kind: &str, name: &str) -> Result<Uuid, String> {
+    DEFAULT_GRAPH.with(|g| g.borrow_mut().derive_id(kind, name))
+}
+
 pub fn clone() -> Graph {
     DEFAULT_GRAPH.with(|g| g.borrow().clone())
 }
@@ -0,0 +1,172 @@
+use crate::{dataflow::stream::StreamId, OperatorId};
+
+use super::{Channel, Graph, OperatorMetadata, StreamMetadata, Vertex};
+
+/// Differences between two dataflow graphs, e.g. between the graph a cluster runs and an upgraded
+/// version of it.
+///
+/// Operators and streams are matched by ID. Because IDs are generated deterministically in the
+/// order in which the application creates operators and streams, the graphs must be built by the
+/// same sequence of calls up to the first difference.
+///
+/// Only [additive](GraphDiff::is_additive) diffs can be applied to the running dataflow, by
+/// connecting the added operators and calling
+/// [`NodeHandle::add_operators`](crate::node::NodeHandle::add_operators), which starts them and
+/// connects them to the streams which already run. Running operators can not be drained, stopped,
+/// or rewired, so applying a diff which removes or changes operators or removes streams requires
+/// restarting the nodes whose operators or channels changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GraphDiff {
+    pub added_operators: Vec<OperatorId>,
+    pub removed_operators: Vec<OperatorId>,
+    /// Operators whose name, node, streams, or dependencies changed.
+    pub changed_operators: Vec<OperatorId>,
+    pub added_streams: Vec<StreamId>,
+    pub removed_streams: Vec<StreamId>,
+    /// Streams whose source or consumers changed.
+    pub changed_streams: Vec<StreamId>,
+}
+
+impl GraphDiff {
+    /// Computes the changes which turn the old graph into the new graph.
+    pub fn new(old: &Graph, new: &Graph) -> Self {
+        let mut diff = Self::default();
+        for operator in old.get_operators() {
+            match new.get_operator(operator.id) {
+                None => diff.removed_operators.push(operator.id),
+                Some(new_operator) if operator_changed(&operator, &new_operator) => {
+                    diff.changed_operators.push(operator.id)
+                }
+                Some(_) => (),
+            }
+        }
+        for operator in new.get_operators() {
+            if old.get_operator(operator.id).is_none() {
+                diff.added_operators.push(operator.id);
+            }
+        }
+        for stream in old.get_streams() {
+            match new.get_stream(stream.get_id()) {
+                None => diff.removed_streams.push(stream.get_id()),
+                Some(new_stream) if stream_changed(&stream, &new_stream) => {
+                    diff.changed_streams.push(stream.get_id())
+                }
+                Some(_) => (),
+            }
+        }
+        for stream in new.get_streams() {
+            if old.get_stream(stream.get_id()).is_none() {
+                diff.added_streams.push(stream.get_id());
+            }
+        }
+        diff.added_operators.sort();
+        diff.removed_operators.sort();
+        diff.changed_operators.sort();
+        diff.added_streams.sort();
+        diff.removed_streams.sort();
+        diff.changed_streams.sort();
+        diff
+    }
+
+    /// Returns true if the graphs are the same.
+    pub fn is_empty(&self) -> bool {
+        self.added_operators.is_empty()
+            && self.removed_operators.is_empty()
+            && self.changed_operators.is_empty()
+            && self.added_streams.is_empty()
+            && self.removed_streams.is_empty()
+            && self.changed_streams.is_empty()
+    }
+
+    /// Returns true if the new graph only adds operators and streams, and consumers to the
+    /// streams of the old graph.
+    pub fn is_additive(&self) -> bool {
+        self.removed_operators.is_empty()
+            && self.changed_operators.is_empty()
+            && self.removed_streams.is_empty()
+    }
+}
+
+fn operator_changed(old: &OperatorMetadata, new: &OperatorMetadata) -> bool {
+    old.name != new.name
+        || old.node_id != new.node_id
+        || old.read_stream_ids != new.read_stream_ids
+        || old.write_stream_ids != new.write_stream_ids
+        || old.dependencies != new.dependencies
+}
+
+fn stream_changed(old: &StreamMetadata, new: &StreamMetadata) -> bool {
+    let sinks = |stream: &StreamMetadata| -> Vec<Vertex> {
+        stream
+            .get_channels()
+            .into_iter()
+            .map(|channel| match channel {
+                Channel::InterNode(cm) | Channel::InterThread(cm) | Channel::Unscheduled(cm) => {
+                    cm.sink
+                }
+            })
+            .collect()
+    };
+    let old_sinks = sinks(old);
+    let new_sinks = sinks(new);
+    old.get_source() != new.get_source()
+        || old_sinks.len() != new_sinks.len()
+        || old_sinks.iter().any(|sink| !new_sinks.contains(sink))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+    use super::*;
+
+    use crate::{
+        communication::ControlMessage, dataflow::WriteStream,
+        node::operator_executor::OperatorExecutor, scheduler::channel_manager::ChannelManager,
+    };
+
+    fn runner(
+        _: Arc<Mutex<ChannelManager>>,
+        _: UnboundedSender<ControlMessage>,
+        _: UnboundedReceiver<ControlMessage>,
+    ) -> OperatorExecutor {
+        unreachable!()
+    }
+
+    /// Test that added, removed, and rewired operators and streams are reported.
+    #[test]
+    fn test_graph_diff() {
+        let source_id = OperatorId::new_deterministic();
+        let sink_id = OperatorId::new_deterministic();
+        let new_sink_id = OperatorId::new_deterministic();
+        let stream: WriteStream<u32> = WriteStream::new();
+        let stream_id = stream.get_id();
+
+        let mut old = Graph::new();
+        old.add_operator(source_id, None, 0, vec![], vec![stream_id], runner);
+        old.add_operator_stream(source_id, &stream);
+        old.add_operator(sink_id, None, 0, vec![stream_id], vec![], runner);
+        assert!(GraphDiff::new(&old, &old.clone()).is_empty());
+
+        let mut extended = old.clone();
+        extended.add_operator(new_sink_id, None, 1, vec![stream_id], vec![], runner);
+        let diff = GraphDiff::new(&old, &extended);
+        assert_eq!(diff.added_operators, vec![new_sink_id]);
+        assert_eq!(diff.changed_streams, vec![stream_id]);
+        assert!(diff.is_additive());
+
+        let mut new = Graph::new();
+        new.add_operator(source_id, None, 0, vec![], vec![stream_id], runner);
+        new.add_operator_stream(source_id, &stream);
+        new.add_operator(new_sink_id, None, 1, vec![stream_id], vec![], runner);
+        let diff = GraphDiff::new(&old, &new);
+        assert_eq!(diff.added_operators, vec![new_sink_id]);
+        assert_eq!(diff.removed_operators, vec![sink_id]);
+        assert!(diff.changed_operators.is_empty());
+        assert_eq!(diff.changed_streams, vec![stream_id]);
+        assert!(diff.added_streams.is_empty() && diff.removed_streams.is_empty());
+        assert!(!diff.is_additive());
+    }
+}
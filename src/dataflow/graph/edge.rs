@@ -104,6 +104,25 @@ where
     }
 }
 
+/// Human-readable documentation of the messages sent on a stream, which is included in the
+/// exports of the dataflow graph.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamDescription {
+    /// What the messages on the stream represent.
+    pub description: String,
+    /// The schema of the messages, e.g. the fields of the message type and their units.
+    pub schema: Option<String>,
+}
+
+impl StreamDescription {
+    pub fn new(description: &str, schema: Option<&str>) -> Self {
+        Self {
+            description: description.to_string(),
+            schema: schema.map(String::from),
+        }
+    }
+}
+
 pub struct StreamMetadata {
     stream_metadata_t: Box<dyn StreamMetadataT>,
 }
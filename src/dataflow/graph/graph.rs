@@ -1,6 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs::File;
 use std::io::prelude::*;
+use std::time::Duration;
 
 use serde::Deserialize;
 
@@ -9,13 +10,15 @@ use crate::{
         stream::{ExtractStream, IngestStream, LoopStream, StreamId, WriteStream},
         Data,
     },
+    ids::DerivedIds,
     node::NodeId,
-    OperatorId,
+    scheduler::NodeSelector,
+    OperatorId, Uuid,
 };
 
 use super::{
-    Channel, ChannelMetadata, DriverMetadata, OperatorMetadata, OperatorRunner, StreamMetadata,
-    StreamSetupHook, Vertex,
+    Channel, ChannelMetadata, DriverMetadata, DriverTeardownHook, OperatorMetadata, OperatorRunner,
+    StreamDescription, StreamMetadata, StreamSetupHook, Vertex, DEFAULT_DRIVER_SECTION,
 };
 
 /// Represents a data-flow computation.
@@ -33,6 +36,15 @@ pub struct Graph {
     streams: HashMap<StreamId, StreamMetadata>,
     /// ID mappings for streams aliasing other streams, e.g. LoopStreams
     stream_aliases: HashMap<StreamId, StreamId>,
+    /// The driver section to which new driver hooks are added.
+    driver_section: String,
+    /// Streams whose connections to other nodes are established at startup when nodes connect
+    /// lazily.
+    warm_up_streams: HashSet<StreamId>,
+    /// Documentation of the messages sent on streams.
+    stream_descriptions: HashMap<StreamId, StreamDescription>,
+    /// The IDs of the named operators and streams of the graph.
+    derived_ids: DerivedIds,
 }
 
 impl Graph {
@@ -42,9 +54,28 @@ impl Graph {
             drivers: HashMap::new(),
             streams: HashMap::new(),
             stream_aliases: HashMap::new(),
+            driver_section: DEFAULT_DRIVER_SECTION.to_string(),
+            warm_up_streams: HashSet::new(),
+            stream_descriptions: HashMap::new(),
+            derived_ids: DerivedIds::default(),
         }
     }
 
+    /// Returns the name of the graph, from which the IDs of named operators and streams are
+    /// derived.
+    pub fn name(&self) -> &str {
+        self.derived_ids.graph_name()
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.derived_ids.set_graph_name(name);
+    }
+
+    /// Derives the ID of the operator or stream of the kind from its name.
+    pub(crate) fn derive_id(&mut self, kind: &str, name: &str) -> Result<Uuid, String> {
+        self.derived_ids.derive(kind, name)
+    }
+
     pub fn add_operator<F: OperatorRunner>(
         &mut self,
         id: OperatorId,
@@ -54,6 +85,9 @@ impl Graph {
         write_stream_ids: Vec<StreamId>,
         runner: F,
     ) {
+        if let Some(name) = &name {
+            id.set_name(name);
+        }
         let read_stream_ids: Vec<StreamId> = read_stream_ids
             .into_iter()
             .map(|id| self.resolve_stream_id(id))
@@ -80,6 +114,89 @@ impl Graph {
         );
     }
 
+    /// Sets the names of the operators which must finish running before the operator runs.
+    pub fn set_operator_dependencies(
+        &mut self,
+        operator_id: OperatorId,
+        dependencies: Vec<String>,
+    ) {
+        if let Some(operator) = self.operators.get_mut(&operator_id) {
+            operator.dependencies = dependencies;
+        }
+    }
+
+    /// Sets the time after which the node fails if the operator has not finished setting up.
+    pub fn set_operator_setup_timeout(&mut self, operator_id: OperatorId, timeout: Duration) {
+        if let Some(operator) = self.operators.get_mut(&operator_id) {
+            operator.setup_timeout = Some(timeout);
+        }
+    }
+
+    /// Sets whether the operator may be moved to another node, and its estimated CPU demand.
+    pub fn set_operator_partitioning(
+        &mut self,
+        operator_id: OperatorId,
+        movable: bool,
+        cpu_demand: f64,
+    ) {
+        if let Some(operator) = self.operators.get_mut(&operator_id) {
+            operator.movable = movable;
+            operator.cpu_demand = cpu_demand;
+        }
+    }
+
+    /// Sets the names of the operators which must and must not run on the same node as the
+    /// operator.
+    pub fn set_operator_constraints(
+        &mut self,
+        operator_id: OperatorId,
+        colocated_with: Vec<String>,
+        separated_from: Vec<String>,
+    ) {
+        if let Some(operator) = self.operators.get_mut(&operator_id) {
+            operator.colocated_with = colocated_with;
+            operator.separated_from = separated_from;
+        }
+    }
+
+    /// Runs the operator on a node whose labels match the selector.
+    pub fn set_operator_node_selector(&mut self, operator_id: OperatorId, selector: NodeSelector) {
+        if let Some(operator) = self.operators.get_mut(&operator_id) {
+            operator.node_selector = Some(selector);
+        }
+    }
+
+    /// Sets the nodes on which the operator prefers to run, in order of preference.
+    pub fn set_operator_node_affinity(&mut self, operator_id: OperatorId, node_ids: Vec<NodeId>) {
+        if let Some(operator) = self.operators.get_mut(&operator_id) {
+            operator.node_affinity = node_ids;
+        }
+    }
+
+    /// Moves the operator to another node.
+    pub fn set_operator_node(&mut self, operator_id: OperatorId, node_id: NodeId) {
+        if let Some(operator) = self.operators.get_mut(&operator_id) {
+            operator.node_id = node_id;
+        }
+    }
+
+    /// Documents the messages sent on the stream.
+    pub fn describe_stream(&mut self, stream_id: StreamId, description: StreamDescription) {
+        self.stream_descriptions.insert(stream_id, description);
+    }
+
+    /// Returns the documentation of the messages sent on the stream, if any.
+    pub fn get_stream_description(&self, stream_id: StreamId) -> Option<&StreamDescription> {
+        self.stream_descriptions.get(&stream_id).or_else(|| {
+            self.stream_descriptions
+                .get(&self.resolve_stream_id(stream_id))
+        })
+    }
+
+    pub fn warm_up_stream(&mut self, stream_id: StreamId) {
+        self.warm_up_streams.insert(stream_id);
+    }
+
     pub fn add_operator_stream<D>(&mut self, operator_id: OperatorId, write_stream: &WriteStream<D>)
     where
         for<'a> D: Data + Deserialize<'a>,
@@ -105,7 +222,7 @@ impl Graph {
             .drivers
             .entry(ingest_stream.get_node_id())
             .or_insert_with(|| DriverMetadata::new(node_id));
-        driver.add_ingest_stream(stream_id, setup_hook);
+        driver.add_ingest_stream(&self.driver_section, stream_id, setup_hook);
         // Add stream to graph
         let mut stream_metadata = StreamMetadata::new::<D>(stream_id, Vertex::Driver(node_id));
         self.add_channels(&mut stream_metadata);
@@ -126,7 +243,7 @@ impl Graph {
             .drivers
             .entry(extract_stream.get_node_id())
             .or_insert_with(|| DriverMetadata::new(node_id));
-        driver.add_extract_stream(stream_id, setup_hook);
+        driver.add_extract_stream(&self.driver_section, stream_id, setup_hook);
         // Add channel to stream
         if let Some(stream_metadata) = self.streams.get_mut(&stream_id) {
             let channel = Channel::Unscheduled(ChannelMetadata::new(
@@ -222,6 +339,30 @@ impl Graph {
         }
     }
 
+    /// Selects the driver section to which new driver hooks are added, and returns the previously
+    /// selected section.
+    pub fn set_driver_section(&mut self, name: &str) -> String {
+        std::mem::replace(&mut self.driver_section, name.to_string())
+    }
+
+    pub fn add_driver_setup_hook<F: StreamSetupHook>(&mut self, node_id: NodeId, setup_hook: F) {
+        self.drivers
+            .entry(node_id)
+            .or_insert_with(|| DriverMetadata::new(node_id))
+            .add_setup_hook(&self.driver_section, setup_hook);
+    }
+
+    pub fn add_driver_teardown_hook<F: DriverTeardownHook>(
+        &mut self,
+        node_id: NodeId,
+        teardown_hook: F,
+    ) {
+        self.drivers
+            .entry(node_id)
+            .or_insert_with(|| DriverMetadata::new(node_id))
+            .add_teardown_hook(&self.driver_section, teardown_hook);
+    }
+
     pub fn get_operator(&self, operator_id: OperatorId) -> Option<OperatorMetadata> {
         self.operators.get(&operator_id).cloned()
     }
@@ -238,6 +379,13 @@ impl Graph {
         self.streams.get(&stream_id).cloned()
     }
 
+    /// Returns the node on which the stream is written.
+    pub fn get_source_node(&self, stream_id: StreamId) -> Option<NodeId> {
+        self.streams
+            .get(&self.resolve_stream_id(stream_id))
+            .map(|stream| self.get_node_id(&stream.get_source()))
+    }
+
     pub fn get_streams(&self) -> Vec<StreamMetadata> {
         self.streams.values().cloned().collect()
     }
@@ -245,6 +393,54 @@ impl Graph {
     pub fn get_streams_ref_mut(&mut self) -> Vec<&mut StreamMetadata> {
         self.streams.values_mut().collect()
     }
+    /// Returns the other nodes with which the node shares inter-node channels, sorted by id.
+    ///
+    /// Only returns channels' nodes once the graph is scheduled.
+    pub fn get_peers(&self, node_id: NodeId) -> Vec<NodeId> {
+        let mut peers = BTreeSet::new();
+        for stream in self.streams.values() {
+            for channel in stream.get_channels() {
+                if let Channel::InterNode(cm) = channel {
+                    let source_node_id = self.get_node_id(&cm.source);
+                    let sink_node_id = self.get_node_id(&cm.sink);
+                    if source_node_id == node_id {
+                        peers.insert(sink_node_id);
+                    } else if sink_node_id == node_id {
+                        peers.insert(source_node_id);
+                    }
+                }
+            }
+        }
+        peers.into_iter().collect()
+    }
+
+    /// Returns the other nodes to which the node sends messages of warmed up streams, sorted by
+    /// id.
+    pub fn get_warm_up_peers(&self, node_id: NodeId) -> Vec<NodeId> {
+        let mut peers = BTreeSet::new();
+        for stream_id in self.warm_up_streams.iter() {
+            let stream = match self.streams.get(&self.resolve_stream_id(*stream_id)) {
+                Some(stream) => stream,
+                None => continue,
+            };
+            for channel in stream.get_channels() {
+                if let Channel::InterNode(cm) = channel {
+                    if self.get_node_id(&cm.source) == node_id {
+                        peers.insert(self.get_node_id(&cm.sink));
+                    }
+                }
+            }
+        }
+        peers.into_iter().collect()
+    }
+
+    fn get_node_id(&self, vertex: &Vertex) -> NodeId {
+        match vertex {
+            Vertex::Driver(node_id) => *node_id,
+            Vertex::Operator(operator_id) => self.operators[operator_id].node_id,
+        }
+    }
+
     pub fn get_vertices_on(&self, node_id: NodeId) -> Vec<Vertex> {
         let mut result = Vec::new();
         result.extend(
@@ -262,6 +458,77 @@ impl Graph {
         result
     }
 
+    /// Exports the dataflow graph as a JSON file if the filename ends with `.json`, and as a DOT
+    /// file otherwise.
+    pub fn export(&self, filename: &str) -> std::io::Result<()> {
+        if filename.ends_with(".json") {
+            let mut file = File::create(filename)?;
+            file.write_all(self.to_json().as_bytes())?;
+            file.flush()
+        } else {
+            self.to_dot(filename)
+        }
+    }
+
+    /// Returns the operators and streams of the dataflow graph as a JSON document, including the
+    /// documentation of the streams.
+    pub fn to_json(&self) -> String {
+        let vertex_id = |vertex: &Vertex| match vertex {
+            Vertex::Driver(node_id) => format!("\"driver {}\"", node_id),
+            Vertex::Operator(operator_id) => format!("\"{}\"", operator_id),
+        };
+        let optional =
+            |value: Option<&String>| value.map_or_else(|| "null".to_string(), |v| json_string(v));
+
+        let mut operators: Vec<&OperatorMetadata> = self.operators.values().collect();
+        operators.sort_by_key(|operator| operator.id);
+        let operators: Vec<String> = operators
+            .into_iter()
+            .map(|operator| {
+                format!(
+                    "{{\"id\": \"{}\", \"name\": {}, \"node\": {}}}",
+                    operator.id,
+                    optional(operator.name.as_ref()),
+                    operator.node_id
+                )
+            })
+            .collect();
+
+        let mut streams: Vec<&StreamMetadata> = self.streams.values().collect();
+        streams.sort_by_key(|stream| stream.get_id());
+        let streams: Vec<String> = streams
+            .into_iter()
+            .map(|stream| {
+                let id = stream.get_id();
+                let sinks: Vec<String> = stream
+                    .get_channels()
+                    .iter()
+                    .map(|channel| match channel {
+                        Channel::InterNode(x)
+                        | Channel::InterThread(x)
+                        | Channel::Unscheduled(x) => vertex_id(&x.sink),
+                    })
+                    .collect();
+                let description = self.get_stream_description(id);
+                format!(
+                    "{{\"id\": \"{}\", \"name\": {}, \"source\": {}, \"sinks\": [{}], \
+                     \"description\": {}, \"schema\": {}}}",
+                    id,
+                    optional(id.name().as_ref()),
+                    vertex_id(&stream.get_source()),
+                    sinks.join(", "),
+                    optional(description.map(|d| &d.description)),
+                    optional(description.and_then(|d| d.schema.as_ref()))
+                )
+            })
+            .collect();
+        format!(
+            "{{\n  \"operators\": [\n    {}\n  ],\n  \"streams\": [\n    {}\n  ]\n}}\n",
+            operators.join(",\n    "),
+            streams.join(",\n    ")
+        )
+    }
+
     /// Exports the dataflow graph as a DOT file.
     pub fn to_dot(&self, filename: &str) -> std::io::Result<()> {
         let mut file = File::create(filename)?;
@@ -308,13 +575,28 @@ impl Graph {
                     Vertex::Driver(node_id) => format!("{}", node_id),
                     Vertex::Operator(op_id) => format!("{}", op_id),
                 };
-                writeln!(
-                    file,
-                    "   \"{from}\" -> \"{to}\" [label=\"{stream_id}\"];",
-                    from = from,
-                    to = to,
-                    stream_id = stream.get_id()
-                )?;
+                let stream_id = stream.get_id();
+                match self.get_stream_description(stream_id) {
+                    Some(description) => writeln!(
+                        file,
+                        "   \"{from}\" -> \"{to}\" [label={label}, tooltip={tooltip}];",
+                        from = from,
+                        to = to,
+                        label = json_string(&format!(
+                            "{}\n{}",
+                            stream_id.name().unwrap_or_else(|| stream_id.to_string()),
+                            description.description
+                        )),
+                        tooltip = json_string(description.schema.as_deref().unwrap_or(""))
+                    )?,
+                    None => writeln!(
+                        file,
+                        "   \"{from}\" -> \"{to}\" [label=\"{stream_id}\"];",
+                        from = from,
+                        to = to,
+                        stream_id = stream_id
+                    )?,
+                }
             }
         }
 
@@ -323,3 +605,71 @@ impl Graph {
         Ok(())
     }
 }
+
+/// Quotes the value as a JSON string, which is also a valid DOT string.
+pub(crate) fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+    use super::*;
+
+    use crate::{
+        communication::ControlMessage, node::operator_executor::OperatorExecutor,
+        scheduler::channel_manager::ChannelManager,
+    };
+
+    fn runner(
+        _: Arc<Mutex<ChannelManager>>,
+        _: UnboundedSender<ControlMessage>,
+        _: UnboundedReceiver<ControlMessage>,
+    ) -> OperatorExecutor {
+        unreachable!()
+    }
+
+    /// Test that the JSON export includes the documentation of the streams.
+    #[test]
+    fn test_stream_descriptions_in_json() {
+        let (source, sink) = (OperatorId::new_v4(), OperatorId::new_v4());
+        let stream: WriteStream<u32> = WriteStream::new_with_name("test_stream_descriptions");
+        let mut graph = Graph::new();
+        graph.add_operator(source, None, 0, vec![], vec![stream.get_id()], runner);
+        graph.add_operator_stream(source, &stream);
+        graph.add_operator(sink, None, 0, vec![stream.get_id()], vec![], runner);
+        graph.describe_stream(
+            stream.get_id(),
+            StreamDescription::new("Detected \"objects\"", Some("u32: count")),
+        );
+
+        let json = graph.to_json();
+        assert!(json.contains(&format!(
+            "{{\"id\": \"{}\", \"name\": \"test_stream_descriptions\", \"source\": \"{}\", \
+             \"sinks\": [\"{}\"], \"description\": \"Detected \\\"objects\\\"\", \
+             \"schema\": \"u32: count\"}}",
+            stream.get_id(),
+            source,
+            sink
+        )));
+        assert!(json.contains(&format!(
+            "{{\"id\": \"{}\", \"name\": null, \"node\": 0}}",
+            sink
+        )));
+    }
+}
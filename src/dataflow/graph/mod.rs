@@ -8,6 +8,7 @@ use crate::{
 };
 
 // Private submodules
+mod diff;
 mod edge;
 mod graph;
 mod vertex;
@@ -17,10 +18,14 @@ pub mod default_graph;
 
 // Crate-wide exports
 pub(crate) use edge::{Channel, ChannelMetadata, StreamMetadata};
-pub(crate) use vertex::{DriverMetadata, OperatorMetadata, Vertex};
+pub(crate) use graph::json_string;
+pub(crate) use vertex::{DriverMetadata, DriverSection, OperatorMetadata, Vertex};
 
 // Public exports
+pub use diff::GraphDiff;
+pub use edge::StreamDescription;
 pub use graph::Graph;
+pub use vertex::DEFAULT_DRIVER_SECTION;
 
 pub trait OperatorRunner:
     'static
@@ -61,3 +66,15 @@ impl<T: 'static + Fn(Arc<Mutex<ChannelManager>>) + Sync + Send + Clone> StreamSe
         Box::new(self.clone())
     }
 }
+
+/// Called on the driver's node when the dataflow shuts down, e.g. to flush or close the resources
+/// used by the driver.
+pub trait DriverTeardownHook: 'static + Fn() + Sync + Send {
+    fn box_clone(&self) -> Box<dyn DriverTeardownHook>;
+}
+
+impl<T: 'static + Fn() + Sync + Send + Clone> DriverTeardownHook for T {
+    fn box_clone(&self) -> Box<dyn DriverTeardownHook> {
+        Box::new(self.clone())
+    }
+}
@@ -1,6 +1,8 @@
-use crate::{dataflow::stream::StreamId, node::NodeId, OperatorId};
+use std::time::Duration;
 
-use super::{OperatorRunner, StreamSetupHook};
+use crate::{dataflow::stream::StreamId, node::NodeId, scheduler::NodeSelector, OperatorId};
+
+use super::{DriverTeardownHook, OperatorRunner, StreamSetupHook};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Vertex {
@@ -8,12 +10,57 @@ pub enum Vertex {
     Operator(OperatorId),
 }
 
+/// Name of the driver section to which hooks are added unless another section is selected with
+/// [`default_graph::with_driver_section`](super::default_graph::with_driver_section).
+pub const DEFAULT_DRIVER_SECTION: &str = "default";
+
+/// Hooks installed on a driver by one component of the application, e.g. a library which sets
+/// up its own ingest and extract streams.
+pub struct DriverSection {
+    pub name: String,
+    /// Called before the dataflow runs, in the order in which they were added.
+    pub setup_hooks: Vec<Box<dyn StreamSetupHook>>,
+    /// Called when the dataflow shuts down, in the order in which they were added.
+    pub teardown_hooks: Vec<Box<dyn DriverTeardownHook>>,
+}
+
+impl DriverSection {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            setup_hooks: Vec::new(),
+            teardown_hooks: Vec::new(),
+        }
+    }
+}
+
+impl Clone for DriverSection {
+    fn clone(&self) -> Self {
+        let mut setup_hooks = Vec::new();
+        for i in 0..self.setup_hooks.len() {
+            setup_hooks.push(self.setup_hooks[i].box_clone())
+        }
+        let mut teardown_hooks = Vec::new();
+        for i in 0..self.teardown_hooks.len() {
+            teardown_hooks.push(self.teardown_hooks[i].box_clone())
+        }
+
+        Self {
+            name: self.name.clone(),
+            setup_hooks,
+            teardown_hooks,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct DriverMetadata {
     /// The id of the node on which the driver executes.
     pub id: NodeId,
     pub ingest_stream_ids: Vec<StreamId>,
     pub extract_stream_ids: Vec<StreamId>,
-    pub setup_hooks: Vec<Box<dyn StreamSetupHook>>,
+    /// The sections of the driver, in the order in which they were first used.
+    pub sections: Vec<DriverSection>,
 }
 
 impl DriverMetadata {
@@ -22,34 +69,55 @@ impl DriverMetadata {
             id,
             ingest_stream_ids: Vec::new(),
             extract_stream_ids: Vec::new(),
-            setup_hooks: Vec::new(),
+            sections: Vec::new(),
         }
     }
 
-    pub fn add_ingest_stream<F: StreamSetupHook>(&mut self, stream_id: StreamId, setup_hook: F) {
+    pub fn add_ingest_stream<F: StreamSetupHook>(
+        &mut self,
+        section: &str,
+        stream_id: StreamId,
+        setup_hook: F,
+    ) {
         self.ingest_stream_ids.push(stream_id);
-        self.setup_hooks.push(Box::new(setup_hook));
+        self.add_setup_hook(section, setup_hook);
     }
 
-    pub fn add_extract_stream<F: StreamSetupHook>(&mut self, stream_id: StreamId, setup_hook: F) {
+    pub fn add_extract_stream<F: StreamSetupHook>(
+        &mut self,
+        section: &str,
+        stream_id: StreamId,
+        setup_hook: F,
+    ) {
         self.extract_stream_ids.push(stream_id);
-        self.setup_hooks.push(Box::new(setup_hook));
+        self.add_setup_hook(section, setup_hook);
     }
-}
 
-impl Clone for DriverMetadata {
-    fn clone(&self) -> Self {
-        let mut setup_hooks = Vec::new();
-        for i in 0..self.setup_hooks.len() {
-            setup_hooks.push(self.setup_hooks[i].box_clone())
-        }
+    pub fn add_setup_hook<F: StreamSetupHook>(&mut self, section: &str, setup_hook: F) {
+        self.section_mut(section)
+            .setup_hooks
+            .push(Box::new(setup_hook));
+    }
 
-        Self {
-            id: self.id,
-            ingest_stream_ids: self.ingest_stream_ids.clone(),
-            extract_stream_ids: self.extract_stream_ids.clone(),
-            setup_hooks,
-        }
+    pub fn add_teardown_hook<F: DriverTeardownHook>(&mut self, section: &str, teardown_hook: F) {
+        self.section_mut(section)
+            .teardown_hooks
+            .push(Box::new(teardown_hook));
+    }
+
+    fn section_mut(&mut self, name: &str) -> &mut DriverSection {
+        let index = match self
+            .sections
+            .iter()
+            .position(|section| section.name == name)
+        {
+            Some(index) => index,
+            None => {
+                self.sections.push(DriverSection::new(name));
+                self.sections.len() - 1
+            }
+        };
+        &mut self.sections[index]
     }
 }
 
@@ -62,12 +130,30 @@ pub struct OperatorMetadata {
     /// TODO: change this to a scheduling restriction which is an
     /// enum that may point to a node id.
     pub node_id: NodeId,
+    /// Whether a [`PartitioningScheduler`](crate::scheduler::PartitioningScheduler) may move the
+    /// operator to another node.
+    pub movable: bool,
+    /// Estimated number of cores the operator uses.
+    pub cpu_demand: f64,
+    /// Selects the nodes on which the operator may run by their labels.
+    pub node_selector: Option<NodeSelector>,
+    /// The nodes on which the operator prefers to run, in order of preference.
+    pub node_affinity: Vec<NodeId>,
+    /// The names of the operators which must run on the same node.
+    pub colocated_with: Vec<String>,
+    /// The names of the operators which must not run on the same node.
+    pub separated_from: Vec<String>,
     /// The ids of the read streams the operator uses.
     pub read_stream_ids: Vec<StreamId>,
     /// The ids of the write streams the operators uses.
     pub write_stream_ids: Vec<StreamId>,
     /// Closure to be used to run the operator.
     pub runner: Box<dyn OperatorRunner>,
+    /// The names of the operators which must finish running before the operator runs.
+    pub dependencies: Vec<String>,
+    /// Time after which the node fails if the operator has not finished setting up. Defaults
+    /// to the setup timeout of the node if `None`.
+    pub setup_timeout: Option<Duration>,
 }
 
 impl OperatorMetadata {
@@ -83,9 +169,17 @@ impl OperatorMetadata {
             id,
             name,
             node_id,
+            movable: false,
+            cpu_demand: 1.0,
+            node_selector: None,
+            node_affinity: Vec::new(),
+            colocated_with: Vec::new(),
+            separated_from: Vec::new(),
             read_stream_ids,
             write_stream_ids,
             runner: Box::new(runner),
+            dependencies: Vec::new(),
+            setup_timeout: None,
         }
     }
 }
@@ -96,9 +190,17 @@ impl Clone for OperatorMetadata {
             id: self.id,
             name: self.name.clone(),
             node_id: self.node_id,
+            movable: self.movable,
+            cpu_demand: self.cpu_demand,
+            node_selector: self.node_selector.clone(),
+            node_affinity: self.node_affinity.clone(),
+            colocated_with: self.colocated_with.clone(),
+            separated_from: self.separated_from.clone(),
             read_stream_ids: self.read_stream_ids.clone(),
             write_stream_ids: self.write_stream_ids.clone(),
             runner: self.runner.box_clone(),
+            dependencies: self.dependencies.clone(),
+            setup_timeout: self.setup_timeout,
         }
     }
 }
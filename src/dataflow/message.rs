@@ -25,6 +25,18 @@ impl<D: Data> Message<D> {
         Self::TimestampedData(TimestampedData::new(timestamp, data))
     }
 
+    /// Creates a new backfill `TimestampedData` message, which carries historical data, e.g.
+    /// from corrected sensor logs.
+    ///
+    /// Backfill messages may have a timestamp lower than the low watermark of the stream, and
+    /// do not affect watermarks. They are only delivered to the callbacks registered with
+    /// [`ReadStream::add_backfill_callback`](crate::dataflow::ReadStream::add_backfill_callback).
+    pub fn new_backfill(timestamp: Timestamp, data: D) -> Message<D> {
+        let mut msg = TimestampedData::new(timestamp, data);
+        msg.backfill = true;
+        Self::TimestampedData(msg)
+    }
+
     /// Creates a new `Watermark` message.
     pub fn new_watermark(timestamp: Timestamp) -> Message<D> {
         Self::Watermark(timestamp)
@@ -38,6 +50,14 @@ impl<D: Data> Message<D> {
         }
     }
 
+    /// Returns true if the message was created with [`Message::new_backfill`].
+    pub fn is_backfill(&self) -> bool {
+        match self {
+            Self::TimestampedData(d) => d.backfill,
+            _ => false,
+        }
+    }
+
     pub fn data(&self) -> Option<&D> {
         match self {
             Self::TimestampedData(d) => Some(&d.data),
@@ -70,17 +90,70 @@ pub struct TimestampedData<D: Data> {
     pub timestamp: Timestamp,
     /// Data is an option in case one wants to send null messages.
     pub data: D,
+    /// Whether the message carries historical data injected below the low watermark.
+    pub backfill: bool,
+    /// Time at which the data was produced, if it differs from the timestamp.
+    pub event_time: Option<Timestamp>,
+    /// Time at which the data entered the dataflow, set by
+    /// [`IngestStream`](crate::dataflow::stream::IngestStream)s.
+    pub ingestion_time: Option<Timestamp>,
+    /// Size of the frame in which the message was received from another node, if it was.
+    #[serde(skip)]
+    pub(crate) frame_size: Option<usize>,
 }
 
 impl<D: Data> TimestampedData<D> {
     pub fn new(timestamp: Timestamp, data: D) -> Self {
-        Self { timestamp, data }
+        Self {
+            timestamp,
+            data,
+            backfill: false,
+            event_time: None,
+            ingestion_time: None,
+            frame_size: None,
+        }
+    }
+
+    /// Returns the time of the message in the domain, which defaults to the timestamp.
+    pub fn time(&self, domain: TimeDomain) -> &Timestamp {
+        let time = match domain {
+            TimeDomain::EventTime => &self.event_time,
+            TimeDomain::IngestionTime => &self.ingestion_time,
+        };
+        time.as_ref().unwrap_or(&self.timestamp)
     }
+
+    /// Sets the timestamp of the message, which drives callbacks and watermarks, to its time in
+    /// the domain. The times of the message in both domains are preserved.
+    pub fn retimestamp(&mut self, domain: TimeDomain) {
+        let event_time = self.time(TimeDomain::EventTime).clone();
+        let ingestion_time = self.time(TimeDomain::IngestionTime).clone();
+        self.timestamp = match domain {
+            TimeDomain::EventTime => event_time.clone(),
+            TimeDomain::IngestionTime => ingestion_time.clone(),
+        };
+        self.event_time = Some(event_time);
+        self.ingestion_time = Some(ingestion_time);
+    }
+}
+
+/// The domains of the times carried by a [`TimestampedData`] message.
+///
+/// The timestamp of a message is in the event time domain unless it was re-timestamped, e.g.
+/// with a [`RetimestampOperator`](crate::dataflow::operators::RetimestampOperator).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeDomain {
+    /// The time at which the data was produced, e.g. by a sensor.
+    EventTime,
+    /// The time at which the data entered the dataflow.
+    IngestionTime,
 }
 
 impl<D: Data + PartialEq> PartialEq for TimestampedData<D> {
     fn eq(&self, other: &Self) -> bool {
-        self.timestamp == other.timestamp && self.data == other.data
+        self.timestamp == other.timestamp
+            && self.data == other.data
+            && self.backfill == other.backfill
     }
 }
 
@@ -1,7 +1,11 @@
 //! Functions and structures for building an ERDOS application.
 
 // Public submodules
+pub mod barrier;
+pub mod buffer;
 pub mod callback_builder;
+pub mod context;
+pub mod control;
 #[doc(hidden)]
 pub mod graph;
 pub mod message;
@@ -14,8 +18,13 @@ pub mod stream;
 pub(crate) use stream::EventMakerT;
 
 // Public exports
-pub use message::{Data, Message, Timestamp, TimestampedData};
-pub use operator::{Operator, OperatorConfig};
+pub use buffer::{Buffer, BufferAllocator};
+pub use context::OperatorContext;
+pub use message::{Data, Message, TimeDomain, Timestamp, TimestampedData};
+pub use operator::{
+    CallbackResult, ErrorPolicy, MemoryLimitPolicy, Operator, OperatorConfig, OperatorError,
+    OperatorErrorReport, RunContext, StatefulOperator,
+};
 pub use state::State;
 pub use stream::{LoopStream, ReadStream, StatefulReadStream, WriteStream};
 
@@ -61,9 +70,10 @@ mod tests {
     use crate::dataflow::{
         callback_builder::MultiStreamEventMaker,
         stream::{
-            EventMakerT, InternalReadStream, ReadStream, StreamId, WriteStream, WriteStreamT,
+            errors::WriteStreamError, EventMakerT, InternalReadStream, ReadStream, StreamId,
+            WriteStream, WriteStreamT,
         },
-        Message, Timestamp, TimestampedData,
+        Message, OperatorError, Timestamp, TimestampedData,
     };
 
     // Tests if the `EventMakerT` creates a callback event.
@@ -84,13 +94,38 @@ mod tests {
         // Invoke callback
         match events.pop() {
             Some(event) => {
-                (event.callback)();
+                (event.callback)().unwrap();
                 assert_eq!(rx.try_recv().unwrap(), "msg 1");
             }
             None => unreachable!(),
         }
     }
 
+    // Tests if errors returned by a callback are propagated by the event.
+    #[test]
+    fn test_fallible_callback() {
+        let rs: ReadStream<usize> = ReadStream::new();
+        rs.add_callback(|_t: &Timestamp, msg: &usize| {
+            if *msg > 1 {
+                Err(OperatorError::new("message too large"))
+            } else {
+                Ok(())
+            }
+        });
+
+        let irs: Rc<RefCell<InternalReadStream<usize>>> = (&rs).into();
+        let msg = Message::new_message(Timestamp::new(vec![1]), 1);
+        let mut events = irs.borrow().make_events(Arc::new(msg));
+        assert_eq!((events.pop().unwrap().callback)(), Ok(()));
+
+        let msg = Message::new_message(Timestamp::new(vec![2]), 2);
+        let mut events = irs.borrow().make_events(Arc::new(msg));
+        assert_eq!(
+            (events.pop().unwrap().callback)(),
+            Err(OperatorError::new("message too large"))
+        );
+    }
+
     // Tests if the `EventMakerT` creates a watermark callback event.
     #[test]
     fn test_watermark_callback() {
@@ -115,7 +150,7 @@ mod tests {
         // Invoke callback
         match events.pop() {
             Some(event) => {
-                (event.callback)();
+                (event.callback)().unwrap();
                 assert_eq!(rx.try_recv().unwrap(), "received watermark");
             }
             None => unreachable!(),
@@ -147,7 +182,7 @@ mod tests {
         // Invoke callback
         match events.pop() {
             Some(event) => {
-                (event.callback)();
+                (event.callback)().unwrap();
                 assert_eq!(srs.get_state().count, 42);
             }
             None => unreachable!(),
@@ -178,7 +213,7 @@ mod tests {
         // Invoke callback
         match events.pop() {
             Some(event) => {
-                (event.callback)();
+                (event.callback)().unwrap();
                 assert_eq!(srs.get_state().count, 42);
             }
             None => unreachable!(),
@@ -220,7 +255,7 @@ mod tests {
         // Invoke callback
         match events.pop() {
             Some(event) => {
-                (event.callback)();
+                (event.callback)().unwrap();
                 assert_eq!(rx.try_recv().unwrap(), 3);
             }
             None => unreachable!(),
@@ -286,7 +321,7 @@ mod tests {
         // Invoke callback
         match events.pop() {
             Some(event) => {
-                (event.callback)();
+                (event.callback)().unwrap();
                 assert_eq!(rx.try_recv().unwrap(), 6);
             }
             None => unreachable!(),
@@ -300,16 +335,13 @@ mod tests {
         let state = CounterState { count: 5 };
         let srs = rs.add_state(state);
         let (tx, mut rx) = mpsc::unbounded_channel();
-        let endpoints = vec![SendEndpoint::InterThread(tx)];
+        let endpoints = vec![SendEndpoint::InterThread(tx, Default::default())];
         let ws: WriteStream<usize> =
             WriteStream::from_endpoints(endpoints, StreamId::new_deterministic());
         let rws = srs.add_write_stream(&ws);
         rws.borrow_mut().add_watermark_callback(
             |_t: &Timestamp, state: &CounterState, output_stream: &mut WriteStream<usize>| {
-                let msg = TimestampedData {
-                    timestamp: Timestamp::new(vec![1]),
-                    data: state.count,
-                };
+                let msg = TimestampedData::new(Timestamp::new(vec![1]), state.count);
                 output_stream.send(Message::TimestampedData(msg)).unwrap()
             },
         );
@@ -334,7 +366,7 @@ mod tests {
         // Invoke callback.
         match events.pop() {
             Some(event) => {
-                (event.callback)();
+                (event.callback)().unwrap();
                 match &*rx.try_recv().unwrap() {
                     Message::TimestampedData(msg) => assert_eq!(msg.data, 5),
                     _ => unreachable!(),
@@ -364,7 +396,7 @@ mod tests {
         assert!(events.len() == 1);
         match events.pop() {
             Some(event) => {
-                (event.callback)();
+                (event.callback)().unwrap();
                 assert_eq!(rx1.try_recv().unwrap(), "callback invoked");
             }
             None => unreachable!(),
@@ -375,10 +407,75 @@ mod tests {
         // Invoke callback
         match events.pop() {
             Some(event) => {
-                (event.callback)();
+                (event.callback)().unwrap();
                 assert_eq!(rx2.try_recv().unwrap(), "watermark callback invoked");
             }
             None => unreachable!(),
         }
     }
+
+    // Tests if watermarks are held back until the holds on lower timestamps are released.
+    #[test]
+    fn test_hold_watermark() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let endpoints = vec![SendEndpoint::InterThread(tx, Default::default())];
+        let mut ws: WriteStream<usize> =
+            WriteStream::from_endpoints(endpoints, StreamId::new_deterministic());
+        let t1 = Timestamp::new(vec![1]);
+        let t2 = Timestamp::new(vec![2]);
+        let t3 = Timestamp::new(vec![3]);
+
+        ws.hold_watermark(t2.clone()).unwrap();
+        ws.send(Message::new_watermark(t1.clone())).unwrap();
+        ws.send(Message::new_watermark(t2.clone())).unwrap();
+        ws.send(Message::new_watermark(t3.clone())).unwrap();
+        assert_eq!(*rx.try_recv().unwrap(), Message::new_watermark(t1.clone()));
+        assert!(rx.try_recv().is_err());
+
+        // Output for the held timestamp can be sent after later watermarks were flowed.
+        ws.send(Message::new_message(t2.clone(), 2)).unwrap();
+        assert_eq!(*rx.try_recv().unwrap(), Message::new_message(t2.clone(), 2));
+
+        ws.release_watermark(t2.clone()).unwrap();
+        assert_eq!(*rx.try_recv().unwrap(), Message::new_watermark(t3));
+        assert_eq!(
+            ws.release_watermark(t2),
+            Err(WriteStreamError::TimestampError)
+        );
+        assert_eq!(ws.hold_watermark(t1), Err(WriteStreamError::TimestampError));
+    }
+
+    // Tests if backfill messages below the low watermark are only passed to backfill callbacks.
+    #[test]
+    fn test_backfill_callback() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let endpoints = vec![SendEndpoint::InterThread(tx, Default::default())];
+        let mut ws: WriteStream<usize> =
+            WriteStream::from_endpoints(endpoints, StreamId::new_deterministic());
+        ws.send(Message::new_watermark(Timestamp::new(vec![5])))
+            .unwrap();
+        assert_eq!(
+            ws.send(Message::new_message(Timestamp::new(vec![1]), 1)),
+            Err(WriteStreamError::TimestampError)
+        );
+        ws.send(Message::new_backfill(Timestamp::new(vec![1]), 1))
+            .unwrap();
+        rx.try_recv().unwrap();
+        let backfill_msg = rx.try_recv().unwrap();
+        assert!(backfill_msg.is_backfill());
+
+        let rs: ReadStream<usize> = ReadStream::new();
+        let irs: Rc<RefCell<InternalReadStream<usize>>> = (&rs).into();
+        let (cb_tx, mut cb_rx) = mpsc::unbounded_channel();
+        rs.add_callback(|_t: &Timestamp, _msg: &usize| {
+            panic!("Backfill messages must not be passed to regular callbacks");
+        });
+        rs.add_backfill_callback(move |_t: &Timestamp, msg: &usize| {
+            cb_tx.send(*msg).unwrap();
+        });
+        let mut events = irs.borrow().make_events(backfill_msg);
+        assert_eq!(events.len(), 1);
+        (events.pop().unwrap().callback)().unwrap();
+        assert_eq!(cb_rx.try_recv().unwrap(), 1);
+    }
 }
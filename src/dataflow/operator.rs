@@ -1,17 +1,269 @@
-use crate::{node::NodeId, OperatorId};
+use std::{fmt, time::Duration};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    communication::ControlMessage,
+    dataflow::{
+        graph::StreamDescription,
+        stream::{errors::WriteStreamError, InternalReadStream, StreamId},
+        ReadStream, Timestamp,
+    },
+    node::NodeId,
+    scheduler::NodeSelector,
+    time::Clock,
+    OperatorId,
+};
 
 /// Trait that must be implemented by any operator.
+#[async_trait(?Send)]
 pub trait Operator {
     /// Implement this method if you want to take control of the execution loop of an
     /// operator (e.g., pull messages from streams).
     /// Note: No callbacks are invoked before the completion of this method.
     fn run(&mut self) {}
 
+    /// Like [`Operator::run`], with the context of the operator, e.g. to let the operators which
+    /// depend on it run with [`RunContext::ready`] before a `run` which never returns. Calls
+    /// [`Operator::run`] by default.
+    fn run_with_context(&mut self, _ctx: &mut RunContext) {
+        self.run()
+    }
+
+    /// Implement this method to run the operator in a pull loop, which awaits messages with
+    /// [`ReadStream::recv`] instead of registering callbacks, e.g. for complex control flow
+    /// across several streams. Runs after [`Operator::run`] completes.
+    ///
+    /// The executor still handles the lifecycle of the operator while the loop runs: the
+    /// callbacks registered on the streams, such as the ones which flow watermarks, are invoked
+    /// for the messages the loop receives. Callbacks are invoked for the remaining messages once
+    /// the loop returns. The operators which depend on the operator do not wait for the loop.
+    ///
+    /// Implementations must be annotated with `#[erdos::async_trait(?Send)]`:
+    /// ```
+    /// # use erdos::dataflow::{Operator, ReadStream, RunContext};
+    /// struct PrintOperator {
+    ///     read_stream: ReadStream<u32>,
+    /// }
+    ///
+    /// #[erdos::async_trait(?Send)]
+    /// impl Operator for PrintOperator {
+    ///     async fn run_async(&mut self, ctx: &mut RunContext) {
+    ///         while let Ok(msg) = self.read_stream.recv().await {
+    ///             println!("{:?}: {:?}", ctx.config().name, msg.data());
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    async fn run_async(&mut self, _ctx: &mut RunContext) {}
+
     /// Implement this method if you need to do clean-up before the operator completes.
     /// An operator completes after it has received top watermark on all its read streams.
     fn destroy(&mut self) {}
 }
 
+/// Trait implemented by operators whose state is checkpointed, and restored from the latest
+/// checkpoint when the operator restarts.
+///
+/// Checkpoints are taken periodically if the node is configured with a
+/// [`CheckpointBackend`](crate::node::CheckpointBackend). Callbacks may run while a checkpoint is
+/// taken, so the state which the operator shares with its callbacks must be synchronized, e.g.
+/// with a `Mutex`. Checkpoints are not aligned across operators, so an operator may be restored
+/// to a state which reflects messages its downstream operators did not receive. The state of an
+/// operator is not checkpointed while its [pull loop](Operator::run_async) runs.
+pub trait StatefulOperator: Operator {
+    /// Returns the serialized state of the operator.
+    fn checkpoint(&mut self) -> Result<Vec<u8>, OperatorError>;
+
+    /// Restores the state of the operator from a checkpoint, before [`Operator::run`].
+    fn restore(&mut self, checkpoint: &[u8]) -> Result<(), OperatorError>;
+}
+
+/// Operator run by the executor, which checkpoints it if it is a [`StatefulOperator`].
+pub(crate) trait OperatorT: Operator {
+    fn as_stateful(&mut self) -> Option<&mut dyn StatefulOperator>;
+}
+
+impl<O: Operator> OperatorT for O {
+    default fn as_stateful(&mut self) -> Option<&mut dyn StatefulOperator> {
+        None
+    }
+}
+
+impl<O: StatefulOperator> OperatorT for O {
+    fn as_stateful(&mut self) -> Option<&mut dyn StatefulOperator> {
+        Some(self)
+    }
+}
+
+/// Passed to [`Operator::run_with_context`] and to the pull loop of an operator,
+/// [`Operator::run_async`].
+pub struct RunContext {
+    config: OperatorConfig<()>,
+    /// Tells the node that the operator is ready, until [`RunContext::ready`] is called.
+    ready_tx: Option<UnboundedSender<ControlMessage>>,
+}
+
+impl RunContext {
+    pub(crate) fn new(
+        config: OperatorConfig<()>,
+        ready_tx: UnboundedSender<ControlMessage>,
+    ) -> Self {
+        Self {
+            config,
+            ready_tx: Some(ready_tx),
+        }
+    }
+
+    /// Returns the configuration with which the operator was instantiated, without its argument.
+    pub fn config(&self) -> &OperatorConfig<()> {
+        &self.config
+    }
+
+    /// Lets the operators which [depend](OperatorConfig::depends_on) on the operator run, e.g.
+    /// once a source finished initializing. Called once [`Operator::run`] returns otherwise.
+    pub fn ready(&mut self) {
+        if let Some(ready_tx) = self.ready_tx.take() {
+            if let Err(e) = ready_tx.send(ControlMessage::OperatorRan(self.config.id)) {
+                slog::error!(
+                    crate::TERMINAL_LOGGER,
+                    "Node {}: operator {} unable to notify the node that it is ready: {:?}",
+                    self.config.node_id,
+                    self.config.id,
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// Error returned by a callback to signal that it failed to process a message or watermark.
+///
+/// The [`OperatorExecutor`](crate::node::operator_executor::OperatorExecutor) handles the error
+/// according to the [`ErrorPolicy`] set in the operator's [`OperatorConfig`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperatorError {
+    reason: String,
+}
+
+impl OperatorError {
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+        }
+    }
+
+    /// Returns the reason for which the callback failed.
+    pub fn reason(&self) -> &str {
+        &self.reason[..]
+    }
+}
+
+impl fmt::Display for OperatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl From<WriteStreamError> for OperatorError {
+    fn from(e: WriteStreamError) -> Self {
+        Self::new(format!("failed to send on write stream: {:?}", e))
+    }
+}
+
+impl From<String> for OperatorError {
+    fn from(reason: String) -> Self {
+        Self::new(reason)
+    }
+}
+
+impl From<&str> for OperatorError {
+    fn from(reason: &str) -> Self {
+        Self::new(reason)
+    }
+}
+
+/// Specifies how an operator reacts when one of its callbacks returns an [`OperatorError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Logs the error and continues processing the following messages.
+    SkipMessage,
+    /// Stops processing callbacks and shuts down the operator without invoking
+    /// [`Operator::destroy`]. The top watermark is sent on the operator's write streams, so that
+    /// the downstream operators complete. Other operators keep running.
+    FailOperator,
+    /// Notifies the node, which tears down the whole dataflow.
+    FailDataflow,
+    /// Sends an [`OperatorErrorReport`] on the operator's error stream and continues processing.
+    /// The error stream is available via [`OperatorConfig::error_stream`].
+    SendToErrorStream,
+}
+
+impl Default for ErrorPolicy {
+    fn default() -> Self {
+        ErrorPolicy::SkipMessage
+    }
+}
+
+/// Specifies how an operator reacts when the messages buffered for its callbacks and the state it
+/// [reports](crate::dataflow::OperatorContext::report_state_size) use more memory than its
+/// [limit](OperatorConfig::with_memory_limit).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryLimitPolicy {
+    /// Stops reading messages from the input streams until the callbacks of the buffered messages
+    /// ran and the usage is within the limit.
+    Backpressure,
+    /// Drops the messages received while the usage is over the limit without running their
+    /// callbacks. Watermarks are still processed.
+    DropMessages,
+    /// Shuts down the operator like [`ErrorPolicy::FailOperator`]. Other operators keep running.
+    FailOperator,
+    /// Tears down the whole dataflow like [`ErrorPolicy::FailDataflow`].
+    FailDataflow,
+}
+
+impl Default for MemoryLimitPolicy {
+    fn default() -> Self {
+        MemoryLimitPolicy::Backpressure
+    }
+}
+
+/// Describes a callback failure. Sent on the error stream of operators configured with
+/// [`ErrorPolicy::SendToErrorStream`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperatorErrorReport {
+    /// The ID of the operator whose callback failed.
+    pub operator_id: OperatorId,
+    /// The name of the operator whose callback failed.
+    pub operator_name: Option<String>,
+    /// The node on which the operator runs.
+    pub node_id: NodeId,
+    /// The timestamp of the message or watermark that the callback processed.
+    pub timestamp: Timestamp,
+    /// The error returned by the callback.
+    pub error: OperatorError,
+}
+
+/// Return types accepted from callbacks. Callbacks either return `()`, or
+/// `Result<(), OperatorError>` if they can fail.
+pub trait CallbackResult {
+    fn into_result(self) -> Result<(), OperatorError>;
+}
+
+impl CallbackResult for () {
+    fn into_result(self) -> Result<(), OperatorError> {
+        Ok(())
+    }
+}
+
+impl CallbackResult for Result<(), OperatorError> {
+    fn into_result(self) -> Result<(), OperatorError> {
+        self
+    }
+}
+
 #[derive(Clone)]
 pub struct OperatorConfig<T: Clone> {
     /// A human-readable name for the [`Operator`] used in logging.
@@ -35,10 +287,57 @@ pub struct OperatorConfig<T: Clone> {
     pub flow_watermarks: bool,
     /// The ID of the node on which the operator should run. Defaults to `0`.
     pub node_id: NodeId,
+    /// Whether a [`PartitioningScheduler`](crate::scheduler::PartitioningScheduler) may run the
+    /// [`Operator`] on another node than [`node_id`](OperatorConfig::node_id) to reduce the
+    /// traffic between nodes. Defaults to `false`.
+    pub movable: bool,
+    /// Estimated number of cores the [`Operator`] uses, which a
+    /// [`PartitioningScheduler`](crate::scheduler::PartitioningScheduler) keeps within the
+    /// capacity of the nodes. Defaults to `1.0`.
+    pub cpu_demand: f64,
+    /// Selects the nodes on which the [`Operator`] may run by their labels, instead of running on
+    /// [`node_id`](OperatorConfig::node_id).
+    pub node_selector: Option<NodeSelector>,
+    /// Nodes on which a [`ConstraintScheduler`](crate::scheduler::ConstraintScheduler) prefers to
+    /// run the [`Operator`] if it is [movable](OperatorConfig::movable), in order of preference.
+    pub node_affinity: Vec<NodeId>,
+    /// Names of the operators which must run on the same node as the [`Operator`].
+    pub colocated_with: Vec<String>,
+    /// Names of the operators which must not run on the same node as the [`Operator`].
+    pub separated_from: Vec<String>,
     /// Number of parallel tasks which process callbacks.
     /// A higher number may result in more parallelism; however this may be limited
     /// by dependencies on [`State`](crate::dataflow::State) and timestamps.
     pub num_event_runners: usize,
+    /// How the [`Operator`] reacts to callbacks that return an [`OperatorError`].
+    /// Defaults to [`ErrorPolicy::SkipMessage`].
+    pub error_policy: ErrorPolicy,
+    /// The ID of the stream on which [`OperatorErrorReport`]s are sent. Only set if
+    /// the error policy is [`ErrorPolicy::SendToErrorStream`].
+    pub error_stream_id: Option<StreamId>,
+    /// Maximum number of bytes used by the messages buffered for the callbacks of the
+    /// [`Operator`] and by the state it reports. Unlimited if `None`.
+    pub memory_limit: Option<u64>,
+    /// How the [`Operator`] reacts when it uses more memory than its
+    /// [`memory_limit`](OperatorConfig::memory_limit). Defaults to
+    /// [`MemoryLimitPolicy::Backpressure`].
+    pub memory_limit_policy: MemoryLimitPolicy,
+    /// Names of the operators whose [`Operator::run`] must complete before the [`Operator`]
+    /// runs.
+    pub dependencies: Vec<String>,
+    /// The source of time of the [`Operator`]. Defaults to the wallclock.
+    pub clock: Clock,
+    /// Time after which running the dataflow fails if the [`Operator`] has not finished setting
+    /// up. Defaults to the [setup timeout](crate::Configuration::setup_timeout) of the node.
+    pub setup_timeout: Option<Duration>,
+    /// Duration after which a callback of the [`Operator`] is slow, which logs a warning with the
+    /// timestamp of the callback. Defaults to the
+    /// [slow callback threshold](crate::Configuration::slow_callback_threshold) of the node.
+    pub slow_callback_threshold: Option<Duration>,
+    /// Documentation of the messages on the [`WriteStream`](crate::dataflow::WriteStream)s of the
+    /// [`Operator`], indexed by the position of the stream among the streams returned by
+    /// `connect`.
+    pub stream_descriptions: Vec<(usize, StreamDescription)>,
 }
 
 impl<T: Clone> OperatorConfig<T> {
@@ -49,7 +348,22 @@ impl<T: Clone> OperatorConfig<T> {
             arg: None,
             flow_watermarks: true,
             node_id: 0,
+            movable: false,
+            cpu_demand: 1.0,
+            node_selector: None,
+            node_affinity: Vec::new(),
+            colocated_with: Vec::new(),
+            separated_from: Vec::new(),
             num_event_runners: 1,
+            error_policy: ErrorPolicy::default(),
+            error_stream_id: None,
+            memory_limit: None,
+            memory_limit_policy: MemoryLimitPolicy::default(),
+            dependencies: Vec::new(),
+            clock: Clock::default(),
+            setup_timeout: None,
+            slow_callback_threshold: None,
+            stream_descriptions: Vec::new(),
         }
     }
 
@@ -77,6 +391,60 @@ impl<T: Clone> OperatorConfig<T> {
         self
     }
 
+    /// Sets whether a [`PartitioningScheduler`](crate::scheduler::PartitioningScheduler) may move
+    /// the [`Operator`] to another node, e.g. for stateless operators which read large messages.
+    pub fn movable(mut self, movable: bool) -> Self {
+        self.movable = movable;
+        self
+    }
+
+    /// Sets the estimated number of cores the [`Operator`] uses.
+    pub fn cpu_demand(mut self, cpu_demand: f64) -> Self {
+        self.cpu_demand = cpu_demand;
+        self
+    }
+
+    /// Runs the [`Operator`] on a node whose [labels](crate::Configuration::node_labels) match
+    /// the [`NodeSelector`] expression, e.g. `gpu=true,zone=vehicle`, instead of on a fixed node.
+    ///
+    /// Panics if the expression is invalid.
+    pub fn with_node_selector(mut self, expression: &str) -> Self {
+        self.node_selector = Some(
+            expression
+                .parse()
+                .unwrap_or_else(|e| panic!("{}: {}", e, expression)),
+        );
+        self
+    }
+
+    /// Prefers to run the [`Operator`] on the node when it is placed by a
+    /// [`ConstraintScheduler`](crate::scheduler::ConstraintScheduler), e.g. the node with the
+    /// sensor it reads. Nodes added first are preferred.
+    pub fn with_node_affinity(mut self, node_id: NodeId) -> Self {
+        self.node_affinity.push(node_id);
+        self
+    }
+
+    /// Runs the [`Operator`] on the same node as the operators with the given name, e.g. so that
+    /// they exchange large messages through shared memory.
+    ///
+    /// Running the dataflow fails if no operator has the given name, or if the operators cannot
+    /// be placed so that all co-location and separation constraints hold.
+    pub fn colocate_with(mut self, name: &str) -> Self {
+        self.colocated_with.push(name.to_string());
+        self
+    }
+
+    /// Runs the [`Operator`] on another node than the operators with the given name, e.g. so
+    /// that replicas do not fail together.
+    ///
+    /// Running the dataflow fails if no operator has the given name, or if the operators cannot
+    /// be placed so that all co-location and separation constraints hold.
+    pub fn separate_from(mut self, name: &str) -> Self {
+        self.separated_from.push(name.to_string());
+        self
+    }
+
     /// Sets the maximum number of callbacks the operator can process in parallel
     /// at a time. Defaults to 1.
     pub fn num_event_runners(mut self, num_event_runners: usize) -> Self {
@@ -88,6 +456,99 @@ impl<T: Clone> OperatorConfig<T> {
         self
     }
 
+    /// Sets how the [`Operator`] reacts to callbacks that return an [`OperatorError`].
+    pub fn error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        if error_policy == ErrorPolicy::SendToErrorStream {
+            if self.error_stream_id.is_none() {
+                self.error_stream_id = Some(StreamId::new_deterministic());
+            }
+        } else {
+            self.error_stream_id = None;
+        }
+        self
+    }
+
+    /// Limits the memory used by the messages buffered for the callbacks of the [`Operator`] and
+    /// by the state it [reports](crate::dataflow::OperatorContext::report_state_size), so that a
+    /// leaking operator does not exhaust the memory of the node. The size of a message received
+    /// from another node is the size of its frame, and the size of a message sent within the node
+    /// is the size of its value, without the memory it points to.
+    pub fn with_memory_limit(mut self, bytes: u64) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Sets how the [`Operator`] reacts when it uses more memory than its limit.
+    pub fn memory_limit_policy(mut self, policy: MemoryLimitPolicy) -> Self {
+        self.memory_limit_policy = policy;
+        self
+    }
+
+    /// Runs the [`Operator`] only after the [`Operator::run`] method of the operators with the
+    /// given name completes, e.g. to load calibration data before starting perception, or once
+    /// they call [`RunContext::ready`].
+    ///
+    /// Dependencies may run on other nodes. Running the dataflow fails if no operator has the
+    /// given name, or if the dependencies contain a cycle.
+    pub fn depends_on(mut self, name: &str) -> Self {
+        self.dependencies.push(name.to_string());
+        self
+    }
+
+    /// Sets the source of time of the [`Operator`], e.g. a virtual
+    /// [`Clock`] in tests.
+    pub fn clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Sets the time after which running the dataflow fails if the [`Operator`] has not finished
+    /// setting up, e.g. because it waits for a GPU.
+    pub fn setup_timeout(mut self, setup_timeout: Duration) -> Self {
+        self.setup_timeout = Some(setup_timeout);
+        self
+    }
+
+    /// Sets the duration after which a callback of the [`Operator`] is slow, e.g. for operators
+    /// which process large batches.
+    pub fn slow_callback_threshold(mut self, slow_callback_threshold: Duration) -> Self {
+        self.slow_callback_threshold = Some(slow_callback_threshold);
+        self
+    }
+
+    /// Documents the messages on the `index`-th stream returned by the `connect` method of the
+    /// [`Operator`], and optionally their schema. The documentation is included in the exports
+    /// of the dataflow graph, so that large pipelines remain understandable.
+    ///
+    /// Connecting the [`Operator`] panics if it writes on fewer streams.
+    pub fn describe_write_stream(
+        mut self,
+        index: usize,
+        description: &str,
+        schema: Option<&str>,
+    ) -> Self {
+        self.stream_descriptions
+            .push((index, StreamDescription::new(description, schema)));
+        self
+    }
+
+    /// Returns a [`ReadStream`] on which the [`Operator`] sends an [`OperatorErrorReport`]
+    /// for every failed callback. The stream can be connected to other operators or to an
+    /// [`ExtractStream`](crate::dataflow::stream::ExtractStream).
+    ///
+    /// Panics if the error policy is not [`ErrorPolicy::SendToErrorStream`].
+    pub fn error_stream(&self) -> ReadStream<OperatorErrorReport> {
+        let id = self
+            .error_stream_id
+            .expect("The error stream requires ErrorPolicy::SendToErrorStream");
+        let name = match &self.name {
+            Some(name) => format!("{}-errors", name),
+            None => format!("{}-errors", id),
+        };
+        ReadStream::from(InternalReadStream::new_with_id_name(id, &name))
+    }
+
     /// Removes the argument to lose type information. Used in
     /// [`OperatorExecutor`](crate::node::operator_executor::OperatorExecutor).
     pub(crate) fn drop_arg(self) -> OperatorConfig<()> {
@@ -97,7 +558,22 @@ impl<T: Clone> OperatorConfig<T> {
             arg: None,
             flow_watermarks: self.flow_watermarks,
             node_id: self.node_id,
+            movable: self.movable,
+            cpu_demand: self.cpu_demand,
+            node_selector: self.node_selector,
+            node_affinity: self.node_affinity,
+            colocated_with: self.colocated_with,
+            separated_from: self.separated_from,
             num_event_runners: self.num_event_runners,
+            error_policy: self.error_policy,
+            error_stream_id: self.error_stream_id,
+            memory_limit: self.memory_limit,
+            memory_limit_policy: self.memory_limit_policy,
+            dependencies: self.dependencies,
+            clock: self.clock,
+            setup_timeout: self.setup_timeout,
+            slow_callback_threshold: self.slow_callback_threshold,
+            stream_descriptions: self.stream_descriptions,
         }
     }
 }
@@ -0,0 +1,266 @@
+use std::task::{Context, Poll};
+
+use async_trait::async_trait;
+use futures::future;
+use serde::{Deserialize, Serialize};
+
+use crate::dataflow::{
+    operators::fn_operators::connect_pull_operator,
+    stream::{errors::ReadError, WriteStreamT},
+    Data, Message, Operator, OperatorConfig, ReadStream, RunContext, Timestamp, WriteStream,
+};
+
+/// Configures the operator connected by [`connect_align`].
+#[derive(Clone, Debug)]
+pub struct AlignConfig {
+    /// Largest difference between the time of the reference message and the time of a message
+    /// aligned with it, in units of the first coordinate of the timestamps.
+    pub max_skew: u64,
+}
+
+impl AlignConfig {
+    pub fn new(max_skew: u64) -> Self {
+        Self { max_skew }
+    }
+}
+
+/// The messages aligned with a message of the reference stream, sent by the operator connected
+/// by [`connect_align`] for the timestamp of the reference message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlignedMessages<D> {
+    /// For each input stream, in the order they were connected, the timestamp and the data of
+    /// the message nearest in time to the reference message, or `None` if no message of the
+    /// stream is within the maximum skew. The first entry is the reference message.
+    pub messages: Vec<Option<(Timestamp, D)>>,
+}
+
+/// Returns the time of a timestamp which the skew is measured in.
+fn time_of(timestamp: &Timestamp) -> u64 {
+    timestamp.time.first().copied().unwrap_or_default()
+}
+
+/// Aligns the messages of the streams with the messages of the first stream, the reference.
+struct AlignOperator<D: Data> {
+    name: String,
+    max_skew: u64,
+    read_streams: Vec<ReadStream<D>>,
+    write_stream: WriteStream<AlignedMessages<D>>,
+    /// The messages of the reference stream which are not aligned yet, in order of arrival.
+    pending: Vec<(Timestamp, D)>,
+    /// The messages of the other streams which may be aligned with a reference message, indexed
+    /// like the read streams.
+    buffers: Vec<Vec<(Timestamp, D)>>,
+    /// The highest watermark received on each stream.
+    watermarks: Vec<Option<Timestamp>>,
+    /// Whether each stream is closed, either by a top watermark or by an error.
+    closed: Vec<bool>,
+    /// The watermark last sent.
+    sent_watermark: Option<Timestamp>,
+    /// The stream polled first, which rotates so that a busy stream does not starve the others.
+    next_poll: usize,
+}
+
+impl<D> AlignOperator<D>
+where
+    for<'a> D: Data + Deserialize<'a>,
+{
+    /// Returns the index of the stream and the next message received on any open stream.
+    fn poll_streams(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<(usize, Result<Message<D>, ReadError>)> {
+        let num_streams = self.read_streams.len();
+        for offset in 0..num_streams {
+            let index = (self.next_poll + offset) % num_streams;
+            if self.closed[index] {
+                continue;
+            }
+            if let Poll::Ready(result) = self.read_streams[index].poll_recv(cx) {
+                self.next_poll = (index + 1) % num_streams;
+                return Poll::Ready((index, result));
+            }
+        }
+        Poll::Pending
+    }
+
+    /// Whether all the messages of the stream within the maximum skew of the time were received.
+    fn is_complete(&self, index: usize, time: u64) -> bool {
+        self.closed[index]
+            || self.watermarks[index].as_ref().map_or(false, |watermark| {
+                time_of(watermark) >= time.saturating_add(self.max_skew)
+            })
+    }
+
+    /// Sends the aligned messages of the pending reference messages for which the messages of all
+    /// streams were received, in timestamp order, and the watermark of the reference stream once
+    /// no reference message up to it is pending.
+    fn align(&mut self) {
+        self.pending.sort_by(|(t1, _), (t2, _)| t1.cmp(t2));
+        while let Some((timestamp, _)) = self.pending.first() {
+            let time = time_of(timestamp);
+            if !(1..self.read_streams.len()).all(|index| self.is_complete(index, time)) {
+                break;
+            }
+            let (timestamp, data) = self.pending.remove(0);
+            let mut messages = vec![Some((timestamp.clone(), data))];
+            for buffer in self.buffers.iter().skip(1) {
+                let nearest = buffer
+                    .iter()
+                    .map(|(t, data)| ((time_of(t) as i128 - time as i128).abs(), t, data))
+                    .filter(|(skew, _, _)| *skew <= self.max_skew as i128)
+                    .min_by_key(|(skew, _, _)| *skew)
+                    .map(|(_, t, data)| (t.clone(), data.clone()));
+                messages.push(nearest);
+            }
+            let msg = Message::new_message(timestamp, AlignedMessages { messages });
+            self.send(msg);
+        }
+
+        let watermark = if self.closed[0] {
+            Some(Timestamp::top())
+        } else {
+            self.watermarks[0].clone()
+        };
+        if let Some(watermark) = watermark {
+            let covers_pending = self.pending.iter().any(|(t, _)| *t <= watermark);
+            if !covers_pending && self.sent_watermark.as_ref() < Some(&watermark) {
+                self.sent_watermark = Some(watermark.clone());
+                self.send(Message::new_watermark(watermark));
+            }
+        }
+
+        // Drops the messages which are too early to be aligned with the next reference messages.
+        let min_time = self
+            .pending
+            .iter()
+            .map(|(t, _)| time_of(t))
+            .chain(self.watermarks[0].iter().map(time_of))
+            .min()
+            .unwrap_or_default();
+        let max_skew = self.max_skew;
+        for buffer in self.buffers.iter_mut().skip(1) {
+            buffer.retain(|(t, _)| time_of(t).saturating_add(max_skew) >= min_time);
+        }
+    }
+
+    fn send(&mut self, msg: Message<AlignedMessages<D>>) {
+        if let Err(e) = self.write_stream.send(msg) {
+            slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "{} unable to send message on stream {}: {:?}",
+                self.name,
+                self.write_stream.get_id(),
+                e
+            );
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<D> Operator for AlignOperator<D>
+where
+    for<'a> D: Data + Deserialize<'a>,
+{
+    async fn run_async(&mut self, _ctx: &mut RunContext) {
+        while self.closed.iter().any(|closed| !closed) {
+            let (index, result) = future::poll_fn(|cx| self.poll_streams(cx)).await;
+            match result {
+                Ok(Message::TimestampedData(msg)) if index == 0 => {
+                    self.pending.push((msg.timestamp, msg.data));
+                }
+                Ok(Message::TimestampedData(msg)) => {
+                    self.buffers[index].push((msg.timestamp, msg.data));
+                }
+                Ok(Message::Watermark(watermark)) => {
+                    if watermark.is_top() {
+                        self.closed[index] = true;
+                    } else {
+                        self.watermarks[index] = Some(watermark);
+                    }
+                }
+                Err(ReadError::SerializationError) => continue,
+                Err(_) => self.closed[index] = true,
+            }
+            self.align();
+        }
+    }
+}
+
+/// Connects an operator which aligns the messages of sensor streams, e.g. of cameras and lidars
+/// which sample at different rates, and returns the stream of the aligned messages.
+///
+/// For each message of the first stream, the reference, the operator sends the message nearest
+/// in time from each other stream, within the [maximum skew](AlignConfig::max_skew), for the
+/// timestamp of the reference message. The time of a timestamp is its first coordinate. The
+/// messages are aligned once the watermarks of the other streams reach the time of the reference
+/// message plus the maximum skew, so that all messages which could be nearer were received, and
+/// the watermarks of the reference stream are forwarded once the messages up to them are
+/// aligned.
+///
+/// # Example
+/// The below example shows how to align the messages of a lidar and a radar, within 50
+/// milliseconds, with the messages of a camera.
+///
+/// ```
+/// # use erdos::dataflow::{
+/// #     operators::{connect_align, AlignConfig, AlignedMessages},
+/// #     stream::IngestStream,
+/// #     OperatorConfig, ReadStream,
+/// # };
+/// #
+/// # let camera_stream = IngestStream::<Vec<u8>>::new(0);
+/// # let lidar_stream = IngestStream::<Vec<u8>>::new(0);
+/// # let radar_stream = IngestStream::<Vec<u8>>::new(0);
+/// #
+/// let aligned: ReadStream<AlignedMessages<Vec<u8>>> = connect_align(
+///     OperatorConfig::new()
+///         .name("AlignSensors")
+///         .arg(AlignConfig::new(50)),
+///     vec![&camera_stream, &lidar_stream, &radar_stream],
+/// );
+/// ```
+pub fn connect_align<D, S>(
+    config: OperatorConfig<AlignConfig>,
+    read_streams: Vec<S>,
+) -> ReadStream<AlignedMessages<D>>
+where
+    for<'a> D: Data + Deserialize<'a>,
+    S: Into<ReadStream<D>>,
+{
+    let read_streams: Vec<ReadStream<D>> = read_streams.into_iter().map(Into::into).collect();
+    assert!(
+        !read_streams.is_empty(),
+        "Unable to align messages: no stream supplied"
+    );
+    let write_stream = WriteStream::new();
+    connect_pull_operator(
+        config,
+        read_streams,
+        Some(write_stream),
+        |config, read_streams, write_stream| {
+            let name = config
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("AlignOperator {}", config.id));
+            let max_skew = config
+                .arg
+                .as_ref()
+                .unwrap_or_else(|| panic!("{}: no align config supplied", name))
+                .max_skew;
+            let num_streams = read_streams.len();
+            AlignOperator {
+                name,
+                max_skew,
+                read_streams,
+                write_stream: write_stream.unwrap(),
+                pending: Vec::new(),
+                buffers: vec![Vec::new(); num_streams],
+                watermarks: vec![None; num_streams],
+                closed: vec![false; num_streams],
+                sent_watermark: None,
+                next_poll: 0,
+            }
+        },
+    )
+    .unwrap()
+}
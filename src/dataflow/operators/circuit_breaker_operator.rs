@@ -0,0 +1,397 @@
+use std::{collections::VecDeque, future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dataflow::{
+        stream::WriteStreamT, Data, Message, Operator, OperatorConfig, OperatorError, ReadStream,
+        RunContext, Timestamp, WriteStream,
+    },
+    node::OperatorMetrics,
+    time::Clock,
+};
+
+type Call<D1, D2> =
+    Arc<dyn Fn(D1) -> Pin<Box<dyn Future<Output = Result<D2, OperatorError>>>> + Send + Sync>;
+
+/// The state of the circuit of a [`CircuitBreakerOperator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CircuitState {
+    /// The calls are made.
+    Closed,
+    /// The calls are short-circuited until the open duration elapsed.
+    Open,
+    /// The next call is a trial which closes the circuit if it succeeds, and opens it otherwise.
+    HalfOpen,
+}
+
+impl CircuitState {
+    /// Value of the state exported by the `circuit_breaker_state` gauge.
+    fn gauge_value(self) -> f64 {
+        match self {
+            CircuitState::Closed => 0.0,
+            CircuitState::HalfOpen => 1.0,
+            CircuitState::Open => 2.0,
+        }
+    }
+}
+
+/// A change of the state of the circuit, sent by the [`CircuitBreakerOperator`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CircuitTransition {
+    pub from: CircuitState,
+    pub to: CircuitState,
+    /// The timestamp of the message whose call changed the state.
+    pub timestamp: Timestamp,
+    /// The rate of failed calls in the window when the state changed.
+    pub failure_rate: f64,
+}
+
+/// Configures the [`CircuitBreakerOperator`].
+pub struct CircuitBreakerConfig<D1, D2> {
+    call: Call<D1, D2>,
+    /// The circuit opens once the rate of failed calls in the window reaches the threshold.
+    /// Defaults to 0.5.
+    pub failure_rate_threshold: f64,
+    /// Number of most recent calls from which the failure rate is computed. Defaults to 10.
+    pub window: usize,
+    /// Number of calls in the window below which the circuit does not open. Defaults to 5.
+    pub min_calls: usize,
+    /// Duration after which a successful call counts as failed, if any. Defaults to none.
+    pub slow_call_threshold: Option<Duration>,
+    /// Duration for which the circuit stays open before a trial call. Defaults to 1 second.
+    pub open_duration: Duration,
+    /// Value sent for the messages whose call failed or was short-circuited, if any. The
+    /// messages are skipped otherwise. Defaults to none.
+    pub fallback: Option<D2>,
+}
+
+impl<D1, D2> CircuitBreakerConfig<D1, D2> {
+    /// Returns the configuration which protects the async function, e.g. a call to a downstream
+    /// service.
+    pub fn new<F, Fut>(call: F) -> Self
+    where
+        F: 'static + Fn(D1) -> Fut + Send + Sync,
+        Fut: 'static + Future<Output = Result<D2, OperatorError>>,
+    {
+        Self {
+            call: Arc::new(move |data| Box::pin(call(data))),
+            failure_rate_threshold: 0.5,
+            window: 10,
+            min_calls: 5,
+            slow_call_threshold: None,
+            open_duration: Duration::from_secs(1),
+            fallback: None,
+        }
+    }
+
+    pub fn failure_rate_threshold(mut self, failure_rate_threshold: f64) -> Self {
+        self.failure_rate_threshold = failure_rate_threshold;
+        self
+    }
+
+    pub fn window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    pub fn min_calls(mut self, min_calls: usize) -> Self {
+        self.min_calls = min_calls;
+        self
+    }
+
+    pub fn slow_call_threshold(mut self, slow_call_threshold: Duration) -> Self {
+        self.slow_call_threshold = Some(slow_call_threshold);
+        self
+    }
+
+    pub fn open_duration(mut self, open_duration: Duration) -> Self {
+        self.open_duration = open_duration;
+        self
+    }
+
+    pub fn fallback(mut self, fallback: D2) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+}
+
+impl<D1, D2: Clone> Clone for CircuitBreakerConfig<D1, D2> {
+    fn clone(&self) -> Self {
+        Self {
+            call: Arc::clone(&self.call),
+            failure_rate_threshold: self.failure_rate_threshold,
+            window: self.window,
+            min_calls: self.min_calls,
+            slow_call_threshold: self.slow_call_threshold,
+            open_duration: self.open_duration,
+            fallback: self.fallback.clone(),
+        }
+    }
+}
+
+/// An operator that maps each message of a stream with an async function, e.g. a call to a
+/// downstream service, and stops calling the function while too many calls fail or are slow.
+///
+/// Once the rate of failed calls in the [window](CircuitBreakerConfig::window) reaches the
+/// threshold, the circuit opens and the messages are short-circuited: the operator sends the
+/// [fallback value](CircuitBreakerConfig::fallback) for their timestamp, or skips them. After
+/// the open duration, the call for the next message is a trial which closes the circuit if it
+/// succeeds. Each change of state is sent as a [`CircuitTransition`] on the second write stream,
+/// and the state, calls, failures and short-circuits are exported as metrics of the operator.
+///
+/// Latencies and open durations are measured on the [`Clock`] of the operator. The operator sends
+/// its own watermarks, so it must be connected with
+/// [`flow_watermarks`](OperatorConfig::flow_watermarks) set to `false`.
+///
+/// # Example
+/// The below example shows how to stop looking up the messages in a service for 5 seconds once
+/// half of the last 20 lookups failed.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use erdos::dataflow::{
+/// #     stream::IngestStream,
+/// #     operators::{CircuitBreakerConfig, CircuitBreakerOperator},
+/// #     OperatorConfig, OperatorError,
+/// # };
+/// # use erdos::*;
+/// #
+/// # let mut ingest_stream: IngestStream<u32> = IngestStream::new(0);
+/// #
+/// async fn lookup(key: u32) -> Result<String, OperatorError> {
+///     Ok(format!("value of {}", key))
+/// }
+///
+/// let breaker_config = CircuitBreakerConfig::new(lookup)
+///     .failure_rate_threshold(0.5)
+///     .window(20)
+///     .open_duration(Duration::from_secs(5))
+///     .fallback(String::new());
+/// let config = OperatorConfig::new()
+///     .name("CircuitBreakerOperator")
+///     .flow_watermarks(false)
+///     .arg(breaker_config);
+/// let (values, transitions) =
+///     connect_2_write!(CircuitBreakerOperator<u32, String>, config, ingest_stream);
+/// ```
+pub struct CircuitBreakerOperator<D1: Data, D2: Data> {
+    breaker_config: CircuitBreakerConfig<D1, D2>,
+    name: String,
+    clock: Clock,
+    metrics: OperatorMetrics,
+    input_stream: ReadStream<D1>,
+    output_stream: WriteStream<D2>,
+    transition_stream: WriteStream<CircuitTransition>,
+    state: CircuitState,
+    /// Whether each of the most recent calls failed.
+    outcomes: VecDeque<bool>,
+    /// The time at which the circuit last opened.
+    opened_at: Duration,
+}
+
+impl<D1, D2> CircuitBreakerOperator<D1, D2>
+where
+    for<'a> D1: Data + Deserialize<'a>,
+    for<'a> D2: Data + Deserialize<'a>,
+{
+    pub fn new(
+        config: OperatorConfig<CircuitBreakerConfig<D1, D2>>,
+        input_stream: ReadStream<D1>,
+        output_stream: WriteStream<D2>,
+        transition_stream: WriteStream<CircuitTransition>,
+    ) -> Self {
+        assert!(
+            !config.flow_watermarks,
+            "CircuitBreakerOperator sends its own watermarks"
+        );
+        let name = config
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("CircuitBreakerOperator {}", config.id));
+        // Labels the metrics like those registered through the operator's context.
+        let metrics = match &config.name {
+            Some(name) => OperatorMetrics::new(name),
+            None => OperatorMetrics::new(&config.id.to_string()),
+        };
+        Self {
+            breaker_config: config
+                .arg
+                .unwrap_or_else(|| panic!("{}: no circuit breaker config supplied", name)),
+            metrics,
+            name,
+            clock: config.clock,
+            input_stream,
+            output_stream,
+            transition_stream,
+            state: CircuitState::Closed,
+            outcomes: VecDeque::new(),
+            opened_at: Duration::default(),
+        }
+    }
+
+    pub fn connect(
+        _input_stream: &ReadStream<D1>,
+    ) -> (WriteStream<D2>, WriteStream<CircuitTransition>) {
+        (WriteStream::new(), WriteStream::new())
+    }
+
+    fn failure_rate(&self) -> f64 {
+        if self.outcomes.is_empty() {
+            return 0.0;
+        }
+        let failures = self.outcomes.iter().filter(|&&failed| failed).count();
+        failures as f64 / self.outcomes.len() as f64
+    }
+
+    fn transition(&mut self, to: CircuitState, timestamp: &Timestamp) {
+        let transition = CircuitTransition {
+            from: self.state,
+            to,
+            timestamp: timestamp.clone(),
+            failure_rate: self.failure_rate(),
+        };
+        slog::info!(
+            crate::TERMINAL_LOGGER,
+            "{}: circuit changed from {:?} to {:?} at {:?} with a failure rate of {}",
+            self.name,
+            transition.from,
+            transition.to,
+            timestamp,
+            transition.failure_rate
+        );
+        self.state = to;
+        match to {
+            CircuitState::Open => self.opened_at = self.clock.now(),
+            CircuitState::Closed => self.outcomes.clear(),
+            CircuitState::HalfOpen => (),
+        }
+        self.metrics
+            .gauge("circuit_breaker_state")
+            .set(to.gauge_value());
+        self.metrics
+            .counter("circuit_breaker_transitions_total")
+            .increment();
+        let msg = Message::new_message(timestamp.clone(), transition);
+        Self::send(&self.name, &mut self.transition_stream, msg);
+    }
+
+    /// Makes the call for the message unless the circuit is open, and returns the output of the
+    /// call if it succeeded.
+    async fn process(&mut self, timestamp: &Timestamp, data: D1) -> Option<D2> {
+        if self.state == CircuitState::Open {
+            if self.clock.elapsed(self.opened_at) < self.breaker_config.open_duration {
+                self.metrics
+                    .counter("circuit_breaker_short_circuits_total")
+                    .increment();
+                return None;
+            }
+            self.transition(CircuitState::HalfOpen, timestamp);
+        }
+
+        let start = self.clock.now();
+        let result = (self.breaker_config.call)(data).await;
+        let latency = self.clock.elapsed(start);
+        self.metrics
+            .counter("circuit_breaker_calls_total")
+            .increment();
+        let is_slow = self
+            .breaker_config
+            .slow_call_threshold
+            .map_or(false, |threshold| latency > threshold);
+        let failed = match &result {
+            Err(e) => {
+                slog::warn!(
+                    crate::TERMINAL_LOGGER,
+                    "{}: call for {:?} failed: {}",
+                    self.name,
+                    timestamp,
+                    e
+                );
+                true
+            }
+            Ok(_) => is_slow,
+        };
+        if failed {
+            self.metrics
+                .counter("circuit_breaker_failures_total")
+                .increment();
+        }
+
+        self.outcomes.push_back(failed);
+        if self.outcomes.len() > self.breaker_config.window {
+            self.outcomes.pop_front();
+        }
+        match self.state {
+            CircuitState::HalfOpen if failed => self.transition(CircuitState::Open, timestamp),
+            CircuitState::HalfOpen => self.transition(CircuitState::Closed, timestamp),
+            _ if self.outcomes.len() >= self.breaker_config.min_calls
+                && self.failure_rate() >= self.breaker_config.failure_rate_threshold =>
+            {
+                self.transition(CircuitState::Open, timestamp)
+            }
+            _ => (),
+        }
+        result.ok()
+    }
+
+    fn send<D: Data>(name: &str, write_stream: &mut WriteStream<D>, msg: Message<D>) {
+        if let Err(e) = write_stream.send(msg) {
+            slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "{} unable to send message on stream {}: {:?}",
+                name,
+                write_stream.get_id(),
+                e
+            );
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<D1, D2> Operator for CircuitBreakerOperator<D1, D2>
+where
+    for<'a> D1: Data + Deserialize<'a>,
+    for<'a> D2: Data + Deserialize<'a>,
+{
+    async fn run_async(&mut self, _ctx: &mut RunContext) {
+        self.metrics
+            .gauge("circuit_breaker_state")
+            .set(self.state.gauge_value());
+        while let Ok(msg) = self.input_stream.recv().await {
+            match msg {
+                Message::TimestampedData(msg) => {
+                    let timestamp: Timestamp = msg.timestamp;
+                    let output = match self.process(&timestamp, msg.data).await {
+                        Some(output) => Some(output),
+                        None => self.breaker_config.fallback.clone(),
+                    };
+                    if let Some(output) = output {
+                        Self::send(
+                            &self.name,
+                            &mut self.output_stream,
+                            Message::new_message(timestamp, output),
+                        );
+                    }
+                }
+                Message::Watermark(watermark) => {
+                    let is_top = watermark.is_top();
+                    Self::send(
+                        &self.name,
+                        &mut self.output_stream,
+                        Message::new_watermark(watermark.clone()),
+                    );
+                    Self::send(
+                        &self.name,
+                        &mut self.transition_stream,
+                        Message::new_watermark(watermark),
+                    );
+                    if is_top {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use crate::dataflow::message::Message;
+use crate::dataflow::{
+    stream::WriteStreamT, Data, Operator, OperatorConfig, OperatorError, ReadStream, Timestamp,
+    WriteStream,
+};
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+/// A message sent by the [`DeltaEncodeOperator`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DeltaFrame<D, P> {
+    /// The full message, from which the [`DeltaDecodeOperator`] can start decoding.
+    Keyframe(D),
+    /// The difference between the message and the previous message.
+    Delta(P),
+}
+
+/// Configures the [`DeltaEncodeOperator`].
+#[derive(Clone)]
+pub struct DeltaEncodeConfig<F> {
+    /// Computes the difference between the previous message and the current message.
+    pub diff: F,
+    /// Number of messages between successive keyframes. A value of 1 sends only keyframes.
+    pub keyframe_interval: usize,
+}
+
+impl<F> DeltaEncodeConfig<F> {
+    pub fn new(diff: F, keyframe_interval: usize) -> Self {
+        assert!(
+            keyframe_interval > 0,
+            "The keyframe interval must be greater than 0"
+        );
+        Self {
+            diff,
+            keyframe_interval,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct EncodeState<D: Data, P: Data> {
+    output_stream: WriteStream<DeltaFrame<D, P>>,
+    previous: Option<D>,
+    messages_since_keyframe: usize,
+}
+
+/// An operator that sends the differences between consecutive messages of type D as patches of
+/// type P, using the provided diff function.
+///
+/// A full message is sent as a keyframe for the first message, and then every `keyframe_interval`
+/// messages, so that receivers which join late or lose messages can resume decoding. The
+/// differences are applied by a [`DeltaDecodeOperator`]. This reduces bandwidth for slowly
+/// changing data, such as occupancy grids.
+///
+/// # Example
+/// The below example shows how to send the difference between successive u64 counters, with a
+/// keyframe every 10 messages.
+///
+/// ```
+/// # use erdos::dataflow::{
+/// #     stream::IngestStream,
+/// #     operators::{DeltaDecodeOperator, DeltaEncodeConfig, DeltaEncodeOperator},
+/// #     OperatorConfig,
+/// # };
+/// # use erdos::*;
+/// #
+/// # let mut u64_stream = IngestStream::new(0);
+/// #
+/// let encode_config = OperatorConfig::new().name("DeltaEncodeOperator").arg(
+///     DeltaEncodeConfig::new(|previous: &u64, current: &u64| current - previous, 10),
+/// );
+/// let delta_stream = connect_1_write!(
+///     DeltaEncodeOperator<u64, u64>,
+///     encode_config,
+///     u64_stream
+/// );
+/// let decode_config = OperatorConfig::new()
+///     .name("DeltaDecodeOperator")
+///     .arg(|previous: &u64, delta: &u64| previous + delta);
+/// let decoded_stream = connect_1_write!(
+///     DeltaDecodeOperator<u64, u64>,
+///     decode_config,
+///     delta_stream
+/// );
+/// ```
+pub struct DeltaEncodeOperator<D: Data, P: Data> {
+    phantom_data: PhantomData<(D, P)>,
+}
+
+impl<D, P> DeltaEncodeOperator<D, P>
+where
+    for<'a> D: Data + Deserialize<'a>,
+    for<'a> P: Data + Deserialize<'a>,
+{
+    /// Returns a new instance of the DeltaEncodeOperator.
+    ///
+    /// # Arguments
+    /// * `config` - An instance of OperatorConfig that provides the [`DeltaEncodeConfig`].
+    /// * `input_stream` - Represents the incoming stream of messages of type D.
+    /// * `output_stream` - Represents an outgoing stream of keyframes and patches.
+    pub fn new<F: 'static + Clone + Fn(&D, &D) -> P>(
+        config: OperatorConfig<DeltaEncodeConfig<F>>,
+        input_stream: ReadStream<D>,
+        output_stream: WriteStream<DeltaFrame<D, P>>,
+    ) -> Self {
+        let stateful_stream = input_stream.add_state(EncodeState {
+            output_stream,
+            previous: None,
+            messages_since_keyframe: 0,
+        });
+
+        let name: String = config
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("DeltaEncodeOperator {}", config.id));
+        let delta_config = config
+            .arg
+            .unwrap_or_else(|| panic!("{}: no delta encode config supplied", name));
+
+        stateful_stream.add_callback(
+            move |t: &Timestamp, msg: &D, state: &mut EncodeState<D, P>| {
+                Self::on_data_callback(t, msg, state, &delta_config)
+            },
+        );
+        Self {
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// Returns a new instance of a WriteStream to send its outgoing messages on.
+    ///
+    /// # Arguments
+    /// * `input_stream` - Represents the incoming stream of messages of type D.
+    pub fn connect(_input_stream: &ReadStream<D>) -> WriteStream<DeltaFrame<D, P>> {
+        WriteStream::new()
+    }
+
+    /// The callback function to be invoked upon receipt of a message on the input stream.
+    ///
+    /// # Arguments
+    /// * `t` - The timestamp of the message.
+    /// * `msg` - The incoming message on the input stream.
+    /// * `state` - The output stream and the previously sent message.
+    /// * `delta_config` - The diff function and the keyframe interval.
+    fn on_data_callback<F: 'static + Clone + Fn(&D, &D) -> P>(
+        t: &Timestamp,
+        msg: &D,
+        state: &mut EncodeState<D, P>,
+        delta_config: &DeltaEncodeConfig<F>,
+    ) -> Result<(), OperatorError> {
+        let frame = match &state.previous {
+            Some(previous) if state.messages_since_keyframe < delta_config.keyframe_interval => {
+                state.messages_since_keyframe += 1;
+                DeltaFrame::Delta((delta_config.diff)(previous, msg))
+            }
+            _ => {
+                state.messages_since_keyframe = 1;
+                DeltaFrame::Keyframe(msg.clone())
+            }
+        };
+        state.previous = Some(msg.clone());
+        state
+            .output_stream
+            .send(Message::new_message(t.clone(), frame))?;
+        Ok(())
+    }
+}
+
+impl<D, P> Operator for DeltaEncodeOperator<D, P>
+where
+    for<'a> D: Data + Deserialize<'a>,
+    for<'a> P: Data + Deserialize<'a>,
+{
+}
+
+#[derive(Clone)]
+struct DecodeState<D: Data> {
+    output_stream: WriteStream<D>,
+    previous: Option<D>,
+}
+
+/// An operator that reconstructs the messages sent by a [`DeltaEncodeOperator`] by applying the
+/// received patches of type P to the previous message of type D, using the provided patch
+/// function.
+///
+/// Patches received before the first keyframe are dropped, as there is no message to apply them
+/// to.
+pub struct DeltaDecodeOperator<D: Data, P: Data> {
+    phantom_data: PhantomData<(D, P)>,
+}
+
+impl<D, P> DeltaDecodeOperator<D, P>
+where
+    for<'a> D: Data + Deserialize<'a>,
+    for<'a> P: Data + Deserialize<'a>,
+{
+    /// Returns a new instance of the DeltaDecodeOperator.
+    ///
+    /// # Arguments
+    /// * `config` - An instance of OperatorConfig that provides the closure used to apply a patch
+    /// of type P to the previous message of type D.
+    /// * `input_stream` - Represents the incoming stream of keyframes and patches.
+    /// * `output_stream` - Represents an outgoing stream of messages of type D.
+    pub fn new<F: 'static + Clone + Fn(&D, &P) -> D>(
+        config: OperatorConfig<F>,
+        input_stream: ReadStream<DeltaFrame<D, P>>,
+        output_stream: WriteStream<D>,
+    ) -> Self {
+        let stateful_stream = input_stream.add_state(DecodeState {
+            output_stream,
+            previous: None,
+        });
+
+        let name: String = config
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("DeltaDecodeOperator {}", config.id));
+        let patch_function = config
+            .arg
+            .unwrap_or_else(|| panic!("{}: no patch function supplied", name));
+
+        stateful_stream.add_callback(
+            move |t: &Timestamp, msg: &DeltaFrame<D, P>, state: &mut DecodeState<D>| {
+                Self::on_data_callback(t, msg, state, &name, &patch_function)
+            },
+        );
+        Self {
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// Returns a new instance of a WriteStream to send its outgoing messages on.
+    ///
+    /// # Arguments
+    /// * `input_stream` - Represents the incoming stream of keyframes and patches.
+    pub fn connect(_input_stream: &ReadStream<DeltaFrame<D, P>>) -> WriteStream<D> {
+        WriteStream::new()
+    }
+
+    /// The callback function to be invoked upon receipt of a message on the input stream.
+    ///
+    /// # Arguments
+    /// * `t` - The timestamp of the message.
+    /// * `msg` - The incoming keyframe or patch on the input stream.
+    /// * `state` - The output stream and the previously decoded message.
+    /// * `name` - The name of the operator, used for logging.
+    /// * `patch_function` - A reference to the function to invoke for patches.
+    fn on_data_callback<F: 'static + Clone + Fn(&D, &P) -> D>(
+        t: &Timestamp,
+        msg: &DeltaFrame<D, P>,
+        state: &mut DecodeState<D>,
+        name: &str,
+        patch_function: &F,
+    ) -> Result<(), OperatorError> {
+        let decoded = match (msg, &state.previous) {
+            (DeltaFrame::Keyframe(data), _) => data.clone(),
+            (DeltaFrame::Delta(patch), Some(previous)) => patch_function(previous, patch),
+            (DeltaFrame::Delta(_), None) => {
+                slog::warn!(
+                    crate::TERMINAL_LOGGER,
+                    "{}: dropping patch with timestamp {:?} received before the first keyframe",
+                    name,
+                    t
+                );
+                return Ok(());
+            }
+        };
+        state.previous = Some(decoded.clone());
+        state
+            .output_stream
+            .send(Message::new_message(t.clone(), decoded))?;
+        Ok(())
+    }
+}
+
+impl<D, P> Operator for DeltaDecodeOperator<D, P>
+where
+    for<'a> D: Data + Deserialize<'a>,
+    for<'a> P: Data + Deserialize<'a>,
+{
+}
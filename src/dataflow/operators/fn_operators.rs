@@ -0,0 +1,376 @@
+use std::{
+    future::{self, Future},
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+use crate::{
+    communication::ControlMessage,
+    dataflow::{
+        graph::default_graph,
+        stream::{InternalReadStream, WriteStreamT},
+        Data, Message, Operator, OperatorConfig, OperatorErrorReport, ReadStream, RunContext,
+        Timestamp, WriteStream,
+    },
+    node::operator_executor::{
+        OperatorExecutor, OperatorExecutorStream, OperatorExecutorStreamT,
+        OperatorExecutorWriteStreamT,
+    },
+    scheduler::channel_manager::ChannelManager,
+    OperatorId,
+};
+
+/// An operator that runs a function on the messages of a stream, one message at a time, in its
+/// [pull loop](Operator::run_async). Forwards the watermarks once the function returned for the
+/// messages before them.
+struct FnOperator<D1: Data, D2: Data, F> {
+    name: String,
+    read_stream: ReadStream<D1>,
+    write_stream: Option<WriteStream<D2>>,
+    function: F,
+}
+
+impl<D1: Data, D2: Data, F> FnOperator<D1, D2, F> {
+    fn send(&mut self, msg: Message<D2>) {
+        if let Some(write_stream) = &mut self.write_stream {
+            if let Err(e) = write_stream.send(msg) {
+                slog::warn!(
+                    crate::TERMINAL_LOGGER,
+                    "{} unable to send message on stream {}: {:?}",
+                    self.name,
+                    write_stream.get_id(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<D1, D2, F, Fut> Operator for FnOperator<D1, D2, F>
+where
+    for<'a> D1: Data + Deserialize<'a>,
+    for<'a> D2: Data + Deserialize<'a>,
+    F: Fn(Timestamp, D1) -> Fut,
+    Fut: 'static + Future<Output = Option<D2>>,
+{
+    async fn run_async(&mut self, _ctx: &mut RunContext) {
+        while let Ok(msg) = self.read_stream.recv().await {
+            match msg {
+                Message::TimestampedData(msg) => {
+                    let timestamp = msg.timestamp.clone();
+                    if let Some(data) = (self.function)(msg.timestamp, msg.data).await {
+                        self.send(Message::new_message(timestamp, data));
+                    }
+                }
+                Message::Watermark(watermark) => {
+                    let is_top = watermark.is_top();
+                    self.send(Message::new_watermark(watermark));
+                    if is_top {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Connects an operator which maps the messages of a stream with a function, without defining
+/// an operator.
+///
+/// # Example
+/// ```
+/// # use erdos::dataflow::{
+/// #     operators::connect_map_fn, stream::IngestStream, OperatorConfig, ReadStream,
+/// # };
+/// #
+/// # let ingest_stream = IngestStream::<u32>::new(0);
+/// #
+/// let doubled: ReadStream<u64> = connect_map_fn(
+///     OperatorConfig::new().name("Double"),
+///     &ingest_stream,
+///     |data: &u32| 2 * *data as u64,
+/// );
+/// ```
+pub fn connect_map_fn<D1, D2, S, F>(
+    config: OperatorConfig<()>,
+    read_stream: S,
+    map_fn: F,
+) -> ReadStream<D2>
+where
+    for<'a> D1: Data + Deserialize<'a>,
+    for<'a> D2: Data + Deserialize<'a>,
+    S: Into<ReadStream<D1>>,
+    F: 'static + Clone + Send + Sync + Fn(&D1) -> D2,
+{
+    let function = move |_: Timestamp, data: D1| future::ready(Some(map_fn(&data)));
+    connect_fn(config, read_stream.into(), true, function).unwrap()
+}
+
+/// Connects an operator which only forwards the messages of a stream for which the predicate
+/// returns `true`, without defining an operator.
+pub fn connect_filter_fn<D, S, F>(
+    config: OperatorConfig<()>,
+    read_stream: S,
+    predicate: F,
+) -> ReadStream<D>
+where
+    for<'a> D: Data + Deserialize<'a>,
+    S: Into<ReadStream<D>>,
+    F: 'static + Clone + Send + Sync + Fn(&D) -> bool,
+{
+    let function = move |_: Timestamp, data: D| future::ready(Some(data).filter(&predicate));
+    connect_fn(config, read_stream.into(), true, function).unwrap()
+}
+
+/// Connects an operator which calls a function with the timestamp and the data of each message
+/// of a stream, without defining an operator.
+pub fn connect_sink_fn<D, S, F>(config: OperatorConfig<()>, read_stream: S, sink_fn: F)
+where
+    for<'a> D: Data + Deserialize<'a>,
+    S: Into<ReadStream<D>>,
+    F: 'static + Clone + Send + Sync + Fn(&Timestamp, &D),
+{
+    let function = move |timestamp: Timestamp, data: D| {
+        sink_fn(&timestamp, &data);
+        future::ready(None::<()>)
+    };
+    connect_fn(config, read_stream.into(), false, function);
+}
+
+/// Connects an operator which maps the messages of a stream with an async function, e.g. an
+/// `async` closure, without defining an operator.
+///
+/// The function is awaited for each message before the next message is read, so the messages
+/// are mapped in order.
+///
+/// # Example
+/// ```
+/// # use erdos::dataflow::{
+/// #     operators::connect_async_map_fn, stream::IngestStream, OperatorConfig, ReadStream,
+/// # };
+/// #
+/// # let ingest_stream = IngestStream::<u32>::new(0);
+/// #
+/// let doubled: ReadStream<u64> = connect_async_map_fn(
+///     OperatorConfig::new().name("AsyncDouble"),
+///     &ingest_stream,
+///     |data: u32| async move { 2 * data as u64 },
+/// );
+/// ```
+pub fn connect_async_map_fn<D1, D2, S, F, Fut>(
+    config: OperatorConfig<()>,
+    read_stream: S,
+    map_fn: F,
+) -> ReadStream<D2>
+where
+    for<'a> D1: Data + Deserialize<'a>,
+    for<'a> D2: Data + Deserialize<'a>,
+    S: Into<ReadStream<D1>>,
+    F: 'static + Clone + Send + Sync + Fn(D1) -> Fut,
+    Fut: 'static + Future<Output = D2>,
+{
+    let function = move |_: Timestamp, data: D1| {
+        let output = map_fn(data);
+        async move { Some(output.await) }
+    };
+    connect_fn(config, read_stream.into(), true, function).unwrap()
+}
+
+/// Connects an operator which awaits an async function with the timestamp and the data of each
+/// message of a stream, without defining an operator.
+pub fn connect_async_sink_fn<D, S, F, Fut>(config: OperatorConfig<()>, read_stream: S, sink_fn: F)
+where
+    for<'a> D: Data + Deserialize<'a>,
+    S: Into<ReadStream<D>>,
+    F: 'static + Clone + Send + Sync + Fn(Timestamp, D) -> Fut,
+    Fut: 'static + Future<Output = ()>,
+{
+    let function = move |timestamp: Timestamp, data: D| {
+        let done = sink_fn(timestamp, data);
+        async move {
+            done.await;
+            None::<()>
+        }
+    };
+    connect_fn(config, read_stream.into(), false, function);
+}
+
+/// Registers a [`FnOperator`] in the dataflow graph like
+/// [`connect_1_write`](crate::connect_1_write) and [`connect_0_write`](crate::connect_0_write) do,
+/// and returns its output stream, if any.
+fn connect_fn<D1, D2, F, Fut>(
+    config: OperatorConfig<()>,
+    read_stream: ReadStream<D1>,
+    has_output: bool,
+    function: F,
+) -> Option<ReadStream<D2>>
+where
+    for<'a> D1: Data + Deserialize<'a>,
+    for<'a> D2: Data + Deserialize<'a>,
+    F: 'static + Clone + Send + Sync + Fn(Timestamp, D1) -> Fut,
+    Fut: 'static + Future<Output = Option<D2>>,
+{
+    let write_stream: Option<WriteStream<D2>> = if has_output {
+        Some(WriteStream::new())
+    } else {
+        None
+    };
+    connect_pull_operator(
+        config,
+        vec![read_stream],
+        write_stream,
+        move |config, mut read_streams, write_stream| FnOperator {
+            name: config
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("FnOperator {}", config.id)),
+            read_stream: read_streams.remove(0),
+            write_stream,
+            function: function.clone(),
+        },
+    )
+}
+
+/// Registers an operator which reads any number of streams with the same type of data in its
+/// [pull loop](Operator::run_async), and returns its output stream, if any.
+///
+/// The operator is made from its configuration and streams on the node it runs on, and forwards
+/// the watermarks itself.
+pub(crate) fn connect_pull_operator<D1, D2, T, O, M>(
+    config: OperatorConfig<T>,
+    read_streams: Vec<ReadStream<D1>>,
+    write_stream: Option<WriteStream<D2>>,
+    make_operator: M,
+) -> Option<ReadStream<D2>>
+where
+    for<'a> D1: Data + Deserialize<'a>,
+    for<'a> D2: Data + Deserialize<'a>,
+    T: 'static + Clone + Send + Sync,
+    O: 'static + Operator,
+    M: 'static
+        + Clone
+        + Send
+        + Sync
+        + Fn(&OperatorConfig<T>, Vec<ReadStream<D1>>, Option<WriteStream<D2>>) -> O,
+{
+    let mut config = config.flow_watermarks(false);
+    config.id = match &config.name {
+        Some(name) => OperatorId::new_named(name)
+            .unwrap_or_else(|e| panic!("Unable to connect the operator {}: {}", name, e)),
+        None => OperatorId::new_deterministic(),
+    };
+
+    let read_stream_ids: Vec<_> = read_streams.iter().map(|rs| rs.get_id()).collect();
+    let write_stream_id = write_stream.as_ref().map(|ws| ws.get_id());
+    let mut write_stream_ids: Vec<_> = write_stream_id.into_iter().collect();
+    if let Some(error_stream_id) = config.error_stream_id {
+        write_stream_ids.push(error_stream_id);
+    }
+    for (index, description) in config.stream_descriptions.iter() {
+        let stream_id = *write_stream_ids.get(*index).unwrap_or_else(|| {
+            panic!(
+                "Unable to describe write stream {} of operator {:?}",
+                index, config.id
+            )
+        });
+        default_graph::describe_stream(stream_id, description.clone());
+    }
+
+    let config_copy = config.clone();
+    let runner_read_stream_ids = read_stream_ids.clone();
+    let op_runner = move |channel_manager: Arc<Mutex<ChannelManager>>,
+                          control_sender: UnboundedSender<ControlMessage>,
+                          control_receiver: UnboundedReceiver<ControlMessage>| {
+        let mut op_ex_streams: Vec<Box<dyn OperatorExecutorStreamT>> = Vec::new();
+        let read_streams: Vec<ReadStream<D1>> = runner_read_stream_ids
+            .iter()
+            .map(|&read_stream_id| {
+                let recv_endpoint = channel_manager
+                    .lock()
+                    .unwrap()
+                    .take_recv_endpoint(read_stream_id)
+                    .unwrap();
+                let read_stream = ReadStream::from(InternalReadStream::from_endpoint(
+                    recv_endpoint,
+                    read_stream_id,
+                ));
+                op_ex_streams.push(Box::new(OperatorExecutorStream::from(&read_stream)));
+                read_stream
+            })
+            .collect();
+        let write_stream = write_stream_id.map(|write_stream_id| {
+            channel_manager
+                .lock()
+                .unwrap()
+                .get_write_stream(write_stream_id)
+                .unwrap()
+        });
+        let mut config = config_copy.clone();
+        config.node_id = channel_manager.lock().unwrap().node_id();
+        let error_stream = config.error_stream_id.map(|error_stream_id| {
+            channel_manager
+                .lock()
+                .unwrap()
+                .get_write_stream::<OperatorErrorReport>(error_stream_id)
+                .unwrap()
+        });
+        let failure_write_streams: Vec<Box<dyn OperatorExecutorWriteStreamT>> = write_stream
+            .iter()
+            .map(|write_stream| Box::new(write_stream.clone()) as _)
+            .collect();
+        let operator = make_operator(&config, read_streams, write_stream);
+        if let Err(e) = control_sender.send(ControlMessage::OperatorInitialized(config.id)) {
+            panic!(
+                "Error sending OperatorInitialized message to control handler: {:?}",
+                e
+            );
+        }
+        let mut op_executor = OperatorExecutor::new(
+            operator,
+            config,
+            op_ex_streams,
+            control_sender,
+            control_receiver,
+            error_stream,
+        );
+        op_executor.set_write_streams(failure_write_streams);
+        op_executor
+    };
+
+    default_graph::add_operator(
+        config.id,
+        config.name.clone(),
+        config.node_id,
+        read_stream_ids,
+        write_stream_ids,
+        op_runner,
+    );
+    default_graph::set_operator_dependencies(config.id, config.dependencies.clone());
+    default_graph::set_operator_partitioning(config.id, config.movable, config.cpu_demand);
+    default_graph::set_operator_constraints(
+        config.id,
+        config.colocated_with.clone(),
+        config.separated_from.clone(),
+    );
+    if let Some(node_selector) = &config.node_selector {
+        default_graph::set_operator_node_selector(config.id, node_selector.clone());
+    }
+    default_graph::set_operator_node_affinity(config.id, config.node_affinity.clone());
+    if let Some(setup_timeout) = config.setup_timeout {
+        default_graph::set_operator_setup_timeout(config.id, setup_timeout);
+    }
+    if let Some(write_stream) = &write_stream {
+        default_graph::add_operator_stream(config.id, write_stream);
+    }
+    if let Some(error_stream_id) = config.error_stream_id {
+        default_graph::add_operator_stream(
+            config.id,
+            &WriteStream::<OperatorErrorReport>::new_with_id(error_stream_id),
+        );
+    }
+    write_stream.as_ref().map(ReadStream::from)
+}
@@ -0,0 +1,42 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::dataflow::{Data, Timestamp};
+
+/// Buffers the messages received on a stream until their timestamp completes.
+///
+/// Used as the state of the streams of operators which combine several streams in their
+/// watermark callbacks.
+#[derive(Clone)]
+pub(super) struct MessageBuffer<D: Data> {
+    msgs: Arc<Mutex<BTreeMap<Timestamp, Vec<D>>>>,
+}
+
+impl<D: Data> MessageBuffer<D> {
+    pub fn new() -> Self {
+        Self {
+            msgs: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    pub fn add_msg(&self, timestamp: &Timestamp, msg: D) {
+        self.msgs
+            .lock()
+            .unwrap()
+            .entry(timestamp.clone())
+            .or_default()
+            .push(msg);
+    }
+
+    /// Removes the messages with timestamps up to and including the timestamp, and returns the
+    /// messages with the timestamp.
+    pub fn take(&self, timestamp: &Timestamp) -> Vec<D> {
+        let mut msgs = self.msgs.lock().unwrap();
+        let taken = msgs.remove(timestamp).unwrap_or_default();
+        let later_msgs = msgs.split_off(timestamp);
+        *msgs = later_msgs;
+        taken
+    }
+}
@@ -1,11 +1,83 @@
 //! Library of generic operators for building ERDOS applications.
+//!
+//! Operators in the library read the time from the [`Clock`](crate::time::Clock) of their
+//! [`OperatorConfig`](crate::dataflow::OperatorConfig), so that they run on virtual time.
 
 // Private submodules
+mod align_operator;
+mod circuit_breaker_operator;
+mod delta_operator;
+mod fn_operators;
 mod join_operator;
 mod map_operator;
+mod message_buffer;
+mod resample_operator;
+mod retimestamp_operator;
+mod retry_operator;
+mod shadow_compare_operator;
 mod source_operator;
+mod switch_operator;
 
 // Public exports
+pub use crate::dataflow::operators::align_operator::{connect_align, AlignConfig, AlignedMessages};
+pub use crate::dataflow::operators::circuit_breaker_operator::{
+    CircuitBreakerConfig, CircuitBreakerOperator, CircuitState, CircuitTransition,
+};
+pub use crate::dataflow::operators::delta_operator::{
+    DeltaDecodeOperator, DeltaEncodeConfig, DeltaEncodeOperator, DeltaFrame,
+};
+pub use crate::dataflow::operators::fn_operators::{
+    connect_async_map_fn, connect_async_sink_fn, connect_filter_fn, connect_map_fn, connect_sink_fn,
+};
 pub use crate::dataflow::operators::join_operator::JoinOperator;
 pub use crate::dataflow::operators::map_operator::MapOperator;
+pub use crate::dataflow::operators::resample_operator::{ResampleConfig, ResampleOperator};
+pub use crate::dataflow::operators::retimestamp_operator::RetimestampOperator;
+pub use crate::dataflow::operators::retry_operator::{RetryConfig, RetryOperator};
+pub use crate::dataflow::operators::shadow_compare_operator::{
+    DivergenceReport, ShadowCompareConfig, ShadowCompareOperator,
+};
 pub use crate::dataflow::operators::source_operator::SourceOperator;
+pub use crate::dataflow::operators::switch_operator::{Deployment, SwitchConfig, SwitchOperator};
+
+#[cfg(test)]
+mod tests {
+    /// Test that the operators do not read the time of the system directly.
+    #[test]
+    fn test_operators_use_clock() {
+        let sources = [
+            ("align_operator.rs", include_str!("align_operator.rs")),
+            (
+                "circuit_breaker_operator.rs",
+                include_str!("circuit_breaker_operator.rs"),
+            ),
+            ("delta_operator.rs", include_str!("delta_operator.rs")),
+            ("fn_operators.rs", include_str!("fn_operators.rs")),
+            ("join_operator.rs", include_str!("join_operator.rs")),
+            ("map_operator.rs", include_str!("map_operator.rs")),
+            ("message_buffer.rs", include_str!("message_buffer.rs")),
+            ("resample_operator.rs", include_str!("resample_operator.rs")),
+            (
+                "retimestamp_operator.rs",
+                include_str!("retimestamp_operator.rs"),
+            ),
+            ("retry_operator.rs", include_str!("retry_operator.rs")),
+            (
+                "shadow_compare_operator.rs",
+                include_str!("shadow_compare_operator.rs"),
+            ),
+            ("source_operator.rs", include_str!("source_operator.rs")),
+            ("switch_operator.rs", include_str!("switch_operator.rs")),
+        ];
+        for (file, source) in sources.iter() {
+            for forbidden in &["Instant::now", "SystemTime::now", "thread::sleep"] {
+                assert!(
+                    !source.contains(forbidden),
+                    "{} calls {} instead of using the operator's Clock",
+                    file,
+                    forbidden
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::dataflow::{
+    stream::WriteStreamT, Data, Message, Operator, OperatorConfig, ReadStream, RunContext,
+    Timestamp, WriteStream,
+};
+
+type Lerp<D> = Arc<dyn Fn(&D, &D, f64) -> D + Send + Sync>;
+
+/// Configures the [`ResampleOperator`].
+pub struct ResampleConfig<D> {
+    /// Duration between the output messages, in units of the first coordinate of the
+    /// timestamps, which is at least 1.
+    pub period: u64,
+    lerp: Lerp<D>,
+}
+
+impl<D> ResampleConfig<D> {
+    /// Returns the configuration which sends a message every `period`, interpolated with `lerp`
+    /// between the samples before and after it. `lerp` is called with the earlier sample, the
+    /// later sample, and the fraction of the way from the earlier to the later sample.
+    pub fn new<F>(period: u64, lerp: F) -> Self
+    where
+        F: 'static + Fn(&D, &D, f64) -> D + Send + Sync,
+    {
+        Self {
+            period: period.max(1),
+            lerp: Arc::new(lerp),
+        }
+    }
+}
+
+impl<D> Clone for ResampleConfig<D> {
+    fn clone(&self) -> Self {
+        Self {
+            period: self.period,
+            lerp: Arc::clone(&self.lerp),
+        }
+    }
+}
+
+/// Returns the time of a timestamp on which the samples are interpolated.
+fn time_of(timestamp: &Timestamp) -> u64 {
+    timestamp.time.first().copied().unwrap_or_default()
+}
+
+/// An operator that resamples a stream of sensor samples at a variable rate to a fixed rate,
+/// e.g. to feed a controller which runs every 10 milliseconds.
+///
+/// The operator sends a message for each multiple of the [period](ResampleConfig::period) from
+/// the earliest sample on, with a timestamp whose only coordinate is the multiple. The message is
+/// interpolated between the samples nearest before and after it, using the time of their
+/// timestamps, i.e. the first coordinate, and is sent once the watermark reaches the sample
+/// after it, so that no nearer sample can arrive. A sample at a multiple is sent as is.
+///
+/// Samples received at or before the last watermark are late; they are dropped with a warning.
+/// The operator sends its own watermarks, so it must be connected with
+/// [`flow_watermarks`](OperatorConfig::flow_watermarks) set to `false`.
+///
+/// # Example
+/// The below example shows how to resample the speed measured by an odometer every 10
+/// milliseconds.
+///
+/// ```
+/// # use erdos::dataflow::{
+/// #     stream::IngestStream,
+/// #     operators::{ResampleConfig, ResampleOperator},
+/// #     OperatorConfig,
+/// # };
+/// # use erdos::*;
+/// #
+/// # let mut speed_stream: IngestStream<f64> = IngestStream::new(0);
+/// #
+/// let resample_config =
+///     ResampleConfig::new(10, |before: &f64, after: &f64, t: f64| before + (after - before) * t);
+/// let config = OperatorConfig::new()
+///     .name("ResampleOperator")
+///     .flow_watermarks(false)
+///     .arg(resample_config);
+/// let resampled = connect_1_write!(ResampleOperator<f64>, config, speed_stream);
+/// ```
+pub struct ResampleOperator<D: Data> {
+    name: String,
+    resample_config: ResampleConfig<D>,
+    input_stream: ReadStream<D>,
+    output_stream: WriteStream<D>,
+    /// The samples by time from the latest sample before the next output.
+    samples: BTreeMap<u64, D>,
+    /// The time of the next output, set once a sample is received.
+    next_output: Option<u64>,
+    /// The time of the highest watermark received.
+    watermark: Option<u64>,
+    /// Whether the top watermark was received.
+    closed: bool,
+    /// The time of the watermark last sent.
+    sent_watermark: Option<u64>,
+}
+
+impl<D> ResampleOperator<D>
+where
+    for<'a> D: Data + Deserialize<'a>,
+{
+    pub fn new(
+        config: OperatorConfig<ResampleConfig<D>>,
+        input_stream: ReadStream<D>,
+        output_stream: WriteStream<D>,
+    ) -> Self {
+        assert!(
+            !config.flow_watermarks,
+            "ResampleOperator sends its own watermarks"
+        );
+        let name = config
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("ResampleOperator {}", config.id));
+        Self {
+            resample_config: config
+                .arg
+                .unwrap_or_else(|| panic!("{}: no resample config supplied", name)),
+            name,
+            input_stream,
+            output_stream,
+            samples: BTreeMap::new(),
+            next_output: None,
+            watermark: None,
+            closed: false,
+            sent_watermark: None,
+        }
+    }
+
+    pub fn connect(_input_stream: &ReadStream<D>) -> WriteStream<D> {
+        WriteStream::new()
+    }
+
+    /// Whether all samples up to the time were received.
+    fn is_complete(&self, time: u64) -> bool {
+        self.closed || self.watermark.map_or(false, |watermark| watermark >= time)
+    }
+
+    fn add_sample(&mut self, timestamp: Timestamp, data: D) {
+        let time = time_of(&timestamp);
+        if self.watermark.map_or(false, |watermark| time <= watermark) {
+            slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "{}: dropping the sample for {:?} received after the watermark",
+                self.name,
+                timestamp
+            );
+            return;
+        }
+        // Samples may arrive out of order before the first output is sent.
+        let period = self.resample_config.period;
+        let first_output = (time + period - 1) / period * period;
+        self.next_output = Some(
+            self.next_output
+                .map_or(first_output, |next_time| next_time.min(first_output)),
+        );
+        self.samples.insert(time, data);
+    }
+
+    /// Sends the outputs whose samples before and after were received, and the watermark up to
+    /// which all outputs were sent.
+    fn resample(&mut self) {
+        while let Some(time) = self.next_output {
+            if !self.is_complete(time) {
+                break;
+            }
+            let before = self.samples.range(..=time).next_back();
+            let after = self.samples.range(time..).next();
+            let output = match (before, after) {
+                (Some((&before_time, before)), _) if before_time == time => before.clone(),
+                (Some((&before_time, before)), Some((&after_time, after)))
+                    if self.is_complete(after_time) =>
+                {
+                    let fraction = (time - before_time) as f64 / (after_time - before_time) as f64;
+                    (self.resample_config.lerp)(before, after, fraction)
+                }
+                _ => break,
+            };
+            self.send(Message::new_message(Timestamp::new(vec![time]), output));
+
+            let next_time = time + self.resample_config.period;
+            self.next_output = Some(next_time);
+            // Only the latest sample before the next output is needed to interpolate it.
+            if let Some((&latest_time, _)) = self.samples.range(..=next_time).next_back() {
+                self.samples = self.samples.split_off(&latest_time);
+            }
+        }
+
+        if self.closed {
+            self.send(Message::new_watermark(Timestamp::top()));
+            return;
+        }
+        let watermark = match (self.watermark, self.next_output) {
+            (Some(watermark), Some(next_time)) => {
+                next_time.checked_sub(1).map(|t| t.min(watermark))
+            }
+            (watermark, _) => watermark,
+        };
+        if let Some(watermark) = watermark {
+            if self.sent_watermark.map_or(true, |sent| watermark > sent) {
+                self.sent_watermark = Some(watermark);
+                self.send(Message::new_watermark(Timestamp::new(vec![watermark])));
+            }
+        }
+    }
+
+    fn send(&mut self, msg: Message<D>) {
+        if let Err(e) = self.output_stream.send(msg) {
+            slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "{} unable to send message on stream {}: {:?}",
+                self.name,
+                self.output_stream.get_id(),
+                e
+            );
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<D> Operator for ResampleOperator<D>
+where
+    for<'a> D: Data + Deserialize<'a>,
+{
+    async fn run_async(&mut self, _ctx: &mut RunContext) {
+        while let Ok(msg) = self.input_stream.recv().await {
+            match msg {
+                Message::TimestampedData(msg) => self.add_sample(msg.timestamp, msg.data),
+                Message::Watermark(watermark) => {
+                    if watermark.is_top() {
+                        self.closed = true;
+                    } else {
+                        self.watermark = Some(time_of(&watermark));
+                    }
+                    self.resample();
+                    if self.closed {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
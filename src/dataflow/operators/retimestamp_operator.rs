@@ -0,0 +1,100 @@
+use serde::Deserialize;
+
+use crate::dataflow::{
+    stream::WriteStreamT, Data, Message, Operator, OperatorConfig, ReadStream, TimeDomain,
+    Timestamp, WriteStream,
+};
+
+/// An operator that sets the timestamps of an incoming stream of type D to their time in another
+/// [`TimeDomain`], so that the time domain drives the callbacks and watermarks of the operators
+/// downstream.
+///
+/// The operator sends a watermark every time it receives one, for the highest time in the new
+/// domain of the messages it received. The watermarks are therefore only correct if the times in
+/// the new domain increase along the stream, as ingestion times do for messages sent on a single
+/// [`IngestStream`](crate::dataflow::stream::IngestStream).
+///
+/// The operator must be connected with [`flow_watermarks`](OperatorConfig::flow_watermarks) set
+/// to `false`.
+///
+/// # Example
+/// The below example shows how to drive the watermarks of a stream by ingestion time.
+///
+/// ```
+/// # use erdos::dataflow::{
+/// #     stream::IngestStream, operators::RetimestampOperator, OperatorConfig, TimeDomain,
+/// # };
+/// # use erdos::*;
+/// #
+/// # let mut ingest_stream = IngestStream::new(0);
+/// #
+/// let config = OperatorConfig::new()
+///     .name("RetimestampOperator")
+///     .flow_watermarks(false)
+///     .arg(TimeDomain::IngestionTime);
+/// let ingestion_time_stream =
+///     connect_1_write!(RetimestampOperator<u32>, config, ingest_stream);
+/// ```
+pub struct RetimestampOperator<D: Data> {
+    domain: TimeDomain,
+    input_stream: ReadStream<D>,
+    output_stream: WriteStream<D>,
+}
+
+impl<'a, D: Data + Deserialize<'a>> RetimestampOperator<D> {
+    pub fn new(
+        config: OperatorConfig<TimeDomain>,
+        input_stream: ReadStream<D>,
+        output_stream: WriteStream<D>,
+    ) -> Self {
+        assert!(
+            !config.flow_watermarks,
+            "RetimestampOperator sends its own watermarks"
+        );
+        Self {
+            domain: config.arg.unwrap_or(TimeDomain::EventTime),
+            input_stream,
+            output_stream,
+        }
+    }
+
+    pub fn connect(_input_stream: &ReadStream<D>) -> WriteStream<D> {
+        WriteStream::new()
+    }
+}
+
+impl<'a, D: Data + Deserialize<'a>> Operator for RetimestampOperator<D> {
+    fn run(&mut self) {
+        let mut max_time: Option<Timestamp> = None;
+        while let Ok(msg) = self.input_stream.read() {
+            let msg = match msg {
+                Message::TimestampedData(mut td) => {
+                    td.retimestamp(self.domain);
+                    if max_time.as_ref().map_or(true, |time| time < &td.timestamp) {
+                        max_time = Some(td.timestamp.clone());
+                    }
+                    Message::TimestampedData(td)
+                }
+                Message::Watermark(watermark) if watermark.is_top() => {
+                    Message::Watermark(watermark)
+                }
+                Message::Watermark(_) => match &max_time {
+                    Some(time) => Message::new_watermark(time.clone()),
+                    None => continue,
+                },
+            };
+            let is_top_watermark = msg.is_top_watermark();
+            if let Err(e) = self.output_stream.send(msg) {
+                slog::warn!(
+                    crate::TERMINAL_LOGGER,
+                    "RetimestampOperator unable to send message on stream {}: {:?}",
+                    self.output_stream.get_id(),
+                    e
+                );
+            }
+            if is_top_watermark {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::{
+    dataflow::{
+        stream::WriteStreamT, Data, Message, Operator, OperatorConfig, OperatorError,
+        OperatorErrorReport, ReadStream, RunContext, Timestamp, WriteStream,
+    },
+    node::NodeId,
+    OperatorId,
+};
+
+type Call<D1, D2> =
+    Arc<dyn Fn(D1) -> Pin<Box<dyn Future<Output = Result<D2, OperatorError>>>> + Send + Sync>;
+
+/// Configures the [`RetryOperator`].
+pub struct RetryConfig<D1, D2> {
+    call: Call<D1, D2>,
+    /// Number of times the call is attempted for a message, which is at least 1. Defaults to 3.
+    pub max_attempts: u32,
+    /// Duration after which an attempt fails, if any. Defaults to none.
+    pub timeout: Option<Duration>,
+    /// Duration to wait before the second attempt, which doubles before each following attempt.
+    /// Defaults to 100 milliseconds.
+    pub backoff: Duration,
+    /// Value sent for the messages for which all attempts failed, if any. Defaults to none.
+    pub fallback: Option<D2>,
+}
+
+impl<D1, D2> RetryConfig<D1, D2> {
+    /// Returns the configuration which retries the async function, e.g. a call to an external
+    /// service.
+    pub fn new<F, Fut>(call: F) -> Self
+    where
+        F: 'static + Fn(D1) -> Fut + Send + Sync,
+        Fut: 'static + Future<Output = Result<D2, OperatorError>>,
+    {
+        Self {
+            call: Arc::new(move |data| Box::pin(call(data))),
+            max_attempts: 3,
+            timeout: None,
+            backoff: Duration::from_millis(100),
+            fallback: None,
+        }
+    }
+
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn fallback(mut self, fallback: D2) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+}
+
+impl<D1, D2: Clone> Clone for RetryConfig<D1, D2> {
+    fn clone(&self) -> Self {
+        Self {
+            call: Arc::clone(&self.call),
+            max_attempts: self.max_attempts,
+            timeout: self.timeout,
+            backoff: self.backoff,
+            fallback: self.fallback.clone(),
+        }
+    }
+}
+
+/// An operator that maps each message of a stream with an async function, e.g. a call to a flaky
+/// external service, which is retried until it succeeds, with a timeout on each attempt.
+///
+/// If all attempts fail for a message, the operator sends the
+/// [fallback value](RetryConfig::fallback) for its timestamp, if any, and an
+/// [`OperatorErrorReport`] on its second write stream. The messages are processed one at a time
+/// in order, and the timeouts and backoffs are measured on the wallclock.
+///
+/// The operator sends its own watermarks, so it must be connected with
+/// [`flow_watermarks`](OperatorConfig::flow_watermarks) set to `false`.
+///
+/// # Example
+/// The below example shows how to look up the messages in a service, with 3 attempts of 1
+/// second each.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use erdos::dataflow::{
+/// #     stream::IngestStream,
+/// #     operators::{RetryConfig, RetryOperator},
+/// #     OperatorConfig, OperatorError,
+/// # };
+/// # use erdos::*;
+/// #
+/// # let mut ingest_stream: IngestStream<u32> = IngestStream::new(0);
+/// #
+/// async fn lookup(key: u32) -> Result<String, OperatorError> {
+///     Ok(format!("value of {}", key))
+/// }
+///
+/// let retry_config = RetryConfig::new(lookup)
+///     .max_attempts(3)
+///     .timeout(Duration::from_secs(1))
+///     .fallback(String::new());
+/// let config = OperatorConfig::new()
+///     .name("RetryOperator")
+///     .flow_watermarks(false)
+///     .arg(retry_config);
+/// let (values, failures) =
+///     connect_2_write!(RetryOperator<u32, String>, config, ingest_stream);
+/// ```
+pub struct RetryOperator<D1: Data, D2: Data> {
+    retry_config: RetryConfig<D1, D2>,
+    operator_id: OperatorId,
+    operator_name: Option<String>,
+    node_id: NodeId,
+    input_stream: ReadStream<D1>,
+    output_stream: WriteStream<D2>,
+    failure_stream: WriteStream<OperatorErrorReport>,
+}
+
+impl<D1, D2> RetryOperator<D1, D2>
+where
+    for<'a> D1: Data + Deserialize<'a>,
+    for<'a> D2: Data + Deserialize<'a>,
+{
+    pub fn new(
+        config: OperatorConfig<RetryConfig<D1, D2>>,
+        input_stream: ReadStream<D1>,
+        output_stream: WriteStream<D2>,
+        failure_stream: WriteStream<OperatorErrorReport>,
+    ) -> Self {
+        assert!(
+            !config.flow_watermarks,
+            "RetryOperator sends its own watermarks"
+        );
+        let name = config
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("RetryOperator {}", config.id));
+        Self {
+            retry_config: config
+                .arg
+                .unwrap_or_else(|| panic!("{}: no retry config supplied", name)),
+            operator_id: config.id,
+            operator_name: config.name,
+            node_id: config.node_id,
+            input_stream,
+            output_stream,
+            failure_stream,
+        }
+    }
+
+    pub fn connect(
+        _input_stream: &ReadStream<D1>,
+    ) -> (WriteStream<D2>, WriteStream<OperatorErrorReport>) {
+        (WriteStream::new(), WriteStream::new())
+    }
+
+    /// Attempts the call until it succeeds, and returns the error of the last attempt otherwise.
+    async fn call_with_retries(&self, data: D1) -> Result<D2, OperatorError> {
+        let mut backoff = self.retry_config.backoff;
+        let mut attempt = 1;
+        loop {
+            let call = (self.retry_config.call)(data.clone());
+            let result = match self.retry_config.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, call)
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(OperatorError::new(format!("timed out after {:?}", timeout)))
+                    }),
+                None => call.await,
+            };
+            match result {
+                Ok(output) => return Ok(output),
+                Err(e) if attempt >= self.retry_config.max_attempts => {
+                    return Err(OperatorError::new(format!(
+                        "failed after {} attempts: {}",
+                        attempt, e
+                    )));
+                }
+                Err(_) => {
+                    tokio::time::delay_for(backoff).await;
+                    backoff *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    fn send<D: Data>(name: &str, write_stream: &mut WriteStream<D>, msg: Message<D>) {
+        if let Err(e) = write_stream.send(msg) {
+            slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "{} unable to send message on stream {}: {:?}",
+                name,
+                write_stream.get_id(),
+                e
+            );
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<D1, D2> Operator for RetryOperator<D1, D2>
+where
+    for<'a> D1: Data + Deserialize<'a>,
+    for<'a> D2: Data + Deserialize<'a>,
+{
+    async fn run_async(&mut self, _ctx: &mut RunContext) {
+        let name = self
+            .operator_name
+            .clone()
+            .unwrap_or_else(|| format!("RetryOperator {}", self.operator_id));
+        while let Ok(msg) = self.input_stream.recv().await {
+            match msg {
+                Message::TimestampedData(msg) => {
+                    let timestamp: Timestamp = msg.timestamp;
+                    match self.call_with_retries(msg.data).await {
+                        Ok(output) => Self::send(
+                            &name,
+                            &mut self.output_stream,
+                            Message::new_message(timestamp, output),
+                        ),
+                        Err(error) => {
+                            if let Some(fallback) = self.retry_config.fallback.clone() {
+                                Self::send(
+                                    &name,
+                                    &mut self.output_stream,
+                                    Message::new_message(timestamp.clone(), fallback),
+                                );
+                            }
+                            let report = OperatorErrorReport {
+                                operator_id: self.operator_id,
+                                operator_name: self.operator_name.clone(),
+                                node_id: self.node_id,
+                                timestamp: timestamp.clone(),
+                                error,
+                            };
+                            Self::send(
+                                &name,
+                                &mut self.failure_stream,
+                                Message::new_message(timestamp, report),
+                            );
+                        }
+                    }
+                }
+                Message::Watermark(watermark) => {
+                    let is_top = watermark.is_top();
+                    Self::send(
+                        &name,
+                        &mut self.output_stream,
+                        Message::new_watermark(watermark.clone()),
+                    );
+                    Self::send(
+                        &name,
+                        &mut self.failure_stream,
+                        Message::new_watermark(watermark),
+                    );
+                    if is_top {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
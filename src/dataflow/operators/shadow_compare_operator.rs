@@ -0,0 +1,170 @@
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dataflow::{
+    message::Message, operators::message_buffer::MessageBuffer, stream::WriteStreamT, Data,
+    Operator, OperatorConfig, ReadStream, Timestamp, WriteStream,
+};
+
+/// A divergence between the primary and the candidate outputs for a timestamp, sent by the
+/// [`ShadowCompareOperator`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DivergenceReport<D> {
+    /// The value of the diff metric.
+    pub divergence: f64,
+    pub primary: Vec<D>,
+    pub candidate: Vec<D>,
+}
+
+/// Configures the [`ShadowCompareOperator`].
+#[derive(Clone)]
+pub struct ShadowCompareConfig<F> {
+    /// Computes how much the candidate messages diverge from the primary messages for a
+    /// timestamp.
+    pub diff: F,
+    /// Divergences up to and including the threshold are not reported.
+    pub threshold: f64,
+}
+
+impl<F> ShadowCompareConfig<F> {
+    pub fn new(diff: F, threshold: f64) -> Self {
+        Self { diff, threshold }
+    }
+}
+
+/// An operator that compares the messages of a candidate version of a sub-graph against the
+/// messages of the primary version for the same timestamps, and sends a [`DivergenceReport`]
+/// when the provided diff metric exceeds the threshold.
+///
+/// The messages are compared once the watermark for their timestamp is received on both
+/// streams. The candidate can run in shadow mode next to the primary before switching over with
+/// a [`SwitchOperator`](crate::dataflow::operators::SwitchOperator), or be fed recorded data to
+/// test changes of an operator for regressions.
+///
+/// # Example
+/// The below example shows how to report timestamps for which a new detector finds a different
+/// number of obstacles.
+///
+/// ```
+/// # use erdos::dataflow::{
+/// #     stream::IngestStream,
+/// #     operators::{ShadowCompareConfig, ShadowCompareOperator},
+/// #     OperatorConfig,
+/// # };
+/// # use erdos::*;
+/// #
+/// # let mut primary_stream: IngestStream<u32> = IngestStream::new(0);
+/// # let mut candidate_stream: IngestStream<u32> = IngestStream::new(0);
+/// #
+/// let compare_config = OperatorConfig::new()
+///     .name("ShadowCompareOperator")
+///     .arg(ShadowCompareConfig::new(
+///         |primary: &[u32], candidate: &[u32]| {
+///             (primary.len() as f64 - candidate.len() as f64).abs()
+///         },
+///         0.0,
+///     ));
+/// let reports = connect_1_write!(
+///     ShadowCompareOperator<u32>, compare_config, primary_stream, candidate_stream);
+/// ```
+pub struct ShadowCompareOperator<D: Data> {
+    phantom_data: PhantomData<D>,
+}
+
+impl<D> ShadowCompareOperator<D>
+where
+    for<'a> D: Data + Deserialize<'a>,
+{
+    /// Returns a new instance of the ShadowCompareOperator.
+    ///
+    /// # Arguments
+    /// * `config` - An instance of OperatorConfig that provides the [`ShadowCompareConfig`].
+    /// * `primary_stream` - Represents the incoming stream of the primary version.
+    /// * `candidate_stream` - Represents the incoming stream of the candidate version.
+    /// * `report_stream` - Represents an outgoing stream of divergence reports.
+    pub fn new<F: 'static + Clone + Fn(&[D], &[D]) -> f64>(
+        config: OperatorConfig<ShadowCompareConfig<F>>,
+        primary_stream: ReadStream<D>,
+        candidate_stream: ReadStream<D>,
+        report_stream: WriteStream<DivergenceReport<D>>,
+    ) -> Self {
+        let name = config
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("ShadowCompareOperator {}", config.id));
+        let compare_config = config
+            .arg
+            .unwrap_or_else(|| panic!("{}: no shadow compare config supplied", name));
+
+        let stateful_primary_stream = primary_stream.add_state(MessageBuffer::<D>::new());
+        stateful_primary_stream.add_callback(Self::on_data_callback);
+        let stateful_candidate_stream = candidate_stream.add_state(MessageBuffer::<D>::new());
+        stateful_candidate_stream.add_callback(Self::on_data_callback);
+
+        stateful_primary_stream
+            .add_read_stream(&stateful_candidate_stream)
+            .borrow_mut()
+            .add_write_stream(&report_stream)
+            .borrow_mut()
+            .add_watermark_callback(
+                move |t: &Timestamp,
+                      primary_state: &MessageBuffer<D>,
+                      candidate_state: &MessageBuffer<D>,
+                      write_stream: &mut WriteStream<DivergenceReport<D>>| {
+                    Self::on_watermark_callback(
+                        t,
+                        primary_state,
+                        candidate_state,
+                        write_stream,
+                        &compare_config,
+                    )
+                },
+            );
+
+        Self {
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// Returns a new instance of a WriteStream to send its outgoing messages on.
+    ///
+    /// # Arguments
+    /// * `primary_stream` - Represents the incoming stream of the primary version.
+    /// * `candidate_stream` - Represents the incoming stream of the candidate version.
+    pub fn connect(
+        _primary_stream: &ReadStream<D>,
+        _candidate_stream: &ReadStream<D>,
+    ) -> WriteStream<DivergenceReport<D>> {
+        WriteStream::new()
+    }
+
+    fn on_data_callback(t: &Timestamp, msg: &D, state: &mut MessageBuffer<D>) {
+        state.add_msg(t, msg.clone());
+    }
+
+    /// Compares the messages of both versions once both versions completed the timestamp.
+    fn on_watermark_callback<F: 'static + Clone + Fn(&[D], &[D]) -> f64>(
+        t: &Timestamp,
+        primary_state: &MessageBuffer<D>,
+        candidate_state: &MessageBuffer<D>,
+        write_stream: &mut WriteStream<DivergenceReport<D>>,
+        compare_config: &ShadowCompareConfig<F>,
+    ) {
+        let primary = primary_state.take(t);
+        let candidate = candidate_state.take(t);
+        let divergence = (compare_config.diff)(&primary[..], &candidate[..]);
+        if divergence > compare_config.threshold {
+            let report = DivergenceReport {
+                divergence,
+                primary,
+                candidate,
+            };
+            write_stream
+                .send(Message::new_message(t.clone(), report))
+                .expect("ShadowCompareOperator: error sending on write stream");
+        }
+    }
+}
+
+impl<D> Operator for ShadowCompareOperator<D> where for<'a> D: Data + Deserialize<'a> {}
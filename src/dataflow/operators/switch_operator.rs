@@ -0,0 +1,198 @@
+use std::{fmt, marker::PhantomData, str::FromStr};
+
+use serde::Deserialize;
+
+use crate::{
+    dataflow::{
+        message::Message, operators::message_buffer::MessageBuffer, stream::WriteStreamT, Data,
+        Operator, OperatorConfig, ReadStream, Timestamp, WriteStream,
+    },
+    node::RuntimeKnobs,
+};
+
+/// One of the two versions of a sub-graph run by a blue/green deployment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deployment {
+    Blue,
+    Green,
+}
+
+impl fmt::Display for Deployment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Deployment::Blue => write!(f, "blue"),
+            Deployment::Green => write!(f, "green"),
+        }
+    }
+}
+
+impl FromStr for Deployment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blue" => Ok(Deployment::Blue),
+            "green" => Ok(Deployment::Green),
+            _ => Err(format!("Unknown deployment {}, expected blue or green", s)),
+        }
+    }
+}
+
+/// Configures the [`SwitchOperator`].
+#[derive(Clone)]
+pub struct SwitchConfig {
+    knobs: RuntimeKnobs,
+    knob: String,
+}
+
+impl SwitchConfig {
+    /// Registers the runtime knob which selects the active deployment.
+    ///
+    /// The deployment is switched by setting the knob to `blue` or `green` with
+    /// [`NodeHandle::set_knob`](crate::node::NodeHandle::set_knob).
+    pub fn register(knobs: &RuntimeKnobs, knob: &str, initial: Deployment) -> Self {
+        knobs.register(knob, &initial.to_string(), |value: &str| {
+            value.parse::<Deployment>().map(|_| ())
+        });
+        Self {
+            knobs: knobs.clone(),
+            knob: knob.to_string(),
+        }
+    }
+
+    /// Returns the active deployment.
+    pub fn active(&self) -> Deployment {
+        self.knobs
+            .get_parsed(&self.knob)
+            .expect("SwitchOperator: the deployment knob is not registered")
+    }
+}
+
+/// An operator that forwards the messages of either the blue or the green version of a sub-graph,
+/// both of which are fed by the same inputs.
+///
+/// The messages of both versions are buffered until the watermark for their timestamp is
+/// received on both streams, and then the messages of the active version are sent. Thus, the
+/// downstream operators switch to the other version atomically at a timestamp boundary when the
+/// knob of the [`SwitchConfig`] is updated, and receive no partial output of either version.
+///
+/// # Example
+/// The below example shows how to switch between two versions of a detector.
+///
+/// ```
+/// # use erdos::dataflow::{
+/// #     stream::IngestStream,
+/// #     operators::{Deployment, SwitchConfig, SwitchOperator},
+/// #     OperatorConfig,
+/// # };
+/// # use erdos::node::Node;
+/// # use erdos::*;
+/// #
+/// # let node = Node::new(Configuration::new(0, vec![], vec![], 1, None));
+/// # let mut blue_stream: IngestStream<u32> = IngestStream::new(0);
+/// # let mut green_stream: IngestStream<u32> = IngestStream::new(0);
+/// #
+/// let switch_config = OperatorConfig::new()
+///     .name("SwitchOperator")
+///     .arg(SwitchConfig::register(
+///         &node.runtime_knobs(),
+///         "detector",
+///         Deployment::Blue,
+///     ));
+/// let detections = connect_1_write!(
+///     SwitchOperator<u32>, switch_config, blue_stream, green_stream);
+/// // Once the green version is validated:
+/// // node_handle.set_knob("detector", "green");
+/// ```
+pub struct SwitchOperator<D: Data> {
+    phantom_data: PhantomData<D>,
+}
+
+impl<'a, D: Data + Deserialize<'a>> SwitchOperator<D> {
+    /// Returns a new instance of the SwitchOperator.
+    ///
+    /// # Arguments
+    /// * `config` - An instance of OperatorConfig that provides the [`SwitchConfig`].
+    /// * `blue_stream` - Represents the incoming stream of the blue version.
+    /// * `green_stream` - Represents the incoming stream of the green version.
+    /// * `output_stream` - Represents an outgoing stream of messages of the active version.
+    pub fn new(
+        config: OperatorConfig<SwitchConfig>,
+        blue_stream: ReadStream<D>,
+        green_stream: ReadStream<D>,
+        output_stream: WriteStream<D>,
+    ) -> Self {
+        let name = config
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("SwitchOperator {}", config.id));
+        let switch_config = config
+            .arg
+            .unwrap_or_else(|| panic!("{}: no switch config supplied", name));
+
+        let stateful_blue_stream = blue_stream.add_state(MessageBuffer::<D>::new());
+        stateful_blue_stream.add_callback(Self::on_data_callback);
+        let stateful_green_stream = green_stream.add_state(MessageBuffer::<D>::new());
+        stateful_green_stream.add_callback(Self::on_data_callback);
+
+        stateful_blue_stream
+            .add_read_stream(&stateful_green_stream)
+            .borrow_mut()
+            .add_write_stream(&output_stream)
+            .borrow_mut()
+            .add_watermark_callback(
+                move |t: &Timestamp,
+                      blue_state: &MessageBuffer<D>,
+                      green_state: &MessageBuffer<D>,
+                      write_stream: &mut WriteStream<D>| {
+                    Self::on_watermark_callback(
+                        t,
+                        blue_state,
+                        green_state,
+                        write_stream,
+                        &switch_config,
+                    )
+                },
+            );
+
+        Self {
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// Returns a new instance of a WriteStream to send its outgoing messages on.
+    ///
+    /// # Arguments
+    /// * `blue_stream` - Represents the incoming stream of the blue version.
+    /// * `green_stream` - Represents the incoming stream of the green version.
+    pub fn connect(_blue_stream: &ReadStream<D>, _green_stream: &ReadStream<D>) -> WriteStream<D> {
+        WriteStream::new()
+    }
+
+    fn on_data_callback(t: &Timestamp, msg: &D, state: &mut MessageBuffer<D>) {
+        state.add_msg(t, msg.clone());
+    }
+
+    /// Sends the messages of the active version once both versions completed the timestamp.
+    fn on_watermark_callback(
+        t: &Timestamp,
+        blue_state: &MessageBuffer<D>,
+        green_state: &MessageBuffer<D>,
+        write_stream: &mut WriteStream<D>,
+        switch_config: &SwitchConfig,
+    ) {
+        let blue_msgs = blue_state.take(t);
+        let green_msgs = green_state.take(t);
+        let msgs = match switch_config.active() {
+            Deployment::Blue => blue_msgs,
+            Deployment::Green => green_msgs,
+        };
+        for msg in msgs {
+            write_stream
+                .send(Message::new_message(t.clone(), msg))
+                .expect("SwitchOperator: error sending on write stream");
+        }
+    }
+}
+
+impl<'a, D: Data + Deserialize<'a>> Operator for SwitchOperator<D> {}
@@ -0,0 +1,453 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncRead;
+
+use crate::{
+    communication::{
+        bounded_channel::{self, BoundedReceiver, BoundedSender},
+        BackpressurePolicy, ChannelBound,
+    },
+    dataflow::{CallbackResult, Message, OperatorError, Timestamp},
+    Uuid,
+};
+
+use super::{
+    errors::{BlobError, WriteStreamError},
+    ReadStream, WriteStream, WriteStreamT,
+};
+
+/// A [`WriteStream`] on which operators send large payloads incrementally.
+pub type BlobWriteStream = WriteStream<BlobChunk>;
+/// A [`ReadStream`] from which operators receive large payloads incrementally.
+pub type BlobReadStream = ReadStream<BlobChunk>;
+
+/// Number of chunks queued for a [`BlobReader`], after which the operator waits for the reader.
+const READER_CAPACITY: usize = 16;
+/// Duration after which a blob which received no chunk is dropped.
+const BLOB_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Content of a [`BlobChunk`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BlobPayload {
+    /// A part of the blob.
+    Data(Vec<u8>),
+    /// The writer finished the blob.
+    End,
+    /// The writer was dropped before finishing the blob.
+    Aborted,
+}
+
+/// A part of a blob sent on a [`BlobWriteStream`].
+///
+/// Each chunk is sent as a separate message, so neither the writer nor the readers hold the whole
+/// blob in memory. Chunks which exceed the maximum chunk size of the node are further split by
+/// the transport.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlobChunk {
+    /// Identifies the blob to which the chunk belongs.
+    pub blob_id: Uuid,
+    /// Index of the chunk within the blob.
+    pub index: u32,
+    pub payload: BlobPayload,
+}
+
+impl WriteStream<BlobChunk> {
+    /// Starts sending a blob with the given timestamp.
+    ///
+    /// The stream cannot be used for other messages until the returned [`BlobWriter`] is
+    /// finished or dropped.
+    pub fn start_blob(&mut self, timestamp: Timestamp) -> BlobWriter<'_> {
+        BlobWriter {
+            stream: self,
+            timestamp,
+            blob_id: Uuid::new_v4(),
+            next_index: 0,
+            finished: false,
+        }
+    }
+}
+
+/// Writes a blob incrementally to a [`BlobWriteStream`].
+///
+/// Readers are notified that the blob was aborted if the [`BlobWriter`] is dropped before calling
+/// [`finish`](BlobWriter::finish).
+pub struct BlobWriter<'a> {
+    stream: &'a mut WriteStream<BlobChunk>,
+    timestamp: Timestamp,
+    blob_id: Uuid,
+    next_index: u32,
+    finished: bool,
+}
+
+impl<'a> BlobWriter<'a> {
+    /// Sends the next part of the blob.
+    pub fn write_chunk(&mut self, bytes: &[u8]) -> Result<(), WriteStreamError> {
+        self.send(BlobPayload::Data(bytes.to_vec()))
+    }
+
+    /// Notifies readers that the blob is complete.
+    pub fn finish(mut self) -> Result<(), WriteStreamError> {
+        self.finished = true;
+        self.send(BlobPayload::End)
+    }
+
+    fn send(&mut self, payload: BlobPayload) -> Result<(), WriteStreamError> {
+        let chunk = BlobChunk {
+            blob_id: self.blob_id,
+            index: self.next_index,
+            payload,
+        };
+        self.next_index += 1;
+        self.stream
+            .send(Message::new_message(self.timestamp.clone(), chunk))
+    }
+}
+
+impl<'a> Drop for BlobWriter<'a> {
+    fn drop(&mut self) {
+        if !self.finished && !self.stream.is_closed() {
+            if let Err(e) = self.send(BlobPayload::Aborted) {
+                slog::warn!(
+                    crate::TERMINAL_LOGGER,
+                    "Unable to abort blob {} on stream {}: {:?}",
+                    self.blob_id,
+                    self.stream.get_id(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+impl ReadStream<BlobChunk> {
+    /// Request a callback upon the receipt of the first chunk of each blob.
+    ///
+    /// The callback receives a [`BlobReader`] from which the rest of the blob can be read
+    /// asynchronously, e.g. in a task spawned with [`tokio::spawn`]. The callback must not block
+    /// on the reader, as the chunks are delivered by the operator's callbacks. Once a few chunks
+    /// are queued for the reader, the operator waits for the reader to read them before it
+    /// processes the next message.
+    ///
+    /// A blob which is not complete once the watermark of the stream reaches its timestamp, or
+    /// which receives no chunk for a minute, is dropped, and its reader returns
+    /// [`BlobError::Incomplete`].
+    ///
+    /// # Arguments
+    /// * callback - The callback to be invoked when a new blob is received.
+    pub fn add_blob_callback<F, R>(&self, callback: F)
+    where
+        F: 'static + Fn(&Timestamp, BlobReader) -> R,
+        R: CallbackResult,
+    {
+        let blobs = Arc::new(Mutex::new(PartialBlobs::default()));
+        let watermark_blobs = Arc::clone(&blobs);
+        self.add_callback(move |t: &Timestamp, chunk: &BlobChunk| {
+            let new_reader = blobs.lock().unwrap().add_chunk(t, chunk);
+            match new_reader {
+                Some(reader) => callback(t, reader).into_result(),
+                None => Ok::<(), OperatorError>(()),
+            }
+        });
+        self.add_watermark_callback(move |t: &Timestamp| {
+            watermark_blobs.lock().unwrap().advance_watermark(t);
+        });
+    }
+}
+
+/// The blobs of a stream which are not complete.
+#[derive(Default)]
+struct PartialBlobs {
+    blobs: HashMap<Uuid, PartialBlob>,
+    /// The blobs dropped after the timeout, whose late chunks are ignored until the watermark
+    /// reaches their timestamp.
+    timed_out: HashMap<Uuid, Timestamp>,
+    watermark: Option<Timestamp>,
+}
+
+impl PartialBlobs {
+    /// Forwards the chunk, and returns the reader of the blob if it is the first chunk received.
+    fn add_chunk(&mut self, t: &Timestamp, chunk: &BlobChunk) -> Option<BlobReader> {
+        self.drop_timed_out();
+        if self
+            .watermark
+            .as_ref()
+            .map_or(false, |watermark| t <= watermark)
+            || self.timed_out.contains_key(&chunk.blob_id)
+        {
+            return None;
+        }
+        let mut new_reader = None;
+        let blob = self.blobs.entry(chunk.blob_id).or_insert_with(|| {
+            let (tx, rx) = bounded_channel::channel(ChannelBound::new(
+                READER_CAPACITY,
+                BackpressurePolicy::Block,
+            ));
+            new_reader = Some(BlobReader::new(rx));
+            PartialBlob::new(t.clone(), tx)
+        });
+        if blob.add_chunk(chunk) {
+            self.blobs.remove(&chunk.blob_id);
+        }
+        new_reader
+    }
+
+    /// Drops the blobs which can no longer be completed, as all the chunks up to the watermark
+    /// were received.
+    fn advance_watermark(&mut self, watermark: &Timestamp) {
+        self.blobs.retain(|_, blob| {
+            if blob.timestamp <= *watermark {
+                blob.drop_incomplete();
+                false
+            } else {
+                true
+            }
+        });
+        self.timed_out.retain(|_, t| t > watermark);
+        self.watermark = Some(watermark.clone());
+        self.drop_timed_out();
+    }
+
+    fn drop_timed_out(&mut self) {
+        let timed_out = &mut self.timed_out;
+        self.blobs.retain(|blob_id, blob| {
+            if blob.last_chunk.elapsed() > BLOB_TIMEOUT {
+                blob.drop_incomplete();
+                timed_out.insert(*blob_id, blob.timestamp.clone());
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+/// Forwards the chunks of a blob to its [`BlobReader`] in order.
+struct PartialBlob {
+    timestamp: Timestamp,
+    tx: BoundedSender<Result<BlobPayload, BlobError>>,
+    next_index: u32,
+    /// Chunks received before their predecessors.
+    pending: BTreeMap<u32, BlobPayload>,
+    /// When the last chunk was received.
+    last_chunk: Instant,
+}
+
+impl PartialBlob {
+    fn new(timestamp: Timestamp, tx: BoundedSender<Result<BlobPayload, BlobError>>) -> Self {
+        Self {
+            timestamp,
+            tx,
+            next_index: 0,
+            pending: BTreeMap::new(),
+            last_chunk: Instant::now(),
+        }
+    }
+
+    /// Returns true once the last chunk of the blob was forwarded.
+    fn add_chunk(&mut self, chunk: &BlobChunk) -> bool {
+        self.last_chunk = Instant::now();
+        self.pending.insert(chunk.index, chunk.payload.clone());
+        while let Some(payload) = self.pending.remove(&self.next_index) {
+            self.next_index += 1;
+            let is_data = matches!(payload, BlobPayload::Data(_));
+            // The reader may have been dropped, in which case the blob is discarded.
+            let _ = self.tx.send(Ok(payload), is_data);
+            if !is_data {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Notifies the reader that the blob will not be completed.
+    fn drop_incomplete(&mut self) {
+        let _ = self.tx.send(Err(BlobError::Incomplete), false);
+    }
+}
+
+/// Reads a blob sent by a [`BlobWriter`].
+///
+/// Chunks can be read with [`read_chunk`](BlobReader::read_chunk), or as a byte stream via the
+/// [`AsyncRead`] implementation.
+pub struct BlobReader {
+    rx: BoundedReceiver<Result<BlobPayload, BlobError>>,
+    /// Unread bytes of the last received chunk.
+    buffer: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl BlobReader {
+    fn new(rx: BoundedReceiver<Result<BlobPayload, BlobError>>) -> Self {
+        Self {
+            rx,
+            buffer: Vec::new(),
+            position: 0,
+            finished: false,
+        }
+    }
+
+    /// Returns the next chunk of the blob, or `None` once the blob is complete.
+    pub async fn read_chunk(&mut self) -> Option<Result<Vec<u8>, BlobError>> {
+        // Return the bytes left over by a partial read first.
+        if self.position < self.buffer.len() {
+            let bytes = self.buffer.split_off(self.position);
+            self.buffer.clear();
+            self.position = 0;
+            return Some(Ok(bytes));
+        }
+        futures::future::poll_fn(|cx| self.poll_chunk(cx)).await
+    }
+
+    /// Reads the rest of the blob into memory.
+    pub async fn read_to_vec(&mut self) -> Result<Vec<u8>, BlobError> {
+        let mut bytes = Vec::new();
+        while let Some(chunk) = self.read_chunk().await {
+            bytes.extend_from_slice(&chunk?);
+        }
+        Ok(bytes)
+    }
+
+    fn poll_chunk(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Vec<u8>, BlobError>>> {
+        if self.finished {
+            return Poll::Ready(None);
+        }
+        match self.rx.poll_recv(cx) {
+            Poll::Ready(Some(Ok(BlobPayload::Data(bytes)))) => Poll::Ready(Some(Ok(bytes))),
+            Poll::Ready(Some(Ok(BlobPayload::End))) => {
+                self.finished = true;
+                Poll::Ready(None)
+            }
+            Poll::Ready(Some(Ok(BlobPayload::Aborted))) => {
+                self.finished = true;
+                Poll::Ready(Some(Err(BlobError::Aborted)))
+            }
+            Poll::Ready(Some(Err(e))) => {
+                self.finished = true;
+                Poll::Ready(Some(Err(e)))
+            }
+            // The operator shut down before the blob was complete.
+            Poll::Ready(None) => {
+                self.finished = true;
+                Poll::Ready(Some(Err(BlobError::Disconnected)))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl AsyncRead for BlobReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        while self.position == self.buffer.len() {
+            match self.poll_chunk(cx) {
+                Poll::Ready(Some(Ok(bytes))) => {
+                    self.buffer = bytes;
+                    self.position = 0;
+                }
+                Poll::Ready(Some(Err(e))) => {
+                    return Poll::Ready(Err(io::Error::new(io::ErrorKind::UnexpectedEof, e)))
+                }
+                Poll::Ready(None) => return Poll::Ready(Ok(0)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        let num_bytes = std::cmp::min(buf.len(), self.buffer.len() - self.position);
+        let position = self.position;
+        buf[..num_bytes].copy_from_slice(&self.buffer[position..position + num_bytes]);
+        self.position += num_bytes;
+        Poll::Ready(Ok(num_bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    fn make_chunk(blob_id: Uuid, index: u32, payload: BlobPayload) -> BlobChunk {
+        BlobChunk {
+            blob_id,
+            index,
+            payload,
+        }
+    }
+
+    fn make_channel() -> (
+        BoundedSender<Result<BlobPayload, BlobError>>,
+        BoundedReceiver<Result<BlobPayload, BlobError>>,
+    ) {
+        bounded_channel::channel(ChannelBound::new(
+            READER_CAPACITY,
+            BackpressurePolicy::Block,
+        ))
+    }
+
+    /// Test that chunks received out of order are forwarded to the reader in order.
+    #[test]
+    fn test_out_of_order_chunks() {
+        let blob_id = Uuid::new_v4();
+        let (tx, rx) = make_channel();
+        let mut blob = PartialBlob::new(Timestamp::new(vec![1]), tx);
+        assert!(!blob.add_chunk(&make_chunk(blob_id, 1, BlobPayload::Data(vec![3, 4]))));
+        assert!(!blob.add_chunk(&make_chunk(blob_id, 2, BlobPayload::End)));
+        assert!(blob.add_chunk(&make_chunk(blob_id, 0, BlobPayload::Data(vec![1, 2]))));
+
+        let mut reader = BlobReader::new(rx);
+        let mut bytes = Vec::new();
+        block_on(reader.read_to_end(&mut bytes)).unwrap();
+        assert_eq!(bytes, vec![1, 2, 3, 4]);
+    }
+
+    /// Test that the reader returns an error if the writer aborts the blob.
+    #[test]
+    fn test_aborted_blob() {
+        let blob_id = Uuid::new_v4();
+        let (tx, rx) = make_channel();
+        let mut blob = PartialBlob::new(Timestamp::new(vec![1]), tx);
+        assert!(!blob.add_chunk(&make_chunk(blob_id, 0, BlobPayload::Data(vec![1, 2]))));
+        assert!(blob.add_chunk(&make_chunk(blob_id, 1, BlobPayload::Aborted)));
+
+        let mut reader = BlobReader::new(rx);
+        assert_eq!(block_on(reader.read_chunk()), Some(Ok(vec![1, 2])));
+        assert_eq!(block_on(reader.read_chunk()), Some(Err(BlobError::Aborted)));
+        assert_eq!(block_on(reader.read_chunk()), None);
+    }
+
+    /// Test that the blobs which are not complete once the watermark reaches their timestamp are
+    /// dropped, and that their late chunks are ignored.
+    #[test]
+    fn test_watermark_drops_incomplete_blob() {
+        let blob_id = Uuid::new_v4();
+        let t = Timestamp::new(vec![1]);
+        let mut blobs = PartialBlobs::default();
+        let mut reader = blobs
+            .add_chunk(&t, &make_chunk(blob_id, 0, BlobPayload::Data(vec![1, 2])))
+            .unwrap();
+        blobs.advance_watermark(&t);
+        assert!(blobs.blobs.is_empty());
+        assert!(blobs
+            .add_chunk(&t, &make_chunk(blob_id, 1, BlobPayload::End))
+            .is_none());
+
+        assert_eq!(block_on(reader.read_chunk()), Some(Ok(vec![1, 2])));
+        assert_eq!(
+            block_on(reader.read_chunk()),
+            Some(Err(BlobError::Incomplete))
+        );
+        assert_eq!(block_on(reader.read_chunk()), None);
+    }
+}
@@ -0,0 +1,139 @@
+use std::thread;
+
+use futures::{future, SinkExt, Stream, StreamExt};
+use serde::Deserialize;
+use tokio::sync::{
+    broadcast::{self, RecvError},
+    mpsc::UnboundedSender,
+};
+
+use crate::{
+    dataflow::{Data, Message, Timestamp},
+    node::NodeId,
+};
+
+use super::{ExtractStream, IngestStream, ReadStream};
+
+/// Returns a stream on which the messages received from a channel are sent, e.g. to integrate an
+/// existing asynchronous component without writing an operator.
+///
+/// The receiver may be any [`Stream`], such as a tokio `mpsc` receiver, or a tokio `broadcast`
+/// receiver adapted by [`skip_lagged`]. Each message is sent with the timestamp assigned
+/// by `assign_timestamp`, followed by a watermark for the timestamp, so timestamps must not
+/// decrease. A top watermark is sent once the channel closes.
+///
+/// Messages are forwarded from a separate thread once the dataflow is set up on the node.
+pub fn stream_from_channel<D, R, F>(
+    node_id: NodeId,
+    receiver: R,
+    mut assign_timestamp: F,
+) -> ReadStream<D>
+where
+    for<'a> D: Data + Deserialize<'a>,
+    R: 'static + Stream<Item = D> + Send + Unpin,
+    F: 'static + FnMut(&D) -> Timestamp + Send,
+{
+    let mut ingest_stream = IngestStream::new(node_id);
+    let read_stream = ReadStream::from(&ingest_stream);
+    thread::spawn(move || {
+        futures::executor::block_on(async {
+            let mut receiver = receiver;
+            while let Some(data) = receiver.next().await {
+                let timestamp = assign_timestamp(&data);
+                // Awaits the setup of the dataflow before sending the first message.
+                let msg = Message::new_message(timestamp.clone(), data);
+                let result = match SinkExt::send(&mut ingest_stream, msg).await {
+                    Ok(()) => {
+                        let watermark = Message::new_watermark(timestamp);
+                        SinkExt::send(&mut ingest_stream, watermark).await
+                    }
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    slog::warn!(
+                        crate::TERMINAL_LOGGER,
+                        "Stopped forwarding messages from a channel to IngestStream {} (ID: {}): \
+                         {:?}",
+                        ingest_stream.get_name(),
+                        ingest_stream.get_id(),
+                        e
+                    );
+                    return;
+                }
+            }
+            ingest_stream.close().await.ok();
+        })
+    });
+    read_stream
+}
+
+/// Adapts a tokio `broadcast` receiver to pass to [`stream_from_channel`].
+///
+/// The messages which the receiver missed because it lagged behind the sender are skipped with a
+/// warning, and the stream ends once the channel closes.
+pub fn skip_lagged<D>(receiver: broadcast::Receiver<D>) -> impl Stream<Item = D> + Send + Unpin
+where
+    D: 'static + Clone + Send,
+{
+    receiver
+        .take_while(|result| future::ready(!matches!(result, Err(RecvError::Closed))))
+        .filter_map(|result| {
+            future::ready(match result {
+                Ok(data) => Some(data),
+                Err(RecvError::Lagged(num_skipped)) => {
+                    slog::warn!(
+                        crate::TERMINAL_LOGGER,
+                        "Skipped {} messages of a lagging broadcast receiver",
+                        num_skipped
+                    );
+                    None
+                }
+                Err(RecvError::Closed) => None,
+            })
+        })
+}
+
+/// Sends the data of the messages on the stream to a channel, e.g. to hand results to an
+/// existing asynchronous component.
+///
+/// Watermarks are not forwarded. The sender is dropped, which closes the channel, once the
+/// stream receives a top watermark or the receiver is dropped.
+pub fn stream_to_channel<D>(
+    node_id: NodeId,
+    read_stream: &ReadStream<D>,
+    sender: UnboundedSender<D>,
+) where
+    for<'a> D: Data + Deserialize<'a>,
+{
+    let extract_stream = ExtractStream::new(node_id, read_stream);
+    thread::spawn(move || {
+        futures::executor::block_on(async {
+            let mut extract_stream = extract_stream;
+            while let Some(msg) = extract_stream.next().await {
+                if let Message::TimestampedData(td) = msg {
+                    if sender.send(td.data).is_err() {
+                        return;
+                    }
+                }
+            }
+        })
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the messages missed by a lagging broadcast receiver are skipped, and that the
+    /// stream ends once the channel closes.
+    #[test]
+    fn test_skip_lagged() {
+        let (tx, rx) = broadcast::channel(2);
+        for i in 0..4usize {
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+        let received: Vec<usize> = futures::executor::block_on(skip_lagged(rx).collect());
+        assert_eq!(received, vec![2, 3]);
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Condvar, Mutex},
+};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{communication::ControlMessage, Uuid};
+
+use super::StreamId;
+
+lazy_static! {
+    static ref DEMAND: StreamDemand = StreamDemand::new();
+}
+
+/// A consumer subscribed to or unsubscribed from a stream.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DemandChange {
+    pub stream_id: StreamId,
+    pub subscription_id: Uuid,
+    pub subscribed: bool,
+}
+
+/// Tracks the consumers which demand the messages of each stream.
+///
+/// Changes requested on the current process are sent to the nodes, which apply them and
+/// propagate them to the other nodes via the control plane.
+struct StreamDemand {
+    subscriptions: Mutex<HashMap<StreamId, HashSet<Uuid>>>,
+    changed: Condvar,
+    channels_to_nodes: Mutex<Vec<UnboundedSender<ControlMessage>>>,
+    /// Changes requested before any node started running.
+    pending: Mutex<Vec<DemandChange>>,
+}
+
+impl StreamDemand {
+    fn new() -> Self {
+        Self {
+            subscriptions: Mutex::new(HashMap::new()),
+            changed: Condvar::new(),
+            channels_to_nodes: Mutex::new(Vec::new()),
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn request(&self, change: DemandChange) {
+        let mut channels_to_nodes = self.channels_to_nodes.lock().unwrap();
+        if channels_to_nodes.is_empty() {
+            self.pending.lock().unwrap().push(change);
+            return;
+        }
+        channels_to_nodes.retain(|tx| {
+            tx.send(ControlMessage::StreamDemand(change.clone()))
+                .is_ok()
+        });
+    }
+
+    fn apply(&self, change: &DemandChange) -> bool {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let subscribers = subscriptions.entry(change.stream_id).or_default();
+        let changed = if change.subscribed {
+            subscribers.insert(change.subscription_id)
+        } else {
+            subscribers.remove(&change.subscription_id)
+        };
+        if changed {
+            self.changed.notify_all();
+        }
+        changed
+    }
+
+    fn has_demand(&self, stream_id: StreamId) -> bool {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .get(&stream_id)
+            .map_or(false, |subscribers| !subscribers.is_empty())
+    }
+
+    fn wait_for_demand(&self, stream_id: StreamId) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        while subscriptions
+            .get(&stream_id)
+            .map_or(true, |subscribers| subscribers.is_empty())
+        {
+            subscriptions = self.changed.wait(subscriptions).unwrap();
+        }
+    }
+}
+
+/// Signals demand for the messages of a stream until it is dropped.
+///
+/// Created by [`ReadStream::subscribe`](super::ReadStream::subscribe) and
+/// [`ExtractStream::subscribe`](super::ExtractStream::subscribe).
+pub struct DemandSubscription {
+    stream_id: StreamId,
+    subscription_id: Uuid,
+}
+
+impl DemandSubscription {
+    pub(crate) fn new(stream_id: StreamId) -> Self {
+        let subscription_id = Uuid::new_v4();
+        DEMAND.request(DemandChange {
+            stream_id,
+            subscription_id,
+            subscribed: true,
+        });
+        Self {
+            stream_id,
+            subscription_id,
+        }
+    }
+}
+
+impl Drop for DemandSubscription {
+    fn drop(&mut self) {
+        DEMAND.request(DemandChange {
+            stream_id: self.stream_id,
+            subscription_id: self.subscription_id,
+            subscribed: false,
+        });
+    }
+}
+
+/// Sends the demand changes requested on the current process to the node's control message
+/// handler, including the changes requested before the node started running.
+pub(crate) fn register_node(tx: UnboundedSender<ControlMessage>) {
+    let mut channels_to_nodes = DEMAND.channels_to_nodes.lock().unwrap();
+    for change in DEMAND.pending.lock().unwrap().drain(..) {
+        let _ = tx.send(ControlMessage::StreamDemand(change));
+    }
+    channels_to_nodes.push(tx);
+}
+
+/// Applies a demand change. Returns true if the change must be propagated to the other nodes,
+/// i.e. if it was not applied before.
+pub(crate) fn apply(change: &DemandChange) -> bool {
+    DEMAND.apply(change)
+}
+
+pub(crate) fn has_demand(stream_id: StreamId) -> bool {
+    DEMAND.has_demand(stream_id)
+}
+
+pub(crate) fn wait_for_demand(stream_id: StreamId) {
+    DEMAND.wait_for_demand(stream_id)
+}
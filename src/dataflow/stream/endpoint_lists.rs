@@ -0,0 +1,170 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{
+    communication::SendEndpoint,
+    dataflow::{Data, Message},
+};
+
+use super::StreamId;
+
+/// The send endpoints of the streams written by the operators and drivers of a node, which the
+/// [`WriteStream`](super::WriteStream)s of each stream share.
+///
+/// The endpoints of a stream are published as an immutable list, which write streams load
+/// through their [`Handle`] without locking while the list does not change. A list is only
+/// locked when the node reconfigures the channels of its stream, e.g. when operators are added
+/// to the running dataflow.
+///
+/// Kept by each node, so that nodes which run in the same process do not share endpoints.
+#[derive(Default)]
+pub(crate) struct EndpointLists {
+    /// The [`Handle`] of each stream.
+    streams: HashMap<StreamId, Box<dyn Any + Send>>,
+}
+
+impl EndpointLists {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the handle through which the write streams of the stream load the endpoints of
+    /// the stream.
+    pub fn handle<D: Data>(&mut self, stream_id: StreamId) -> Result<Handle<D>, String> {
+        self.streams
+            .entry(stream_id)
+            .or_insert_with(|| Box::new(Handle::<D>::new()))
+            .downcast_ref::<Handle<D>>()
+            .cloned()
+            .ok_or_else(|| {
+                format!(
+                    "The endpoints of stream {} do not match the type of its data",
+                    stream_id
+                )
+            })
+    }
+
+    /// Adds the send endpoints to the list of the stream, which its write streams load before
+    /// they send their next message.
+    pub fn add<D: Data>(
+        &mut self,
+        stream_id: StreamId,
+        endpoints: Vec<SendEndpoint<Arc<Message<D>>>>,
+    ) -> Result<(), String> {
+        if !endpoints.is_empty() {
+            self.handle::<D>(stream_id)?
+                .update(|list| list.extend(endpoints));
+        }
+        Ok(())
+    }
+}
+
+/// Loads the endpoints of a stream. Each clone of a [`WriteStream`](super::WriteStream) holds its
+/// own handle, which tells it whether the list changed since it last loaded it.
+pub(crate) struct Handle<D: Data> {
+    shared: Arc<SharedList<D>>,
+    /// The version of the list which the holder of the handle loaded last.
+    loaded_version: usize,
+}
+
+/// The list of endpoints of a stream, shared by the handles of the stream.
+struct SharedList<D: Data> {
+    /// Swapped for an updated copy when the list changes, so that loading the list only clones
+    /// the [`Arc`].
+    endpoints: Mutex<Arc<Vec<SendEndpoint<Arc<Message<D>>>>>>,
+    /// Incremented on every change of the list, which lets the handles check whether the list
+    /// changed without locking.
+    version: AtomicUsize,
+}
+
+impl<D: Data> Handle<D> {
+    fn new() -> Self {
+        Self {
+            shared: Arc::new(SharedList {
+                endpoints: Mutex::new(Arc::new(Vec::new())),
+                version: AtomicUsize::new(0),
+            }),
+            loaded_version: 0,
+        }
+    }
+
+    /// Replaces the list with a copy changed by `f`. The write streams which loaded the previous
+    /// list send on it until they load the new one.
+    fn update(&self, f: impl FnOnce(&mut Vec<SendEndpoint<Arc<Message<D>>>>)) {
+        let mut endpoints = self.shared.endpoints.lock().unwrap();
+        let mut updated_endpoints = Vec::clone(&endpoints);
+        f(&mut updated_endpoints);
+        *endpoints = Arc::new(updated_endpoints);
+        self.shared.version.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Returns the list of endpoints if it changed since the last call, without locking if it
+    /// did not.
+    pub fn load(&mut self) -> Option<Arc<Vec<SendEndpoint<Arc<Message<D>>>>>> {
+        let version = self.shared.version.load(Ordering::SeqCst);
+        if version == self.loaded_version {
+            return None;
+        }
+        let endpoints = Arc::clone(&self.shared.endpoints.lock().unwrap());
+        self.loaded_version = version;
+        Some(endpoints)
+    }
+}
+
+impl<D: Data> Clone for Handle<D> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+            loaded_version: self.loaded_version,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    /// Test that every handle loads the list of endpoints once per change, and that nodes do not
+    /// share endpoints.
+    #[test]
+    fn test_load_endpoint_lists() {
+        let stream_id = StreamId::new_deterministic();
+        let mut endpoint_lists = EndpointLists::new();
+        let mut first_handle = endpoint_lists.handle::<usize>(stream_id).unwrap();
+        assert!(first_handle.load().is_none());
+        let (tx, _rx) = mpsc::unbounded_channel();
+        endpoint_lists
+            .add::<usize>(
+                stream_id,
+                vec![SendEndpoint::InterThread(tx, Default::default())],
+            )
+            .unwrap();
+
+        let mut second_handle = first_handle.clone();
+        assert_eq!(first_handle.load().unwrap().len(), 1);
+        assert!(first_handle.load().is_none());
+        assert_eq!(second_handle.load().unwrap().len(), 1);
+
+        let (tx, _rx) = mpsc::unbounded_channel();
+        endpoint_lists
+            .add::<usize>(
+                stream_id,
+                vec![SendEndpoint::InterThread(tx, Default::default())],
+            )
+            .unwrap();
+        assert_eq!(first_handle.load().unwrap().len(), 2);
+        assert_eq!(second_handle.load().unwrap().len(), 2);
+
+        let mut other_node_handle = EndpointLists::new().handle::<usize>(stream_id).unwrap();
+        assert!(other_node_handle.load().is_none());
+        assert!(endpoint_lists.handle::<String>(stream_id).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use std::{error::Error, fmt};
+
 use crate::communication::{CommunicationError, TryRecvError};
 
 /// Errors raised by reading from a `ReadStream`.
@@ -48,6 +50,8 @@ pub enum WriteStreamError {
     TimestampError,
     /// Stream is closed and can no longer send messages.
     Closed,
+    /// The serialized message exceeds the maximum message size of the node.
+    MessageTooLarge { size: usize, limit: usize },
 }
 
 impl From<CommunicationError> for WriteStreamError {
@@ -69,6 +73,13 @@ impl From<CommunicationError> for WriteStreamError {
                 eprintln!("Bincode error {}", error);
                 WriteStreamError::SerializationError
             }
+            CommunicationError::MessageTooLarge { size, limit } => {
+                WriteStreamError::MessageTooLarge { size, limit }
+            }
+            CommunicationError::CompressionError(error) => {
+                eprintln!("Compression error {}", error);
+                WriteStreamError::SerializationError
+            }
             CommunicationError::IoError(io_error) => {
                 eprintln!("Got write stream IOError {}", io_error);
                 WriteStreamError::IOError
@@ -91,3 +102,27 @@ impl From<CommunicationError> for WriteStreamError {
         }
     }
 }
+
+/// Errors raised by reading from a [`BlobReader`](crate::dataflow::stream::BlobReader).
+#[derive(Debug, PartialEq)]
+pub enum BlobError {
+    /// The writer was dropped before finishing the blob.
+    Aborted,
+    /// The stream was closed before the blob was complete.
+    Disconnected,
+    /// The watermark reached the timestamp of the blob, or the blob timed out, before the blob
+    /// was complete.
+    Incomplete,
+}
+
+impl fmt::Display for BlobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlobError::Aborted => write!(f, "the writer aborted the blob"),
+            BlobError::Disconnected => write!(f, "the stream closed before the blob was complete"),
+            BlobError::Incomplete => write!(f, "chunks of the blob were lost"),
+        }
+    }
+}
+
+impl Error for BlobError {}
@@ -1,20 +1,23 @@
 use std::{
+    pin::Pin,
     sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
     thread,
     time::Duration,
 };
 
+use futures::Stream;
 use serde::Deserialize;
 
 use crate::{
-    dataflow::{graph::default_graph, Data, Message},
+    dataflow::{graph::default_graph, Data, Message, Timestamp},
     node::NodeId,
     scheduler::channel_manager::ChannelManager,
 };
 
 use super::{
     errors::{ReadError, TryReadError},
-    InternalReadStream, ReadStream, StreamId,
+    DemandSubscription, InternalReadStream, ReadStream, StreamId,
 };
 
 /// An [`ExtractStream`] enables drivers to read data from a running ERDOS application.
@@ -23,6 +26,14 @@ use super::{
 /// [`try_read`](ExtractStream::try_read) functions to allow drivers to read data output by the
 /// operators of the graph.
 ///
+/// The [`ExtractStream`] is also a [`Stream`] of the messages, which lets drivers in an
+/// asynchronous context await messages with `while let Some(msg) = extract_stream.next().await`.
+/// The stream ends after the top watermark or if the [`ExtractStream`] is disconnected.
+///
+/// Drivers learn that all results for a timestamp arrived from the watermarks of the stream,
+/// either with [`read_until_watermark`](ExtractStream::read_until_watermark) or with
+/// [watermark callbacks](ExtractStream::add_watermark_callback).
+///
 /// # Example
 /// The below example shows how to use an [`IngestStream`] to send data to a
 /// [`MapOperator`](crate::dataflow::operators::MapOperator), and retrieve the mapped values
@@ -78,6 +89,11 @@ where
     read_stream_option: Option<ReadStream<D>>,
     // Used to circumvent requiring Send to transfer ReadStream across threads
     channel_manager_option: Arc<Mutex<Option<Arc<Mutex<ChannelManager>>>>>,
+    /// Woken up once the channel manager is set, if the stream was polled before.
+    setup_waker: Arc<Mutex<Option<Waker>>>,
+    /// The last watermark read from the stream.
+    low_watermark: Option<Timestamp>,
+    watermark_callbacks: Vec<Box<dyn FnMut(&Timestamp) + Send>>,
 }
 
 impl<D> ExtractStream<D>
@@ -136,8 +152,12 @@ where
             node_id,
             read_stream_option: None,
             channel_manager_option: Arc::new(Mutex::new(None)),
+            setup_waker: Arc::new(Mutex::new(None)),
+            low_watermark: None,
+            watermark_callbacks: Vec::new(),
         };
         let channel_manager_option_copy = Arc::clone(&extract_stream.channel_manager_option);
+        let setup_waker_copy = Arc::clone(&extract_stream.setup_waker);
 
         // Sets up self.read_stream_option using channel_manager
         let setup_hook = move |channel_manager: Arc<Mutex<ChannelManager>>| {
@@ -145,6 +165,9 @@ where
                 .lock()
                 .unwrap()
                 .replace(channel_manager);
+            if let Some(waker) = setup_waker_copy.lock().unwrap().take() {
+                waker.wake();
+            }
         };
 
         default_graph::add_extract_stream(&extract_stream, setup_hook);
@@ -156,6 +179,11 @@ where
         self.id
     }
 
+    /// Signals demand for the messages of the stream until the returned subscription is dropped.
+    pub fn subscribe(&self) -> DemandSubscription {
+        DemandSubscription::new(self.id)
+    }
+
     /// Get the name of the stream.
     /// Returns a [`str`] version of the ID if the stream was not constructed with
     /// [`new_with_name`](ExtractStream::new_with_name).
@@ -177,11 +205,68 @@ where
             .unwrap_or(true)
     }
 
+    /// Returns the last watermark read from the stream, or `None` if no watermark was read yet.
+    pub fn get_low_watermark(&self) -> Option<&Timestamp> {
+        self.low_watermark.as_ref()
+    }
+
+    /// Request a callback on the receipt of a
+    /// [`Watermark`](crate::dataflow::message::Message::Watermark) message on the stream, i.e.
+    /// once all messages with timestamps up to the watermark were read.
+    ///
+    /// The callback runs on the driver's thread when the driver reads the watermark, with
+    /// [`read`](ExtractStream::read), [`try_read`](ExtractStream::try_read), or by polling the
+    /// stream.
+    pub fn add_watermark_callback<F: 'static + FnMut(&Timestamp) + Send>(&mut self, callback: F) {
+        self.watermark_callbacks.push(Box::new(callback));
+    }
+
+    /// Blocks until a watermark greater than or equal to `timestamp` is read, and returns the
+    /// data messages read until then, in the order in which they were received.
+    ///
+    /// The messages include all messages with timestamps up to `timestamp`, as well as later
+    /// messages received before the watermark. Returns immediately if such a watermark was
+    /// already read.
+    pub fn read_until_watermark(
+        &mut self,
+        timestamp: &Timestamp,
+    ) -> Result<Vec<Message<D>>, ReadError> {
+        let mut messages = Vec::new();
+        while self
+            .low_watermark
+            .as_ref()
+            .map_or(true, |watermark| watermark < timestamp)
+        {
+            if let msg @ Message::TimestampedData(_) = self.read()? {
+                messages.push(msg);
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Updates the low watermark and runs the watermark callbacks if the message is a watermark.
+    fn on_read(&mut self, msg: &Message<D>) {
+        if let Message::Watermark(timestamp) = msg {
+            self.low_watermark = Some(timestamp.clone());
+            for callback in self.watermark_callbacks.iter_mut() {
+                callback(timestamp);
+            }
+        }
+    }
+
     /// Non-blocking read from the [`ExtractStream`].
     ///
     /// Returns the Message available on the [`ReadStream`], or an [`Empty`](TryReadError::Empty)
     /// if no message is available.
     pub fn try_read(&mut self) -> Result<Message<D>, TryReadError> {
+        let result = self.try_read_internal();
+        if let Ok(msg) = &result {
+            self.on_read(msg);
+        }
+        result
+    }
+
+    fn try_read_internal(&mut self) -> Result<Message<D>, TryReadError> {
         if let Some(read_stream) = &self.read_stream_option {
             read_stream.try_read()
         } else {
@@ -211,6 +296,12 @@ where
         }
     }
 
+    /// Returns the [`ExtractStream`] as a [`Stream`] of its messages, e.g. to pass it to
+    /// combinators which take ownership of the stream.
+    pub fn into_async_iter(self) -> impl Stream<Item = Message<D>> {
+        self
+    }
+
     /// Blocking read from the [`ExtractStream`].
     ///
     /// Returns the Message available on the [`ReadStream`].
@@ -221,7 +312,13 @@ where
                 break match result {
                     Ok(msg) => Ok(msg),
                     Err(TryReadError::Disconnected) => Err(ReadError::Disconnected),
-                    Err(TryReadError::Empty) => self.read_stream_option.as_ref().unwrap().read(),
+                    Err(TryReadError::Empty) => {
+                        let result = self.read_stream_option.as_ref().unwrap().read();
+                        if let Ok(msg) = &result {
+                            self.on_read(msg);
+                        }
+                        result
+                    }
                     Err(TryReadError::SerializationError) => Err(ReadError::SerializationError),
                     Err(TryReadError::Closed) => Err(ReadError::Closed),
                 };
@@ -232,5 +329,56 @@ where
     }
 }
 
+impl<D> Stream for ExtractStream<D>
+where
+    for<'a> D: Data + Deserialize<'a>,
+{
+    type Item = Message<D>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Message<D>>> {
+        let extract_stream = self.get_mut();
+        if extract_stream.read_stream_option.is_none() {
+            // Register the waker before checking the channel manager to avoid missing the setup.
+            extract_stream
+                .setup_waker
+                .lock()
+                .unwrap()
+                .replace(cx.waker().clone());
+            match extract_stream.try_read() {
+                Ok(msg) => return Poll::Ready(Some(msg)),
+                Err(_) if extract_stream.read_stream_option.is_some() => (),
+                Err(_)
+                    if extract_stream
+                        .channel_manager_option
+                        .lock()
+                        .unwrap()
+                        .is_none() =>
+                {
+                    return Poll::Pending;
+                }
+                // The dataflow is set up, but the stream has no endpoint.
+                Err(_) => return Poll::Ready(None),
+            }
+        }
+        loop {
+            let read_stream = extract_stream.read_stream_option.as_ref().unwrap();
+            match read_stream.poll_recv(cx) {
+                Poll::Ready(Ok(msg)) => {
+                    extract_stream.on_read(&msg);
+                    return Poll::Ready(Some(msg));
+                }
+                Poll::Ready(Err(ReadError::SerializationError)) => slog::error!(
+                    crate::TERMINAL_LOGGER,
+                    "ExtractStream {} (ID: {}): unable to deserialize a message",
+                    extract_stream.get_name(),
+                    extract_stream.get_id()
+                ),
+                Poll::Ready(Err(_)) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
 // Needed to avoid deadlock in Python
 unsafe impl<D> Send for ExtractStream<D> where for<'a> D: Data + Deserialize<'a> {}
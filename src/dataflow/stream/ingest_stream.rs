@@ -1,15 +1,19 @@
 use std::{
+    pin::Pin,
     sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
     thread,
     time::Duration,
 };
 
+use futures::Sink;
 use serde::Deserialize;
 
 use crate::{
-    dataflow::{graph::default_graph, Data, Message},
+    dataflow::{graph::default_graph, Data, Message, Timestamp},
     node::NodeId,
     scheduler::channel_manager::ChannelManager,
+    time::Clock,
 };
 
 use super::{errors::WriteStreamError, StreamId, WriteStream, WriteStreamT};
@@ -19,6 +23,10 @@ use super::{errors::WriteStreamError, StreamId, WriteStream, WriteStreamT};
 /// Similar to a [`WriteStream`], an [`IngestStream`] exposes a [`send`](IngestStream::send)
 /// function to allow drivers to send data to the operators of the constructed graph.
 ///
+/// The [`IngestStream`] is also a [`Sink`] of messages for drivers in an asynchronous context,
+/// which is ready once the dataflow is set up and the stream can accept messages. Closing the
+/// sink sends a top watermark.
+///
 /// # Example
 /// The below example shows how to use a [`MapOperator`](crate::dataflow::operators::MapOperator)
 /// to double an incoming stream of [`u32`] messages, and return them as [`u64`] messages.
@@ -59,6 +67,13 @@ where
     node_id: NodeId,
     // Use a std mutex because the driver doesn't run on the tokio runtime.
     write_stream_option: Arc<Mutex<Option<WriteStream<D>>>>,
+    /// The write stream once it is set up, taken from `write_stream_option` so that sending
+    /// does not lock it.
+    write_stream: Option<WriteStream<D>>,
+    /// The clock used to set the ingestion time of messages.
+    clock: Clock,
+    /// Woken up once the write stream is set up, if the sink was polled before.
+    setup_waker: Arc<Mutex<Option<Waker>>>,
 }
 
 impl<D> IngestStream<D>
@@ -86,6 +101,8 @@ where
     /// # Arguments
     /// * `node_id` - The ID of the Node where the driver is running (typically, 0).
     /// * `name` - The name to be given to the stream.
+    ///
+    /// Panics if the ID derived from the name collides with the ID of another stream.
     pub fn new_with_name(node_id: NodeId, name: &str) -> Self {
         slog::debug!(
             crate::TERMINAL_LOGGER,
@@ -93,7 +110,9 @@ where
             node_id,
             name
         );
-        let id = StreamId::new_deterministic();
+        let id = StreamId::new_named(name)
+            .unwrap_or_else(|e| panic!("Unable to create the stream {}: {}", name, e));
+        id.set_name(name);
         IngestStream::new_internal(node_id, id, name.to_string())
     }
 
@@ -106,21 +125,27 @@ where
             name,
             node_id,
             write_stream_option: Arc::new(Mutex::new(None)),
+            write_stream: None,
+            clock: Clock::default(),
+            setup_waker: Arc::new(Mutex::new(None)),
         };
         let write_stream_option_copy = Arc::clone(&ingest_stream.write_stream_option);
+        let setup_waker_copy = Arc::clone(&ingest_stream.setup_waker);
 
         // Sets up self.write_stream_option using channel_manager
         let setup_hook = move |channel_manager: Arc<Mutex<ChannelManager>>| match channel_manager
             .lock()
             .unwrap()
-            .get_send_endpoints(id)
+            .get_write_stream(id)
         {
-            Ok(send_endpoints) => {
-                let write_stream = WriteStream::from_endpoints(send_endpoints, id);
+            Ok(write_stream) => {
                 write_stream_option_copy
                     .lock()
                     .unwrap()
                     .replace(write_stream);
+                if let Some(waker) = setup_waker_copy.lock().unwrap().take() {
+                    waker.wake();
+                }
             }
             Err(msg) => panic!("Unable to set up IngestStream {}: {}", id, msg),
         };
@@ -149,26 +174,49 @@ where
     /// Returns `true` if a top watermark message was received or the [`IngestStream`] failed to
     /// set up.
     pub fn is_closed(&self) -> bool {
-        self.write_stream_option
-            .lock()
-            .unwrap()
-            .as_ref()
-            .map(WriteStream::is_closed)
-            .unwrap_or(true)
+        match &self.write_stream {
+            Some(write_stream) => write_stream.is_closed(),
+            None => self
+                .write_stream_option
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map(WriteStream::is_closed)
+                .unwrap_or(true),
+        }
+    }
+
+    /// Sets the clock used to set the ingestion time of messages. Defaults to the wallclock.
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
     }
 
     /// Sends data on the stream.
     ///
+    /// Sets the ingestion time of data messages which do not have one to the current time of the
+    /// stream's clock, in milliseconds since the UNIX epoch.
+    ///
     /// # Arguments
     /// * `msg` - The message to be sent on the stream.
-    pub fn send(&mut self, msg: Message<D>) -> Result<(), WriteStreamError> {
+    pub fn send(&mut self, mut msg: Message<D>) -> Result<(), WriteStreamError> {
+        if let Message::TimestampedData(td) = &mut msg {
+            if td.ingestion_time.is_none() {
+                let now = self.clock.now().as_millis() as u64;
+                td.ingestion_time = Some(Timestamp::new(vec![now]));
+            }
+        }
+        self.send_unstamped(msg)
+    }
+
+    /// Sends the message without setting its ingestion time.
+    pub(crate) fn send_unstamped(&mut self, msg: Message<D>) -> Result<(), WriteStreamError> {
         if !self.is_closed() {
             loop {
-                {
-                    if let Some(write_stream) = self.write_stream_option.lock().unwrap().as_mut() {
-                        let res = write_stream.send(msg);
-                        return res;
-                    }
+                if self.write_stream.is_none() {
+                    self.write_stream = self.write_stream_option.lock().unwrap().take();
+                }
+                if let Some(write_stream) = self.write_stream.as_mut() {
+                    return write_stream.send(msg);
                 }
                 thread::sleep(Duration::from_millis(100));
             }
@@ -185,6 +233,54 @@ where
     }
 }
 
+impl<D> Sink<Message<D>> for IngestStream<D>
+where
+    for<'a> D: Data + Deserialize<'a>,
+{
+    type Error = WriteStreamError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let ingest_stream = self.get_mut();
+        if ingest_stream.write_stream.is_none() {
+            // Register the waker before taking the write stream to avoid missing the setup.
+            ingest_stream
+                .setup_waker
+                .lock()
+                .unwrap()
+                .replace(cx.waker().clone());
+            ingest_stream.write_stream = ingest_stream.write_stream_option.lock().unwrap().take();
+        }
+        match &ingest_stream.write_stream {
+            Some(write_stream) if write_stream.is_closed() => {
+                Poll::Ready(Err(WriteStreamError::Closed))
+            }
+            Some(_) => Poll::Ready(Ok(())),
+            None => Poll::Pending,
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: Message<D>) -> Result<(), Self::Error> {
+        self.get_mut().send(msg)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // Messages are handed to the channels when they are sent.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.as_mut().poll_ready(cx) {
+            Poll::Ready(Ok(())) => Poll::Ready(
+                self.get_mut()
+                    .send(Message::new_watermark(Timestamp::top())),
+            ),
+            // The stream is already closed.
+            Poll::Ready(Err(WriteStreamError::Closed)) => Poll::Ready(Ok(())),
+            result => result,
+        }
+    }
+}
+
 impl<D> WriteStreamT<D> for IngestStream<D>
 where
     for<'a> D: Data + Deserialize<'a>,
@@ -1,9 +1,23 @@
-use std::{cell::RefCell, collections::HashSet, rc::Rc, sync::Arc};
+use std::{
+    cell::RefCell,
+    collections::{HashSet, VecDeque},
+    rc::Rc,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use tokio::sync::mpsc;
 
 use crate::{
-    communication::{RecvEndpoint, TryRecvError},
-    dataflow::{Data, Message, State, Timestamp},
-    node::operator_event::OperatorEvent,
+    communication::{CommunicationError, RecvEndpoint, TryRecvError},
+    dataflow::{CallbackResult, Data, Message, OperatorError, State, Timestamp},
+    node::{
+        latency::{self, LatencyProbe},
+        operator_event::OperatorEvent,
+        provenance::Origin,
+        NodeContext,
+    },
 };
 
 use super::{
@@ -21,12 +35,22 @@ pub struct InternalReadStream<D: Data> {
     closed: bool,
     /// The endpoint on which the stream receives data.
     recv_endpoint: Option<RecvEndpoint<Arc<Message<D>>>>,
+    /// The state of the node which reads from the endpoint, on which the callbacks of the
+    /// messages are counted and traced.
+    context: Option<Arc<NodeContext>>,
+    /// Messages received on the endpoint which were not read yet, e.g. because they were peeked.
+    buffer: VecDeque<Arc<Message<D>>>,
+    /// Sends the callbacks of the messages received with [`poll_recv`](Self::poll_recv) to the
+    /// operator executor.
+    pulled_events_tx: Option<mpsc::UnboundedSender<Vec<OperatorEvent>>>,
     /// Vector of stream bundles that must be invoked when this stream receives a message.
     children: Vec<Rc<RefCell<dyn EventMakerT<EventDataType = D>>>>,
     /// A vector on callbacks registered on the stream.
-    callbacks: Vec<Arc<dyn Fn(&Timestamp, &D)>>,
+    callbacks: Vec<Arc<dyn Fn(&Timestamp, &D) -> Result<(), OperatorError>>>,
     /// A vector of watermark callbacks registered on the stream.
-    watermark_cbs: Vec<Arc<dyn Fn(&Timestamp)>>,
+    watermark_cbs: Vec<Arc<dyn Fn(&Timestamp) -> Result<(), OperatorError>>>,
+    /// A vector of callbacks invoked for backfill messages.
+    backfill_cbs: Vec<Arc<dyn Fn(&Timestamp, &D) -> Result<(), OperatorError>>>,
 }
 
 impl<D: Data> InternalReadStream<D> {
@@ -38,9 +62,13 @@ impl<D: Data> InternalReadStream<D> {
             name: id.to_string(),
             closed: false,
             recv_endpoint: None,
+            context: None,
+            buffer: VecDeque::new(),
+            pulled_events_tx: None,
             children: Vec::new(),
             callbacks: Vec::new(),
             watermark_cbs: Vec::new(),
+            backfill_cbs: Vec::new(),
         }
     }
 
@@ -50,9 +78,13 @@ impl<D: Data> InternalReadStream<D> {
             name: name.to_string(),
             closed: false,
             recv_endpoint: None,
+            context: None,
+            buffer: VecDeque::new(),
+            pulled_events_tx: None,
             children: Vec::new(),
             callbacks: Vec::new(),
             watermark_cbs: Vec::new(),
+            backfill_cbs: Vec::new(),
         }
     }
 
@@ -73,22 +105,50 @@ impl<D: Data> InternalReadStream<D> {
             id,
             name: id.to_string(),
             closed: false,
+            context: Some(Arc::clone(recv_endpoint.context())),
             recv_endpoint: Some(recv_endpoint),
+            buffer: VecDeque::new(),
+            pulled_events_tx: None,
             children: Vec::new(),
             callbacks: Vec::new(),
             watermark_cbs: Vec::new(),
+            backfill_cbs: Vec::new(),
         }
     }
 
     /// Add a callback to be invoked when the stream receives a message.
-    pub fn add_callback<F: 'static + Fn(&Timestamp, &D)>(&mut self, callback: F) {
-        self.callbacks.push(Arc::new(callback));
+    pub fn add_callback<F, R>(&mut self, callback: F)
+    where
+        F: 'static + Fn(&Timestamp, &D) -> R,
+        R: CallbackResult,
+    {
+        self.callbacks
+            .push(Arc::new(move |t: &Timestamp, data: &D| {
+                callback(t, data).into_result()
+            }));
     }
 
     /// Add a callback to be invoked after the stream received, and the operator
     /// processed all the messages with a timestamp.
-    pub fn add_watermark_callback<F: 'static + Fn(&Timestamp)>(&mut self, callback: F) {
-        self.watermark_cbs.push(Arc::new(callback));
+    pub fn add_watermark_callback<F, R>(&mut self, callback: F)
+    where
+        F: 'static + Fn(&Timestamp) -> R,
+        R: CallbackResult,
+    {
+        self.watermark_cbs
+            .push(Arc::new(move |t: &Timestamp| callback(t).into_result()));
+    }
+
+    /// Add a callback to be invoked when the stream receives a backfill message.
+    pub fn add_backfill_callback<F, R>(&mut self, callback: F)
+    where
+        F: 'static + Fn(&Timestamp, &D) -> R,
+        R: CallbackResult,
+    {
+        self.backfill_cbs
+            .push(Arc::new(move |t: &Timestamp, data: &D| {
+                callback(t, data).into_result()
+            }));
     }
 
     /// Returns a new instance of the stream with state associated to it.
@@ -111,34 +171,180 @@ impl<D: Data> InternalReadStream<D> {
     /// Returns an immutable reference, or `None` if no messages are
     /// available at the moment (i.e., non-blocking read).
     pub fn try_read(&mut self) -> Result<Message<D>, TryReadError> {
+        self.try_read_arc().map(into_message)
+    }
+
+    /// Tries to read a message from a channel without copying it.
+    pub fn try_read_arc(&mut self) -> Result<Arc<Message<D>>, TryReadError> {
         if self.closed {
             return Err(TryReadError::Closed);
         }
-        let result = self
-            .recv_endpoint
+        let result = match self.buffer.pop_front() {
+            Some(msg) => Ok(msg),
+            None => self.try_read_endpoint(),
+        };
+        self.close_on_top_watermark(result)
+    }
+
+    /// Returns a copy of the next message without reading it.
+    pub fn try_peek(&mut self) -> Result<Message<D>, TryReadError> {
+        if self.closed {
+            return Err(TryReadError::Closed);
+        }
+        if self.buffer.is_empty() {
+            let msg = self.try_read_endpoint()?;
+            self.buffer.push_back(msg);
+        }
+        Ok(Message::clone(self.buffer.front().unwrap()))
+    }
+
+    /// Discards the available messages up to the most recent data message, and returns it.
+    ///
+    /// The highest watermark received before the returned message and the watermarks received
+    /// after it remain to be read, so that no watermark is lost. Returns
+    /// [`Empty`](TryReadError::Empty) without discarding messages if no data message is
+    /// available.
+    pub fn skip_to_latest(&mut self) -> Result<Message<D>, TryReadError> {
+        if self.closed {
+            return Err(TryReadError::Closed);
+        }
+        self.buffer_available()?;
+        let latest = self
+            .buffer
+            .iter()
+            .rposition(|msg| msg.data().is_some())
+            .ok_or(TryReadError::Empty)?;
+        // Watermarks are received in increasing order.
+        let skipped_watermark = self
+            .buffer
+            .drain(..latest)
+            .filter(|msg| msg.data().is_none())
+            .last();
+        let msg = self.buffer.pop_front().unwrap();
+        if let Some(watermark) = skipped_watermark {
+            self.buffer.push_front(watermark);
+        }
+        Ok(into_message(msg))
+    }
+
+    /// Reads the available messages whose timestamp is lower than `timestamp`.
+    ///
+    /// The first message with a later timestamp remains to be read.
+    pub fn drain_until(&mut self, timestamp: &Timestamp) -> Vec<Message<D>> {
+        if self.closed {
+            return Vec::new();
+        }
+        // Messages which were received before the error are still drained.
+        let _ = self.buffer_available();
+        let num_drained = self
+            .buffer
+            .iter()
+            .position(|msg| msg.timestamp() >= timestamp)
+            .unwrap_or_else(|| self.buffer.len());
+        self.buffer.drain(..num_drained).map(into_message).collect()
+    }
+
+    /// Polls for the next message, and sends the callbacks it invokes to the operator executor.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<Message<D>, ReadError>> {
+        self.poll_recv_arc(cx).map_ok(into_message)
+    }
+
+    /// Polls for the next message like [`poll_recv`](Self::poll_recv) without copying it.
+    pub fn poll_recv_arc(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Arc<Message<D>>, ReadError>> {
+        if self.closed {
+            return Poll::Ready(Err(ReadError::Closed));
+        }
+        let msg = match self.buffer.pop_front() {
+            Some(msg) => msg,
+            None => match self.recv_endpoint.as_mut() {
+                Some(rx) => match rx.poll_read(cx) {
+                    Poll::Ready(Ok(msg)) => msg,
+                    Poll::Ready(Err(CommunicationError::Disconnected)) => {
+                        return Poll::Ready(Err(ReadError::Disconnected))
+                    }
+                    Poll::Ready(Err(_)) => return Poll::Ready(Err(ReadError::SerializationError)),
+                    Poll::Pending => return Poll::Pending,
+                },
+                None => return Poll::Ready(Err(ReadError::Disconnected)),
+            },
+        };
+        if let Some(tx) = &self.pulled_events_tx {
+            let events = self.make_events(Arc::clone(&msg));
+            if let Some(context) = &self.context {
+                context.counters.events_added(events.len());
+            }
+            // The executor only stops receiving events once the operator completes.
+            tx.send(events).ok();
+        }
+        Poll::Ready(self.close_on_top_watermark(Ok(msg)))
+    }
+
+    pub(crate) fn set_pulled_events_tx(&mut self, tx: mpsc::UnboundedSender<Vec<OperatorEvent>>) {
+        self.pulled_events_tx = Some(tx);
+    }
+
+    /// Moves all messages available on the endpoint to the buffer.
+    ///
+    /// Returns an error if the buffer is empty and no message is available.
+    fn buffer_available(&mut self) -> Result<(), TryReadError> {
+        loop {
+            match self.try_read_endpoint() {
+                Ok(msg) => {
+                    let is_top_watermark = msg.is_top_watermark();
+                    self.buffer.push_back(msg);
+                    // No messages are sent after the top watermark.
+                    if is_top_watermark {
+                        return Ok(());
+                    }
+                }
+                Err(_) if !self.buffer.is_empty() => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn try_read_endpoint(&mut self) -> Result<Arc<Message<D>>, TryReadError> {
+        self.recv_endpoint
             .as_mut()
             .map_or(Err(TryReadError::Disconnected), |rx| {
-                rx.try_read()
-                    .map(|msg| Message::clone(&msg))
-                    .map_err(TryReadError::from)
-            });
+                rx.try_read().map_err(TryReadError::from)
+            })
+    }
+
+    /// Closes the stream once the top watermark is read.
+    fn close_on_top_watermark<E>(
+        &mut self,
+        result: Result<Arc<Message<D>>, E>,
+    ) -> Result<Arc<Message<D>>, E> {
         if result
             .as_ref()
-            .map(Message::is_top_watermark)
+            .map(|msg| msg.is_top_watermark())
             .unwrap_or(false)
         {
             self.closed = true;
             self.recv_endpoint = None;
+            self.buffer.clear();
         }
         result
     }
 
     /// Blocking read which polls the tokio channel.
-    // TODO: make async or find a way to run on tokio.
     pub fn read(&mut self) -> Result<Message<D>, ReadError> {
+        self.read_arc().map(into_message)
+    }
+
+    /// Blocking read which returns the message without copying it.
+    // TODO: make async or find a way to run on tokio.
+    pub fn read_arc(&mut self) -> Result<Arc<Message<D>>, ReadError> {
         if self.closed {
             return Err(ReadError::Closed);
         }
+        if let Some(msg) = self.buffer.pop_front() {
+            return self.close_on_top_watermark(Ok(msg));
+        }
         // Poll for the next message
         let result = self
             .recv_endpoint
@@ -146,7 +352,7 @@ impl<D: Data> InternalReadStream<D> {
             .map_or(Err(ReadError::Disconnected), |rx| loop {
                 match rx.try_read() {
                     Ok(msg) => {
-                        break Ok(Message::clone(&msg));
+                        break Ok(msg);
                     }
                     Err(TryRecvError::Empty) => (),
                     Err(TryRecvError::Disconnected) => {
@@ -157,18 +363,15 @@ impl<D: Data> InternalReadStream<D> {
                     }
                 }
             });
-        if result
-            .as_ref()
-            .map(Message::is_top_watermark)
-            .unwrap_or(false)
-        {
-            self.closed = true;
-            self.recv_endpoint = None;
-        }
-        result
+        self.close_on_top_watermark(result)
     }
 }
 
+/// Returns the message, which is only copied if other readers or callbacks share it.
+fn into_message<D: Data>(msg: Arc<Message<D>>) -> Message<D> {
+    Arc::try_unwrap(msg).unwrap_or_else(|msg| Message::clone(&msg))
+}
+
 impl<D: Data> Default for InternalReadStream<D> {
     fn default() -> Self {
         Self::new()
@@ -185,6 +388,23 @@ impl<D: Data> EventMakerT for InternalReadStream<D> {
     fn make_events(&self, msg: Arc<Message<Self::EventDataType>>) -> Vec<OperatorEvent> {
         let mut events: Vec<OperatorEvent> = Vec::new();
         match msg.as_ref() {
+            // Backfill messages are only processed by the callbacks which opt in, so they do not
+            // corrupt the state of the other callbacks.
+            Message::TimestampedData(td) if td.backfill => {
+                for callback in self.backfill_cbs.iter() {
+                    let callback = Arc::clone(callback);
+                    let msg_arc = Arc::clone(&msg);
+                    events.push(OperatorEvent::new(
+                        msg_arc.timestamp().clone(),
+                        false,
+                        0,
+                        HashSet::with_capacity(0),
+                        HashSet::with_capacity(0),
+                        move || (callback)(msg_arc.timestamp(), msg_arc.data().unwrap()),
+                    ))
+                }
+                return events;
+            }
             Message::TimestampedData(_) => {
                 // Stateless callbacks may run in parallel, so create 1 event for each
                 let stateless_cbs = self.callbacks.clone();
@@ -196,9 +416,7 @@ impl<D: Data> EventMakerT for InternalReadStream<D> {
                         0,
                         HashSet::with_capacity(0),
                         HashSet::with_capacity(0),
-                        move || {
-                            (callback)(msg_arc.timestamp(), msg_arc.data().unwrap());
-                        },
+                        move || (callback)(msg_arc.timestamp(), msg_arc.data().unwrap()),
                     ))
                 }
             }
@@ -222,6 +440,27 @@ impl<D: Data> EventMakerT for InternalReadStream<D> {
         for child in self.children.iter() {
             events.append(&mut child.borrow_mut().make_events(msg.clone()));
         }
+        if latency::is_tracked(self.id) {
+            let read_at = Instant::now();
+            for event in events.iter_mut().filter(|e| !e.is_watermark_callback) {
+                event.latency_probe = Some(LatencyProbe {
+                    stream_id: self.id,
+                    read_at,
+                });
+            }
+        }
+        if self
+            .context
+            .as_ref()
+            .map_or(false, |context| context.provenance.is_tracked())
+        {
+            for event in events.iter_mut() {
+                event.provenance = Some(Origin {
+                    stream_id: self.id,
+                    timestamp: event.timestamp.clone(),
+                });
+            }
+        }
         events
     }
 }
@@ -4,7 +4,7 @@ use crate::{
     dataflow::{
         callback_builder::MultiStreamEventMaker,
         state::{AccessContext, ManagedState},
-        Data, Message, State, Timestamp,
+        CallbackResult, Data, Message, OperatorError, State, Timestamp,
     },
     node::operator_event::OperatorEvent,
     Uuid,
@@ -22,9 +22,12 @@ pub struct InternalStatefulReadStream<D: Data, S: State> {
     state: Arc<S>,
     state_id: Uuid,
     /// Callbacks registered on the stream.
-    callbacks: Vec<Arc<dyn Fn(&Timestamp, &D, &mut S)>>,
+    callbacks: Vec<Arc<dyn Fn(&Timestamp, &D, &mut S) -> Result<(), OperatorError>>>,
     /// Watermark callbacks registered on the stream.
-    watermark_cbs: Vec<(Arc<dyn Fn(&Timestamp, &mut S)>, i8)>,
+    watermark_cbs: Vec<(
+        Arc<dyn Fn(&Timestamp, &mut S) -> Result<(), OperatorError>>,
+        i8,
+    )>,
     /// Vector of stream bundles that must be invoked when this stream receives a message.
     children: RefCell<Vec<Rc<RefCell<dyn MultiStreamEventMaker>>>>,
 }
@@ -48,24 +51,38 @@ impl<D: Data, S: State> InternalStatefulReadStream<D, S> {
     /// Add a callback to be invoked when the stream receives a message.
     /// The callback will be invoked for each message, and will receive the
     /// message and the stream's state as arguments.
-    pub fn add_callback<F: 'static + Fn(&Timestamp, &D, &mut S)>(&mut self, callback: F) {
-        self.callbacks.push(Arc::new(callback));
+    pub fn add_callback<F, R>(&mut self, callback: F)
+    where
+        F: 'static + Fn(&Timestamp, &D, &mut S) -> R,
+        R: CallbackResult,
+    {
+        self.callbacks
+            .push(Arc::new(move |t: &Timestamp, data: &D, state: &mut S| {
+                callback(t, data, state).into_result()
+            }));
     }
 
     /// Add a callback to be invoked after the stream received, and the operator
     /// processed all the messages with a timestamp.
-    pub fn add_watermark_callback<F: 'static + Fn(&Timestamp, &mut S)>(&mut self, callback: F) {
+    pub fn add_watermark_callback<F, R>(&mut self, callback: F)
+    where
+        F: 'static + Fn(&Timestamp, &mut S) -> R,
+        R: CallbackResult,
+    {
         self.add_watermark_callback_with_priority(callback, 0);
     }
 
     /// Add a callback to be invoked after the stream received, and the operator
     /// processed all the messages with a timestamp.
-    pub(crate) fn add_watermark_callback_with_priority<F: 'static + Fn(&Timestamp, &mut S)>(
-        &mut self,
-        callback: F,
-        priority: i8,
-    ) {
-        self.watermark_cbs.push((Arc::new(callback), priority));
+    pub(crate) fn add_watermark_callback_with_priority<F, R>(&mut self, callback: F, priority: i8)
+    where
+        F: 'static + Fn(&Timestamp, &mut S) -> R,
+        R: CallbackResult,
+    {
+        self.watermark_cbs.push((
+            Arc::new(move |t: &Timestamp, state: &mut S| callback(t, state).into_result()),
+            priority,
+        ));
     }
 
     /// Gets a reference to the stream state.
@@ -33,8 +33,12 @@ where
         LoopStream::new_internal(id, id.to_string())
     }
 
+    /// Panics if the ID derived from the name collides with the ID of another stream.
     pub fn new_with_name(name: &str) -> Self {
-        LoopStream::new_internal(StreamId::new_deterministic(), name.to_string())
+        let id = StreamId::new_named(name)
+            .unwrap_or_else(|e| panic!("Unable to create the stream {}: {}", name, e));
+        id.set_name(name);
+        LoopStream::new_internal(id, name.to_string())
     }
 
     fn new_internal(id: StreamId, name: String) -> Self {
@@ -23,6 +23,8 @@ use crate::{
 };
 
 // Private submodules
+mod blob_stream;
+mod channel_stream;
 mod extract_stream;
 mod ingest_stream;
 mod internal_read_stream;
@@ -30,15 +32,26 @@ mod internal_stateful_read_stream;
 mod loop_stream;
 mod read_stream;
 mod stateful_read_stream;
+mod tick_stream;
+mod transaction;
 mod write_stream;
 
 // Public submodules
 pub mod errors;
 
+// Crate-wide visible submodules
+pub(crate) mod demand;
+pub(crate) mod endpoint_lists;
+
 // Private imports
 use errors::WriteStreamError;
 
 // Public exports
+pub use blob_stream::{
+    BlobChunk, BlobPayload, BlobReadStream, BlobReader, BlobWriteStream, BlobWriter,
+};
+pub use channel_stream::{skip_lagged, stream_from_channel, stream_to_channel};
+pub use demand::DemandSubscription;
 pub use extract_stream::ExtractStream;
 pub use ingest_stream::IngestStream;
 #[doc(hidden)]
@@ -48,9 +61,11 @@ pub use internal_stateful_read_stream::InternalStatefulReadStream;
 pub use loop_stream::LoopStream;
 pub use read_stream::ReadStream;
 pub use stateful_read_stream::StatefulReadStream;
+pub use tick_stream::{TickReadStream, TickWriteStream, Ticker};
+pub use transaction::Transaction;
 pub use write_stream::WriteStream;
 
-pub type StreamId = crate::Uuid;
+pub use crate::ids::StreamId;
 
 pub(crate) trait EventMakerT {
     type EventDataType: Data;
@@ -71,8 +86,8 @@ pub trait WriteStreamT<D: Data> {
 
 #[cfg(test)]
 mod tests {
-    use super::{WriteStream, WriteStreamT};
-    use crate::communication::SendEndpoint;
+    use super::{errors::TryReadError, InternalReadStream, ReadStream, WriteStream, WriteStreamT};
+    use crate::communication::{RecvEndpoint, SendEndpoint};
     use crate::dataflow::{message::TimestampedData, stream::StreamId, Message, Timestamp};
     use std::thread;
     use tokio::runtime::{Builder, Runtime};
@@ -115,7 +130,7 @@ mod tests {
     fn test_write_stream_send() {
         let mut rt = make_default_runtime();
         let (tx, mut rx) = mpsc::unbounded_channel();
-        let endpoints = vec![SendEndpoint::InterThread(tx)];
+        let endpoints = vec![SendEndpoint::InterThread(tx, Default::default())];
         let mut ws: WriteStream<usize> =
             WriteStream::from_endpoints(endpoints, StreamId::new_deterministic());
         thread::spawn(move || {
@@ -150,7 +165,7 @@ mod tests {
     fn test_write_stream_watermark() {
         let mut rt = make_default_runtime();
         let (tx, mut rx) = mpsc::unbounded_channel();
-        let endpoints = vec![SendEndpoint::InterThread(tx)];
+        let endpoints = vec![SendEndpoint::InterThread(tx, Default::default())];
         let mut ws: WriteStream<usize> =
             WriteStream::from_endpoints(endpoints, StreamId::new_deterministic());
         thread::spawn(move || {
@@ -179,11 +194,62 @@ mod tests {
         }
     }
 
+    /// Test that peeked messages are read next, that the backlog is skipped up to its freshest
+    /// message and keeps the highest skipped watermark, and that it is drained up to a timestamp.
+    #[test]
+    fn test_read_stream_peek_skip_drain() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let stream: ReadStream<usize> = ReadStream::from(InternalReadStream::from_endpoint(
+            RecvEndpoint::InterThread(rx, Default::default()),
+            StreamId::new_deterministic(),
+        ));
+        let send = |msg: Message<usize>| tx.send(std::sync::Arc::new(msg)).unwrap();
+        let time = |t: u64| Timestamp::new(vec![t]);
+        assert_eq!(stream.try_peek().err(), Some(TryReadError::Empty));
+
+        send(Message::new_message(time(1), 1));
+        assert_eq!(stream.try_peek().unwrap().data(), Some(&1));
+        assert_eq!(stream.try_read().unwrap().data(), Some(&1));
+
+        send(Message::new_watermark(time(1)));
+        send(Message::new_message(time(2), 2));
+        send(Message::new_watermark(time(2)));
+        send(Message::new_message(time(3), 3));
+        send(Message::new_watermark(time(3)));
+        assert_eq!(stream.skip_to_latest().unwrap().data(), Some(&3));
+        assert_eq!(stream.skip_to_latest().err(), Some(TryReadError::Empty));
+        assert_eq!(stream.try_read().unwrap(), Message::new_watermark(time(2)));
+        assert_eq!(stream.try_read().unwrap(), Message::new_watermark(time(3)));
+
+        for t in 4..7 {
+            send(Message::new_message(time(t), t as usize));
+        }
+        let drained = stream.drain_until(&time(6));
+        assert_eq!(drained.len(), 2);
+        assert_eq!(stream.try_read().unwrap().data(), Some(&6));
+    }
+
+    /// Test that messages read with `read_arc` are the messages sent, not copies.
+    #[test]
+    fn test_read_stream_read_arc() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let stream: ReadStream<Vec<u8>> = ReadStream::from(InternalReadStream::from_endpoint(
+            RecvEndpoint::InterThread(rx, Default::default()),
+            StreamId::new_deterministic(),
+        ));
+        let msg = std::sync::Arc::new(Message::new_message(Timestamp::new(vec![1]), vec![0; 1024]));
+        tx.send(std::sync::Arc::clone(&msg)).unwrap();
+        assert!(std::sync::Arc::ptr_eq(&stream.read_arc().unwrap(), &msg));
+
+        tx.send(std::sync::Arc::clone(&msg)).unwrap();
+        assert_eq!(stream.read().unwrap(), *msg);
+    }
+
     // Test that sends watermarks out of order. It expects that an error is raised.
     #[test]
     fn test_write_stream_out_of_order_watermark() -> Result<(), String> {
         let (tx, _rx) = mpsc::unbounded_channel();
-        let endpoints = vec![SendEndpoint::InterThread(tx)];
+        let endpoints = vec![SendEndpoint::InterThread(tx, Default::default())];
         let mut ws: WriteStream<usize> =
             WriteStream::from_endpoints(endpoints, StreamId::new_deterministic());
         let w1 = Message::Watermark(Timestamp::new(vec![2]));
@@ -202,7 +268,7 @@ mod tests {
     #[test]
     fn test_write_stream_invalid_send() -> Result<(), String> {
         let (tx, _rx) = mpsc::unbounded_channel();
-        let endpoints = vec![SendEndpoint::InterThread(tx)];
+        let endpoints = vec![SendEndpoint::InterThread(tx, Default::default())];
         let mut ws: WriteStream<usize> =
             WriteStream::from_endpoints(endpoints, StreamId::new_deterministic());
         let w1 = Message::Watermark(Timestamp::new(vec![2]));
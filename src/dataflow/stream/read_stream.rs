@@ -1,12 +1,19 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::Arc,
+    task::{Context, Poll},
+};
 
+use futures::future;
 use serde::Deserialize;
 
-use crate::dataflow::{Data, Message, State, Timestamp};
+use crate::dataflow::{CallbackResult, Data, Message, OperatorContext, State, Timestamp};
 
 use super::{
     errors::{ReadError, TryReadError},
-    IngestStream, InternalReadStream, LoopStream, StatefulReadStream, StreamId, WriteStream,
+    DemandSubscription, IngestStream, InternalReadStream, LoopStream, StatefulReadStream, StreamId,
+    WriteStream,
 };
 
 /// A [`ReadStream`] allows operators to read data from a corresponding [`WriteStream`].
@@ -118,9 +125,16 @@ impl<D: Data> ReadStream<D> {
     /// [`TimestampedData`](crate::dataflow::message::Message::TimestampedData) message on the
     /// stream.
     ///
+    /// The callback may return `()` or `Result<(), OperatorError>`; errors are handled
+    /// according to the operator's [`ErrorPolicy`](crate::dataflow::ErrorPolicy).
+    ///
     /// # Arguments
     /// * callback - The callback to be invoked when a message is received.
-    pub fn add_callback<F: 'static + Fn(&Timestamp, &D)>(&self, callback: F) {
+    pub fn add_callback<F, R>(&self, callback: F)
+    where
+        F: 'static + Fn(&Timestamp, &D) -> R,
+        R: CallbackResult,
+    {
         slog::debug!(
             crate::TERMINAL_LOGGER,
             "Registering a message callback on the ReadStream {} (ID: {})",
@@ -130,13 +144,41 @@ impl<D: Data> ReadStream<D> {
         self.internal_stream.borrow_mut().add_callback(callback);
     }
 
+    /// Adds a callback to be invoked when the stream receives a
+    /// [backfill](crate::dataflow::Message::new_backfill) message.
+    ///
+    /// Backfill messages carry historical data with timestamps that may be lower than the low
+    /// watermark, and are not passed to the other callbacks of the stream.
+    ///
+    /// # Arguments
+    /// * callback - The callback to be invoked when a backfill message is received.
+    pub fn add_backfill_callback<F, R>(&self, callback: F)
+    where
+        F: 'static + Fn(&Timestamp, &D) -> R,
+        R: CallbackResult,
+    {
+        slog::debug!(
+            crate::TERMINAL_LOGGER,
+            "Registering a backfill callback on the ReadStream {} (ID: {})",
+            self.get_name(),
+            self.get_id()
+        );
+        self.internal_stream
+            .borrow_mut()
+            .add_backfill_callback(callback);
+    }
+
     /// Request a callback on the receipt of a
     /// [`Watermark`](crate::dataflow::message::Message::Watermark) message on the
     /// stream.
     ///
     /// # Arguments
     /// * callback - The callback to be invoked when a watermark is received.
-    pub fn add_watermark_callback<F: 'static + Fn(&Timestamp)>(&self, callback: F) {
+    pub fn add_watermark_callback<F, R>(&self, callback: F)
+    where
+        F: 'static + Fn(&Timestamp) -> R,
+        R: CallbackResult,
+    {
         slog::debug!(
             crate::TERMINAL_LOGGER,
             "Registering a watermark callback on the ReadStream {} (ID: {})",
@@ -148,6 +190,49 @@ impl<D: Data> ReadStream<D> {
             .add_watermark_callback(callback);
     }
 
+    /// Request a callback on the receipt of a
+    /// [`TimestampedData`](crate::dataflow::message::Message::TimestampedData) message on the
+    /// stream, which is handed the [`OperatorContext`].
+    ///
+    /// The context [tracks the watermarks](OperatorContext::track_watermarks) of the stream.
+    ///
+    /// # Arguments
+    /// * ctx - The context of the operator.
+    /// * callback - The callback to be invoked when a message is received.
+    pub fn add_callback_with_context<T, F, R>(&self, ctx: &OperatorContext<T>, callback: F)
+    where
+        T: 'static + Clone,
+        F: 'static + Fn(&OperatorContext<T>, &Timestamp, &D) -> R,
+        R: CallbackResult,
+    {
+        ctx.track_watermarks(self);
+        let ctx = ctx.clone();
+        self.add_callback(move |t: &Timestamp, data: &D| callback(&ctx, t, data));
+    }
+
+    /// Request a callback on the receipt of a
+    /// [`Watermark`](crate::dataflow::message::Message::Watermark) message on the stream, which
+    /// is handed the [`OperatorContext`].
+    ///
+    /// The context [tracks the watermarks](OperatorContext::track_watermarks) of the stream.
+    ///
+    /// # Arguments
+    /// * ctx - The context of the operator.
+    /// * callback - The callback to be invoked when a watermark is received.
+    pub fn add_watermark_callback_with_context<T, F, R>(
+        &self,
+        ctx: &OperatorContext<T>,
+        callback: F,
+    ) where
+        T: 'static + Clone,
+        F: 'static + Fn(&OperatorContext<T>, &Timestamp) -> R,
+        R: CallbackResult,
+    {
+        ctx.track_watermarks(self);
+        let ctx = ctx.clone();
+        self.add_watermark_callback(move |t: &Timestamp| callback(&ctx, t));
+    }
+
     /// Attaches state to the [`ReadStream`] and returns a [`StatefulReadStream`].
     ///
     /// In order to access the registered state in the callbacks, register callbacks on the
@@ -170,6 +255,13 @@ impl<D: Data> ReadStream<D> {
         self.internal_stream.borrow().get_id()
     }
 
+    /// Signals demand for the messages of the stream until the returned subscription is dropped.
+    ///
+    /// Sources which produce on demand check [`WriteStream::has_demand`].
+    pub fn subscribe(&self) -> DemandSubscription {
+        DemandSubscription::new(self.get_id())
+    }
+
     /// Get the name of the stream.
     /// Returns a [`str`] version of the ID if the stream was not constructed with
     /// [`new_with_name`](ReadStream::new_with_name).
@@ -196,6 +288,62 @@ impl<D: Data> ReadStream<D> {
     pub fn read(&self) -> Result<Message<D>, ReadError> {
         self.internal_stream.borrow_mut().read()
     }
+
+    /// Blocking read from the [`ReadStream`] which returns the message without copying it.
+    ///
+    /// Messages sent by operators on the same node are shared with the sending operator, so
+    /// large payloads, e.g. images or point clouds, are never copied. [`read`](Self::read) only
+    /// copies the message if other operators or callbacks also hold it.
+    pub fn read_arc(&self) -> Result<Arc<Message<D>>, ReadError> {
+        self.internal_stream.borrow_mut().read_arc()
+    }
+
+    /// Asynchronous read from the [`ReadStream`], e.g. in the pull loop of an operator
+    /// ([`Operator::run_async`](crate::dataflow::Operator::run_async)).
+    ///
+    /// Returns the next Message available on the [`ReadStream`]. The callbacks registered on
+    /// the stream are invoked for the message.
+    pub async fn recv(&self) -> Result<Message<D>, ReadError> {
+        future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Asynchronous read from the [`ReadStream`] which returns the message without copying it,
+    /// like [`read_arc`](Self::read_arc).
+    pub async fn recv_arc(&self) -> Result<Arc<Message<D>>, ReadError> {
+        future::poll_fn(|cx| self.internal_stream.borrow_mut().poll_recv_arc(cx)).await
+    }
+
+    /// Polls for the next message, and registers the task to be woken up if none is available.
+    pub(crate) fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Result<Message<D>, ReadError>> {
+        self.internal_stream.borrow_mut().poll_recv(cx)
+    }
+
+    /// Non-blocking peek at the [`ReadStream`].
+    ///
+    /// Returns a copy of the next message without reading it, or an
+    /// [`Empty`](TryReadError::Empty) if no message is available.
+    pub fn try_peek(&self) -> Result<Message<D>, TryReadError> {
+        self.internal_stream.borrow_mut().try_peek()
+    }
+
+    /// Discards the backlog of the [`ReadStream`] and returns the freshest data message, e.g.
+    /// for consumers which prefer the latest sensor reading over processing every reading.
+    ///
+    /// Only data messages are discarded: the highest watermark skipped and the watermarks
+    /// received after the returned message remain to be read. Returns an
+    /// [`Empty`](TryReadError::Empty) without discarding messages if no data message is
+    /// available.
+    pub fn skip_to_latest(&self) -> Result<Message<D>, TryReadError> {
+        self.internal_stream.borrow_mut().skip_to_latest()
+    }
+
+    /// Reads the available messages with a timestamp lower than `timestamp`, e.g. to catch up
+    /// with the other inputs of an operator.
+    ///
+    /// The first message with a later timestamp remains to be read.
+    pub fn drain_until(&self, timestamp: &Timestamp) -> Vec<Message<D>> {
+        self.internal_stream.borrow_mut().drain_until(timestamp)
+    }
 }
 
 impl<D: Data> From<&ReadStream<D>> for ReadStream<D> {
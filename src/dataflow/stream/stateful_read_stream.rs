@@ -3,7 +3,7 @@ use std::{cell::RefCell, rc::Rc, sync::Arc};
 use crate::{
     dataflow::{
         callback_builder::{OneReadOneWrite, TwoReadZeroWrite},
-        Data, State, Timestamp,
+        CallbackResult, Data, State, Timestamp,
     },
     Uuid,
 };
@@ -25,13 +25,21 @@ impl<D: Data, T: State> StatefulReadStream<D, T> {
     /// Add a callback to be invoked when the stream receives a message.
     /// The callback will be invoked for each message, and will receive the
     /// message and the stream's state as arguments.
-    pub fn add_callback<F: 'static + Fn(&Timestamp, &D, &mut T)>(&self, callback: F) {
+    pub fn add_callback<F, R>(&self, callback: F)
+    where
+        F: 'static + Fn(&Timestamp, &D, &mut T) -> R,
+        R: CallbackResult,
+    {
         self.internal_stream.borrow_mut().add_callback(callback);
     }
 
     /// Add a callback to be invoked after the stream received, and the operator
     /// processed all the messages with a timestamp.
-    pub fn add_watermark_callback<F: 'static + Fn(&Timestamp, &mut T)>(&self, callback: F) {
+    pub fn add_watermark_callback<F, R>(&self, callback: F)
+    where
+        F: 'static + Fn(&Timestamp, &mut T) -> R,
+        R: CallbackResult,
+    {
         self.internal_stream
             .borrow_mut()
             .add_watermark_callback(callback);
@@ -40,11 +48,11 @@ impl<D: Data, T: State> StatefulReadStream<D, T> {
     /// Add a callback to be invoked after the stream received, and the operator
     /// processed all the messages with a timestamp.
     #[allow(unused)]
-    pub(crate) fn add_watermark_callback_with_priority<F: 'static + Fn(&Timestamp, &mut T)>(
-        &self,
-        callback: F,
-        priority: i8,
-    ) {
+    pub(crate) fn add_watermark_callback_with_priority<F, R>(&self, callback: F, priority: i8)
+    where
+        F: 'static + Fn(&Timestamp, &mut T) -> R,
+        R: CallbackResult,
+    {
         self.internal_stream
             .borrow_mut()
             .add_watermark_callback_with_priority(callback, priority);
@@ -0,0 +1,78 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::dataflow::{Message, Timestamp};
+
+use super::{errors::WriteStreamError, IngestStream, ReadStream, WriteStream, WriteStreamT};
+
+/// A [`WriteStream`] on which operators send ticks, i.e. messages without data, e.g. to trigger
+/// periodic operators or to signal barriers.
+///
+/// Ticks are sent to other nodes in the fixed-layout frames used for watermarks, so sending them
+/// neither serializes nor allocates a message. Ticks which are backfilled or carry an event time
+/// or an ingestion time are serialized like other messages.
+pub type TickWriteStream = WriteStream<()>;
+/// A [`ReadStream`] from which operators receive ticks.
+pub type TickReadStream = ReadStream<()>;
+
+impl WriteStream<()> {
+    /// Sends a tick with the given timestamp.
+    pub fn send_tick(&mut self, timestamp: Timestamp) -> Result<(), WriteStreamError> {
+        self.send(Message::new_message(timestamp, ()))
+    }
+}
+
+impl IngestStream<()> {
+    /// Sends a tick with the given timestamp. Unlike messages sent with
+    /// [`send`](IngestStream::send), ticks carry no ingestion time.
+    pub fn send_tick(&mut self, timestamp: Timestamp) -> Result<(), WriteStreamError> {
+        self.send_unstamped(Message::new_message(timestamp, ()))
+    }
+
+    /// Sends a tick followed by a watermark every `period` from a separate thread, e.g. to drive
+    /// operators which run periodically in their watermark callbacks. The `i`-th tick has the
+    /// timestamp `[i]`.
+    ///
+    /// Ticking stops when the returned [`Ticker`] is dropped or the stream is closed.
+    pub fn tick_every(mut self, period: Duration) -> Ticker {
+        let running = Arc::new(AtomicBool::new(true));
+        let running_copy = Arc::clone(&running);
+        thread::spawn(move || {
+            let mut next_tick = Instant::now();
+            let mut index = 0;
+            while running_copy.load(Ordering::Acquire) {
+                let timestamp = Timestamp::new(vec![index]);
+                if self.send_tick(timestamp.clone()).is_err()
+                    || self.send(Message::new_watermark(timestamp)).is_err()
+                {
+                    return;
+                }
+                index += 1;
+                // Ticks do not drift if sending a tick takes time.
+                next_tick += period;
+                if let Some(delay) = next_tick.checked_duration_since(Instant::now()) {
+                    thread::sleep(delay);
+                }
+            }
+        });
+        Ticker { running }
+    }
+}
+
+/// Sends ticks on an [`IngestStream`] created with [`IngestStream::tick_every`] until it is
+/// dropped.
+pub struct Ticker {
+    running: Arc<AtomicBool>,
+}
+
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Release);
+    }
+}
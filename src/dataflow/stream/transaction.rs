@@ -0,0 +1,216 @@
+use std::{any::Any, collections::HashMap};
+
+use serde::Deserialize;
+
+use crate::dataflow::{Data, Message, Timestamp};
+
+use super::{errors::WriteStreamError, StreamId, WriteStream, WriteStreamT};
+
+/// Messages of a [`Transaction`] buffered for one stream.
+trait PendingMessages: Send {
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    /// Sends the messages, and releases the hold on the watermark of the stream.
+    fn commit(self: Box<Self>, timestamp: &Timestamp) -> Result<(), WriteStreamError>;
+
+    /// Discards the messages, and releases the hold on the watermark of the stream.
+    fn abort(self: Box<Self>, timestamp: &Timestamp);
+}
+
+struct StreamMessages<D: Data> {
+    write_stream: WriteStream<D>,
+    data: Vec<D>,
+}
+
+impl<D> PendingMessages for StreamMessages<D>
+where
+    for<'a> D: Data + Deserialize<'a>,
+{
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn commit(self: Box<Self>, timestamp: &Timestamp) -> Result<(), WriteStreamError> {
+        let StreamMessages {
+            mut write_stream,
+            data,
+        } = *self;
+        let result = data
+            .into_iter()
+            .try_for_each(|data| write_stream.send(Message::new_message(timestamp.clone(), data)));
+        write_stream.release_watermark(timestamp.clone())?;
+        result
+    }
+
+    fn abort(mut self: Box<Self>, timestamp: &Timestamp) {
+        if let Err(e) = self.write_stream.release_watermark(timestamp.clone()) {
+            slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "Unable to release the watermark of stream {} (ID: {}): {:?}",
+                self.write_stream.get_name(),
+                self.write_stream.get_id(),
+                e
+            );
+        }
+    }
+}
+
+/// Sends messages with the same timestamp on several [`WriteStream`]s atomically.
+///
+/// The messages are buffered until the transaction is [committed](Transaction::commit), and
+/// are discarded if the transaction is dropped before, e.g. because the callback failed. Until
+/// then, the watermarks of the streams are [held](WriteStream::hold_watermark) at the timestamp
+/// of the transaction, so that downstream operators such as joins which complete a timestamp
+/// upon receiving its watermark never observe a partial result.
+///
+/// # Example
+/// ```
+/// # use erdos::dataflow::{stream::Transaction, Timestamp, WriteStream};
+/// fn on_callback(t: &Timestamp, left: &WriteStream<u32>, right: &WriteStream<String>) {
+///     let mut transaction = Transaction::new(t.clone());
+///     transaction.send(left, 1).unwrap();
+///     transaction.send(right, "one".to_string()).unwrap();
+///     transaction.commit().unwrap();
+/// }
+/// ```
+pub struct Transaction {
+    timestamp: Timestamp,
+    pending_messages: HashMap<StreamId, Box<dyn PendingMessages>>,
+    /// The order in which the streams were added to the transaction.
+    stream_ids: Vec<StreamId>,
+}
+
+impl Transaction {
+    pub fn new(timestamp: Timestamp) -> Self {
+        Self {
+            timestamp,
+            pending_messages: HashMap::new(),
+            stream_ids: Vec::new(),
+        }
+    }
+
+    /// Returns the timestamp of the messages sent by the transaction.
+    pub fn timestamp(&self) -> &Timestamp {
+        &self.timestamp
+    }
+
+    /// Buffers a message to be sent on the stream when the transaction commits.
+    ///
+    /// Returns an error if the stream is closed, or if it already sent a watermark greater than
+    /// the timestamp of the transaction.
+    pub fn send<D>(
+        &mut self,
+        write_stream: &WriteStream<D>,
+        data: D,
+    ) -> Result<(), WriteStreamError>
+    where
+        for<'a> D: Data + Deserialize<'a>,
+    {
+        let stream_id = write_stream.get_id();
+        if !self.pending_messages.contains_key(&stream_id) {
+            if write_stream.is_closed() {
+                return Err(WriteStreamError::Closed);
+            }
+            write_stream.hold_watermark(self.timestamp.clone())?;
+            self.pending_messages.insert(
+                stream_id,
+                Box::new(StreamMessages {
+                    write_stream: write_stream.clone(),
+                    data: Vec::new(),
+                }),
+            );
+            self.stream_ids.push(stream_id);
+        }
+        self.pending_messages
+            .get_mut(&stream_id)
+            .unwrap()
+            .as_any_mut()
+            .downcast_mut::<StreamMessages<D>>()
+            .expect("Streams with the same ID must have the same type")
+            .data
+            .push(data);
+        Ok(())
+    }
+
+    /// Sends the buffered messages on their streams, and allows the watermarks to advance past
+    /// the timestamp of the transaction.
+    ///
+    /// The messages of a stream are only sent if the messages of all preceding streams were
+    /// sent. Because the watermarks are held, sending only fails if a communication channel
+    /// fails.
+    pub fn commit(mut self) -> Result<(), WriteStreamError> {
+        let mut result = Ok(());
+        for stream_id in std::mem::take(&mut self.stream_ids) {
+            let pending = self.pending_messages.remove(&stream_id).unwrap();
+            if result.is_ok() {
+                result = pending.commit(&self.timestamp);
+            } else {
+                pending.abort(&self.timestamp);
+            }
+        }
+        result
+    }
+}
+
+impl Drop for Transaction {
+    /// Aborts the transaction if it was not committed.
+    fn drop(&mut self) {
+        for stream_id in self.stream_ids.drain(..) {
+            if let Some(pending) = self.pending_messages.remove(&stream_id) {
+                pending.abort(&self.timestamp);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use crate::communication::SendEndpoint;
+
+    use super::*;
+
+    /// Test that messages and watermarks are only sent once the transaction commits.
+    #[test]
+    fn test_transaction() {
+        let (left_tx, mut left_rx) = mpsc::unbounded_channel();
+        let (right_tx, mut right_rx) = mpsc::unbounded_channel();
+        let mut left: WriteStream<u32> = WriteStream::from_endpoints(
+            vec![SendEndpoint::InterThread(left_tx, Default::default())],
+            StreamId::new_deterministic(),
+        );
+        let right: WriteStream<String> = WriteStream::from_endpoints(
+            vec![SendEndpoint::InterThread(right_tx, Default::default())],
+            StreamId::new_deterministic(),
+        );
+        let t1 = Timestamp::new(vec![1]);
+
+        // Aborted transactions do not send messages.
+        let mut transaction = Transaction::new(t1.clone());
+        transaction.send(&left, 1).unwrap();
+        drop(transaction);
+        assert!(left_rx.try_recv().is_err());
+
+        let mut transaction = Transaction::new(t1.clone());
+        transaction.send(&left, 1).unwrap();
+        transaction.send(&right, "one".to_string()).unwrap();
+        left.send(Message::new_watermark(t1.clone())).unwrap();
+        assert!(left_rx.try_recv().is_err());
+        assert!(right_rx.try_recv().is_err());
+
+        transaction.commit().unwrap();
+        assert_eq!(
+            *left_rx.try_recv().unwrap(),
+            Message::new_message(t1.clone(), 1)
+        );
+        assert_eq!(
+            *left_rx.try_recv().unwrap(),
+            Message::new_watermark(t1.clone())
+        );
+        assert_eq!(
+            *right_rx.try_recv().unwrap(),
+            Message::new_message(t1, "one".to_string())
+        );
+    }
+}
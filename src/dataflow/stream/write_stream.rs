@@ -1,13 +1,21 @@
-use std::{fmt, sync::Arc};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
 
 use serde::Deserialize;
 
 use crate::{
     communication::{Pusher, SendEndpoint},
     dataflow::{Data, Message, Timestamp},
+    node::NodeContext,
 };
 
-use super::{errors::WriteStreamError, StreamId, WriteStreamT};
+use super::{demand, endpoint_lists, errors::WriteStreamError, StreamId, WriteStreamT};
 
 // TODO (Sukrit) :: This example needs to be fixed after we enable attaching WriteStreams to
 // callbacks for normal read streams.
@@ -69,10 +77,38 @@ pub struct WriteStream<D: Data> {
     name: String,
     /// Sends message to other operators.
     pusher: Option<Pusher<Arc<Message<D>>>>,
+    /// Loads the endpoints of the stream when the node changes them, if the stream was set up by
+    /// a node.
+    endpoints: Option<endpoint_lists::Handle<D>>,
+    /// The state of the node which set up the stream, on which the sent messages are captured,
+    /// traced, and watched.
+    context: Option<Arc<NodeContext>>,
     /// Current low watermark.
     low_watermark: Timestamp,
     /// Whether the stream is closed.
     stream_closed: bool,
+    /// Holds on the output watermark, shared by the clones of the stream.
+    watermark_holds: Arc<Mutex<WatermarkHolds>>,
+    /// Number of holds, which lets watermarks skip locking the holds if there are none.
+    num_holds: Arc<AtomicUsize>,
+}
+
+/// Timestamps for which an operator still produces output, e.g. from asynchronous work.
+#[derive(Default)]
+struct WatermarkHolds {
+    /// Number of holds on each timestamp.
+    holds: BTreeMap<Timestamp, usize>,
+    /// The largest watermark which was held back.
+    held_watermark: Option<Timestamp>,
+}
+
+impl WatermarkHolds {
+    fn is_held(&self, watermark: &Timestamp) -> bool {
+        self.holds
+            .keys()
+            .next()
+            .map_or(false, |min_hold| min_hold <= watermark)
+    }
 }
 
 impl<D: Data> WriteStream<D> {
@@ -86,8 +122,13 @@ impl<D: Data> WriteStream<D> {
     ///
     /// # Arguments
     /// * `name` - The name to be given to the stream.
+    ///
+    /// Panics if the ID derived from the name collides with the ID of another stream.
     pub fn new_with_name(name: &str) -> Self {
-        WriteStream::new_internal(StreamId::new_deterministic(), name.to_string())
+        let id = StreamId::new_named(name)
+            .unwrap_or_else(|e| panic!("Unable to create the stream {}: {}", name, e));
+        id.set_name(name);
+        WriteStream::new_internal(id, name.to_string())
     }
 
     /// Returns a new instance of the [`WriteStream`].
@@ -110,8 +151,12 @@ impl<D: Data> WriteStream<D> {
             id,
             name,
             pusher: Some(Pusher::new()),
+            endpoints: None,
+            context: None,
             low_watermark: Timestamp::new(vec![0]),
             stream_closed: false,
+            watermark_holds: Arc::new(Mutex::new(WatermarkHolds::default())),
+            num_holds: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -123,6 +168,19 @@ impl<D: Data> WriteStream<D> {
         stream
     }
 
+    /// Sends on the endpoints loaded through the handle, which the stream reloads before sending
+    /// its next message whenever the node changes them.
+    pub(crate) fn with_endpoint_list(mut self, handle: endpoint_lists::Handle<D>) -> Self {
+        self.endpoints = Some(handle);
+        self
+    }
+
+    /// Records the messages sent on the stream in the state of the node which set it up.
+    pub(crate) fn with_context(mut self, context: Arc<NodeContext>) -> Self {
+        self.context = Some(context);
+        self
+    }
+
     /// Get the ID given to the stream by the constructor
     pub fn get_id(&self) -> StreamId {
         self.id
@@ -141,6 +199,22 @@ impl<D: Data> WriteStream<D> {
         self.stream_closed
     }
 
+    /// Returns `true` if a consumer [subscribed](super::ReadStream::subscribe) to the stream.
+    ///
+    /// Sources which feed optional branches of the dataflow (e.g., visualization) can skip
+    /// producing messages while nobody subscribes.
+    pub fn has_demand(&self) -> bool {
+        demand::has_demand(self.id)
+    }
+
+    /// Blocks until a consumer [subscribes](super::ReadStream::subscribe) to the stream.
+    ///
+    /// Intended for the [`run`](crate::dataflow::Operator::run) loop of source operators which
+    /// only produce messages on demand.
+    pub fn wait_for_demand(&self) {
+        demand::wait_for_demand(self.id)
+    }
+
     fn add_endpoint(&mut self, endpoint: SendEndpoint<Arc<Message<D>>>) {
         self.pusher
             .as_mut()
@@ -167,7 +241,7 @@ impl<D: Data> WriteStream<D> {
     fn update_watermark(&mut self, msg: &Message<D>) -> Result<(), WriteStreamError> {
         match msg {
             Message::TimestampedData(td) => {
-                if td.timestamp < self.low_watermark {
+                if td.timestamp < self.low_watermark && !td.backfill {
                     return Err(WriteStreamError::TimestampError);
                 }
             }
@@ -206,6 +280,73 @@ impl<D: Data> fmt::Debug for WriteStream<D> {
     }
 }
 
+impl<'a, D: Data + Deserialize<'a>> WriteStream<D> {
+    /// Holds back watermarks greater than or equal to `timestamp` until the hold is
+    /// [released](WriteStream::release_watermark).
+    ///
+    /// Operators hold the watermark while producing output for `timestamp` asynchronously, e.g.
+    /// from a timer or a background thread, so that downstream operators do not receive the
+    /// output after the watermark. Watermarks sent during the hold, including the watermarks
+    /// flowed by ERDOS, are sent once the hold is released. Holds are shared by the clones of the
+    /// stream.
+    ///
+    /// Returns a [`WriteStreamError::TimestampError`] if the stream already sent a watermark
+    /// greater than `timestamp`.
+    pub fn hold_watermark(&self, timestamp: Timestamp) -> Result<(), WriteStreamError> {
+        if timestamp < self.low_watermark {
+            return Err(WriteStreamError::TimestampError);
+        }
+        let mut watermark_holds = self.watermark_holds.lock().unwrap();
+        *watermark_holds.holds.entry(timestamp).or_insert(0) += 1;
+        self.num_holds.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Releases a hold set with [`hold_watermark`](WriteStream::hold_watermark), and sends the
+    /// largest watermark held back if no other hold is lower than it.
+    ///
+    /// Returns a [`WriteStreamError::TimestampError`] if `timestamp` is not held.
+    pub fn release_watermark(&mut self, timestamp: Timestamp) -> Result<(), WriteStreamError> {
+        let held_watermark = {
+            let mut watermark_holds = self.watermark_holds.lock().unwrap();
+            let count = watermark_holds
+                .holds
+                .get_mut(&timestamp)
+                .ok_or(WriteStreamError::TimestampError)?;
+            *count -= 1;
+            self.num_holds.fetch_sub(1, Ordering::SeqCst);
+            if *count == 0 {
+                watermark_holds.holds.remove(&timestamp);
+            }
+            match watermark_holds.held_watermark.take() {
+                Some(watermark) if watermark_holds.is_held(&watermark) => {
+                    watermark_holds.held_watermark = Some(watermark);
+                    None
+                }
+                held_watermark => held_watermark,
+            }
+        };
+        match held_watermark {
+            Some(watermark) => self.send(Message::new_watermark(watermark)),
+            None => Ok(()),
+        }
+    }
+
+    /// Releases all the holds on the watermark, and sends the top watermark unless the stream is
+    /// closed, e.g. once the operator failed.
+    pub(crate) fn release_holds_and_close(&mut self) -> Result<(), WriteStreamError> {
+        if self.stream_closed {
+            return Ok(());
+        }
+        if self.num_holds.swap(0, Ordering::SeqCst) > 0 {
+            let mut watermark_holds = self.watermark_holds.lock().unwrap();
+            watermark_holds.holds.clear();
+            watermark_holds.held_watermark = None;
+        }
+        self.send(Message::new_watermark(Timestamp::top()))
+    }
+}
+
 impl<'a, D: Data + Deserialize<'a>> WriteStreamT<D> for WriteStream<D> {
     fn send(&mut self, msg: Message<D>) -> Result<(), WriteStreamError> {
         // Check if the stream was closed before, and return an error.
@@ -219,6 +360,25 @@ impl<'a, D: Data + Deserialize<'a>> WriteStreamT<D> for WriteStream<D> {
             return Err(WriteStreamError::Closed);
         }
 
+        // Defer watermarks until the operator releases the holds on lower timestamps. The holds
+        // are only locked if there are any.
+        match &msg {
+            Message::Watermark(watermark) if self.num_holds.load(Ordering::SeqCst) > 0 => {
+                let mut watermark_holds = self.watermark_holds.lock().unwrap();
+                if watermark_holds.is_held(watermark) {
+                    if watermark_holds
+                        .held_watermark
+                        .as_ref()
+                        .map_or(true, |held_watermark| held_watermark < watermark)
+                    {
+                        watermark_holds.held_watermark = Some(watermark.clone());
+                    }
+                    return Ok(());
+                }
+            }
+            _ => (),
+        }
+
         // Close the stream later if the message being sent represents the top watermark.
         let mut close_stream: bool = false;
         if msg.is_top_watermark() {
@@ -233,10 +393,38 @@ impl<'a, D: Data + Deserialize<'a>> WriteStreamT<D> for WriteStream<D> {
 
         // Update the watermark and send the message forward.
         self.update_watermark(&msg)?;
+        if let Some(context) = &self.context {
+            if context.provenance.is_tracked() {
+                let timestamp = match &msg {
+                    Message::TimestampedData(data) => Some(&data.timestamp),
+                    Message::Watermark(_) => None,
+                };
+                context.provenance.record_sent(self.id, timestamp);
+            }
+            if context.captures.is_capturing() {
+                context.captures.message_sent(self.id, &msg);
+            }
+            if context.triggers.is_watching() {
+                context.triggers.message_sent(self.id, &msg);
+            }
+            if context.watermark_progress.is_watching() {
+                if let Message::Watermark(watermark) = &msg {
+                    context
+                        .watermark_progress
+                        .watermark_sent(self.id, watermark);
+                }
+            }
+        }
         let msg_arc = Arc::new(msg);
 
         match self.pusher.as_mut() {
-            Some(pusher) => pusher.send(msg_arc).map_err(WriteStreamError::from)?,
+            // Send on the current endpoints if the node changed them since the last message.
+            Some(pusher) => {
+                if let Some(endpoints) = self.endpoints.as_mut().and_then(|handle| handle.load()) {
+                    pusher.set_endpoints(endpoints.to_vec());
+                }
+                pusher.send(msg_arc).map_err(WriteStreamError::from)?
+            }
             None => {
                 slog::debug!(
                     crate::TERMINAL_LOGGER,
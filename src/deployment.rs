@@ -0,0 +1,188 @@
+//! Files which describe the configuration of every node of a deployment, so that a single binary
+//! runs any node given its index.
+use std::{collections::BTreeMap, fs, io, path::Path, str::FromStr};
+
+use crate::{node::NodeId, Configuration};
+
+/// The options of every node in a deployment, loaded from a file with one `option = value` per
+/// line. Options are named like the command line arguments of [`new_app`](crate::new_app), and
+/// flags are set with the value `true`.
+///
+/// Options before the first section apply to all nodes. The options in a `[node <index>]`
+/// section override them for the node with the index, e.g. its number of threads or transport
+/// options. In node sections, `data-address`, `control-address`, and `labels` set the entries
+/// of the node in `data-addresses`, `control-addresses`, and `node-labels`, so that every node
+/// knows the addresses and labels of all nodes:
+///
+/// ```text
+/// data-addresses = 10.0.0.1:9000,10.0.0.2:9000
+/// control-addresses = 10.0.0.1:9001,10.0.0.2:9001
+/// threads = 4
+///
+/// [node 1]
+/// threads = 16
+/// labels = gpu=true,zone=vehicle
+/// compact-headers = true
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Deployment {
+    options: Vec<(String, String)>,
+    node_options: BTreeMap<NodeId, Vec<(String, String)>>,
+}
+
+impl Deployment {
+    /// Loads the deployment from a file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Returns the command line arguments of the node, which are parsed by
+    /// [`new_app`](crate::new_app).
+    pub fn args(&self, index: NodeId) -> Vec<String> {
+        let mut options: BTreeMap<&str, String> = BTreeMap::new();
+        for (option, value) in self.options.iter() {
+            options.insert(option, value.clone());
+        }
+        // Every node knows the addresses and labels of all nodes.
+        let list = |options: &BTreeMap<&str, String>, option: &str, separator: char| {
+            options.get(option).map_or_else(Vec::new, |value| {
+                value.split(separator).map(String::from).collect::<Vec<_>>()
+            })
+        };
+        let mut data_addresses = list(&options, "data-addresses", ',');
+        let mut control_addresses = list(&options, "control-addresses", ',');
+        let mut node_labels = list(&options, "node-labels", ';');
+        for (&node_id, node_options) in self.node_options.iter() {
+            for (option, value) in node_options.iter() {
+                let entries = match option.as_str() {
+                    "data-address" => &mut data_addresses,
+                    "control-address" => &mut control_addresses,
+                    "labels" => &mut node_labels,
+                    _ => continue,
+                };
+                if entries.len() <= node_id {
+                    entries.resize(node_id + 1, String::new());
+                }
+                entries[node_id] = value.clone();
+            }
+        }
+        for (option, entries, separator) in [
+            ("data-addresses", data_addresses, ","),
+            ("control-addresses", control_addresses, ","),
+            ("node-labels", node_labels, ";"),
+        ]
+        .iter()
+        {
+            if !entries.is_empty() {
+                options.insert(*option, entries.join(*separator));
+            }
+        }
+        for (option, value) in self.node_options.get(&index).into_iter().flatten() {
+            match option.as_str() {
+                "data-address" | "control-address" | "labels" => (),
+                option => {
+                    options.insert(option, value.clone());
+                }
+            }
+        }
+        options.insert("index", index.to_string());
+
+        let mut args = vec!["erdos".to_string()];
+        for (option, value) in options {
+            match value.as_str() {
+                "false" => (),
+                "true" => args.push(format!("--{}", option)),
+                _ => {
+                    args.push(format!("--{}", option));
+                    args.push(value);
+                }
+            }
+        }
+        args
+    }
+
+    /// Returns the configuration of the node, or an error if an option is invalid.
+    #[cfg(feature = "cli")]
+    pub fn configuration(&self, index: NodeId) -> Result<Configuration, String> {
+        let args = crate::new_app("erdos")
+            .get_matches_from_safe(self.args(index))
+            .map_err(|e| e.message)?;
+        Ok(Configuration::from_args(&args))
+    }
+}
+
+impl FromStr for Deployment {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let mut deployment = Self::default();
+        let mut section: Option<NodeId> = None;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("Invalid deployment on line {}: {}", number + 1, line);
+            if line.starts_with('[') && line.ends_with(']') {
+                let header: Vec<&str> = line[1..line.len() - 1].split_whitespace().collect();
+                section = match header.as_slice() {
+                    ["node", index] => Some(index.parse().map_err(|_| invalid())?),
+                    _ => return Err(invalid()),
+                };
+                continue;
+            }
+            let index = line.find('=').ok_or_else(invalid)?;
+            let (option, value) = (line[..index].trim(), line[index + 1..].trim());
+            if option.is_empty() {
+                return Err(invalid());
+            }
+            let options = match section {
+                Some(node_id) => deployment.node_options.entry(node_id).or_default(),
+                None => &mut deployment.options,
+            };
+            options.push((option.to_string(), value.to_string()));
+        }
+        Ok(deployment)
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+    use super::*;
+
+    /// Test that nodes combine the options of all nodes with their own overrides.
+    #[test]
+    fn test_deployment() {
+        let deployment: Deployment = "
+            # Two nodes.
+            data-addresses = 10.0.0.1:9000
+            control-addresses = 10.0.0.1:9001,10.0.0.2:9001
+            threads = 4
+
+            [node 1]
+            data-address = 10.0.0.2:9000
+            threads = 16
+            labels = gpu=true
+            compact-headers = true
+        "
+        .parse()
+        .unwrap();
+
+        let config = deployment.configuration(0).unwrap();
+        assert_eq!(config.index, 0);
+        assert_eq!(config.num_worker_threads, 4);
+        assert!(!config.compact_headers);
+        let config = deployment.configuration(1).unwrap();
+        assert_eq!(config.index, 1);
+        assert_eq!(config.num_worker_threads, 16);
+        assert!(config.compact_headers);
+        assert_eq!(config.data_addresses[1], "10.0.0.2:9000".parse().unwrap());
+        assert_eq!(config.node_labels[1]["gpu"], "true");
+        assert!(config.node_labels[0].is_empty());
+
+        assert!("[node x]".parse::<Deployment>().is_err());
+        assert!("threads".parse::<Deployment>().is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use std::{collections::HashMap, fmt, sync::RwLock};
+
+use abomonation_derive::Abomonation;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::{dataflow::graph::default_graph, Uuid};
+
+lazy_static! {
+    /// Human-readable names of the operators and streams, indexed by their IDs.
+    ///
+    /// Every node constructs the same dataflow graph, so the names are known on all nodes. The
+    /// named IDs are derived from the name of their graph, and the other IDs are random, so the
+    /// nodes which run in the same process share the names without overwriting each other's.
+    static ref NAMES: RwLock<HashMap<Uuid, String>> = RwLock::new(HashMap::new());
+    /// The namespace of the IDs derived from names.
+    static ref NAMESPACE: uuid::Uuid =
+        uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_URL, b"https://github.com/erdos-project/erdos");
+}
+
+/// The IDs derived from names in a dataflow graph, and the keys from which they were derived.
+///
+/// Kept by each [`Graph`](crate::dataflow::graph::Graph), so that the IDs of a graph do not
+/// depend on the graphs built before it in the same process.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DerivedIds {
+    /// The name of the graph, from which the IDs are derived.
+    graph_name: String,
+    keys: HashMap<Uuid, String>,
+    /// The number of IDs derived from each name, which tells apart operators or streams which
+    /// share a name.
+    occurrences: HashMap<String, usize>,
+}
+
+impl DerivedIds {
+    pub fn graph_name(&self) -> &str {
+        &self.graph_name
+    }
+
+    pub fn set_graph_name(&mut self, name: &str) {
+        self.graph_name = name.to_string();
+    }
+
+    /// Derives an ID from the name of the graph, the kind of the ID, and the name given to the
+    /// ID.
+    ///
+    /// IDs with the same name are told apart by the order in which they are derived, which is
+    /// the same across processes that build the dataflow graph in the same order. Returns an
+    /// error if the ID collides with an ID derived from another name.
+    pub fn derive(&mut self, kind: &str, name: &str) -> Result<Uuid, String> {
+        let prefix = format!("{}/{}/{}", self.graph_name, kind, name);
+        let occurrence = self.occurrences.get(&prefix).cloned().unwrap_or(0);
+        let key = match occurrence {
+            0 => prefix.clone(),
+            n => format!("{}#{}", prefix, n),
+        };
+        let id = Uuid::from_bytes(*uuid::Uuid::new_v5(&NAMESPACE, key.as_bytes()).as_bytes());
+        self.insert(id, key)?;
+        self.occurrences.insert(prefix, occurrence + 1);
+        Ok(id)
+    }
+
+    /// Returns an error if another key hashes to the same ID.
+    fn insert(&mut self, id: Uuid, key: String) -> Result<(), String> {
+        match self.keys.get(&id) {
+            Some(other_key) if *other_key != key => Err(format!(
+                "ID {} derived from {} collides with the ID derived from {}",
+                id, key, other_key
+            )),
+            _ => {
+                self.keys.insert(id, key);
+                Ok(())
+            }
+        }
+    }
+}
+
+macro_rules! make_id {
+    ($(#[$attr:meta])* $name:ident, $kind:expr) => {
+        $(#[$attr])*
+        ///
+        /// Its [`Debug`](fmt::Debug) representation contains the name given to it, if any, so
+        /// that logs and error messages can be read without looking up the dataflow graph. Its
+        /// [`Display`](fmt::Display) representation only contains the ID.
+        #[derive(
+            Abomonation, Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Deserialize,
+        )]
+        pub struct $name(Uuid);
+
+        impl $name {
+            pub fn new_v4() -> Self {
+                Self(Uuid::new_v4())
+            }
+
+            pub fn new_deterministic() -> Self {
+                Self(Uuid::new_deterministic())
+            }
+
+            /// Returns an ID derived from the name, which is the same across processes and
+            /// builds, so that the logs and checkpoints of different runs can be correlated.
+            ///
+            /// The ID is derived in the default graph of the thread, and also depends on the
+            /// name of the graph set with [`set_graph_name`](crate::set_graph_name). Returns an
+            /// error if the ID collides with an ID derived from another name.
+            pub fn new_named(name: &str) -> Result<Self, String> {
+                default_graph::derive_id($kind, name).map(Self)
+            }
+
+            pub fn nil() -> Self {
+                Self(Uuid::nil())
+            }
+
+            pub(crate) fn as_bytes(&self) -> &[u8; 16] {
+                self.0.as_bytes()
+            }
+
+            /// Returns the name given to the ID, if any.
+            pub fn name(&self) -> Option<String> {
+                NAMES.read().unwrap().get(&self.0).cloned()
+            }
+
+            /// Gives a human-readable name to the ID.
+            pub(crate) fn set_name(&self, name: &str) {
+                NAMES.write().unwrap().insert(self.0, name.to_string());
+            }
+        }
+
+        impl From<Uuid> for $name {
+            fn from(id: Uuid) -> Self {
+                Self(id)
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self.name() {
+                    Some(name) => write!(f, "{} ({})", name, self.0),
+                    None => fmt::Display::fmt(&self.0, f),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+    };
+}
+
+make_id!(
+    /// A unique identifier for an operator.
+    OperatorId,
+    "operator"
+);
+
+make_id!(
+    /// A unique identifier for a stream.
+    StreamId,
+    "stream"
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that named IDs show their name in debug output, but not in display output.
+    #[test]
+    fn test_named_id_debug() {
+        let operator_id = OperatorId::new_v4();
+        let stream_id = StreamId::new_v4();
+        assert_eq!(format!("{:?}", operator_id), operator_id.to_string());
+
+        operator_id.set_name("MapOperator");
+        assert_eq!(operator_id.name(), Some("MapOperator".to_string()));
+        assert_eq!(
+            format!("{:?}", operator_id),
+            format!("MapOperator ({})", operator_id)
+        );
+        assert_eq!(stream_id.name(), None);
+        assert_eq!(format!("{:?}", stream_id), stream_id.to_string());
+    }
+
+    /// Test that IDs derived from names are stable, tell apart IDs which share a name, and
+    /// only depend on the graph in which they are derived.
+    #[test]
+    fn test_named_ids() {
+        let expected = |key: &str| {
+            let id = uuid::Uuid::new_v5(&NAMESPACE, key.as_bytes());
+            Uuid::from_bytes(*id.as_bytes())
+        };
+        let mut derived_ids = DerivedIds::default();
+        derived_ids.set_graph_name("graph");
+        assert_eq!(
+            derived_ids.derive("operator", "a"),
+            Ok(expected("graph/operator/a"))
+        );
+        assert_eq!(
+            derived_ids.derive("stream", "a"),
+            Ok(expected("graph/stream/a"))
+        );
+        assert_eq!(
+            derived_ids.derive("operator", "a"),
+            Ok(expected("graph/operator/a#1"))
+        );
+
+        // The occurrences are counted per graph.
+        let mut other_derived_ids = DerivedIds::default();
+        other_derived_ids.set_graph_name("graph");
+        assert_eq!(
+            other_derived_ids.derive("operator", "a"),
+            Ok(expected("graph/operator/a"))
+        );
+
+        // Named IDs are derived in the default graph of the thread.
+        crate::set_graph_name("test_named_ids");
+        assert_eq!(
+            OperatorId::new_named("a"),
+            Ok(OperatorId::from(expected("test_named_ids/operator/a")))
+        );
+    }
+
+    /// Test that deriving an ID which collides with an ID derived from another name fails.
+    #[test]
+    fn test_named_id_collision() {
+        let mut derived_ids = DerivedIds::default();
+        let id = Uuid::new_v4();
+        assert!(derived_ids.insert(id, "g/operator/a".to_string()).is_ok());
+        assert!(derived_ids.insert(id, "g/operator/a".to_string()).is_ok());
+        assert!(derived_ids.insert(id, "g/operator/b".to_string()).is_err());
+
+        let mut derived_ids = DerivedIds::default();
+        let id = derived_ids.derive("operator", "a").unwrap();
+        let mut other_derived_ids = DerivedIds::default();
+        assert!(other_derived_ids
+            .insert(id, "/operator/b".to_string())
+            .is_ok());
+        assert!(other_derived_ids.derive("operator", "a").is_err());
+        // The failed derivation is not counted.
+        other_derived_ids.keys.clear();
+        assert_eq!(other_derived_ids.derive("operator", "a"), Ok(id));
+    }
+}
@@ -168,15 +168,20 @@ pub use ::slog;
 #[doc(hidden)]
 pub use ::tokio;
 
+// Re-export of the library used to implement pull-based operators.
+pub use ::async_trait::async_trait;
+
 // Libraries used in this file.
 use std::{cell::RefCell, fmt};
 
 use abomonation_derive::Abomonation;
+#[cfg(feature = "cli")]
 use clap::{self, App, Arg};
 use lazy_static::lazy_static;
 use rand::{Rng, SeedableRng, StdRng};
 use serde::{Deserialize, Serialize};
 use slog::{Drain, Logger};
+#[cfg(feature = "term_logger")]
 use slog_term::{self, term_full};
 use uuid;
 
@@ -184,6 +189,8 @@ use uuid;
 mod configuration;
 #[macro_use]
 mod connect;
+mod deployment;
+mod ids;
 #[cfg(feature = "python")]
 mod python;
 
@@ -194,13 +201,13 @@ pub mod dataflow;
 pub mod node;
 #[doc(hidden)]
 pub mod scheduler;
+pub mod time;
 
 // Public exports
-pub use configuration::Configuration;
+pub use configuration::{Configuration, ExecutorModel, ShmOverflowPolicy, ZenohMode};
 pub use dataflow::OperatorConfig;
-
-/// A unique identifier for an operator.
-pub type OperatorId = Uuid;
+pub use deployment::Deployment;
+pub use ids::OperatorId;
 
 // Random number generator which should be the same accross threads and processes.
 thread_local!(static RNG: RefCell<StdRng>= RefCell::new(StdRng::from_seed(&[1913, 03, 26])));
@@ -232,6 +239,14 @@ impl Uuid {
     pub fn nil() -> Uuid {
         Uuid([0; 16])
     }
+
+    pub(crate) fn from_bytes(bytes: uuid::Bytes) -> Self {
+        Self(bytes)
+    }
+
+    pub(crate) fn as_bytes(&self) -> &uuid::Bytes {
+        &self.0
+    }
 }
 
 impl fmt::Debug for Uuid {
@@ -250,18 +265,32 @@ impl fmt::Display for Uuid {
     }
 }
 
+/// Sets the name of the dataflow graph, from which the IDs of named operators and streams are
+/// derived so that they are the same across runs.
+///
+/// Must be called on the driver thread before connecting operators.
+pub fn set_graph_name(name: &str) {
+    dataflow::graph::default_graph::set_name(name);
+}
+
 /// Resets seed and creates a new dataflow graph.
 pub fn reset() {
     // All global variables should be reset here.
     RNG.with(|rng| {
         *rng.borrow_mut() = StdRng::from_seed(&[1913, 03, 26]);
     });
-    dataflow::graph::default_graph::set(dataflow::graph::Graph::new());
+    // The graph keeps its name, but derives the IDs of named operators and streams anew.
+    let mut graph = dataflow::graph::Graph::new();
+    graph.set_name(&dataflow::graph::default_graph::name());
+    dataflow::graph::default_graph::set(graph);
 }
 
 lazy_static! {
     static ref TERMINAL_LOGGER: Logger = {
-        let drain = std::sync::Mutex::new(term_full()).fuse();
+        #[cfg(feature = "term_logger")]
+        let drain = node::RuntimeLevelFilter(std::sync::Mutex::new(term_full())).fuse();
+        #[cfg(not(feature = "term_logger"))]
+        let drain = node::RuntimeLevelFilter(node::PlainDrain).fuse();
         // let drain = slog_async::Async::new(drain).build().fuse();
         // let drain = AtomicSwitch::new(drain);
         // let decorator = slog_term::TermDecorator::new().build();
@@ -276,11 +305,11 @@ pub fn get_terminal_logger() -> slog::Logger {
     TERMINAL_LOGGER.clone()
 }
 
-
-#[cfg(feature = "zenoh_zerocopy_transport")]
-static SHM_SIZE: usize = 512 * 1024 * 1024;
-
 /// Defines command line arguments for running a multi-node ERDOS application.
+///
+/// The options of a [`Deployment`] file passed with `--config` apply unless they are passed on
+/// the command line.
+#[cfg(feature = "cli")]
 pub fn new_app(name: &str) -> clap::App {
     App::new(name)
         .arg(
@@ -288,14 +317,14 @@ pub fn new_app(name: &str) -> clap::App {
                 .short("t")
                 .long("threads")
                 .default_value("4")
-                .help("Number of worker threads per process"),
+                .help("Number of worker threads per process, or 0 to use the available CPUs"),
         )
         .arg(
             Arg::with_name("data-addresses")
                 .short("d")
                 .long("data-addresses")
                 .default_value("127.0.0.1:9000")
-                .help("Comma separated list of data socket addresses of all nodes"),
+                .help("Comma separated list of data socket addresses, or socket paths, of the nodes"),
         )
         .arg(
             Arg::with_name("control-addresses")
@@ -315,7 +344,246 @@ pub fn new_app(name: &str) -> clap::App {
             Arg::with_name("graph-filename")
                 .short("g")
                 .long("graph-filename")
+                .alias("graph-file")
                 .default_value("")
-                .help("Exports the dataflow graph as a DOT file to the provided filename"),
+                .help("Exports the dataflow graph as a DOT file, or JSON if it ends with .json"),
+        )
+        .arg(
+            Arg::with_name("plan-filename")
+                .long("plan-filename")
+                .takes_value(true)
+                .help("Exports the placement of the dataflow as a JSON file when planning"),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Validates and schedules the dataflow without running it"),
+        )
+        .arg(
+            Arg::with_name("max-message-size")
+                .long("max-message-size")
+                .takes_value(true)
+                .help("Maximum size in bytes of a message sent to another node"),
+        )
+        .arg(
+            Arg::with_name("max-chunk-size")
+                .long("max-chunk-size")
+                .takes_value(true)
+                .help("Splits messages sent to other nodes into chunks of at most this many bytes"),
+        )
+        .arg(
+            Arg::with_name("liveness-file")
+                .long("liveness-file")
+                .takes_value(true)
+                .help("File updated periodically while the node is healthy"),
+        )
+        .arg(
+            Arg::with_name("watchdog-interval")
+                .long("watchdog-interval")
+                .default_value("1000")
+                .help("Interval in milliseconds at which the liveness file and systemd are notified"),
+        )
+        .arg(
+            Arg::with_name("admin-address")
+                .long("admin-address")
+                .takes_value(true)
+                .help("Socket address on which the /healthz and /readyz endpoints are served"),
+        )
+        .arg(
+            Arg::with_name("metrics-address")
+                .long("metrics-address")
+                .takes_value(true)
+                .help("Socket address on which only the /metrics endpoint is served"),
+        )
+        .arg(
+            Arg::with_name("admin-tokens-file")
+                .long("admin-tokens-file")
+                .takes_value(true)
+                .help("File with a role and a token per line which may use the admin address"),
+        )
+        .arg(
+            Arg::with_name("stall-timeout")
+                .long("stall-timeout")
+                .default_value("30")
+                .help("Seconds after which an operator running a callback is considered stalled"),
+        )
+        .arg(
+            Arg::with_name("slow-callback-threshold")
+                .long("slow-callback-threshold")
+                .default_value("1000")
+                .help("Milliseconds after which a warning is logged for a running callback"),
+        )
+        .arg(
+            Arg::with_name("peer-check-interval")
+                .long("peer-check-interval")
+                .default_value("500")
+                .help("Interval in milliseconds at which the liveliness of other nodes is checked"),
+        )
+        .arg(
+            Arg::with_name("peer-check-timeout")
+                .long("peer-check-timeout")
+                .default_value("500")
+                .help("Duration in milliseconds to wait for replies to liveliness checks"),
+        )
+        .arg(
+            Arg::with_name("peer-check-misses")
+                .long("peer-check-misses")
+                .default_value("3")
+                .help("Number of missed liveliness checks after which another node is failed"),
+        )
+        .arg(
+            Arg::with_name("heartbeat-interval")
+                .long("heartbeat-interval")
+                .default_value("1000")
+                .help("Interval in milliseconds at which the node sends heartbeats to other nodes"),
+        )
+        .arg(
+            Arg::with_name("heartbeat-timeout")
+                .long("heartbeat-timeout")
+                .default_value("10000")
+                .help("Milliseconds without heartbeats after which another node has failed"),
+        )
+        .arg(
+            Arg::with_name("discovery-timeout")
+                .long("discovery-timeout")
+                .default_value("300")
+                .help("Seconds after which the node fails if it has not discovered all other nodes"),
+        )
+        .arg(
+            Arg::with_name("setup-timeout")
+                .long("setup-timeout")
+                .default_value("300")
+                .help("Seconds after which the node fails if an operator has not finished setting up"),
+        )
+        .arg(
+            Arg::with_name("shm-segment-size")
+                .long("shm-segment-size")
+                .default_value("536870912")
+                .help("Size in bytes of each shared memory segment of the zero-copy transport"),
+        )
+        .arg(
+            Arg::with_name("shm-num-segments")
+                .long("shm-num-segments")
+                .default_value("1")
+                .help("Number of shared memory segments of the zero-copy transport per node"),
+        )
+        .arg(
+            Arg::with_name("shm-overflow-policy")
+                .long("shm-overflow-policy")
+                .possible_values(&["backpressure", "serialize"])
+                .default_value("backpressure")
+                .help("Whether messages wait or are copied when the shared memory is full"),
+        )
+        .arg(
+            Arg::with_name("shm-directory")
+                .long("shm-directory")
+                .takes_value(true)
+                .help("Directory shared with other containers to open the shared memory"),
+        )
+        .arg(
+            Arg::with_name("control-log-directory")
+                .long("control-log-directory")
+                .takes_value(true)
+                .help("Directory to which the node logs the control messages it exchanges"),
+        )
+        .arg(
+            Arg::with_name("shm-huge-pages")
+                .long("shm-huge-pages")
+                .help("Backs the shared memory of the zero-copy transport with huge pages"),
+        )
+        .arg(
+            Arg::with_name("executor")
+                .long("executor")
+                .possible_values(&["work-stealing", "thread-per-core"])
+                .default_value("work-stealing")
+                .help("Whether operators are pinned to the worker threads"),
+        )
+        .arg(
+            Arg::with_name("scheduler")
+                .long("scheduler")
+                .takes_value(true)
+                .possible_values(&["round-robin", "load-aware", "constraint"])
+                .help("How the movable operators are placed on nodes"),
+        )
+        .arg(
+            Arg::with_name("core-affinity")
+                .long("core-affinity")
+                .help("Pins the thread-per-core executor to CPUs and receivers to operators"),
+        )
+        .arg(
+            Arg::with_name("multiplex-connections")
+                .long("multiplex-connections")
+                .help("Sends control and data messages over a single connection to each node"),
+        )
+        .arg(
+            Arg::with_name("lazy-connections")
+                .long("lazy-connections")
+                .help("Connects the data plane to other nodes upon sending them the first message"),
+        )
+        .arg(
+            Arg::with_name("compact-headers")
+                .long("compact-headers")
+                .help("Sends small messages to other nodes with compact headers"),
+        )
+        .arg(
+            Arg::with_name("serialization-threads")
+                .long("serialization-threads")
+                .default_value("0")
+                .help("Number of threads serializing large messages sent to other nodes"),
+        )
+        .arg(
+            Arg::with_name("node-labels")
+                .long("node-labels")
+                .takes_value(true)
+                .help("Labels of each node, e.g. gpu=true,zone=vehicle;zone=cloud"),
+        )
+        .arg(
+            Arg::with_name("shared-secret-file")
+                .long("shared-secret-file")
+                .takes_value(true)
+                .help("File with the secret with which the nodes authenticate each other"),
+        )
+        .arg(
+            Arg::with_name("checkpoint-directory")
+                .long("checkpoint-directory")
+                .takes_value(true)
+                .help("Directory in which the state of stateful operators is checkpointed"),
+        )
+        .arg(
+            Arg::with_name("checkpoint-interval")
+                .long("checkpoint-interval")
+                .default_value("10000")
+                .help("Interval in milliseconds at which the leader node requests checkpoints"),
+        )
+        .arg(
+            Arg::with_name("zenoh-mode")
+                .long("zenoh-mode")
+                .possible_values(&["peer", "client"])
+                .default_value("peer")
+                .help("Whether the Zenoh session of the node is a peer or a client of a router"),
+        )
+        .arg(
+            Arg::with_name("zenoh-peers")
+                .long("zenoh-peers")
+                .takes_value(true)
+                .help("Comma-separated locators of the Zenoh routers or peers to connect to"),
+        )
+        .arg(
+            Arg::with_name("zenoh-multicast-interface")
+                .long("zenoh-multicast-interface")
+                .takes_value(true)
+                .help("Network interface on which the Zenoh session scouts for peers"),
+        )
+        .arg(
+            Arg::with_name("zenoh-scouting-delay")
+                .long("zenoh-scouting-delay")
+                .takes_value(true)
+                .help("Milliseconds during which the Zenoh session scouts for peers at startup"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .takes_value(true)
+                .help("Deployment file with the options of all nodes"),
         )
 }
@@ -0,0 +1,384 @@
+use std::{collections::HashMap, fmt, io, net::SocketAddr, path::Path, str::FromStr, sync::Arc};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::node::{NodeContext, NodeHealth};
+
+/// Permission granted by a token on the admin interface of a [`Node`](crate::node::Node), set
+/// with [`Configuration::admin_tokens`](crate::Configuration::admin_tokens).
+///
+/// Each role allows the requests of the roles below it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AdminRole {
+    /// Reads the health and the metrics of the node.
+    ReadOnly,
+    /// Updates the [runtime knobs](crate::node::RuntimeKnobs) of the dataflow.
+    Operate,
+    /// Shuts down the node.
+    Admin,
+}
+
+impl fmt::Display for AdminRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ReadOnly => write!(f, "read-only"),
+            Self::Operate => write!(f, "operate"),
+            Self::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl FromStr for AdminRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read-only" => Ok(Self::ReadOnly),
+            "operate" => Ok(Self::Operate),
+            "admin" => Ok(Self::Admin),
+            _ => Err(format!(
+                "Unknown admin role {}, expected read-only, operate, or admin",
+                s
+            )),
+        }
+    }
+}
+
+/// Reads the admin tokens from a file with a role and a token on each line, e.g.
+/// `operate 3a9f...`. Empty lines and lines starting with `#` are ignored.
+#[cfg(feature = "cli")]
+pub(crate) fn read_admin_tokens<P: AsRef<Path>>(path: P) -> io::Result<HashMap<String, AdminRole>> {
+    let mut tokens = HashMap::new();
+    for line in std::fs::read_to_string(path)?.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let (role, token) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(role), Some(token), None) => (role, token),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Expected a role and a token, got {}", line),
+                ))
+            }
+        };
+        let role = role
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        tokens.insert(token.to_string(), role);
+    }
+    Ok(tokens)
+}
+
+/// A request to the admin interface.
+#[derive(Debug, PartialEq)]
+enum AdminRequest<'a> {
+    /// Reads the health or the metrics of the node at the path.
+    Read(&'a str),
+    /// Updates a runtime knob on all nodes, with `POST /knobs/<name>` and the value as the body.
+    SetKnob {
+        name: &'a str,
+        value: &'a str,
+    },
+    /// Shuts down the node, with `POST /shutdown`.
+    Shutdown,
+    NotFound,
+}
+
+impl<'a> AdminRequest<'a> {
+    fn parse(method: &str, path: &'a str, body: &'a str) -> Self {
+        match (method, path) {
+            ("GET", path) => Self::Read(path),
+            ("POST", "/shutdown") => Self::Shutdown,
+            ("POST", path) if path.starts_with("/knobs/") => Self::SetKnob {
+                name: &path["/knobs/".len()..],
+                value: body.trim(),
+            },
+            _ => Self::NotFound,
+        }
+    }
+
+    fn required_role(&self) -> AdminRole {
+        match self {
+            Self::Read(_) | Self::NotFound => AdminRole::ReadOnly,
+            Self::SetKnob { .. } => AdminRole::Operate,
+            Self::Shutdown => AdminRole::Admin,
+        }
+    }
+}
+
+type SetKnobFn = dyn Fn(&str, &str) -> Result<(), String> + Send + Sync;
+
+/// Serves the health checks and the metrics of a node, and executes the control commands
+/// of the tokens which are allowed to run them.
+///
+/// If no tokens are set, anyone may read the health and the metrics, and control commands are
+/// disabled. Otherwise, every request must carry a token in an `Authorization: Bearer <token>`
+/// header, and is rejected unless the role of the token allows the request.
+#[derive(Clone)]
+pub(crate) struct AdminInterface {
+    health: NodeHealth,
+    tokens: HashMap<String, AdminRole>,
+    set_knob: Arc<SetKnobFn>,
+    shutdown: Arc<dyn Fn() + Send + Sync>,
+}
+
+impl AdminInterface {
+    pub fn new<K, S>(
+        health: NodeHealth,
+        tokens: HashMap<String, AdminRole>,
+        set_knob: K,
+        shutdown: S,
+    ) -> Self
+    where
+        K: 'static + Fn(&str, &str) -> Result<(), String> + Send + Sync,
+        S: 'static + Fn() + Send + Sync,
+    {
+        Self {
+            health,
+            tokens,
+            set_knob: Arc::new(set_knob),
+            shutdown: Arc::new(shutdown),
+        }
+    }
+
+    /// Checks that the token is allowed to send the request.
+    fn authorize(
+        &self,
+        token: Option<&str>,
+        request: &AdminRequest,
+    ) -> Result<(), (&'static str, String)> {
+        let required_role = request.required_role();
+        if self.tokens.is_empty() {
+            return if required_role == AdminRole::ReadOnly {
+                Ok(())
+            } else {
+                Err((
+                    "403 Forbidden",
+                    "Control commands are disabled because no admin tokens are set".to_string(),
+                ))
+            };
+        }
+        let role = match token.and_then(|token| self.tokens.get(token)) {
+            Some(role) => *role,
+            None => return Err(("401 Unauthorized", "Invalid admin token".to_string())),
+        };
+        if role < required_role {
+            return Err((
+                "403 Forbidden",
+                format!("The request requires the {} role", required_role),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the status code and body of the response to an HTTP request.
+    fn respond(&self, request: &str) -> (&'static str, String) {
+        let (head, body) = match request.find("\r\n\r\n") {
+            Some(end) => (&request[..end], &request[end + 4..]),
+            None => (request, ""),
+        };
+        let mut lines = head.lines();
+        // The request line has the form `GET /healthz HTTP/1.1`.
+        let mut request_line = lines.next().unwrap_or("").split_whitespace();
+        let method = request_line.next().unwrap_or("");
+        let path = request_line.next().unwrap_or("");
+        let token = lines.find_map(|line| {
+            let mut header = line.splitn(2, ':');
+            match (header.next(), header.next()) {
+                (Some(name), Some(value)) if name.eq_ignore_ascii_case("authorization") => {
+                    let value = value.trim();
+                    if value.starts_with("Bearer ") {
+                        Some(value["Bearer ".len()..].trim())
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            }
+        });
+
+        let request = AdminRequest::parse(method, path, body);
+        if let Err(rejection) = self.authorize(token, &request) {
+            return rejection;
+        }
+        match request {
+            AdminRequest::Read(path) => self.health.respond(path),
+            AdminRequest::SetKnob { name, value } => {
+                slog::info!(
+                    crate::TERMINAL_LOGGER,
+                    "Admin interface: setting knob {} to {}",
+                    name,
+                    value
+                );
+                match (self.set_knob)(name, value) {
+                    Ok(()) => ("200 OK", "ok".to_string()),
+                    Err(e) => ("400 Bad Request", e),
+                }
+            }
+            AdminRequest::Shutdown => {
+                slog::info!(crate::TERMINAL_LOGGER, "Admin interface: shutting down");
+                (self.shutdown)();
+                ("200 OK", "shutting down".to_string())
+            }
+            AdminRequest::NotFound => ("404 Not Found", "Not found".to_string()),
+        }
+    }
+}
+
+/// Serves the admin interface over HTTP: the `/healthz` and `/readyz` endpoints, e.g. for
+/// Kubernetes probes, the metrics of the process on the `/metrics` endpoint in the Prometheus
+/// text format, and the control commands.
+pub(crate) async fn serve(address: SocketAddr, admin: AdminInterface) {
+    listen(address, move |request| admin.respond(request)).await
+}
+
+/// Serves only the metrics of the process and the message counters of the node on the
+/// `/metrics` endpoint, without authentication.
+pub(crate) async fn serve_metrics(address: SocketAddr, context: Arc<NodeContext>) {
+    listen(address, move |request| {
+        // The request line has the form `GET /metrics HTTP/1.1`.
+        match request.split_whitespace().nth(1) {
+            Some("/metrics") => ("200 OK", crate::node::metrics::render(&context.counters)),
+            _ => ("404 Not Found", "Not found".to_string()),
+        }
+    })
+    .await
+}
+
+async fn listen<F>(address: SocketAddr, respond: F)
+where
+    F: Fn(&str) -> (&'static str, String) + Clone + Send + 'static,
+{
+    let mut listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            slog::error!(
+                crate::TERMINAL_LOGGER,
+                "Unable to serve health checks on {}: {}",
+                address,
+                e
+            );
+            return;
+        }
+    };
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let respond = respond.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_connection(stream, respond).await {
+                        slog::debug!(crate::TERMINAL_LOGGER, "Health check failed: {}", e);
+                    }
+                });
+            }
+            Err(e) => slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "Unable to accept health check connection: {}",
+                e
+            ),
+        }
+    }
+}
+
+async fn handle_connection<F>(mut stream: TcpStream, respond: F) -> std::io::Result<()>
+where
+    F: Fn(&str) -> (&'static str, String),
+{
+    let mut buf = [0; 1024];
+    let num_bytes = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..num_bytes]);
+    let (status, body) = respond(&request);
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+
+    /// Test that control commands are only executed for the tokens whose role allows them.
+    #[test]
+    fn test_admin_roles() {
+        let shutdowns = Arc::new(AtomicUsize::new(0));
+        let shutdowns_copy = Arc::clone(&shutdowns);
+        let interface = |tokens: &[(&str, AdminRole)]| {
+            let shutdowns = Arc::clone(&shutdowns_copy);
+            AdminInterface::new(
+                NodeHealth::new(Duration::from_secs(1), Default::default()),
+                tokens
+                    .iter()
+                    .map(|(token, role)| (token.to_string(), *role))
+                    .collect(),
+                |name, _| match name {
+                    "rate" => Ok(()),
+                    _ => Err(format!("Unknown knob {}", name)),
+                },
+                move || {
+                    shutdowns.fetch_add(1, Ordering::SeqCst);
+                },
+            )
+        };
+        let request = |method: &str, path: &str, token: Option<&str>, body: &str| {
+            let authorization = token
+                .map(|token| format!("Authorization: Bearer {}\r\n", token))
+                .unwrap_or_default();
+            format!(
+                "{} {} HTTP/1.1\r\nHost: localhost\r\n{}\r\n{}",
+                method, path, authorization, body
+            )
+        };
+
+        // Without tokens, control commands are disabled.
+        let open = interface(&[]);
+        assert_eq!(
+            open.respond(&request("GET", "/healthz", None, "")).0,
+            "200 OK"
+        );
+        let shutdown = request("POST", "/shutdown", None, "");
+        assert_eq!(open.respond(&shutdown).0, "403 Forbidden");
+
+        let secured = interface(&[
+            ("reader", AdminRole::ReadOnly),
+            ("operator", AdminRole::Operate),
+            ("root", AdminRole::Admin),
+        ]);
+        let healthz = |token| request("GET", "/healthz", token, "");
+        assert_eq!(secured.respond(&healthz(None)).0, "401 Unauthorized");
+        assert_eq!(
+            secured.respond(&healthz(Some("guess"))).0,
+            "401 Unauthorized"
+        );
+        assert_eq!(secured.respond(&healthz(Some("reader"))).0, "200 OK");
+
+        let set_knob = |token, name| request("POST", &format!("/knobs/{}", name), token, "2\r\n");
+        let reader_set_knob = set_knob(Some("reader"), "rate");
+        assert_eq!(secured.respond(&reader_set_knob).0, "403 Forbidden");
+        let operator_set_knob = set_knob(Some("operator"), "rate");
+        assert_eq!(secured.respond(&operator_set_knob).0, "200 OK");
+        let unknown_knob = set_knob(Some("operator"), "size");
+        assert_eq!(secured.respond(&unknown_knob).0, "400 Bad Request");
+
+        let operator_shutdown = request("POST", "/shutdown", Some("operator"), "");
+        assert_eq!(secured.respond(&operator_shutdown).0, "403 Forbidden");
+        assert_eq!(shutdowns.load(Ordering::SeqCst), 0);
+        let root_shutdown = request("POST", "/shutdown", Some("root"), "");
+        assert_eq!(secured.respond(&root_shutdown).0, "200 OK");
+        assert_eq!(shutdowns.load(Ordering::SeqCst), 1);
+    }
+}
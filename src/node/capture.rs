@@ -0,0 +1,360 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryInto,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Deserialize;
+
+use crate::dataflow::{stream::StreamId, Data, Message};
+
+/// Size of the header of a captured message, which contains the time at which the message was
+/// sent and the length of its payload.
+const HEADER_SIZE: usize = 12;
+
+/// Which messages of a stream are captured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CapturePolicy {
+    /// Captures one of every `n` messages.
+    Sample(u64),
+    /// Captures at most one message per interval.
+    Rate(Duration),
+    /// Captures the messages sent within the duration before and after a
+    /// [trigger](crate::node::NodeHandle::trigger_capture) or a slow callback on the current
+    /// node, e.g. 2 seconds around any missed deadline. The messages sent before the trigger
+    /// are kept in memory.
+    AroundTrigger(Duration),
+}
+
+/// Configures the capture of the payloads of a stream to disk with
+/// [`Node::capture_stream`](crate::node::Node::capture_stream), so that incidents come with the
+/// messages needed to reproduce them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureConfig {
+    pub policy: CapturePolicy,
+    /// Directory in which the captured messages are stored.
+    pub directory: PathBuf,
+    /// Maximum size in bytes of the captured messages of the stream on disk. The oldest half of
+    /// the messages is overwritten once the captures are full.
+    pub max_bytes: u64,
+}
+
+impl CaptureConfig {
+    /// Captures at most 64 MiB of messages of the stream in the directory.
+    pub fn new<P: AsRef<Path>>(policy: CapturePolicy, directory: P) -> Self {
+        Self {
+            policy,
+            directory: directory.as_ref().to_path_buf(),
+            max_bytes: 64 * 1024 * 1024,
+        }
+    }
+
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+}
+
+/// A message captured to disk, read with [`CapturedMessage::read_all`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedMessage {
+    /// Time at which the message was sent, in microseconds since the Unix epoch.
+    pub time_micros: u64,
+    /// The message serialized with bincode.
+    pub bytes: Vec<u8>,
+}
+
+impl CapturedMessage {
+    /// Reads the messages of the stream captured in the directory, oldest first.
+    pub fn read_all<P: AsRef<Path>>(directory: P, stream_id: StreamId) -> io::Result<Vec<Self>> {
+        let mut messages = Vec::new();
+        for segment in 0..2 {
+            let path = segment_path(directory.as_ref(), stream_id, segment);
+            match fs::read(&path) {
+                Ok(bytes) => messages.extend(parse_segment(&bytes)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+                Err(e) => return Err(e),
+            }
+        }
+        messages.sort_by_key(|message| message.time_micros);
+        Ok(messages)
+    }
+
+    /// Deserializes the captured message.
+    pub fn message<D>(&self) -> bincode::Result<Message<D>>
+    where
+        for<'a> D: Data + Deserialize<'a>,
+    {
+        bincode::deserialize(&self.bytes)
+    }
+}
+
+/// The captures of a stream, stored in two segment files. Once the current segment is full, the
+/// other segment is truncated and becomes the current segment.
+struct StreamCapture {
+    stream_id: StreamId,
+    config: CaptureConfig,
+    num_messages: u64,
+    last_captured: Option<Instant>,
+    /// The messages sent within the window of [`CapturePolicy::AroundTrigger`], with the time
+    /// at which they were sent.
+    recent: VecDeque<(Instant, CapturedMessage)>,
+    /// Time until which all messages are captured after a trigger.
+    triggered_until: Option<Instant>,
+    segment: usize,
+    file: Option<File>,
+    segment_len: u64,
+}
+
+impl StreamCapture {
+    fn new(stream_id: StreamId, config: CaptureConfig) -> Self {
+        Self {
+            stream_id,
+            config,
+            num_messages: 0,
+            last_captured: None,
+            recent: VecDeque::new(),
+            triggered_until: None,
+            segment: 0,
+            file: None,
+            segment_len: 0,
+        }
+    }
+
+    /// Returns whether the message sent at the time is captured.
+    fn should_capture(&mut self, now: Instant) -> bool {
+        self.num_messages += 1;
+        match self.config.policy {
+            CapturePolicy::Sample(n) => (self.num_messages - 1) % n.max(1) == 0,
+            CapturePolicy::Rate(interval) => {
+                let captured = self
+                    .last_captured
+                    .map_or(true, |last| now.duration_since(last) >= interval);
+                if captured {
+                    self.last_captured = Some(now);
+                }
+                captured
+            }
+            // Messages are kept in memory until a trigger.
+            CapturePolicy::AroundTrigger(_) => true,
+        }
+    }
+
+    fn capture(&mut self, now: Instant, message: CapturedMessage) -> io::Result<()> {
+        let window = match self.config.policy {
+            CapturePolicy::AroundTrigger(window) => window,
+            _ => return self.write(&message),
+        };
+        if self.triggered_until.map_or(false, |until| now <= until) {
+            return self.write(&message);
+        }
+        while let Some((sent_at, _)) = self.recent.front() {
+            if now.duration_since(*sent_at) <= window {
+                break;
+            }
+            self.recent.pop_front();
+        }
+        self.recent.push_back((now, message));
+        Ok(())
+    }
+
+    /// Writes the messages sent within the window before the trigger, and captures the messages
+    /// sent within the window after it.
+    fn trigger(&mut self, now: Instant) -> io::Result<()> {
+        if let CapturePolicy::AroundTrigger(window) = self.config.policy {
+            self.triggered_until = Some(now + window);
+            while let Some((_, message)) = self.recent.pop_front() {
+                self.write(&message)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens the current segment, which is cleared if `truncate` is set.
+    fn open_segment(&mut self, truncate: bool) -> io::Result<()> {
+        fs::create_dir_all(&self.config.directory)?;
+        let path = segment_path(&self.config.directory, self.stream_id, self.segment);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(!truncate)
+            .write(true)
+            .truncate(truncate)
+            .open(path)?;
+        self.segment_len = file.metadata()?.len();
+        self.file = Some(file);
+        Ok(())
+    }
+
+    fn write(&mut self, message: &CapturedMessage) -> io::Result<()> {
+        let record_len = (HEADER_SIZE + message.bytes.len()) as u64;
+        if self.file.is_none() {
+            // Keep the messages captured by previous runs of the process.
+            self.open_segment(false)?;
+        }
+        if self.segment_len + record_len > self.config.max_bytes / 2 {
+            self.segment = 1 - self.segment;
+            self.open_segment(true)?;
+        }
+        let mut record = Vec::with_capacity(record_len as usize);
+        record.extend_from_slice(&message.time_micros.to_be_bytes());
+        record.extend_from_slice(&(message.bytes.len() as u32).to_be_bytes());
+        record.extend_from_slice(&message.bytes);
+        self.file.as_mut().unwrap().write_all(&record)?;
+        self.segment_len += record_len;
+        Ok(())
+    }
+}
+
+fn segment_path(directory: &Path, stream_id: StreamId, segment: usize) -> PathBuf {
+    directory.join(format!("{}.{}.capture", stream_id, segment))
+}
+
+/// Parses the messages of a segment file, ignoring a message which was only partially written,
+/// e.g. because the process crashed.
+fn parse_segment(bytes: &[u8]) -> Vec<CapturedMessage> {
+    let mut messages = Vec::new();
+    let mut offset = 0;
+    while offset + HEADER_SIZE <= bytes.len() {
+        let time_micros = u64::from_be_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        let len = u32::from_be_bytes(bytes[offset + 8..offset + HEADER_SIZE].try_into().unwrap())
+            as usize;
+        let start = offset + HEADER_SIZE;
+        if start + len > bytes.len() {
+            break;
+        }
+        messages.push(CapturedMessage {
+            time_micros,
+            bytes: bytes[start..start + len].to_vec(),
+        });
+        offset = start + len;
+    }
+    messages
+}
+
+/// The captures of the streams sent on a node.
+#[derive(Default)]
+pub(crate) struct Captures {
+    captures: Mutex<HashMap<StreamId, StreamCapture>>,
+    /// Avoids locking the captures for every message if no stream is captured.
+    capturing: AtomicBool,
+}
+
+impl Captures {
+    /// Captures the messages sent on the stream on the node.
+    pub fn capture_stream(&self, stream_id: StreamId, config: CaptureConfig) {
+        self.captures
+            .lock()
+            .unwrap()
+            .insert(stream_id, StreamCapture::new(stream_id, config));
+        self.capturing.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capturing.load(Ordering::Relaxed)
+    }
+
+    /// Captures the message sent on the stream if the capture policy of the stream selects it.
+    pub fn message_sent<D: Data>(&self, stream_id: StreamId, msg: &Message<D>) {
+        let now = Instant::now();
+        let selected = match self.captures.lock().unwrap().get_mut(&stream_id) {
+            Some(capture) => capture.should_capture(now),
+            None => false,
+        };
+        if !selected {
+            return;
+        }
+        // Serialize the message without holding the lock.
+        let bytes = match bincode::serialize(msg) {
+            Ok(bytes) => bytes,
+            Err(_) => return,
+        };
+        let message = CapturedMessage {
+            time_micros: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_micros() as u64,
+            bytes,
+        };
+        if let Some(capture) = self.captures.lock().unwrap().get_mut(&stream_id) {
+            if let Err(e) = capture.capture(now, message) {
+                slog::warn!(
+                    crate::TERMINAL_LOGGER,
+                    "Unable to capture a message of stream {}: {}",
+                    stream_id,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Captures the messages sent around the current time on the streams captured with
+    /// [`CapturePolicy::AroundTrigger`].
+    pub fn trigger(&self) {
+        if !self.is_capturing() {
+            return;
+        }
+        let now = Instant::now();
+        for (stream_id, capture) in self.captures.lock().unwrap().iter_mut() {
+            if let Err(e) = capture.trigger(now) {
+                slog::warn!(
+                    crate::TERMINAL_LOGGER,
+                    "Unable to capture the messages of stream {}: {}",
+                    stream_id,
+                    e
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataflow::Timestamp;
+
+    /// Test that the messages sent around a trigger are captured, and that the captures of a
+    /// stream are bounded on disk.
+    #[test]
+    fn test_capture_around_trigger() {
+        let directory = std::env::temp_dir().join(format!("erdos-capture-{}", StreamId::new_v4()));
+        let stream_id = StreamId::new_v4();
+        let config = CaptureConfig::new(
+            CapturePolicy::AroundTrigger(Duration::from_secs(60)),
+            &directory,
+        )
+        .max_bytes(1024);
+        let mut capture = StreamCapture::new(stream_id, config);
+        let message = |time: u64| CapturedMessage {
+            time_micros: time,
+            bytes: bincode::serialize(&Message::new_message(Timestamp::new(vec![time]), time))
+                .unwrap(),
+        };
+        let now = Instant::now();
+        capture.capture(now, message(0)).unwrap();
+        assert!(CapturedMessage::read_all(&directory, stream_id)
+            .unwrap()
+            .is_empty());
+        capture.trigger(now).unwrap();
+        for time in 1..100 {
+            assert!(capture.should_capture(now));
+            capture.capture(now, message(time)).unwrap();
+        }
+
+        let captured = CapturedMessage::read_all(&directory, stream_id).unwrap();
+        let size: usize = captured.iter().map(|m| HEADER_SIZE + m.bytes.len()).sum();
+        assert!(size <= 1024);
+        // The newest messages are kept.
+        assert_eq!(
+            captured.last().unwrap().message::<u64>().unwrap().data(),
+            Some(&99)
+        );
+        let _ = fs::remove_dir_all(&directory);
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use lazy_static::lazy_static;
+use tokio::sync::watch;
+
+use crate::{dataflow::StatefulOperator, OperatorId};
+
+/// Extension of the files in which the [`FileSystemBackend`] stores checkpoints.
+const CHECKPOINT_EXTENSION: &str = "ckpt";
+
+lazy_static! {
+    /// The backend in which the operators of the current process store their checkpoints.
+    static ref BACKEND: RwLock<Option<Arc<dyn CheckpointBackend>>> = RwLock::new(None);
+    /// The ID of the latest checkpoint requested on the current process, or 0 if none.
+    static ref REQUESTS: (watch::Sender<u64>, watch::Receiver<u64>) = watch::channel(0);
+}
+
+/// Stores the checkpoints of [stateful operators](StatefulOperator), e.g. on a disk which
+/// survives restarts of the nodes.
+///
+/// Checkpoints are identified by increasing IDs, and each operator is restored from its latest
+/// checkpoint when it starts.
+pub trait CheckpointBackend: Send + Sync {
+    /// Stores the state of the operator in the checkpoint.
+    fn save(&self, operator_id: OperatorId, checkpoint_id: u64, state: &[u8]) -> io::Result<()>;
+
+    /// Returns the ID and the state of the latest checkpoint of the operator, if any.
+    fn load_latest(&self, operator_id: OperatorId) -> io::Result<Option<(u64, Vec<u8>)>>;
+}
+
+/// Stores checkpoints as files in a directory, with one subdirectory per operator.
+///
+/// Checkpoints are written to a temporary file which is renamed once complete, so a node which
+/// crashes while checkpointing restores from the previous checkpoint. The latest checkpoints of
+/// each operator are kept, 2 by default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileSystemBackend {
+    directory: PathBuf,
+    retained: usize,
+}
+
+impl FileSystemBackend {
+    pub fn new<P: AsRef<Path>>(directory: P) -> Self {
+        Self {
+            directory: directory.as_ref().to_path_buf(),
+            retained: 2,
+        }
+    }
+
+    /// Sets the number of checkpoints kept for each operator, which is at least 1.
+    pub fn retained(mut self, retained: usize) -> Self {
+        self.retained = retained.max(1);
+        self
+    }
+
+    fn operator_directory(&self, operator_id: OperatorId) -> PathBuf {
+        self.directory.join(format!("{}", operator_id))
+    }
+
+    /// Returns the IDs of the checkpoints of the operator, in increasing order.
+    fn checkpoint_ids(&self, operator_id: OperatorId) -> io::Result<Vec<u64>> {
+        let entries = match fs::read_dir(self.operator_directory(operator_id)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut ids = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(CHECKPOINT_EXTENSION) {
+                continue;
+            }
+            if let Some(id) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse().ok())
+            {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    fn checkpoint_path(&self, operator_id: OperatorId, checkpoint_id: u64) -> PathBuf {
+        self.operator_directory(operator_id)
+            .join(format!("{:020}.{}", checkpoint_id, CHECKPOINT_EXTENSION))
+    }
+}
+
+impl CheckpointBackend for FileSystemBackend {
+    fn save(&self, operator_id: OperatorId, checkpoint_id: u64, state: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(self.operator_directory(operator_id))?;
+        let path = self.checkpoint_path(operator_id, checkpoint_id);
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, state)?;
+        fs::rename(&temp_path, &path)?;
+
+        let ids = self.checkpoint_ids(operator_id)?;
+        for id in &ids[..ids.len().saturating_sub(self.retained)] {
+            fs::remove_file(self.checkpoint_path(operator_id, *id))?;
+        }
+        Ok(())
+    }
+
+    fn load_latest(&self, operator_id: OperatorId) -> io::Result<Option<(u64, Vec<u8>)>> {
+        match self.checkpoint_ids(operator_id)?.last() {
+            Some(&id) => Ok(Some((id, fs::read(self.checkpoint_path(operator_id, id))?))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Sets the backend in which the operators of the current process store their checkpoints.
+pub(crate) fn set_backend(backend: Arc<dyn CheckpointBackend>) {
+    *BACKEND.write().unwrap() = Some(backend);
+}
+
+fn backend() -> Option<Arc<dyn CheckpointBackend>> {
+    BACKEND.read().unwrap().clone()
+}
+
+/// Whether a backend is set, in which case stateful operators are checkpointed.
+pub(crate) fn is_enabled() -> bool {
+    BACKEND.read().unwrap().is_some()
+}
+
+/// Returns the ID of a checkpoint taken now, which is the number of milliseconds since the Unix
+/// epoch so that it is larger than the IDs of the checkpoints taken before a restart.
+pub(crate) fn next_checkpoint_id() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(1)
+}
+
+/// Requests the stateful operators of the current process to take the checkpoint.
+pub(crate) fn request(checkpoint_id: u64) {
+    let _ = REQUESTS.0.broadcast(checkpoint_id);
+}
+
+/// Returns the receiver of the checkpoint requests, which yields 0 until the first request.
+pub(crate) fn requests() -> watch::Receiver<u64> {
+    REQUESTS.1.clone()
+}
+
+/// Restores the operator from its latest checkpoint, if a backend is set. Returns the ID of the
+/// checkpoint.
+pub(crate) fn restore(
+    operator_id: OperatorId,
+    operator: &mut dyn StatefulOperator,
+) -> Result<Option<u64>, String> {
+    let backend = match backend() {
+        Some(backend) => backend,
+        None => return Ok(None),
+    };
+    match backend.load_latest(operator_id) {
+        Ok(Some((checkpoint_id, state))) => operator
+            .restore(&state)
+            .map(|()| Some(checkpoint_id))
+            .map_err(|e| format!("unable to restore checkpoint {}: {}", checkpoint_id, e)),
+        Ok(None) => Ok(None),
+        Err(e) => Err(format!("unable to load the latest checkpoint: {}", e)),
+    }
+}
+
+/// Saves the state of the operator in the checkpoint, if a backend is set.
+pub(crate) fn take(
+    operator_id: OperatorId,
+    operator: &mut dyn StatefulOperator,
+    checkpoint_id: u64,
+) -> Result<(), String> {
+    let backend = match backend() {
+        Some(backend) => backend,
+        None => return Ok(()),
+    };
+    let state = operator
+        .checkpoint()
+        .map_err(|e| format!("unable to take checkpoint {}: {}", checkpoint_id, e))?;
+    backend
+        .save(operator_id, checkpoint_id, &state)
+        .map_err(|e| format!("unable to save checkpoint {}: {}", checkpoint_id, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the latest checkpoint is loaded, and that older checkpoints are removed.
+    #[test]
+    fn test_file_system_backend() {
+        let operator_id = OperatorId::new_v4();
+        let directory = std::env::temp_dir().join(format!("erdos-checkpoints-{}", operator_id));
+        let backend = FileSystemBackend::new(&directory).retained(2);
+        assert_eq!(backend.load_latest(operator_id).unwrap(), None);
+
+        for checkpoint_id in &[5, 20, 100] {
+            let state = checkpoint_id.to_string().into_bytes();
+            backend.save(operator_id, *checkpoint_id, &state).unwrap();
+        }
+        assert_eq!(backend.checkpoint_ids(operator_id).unwrap(), vec![20, 100]);
+        assert_eq!(
+            backend.load_latest(operator_id).unwrap(),
+            Some((100, b"100".to_vec()))
+        );
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,196 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    mpsc, Arc, Mutex,
+};
+
+use crate::{
+    communication::CodecError,
+    dataflow::stream::{
+        errors::{ReadError, TryReadError},
+        StreamId,
+    },
+    node::NodeId,
+};
+
+/// Reason for which a message received from another node could not be delivered.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeadLetterReason {
+    /// The message or its metadata failed to deserialize.
+    Undecodable(String),
+    /// No operator on the current node reads from the message's stream. Data receivers buffer
+    /// the messages of a stream until an operator registers to read from it, and only report the
+    /// messages of the streams with more than 1024 buffered messages, oldest first.
+    NoReceiver,
+    /// The message was split into chunks, and some chunks were not received. The raw bytes of
+    /// incomplete messages are not kept.
+    Incomplete(String),
+    /// Messages sent before this one on the stream were lost, as detected from a gap in the
+    /// sequence numbers of the stream. Holds the number of lost messages; no bytes are kept.
+    Lost(u64),
+    /// The message was larger than the maximum message size of the node, and was skipped
+    /// without being buffered; no bytes are kept.
+    MessageTooLarge { size: usize, limit: usize },
+}
+
+impl DeadLetterReason {
+    /// Returns the reason for which a message which failed to decompress is dropped.
+    pub(crate) fn decompression_failed(error: &CodecError) -> Self {
+        match error {
+            CodecError::MessageTooLarge { size, limit } => DeadLetterReason::MessageTooLarge {
+                size: *size,
+                limit: *limit,
+            },
+            e => DeadLetterReason::Undecodable(format!("{:?}", e)),
+        }
+    }
+}
+
+/// A message received from another node that could not be delivered to any operator.
+#[derive(Clone, Debug)]
+pub struct DeadLetter {
+    /// The node from which the message was received.
+    pub from_node: NodeId,
+    /// The stream on which the message was sent. `None` if the message metadata could not be
+    /// decoded.
+    pub stream_id: Option<StreamId>,
+    /// Why the message was not delivered.
+    pub reason: DeadLetterReason,
+    /// The raw bytes of the message, as received from the network.
+    /// Empty if no [`DeadLetterStream`] was subscribed when the message arrived, or if the message
+    /// could not be reassembled or decompressed.
+    pub bytes: Vec<u8>,
+}
+
+/// Maximum number of dead letters buffered for a [`DeadLetterStream`] which is not read.
+pub const DEAD_LETTER_CAPACITY: usize = 1024;
+
+/// Receives the [`DeadLetter`]s of a [`Node`](crate::node::Node).
+///
+/// Created by calling [`Node::dead_letter_stream`](crate::node::Node::dead_letter_stream) before
+/// running the node. At most [`DEAD_LETTER_CAPACITY`] dead letters are buffered; the dead letters
+/// sent while the stream is full are dropped and counted in
+/// [`num_dropped`](DeadLetterStream::num_dropped).
+pub struct DeadLetterStream {
+    rx: mpsc::Receiver<DeadLetter>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl DeadLetterStream {
+    /// Non-blocking read from the [`DeadLetterStream`].
+    ///
+    /// Returns [`Empty`](TryReadError::Empty) if no dead letter is available.
+    pub fn try_read(&self) -> Result<DeadLetter, TryReadError> {
+        self.rx.try_recv().map_err(|e| match e {
+            mpsc::TryRecvError::Empty => TryReadError::Empty,
+            mpsc::TryRecvError::Disconnected => TryReadError::Disconnected,
+        })
+    }
+
+    /// Blocking read from the [`DeadLetterStream`].
+    ///
+    /// Returns [`Disconnected`](ReadError::Disconnected) once the node shuts down.
+    pub fn read(&self) -> Result<DeadLetter, ReadError> {
+        self.rx.recv().map_err(|_| ReadError::Disconnected)
+    }
+
+    /// Returns the number of dead letters dropped because the stream was full.
+    pub fn num_dropped(&self) -> u64 {
+        self.dropped.load(Ordering::SeqCst)
+    }
+}
+
+/// Sends dead letters to a [`DeadLetterStream`].
+struct Subscriber {
+    tx: mpsc::SyncSender<DeadLetter>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Subscriber {
+    /// Returns false if the [`DeadLetterStream`] was dropped.
+    fn send(&self, dead_letter: DeadLetter) -> bool {
+        match self.tx.try_send(dead_letter) {
+            Ok(()) => true,
+            Err(mpsc::TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::SeqCst);
+                true
+            }
+            Err(mpsc::TrySendError::Disconnected(_)) => false,
+        }
+    }
+}
+
+/// Node-wide queue to which data receivers send undeliverable messages.
+#[derive(Clone, Default)]
+pub(crate) struct DeadLetterQueue {
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+}
+
+impl DeadLetterQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&self) -> DeadLetterStream {
+        let (tx, rx) = mpsc::sync_channel(DEAD_LETTER_CAPACITY);
+        let dropped = Arc::new(AtomicU64::new(0));
+        self.subscribers.lock().unwrap().push(Subscriber {
+            tx,
+            dropped: Arc::clone(&dropped),
+        });
+        DeadLetterStream { rx, dropped }
+    }
+
+    /// Returns true if any [`DeadLetterStream`] is listening. Used to avoid copying the raw bytes
+    /// of messages when nobody reads the dead letters.
+    pub fn has_subscribers(&self) -> bool {
+        !self.subscribers.lock().unwrap().is_empty()
+    }
+
+    /// Logs the dead letter and forwards it to all subscribers.
+    pub fn send(&self, dead_letter: DeadLetter) {
+        slog::warn!(
+            crate::TERMINAL_LOGGER,
+            "Dropping message from node {} on stream {:?}: {:?}",
+            dead_letter.from_node,
+            dead_letter.stream_id,
+            dead_letter.reason
+        );
+        let mut subscribers = self.subscribers.lock().unwrap();
+        // Remove the subscribers whose streams were dropped.
+        subscribers.retain(|subscriber| subscriber.send(dead_letter.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_dead_letter() -> DeadLetter {
+        DeadLetter {
+            from_node: 1,
+            stream_id: None,
+            reason: DeadLetterReason::NoReceiver,
+            bytes: Vec::new(),
+        }
+    }
+
+    /// Test that the dead letters sent to a full stream are dropped and counted, and that the
+    /// dropped streams are unsubscribed.
+    #[test]
+    fn test_bounded_dead_letter_stream() {
+        let queue = DeadLetterQueue::new();
+        let stream = queue.subscribe();
+        for _ in 0..DEAD_LETTER_CAPACITY + 2 {
+            queue.send(make_dead_letter());
+        }
+        assert_eq!(stream.num_dropped(), 2);
+        for _ in 0..DEAD_LETTER_CAPACITY {
+            assert!(stream.try_read().is_ok());
+        }
+        assert_eq!(stream.try_read().unwrap_err(), TryReadError::Empty);
+
+        drop(stream);
+        queue.send(make_dead_letter());
+        assert!(!queue.has_subscribers());
+    }
+}
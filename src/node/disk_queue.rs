@@ -0,0 +1,180 @@
+use std::{
+    collections::VecDeque,
+    convert::TryInto,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+/// Size of the header of a record, which contains the time at which the record was pushed and
+/// the length of its payload.
+const HEADER_SIZE: u64 = 12;
+
+/// The location and the push time of a record in the queue file.
+struct Record {
+    offset: u64,
+    time: u64,
+    len: u32,
+}
+
+/// A FIFO queue of byte payloads stored in a file, so that the payloads survive restarts of the
+/// process.
+///
+/// Records are appended to the queue file, and the offset of the first record which was not
+/// popped yet is stored in a separate head file. The queue file is truncated once all records
+/// were popped.
+pub(crate) struct DiskQueue {
+    file: File,
+    head_path: PathBuf,
+    records: VecDeque<Record>,
+    /// Offset at which the next record is appended.
+    end: u64,
+    /// Total size of the payloads in the queue.
+    num_bytes: usize,
+}
+
+impl DiskQueue {
+    /// Opens the queue stored at the path, or creates an empty queue.
+    ///
+    /// Records which were only partially written, e.g. because the process crashed, are
+    /// discarded.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(path)?;
+        let head_path = path.with_extension("head");
+        let head = match fs::read(&head_path) {
+            Ok(bytes) if bytes.len() == 8 => u64::from_be_bytes(bytes[..].try_into().unwrap()),
+            _ => 0,
+        };
+
+        let file_len = file.metadata()?.len();
+        let mut records = VecDeque::new();
+        let mut num_bytes = 0;
+        // The process stopped after truncating the queue file, but before resetting the head.
+        let mut offset = if head > file_len { 0 } else { head };
+        while offset + HEADER_SIZE <= file_len {
+            let mut header = [0u8; HEADER_SIZE as usize];
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut header)?;
+            let time = u64::from_be_bytes(header[..8].try_into().unwrap());
+            let len = u32::from_be_bytes(header[8..].try_into().unwrap());
+            if offset + HEADER_SIZE + len as u64 > file_len {
+                break;
+            }
+            records.push_back(Record { offset, time, len });
+            num_bytes += len as usize;
+            offset += HEADER_SIZE + len as u64;
+        }
+        file.set_len(offset)?;
+
+        Ok(Self {
+            file,
+            head_path,
+            records,
+            end: offset,
+            num_bytes,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Returns the total size of the payloads in the queue.
+    pub fn num_bytes(&self) -> usize {
+        self.num_bytes
+    }
+
+    /// Appends a payload pushed at the time, in milliseconds since the Unix epoch.
+    pub fn push(&mut self, time: u64, payload: &[u8]) -> io::Result<()> {
+        let mut record = Vec::with_capacity(HEADER_SIZE as usize + payload.len());
+        record.extend_from_slice(&time.to_be_bytes());
+        record.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        record.extend_from_slice(payload);
+        self.file.seek(SeekFrom::Start(self.end))?;
+        self.file.write_all(&record)?;
+        self.records.push_back(Record {
+            offset: self.end,
+            time,
+            len: payload.len() as u32,
+        });
+        self.end += record.len() as u64;
+        self.num_bytes += payload.len();
+        Ok(())
+    }
+
+    /// Returns the push time of the first payload.
+    pub fn front_time(&self) -> Option<u64> {
+        self.records.front().map(|record| record.time)
+    }
+
+    /// Reads the first payload.
+    pub fn front(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let (offset, len) = match self.records.front() {
+            Some(record) => (record.offset, record.len),
+            None => return Ok(None),
+        };
+        let mut payload = vec![0u8; len as usize];
+        self.file.seek(SeekFrom::Start(offset + HEADER_SIZE))?;
+        self.file.read_exact(&mut payload)?;
+        Ok(Some(payload))
+    }
+
+    /// Removes the first payload.
+    pub fn pop_front(&mut self) -> io::Result<()> {
+        let record = match self.records.pop_front() {
+            Some(record) => record,
+            None => return Ok(()),
+        };
+        self.num_bytes -= record.len as usize;
+        let head = match self.records.front() {
+            Some(next) => next.offset,
+            None => {
+                self.file.set_len(0)?;
+                self.end = 0;
+                0
+            }
+        };
+        fs::write(&self.head_path, head.to_be_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the payloads which were not popped are restored when the queue is reopened.
+    #[test]
+    fn test_reopen_disk_queue() {
+        let path = std::env::temp_dir()
+            .join(format!("erdos-disk-queue-{}", crate::Uuid::new_v4()))
+            .join("stream.queue");
+        {
+            let mut queue = DiskQueue::open(&path).unwrap();
+            for i in 0..3u8 {
+                queue.push(i as u64, &[i; 4]).unwrap();
+            }
+            queue.pop_front().unwrap();
+        }
+        let mut queue = DiskQueue::open(&path).unwrap();
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.num_bytes(), 8);
+        assert_eq!(queue.front_time(), Some(1));
+        assert_eq!(queue.front().unwrap(), Some(vec![1; 4]));
+        queue.pop_front().unwrap();
+        queue.pop_front().unwrap();
+        assert_eq!(queue.front().unwrap(), None);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
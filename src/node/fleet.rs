@@ -0,0 +1,715 @@
+//! Two-tier deployments in which vehicle-local dataflows forward selected streams to a cloud
+//! aggregation node.
+//!
+//! Each vehicle runs its own ERDOS cluster, which keeps running when the connection to the cloud
+//! is lost. A [`FleetUplink`] forwards the messages of the streams extracted from the vehicle's
+//! dataflow to the aggregation node over Zenoh, according to a [`ForwardingPolicy`] per
+//! stream, and buffers them in memory or on disk while the aggregation node is unreachable. The
+//! aggregation node receives the messages of all vehicles with a [`FleetAggregator`].
+//!
+//! Messages are published on `/erdos/fleet/<vehicle>/<stream name>`.
+//!
+//! # Example
+//! ```no_run
+//! # use erdos::dataflow::{stream::{ExtractStream, IngestStream}, Message, Timestamp};
+//! # use erdos::node::fleet::{FleetAggregator, FleetUplink, ForwardingPolicy};
+//! // On the vehicle.
+//! # let mut ingest_stream: IngestStream<u32> = IngestStream::new(0);
+//! let uplink = FleetUplink::new(
+//!     zenoh::net::config::client(Some("tcp/10.0.0.1:7447".to_string())),
+//!     "vehicle-7",
+//! );
+//! let extract_stream =
+//!     ExtractStream::new_with_name(0, &(&ingest_stream).into(), "detections");
+//! uplink.forward(extract_stream, ForwardingPolicy::new().sample_every(10).compress(3));
+//!
+//! // On the aggregation node.
+//! let aggregator = FleetAggregator::new(zenoh::net::config::peer());
+//! let detections = aggregator.subscribe::<u32>("detections");
+//! let (vehicle, msg): (String, Message<u32>) = detections.read().unwrap();
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    marker::PhantomData,
+    path::PathBuf,
+    sync::{mpsc, Arc},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures::StreamExt;
+use serde::Deserialize;
+use tokio::sync::mpsc as tokio_mpsc;
+use zenoh::net::{self, config::ConfigProperties};
+
+use crate::{
+    dataflow::{
+        stream::{
+            errors::{ReadError, TryReadError},
+            ExtractStream,
+        },
+        Data, Message,
+    },
+    node::disk_queue::DiskQueue,
+};
+
+const FLEET_PREFIX: &str = "/erdos/fleet";
+
+/// Path of the queryable with which the aggregation node signals that it is reachable.
+const AGGREGATOR_PATH: &str = "/erdos/fleet/aggregator";
+
+/// Time between the checks whether the aggregation node is reachable.
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Prefixes of the forwarded payloads, which tell whether they are compressed with zstd.
+const COMPRESSED: u8 = 1;
+const UNCOMPRESSED: u8 = 0;
+
+/// Configures how a stream is forwarded to the aggregation node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForwardingPolicy {
+    /// Forwards one in every `sample_every` data messages. Watermarks are always forwarded.
+    pub sample_every: usize,
+    /// The zstd compression level of the forwarded messages, if they are compressed.
+    pub compression_level: Option<i32>,
+    /// Maximum number of messages buffered while the aggregation node is unreachable. The
+    /// oldest messages are dropped once the buffer is full.
+    pub buffer_capacity: usize,
+    /// Maximum total size of the buffered messages, if any.
+    pub max_buffer_bytes: Option<usize>,
+    /// Buffered messages older than the maximum age are dropped, if any.
+    pub max_age: Option<Duration>,
+    /// Directory in which the messages are buffered, if they are buffered on disk rather than
+    /// in memory. Messages buffered on disk are sent after the process restarts.
+    pub spool_directory: Option<PathBuf>,
+}
+
+impl ForwardingPolicy {
+    /// Forwards all messages uncompressed, and buffers up to 1000 messages in memory.
+    pub fn new() -> Self {
+        Self {
+            sample_every: 1,
+            compression_level: None,
+            buffer_capacity: 1000,
+            max_buffer_bytes: None,
+            max_age: None,
+            spool_directory: None,
+        }
+    }
+
+    /// Forwards one in every `n` data messages.
+    pub fn sample_every(mut self, n: usize) -> Self {
+        assert!(n > 0, "The sampling interval must be greater than 0");
+        self.sample_every = n;
+        self
+    }
+
+    /// Compresses the forwarded messages with the zstd compression level.
+    ///
+    /// Panics if the `compression` feature is disabled.
+    pub fn compress(mut self, level: i32) -> Self {
+        assert!(
+            cfg!(feature = "compression"),
+            "Unable to compress the forwarded messages: the compression feature is disabled"
+        );
+        self.compression_level = Some(level);
+        self
+    }
+
+    /// Sets the maximum number of messages buffered while the aggregation node is unreachable.
+    pub fn buffer_capacity(mut self, capacity: usize) -> Self {
+        self.buffer_capacity = capacity;
+        self
+    }
+
+    /// Sets the maximum total size of the messages buffered while the aggregation node is
+    /// unreachable.
+    pub fn max_buffer_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_buffer_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Drops the buffered messages which could not be sent within the maximum age.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Buffers the messages in a file in the directory, so that they are sent in order once the
+    /// aggregation node is reachable again, even if the process restarts in the meantime.
+    pub fn spool_to<P: Into<PathBuf>>(mut self, directory: P) -> Self {
+        self.spool_directory = Some(directory.into());
+        self
+    }
+}
+
+impl Default for ForwardingPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Selects the messages of a stream which are forwarded.
+struct Sampler {
+    sample_every: usize,
+    /// Number of data messages received.
+    num_data_msgs: usize,
+}
+
+impl Sampler {
+    fn new(sample_every: usize) -> Self {
+        Self {
+            sample_every,
+            num_data_msgs: 0,
+        }
+    }
+
+    /// Returns true if the message should be forwarded.
+    fn sample<D: Data>(&mut self, msg: &Message<D>) -> bool {
+        if let Message::TimestampedData(_) = msg {
+            self.num_data_msgs += 1;
+            (self.num_data_msgs - 1) % self.sample_every == 0
+        } else {
+            true
+        }
+    }
+}
+
+/// Where a [`ForwardBuffer`] keeps the messages.
+enum Storage {
+    /// The messages with the times at which they were buffered, and their total size.
+    Memory(VecDeque<(u64, Vec<u8>)>, usize),
+    Disk(DiskQueue),
+}
+
+/// Holds the serialized messages of a stream until they are sent to the aggregation node, and
+/// evicts the oldest messages according to the [`ForwardingPolicy`].
+struct ForwardBuffer {
+    capacity: usize,
+    max_bytes: Option<usize>,
+    max_age: Option<Duration>,
+    storage: Storage,
+    /// Number of messages dropped because the buffer was full or the messages expired.
+    num_dropped: usize,
+}
+
+impl ForwardBuffer {
+    /// Creates a buffer for the stream forwarded to the path, which is stored in the spool
+    /// directory of the policy if any.
+    ///
+    /// Messages buffered on disk by a previous run are sent first.
+    fn new(policy: &ForwardingPolicy, path: &str) -> Self {
+        let storage = match &policy.spool_directory {
+            Some(directory) => {
+                let file = directory.join(format!(
+                    "{}.queue",
+                    path.trim_start_matches('/').replace('/', "_")
+                ));
+                match DiskQueue::open(&file) {
+                    Ok(queue) => Storage::Disk(queue),
+                    Err(e) => {
+                        slog::error!(
+                            crate::TERMINAL_LOGGER,
+                            "FleetUplink: unable to open {}, buffering {} in memory: {}",
+                            file.display(),
+                            path,
+                            e
+                        );
+                        Storage::Memory(VecDeque::new(), 0)
+                    }
+                }
+            }
+            None => Storage::Memory(VecDeque::new(), 0),
+        };
+        Self {
+            capacity: policy.buffer_capacity,
+            max_bytes: policy.max_buffer_bytes,
+            max_age: policy.max_age,
+            storage,
+            num_dropped: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match &self.storage {
+            Storage::Memory(msgs, _) => msgs.len(),
+            Storage::Disk(queue) => queue.len(),
+        }
+    }
+
+    fn num_bytes(&self) -> usize {
+        match &self.storage {
+            Storage::Memory(_, num_bytes) => *num_bytes,
+            Storage::Disk(queue) => queue.num_bytes(),
+        }
+    }
+
+    fn front_time(&self) -> Option<u64> {
+        match &self.storage {
+            Storage::Memory(msgs, _) => msgs.front().map(|(time, _)| *time),
+            Storage::Disk(queue) => queue.front_time(),
+        }
+    }
+
+    /// Buffers a message at the time, in milliseconds since the Unix epoch.
+    fn push(&mut self, payload: Vec<u8>, now: u64) -> io::Result<()> {
+        while self.len() > 0
+            && (self.len() >= self.capacity
+                || self
+                    .max_bytes
+                    .map_or(false, |max| self.num_bytes() + payload.len() > max))
+        {
+            self.pop_front()?;
+            self.num_dropped += 1;
+        }
+        match &mut self.storage {
+            Storage::Memory(msgs, num_bytes) => {
+                *num_bytes += payload.len();
+                msgs.push_back((now, payload));
+                Ok(())
+            }
+            Storage::Disk(queue) => queue.push(now, &payload),
+        }
+    }
+
+    /// Returns the oldest message which did not expire at the time.
+    fn front(&mut self, now: u64) -> io::Result<Option<Vec<u8>>> {
+        if let Some(max_age) = self.max_age {
+            let max_age = max_age.as_millis() as u64;
+            while self.front_time().map_or(false, |time| time + max_age < now) {
+                self.pop_front()?;
+                self.num_dropped += 1;
+            }
+        }
+        match &mut self.storage {
+            Storage::Memory(msgs, _) => Ok(msgs.front().map(|(_, payload)| payload.clone())),
+            Storage::Disk(queue) => queue.front(),
+        }
+    }
+
+    fn pop_front(&mut self) -> io::Result<()> {
+        match &mut self.storage {
+            Storage::Memory(msgs, num_bytes) => {
+                if let Some((_, payload)) = msgs.pop_front() {
+                    *num_bytes -= payload.len();
+                }
+                Ok(())
+            }
+            Storage::Disk(queue) => queue.pop_front(),
+        }
+    }
+}
+
+/// Returns the current time in milliseconds since the Unix epoch.
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn encode<D: Data>(msg: &Message<D>, compression_level: Option<i32>) -> Option<Vec<u8>> {
+    let bytes = bincode::serialize(msg).ok()?;
+    match compression_level {
+        #[cfg(feature = "compression")]
+        Some(level) => {
+            let mut payload = vec![COMPRESSED];
+            payload.extend(zstd::stream::encode_all(&bytes[..], level).ok()?);
+            Some(payload)
+        }
+        #[cfg(not(feature = "compression"))]
+        Some(_) => None,
+        None => {
+            let mut payload = vec![UNCOMPRESSED];
+            payload.extend(bytes);
+            Some(payload)
+        }
+    }
+}
+
+fn decode<D>(payload: &[u8]) -> Result<Message<D>, ReadError>
+where
+    for<'a> D: Data + Deserialize<'a>,
+{
+    match payload.split_first() {
+        #[cfg(feature = "compression")]
+        Some((&COMPRESSED, bytes)) => {
+            let bytes =
+                zstd::stream::decode_all(bytes).map_err(|_| ReadError::SerializationError)?;
+            bincode::deserialize(&bytes).map_err(|_| ReadError::SerializationError)
+        }
+        // Compressed payloads cannot be decoded without the compression feature.
+        #[cfg(not(feature = "compression"))]
+        Some((&COMPRESSED, _)) => Err(ReadError::SerializationError),
+        Some((&UNCOMPRESSED, bytes)) => {
+            bincode::deserialize(bytes).map_err(|_| ReadError::SerializationError)
+        }
+        _ => Err(ReadError::SerializationError),
+    }
+}
+
+/// Sent by a [`FleetUplink`] and its forwarded streams to the task which sends the messages.
+enum UplinkMessage {
+    /// Starts forwarding a stream to the path.
+    Forward(String, ForwardingPolicy),
+    /// A serialized message of the stream forwarded to the path.
+    Payload(String, Vec<u8>),
+}
+
+/// Forwards streams of a vehicle-local dataflow to the aggregation node.
+pub struct FleetUplink {
+    vehicle: String,
+    tx: tokio_mpsc::UnboundedSender<UplinkMessage>,
+}
+
+impl FleetUplink {
+    /// Connects to the aggregation node with the Zenoh configuration, e.g. a client
+    /// configuration with the locator of the aggregation node.
+    pub fn new(zconfig: ConfigProperties, vehicle: &str) -> Self {
+        let (tx, rx) = tokio_mpsc::unbounded_channel();
+        let vehicle_copy = vehicle.to_string();
+        thread::spawn(move || {
+            let mut runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(run_uplink(zconfig, vehicle_copy, rx));
+        });
+        Self {
+            vehicle: vehicle.to_string(),
+            tx,
+        }
+    }
+
+    /// Forwards the messages of the extract stream to the aggregation node under the name of the
+    /// extract stream.
+    ///
+    /// Must be called before running the node.
+    pub fn forward<D>(&self, mut extract_stream: ExtractStream<D>, policy: ForwardingPolicy)
+    where
+        for<'a> D: Data + Deserialize<'a>,
+    {
+        let path = format!(
+            "{}/{}/{}",
+            FLEET_PREFIX,
+            self.vehicle,
+            extract_stream.get_name()
+        );
+        let mut sampler = Sampler::new(policy.sample_every);
+        let compression_level = policy.compression_level;
+        if self
+            .tx
+            .send(UplinkMessage::Forward(path.clone(), policy))
+            .is_err()
+        {
+            slog::error!(
+                crate::TERMINAL_LOGGER,
+                "FleetUplink {}: unable to forward {} because the uplink failed",
+                self.vehicle,
+                extract_stream.get_name()
+            );
+            return;
+        }
+        let tx = self.tx.clone();
+        thread::spawn(move || {
+            while let Ok(msg) = extract_stream.read() {
+                if !sampler.sample(&msg) {
+                    continue;
+                }
+                match encode(&msg, compression_level) {
+                    Some(payload) => {
+                        if tx
+                            .send(UplinkMessage::Payload(path.clone(), payload))
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                    None => slog::warn!(
+                        crate::TERMINAL_LOGGER,
+                        "FleetUplink: unable to serialize a message of {}",
+                        extract_stream.get_name()
+                    ),
+                }
+            }
+        });
+    }
+}
+
+/// Buffers the messages of the forwarded streams, and sends them while the aggregation node is
+/// reachable.
+async fn run_uplink(
+    zconfig: ConfigProperties,
+    vehicle: String,
+    mut rx: tokio_mpsc::UnboundedReceiver<UplinkMessage>,
+) {
+    let zsession = match net::open(zconfig).await {
+        Ok(zsession) => zsession,
+        Err(e) => {
+            slog::error!(
+                crate::TERMINAL_LOGGER,
+                "FleetUplink {}: unable to open a Zenoh session: {:?}",
+                vehicle,
+                e
+            );
+            return;
+        }
+    };
+    let mut buffers: HashMap<String, ForwardBuffer> = HashMap::new();
+    let mut connected = false;
+    let mut probe = tokio::time::interval(PROBE_INTERVAL);
+    loop {
+        tokio::select! {
+            msg = rx.recv() => match msg {
+                Some(UplinkMessage::Forward(path, policy)) => {
+                    let buffer = ForwardBuffer::new(&policy, &path);
+                    buffers.insert(path, buffer);
+                }
+                Some(UplinkMessage::Payload(path, payload)) => {
+                    if let Some(buffer) = buffers.get_mut(&path) {
+                        if let Err(e) = buffer.push(payload, now_millis()) {
+                            slog::error!(
+                                crate::TERMINAL_LOGGER,
+                                "FleetUplink {}: unable to buffer a message of {}: {}",
+                                vehicle,
+                                path,
+                                e
+                            );
+                        }
+                    }
+                }
+                // The uplink and the forwarded streams were dropped.
+                None => return,
+            },
+            _ = probe.tick() => {
+                let was_connected = connected;
+                connected = is_aggregator_reachable(&zsession).await;
+                if was_connected != connected {
+                    slog::info!(
+                        crate::TERMINAL_LOGGER,
+                        "FleetUplink {}: the aggregation node is {}",
+                        vehicle,
+                        if connected { "reachable" } else { "unreachable" }
+                    );
+                }
+            }
+        }
+        for (path, buffer) in buffers.iter_mut() {
+            while connected {
+                let payload = match buffer.front(now_millis()) {
+                    Ok(Some(payload)) => payload,
+                    Ok(None) => break,
+                    Err(e) => {
+                        slog::error!(
+                            crate::TERMINAL_LOGGER,
+                            "FleetUplink {}: unable to read the buffered messages of {}: {}",
+                            vehicle,
+                            path,
+                            e
+                        );
+                        break;
+                    }
+                };
+                match zsession.write(&path.clone().into(), payload.into()).await {
+                    Ok(()) => {
+                        if let Err(e) = buffer.pop_front() {
+                            slog::error!(
+                                crate::TERMINAL_LOGGER,
+                                "FleetUplink {}: unable to remove a sent message of {}: {}",
+                                vehicle,
+                                path,
+                                e
+                            );
+                            break;
+                        }
+                    }
+                    // Keep the message until the aggregation node is reachable again.
+                    Err(_) => connected = false,
+                }
+            }
+        }
+    }
+}
+
+async fn is_aggregator_reachable(zsession: &net::Session) -> bool {
+    let replies = zsession
+        .query(
+            &AGGREGATOR_PATH.into(),
+            "",
+            net::protocol::core::QueryTarget::default(),
+            net::protocol::core::QueryConsolidation::default(),
+        )
+        .await;
+    match replies {
+        Ok(mut replies) => matches!(
+            tokio::time::timeout(PROBE_INTERVAL, replies.next()).await,
+            Ok(Some(_))
+        ),
+        Err(_) => false,
+    }
+}
+
+/// Receives the streams forwarded by the vehicles on the aggregation node.
+pub struct FleetAggregator {
+    zsession: Arc<net::Session>,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl FleetAggregator {
+    /// Opens a Zenoh session with the configuration, e.g. a peer configuration which listens on
+    /// the locator to which the vehicles connect.
+    pub fn new(zconfig: ConfigProperties) -> Self {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let zsession = Arc::new(
+            runtime
+                .block_on(net::open(zconfig))
+                .expect("FleetAggregator: unable to open a Zenoh session"),
+        );
+        // Signal to the vehicles that the aggregation node is reachable.
+        let queryable_session = Arc::clone(&zsession);
+        runtime.spawn(async move {
+            let mut queryable = match queryable_session
+                .declare_queryable(&AGGREGATOR_PATH.into(), net::queryable::EVAL)
+                .await
+            {
+                Ok(queryable) => queryable,
+                Err(e) => {
+                    slog::error!(
+                        crate::TERMINAL_LOGGER,
+                        "FleetAggregator: unable to declare a queryable: {:?}",
+                        e
+                    );
+                    return;
+                }
+            };
+            while let Some(zquery) = queryable.stream().next().await {
+                zquery
+                    .reply(net::Sample {
+                        res_name: AGGREGATOR_PATH.to_string(),
+                        payload: (&b"ok"[..]).into(),
+                        data_info: None,
+                    })
+                    .await;
+            }
+        });
+        Self { zsession, runtime }
+    }
+
+    /// Receives the messages of the stream with the name from all vehicles.
+    pub fn subscribe<D>(&self, stream_name: &str) -> FleetReceiver<D>
+    where
+        for<'a> D: Data + Deserialize<'a>,
+    {
+        let (tx, rx) = mpsc::channel();
+        let path = format!("{}/*/{}", FLEET_PREFIX, stream_name);
+        let zsession = Arc::clone(&self.zsession);
+        self.runtime.spawn(async move {
+            let sub_info = net::SubInfo {
+                reliability: net::protocol::core::Reliability::Reliable,
+                mode: net::protocol::core::SubMode::Push,
+                period: None,
+            };
+            let mut subscriber = match zsession.declare_subscriber(&path.into(), &sub_info).await {
+                Ok(subscriber) => subscriber,
+                Err(e) => {
+                    slog::error!(
+                        crate::TERMINAL_LOGGER,
+                        "FleetAggregator: unable to subscribe: {:?}",
+                        e
+                    );
+                    return;
+                }
+            };
+            while let Some(sample) = subscriber.stream().next().await {
+                // The path is /erdos/fleet/<vehicle>/<stream name>.
+                let vehicle = sample
+                    .res_name
+                    .trim_start_matches(FLEET_PREFIX)
+                    .split('/')
+                    .nth(1)
+                    .unwrap_or_default()
+                    .to_string();
+                if tx.send((vehicle, sample.payload.to_vec())).is_err() {
+                    return;
+                }
+            }
+        });
+        FleetReceiver {
+            rx,
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Receives the messages of a stream forwarded by the vehicles, along with the name of the
+/// vehicle which sent them.
+pub struct FleetReceiver<D> {
+    rx: mpsc::Receiver<(String, Vec<u8>)>,
+    phantom: PhantomData<D>,
+}
+
+impl<D> FleetReceiver<D>
+where
+    for<'a> D: Data + Deserialize<'a>,
+{
+    /// Blocks until a message is received.
+    pub fn read(&self) -> Result<(String, Message<D>), ReadError> {
+        let (vehicle, payload) = self.rx.recv().map_err(|_| ReadError::Disconnected)?;
+        Ok((vehicle, decode(&payload)?))
+    }
+
+    /// Returns a message if one was received, or [`TryReadError::Empty`] otherwise.
+    pub fn try_read(&self) -> Result<(String, Message<D>), TryReadError> {
+        let (vehicle, payload) = self.rx.try_recv().map_err(|e| match e {
+            mpsc::TryRecvError::Empty => TryReadError::Empty,
+            mpsc::TryRecvError::Disconnected => TryReadError::Disconnected,
+        })?;
+        let msg = decode(&payload).map_err(|_| TryReadError::SerializationError)?;
+        Ok((vehicle, msg))
+    }
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use super::*;
+
+    use crate::dataflow::Timestamp;
+
+    /// Test that data messages are sampled, that the oldest messages are dropped once the buffer
+    /// is full or they expire, and that compressed messages are decoded.
+    #[test]
+    fn test_forward_buffer() {
+        let policy = ForwardingPolicy::new()
+            .sample_every(2)
+            .compress(3)
+            .buffer_capacity(2);
+        let mut sampler = Sampler::new(policy.sample_every);
+        let mut sampled = Vec::new();
+        for t in 0..4 {
+            let msg = Message::new_message(Timestamp::new(vec![t]), t);
+            if sampler.sample(&msg) {
+                sampled.push(t);
+            }
+            assert!(sampler.sample(&Message::<u64>::new_watermark(Timestamp::new(vec![t]))));
+        }
+        assert_eq!(sampled, vec![0, 2]);
+
+        let policy = policy.max_age(Duration::from_millis(10));
+        let mut buffer = ForwardBuffer::new(&policy, "/erdos/fleet/vehicle/stream");
+        for t in 0..3 {
+            let msg = Message::new_message(Timestamp::new(vec![t]), t);
+            let payload = encode(&msg, policy.compression_level).unwrap();
+            buffer.push(payload, t).unwrap();
+        }
+        assert_eq!(buffer.num_dropped, 1);
+        assert_eq!(
+            decode::<u64>(&buffer.front(5).unwrap().unwrap()),
+            Ok(Message::new_message(Timestamp::new(vec![1]), 1))
+        );
+        // The message buffered at time 1 expired.
+        assert_eq!(
+            decode::<u64>(&buffer.front(12).unwrap().unwrap()),
+            Ok(Message::new_message(Timestamp::new(vec![2]), 2))
+        );
+        assert_eq!(buffer.num_dropped, 2);
+    }
+}
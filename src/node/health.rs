@@ -0,0 +1,266 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Debug,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::node::NodeContext;
+
+/// Tracks the callbacks an operator is running, in order to detect stalled operators.
+#[derive(Default)]
+pub(crate) struct OperatorActivity {
+    /// Maps each event runner of the operator to the time at which its current callback started.
+    running: Mutex<HashMap<usize, Instant>>,
+    /// The ID of the last checkpoint the operator took, set while it takes checkpoints.
+    checkpoint_id: Mutex<Option<u64>>,
+}
+
+impl OperatorActivity {
+    pub fn start_callback(&self, event_runner: usize) {
+        self.running
+            .lock()
+            .unwrap()
+            .insert(event_runner, Instant::now());
+    }
+
+    pub fn finish_callback(&self, event_runner: usize) {
+        self.running.lock().unwrap().remove(&event_runner);
+    }
+
+    /// Reports that the operator takes the checkpoints requested after the checkpoint.
+    pub fn start_checkpoints(&self, checkpoint_id: u64) {
+        *self.checkpoint_id.lock().unwrap() = Some(checkpoint_id);
+    }
+
+    pub fn finish_checkpoint(&self, checkpoint_id: u64) {
+        if let Some(last_checkpoint_id) = self.checkpoint_id.lock().unwrap().as_mut() {
+            *last_checkpoint_id = std::cmp::max(*last_checkpoint_id, checkpoint_id);
+        }
+    }
+
+    /// Reports that the operator stopped running, and takes no more checkpoints.
+    pub fn stop_checkpoints(&self) {
+        *self.checkpoint_id.lock().unwrap() = None;
+    }
+
+    /// Returns the start time of the longest running callback.
+    fn running_since(&self) -> Option<Instant> {
+        self.running.lock().unwrap().values().min().cloned()
+    }
+
+    fn last_checkpoint_id(&self) -> Option<u64> {
+        *self.checkpoint_id.lock().unwrap()
+    }
+}
+
+struct HealthState {
+    stall_timeout: Duration,
+    ready: AtomicBool,
+    /// Describes which peers the node waits for while it sets up.
+    startup_status: Mutex<Option<String>>,
+    /// The first failure of the communication layer or of the dataflow.
+    failure: Mutex<Option<String>>,
+    operators: Mutex<Vec<(String, Arc<OperatorActivity>)>>,
+    /// The IDs and request times of the checkpoints which an operator has not taken yet.
+    checkpoint_requests: Mutex<VecDeque<(u64, Instant)>>,
+}
+
+/// Health of a [`Node`](crate::node::Node), reported on the `/healthz` and `/readyz` endpoints.
+///
+/// The node is ready once all nodes have set up their operators. It is unhealthy if the data or
+/// control senders or receivers fail, if the dataflow fails, if a callback runs for longer than
+/// the stall timeout, or if a stateful operator has not taken a checkpoint requested for longer
+/// than the stall timeout.
+#[derive(Clone)]
+pub(crate) struct NodeHealth {
+    state: Arc<HealthState>,
+    /// The state of the node, whose message counters are reported on the `/metrics` endpoint.
+    context: Arc<NodeContext>,
+}
+
+impl NodeHealth {
+    pub fn new(stall_timeout: Duration, context: Arc<NodeContext>) -> Self {
+        Self {
+            context,
+            state: Arc::new(HealthState {
+                stall_timeout,
+                ready: AtomicBool::new(false),
+                startup_status: Mutex::new(None),
+                failure: Mutex::new(None),
+                operators: Mutex::new(Vec::new()),
+                checkpoint_requests: Mutex::new(VecDeque::new()),
+            }),
+        }
+    }
+
+    pub fn set_ready(&self) {
+        self.state.ready.store(true, Ordering::SeqCst);
+    }
+
+    /// Sets the description of the peers which the node waits for, reported while it is not
+    /// ready.
+    pub fn set_startup_status(&self, status: String) {
+        *self.state.startup_status.lock().unwrap() = Some(status);
+    }
+
+    pub fn fail(&self, reason: String) {
+        let mut failure = self.state.failure.lock().unwrap();
+        if failure.is_none() {
+            *failure = Some(reason);
+        }
+    }
+
+    /// Returns the activity tracker of an operator running on the node.
+    pub fn add_operator(&self, name: &str) -> Arc<OperatorActivity> {
+        let activity = Arc::new(OperatorActivity::default());
+        self.state
+            .operators
+            .lock()
+            .unwrap()
+            .push((name.to_string(), Arc::clone(&activity)));
+        activity
+    }
+
+    /// Reports that the operators were asked to take the checkpoint.
+    pub fn request_checkpoint(&self, checkpoint_id: u64) {
+        let min_checkpoint_id = self
+            .state
+            .operators
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(_, activity)| activity.last_checkpoint_id())
+            .min();
+        let mut requests = self.state.checkpoint_requests.lock().unwrap();
+        // Forgets the requests which all operators took.
+        match min_checkpoint_id {
+            Some(min_checkpoint_id) => requests.retain(|(id, _)| *id > min_checkpoint_id),
+            None => requests.clear(),
+        }
+        requests.push_back((checkpoint_id, Instant::now()));
+    }
+
+    /// Marks the node as unhealthy if the component fails.
+    pub async fn watch<T, E, F>(&self, component: &str, fut: F) -> Result<T, E>
+    where
+        E: Debug,
+        F: Future<Output = Result<T, E>>,
+    {
+        let result = fut.await;
+        if let Err(e) = &result {
+            self.fail(format!("{} failed: {:?}", component, e));
+        }
+        result
+    }
+
+    /// Returns an error describing why the node is unhealthy.
+    pub fn check_liveness(&self) -> Result<(), String> {
+        if let Some(reason) = self.state.failure.lock().unwrap().as_ref() {
+            return Err(reason.clone());
+        }
+        for (name, activity) in self.state.operators.lock().unwrap().iter() {
+            if let Some(running_since) = activity.running_since() {
+                let running_for = running_since.elapsed();
+                if running_for > self.state.stall_timeout {
+                    return Err(format!(
+                        "Operator {} is stalled in a callback running for {:?}",
+                        name, running_for
+                    ));
+                }
+            }
+        }
+        let requests = self.state.checkpoint_requests.lock().unwrap();
+        for (name, activity) in self.state.operators.lock().unwrap().iter() {
+            let last_checkpoint_id = match activity.last_checkpoint_id() {
+                Some(last_checkpoint_id) => last_checkpoint_id,
+                None => continue,
+            };
+            // The requests are in order, so the first one not taken is the oldest.
+            if let Some((checkpoint_id, requested_at)) =
+                requests.iter().find(|(id, _)| *id > last_checkpoint_id)
+            {
+                let lag = requested_at.elapsed();
+                if lag > self.state.stall_timeout {
+                    return Err(format!(
+                        "Operator {} has not taken checkpoint {} requested {:?} ago",
+                        name, checkpoint_id, lag
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns an error describing why the node is not ready to process data.
+    pub fn check_readiness(&self) -> Result<(), String> {
+        if !self.state.ready.load(Ordering::SeqCst) {
+            return Err(match self.state.startup_status.lock().unwrap().as_ref() {
+                Some(status) => format!("The node is setting up: {}", status),
+                None => "The node is setting up".to_string(),
+            });
+        }
+        self.check_liveness()
+    }
+
+    /// Returns the status code and body of the response to a request for the path.
+    pub(crate) fn respond(&self, path: &str) -> (&'static str, String) {
+        let result = match path {
+            "/healthz" => self.check_liveness(),
+            "/readyz" => self.check_readiness(),
+            "/metrics" => {
+                return (
+                    "200 OK",
+                    crate::node::metrics::render(&self.context.counters),
+                )
+            }
+            _ => return ("404 Not Found", "Not found".to_string()),
+        };
+        match result {
+            Ok(()) => ("200 OK", "ok".to_string()),
+            Err(reason) => ("503 Service Unavailable", reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the node is ready once initialized, and unhealthy once a callback stalls or
+    /// checkpointing falls behind.
+    #[test]
+    fn test_health_checks() {
+        let health = NodeHealth::new(Duration::from_millis(10), Default::default());
+        let activity = health.add_operator("Stalled");
+        assert_eq!(health.respond("/healthz").0, "200 OK");
+        assert_eq!(health.respond("/readyz").0, "503 Service Unavailable");
+        assert_eq!(health.respond("/metrics").0, "200 OK");
+        assert_eq!(health.respond("/statusz").0, "404 Not Found");
+
+        health.set_ready();
+        assert_eq!(health.respond("/readyz").0, "200 OK");
+
+        activity.start_callback(0);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(health.respond("/healthz").0, "503 Service Unavailable");
+        activity.finish_callback(0);
+        assert_eq!(health.respond("/healthz").0, "200 OK");
+
+        activity.start_checkpoints(0);
+        health.request_checkpoint(1);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(health.respond("/healthz").0, "503 Service Unavailable");
+        activity.finish_checkpoint(1);
+        assert_eq!(health.respond("/healthz").0, "200 OK");
+
+        health.fail("Data senders failed".to_string());
+        let (status, body) = health.respond("/readyz");
+        assert_eq!(status, "503 Service Unavailable");
+        assert_eq!(body, "Data senders failed");
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, RwLock,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    communication::{to_serialized_bytes, CodecError, InterProcessMessage, MessageMetadata},
+    dataflow::stream::StreamId,
+};
+
+lazy_static! {
+    /// The streams whose latency is tracked on the current process.
+    static ref TRACKED_STREAMS: RwLock<HashSet<StreamId>> = RwLock::new(HashSet::new());
+    static ref BREAKDOWNS: Mutex<HashMap<StreamId, Accumulator>> = Mutex::new(HashMap::new());
+}
+
+/// Avoids locking the tracked streams for every message if no stream is tracked.
+static TRACKING: AtomicBool = AtomicBool::new(false);
+
+/// Timestamps collected while a message of a tracked stream is sent to another node, in
+/// microseconds since the Unix epoch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct HopTimings {
+    /// When the message was sent on the stream.
+    pub sent_at: u64,
+    /// When the data sender started to send the message.
+    pub dequeued_at: u64,
+    /// Microseconds the data sender spent serializing the message.
+    pub serialize_micros: u64,
+}
+
+/// Average latency of the messages of a stream, split into the time spent in each hop.
+///
+/// The components are averaged over the messages for which they were measured on the current
+/// process: the serialization, network, and deserialization components only apply to messages
+/// received from other nodes, and the network component relies on the clocks of the nodes being
+/// synchronized.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LatencyBreakdown {
+    /// Time the data sender spent serializing a message.
+    pub serialize: Duration,
+    /// Time between the serialization of a message and its receipt by the other node, including
+    /// compression, chunking, and the transfer.
+    pub network: Duration,
+    /// Time the data receiver spent deserializing a message and passing it to the operators.
+    pub deserialize: Duration,
+    /// Time a message waited to be processed, in the queue of the data sender and in the
+    /// operator before its callback started.
+    pub queue_wait: Duration,
+    /// Time the callbacks of the operator spent processing a message.
+    pub processing: Duration,
+    /// Number of messages received from other nodes.
+    pub num_transferred: u64,
+    /// Number of callbacks invoked for the messages.
+    pub num_processed: u64,
+}
+
+/// Identifies the message a callback processes, in order to attribute its latency.
+pub(crate) struct LatencyProbe {
+    pub stream_id: StreamId,
+    /// When the operator read the message from its stream.
+    pub read_at: Instant,
+}
+
+#[derive(Default)]
+struct Accumulator {
+    serialize_micros: u64,
+    network_micros: u64,
+    deserialize_micros: u64,
+    transferred_queue_micros: u64,
+    num_transferred: u64,
+    processed_queue_micros: u64,
+    processing_micros: u64,
+    num_processed: u64,
+}
+
+impl Accumulator {
+    fn breakdown(&self) -> LatencyBreakdown {
+        let mean = |micros: u64, count: u64| {
+            Duration::from_micros(if count == 0 { 0 } else { micros / count })
+        };
+        LatencyBreakdown {
+            serialize: mean(self.serialize_micros, self.num_transferred),
+            network: mean(self.network_micros, self.num_transferred),
+            deserialize: mean(self.deserialize_micros, self.num_transferred),
+            queue_wait: mean(self.transferred_queue_micros, self.num_transferred)
+                + mean(self.processed_queue_micros, self.num_processed),
+            processing: mean(self.processing_micros, self.num_processed),
+            num_transferred: self.num_transferred,
+            num_processed: self.num_processed,
+        }
+    }
+}
+
+fn now_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or(0)
+}
+
+pub(crate) fn track(stream_id: StreamId) {
+    TRACKED_STREAMS.write().unwrap().insert(stream_id);
+    TRACKING.store(true, Ordering::SeqCst);
+}
+
+pub(crate) fn is_tracked(stream_id: StreamId) -> bool {
+    TRACKING.load(Ordering::Relaxed) && TRACKED_STREAMS.read().unwrap().contains(&stream_id)
+}
+
+/// Returns the latency breakdown of the stream, or `None` if no message of the stream was
+/// measured on the current process.
+pub(crate) fn breakdown(stream_id: StreamId) -> Option<LatencyBreakdown> {
+    BREAKDOWNS
+        .lock()
+        .unwrap()
+        .get(&stream_id)
+        .map(Accumulator::breakdown)
+}
+
+/// Returns the timings of a message sent now on the stream, if the stream is tracked.
+pub(crate) fn sent_timings(stream_id: StreamId) -> Option<HopTimings> {
+    if is_tracked(stream_id) {
+        Some(HopTimings {
+            sent_at: now_micros(),
+            ..HopTimings::default()
+        })
+    } else {
+        None
+    }
+}
+
+/// Serializes a message of a tracked stream when the data sender starts to send it, in order to
+/// record the time spent serializing it.
+pub(crate) fn serialize_timed(msg: InterProcessMessage) -> Result<InterProcessMessage, CodecError> {
+    match msg {
+        InterProcessMessage::Deserialized { metadata, data } if metadata.timings.is_some() => {
+            let dequeued_at = now_micros();
+            let start = Instant::now();
+            let bytes = to_serialized_bytes(&data.encode_into_vec()?);
+            let mut metadata = metadata;
+            if let Some(timings) = metadata.timings.as_mut() {
+                timings.dequeued_at = dequeued_at;
+                timings.serialize_micros = start.elapsed().as_micros() as u64;
+            }
+            Ok(InterProcessMessage::new_serialized(bytes, metadata))
+        }
+        msg => Ok(msg),
+    }
+}
+
+/// Passes a message received from another node to the operators with `deliver`, and records the
+/// latency of the transfer if the stream is tracked.
+pub(crate) fn deliver_timed<T, F: FnOnce() -> T>(metadata: &MessageMetadata, deliver: F) -> T {
+    let timings = match metadata.timings {
+        Some(timings) => timings,
+        None => return deliver(),
+    };
+    let received_at = now_micros();
+    let start = Instant::now();
+    let result = deliver();
+    let deserialize_micros = start.elapsed().as_micros() as u64;
+    let mut breakdowns = BREAKDOWNS.lock().unwrap();
+    let accumulator = breakdowns.entry(metadata.stream_id).or_default();
+    accumulator.serialize_micros += timings.serialize_micros;
+    accumulator.network_micros += received_at
+        .saturating_sub(timings.dequeued_at)
+        .saturating_sub(timings.serialize_micros);
+    accumulator.deserialize_micros += deserialize_micros;
+    accumulator.transferred_queue_micros += timings.dequeued_at.saturating_sub(timings.sent_at);
+    accumulator.num_transferred += 1;
+    result
+}
+
+/// Records the latency of a callback which started at `start` and ran for `duration`.
+pub(crate) fn record_callback(probe: &LatencyProbe, start: Instant, duration: Duration) {
+    let mut breakdowns = BREAKDOWNS.lock().unwrap();
+    let accumulator = breakdowns.entry(probe.stream_id).or_default();
+    accumulator.processed_queue_micros += (start - probe.read_at).as_micros() as u64;
+    accumulator.processing_micros += duration.as_micros() as u64;
+    accumulator.num_processed += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the components are averaged over the messages for which they were measured.
+    #[test]
+    fn test_latency_breakdown() {
+        let accumulator = Accumulator {
+            serialize_micros: 40,
+            network_micros: 200,
+            deserialize_micros: 20,
+            transferred_queue_micros: 100,
+            num_transferred: 2,
+            processed_queue_micros: 30,
+            processing_micros: 300,
+            num_processed: 3,
+        };
+        let breakdown = accumulator.breakdown();
+        assert_eq!(breakdown.serialize, Duration::from_micros(20));
+        assert_eq!(breakdown.network, Duration::from_micros(100));
+        assert_eq!(breakdown.deserialize, Duration::from_micros(10));
+        assert_eq!(breakdown.queue_wait, Duration::from_micros(60));
+        assert_eq!(breakdown.processing, Duration::from_micros(100));
+        assert_eq!(
+            Accumulator::default().breakdown().processing,
+            Duration::from_secs(0)
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::{
+    dataflow::{graph::json_string, stream::StreamId, Timestamp},
+    node::{provenance::ProvenanceRecords, Origin, Provenance},
+};
+
+/// Maximum number of messages in a lineage. Bounds the time spent reconstructing the lineage of
+/// messages computed over long histories.
+const MAX_MESSAGES: usize = 1000;
+
+/// A message of a [`Lineage`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineageMessage {
+    pub message: Origin,
+    /// The provenance of the message, or `None` if it was not recorded on the node, e.g. because
+    /// the message was sent by a driver.
+    pub provenance: Option<Provenance>,
+}
+
+/// The messages and operators which contributed to a message, returned by
+/// [`NodeHandle::lineage`](crate::node::NodeHandle::lineage), e.g. to analyze which sensor
+/// readings and operators led to a decision of the planner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lineage {
+    /// The messages, starting from the output, where each message precedes its inputs.
+    pub messages: Vec<LineageMessage>,
+    /// Origins of the output which are not inputs of the messages, because the inputs of
+    /// messages received from other nodes are recorded on the nodes which sent them.
+    pub indirect_origins: Vec<Origin>,
+}
+
+impl Lineage {
+    /// Reconstructs the lineage of the messages of the stream with the timestamp from the
+    /// provenance recorded on the node.
+    pub(crate) fn new(
+        records: &ProvenanceRecords,
+        stream_id: StreamId,
+        timestamp: &Timestamp,
+    ) -> Option<Self> {
+        Self::from_records(
+            Origin {
+                stream_id,
+                timestamp: timestamp.clone(),
+            },
+            |origin| records.provenance(origin.stream_id, &origin.timestamp),
+        )
+    }
+
+    fn from_records<F: Fn(&Origin) -> Option<Provenance>>(
+        output: Origin,
+        lookup: F,
+    ) -> Option<Self> {
+        let output_provenance = lookup(&output)?;
+        let mut visited: HashSet<Origin> = HashSet::new();
+        visited.insert(output.clone());
+        let mut to_visit = VecDeque::new();
+        to_visit.push_back((output, Some(output_provenance.clone())));
+        let mut messages = Vec::new();
+        while let Some((message, provenance)) = to_visit.pop_front() {
+            if let Some(provenance) = &provenance {
+                for input in &provenance.inputs {
+                    if visited.len() < MAX_MESSAGES && visited.insert(input.clone()) {
+                        to_visit.push_back((input.clone(), lookup(input)));
+                    }
+                }
+            }
+            messages.push(LineageMessage {
+                message,
+                provenance,
+            });
+        }
+        let indirect_origins = output_provenance
+            .origins
+            .into_iter()
+            .filter(|origin| !visited.contains(origin))
+            .collect();
+        Some(Self {
+            messages,
+            indirect_origins,
+        })
+    }
+
+    /// Returns the lineage as a DOT graph, in which messages are ellipses, the callbacks of the
+    /// operators which sent them are boxes, and indirect origins are linked to the output with
+    /// dashed edges.
+    pub fn to_dot(&self) -> String {
+        let message_id =
+            |origin: &Origin| format!("{}@{:?}", origin.stream_id, origin.timestamp.time);
+        let message_label = |origin: &Origin| {
+            json_string(&format!(
+                "{}\n{:?}",
+                origin
+                    .stream_id
+                    .name()
+                    .unwrap_or_else(|| origin.stream_id.to_string()),
+                origin.timestamp.time
+            ))
+        };
+        let mut dot = String::from("digraph erdos_lineage {\n");
+        dot.push_str("   // Declare messages\n");
+        let indirect_origins = self.indirect_origins.iter();
+        for origin in self
+            .messages
+            .iter()
+            .map(|m| &m.message)
+            .chain(indirect_origins)
+        {
+            dot.push_str(&format!(
+                "   {} [label={}];\n",
+                json_string(&message_id(origin)),
+                message_label(origin)
+            ));
+        }
+        dot.push_str("   // Declare callbacks\n");
+        for message in &self.messages {
+            let provenance = match &message.provenance {
+                Some(provenance) => provenance,
+                None => continue,
+            };
+            let output_id = message_id(&message.message);
+            let callback_id = match provenance.operator {
+                Some(operator) => {
+                    let callback_id = format!("{}/{}", operator, output_id);
+                    dot.push_str(&format!(
+                        "   {} [shape=box, label={}];\n",
+                        json_string(&callback_id),
+                        json_string(&operator.name().unwrap_or_else(|| operator.to_string()))
+                    ));
+                    dot.push_str(&format!(
+                        "   {} -> {};\n",
+                        json_string(&callback_id),
+                        json_string(&output_id)
+                    ));
+                    callback_id
+                }
+                None => output_id,
+            };
+            for input in &provenance.inputs {
+                dot.push_str(&format!(
+                    "   {} -> {};\n",
+                    json_string(&message_id(input)),
+                    json_string(&callback_id)
+                ));
+            }
+        }
+        if let Some(output) = self.messages.first() {
+            for origin in &self.indirect_origins {
+                dot.push_str(&format!(
+                    "   {} -> {} [style=dashed];\n",
+                    json_string(&message_id(origin)),
+                    json_string(&message_id(&output.message))
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::OperatorId;
+
+    /// Test that the lineage of a message follows the inputs of the messages, and includes the
+    /// origins whose inputs were recorded on other nodes.
+    #[test]
+    fn test_lineage() {
+        let timestamp = Timestamp::new(vec![4]);
+        let origin = |stream_id| Origin {
+            stream_id,
+            timestamp: timestamp.clone(),
+        };
+        let (camera, lidar, detections, plan, remote) = (
+            StreamId::new_v4(),
+            StreamId::new_v4(),
+            StreamId::new_v4(),
+            StreamId::new_v4(),
+            StreamId::new_v4(),
+        );
+        let (detector, planner) = (OperatorId::new_v4(), OperatorId::new_v4());
+        let mut records = HashMap::new();
+        records.insert(
+            origin(detections),
+            Provenance {
+                timestamp: timestamp.clone(),
+                operator: Some(detector),
+                inputs: vec![origin(camera), origin(lidar)],
+                origins: vec![origin(camera), origin(lidar)],
+            },
+        );
+        records.insert(
+            origin(plan),
+            Provenance {
+                timestamp: timestamp.clone(),
+                operator: Some(planner),
+                inputs: vec![origin(detections)],
+                origins: vec![
+                    origin(detections),
+                    origin(camera),
+                    origin(lidar),
+                    origin(remote),
+                ],
+            },
+        );
+        let lineage = Lineage::from_records(origin(plan), |o| records.get(o).cloned()).unwrap();
+        let messages: Vec<_> = lineage.messages.iter().map(|m| m.message.clone()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                origin(plan),
+                origin(detections),
+                origin(camera),
+                origin(lidar)
+            ]
+        );
+        assert_eq!(lineage.messages[2].provenance, None);
+        assert_eq!(lineage.indirect_origins, vec![origin(remote)]);
+
+        let dot = lineage.to_dot();
+        // The detections are the input of the callback of the planner which sent the plan.
+        let planner_callback = format!("{}/{}@[4]", planner, plan);
+        assert!(dot.contains(&format!(
+            "\"{}@[4]\" -> \"{}\"",
+            detections, planner_callback
+        )));
+        assert!(dot.contains(&format!("\"{}\" -> \"{}@[4]\"", planner_callback, plan)));
+        assert!(dot.contains(&format!(
+            "\"{}@[4]\" -> \"{}@[4]\" [style=dashed]",
+            remote, plan
+        )));
+
+        assert!(Lineage::from_records(origin(camera), |o| records.get(o).cloned()).is_none());
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use lazy_static::lazy_static;
+use tokio::sync::Notify;
+
+use crate::{
+    dataflow::MemoryLimitPolicy,
+    node::metrics::{Counter, Gauge, OperatorMetrics},
+    OperatorId,
+};
+
+lazy_static! {
+    /// The memory trackers of the operators with a memory limit on the current process.
+    static ref TRACKERS: Mutex<HashMap<OperatorId, Arc<MemoryTracker>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Tracks the memory used by an operator against its
+/// [memory limit](crate::dataflow::OperatorConfig::with_memory_limit).
+///
+/// The usage is exported as the `erdos_operator_memory_bytes` gauge of the operator.
+pub(crate) struct MemoryTracker {
+    limit: u64,
+    policy: MemoryLimitPolicy,
+    /// Size of the messages whose callbacks have not run.
+    buffered_bytes: AtomicU64,
+    /// Size of the state reported by the operator.
+    state_bytes: AtomicU64,
+    /// Whether the usage was over the limit when last checked.
+    exceeded: AtomicBool,
+    /// Notified when the usage decreases.
+    released: Notify,
+    usage: Gauge,
+    dropped_messages: Counter,
+}
+
+impl MemoryTracker {
+    fn new(operator_name: &str, limit: u64, policy: MemoryLimitPolicy) -> Self {
+        let metrics = OperatorMetrics::new(operator_name);
+        Self {
+            limit,
+            policy,
+            buffered_bytes: AtomicU64::new(0),
+            state_bytes: AtomicU64::new(0),
+            exceeded: AtomicBool::new(false),
+            released: Notify::new(),
+            usage: metrics.gauge("erdos_operator_memory_bytes"),
+            dropped_messages: metrics.counter("erdos_memory_limit_dropped_messages_total"),
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn policy(&self) -> MemoryLimitPolicy {
+        self.policy
+    }
+
+    pub fn usage(&self) -> u64 {
+        self.buffered_bytes.load(Ordering::SeqCst) + self.state_bytes.load(Ordering::SeqCst)
+    }
+
+    pub fn buffered_bytes(&self) -> u64 {
+        self.buffered_bytes.load(Ordering::SeqCst)
+    }
+
+    pub fn is_exceeded(&self) -> bool {
+        self.usage() > self.limit
+    }
+
+    /// Returns the usage if it went over the limit since the last check.
+    pub fn newly_exceeded(&self) -> Option<u64> {
+        let usage = self.usage();
+        let exceeded = usage > self.limit;
+        if self.exceeded.swap(exceeded, Ordering::SeqCst) != exceeded && exceeded {
+            Some(usage)
+        } else {
+            None
+        }
+    }
+
+    /// Counts a message buffered for the callbacks of the operator.
+    pub fn buffer(&self, bytes: u64) {
+        self.buffered_bytes.fetch_add(bytes, Ordering::SeqCst);
+        self.usage.set(self.usage() as f64);
+    }
+
+    /// Counts that the callbacks of a buffered message ran.
+    pub fn release(&self, bytes: u64) {
+        self.buffered_bytes.fetch_sub(bytes, Ordering::SeqCst);
+        self.usage.set(self.usage() as f64);
+        self.released.notify();
+    }
+
+    pub fn set_state_bytes(&self, bytes: u64) {
+        self.state_bytes.store(bytes, Ordering::SeqCst);
+        self.usage.set(self.usage() as f64);
+        self.released.notify();
+    }
+
+    /// Waits until the usage may have decreased since the last wait, which returns immediately if
+    /// it was released in the meantime.
+    pub async fn wait_for_release(&self) {
+        self.released.notified().await;
+    }
+
+    /// Counts a message dropped by the [`MemoryLimitPolicy::DropMessages`] policy.
+    pub fn message_dropped(&self) {
+        self.dropped_messages.increment();
+    }
+}
+
+/// Tracks the memory used by the operator against the limit.
+pub(crate) fn register(
+    operator_id: OperatorId,
+    operator_name: &str,
+    limit: u64,
+    policy: MemoryLimitPolicy,
+) -> Arc<MemoryTracker> {
+    let tracker = Arc::new(MemoryTracker::new(operator_name, limit, policy));
+    TRACKERS
+        .lock()
+        .unwrap()
+        .insert(operator_id, Arc::clone(&tracker));
+    tracker
+}
+
+/// Stops tracking the memory used by the operator once it finished.
+pub(crate) fn unregister(operator_id: OperatorId) {
+    TRACKERS.lock().unwrap().remove(&operator_id);
+}
+
+/// Sets the size of the state of the operator, if its memory is limited.
+pub(crate) fn set_state_bytes(operator_id: OperatorId, bytes: u64) {
+    if let Some(tracker) = TRACKERS.lock().unwrap().get(&operator_id) {
+        tracker.set_state_bytes(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the buffered messages and the reported state count against the limit, which is
+    /// exceeded once until the usage is within the limit again.
+    #[test]
+    fn test_memory_tracker() {
+        let operator_id = OperatorId::new_v4();
+        let tracker = register(
+            operator_id,
+            "MemoryLimitTestOp",
+            100,
+            MemoryLimitPolicy::FailOperator,
+        );
+        tracker.buffer(60);
+        set_state_bytes(operator_id, 30);
+        assert_eq!(tracker.usage(), 90);
+        assert_eq!(tracker.newly_exceeded(), None);
+
+        tracker.buffer(20);
+        assert!(tracker.is_exceeded());
+        assert_eq!(tracker.newly_exceeded(), Some(110));
+        assert_eq!(tracker.newly_exceeded(), None);
+
+        tracker.release(60);
+        assert_eq!(tracker.buffered_bytes(), 20);
+        assert_eq!(tracker.newly_exceeded(), None);
+        set_state_bytes(operator_id, 200);
+        assert_eq!(tracker.newly_exceeded(), Some(220));
+    }
+
+    /// Test that an operator waiting for memory is woken by releases made before it waits, and
+    /// that the state of unregistered operators is no longer tracked.
+    #[test]
+    fn test_wait_for_release() {
+        let operator_id = OperatorId::new_v4();
+        let tracker = register(
+            operator_id,
+            "MemoryLimitTestOp",
+            10,
+            MemoryLimitPolicy::Backpressure,
+        );
+        tracker.buffer(20);
+        tracker.release(20);
+        futures::executor::block_on(tracker.wait_for_release());
+
+        unregister(operator_id);
+        set_state_bytes(operator_id, 30);
+        assert_eq!(tracker.usage(), 0);
+    }
+}
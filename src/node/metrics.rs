@@ -0,0 +1,330 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use lazy_static::lazy_static;
+
+use crate::node::quiescence::MessageCounters;
+
+/// Upper bounds of the buckets of histograms registered without buckets.
+pub const DEFAULT_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+lazy_static! {
+    /// The metrics registered on the current process, by name and label, e.g.
+    /// `operator="Detector"`.
+    static ref REGISTRY: Mutex<BTreeMap<(String, String), Metric>> = Mutex::new(BTreeMap::new());
+}
+
+#[derive(Clone)]
+enum Metric {
+    Counter(Counter),
+    Gauge(Gauge),
+    Histogram(Histogram),
+}
+
+impl Metric {
+    fn kind(&self) -> &'static str {
+        match self {
+            Metric::Counter(_) => "counter",
+            Metric::Gauge(_) => "gauge",
+            Metric::Histogram(_) => "histogram",
+        }
+    }
+}
+
+/// A value which only increases, e.g. the number of processed messages.
+#[derive(Clone, Default)]
+pub struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, amount: u64) {
+        self.0.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A value which can increase and decrease, e.g. the size of a buffer.
+#[derive(Clone, Default)]
+pub struct Gauge(Arc<Mutex<f64>>);
+
+impl Gauge {
+    pub fn set(&self, value: f64) {
+        *self.0.lock().unwrap() = value;
+    }
+
+    pub fn add(&self, amount: f64) {
+        *self.0.lock().unwrap() += amount;
+    }
+
+    pub fn get(&self) -> f64 {
+        *self.0.lock().unwrap()
+    }
+}
+
+struct HistogramData {
+    /// Upper bounds of the buckets, in increasing order.
+    bounds: Vec<f64>,
+    /// Number of observations in each bucket, which are not cumulative.
+    counts: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+
+/// Counts observations, e.g. durations in seconds, in buckets.
+#[derive(Clone)]
+pub struct Histogram(Arc<Mutex<HistogramData>>);
+
+impl Histogram {
+    /// Creates a histogram with the upper bounds of its buckets.
+    pub fn new(buckets: &[f64]) -> Self {
+        let mut bounds = buckets.to_vec();
+        bounds.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let counts = vec![0; bounds.len()];
+        Self(Arc::new(Mutex::new(HistogramData {
+            bounds,
+            counts,
+            count: 0,
+            sum: 0.0,
+        })))
+    }
+
+    pub fn observe(&self, value: f64) {
+        let mut data = self.0.lock().unwrap();
+        if let Some(bucket) = data.bounds.iter().position(|bound| value <= *bound) {
+            data.counts[bucket] += 1;
+        }
+        data.count += 1;
+        data.sum += value;
+    }
+
+    /// Returns the number of observations.
+    pub fn count(&self) -> u64 {
+        self.0.lock().unwrap().count
+    }
+
+    /// Returns the sum of the observations.
+    pub fn sum(&self) -> f64 {
+        self.0.lock().unwrap().sum
+    }
+
+    /// Returns the upper bound of each bucket with the number of observations lower than or
+    /// equal to it.
+    pub fn buckets(&self) -> Vec<(f64, u64)> {
+        let data = self.0.lock().unwrap();
+        let mut cumulative_count = 0;
+        data.bounds
+            .iter()
+            .zip(data.counts.iter())
+            .map(|(bound, count)| {
+                cumulative_count += count;
+                (*bound, cumulative_count)
+            })
+            .collect()
+    }
+}
+
+/// Registers the metrics of an operator, which are exported by the node alongside its runtime
+/// metrics with a label for the name of the operator.
+///
+/// Registering a metric again returns the registered metric. Metrics names must be valid
+/// Prometheus metric names, and the metrics of all operators with the same name must have the
+/// same type.
+#[derive(Clone)]
+pub struct OperatorMetrics {
+    label: String,
+}
+
+impl OperatorMetrics {
+    pub(crate) fn new(operator_name: &str) -> Self {
+        Self::with_label("operator", operator_name)
+    }
+
+    /// Registers metrics with another label than the operator, e.g. the stream.
+    pub(crate) fn with_label(name: &str, value: &str) -> Self {
+        Self {
+            label: format!("{}=\"{}\"", name, value.replace('"', "\\\"")),
+        }
+    }
+
+    pub fn counter(&self, name: &str) -> Counter {
+        match self.register(name, || Metric::Counter(Counter::default())) {
+            Metric::Counter(counter) => counter,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn gauge(&self, name: &str) -> Gauge {
+        match self.register(name, || Metric::Gauge(Gauge::default())) {
+            Metric::Gauge(gauge) => gauge,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Registers a histogram with the [default buckets](DEFAULT_BUCKETS).
+    pub fn histogram(&self, name: &str) -> Histogram {
+        self.histogram_with_buckets(name, &DEFAULT_BUCKETS)
+    }
+
+    /// Registers a histogram with the upper bounds of its buckets. The buckets are ignored if
+    /// the histogram is already registered.
+    pub fn histogram_with_buckets(&self, name: &str, buckets: &[f64]) -> Histogram {
+        match self.register(name, || Metric::Histogram(Histogram::new(buckets))) {
+            Metric::Histogram(histogram) => histogram,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns the metric with the name, or registers the metric.
+    ///
+    /// Panics if a metric with the name has another type.
+    fn register<F: FnOnce() -> Metric>(&self, name: &str, make_metric: F) -> Metric {
+        let mut registry = REGISTRY.lock().unwrap();
+        let key = (name.to_string(), self.label.clone());
+        if let Some(metric) = registry.get(&key) {
+            return metric.clone();
+        }
+        let metric = make_metric();
+        if let Some(((_, label), other)) = registry
+            .iter()
+            .find(|((other_name, _), other)| other_name == name && other.kind() != metric.kind())
+        {
+            panic!(
+                "Unable to register {} {}: {{{}}} registered it as a {}",
+                metric.kind(),
+                name,
+                label,
+                other.kind()
+            );
+        }
+        registry.insert(key, metric.clone());
+        metric
+    }
+}
+
+/// Returns the sum of the values of the metric registered on the current process for all
+/// labels, counting the observations of histograms, or `None` if the metric is not registered.
+pub(crate) fn value(name: &str) -> Option<f64> {
+    let registry = REGISTRY.lock().unwrap();
+    let mut values = registry
+        .iter()
+        .filter(|((metric_name, _), _)| metric_name == name)
+        .map(|(_, metric)| match metric {
+            Metric::Counter(counter) => counter.get() as f64,
+            Metric::Gauge(gauge) => gauge.get(),
+            Metric::Histogram(histogram) => histogram.count() as f64,
+        })
+        .peekable();
+    values.peek()?;
+    Some(values.sum())
+}
+
+/// Returns the runtime metrics of the node with the message counters, and the metrics
+/// registered by the operators on the current process in the Prometheus text format.
+pub(crate) fn render(counters: &MessageCounters) -> String {
+    let mut text = String::new();
+    let runtime_counters = counters.local_counters();
+    #[allow(unused_mut)]
+    let mut runtime_metrics = vec![
+        (
+            "erdos_messages_sent_to_nodes_total",
+            "counter",
+            runtime_counters.sent_to_nodes as i64,
+        ),
+        (
+            "erdos_messages_received_from_nodes_total",
+            "counter",
+            runtime_counters.received_from_nodes as i64,
+        ),
+        (
+            "erdos_messages_in_flight",
+            "gauge",
+            runtime_counters.in_flight,
+        ),
+        (
+            "erdos_messages_lost_total",
+            "counter",
+            crate::communication::lost_messages() as i64,
+        ),
+    ];
+    #[cfg(feature = "zenoh_zerocopy_transport")]
+    runtime_metrics.extend(crate::communication::shm_pool_metrics());
+    for (name, kind, value) in runtime_metrics {
+        writeln!(text, "# TYPE {} {}", name, kind).unwrap();
+        writeln!(text, "{} {}", name, value).unwrap();
+    }
+
+    let registry = REGISTRY.lock().unwrap();
+    let mut previous_name = None;
+    for ((name, label), metric) in registry.iter() {
+        if previous_name != Some(name) {
+            writeln!(text, "# TYPE {} {}", name, metric.kind()).unwrap();
+            previous_name = Some(name);
+        }
+        match metric {
+            Metric::Counter(counter) => {
+                writeln!(text, "{}{{{}}} {}", name, label, counter.get()).unwrap()
+            }
+            Metric::Gauge(gauge) => {
+                writeln!(text, "{}{{{}}} {}", name, label, gauge.get()).unwrap()
+            }
+            Metric::Histogram(histogram) => {
+                for (bound, count) in histogram.buckets() {
+                    writeln!(
+                        text,
+                        "{}_bucket{{{},le=\"{}\"}} {}",
+                        name, label, bound, count
+                    )
+                    .unwrap();
+                }
+                let count = histogram.count();
+                writeln!(text, "{}_bucket{{{},le=\"+Inf\"}} {}", name, label, count).unwrap();
+                writeln!(text, "{}_sum{{{}}} {}", name, label, histogram.sum()).unwrap();
+                writeln!(text, "{}_count{{{}}} {}", name, label, count).unwrap();
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the metrics registered by operators are aggregated and rendered.
+    #[test]
+    fn test_operator_metrics() {
+        let metrics = OperatorMetrics::new("MetricsTestOp");
+        metrics.counter("test_detections_total").add(2);
+        metrics.counter("test_detections_total").increment();
+        metrics.gauge("test_queue_size").set(4.0);
+        let histogram = metrics.histogram_with_buckets("test_latency_seconds", &[0.1, 1.0]);
+        histogram.observe(0.05);
+        histogram.observe(0.5);
+        histogram.observe(5.0);
+        assert_eq!(histogram.buckets(), vec![(0.1, 1), (1.0, 2)]);
+
+        let text = render(&MessageCounters::default());
+        assert!(text.contains("# TYPE erdos_messages_in_flight gauge\n"));
+        assert!(text.contains("# TYPE test_detections_total counter\n"));
+        assert!(text.contains("test_detections_total{operator=\"MetricsTestOp\"} 3\n"));
+        assert!(text.contains("test_queue_size{operator=\"MetricsTestOp\"} 4\n"));
+        assert!(
+            text.contains("test_latency_seconds_bucket{operator=\"MetricsTestOp\",le=\"1\"} 2\n")
+        );
+        assert!(text.contains("test_latency_seconds_count{operator=\"MetricsTestOp\"} 3\n"));
+    }
+}
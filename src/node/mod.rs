@@ -11,15 +11,83 @@
 //! scheduling operators, and hope to provide a versatile solution.
 
 // Private submodules
+mod admin;
+mod checkpoint;
+mod dead_letter;
+#[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
+mod disk_queue;
+mod health;
 mod lattice;
+mod lineage;
+mod metrics;
+mod multi_node;
 mod node;
+mod node_context;
+mod node_info;
+mod operator_additions;
+mod placement;
+mod plan;
+mod resources;
+mod runtime_knobs;
+mod start_order;
+mod startup_report;
+mod startup_state;
+mod thread_per_core;
+mod triggers;
+mod watchdog;
+
+// Crate-wide exports
+#[cfg(feature = "cli")]
+pub(crate) use admin::read_admin_tokens;
+pub(crate) use admin::AdminInterface;
+pub(crate) use dead_letter::DeadLetterQueue;
+pub(crate) use health::NodeHealth;
+pub(crate) use node_context::NodeContext;
+pub(crate) use node_info::ClusterInfoRequests;
+pub(crate) use operator_additions::OperatorAdditions;
+pub(crate) use placement::Placement;
+#[cfg(not(feature = "term_logger"))]
+pub(crate) use runtime_knobs::PlainDrain;
+pub(crate) use runtime_knobs::RuntimeLevelFilter;
+pub(crate) use start_order::StartOrder;
+pub(crate) use startup_report::StartupProfiler;
+pub(crate) use startup_state::NodeStartupStateMachine;
+pub(crate) use watchdog::Watchdog;
 
 // Crate-wide visible submodules
+pub(crate) mod capture;
+pub(crate) mod latency;
+pub(crate) mod memory_limit;
 pub(crate) mod operator_event;
+pub(crate) mod provenance;
+pub(crate) mod quiescence;
+pub(crate) mod run_id;
+pub(crate) mod stream_metrics;
+pub(crate) mod watermark_progress;
 
 // Public submodules
+#[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
+pub mod fleet;
 #[doc(hidden)]
 pub mod operator_executor;
 
 // Public exports
+pub use admin::AdminRole;
+pub use capture::{CaptureConfig, CapturePolicy, CapturedMessage};
+pub use checkpoint::{CheckpointBackend, FileSystemBackend};
+pub use dead_letter::{DeadLetter, DeadLetterReason, DeadLetterStream, DEAD_LETTER_CAPACITY};
+pub use latency::{HopTimings, LatencyBreakdown};
+pub use lineage::{Lineage, LineageMessage};
+pub use metrics::{Counter, Gauge, Histogram, OperatorMetrics, DEFAULT_BUCKETS};
+pub use multi_node::{MultiNodeHandle, MultiNodeRunner};
 pub use node::{Node, NodeHandle, NodeId};
+pub use node_info::NodeInfo;
+pub use placement::{OperatorPlacement, PlacementReport, ReceiverPlacement};
+pub use plan::{DataflowPlan, PlannedOperator, PlannedStream};
+pub use provenance::{Origin, Provenance};
+pub use resources::ResourceLimits;
+pub use runtime_knobs::{KnobChange, RuntimeKnobs, LOG_LEVEL_KNOB};
+pub use startup_report::{StartupPhase, StartupReport};
+pub use startup_state::PeerPhase;
+pub use triggers::{Trigger, TriggerAction, TriggerEvent};
+pub use watermark_progress::WatermarkWatcher;
@@ -0,0 +1,186 @@
+use std::{net::TcpListener, thread};
+
+use futures::future;
+use tokio::{runtime::Builder, sync::mpsc::Sender};
+
+use crate::{
+    node::{node::wait_until_initialized, Node, NodeId},
+    Configuration, ExecutorModel,
+};
+
+/// Runs several [`Node`]s of a dataflow on one process and one runtime, e.g. to test a
+/// deployment on several nodes, or to pack nodes which mostly wait on I/O.
+///
+/// Each node runs the dataflow graph built on the thread which starts the runner, and keeps its own
+/// configuration, connections, and control plane, so the nodes communicate like nodes on
+/// different processes. Each node also keeps the state of its streams, e.g. the message
+/// counters, codecs, captures, triggers, provenance, and watched watermarks, so nodes which share
+/// stream IDs do not observe each other's messages. The following state is shared by the nodes
+/// on a process:
+/// - the terminal logger, whose level is set by the
+///   [`LOG_LEVEL_KNOB`](crate::node::LOG_LEVEL_KNOB) of any node.
+/// - the [metrics](crate::node::OperatorMetrics) of the operators and streams, and the latency
+///   tracked with [`Node::track_latency`].
+/// - the checkpoint backend and the checkpoint requests.
+/// - the subscriptions to [control messages](crate::dataflow::control), barriers, and the demand
+///   for streams, which receive the messages of all nodes once.
+///
+/// # Example
+/// ```no_run
+/// # use erdos::node::MultiNodeRunner;
+/// // Connect the operators, placed on nodes 0 and 1, on the current thread.
+/// let handle = MultiNodeRunner::local(2, 2).run_async();
+/// handle.shutdown().unwrap();
+/// ```
+pub struct MultiNodeRunner {
+    nodes: Vec<Node>,
+    num_worker_threads: usize,
+}
+
+impl MultiNodeRunner {
+    /// Creates one node per configuration. The configurations must have the addresses of all
+    /// nodes of the dataflow, which may also include nodes running on other processes.
+    pub fn new(configs: Vec<Configuration>) -> Self {
+        let num_worker_threads = configs
+            .iter()
+            .map(|config| match config.executor {
+                ExecutorModel::WorkStealing => config.worker_threads(),
+                ExecutorModel::ThreadPerCore => 1,
+            })
+            .sum();
+        Self {
+            nodes: configs.into_iter().map(Node::new).collect(),
+            num_worker_threads,
+        }
+    }
+
+    /// Creates a dataflow of `num_nodes` nodes which connect to each other on unused local
+    /// ports.
+    pub fn local(num_nodes: usize, num_worker_threads: usize) -> Self {
+        Self::new(local_configurations(num_nodes, num_worker_threads))
+    }
+
+    /// Returns the node with the index, e.g. to set its partitioner or runtime knobs before
+    /// running it.
+    pub fn node_mut(&mut self, index: NodeId) -> Option<&mut Node> {
+        self.nodes.iter_mut().find(|node| node.get_id() == index)
+    }
+
+    /// Runs the nodes until all of them shut down.
+    pub fn run(mut self) {
+        for node in self.nodes.iter_mut() {
+            node.copy_default_graph();
+        }
+        run_nodes(self.nodes, self.num_worker_threads);
+    }
+
+    /// Runs the nodes in a separate OS thread, and returns once all nodes finished setting up.
+    pub fn run_async(mut self) -> MultiNodeHandle {
+        for node in self.nodes.iter_mut() {
+            node.copy_default_graph();
+        }
+        let shutdown_txs = self.nodes.iter().map(Node::get_shutdown_tx).collect();
+        let initialized: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|node| (node.get_id(), node.get_initialized()))
+            .collect();
+        // Disconnected once the thread exits.
+        let (running_tx, running_rx) = std::sync::mpsc::channel::<()>();
+        let (nodes, num_worker_threads) = (self.nodes, self.num_worker_threads);
+        let thread_handle = thread::spawn(move || {
+            let _running_tx = running_tx;
+            run_nodes(nodes, num_worker_threads);
+        });
+        for (id, initialized) in initialized {
+            wait_until_initialized(id, &initialized, &running_rx);
+        }
+        MultiNodeHandle {
+            thread_handle,
+            shutdown_txs,
+        }
+    }
+}
+
+fn run_nodes(mut nodes: Vec<Node>, num_worker_threads: usize) {
+    let mut runtime = Builder::new()
+        .threaded_scheduler()
+        .core_threads(num_worker_threads.max(1))
+        .thread_name("multi-node")
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(future::join_all(nodes.iter_mut().map(Node::async_run)));
+}
+
+/// Returns the configurations of `num_nodes` nodes which connect to each other on unused local
+/// ports.
+fn local_configurations(num_nodes: usize, num_worker_threads: usize) -> Vec<Configuration> {
+    // The ports are released when the listeners are dropped, after all ports were picked.
+    let listeners: Vec<_> = (0..2 * num_nodes)
+        .map(|_| TcpListener::bind("127.0.0.1:0").expect("Unable to find an unused port"))
+        .collect();
+    let mut addresses = listeners.iter().map(|listener| {
+        listener
+            .local_addr()
+            .expect("Unable to find an unused port")
+    });
+    let data_addresses: Vec<_> = addresses.by_ref().take(num_nodes).collect();
+    let control_addresses: Vec<_> = addresses.collect();
+    (0..num_nodes)
+        .map(|index| {
+            Configuration::new(
+                index,
+                data_addresses.clone(),
+                control_addresses.clone(),
+                num_worker_threads,
+                None,
+            )
+        })
+        .collect()
+}
+
+/// Handle to the [`Node`]s run by a [`MultiNodeRunner`].
+pub struct MultiNodeHandle {
+    thread_handle: thread::JoinHandle<()>,
+    shutdown_txs: Vec<Sender<()>>,
+}
+
+impl MultiNodeHandle {
+    /// Waits for all nodes to finish.
+    pub fn join(self) -> Result<(), String> {
+        self.thread_handle.join().map_err(|e| format!("{:?}", e))
+    }
+
+    /// Blocks until all nodes shut down.
+    pub fn shutdown(mut self) -> Result<(), String> {
+        for shutdown_tx in self.shutdown_txs.iter_mut() {
+            // Error indicates node is already shutting down.
+            shutdown_tx.try_send(()).ok();
+        }
+        self.thread_handle.join().map_err(|e| format!("{:?}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that local nodes know the distinct addresses of all nodes.
+    #[test]
+    fn test_local_configurations() {
+        let configs = local_configurations(3, 2);
+        assert_eq!(configs.len(), 3);
+        for (index, config) in configs.iter().enumerate() {
+            assert_eq!(config.index, index);
+            assert_eq!(config.data_addresses, configs[0].data_addresses);
+            assert_eq!(config.control_addresses.len(), 3);
+        }
+        let mut addresses = configs[0].data_addresses.clone();
+        addresses.extend(configs[0].control_addresses.iter().cloned());
+        addresses.sort();
+        addresses.dedup();
+        assert_eq!(addresses.len(), 6);
+        assert_eq!(MultiNodeRunner::new(configs).num_worker_threads, 6);
+    }
+}
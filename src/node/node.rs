@@ -1,28 +1,37 @@
 use std::{
-    collections::{HashMap, HashSet},
+    any::Any,
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    hash::{Hash, Hasher},
     sync::Arc,
     thread,
+    time::{Duration, Instant},
 };
 
-use futures::future;
+use futures::{future, FutureExt, TryFutureExt};
 use futures_util::stream::StreamExt;
+use serde::Deserialize;
 use slog;
 use tokio::{
     runtime::Builder,
     sync::{
-        mpsc::{self, Receiver, Sender, UnboundedReceiver},
-        Mutex,
+        mpsc::{self, Receiver, Sender, UnboundedReceiver, UnboundedSender},
+        oneshot, Mutex,
     },
 };
 
 #[cfg(feature = "tcp_transport")]
 use crate::communication::{ControlMessageCodec, MessageCodec};
 #[cfg(feature = "tcp_transport")]
-use tokio::net::TcpStream;
+use tokio::net::{TcpListener, TcpStream};
 #[cfg(feature = "tcp_transport")]
 use tokio_util::codec::Framed;
 
-use crate::communication::{self, ControlMessage, ControlMessageHandler};
+use crate::communication::{
+    self, BackpressurePolicy, BandwidthLimit, ChannelBound, CompressionCodec, ConflationKey,
+    ControlMessage, ControlMessageHandler, ControlRecorder, DictionaryConfig, PusherT,
+    SerializationPool, StreamCompressor, StreamDecompressor, StreamDictionary, StreamShaping,
+    TrafficShaper,
+};
 
 #[cfg(feature = "tcp_transport")]
 use crate::communication::{
@@ -50,19 +59,48 @@ use crate::communication::{
     zenoh_shm_senders::{
         self as senders, ZenohShmControlSender as ControlSender, ZenohShmDataSender as DataSender,
     },
+    ShmPoolConfig,
 };
 
-use crate::dataflow::graph::{default_graph, Graph};
+use crate::dataflow::{
+    barrier, control,
+    graph::{default_graph, DriverSection, Graph, GraphDiff, OperatorMetadata},
+    stream::{self, demand::DemandChange, endpoint_lists::EndpointLists, StreamId},
+    BufferAllocator, Data, Message, Timestamp,
+};
+use crate::node::{
+    checkpoint, latency,
+    quiescence::{self, QuiescenceDetector, QuiescenceToken},
+    run_id::RunId,
+    stream_metrics,
+    thread_per_core::CorePool,
+    triggers, AdminInterface, CaptureConfig, ClusterInfoRequests, DataflowPlan, DeadLetterQueue,
+    DeadLetterStream, LatencyBreakdown, Lineage, NodeContext, NodeHealth, NodeInfo,
+    NodeStartupStateMachine, OperatorAdditions, Origin, PeerPhase, Placement, PlacementReport,
+    RuntimeKnobs, StartOrder, StartupProfiler, StartupReport, Trigger, Watchdog, WatermarkWatcher,
+};
 use crate::scheduler::{
     self,
-    channel_manager::ChannelManager,
+    channel_manager::{ChannelManager, ChannelSettings},
     endpoints_manager::{ChannelsToReceivers, ChannelsToSenders},
+    PartitioningScheduler,
 };
-use crate::Configuration;
+use crate::ExecutorModel;
+use crate::{Configuration, OperatorId, Uuid};
 
 /// Unique index for a [`Node`].
 pub type NodeId = usize;
 
+/// Interval at which a node reports the watermarks of the watched streams to the other nodes.
+const WATERMARK_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Interval at which a node grants credits for the messages its operators read from the blocking
+/// bounded streams of other nodes.
+const CREDIT_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Interval after which a node logs the peers it waits for if its startup makes no progress.
+const STARTUP_WARNING_INTERVAL: Duration = Duration::from_secs(10);
+
 /// Structure which executes a portion of an ERDOS application.
 ///
 /// The [`Node`] contains a runtime which executes operators and manages
@@ -75,6 +113,8 @@ pub struct Node {
     id: NodeId,
     /// Dataflow graph which the node will execute.
     dataflow_graph: Option<Graph>,
+    /// The dataflow graph with its operators placed on nodes, once the node runs.
+    scheduled_graph: Option<Graph>,
     /// Structure to be used to send `Sender` updates to receiver threads.
     channels_to_receivers: Arc<Mutex<ChannelsToReceivers>>,
     /// Structure to be used to send messages to sender threads.
@@ -86,40 +126,432 @@ pub struct Node {
     /// Channel used to shut down the node.
     shutdown_tx: Sender<()>,
     shutdown_rx: Option<Receiver<()>>,
+    /// Queue of messages received from other nodes which could not be delivered.
+    dead_letters: DeadLetterQueue,
+    /// Streams whose messages to other nodes are compressed with a trained zstd dictionary.
+    compressed_streams: HashMap<StreamId, DictionaryConfig>,
+    /// Streams whose messages to other nodes are compressed with a codec, without a dictionary.
+    stream_codecs: HashMap<StreamId, CompressionCodec>,
+    /// Shares the bandwidth to other nodes between the streams.
+    traffic_shaper: TrafficShaper,
+    /// Serializes the large messages sent to other nodes, if serialization threads are
+    /// configured.
+    serialization_pool: Option<SerializationPool>,
+    /// The [`ConflationKey`] of each stream whose queued messages to other nodes are replaced by
+    /// newer messages.
+    conflated_streams: HashMap<StreamId, Arc<dyn Any + Send + Sync>>,
+    /// Bounds the messages queued for the operators which read the streams.
+    bounded_streams: HashMap<StreamId, ChannelBound>,
+    /// The [`BufferAllocator`] of each stream whose buffers are allocated by the receiving
+    /// operators.
+    buffer_allocators: HashMap<StreamId, Arc<dyn BufferAllocator>>,
+    /// Places the movable operators on nodes, if set.
+    partitioner: Option<PartitioningScheduler>,
+    /// Channel used to send requests from the [`NodeHandle`] to the running node.
+    handle_tx: UnboundedSender<HandleRequest>,
+    handle_rx: Option<UnboundedReceiver<HandleRequest>>,
+    /// Configuration values which can be updated at runtime.
+    knobs: RuntimeKnobs,
+    /// Health of the node, reported to process supervisors.
+    health: NodeHealth,
+    /// Driver sections set up on the node, whose teardown hooks run when the node stops.
+    driver_sections: Vec<DriverSection>,
+    /// Nodes to which the data plane of the node is connected.
+    data_peers: Vec<NodeId>,
+    /// Records how long each phase of the startup of the node takes.
+    startup: StartupProfiler,
+    /// Tracks the phase of the startup of each peer.
+    startup_state: Arc<std::sync::Mutex<NodeStartupStateMachine>>,
+    /// The ID of the run of the dataflow, on which the nodes agree while setting up.
+    run_id: RunId,
+    /// The threads on which operators run with the thread-per-core executor.
+    core_pool: Option<CorePool>,
+    /// Records the cores on which the operators and data receivers run.
+    placement: Placement,
+    /// Channel on which the local operators send control messages to the node, once it runs.
+    operator_tx: Option<UnboundedSender<ControlMessage>>,
+    /// The pushers of the streams received from other nodes, to which the channels of the
+    /// operators added at runtime are added.
+    receiver_pushers: HashMap<StreamId, Box<dyn PusherT>>,
+    /// The send endpoints of the streams written on the node, shared by their write streams.
+    endpoint_lists: Arc<std::sync::Mutex<EndpointLists>>,
+    /// Called with the ID of another node once it is detected as failed.
+    node_failure_callbacks: Vec<Arc<dyn Fn(NodeId) + Send + Sync>>,
+    /// The state which the node shares with its streams, operators, and data receivers.
+    context: Arc<NodeContext>,
+}
+
+/// Requests sent by a [`NodeHandle`] to its [`Node`].
+enum HandleRequest {
+    /// Requests the information of all nodes.
+    ClusterInfo(std::sync::mpsc::Sender<Vec<NodeInfo>>),
+    /// Propagates a knob update, which was already applied on the current node, to all other
+    /// nodes.
+    SetKnob(String, String),
+    /// Waits until the dataflow is quiescent, or until the deadline.
+    AwaitQuiescent(Instant, std::sync::mpsc::Sender<()>),
+    /// Starts another round of the quiescence detection.
+    ProbeQuiescence,
+    /// Runs the operators added to the dataflow graph, and replies with their IDs once they
+    /// finished setting up on all nodes.
+    AddOperators(
+        Graph,
+        std::sync::mpsc::Sender<Result<Vec<OperatorId>, String>>,
+    ),
 }
 
 impl Node {
     /// Creates a new node.
+    ///
+    /// Panics if the configuration sets an [authenticator](Configuration::authenticator) while
+    /// the node communicates with the Zenoh transports, which do not authenticate the nodes.
     pub fn new(config: Configuration) -> Self {
         let id = config.index;
+        if cfg!(any(
+            feature = "zenoh_transport",
+            feature = "zenoh_zerocopy_transport"
+        )) && config.authenticator.is_some()
+        {
+            panic!(
+                "Node {}: unable to authenticate the nodes with the Zenoh transports",
+                id
+            );
+        }
         let logger = config.logger.clone();
         let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
+        let (handle_tx, handle_rx) = mpsc::unbounded_channel();
+        let context = Arc::new(NodeContext::default());
+        let health = NodeHealth::new(config.stall_timeout, Arc::clone(&context));
+        let startup = StartupProfiler::new(id, config.logger.clone());
+        let startup_state = Arc::new(std::sync::Mutex::new(NodeStartupStateMachine::new(
+            id,
+            config.data_addresses.len(),
+        )));
+        let recorder = match &config.control_log_directory {
+            Some(directory) => ControlRecorder::new(id, directory).unwrap_or_else(|e| {
+                slog::warn!(logger, "Node {}: unable to log control messages: {}", id, e);
+                ControlRecorder::disabled(id)
+            }),
+            None => ControlRecorder::disabled(id),
+        };
+        let serialization_pool = match config.serialization_threads {
+            0 => None,
+            num_threads => Some(SerializationPool::new(num_threads)),
+        };
         Self {
             config,
             id,
             dataflow_graph: None,
+            scheduled_graph: None,
             channels_to_receivers: Arc::new(Mutex::new(ChannelsToReceivers::new())),
             channels_to_senders: Arc::new(Mutex::new(ChannelsToSenders::new())),
-            control_handler: ControlMessageHandler::new(logger),
+            control_handler: ControlMessageHandler::new(logger, recorder),
             initialized: Arc::new((std::sync::Mutex::new(false), std::sync::Condvar::new())),
             shutdown_tx,
             shutdown_rx: Some(shutdown_rx),
+            dead_letters: DeadLetterQueue::new(),
+            compressed_streams: HashMap::new(),
+            stream_codecs: HashMap::new(),
+            traffic_shaper: TrafficShaper::new(),
+            serialization_pool,
+            conflated_streams: HashMap::new(),
+            bounded_streams: HashMap::new(),
+            buffer_allocators: HashMap::new(),
+            partitioner: None,
+            handle_tx,
+            handle_rx: Some(handle_rx),
+            knobs: RuntimeKnobs::new(),
+            health,
+            driver_sections: Vec::new(),
+            data_peers: Vec::new(),
+            startup,
+            startup_state,
+            run_id: RunId::new(),
+            core_pool: None,
+            placement: Placement::new(id),
+            operator_tx: None,
+            receiver_pushers: HashMap::new(),
+            endpoint_lists: Arc::new(std::sync::Mutex::new(EndpointLists::new())),
+            node_failure_callbacks: Vec::new(),
+            context,
+        }
+    }
+
+    /// Returns a stream of messages received from other nodes that could not be delivered,
+    /// e.g. because they failed to deserialize.
+    ///
+    /// Must be called before running the node.
+    pub fn dead_letter_stream(&self) -> DeadLetterStream {
+        self.dead_letters.subscribe()
+    }
+
+    /// Compresses the messages sent on the stream to other nodes with a zstd dictionary, which
+    /// is trained on the first messages of the stream and distributed to the receiving nodes.
+    ///
+    /// Must be called before running the node. A [codec](Node::compress_stream_with_codec) set
+    /// for the stream takes precedence over the dictionary. Panics if the `compression` feature
+    /// is disabled.
+    pub fn compress_stream(&mut self, stream_id: StreamId, config: DictionaryConfig) {
+        assert!(
+            cfg!(feature = "compression"),
+            "Unable to compress stream {} with a dictionary: the compression feature is disabled",
+            stream_id
+        );
+        self.compressed_streams.insert(stream_id, config);
+    }
+
+    /// Compresses the messages of the stream sent between nodes with the codec.
+    ///
+    /// Must be called with the same codec on all nodes before running them; nodes check during
+    /// setup that they agree on the codecs, and fail otherwise. [`CompressionCodec::Zstd`]
+    /// requires the `compression` feature, and [`CompressionCodec::Lz4`] the `lz4` feature.
+    pub fn compress_stream_with_codec(&mut self, stream_id: StreamId, codec: CompressionCodec) {
+        communication::check_codec_available(stream_id, codec);
+        self.stream_codecs.insert(stream_id, codec);
+    }
+
+    /// Tracks the latency of the messages of the stream, which is split into the time spent
+    /// serializing, transferring, deserializing, queueing, and processing the messages. Must be
+    /// called on the nodes which send and receive the stream.
+    ///
+    /// The breakdown is returned by [`NodeHandle::latency_breakdown`]. Must be called before
+    /// running the node.
+    pub fn track_latency(&mut self, stream_id: StreamId) {
+        latency::track(stream_id);
+    }
+
+    /// Records which messages each message sent by an operator was computed from: the message
+    /// read by the callback which sent it, and the origins of that message, up to 32 origins
+    /// per message. The origins are sent with the messages to other nodes, so it must be called
+    /// on all nodes.
+    ///
+    /// The origins are returned by [`NodeHandle::provenance`]. Must be called before running
+    /// the node.
+    pub fn track_provenance(&mut self) {
+        self.context.provenance.track();
+    }
+
+    /// Captures the payloads of a sample of the messages sent on the stream to disk, in a ring
+    /// buffer bounded by [`CaptureConfig::max_bytes`], e.g. so that incidents in the field come
+    /// with the messages needed to reproduce them. Must be called on the nodes which send the
+    /// stream.
+    ///
+    /// The messages are read with
+    /// [`CapturedMessage::read_all`](crate::node::CapturedMessage::read_all). Must be called
+    /// before running the node.
+    pub fn capture_stream(&mut self, stream_id: StreamId, config: CaptureConfig) {
+        self.context.captures.capture_stream(stream_id, config);
+    }
+
+    /// Registers a trigger which runs its actions when its condition holds while the dataflow
+    /// runs, e.g. to capture the inputs of an operator and alert when its output stays empty.
+    pub fn add_trigger(&mut self, trigger: Trigger) {
+        self.context.triggers.add(trigger);
+    }
+
+    /// Returns a watcher of the watermarks sent on the stream, e.g. to show the progress of the
+    /// dataflow from the driver. Must be called on all nodes before running the nodes, so that
+    /// the watcher observes the watermarks sent on other nodes.
+    pub fn watch_watermarks(&mut self, stream_id: StreamId) -> WatermarkWatcher {
+        self.context.watermark_progress.watch(stream_id)
+    }
+
+    /// Registers a callback which is called with the ID of another node once it fails, i.e.
+    /// once it sends no heartbeat within the
+    /// [`heartbeat_timeout`](crate::Configuration::heartbeat_timeout), or once another node
+    /// reports its failure. The node then tears down the dataflow.
+    ///
+    /// The callback runs on the runtime of the node, and must not block. Must be called before
+    /// running the node.
+    pub fn on_node_failure<F: 'static + Fn(NodeId) + Send + Sync>(&mut self, callback: F) {
+        self.node_failure_callbacks.push(Arc::new(callback));
+    }
+
+    /// Caps the bandwidth with which the messages of the stream are sent to other nodes, and
+    /// sets their priority over the messages of other streams, e.g. so that a debug video stream
+    /// does not starve the inputs of the planner on a shared link.
+    ///
+    /// The limit can be updated while the dataflow runs with the `bandwidth.stream.<stream ID>`
+    /// runtime knob, whose value is a [`BandwidthLimit`] or `unlimited`.
+    ///
+    /// Must be called before running the node.
+    pub fn shape_stream(&mut self, stream_id: StreamId, shaping: StreamShaping) {
+        self.traffic_shaper
+            .set_stream_limit(stream_id, shaping.limit);
+        self.traffic_shaper
+            .set_stream_priority(stream_id, shaping.priority);
+        let shaper = self.traffic_shaper.clone();
+        self.knobs.register(
+            &format!("bandwidth.stream.{}", stream_id),
+            &communication::limit_knob_value(shaping.limit),
+            move |value: &str| {
+                shaper.set_stream_limit(stream_id, communication::parse_limit_knob(value)?);
+                Ok(())
+            },
+        );
+    }
+
+    /// Conflates the messages sent on the stream to other nodes, e.g. for streams of the latest
+    /// pose or set of detections.
+    ///
+    /// When messages queue up because the connection to a node is slower than the stream, a
+    /// queued message is replaced by the newest message with the same key instead of sending
+    /// both. Watermarks are never replaced. `D` must be the type of the data of the stream.
+    ///
+    /// Must be called before running the node.
+    pub fn conflate_stream<D, K, F>(&mut self, stream_id: StreamId, key: F)
+    where
+        for<'a> D: Data + Deserialize<'a>,
+        K: Hash,
+        F: 'static + Fn(&D) -> K + Send + Sync,
+    {
+        let key: ConflationKey<Arc<Message<D>>> = Arc::new(move |msg: &Arc<Message<D>>| {
+            msg.data().map(|data| {
+                let mut hasher = DefaultHasher::new();
+                key(data).hash(&mut hasher);
+                hasher.finish()
+            })
+        });
+        self.conflated_streams.insert(stream_id, Arc::new(key));
+    }
+
+    /// Bounds the number of messages queued for each operator which reads the stream, and applies
+    /// the policy of the bound to the messages sent while an operator's queue is full.
+    ///
+    /// With [`BackpressurePolicy::Block`], operators on the node which send on the stream block
+    /// until the reading operators catch up, and messages sent to other nodes are held until
+    /// those nodes grant credits for them.
+    ///
+    /// Must be called with the same bound on every node before running the node. Fails with
+    /// [`BackpressurePolicy::Block`] if the node communicates with the Zenoh transports, which do
+    /// not hold the messages until credits are granted.
+    pub fn bound_stream(&mut self, stream_id: StreamId, bound: ChannelBound) -> Result<(), String> {
+        if bound.policy == BackpressurePolicy::Block {
+            if cfg!(any(
+                feature = "zenoh_transport",
+                feature = "zenoh_zerocopy_transport"
+            )) {
+                return Err(format!(
+                    "Unable to block on stream {}: the Zenoh transports do not support credits",
+                    stream_id
+                ));
+            }
+            self.traffic_shaper
+                .set_stream_credits(stream_id, bound.capacity as u64);
+        }
+        self.bounded_streams.insert(stream_id, bound);
+        Ok(())
+    }
+
+    /// Deserializes the [`Buffer`](crate::dataflow::Buffer)s of the messages received on the
+    /// stream from other nodes into memory provided by the allocator, e.g. pinned host memory
+    /// from which the receiving operators upload images to a GPU, instead of copying them out of
+    /// a `Vec<u8>`.
+    ///
+    /// Must be called on the receiving node before running the node.
+    pub fn allocate_buffers<A>(&mut self, stream_id: StreamId, allocator: A)
+    where
+        A: 'static + BufferAllocator,
+    {
+        self.buffer_allocators
+            .insert(stream_id, Arc::new(allocator));
+    }
+
+    /// Places the [movable](crate::dataflow::OperatorConfig::movable) operators on nodes with
+    /// the scheduler, so that the estimated traffic between nodes is minimized.
+    ///
+    /// Must be called with the same scheduler on every node before running the node.
+    pub fn partition_operators(&mut self, scheduler: PartitioningScheduler) {
+        self.partitioner = Some(scheduler);
+    }
+
+    /// Caps the bandwidth with which messages of all streams are sent to the other node.
+    ///
+    /// The limit can be updated while the dataflow runs with the `bandwidth.node.<node ID>`
+    /// runtime knob, whose value is a [`BandwidthLimit`] or `unlimited`.
+    ///
+    /// Must be called before running the node.
+    pub fn limit_node_bandwidth(&mut self, node_id: NodeId, limit: BandwidthLimit) {
+        self.traffic_shaper.set_peer_limit(node_id, Some(limit));
+        let shaper = self.traffic_shaper.clone();
+        self.knobs.register(
+            &format!("bandwidth.node.{}", node_id),
+            &limit.to_string(),
+            move |value: &str| {
+                shaper.set_peer_limit(node_id, communication::parse_limit_knob(value)?);
+                Ok(())
+            },
+        );
+    }
+
+    /// Returns the configuration values of the node which can be updated at runtime.
+    ///
+    /// Custom knobs must be registered before running the node.
+    pub fn runtime_knobs(&self) -> RuntimeKnobs {
+        self.knobs.clone()
+    }
+
+    /// Validates and schedules the dataflow graph without running it, and returns the placement
+    /// of the operators on nodes.
+    ///
+    /// Exports the scheduled graph if the configuration sets a `graph_filename`,
+    /// and the plan as a JSON file if it sets a `plan_filename`.
+    pub fn plan(&mut self) -> Result<DataflowPlan, String> {
+        self.copy_default_graph();
+        let graph = self.dataflow_graph.as_ref().unwrap();
+        let scheduled_graph = scheduler::schedule(
+            graph,
+            self.config.scheduler.as_deref(),
+            self.partitioner.as_ref(),
+            &self.config.node_labels,
+        )
+        .map_err(|e| e.to_string())?;
+        let num_nodes = self.config.data_addresses.len();
+        let operators = scheduled_graph.get_operators();
+        if let Some(operator) = operators.iter().find(|o| o.node_id >= num_nodes) {
+            return Err(format!(
+                "Operator {:?} is placed on node {}, but the dataflow runs on {} nodes",
+                operator.id, operator.node_id, num_nodes
+            ));
+        }
+        // Checks the start dependencies of the operators.
+        StartOrder::new(&operators, self.id, HashMap::new())?;
+
+        if let Some(filename) = &self.config.graph_filename {
+            scheduled_graph
+                .export(filename.as_str())
+                .map_err(|e| e.to_string())?;
         }
+        let plan = DataflowPlan::new(&scheduled_graph);
+        if let Some(filename) = &self.config.plan_filename {
+            std::fs::write(filename, plan.to_json()).map_err(|e| e.to_string())?;
+        }
+        Ok(plan)
     }
 
     /// Runs an ERDOS node.
     ///
-    /// The method never returns.
+    /// The method never returns, unless the configuration sets `dry_run`, in which case the node
+    /// only [plans](Node::plan) the dataflow and panics if the dataflow is invalid.
     pub fn run(&mut self) {
         slog::debug!(self.config.logger, "Node {}: running", self.id);
         // Set the dataflow graph if it hasn't been set already.
-        if self.dataflow_graph.is_none() {
-            self.dataflow_graph = Some(default_graph::clone());
+        self.copy_default_graph();
+        if self.config.dry_run {
+            match self.plan() {
+                Ok(plan) => slog::info!(self.config.logger, "Node {}: planned\n{}", self.id, plan),
+                Err(e) => panic!("Node {}: invalid dataflow: {}", self.id, e),
+            }
+            return;
         }
-        // Build a runtime with n threads.
+        // Build a runtime with n threads, or only for communication if operators run on cores.
+        let num_threads = match self.config.executor {
+            ExecutorModel::WorkStealing => self.config.worker_threads(),
+            ExecutorModel::ThreadPerCore => 1,
+        };
         let mut runtime = Builder::new()
             .threaded_scheduler()
-            .core_threads(self.config.num_worker_threads)
+            .core_threads(num_threads)
             .thread_name(format!("node-{}", self.id))
             .enable_all()
             .build()
@@ -134,30 +566,67 @@ impl Node {
     pub fn run_async(mut self) -> NodeHandle {
         // Clone to avoid move to other thread.
         let shutdown_tx = self.shutdown_tx.clone();
+        let handle_tx = self.handle_tx.clone();
+        let knobs = self.knobs.clone();
+        let startup = self.startup.clone();
+        let startup_state = self.startup_state.clone();
+        let placement = self.placement.clone();
+        let run_id = self.run_id.clone();
+        let context = Arc::clone(&self.context);
+        let id = self.id;
         // Copy dataflow graph to the other thread
         self.dataflow_graph = Some(default_graph::clone());
         let initialized = self.initialized.clone();
+        // Disconnected once the node's thread exits.
+        let (running_tx, running_rx) = std::sync::mpsc::channel::<()>();
         let thread_handle = thread::spawn(move || {
+            let _running_tx = running_tx;
             self.run();
         });
         // Wait for ERDOS to start up.
-        let (lock, cvar) = &*initialized;
-        let mut started = lock.lock().unwrap();
-        while !*started {
-            started = cvar.wait(started).unwrap();
-        }
+        wait_until_initialized(id, &initialized, &running_rx);
 
         NodeHandle {
             thread_handle,
             shutdown_tx,
+            handle_tx,
+            knobs,
+            startup,
+            startup_state,
+            placement,
+            run_id,
+            context,
+            id,
+        }
+    }
+
+    /// Sets the dataflow graph to a copy of the default graph of the current thread, unless it
+    /// was already set.
+    pub(crate) fn copy_default_graph(&mut self) {
+        if self.dataflow_graph.is_none() {
+            self.dataflow_graph = Some(default_graph::clone());
         }
     }
 
+    pub(crate) fn get_id(&self) -> NodeId {
+        self.id
+    }
+
+    pub(crate) fn get_initialized(&self) -> Arc<(std::sync::Mutex<bool>, std::sync::Condvar)> {
+        self.initialized.clone()
+    }
+
+    pub(crate) fn get_shutdown_tx(&self) -> Sender<()> {
+        self.shutdown_tx.clone()
+    }
+
     fn set_node_initialized(&mut self) {
+        self.startup.finish();
         let (lock, cvar) = &*self.initialized;
         let mut started = lock.lock().unwrap();
         *started = true;
         cvar.notify_all();
+        self.health.set_ready();
 
         // slog::debug!(self.config.logger, "Node {}: done initializing.", self.id);
     }
@@ -199,43 +668,55 @@ impl Node {
         let mut data_senders = Vec::new();
 
         for node_id in nodes {
-            data_receivers.push(
-                DataReceiver::new(
-                    node_id,
-                    self.id,
-                    zsession.clone(),
-                    self.channels_to_receivers.clone(),
-                    &mut self.control_handler,
-                )
-                .await,
-            );
-
-            data_senders.push(
-                DataSender::new(
-                    node_id,
-                    self.id,
-                    zsession.clone(),
-                    self.channels_to_senders.clone(),
-                    &mut self.control_handler,
+            let data_receiver = DataReceiver::new(
+                node_id,
+                self.id,
+                zsession.clone(),
+                self.channels_to_receivers.clone(),
+                &mut self.control_handler,
+                self.dead_letters.clone(),
+                Arc::clone(&self.context),
+            )
+            .await
+            .with_decompressor(self.stream_decompressor());
+            let data_sender = DataSender::new(
+                node_id,
+                self.id,
+                zsession.clone(),
+                self.channels_to_senders.clone(),
+                &mut self.control_handler,
+                self.config.max_message_size,
+                self.config.max_chunk_size,
+                StreamCompressor::new(self.id, node_id, &self.compressed_streams)
+                    .with_codecs(self.stream_codecs.clone()),
+                self.traffic_shaper.clone(),
+            )
+            .await
+            .with_serialization_pool(self.serialization_pool.clone());
+            #[cfg(feature = "zenoh_zerocopy_transport")]
+            let (data_receiver, data_sender) = {
+                let shm_config = ShmPoolConfig::from_configuration(&self.config);
+                (
+                    data_receiver.with_shm_config(shm_config.clone()),
+                    data_sender.with_shm_config(shm_config),
                 )
-                .await,
-            );
+            };
+            data_receivers.push(data_receiver);
+            data_senders.push(data_sender);
         }
         (data_senders, data_receivers)
     }
 
-    /// Splits a vector of TCPStreams into `DataSender`s and `DataReceiver`s.
+    /// Splits the framed halves of the connections to the data peers into `DataSender`s and
+    /// `DataReceiver`s.
     #[cfg(feature = "tcp_transport")]
     async fn split_data_streams(
         &mut self,
-        mut streams: Vec<(NodeId, TcpStream)>,
+        mut streams: Vec<(NodeId, (senders::DataSink, receivers::DataStream))>,
     ) -> (Vec<DataSender>, Vec<DataReceiver>) {
         let mut sink_halves = Vec::new();
         let mut stream_halves = Vec::new();
-        while let Some((node_id, stream)) = streams.pop() {
-            // Use the message codec to divide the TCP stream data into messages.
-            let framed = Framed::new(stream, MessageCodec::new());
-            let (split_sink, split_stream) = framed.split();
+        while let Some((node_id, (split_sink, split_stream))) = streams.pop() {
             // Create an ERDOS receiver for the stream half.
             stream_halves.push(
                 DataReceiver::new(
@@ -243,8 +724,11 @@ impl Node {
                     split_stream,
                     self.channels_to_receivers.clone(),
                     &mut self.control_handler,
+                    self.dead_letters.clone(),
+                    Arc::clone(&self.context),
                 )
-                .await,
+                .await
+                .with_decompressor(self.stream_decompressor()),
             );
 
             // Create an ERDOS sender for the sink half.
@@ -254,13 +738,88 @@ impl Node {
                     split_sink,
                     self.channels_to_senders.clone(),
                     &mut self.control_handler,
+                    self.config.max_chunk_size,
+                    StreamCompressor::new(self.id, node_id, &self.compressed_streams)
+                        .with_codecs(self.stream_codecs.clone()),
+                    self.traffic_shaper.clone(),
                 )
-                .await,
+                .await
+                .with_serialization_pool(self.serialization_pool.clone()),
             );
         }
         (sink_halves, stream_halves)
     }
 
+    /// Returns the codec of the messages sent to and received from other nodes.
+    #[cfg(feature = "tcp_transport")]
+    fn data_codec(&self) -> MessageCodec {
+        MessageCodec::with_max_frame_size(self.config.max_message_size)
+            .with_compact_headers(self.config.compact_headers)
+    }
+
+    /// Uses the message codec to divide the TCP stream data into messages, which are sent and
+    /// received with io_uring if the `io_uring` feature is enabled and the kernel supports it.
+    #[cfg(feature = "tcp_transport")]
+    fn frame_data_stream(
+        stream: TcpStream,
+        codec: MessageCodec,
+    ) -> (senders::DataSink, receivers::DataStream) {
+        #[cfg(all(feature = "io_uring", target_os = "linux"))]
+        let (stream, codec) = match communication::UringFramed::new(stream, codec) {
+            Ok(framed) => {
+                let (split_sink, split_stream) = framed.split();
+                return (Box::pin(split_sink), Box::pin(split_stream));
+            }
+            Err(stream_and_codec) => stream_and_codec,
+        };
+        let framed = Framed::new(stream, codec);
+        let (split_sink, split_stream) = framed.split();
+        (Box::pin(split_sink), Box::pin(split_stream))
+    }
+
+    /// Connects the data planes with TCP streams to the data addresses, or with Unix domain
+    /// sockets at the data socket paths with the `uds_transport` feature, and frames the
+    /// connections with the message codec.
+    #[cfg(feature = "tcp_transport")]
+    async fn create_data_streams(
+        &self,
+    ) -> Vec<(NodeId, (senders::DataSink, receivers::DataStream))> {
+        #[cfg(feature = "uds_transport")]
+        {
+            if let Some(paths) = &self.config.data_socket_paths {
+                let streams = communication::create_unix_streams_to_peers(
+                    paths,
+                    self.id,
+                    &self.data_peers,
+                    self.config.authenticator.as_deref(),
+                    &self.config.logger,
+                )
+                .await;
+                return streams
+                    .into_iter()
+                    .map(|(node_id, stream)| {
+                        let (sink, stream) = Framed::new(stream, self.data_codec()).split();
+                        let halves: (senders::DataSink, receivers::DataStream) =
+                            (Box::pin(sink), Box::pin(stream));
+                        (node_id, halves)
+                    })
+                    .collect();
+            }
+        }
+        let streams = communication::create_tcp_streams_to_peers(
+            self.config.data_addresses.clone(),
+            self.id,
+            &self.data_peers,
+            self.config.authenticator.as_deref(),
+            &self.config.logger,
+        )
+        .await;
+        streams
+            .into_iter()
+            .map(|(node_id, stream)| (node_id, Self::frame_data_stream(stream, self.data_codec())))
+            .collect()
+    }
+
     /// Splits a vector of TCPStreams into `ControlMessageHandler`, `ControlSender`s and `ControlReceiver`s.
     #[cfg(feature = "tcp_transport")]
     async fn split_control_streams(
@@ -277,13 +836,13 @@ impl Node {
             // Create an control receiver for the stream half.
             control_receivers.push(ControlReceiver::new(
                 node_id,
-                split_stream,
+                Box::pin(split_stream),
                 &mut self.control_handler,
             ));
             // Create an control sender for the sink half.
             control_senders.push(ControlSender::new(
                 node_id,
-                split_sink,
+                Box::pin(split_sink),
                 &mut self.control_handler,
             ));
         }
@@ -291,113 +850,929 @@ impl Node {
         (control_senders, control_receivers)
     }
 
-    async fn wait_for_communication_layer_initialized(&mut self) -> Result<(), String> {
-        let num_nodes = self.config.data_addresses.len();
+    /// Creates `DataSender`s and `DataReceiver`s which connect to the data peers lazily.
+    #[cfg(feature = "tcp_transport")]
+    async fn split_lazy_data_streams(&mut self) -> (Vec<DataSender>, Vec<DataReceiver>) {
+        let warm_up_peers = self.scheduled_graph().get_warm_up_peers(self.id);
+        let data_address = self.config.data_addresses[self.id];
+        let listener = TcpListener::bind(&data_address)
+            .await
+            .unwrap_or_else(|e| panic!("Node {}: unable to bind {}: {}", self.id, data_address, e));
 
-        let mut control_senders_initialized = HashSet::new();
-        control_senders_initialized.insert(self.id);
-        let mut control_receivers_initialized = HashSet::new();
-        control_receivers_initialized.insert(self.id);
-        let mut data_senders_initialized = HashSet::new();
-        data_senders_initialized.insert(self.id);
-        let mut data_receivers_initialized = HashSet::new();
-        data_receivers_initialized.insert(self.id);
-
-        while control_senders_initialized.len() < num_nodes
-            || control_receivers_initialized.len() < num_nodes
-            || data_senders_initialized.len() < num_nodes
-            || data_receivers_initialized.len() < num_nodes
-        {
-            let msg = self
-                .control_handler
-                .read_sender_or_receiver_initialized()
+        let mut acceptor = communication::LazyAcceptor::new(self.id);
+        let mut data_senders = Vec::new();
+        let mut data_receivers = Vec::new();
+        for node_id in self.data_peers.clone() {
+            data_receivers.push(
+                DataReceiver::new(
+                    node_id,
+                    acceptor.data_stream(node_id, self.config.max_message_size),
+                    self.channels_to_receivers.clone(),
+                    &mut self.control_handler,
+                    self.dead_letters.clone(),
+                    Arc::clone(&self.context),
+                )
                 .await
-                .map_err(|e| format!("Error receiving control message: {:?}", e))?;
-            match msg {
-                ControlMessage::ControlSenderInitialized(node_id) => {
-                    control_senders_initialized.insert(node_id);
-                }
-                ControlMessage::ControlReceiverInitialized(node_id) => {
-                    control_receivers_initialized.insert(node_id);
-                }
-                ControlMessage::DataSenderInitialized(node_id) => {
-                    data_senders_initialized.insert(node_id);
-                }
-                ControlMessage::DataReceiverInitialized(node_id) => {
-                    data_receivers_initialized.insert(node_id);
-                }
-                _ => unreachable!(),
-            };
+                .with_decompressor(self.stream_decompressor()),
+            );
+            let sink = communication::lazy_data_sink(
+                self.config.data_addresses[node_id],
+                self.id,
+                node_id,
+                warm_up_peers.contains(&node_id),
+                self.data_codec(),
+                self.config.authenticator.clone(),
+                self.config.logger.clone(),
+            );
+            data_senders.push(
+                DataSender::new(
+                    node_id,
+                    sink,
+                    self.channels_to_senders.clone(),
+                    &mut self.control_handler,
+                    self.config.max_chunk_size,
+                    StreamCompressor::new(self.id, node_id, &self.compressed_streams)
+                        .with_codecs(self.stream_codecs.clone()),
+                    self.traffic_shaper.clone(),
+                )
+                .await
+                .with_serialization_pool(self.serialization_pool.clone()),
+            );
         }
-        Ok(())
+        let logger = self.config.logger.clone();
+        let authenticator = self.config.authenticator.clone();
+        let id = self.id;
+        tokio::spawn(async move {
+            if let Err(e) = acceptor.run(listener, authenticator, logger.clone()).await {
+                slog::error!(
+                    logger,
+                    "Node {}: accepting lazy connections errored with {:?}",
+                    id,
+                    e
+                );
+            }
+        });
+        (data_senders, data_receivers)
     }
 
-    async fn wait_for_local_operators_initialized(
+    /// Splits a vector of TCPStreams shared by the control and the data planes into
+    /// `ControlSender`s, `ControlReceiver`s, `DataSender`s and `DataReceiver`s.
+    #[cfg(feature = "tcp_transport")]
+    async fn split_multiplexed_streams(
         &mut self,
-        mut rx_from_operators: UnboundedReceiver<ControlMessage>,
-        num_local_operators: usize,
+        streams: Vec<(NodeId, TcpStream)>,
+    ) -> (
+        Vec<ControlSender>,
+        Vec<ControlReceiver>,
+        Vec<DataSender>,
+        Vec<DataReceiver>,
     ) {
-        let mut initialized_operators = HashSet::new();
-        while initialized_operators.len() < num_local_operators {
-            if let Some(ControlMessage::OperatorInitialized(op_id)) = rx_from_operators.recv().await
-            {
-                initialized_operators.insert(op_id);
-            }
+        let mut control_senders = Vec::new();
+        let mut control_receivers = Vec::new();
+        let mut data_senders = Vec::new();
+        let mut data_receivers = Vec::new();
+
+        for (node_id, stream) in streams {
+            let connection =
+                communication::multiplex(stream, self.data_codec(), self.config.logger.clone());
+            control_receivers.push(ControlReceiver::new(
+                node_id,
+                connection.control_stream,
+                &mut self.control_handler,
+            ));
+            control_senders.push(ControlSender::new(
+                node_id,
+                connection.control_sink,
+                &mut self.control_handler,
+            ));
+            data_receivers.push(
+                DataReceiver::new(
+                    node_id,
+                    connection.data_stream,
+                    self.channels_to_receivers.clone(),
+                    &mut self.control_handler,
+                    self.dead_letters.clone(),
+                    Arc::clone(&self.context),
+                )
+                .await
+                .with_decompressor(self.stream_decompressor()),
+            );
+            data_senders.push(
+                DataSender::new(
+                    node_id,
+                    connection.data_sink,
+                    self.channels_to_senders.clone(),
+                    &mut self.control_handler,
+                    self.config.max_chunk_size,
+                    StreamCompressor::new(self.id, node_id, &self.compressed_streams)
+                        .with_codecs(self.stream_codecs.clone()),
+                    self.traffic_shaper.clone(),
+                )
+                .await
+                .with_serialization_pool(self.serialization_pool.clone()),
+            );
         }
-    }
 
-    async fn broadcast_local_operators_initialized(&mut self) -> Result<(), String> {
-        slog::debug!(
-            self.config.logger,
-            "Node {}: initialized all operators on this node.",
-            self.id
-        );
-        self.control_handler
-            .broadcast_to_nodes(ControlMessage::AllOperatorsInitializedOnNode(self.id))
-            .map_err(|e| format!("Error broadcasting control message: {:?}", e))
+        (
+            control_senders,
+            control_receivers,
+            data_senders,
+            data_receivers,
+        )
     }
 
-    async fn wait_for_all_operators_initialized(&mut self) -> Result<(), String> {
+    /// Returns the nodes with which the node shares channels in the scheduled dataflow graph.
+    ///
+    /// Returns all other nodes if the control and data planes share connections.
+    fn get_data_peers(&self) -> Vec<NodeId> {
         let num_nodes = self.config.data_addresses.len();
-        let mut initialized_nodes = HashSet::new();
-        initialized_nodes.insert(self.id);
-        while initialized_nodes.len() < num_nodes {
-            match self
-                .control_handler
-                .read_all_operators_initialized_on_node_msg()
-                .await
-            {
-                Ok(node_id) => {
-                    initialized_nodes.insert(node_id);
-                }
-                Err(e) => {
-                    return Err(format!("Error waiting for other nodes to set up: {:?}", e));
-                }
-            }
+        if cfg!(feature = "tcp_transport") && self.config.multiplex_connections {
+            return (0..num_nodes).filter(|&id| id != self.id).collect();
         }
-        Ok(())
+        self.scheduled_graph().get_peers(self.id)
     }
 
-    async fn run_operators(&mut self) -> Result<(), String> {
-        self.wait_for_communication_layer_initialized().await?;
-
-        let graph_ref = self
-            .dataflow_graph
+    fn scheduled_graph(&self) -> &Graph {
+        self.scheduled_graph
             .as_ref()
-            .unwrap_or_else(|| panic!("Node {}: dataflow graph must be set.", self.id));
-        let graph = scheduler::schedule(graph_ref);
-        if let Some(filename) = &self.config.graph_filename {
-            graph.to_dot(filename.as_str()).map_err(|e| e.to_string())?;
-        }
+            .unwrap_or_else(|| panic!("Node {}: dataflow graph must be scheduled.", self.id))
+    }
 
-        let channel_manager = ChannelManager::new(
-            &graph,
-            self.id,
-            Arc::clone(&self.channels_to_receivers),
-            Arc::clone(&self.channels_to_senders),
-        )
-        .await;
-        // Execute operators scheduled on the current node.
+    /// Advances the startup state machine with a message, and reports the peers which the node
+    /// still waits for on the health endpoints.
+    fn handle_startup_msg(&self, msg: ControlMessage) -> Result<(), String> {
+        if let ControlMessage::StreamCodecs(node_id, codecs) = &msg {
+            let local_codecs = communication::sorted_codecs(&self.stream_codecs);
+            return communication::check_codecs(*node_id, &local_codecs, codecs)
+                .map_err(|e| format!("Node {}: {}", self.id, e));
+        }
+        let mut state = self.startup_state.lock().unwrap();
+        if let ControlMessage::AllOperatorsInitializedOnNode(node_id, run_id) = &msg {
+            self.run_id.propose(*node_id, *run_id);
+        }
+        if let Some(msg) = state.handle(msg)? {
+            slog::warn!(
+                self.config.logger,
+                "Node {}: ignoring unexpected control message during startup: {:?}",
+                self.id,
+                msg
+            );
+        }
+        self.health.set_startup_status(state.describe_pending());
+        Ok(())
+    }
+
+    /// Logs the peers which the node waits for if the startup makes no progress.
+    fn warn_startup_stalled(&self) {
+        slog::warn!(
+            self.config.logger,
+            "Node {}: startup is taking long, {}",
+            self.id,
+            self.startup_state.lock().unwrap().describe_pending()
+        );
+    }
+
+    async fn wait_for_communication_layer_initialized(&mut self) -> Result<(), String> {
+        {
+            let mut state = self.startup_state.lock().unwrap();
+            state.set_data_peers(&self.data_peers);
+            self.health.set_startup_status(state.describe_pending());
+        }
+        while !self.startup_state.lock().unwrap().is_connected() {
+            let msg = match tokio::time::timeout(
+                STARTUP_WARNING_INTERVAL,
+                self.control_handler.read_sender_or_receiver_initialized(),
+            )
+            .await
+            {
+                Ok(msg) => msg.map_err(|e| {
+                    format!(
+                        "Error receiving control message while {}: {:?}",
+                        self.startup_state.lock().unwrap().describe_pending(),
+                        e
+                    )
+                })?,
+                Err(_) => {
+                    self.warn_startup_stalled();
+                    continue;
+                }
+            };
+            self.handle_startup_msg(msg)?;
+        }
+        Ok(())
+    }
+
+    /// Waits for the local operators to finish setting up.
+    ///
+    /// Fails if an operator does not finish setting up within its setup timeout, and tells the
+    /// other nodes, which would otherwise wait for the node forever.
+    async fn wait_for_local_operators_initialized(
+        &mut self,
+        rx_from_operators: &mut UnboundedReceiver<ControlMessage>,
+        setup_timeouts: Vec<(OperatorId, String, Duration)>,
+    ) -> Result<(), String> {
+        let start = tokio::time::Instant::now();
+        let mut pending_operators: HashMap<OperatorId, (String, tokio::time::Instant)> =
+            setup_timeouts
+                .into_iter()
+                .map(|(op_id, name, timeout)| (op_id, (name, start + timeout)))
+                .collect();
+        while let Some(deadline) = pending_operators.values().map(|(_, d)| *d).min() {
+            match tokio::time::timeout_at(deadline, rx_from_operators.recv()).await {
+                Ok(Some(ControlMessage::OperatorInitialized(op_id))) => {
+                    pending_operators.remove(&op_id);
+                }
+                Ok(Some(_)) => (),
+                Ok(None) => return Err("Operators stopped while setting up".to_string()),
+                Err(_) => {
+                    let now = tokio::time::Instant::now();
+                    let mut names: Vec<_> = pending_operators
+                        .values()
+                        .filter(|(_, deadline)| *deadline <= now)
+                        .map(|(name, _)| name.clone())
+                        .collect();
+                    names.sort();
+                    let reason = format!(
+                        "timed out during operator setup waiting for operators [{}]",
+                        names.join(", ")
+                    );
+                    if let Err(e) =
+                        self.control_handler
+                            .broadcast_to_nodes(ControlMessage::NodeSetupFailed(
+                                self.id,
+                                reason.clone(),
+                            ))
+                    {
+                        slog::warn!(
+                            self.config.logger,
+                            "Node {}: unable to notify the other nodes that setup failed: {:?}",
+                            self.id,
+                            e
+                        );
+                    }
+                    return Err(format!("Node {} {}", self.id, reason));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn broadcast_local_operators_initialized(&mut self) -> Result<(), String> {
+        slog::debug!(
+            self.config.logger,
+            "Node {}: initialized all operators on this node.",
+            self.id
+        );
+        self.control_handler
+            .broadcast_to_nodes(ControlMessage::StreamCodecs(
+                self.id,
+                communication::sorted_codecs(&self.stream_codecs),
+            ))
+            .map_err(|e| format!("Error broadcasting control message: {:?}", e))?;
+        self.control_handler
+            .broadcast_to_nodes(ControlMessage::AllOperatorsInitializedOnNode(
+                self.id,
+                self.run_id.get(),
+            ))
+            .map_err(|e| format!("Error broadcasting control message: {:?}", e))
+    }
+
+    async fn wait_for_all_operators_initialized(&mut self) -> Result<(), String> {
+        while self.startup_state.lock().unwrap().phase() < PeerPhase::Ready {
+            let msg = match tokio::time::timeout(
+                STARTUP_WARNING_INTERVAL,
+                self.control_handler.read_node_setup_msg(),
+            )
+            .await
+            {
+                Ok(msg) => msg.map_err(|e| {
+                    format!(
+                        "Error waiting for other nodes to set up while {}: {:?}",
+                        self.startup_state.lock().unwrap().describe_pending(),
+                        e
+                    )
+                })?,
+                Err(_) => {
+                    self.warn_startup_stalled();
+                    continue;
+                }
+            };
+            self.handle_startup_msg(msg)?;
+        }
+        Ok(())
+    }
+
+    /// Waits until an operator fails with
+    /// [`ErrorPolicy::FailDataflow`](crate::dataflow::ErrorPolicy::FailDataflow), either on the
+    /// current node or on another node.
+    ///
+    /// Failures of local operators are broadcast to all other nodes.
+    ///
+    /// Also handles the requests of the [`NodeHandle`] in the meantime, runs the operators whose
+    /// dependencies finished running, and runs the operators added to the dataflow once they
+    /// finished setting up on all nodes.
+    async fn wait_for_dataflow_failure(
+        &mut self,
+        rx_from_operators: &mut UnboundedReceiver<ControlMessage>,
+        handle_rx: &mut UnboundedReceiver<HandleRequest>,
+        start_order: &mut StartOrder,
+    ) -> Result<(), String> {
+        let mut cluster_info_requests = ClusterInfoRequests::new(self.config.data_addresses.len());
+        let mut quiescence = QuiescenceDetector::new();
+        let mut additions = OperatorAdditions::new(self.config.data_addresses.len());
+        // The leader node asks all nodes to checkpoint their stateful operators.
+        let checkpointing = self.id == 0 && self.config.checkpoint_backend.is_some();
+        let checkpoint_interval = self.config.checkpoint_interval;
+        let mut checkpoint_ticks = tokio::time::interval_at(
+            tokio::time::Instant::now() + checkpoint_interval,
+            checkpoint_interval,
+        );
+        let has_peers = self.config.data_addresses.len() > 1;
+        let mut heartbeat_ticks = tokio::time::interval(self.config.heartbeat_interval);
+        let reporting_progress = has_peers && self.context.watermark_progress.is_watching();
+        let mut progress_ticks = tokio::time::interval(WATERMARK_PROGRESS_INTERVAL);
+        let granting_credits = has_peers
+            && self
+                .bounded_streams
+                .values()
+                .any(|bound| bound.policy == BackpressurePolicy::Block);
+        let mut credit_ticks = tokio::time::interval(CREDIT_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = progress_ticks.tick(), if reporting_progress => {
+                    let updates = self.context.watermark_progress.take_unreported();
+                    if !updates.is_empty() {
+                        self.control_handler
+                            .broadcast_to_nodes(ControlMessage::WatermarkProgress(updates))
+                            .map_err(|e| format!("Error broadcasting control message: {:?}", e))?;
+                    }
+                }
+                _ = credit_ticks.tick(), if granting_credits => {
+                    for (node_id, credits) in self.context.credits.take_grants() {
+                        self.control_handler
+                            .send_to_node(node_id, ControlMessage::StreamCredits(self.id, credits))
+                            .map_err(|e| format!("Error sending stream credits: {:?}", e))?;
+                    }
+                }
+                _ = heartbeat_ticks.tick(), if has_peers => {
+                    if let Err(e) = self.control_handler.send_heartbeats(self.id) {
+                        slog::warn!(
+                            self.config.logger,
+                            "Node {}: unable to send heartbeats: {:?}", self.id, e
+                        );
+                    }
+                    let failed_nodes =
+                        self.control_handler.check_heartbeats(self.config.heartbeat_timeout);
+                    if let Some(&node_id) = failed_nodes.first() {
+                        return Err(self.report_node_failure(node_id, "sent no heartbeat"));
+                    }
+                }
+                _ = checkpoint_ticks.tick(), if checkpointing => {
+                    let checkpoint_id = checkpoint::next_checkpoint_id();
+                    self.control_handler
+                        .broadcast_to_nodes(ControlMessage::TakeCheckpoint(checkpoint_id))
+                        .map_err(|e| format!("Error broadcasting control message: {:?}", e))?;
+                    self.health.request_checkpoint(checkpoint_id);
+                    checkpoint::request(checkpoint_id);
+                }
+                Some(request) = handle_rx.recv() => match request {
+                    HandleRequest::AddOperators(graph, reply_tx) => {
+                        self.add_operators(graph, reply_tx, &mut additions, start_order).await?;
+                    }
+                    request => {
+                        self.handle_request(request, &mut cluster_info_requests, &mut quiescence)?;
+                    }
+                },
+                Some(msg) = rx_from_operators.recv() => match msg {
+                    ControlMessage::OperatorFailed(op_id, reason) => {
+                        self.control_handler
+                            .broadcast_to_nodes(ControlMessage::OperatorFailed(op_id, reason.clone()))
+                            .map_err(|e| format!("Error broadcasting control message: {:?}", e))?;
+                        return Err(format!("Operator {:?} failed: {}", op_id, reason));
+                    }
+                    ControlMessage::OperatorRan(op_id) => {
+                        self.control_handler
+                            .broadcast_to_nodes(ControlMessage::OperatorRan(op_id))
+                            .map_err(|e| format!("Error broadcasting control message: {:?}", e))?;
+                        start_order.operator_ran(op_id)?;
+                    }
+                    ControlMessage::OperatorInitialized(op_id) => {
+                        if let Some(ids) = additions.operator_initialized(op_id) {
+                            self.operators_added(self.id, ids, &mut additions, start_order)?;
+                        }
+                    }
+                    _ => (),
+                },
+                msg = self.control_handler.read() => match msg {
+                    Ok(ControlMessage::OperatorFailed(op_id, reason)) => {
+                        return Err(format!("Operator {:?} on another node failed: {}", op_id, reason));
+                    }
+                    Ok(ControlMessage::OperatorRan(op_id)) => start_order.operator_ran(op_id)?,
+                    Ok(ControlMessage::OperatorsAdded(node_id, operator_ids)) => {
+                        self.operators_added(node_id, operator_ids, &mut additions, start_order)?;
+                    }
+                    Ok(ControlMessage::StreamDemand(change)) => self.apply_stream_demand(change)?,
+                    Ok(ControlMessage::User(bytes, type_tag, origin)) => {
+                        self.route_user_control_message(bytes, type_tag, origin)?;
+                    }
+                    Ok(ControlMessage::StreamDictionary(dictionary)) => {
+                        self.route_stream_dictionary(dictionary)?;
+                    }
+                    Ok(ControlMessage::NodeInfoRequest(node_id)) => {
+                        let info = NodeInfo::current(self.id);
+                        self.control_handler
+                            .send_to_node(node_id, ControlMessage::NodeInfo(info))
+                            .map_err(|e| format!("Error sending node info: {:?}", e))?;
+                    }
+                    Ok(ControlMessage::NodeInfo(info)) => cluster_info_requests.add_reply(info),
+                    Ok(ControlMessage::WatermarkProgress(updates)) => {
+                        self.context.watermark_progress.apply_reported(updates);
+                    }
+                    Ok(ControlMessage::StreamCredits(node_id, credits)) => {
+                        for (stream_id, credits) in credits {
+                            self.traffic_shaper.add_credits(node_id, stream_id, credits);
+                        }
+                    }
+                    Ok(ControlMessage::PeerUnreachable(node_id)) => {
+                        return Err(self.report_node_failure(
+                            node_id,
+                            "did not reply to the liveliness checks",
+                        ));
+                    }
+                    Ok(ControlMessage::NodeFailed(node_id)) => {
+                        return Err(self.node_failed(node_id, "was reported as failed"));
+                    }
+                    Ok(ControlMessage::TakeCheckpoint(checkpoint_id)) => {
+                        self.health.request_checkpoint(checkpoint_id);
+                        checkpoint::request(checkpoint_id);
+                    }
+                    Ok(ControlMessage::QuiescenceToken(mut token)) => {
+                        if token.initiator() == self.id {
+                            self.complete_quiescence_round(token, &mut quiescence);
+                        } else {
+                            token.add_local_counters(&self.context.counters);
+                            self.pass_quiescence_token(token, &mut quiescence)?;
+                        }
+                    }
+                    Ok(ControlMessage::SetKnob(name, value, origin)) => {
+                        if let Err(e) = self.knobs.set(&name, &value, origin) {
+                            slog::warn!(
+                                self.config.logger,
+                                "Node {}: rejected update from node {}: {}", self.id, origin, e
+                            );
+                        }
+                    }
+                    Ok(_) => (),
+                    Err(e) => return Err(format!("Error receiving control message: {:?}", e)),
+                },
+            }
+        }
+    }
+
+    /// Reports the failure of a node detected by the current node to the other nodes, which may
+    /// still reach the failed node, and tears down the dataflow.
+    fn report_node_failure(&mut self, node_id: NodeId, reason: &str) -> String {
+        if let Err(e) = self
+            .control_handler
+            .broadcast_to_nodes(ControlMessage::NodeFailed(node_id))
+        {
+            slog::warn!(
+                self.config.logger,
+                "Node {}: unable to report the failure of node {}: {:?}",
+                self.id,
+                node_id,
+                e
+            );
+        }
+        self.node_failed(node_id, reason)
+    }
+
+    /// Calls the [node failure callbacks](Node::on_node_failure), and returns the error with which
+    /// the dataflow is torn down.
+    fn node_failed(&self, node_id: NodeId, reason: &str) -> String {
+        slog::error!(
+            self.config.logger,
+            "Node {}: node {} {}, shutting down the dataflow",
+            self.id,
+            node_id,
+            reason
+        );
+        for callback in self.node_failure_callbacks.iter() {
+            (callback)(node_id);
+        }
+        format!("Node {} {}", node_id, reason)
+    }
+
+    /// Sets up the operators which the driver added to the dataflow graph since the node started
+    /// running, and connects them to the streams.
+    ///
+    /// Invalid additions are reported to the [`NodeHandle`] instead of failing the node.
+    async fn add_operators(
+        &mut self,
+        graph: Graph,
+        reply_tx: std::sync::mpsc::Sender<Result<Vec<OperatorId>, String>>,
+        additions: &mut OperatorAdditions,
+        start_order: &mut StartOrder,
+    ) -> Result<(), String> {
+        let (operator_ids, scheduled_graph) = match self.schedule_added_operators(&graph) {
+            Ok(scheduled) => scheduled,
+            Err(e) => {
+                let _ = reply_tx.send(Err(e));
+                return Ok(());
+            }
+        };
+        let old_graph = self.scheduled_graph().clone();
+        let settings = self.channel_settings();
+        let channel_manager = match ChannelManager::for_added_channels(
+            &old_graph,
+            &scheduled_graph,
+            &settings,
+            &mut self.receiver_pushers,
+        )
+        .await
+        {
+            Ok(channel_manager) => Arc::new(std::sync::Mutex::new(channel_manager)),
+            Err(e) => {
+                let _ = reply_tx.send(Err(e));
+                return Ok(());
+            }
+        };
+        self.dataflow_graph = Some(graph);
+        self.scheduled_graph = Some(scheduled_graph.clone());
+
+        let operator_tx = self.operator_tx.clone().unwrap();
+        let mut channels_to_operators = HashMap::new();
+        for operator_id in &operator_ids {
+            let operator_info = scheduled_graph.get_operator(*operator_id).unwrap();
+            if operator_info.node_id == self.id {
+                // The node does not wait for the added operators to finish running.
+                let (tx, _) = self.spawn_operator(
+                    operator_info,
+                    &scheduled_graph,
+                    &channel_manager,
+                    &operator_tx,
+                );
+                channels_to_operators.insert(*operator_id, tx);
+            }
+        }
+        // Set up the streams which the driver added with the operators.
+        if let Some(driver) = scheduled_graph.get_driver(self.id) {
+            let old_sections = old_graph
+                .get_driver(self.id)
+                .map(|driver| driver.sections)
+                .unwrap_or_default();
+            for mut section in driver.sections {
+                let (num_setup_hooks, num_teardown_hooks) = old_sections
+                    .iter()
+                    .find(|old_section| old_section.name == section.name)
+                    .map_or((0, 0), |old_section| {
+                        (
+                            old_section.setup_hooks.len(),
+                            old_section.teardown_hooks.len(),
+                        )
+                    });
+                section.setup_hooks.drain(..num_setup_hooks);
+                section.teardown_hooks.drain(..num_teardown_hooks);
+                for setup_hook in section.setup_hooks.iter() {
+                    (setup_hook)(Arc::clone(&channel_manager));
+                }
+                self.driver_sections.push(section);
+            }
+        }
+        if additions.add(operator_ids.clone(), channels_to_operators, reply_tx) {
+            self.operators_added(self.id, operator_ids, additions, start_order)?;
+        }
+        Ok(())
+    }
+
+    /// Places the operators of the graph, which extends the graph the node runs with operators,
+    /// on nodes.
+    ///
+    /// Returns the IDs of the added operators and the scheduled graph, or an error if the graph
+    /// changes the operators which already run.
+    fn schedule_added_operators(&self, graph: &Graph) -> Result<(Vec<OperatorId>, Graph), String> {
+        let diff = GraphDiff::new(self.dataflow_graph.as_ref().unwrap(), graph);
+        if !diff.is_additive() {
+            return Err(format!(
+                "Operators can only be added to the running dataflow, but the graph removes \
+                 operators {:?}, changes operators {:?}, and removes streams {:?}",
+                diff.removed_operators, diff.changed_operators, diff.removed_streams
+            ));
+        }
+        let scheduled_graph = scheduler::schedule(
+            graph,
+            self.config.scheduler.as_deref(),
+            self.partitioner.as_ref(),
+            &self.config.node_labels,
+        )
+        .map_err(|e| e.to_string())?;
+        for operator in self.scheduled_graph().get_operators() {
+            let node_id = scheduled_graph
+                .get_operator(operator.id)
+                .map(|op| op.node_id);
+            if node_id != Some(operator.node_id) {
+                return Err(format!(
+                    "Adding operators moves the running operator {} to another node",
+                    operator.id
+                ));
+            }
+        }
+        Ok((diff.added_operators, scheduled_graph))
+    }
+
+    /// Records that the added operators finished setting up on the node, which the current node
+    /// tells the other nodes, and tells the local operators to run once the operators finished
+    /// setting up on all nodes.
+    fn operators_added(
+        &mut self,
+        node_id: NodeId,
+        operator_ids: Vec<OperatorId>,
+        additions: &mut OperatorAdditions,
+        start_order: &mut StartOrder,
+    ) -> Result<(), String> {
+        if node_id == self.id {
+            self.control_handler
+                .broadcast_to_nodes(ControlMessage::OperatorsAdded(
+                    node_id,
+                    operator_ids.clone(),
+                ))
+                .map_err(|e| format!("Error broadcasting control message: {:?}", e))?;
+        }
+        if let Some(ready) = additions.node_ready(node_id, operator_ids) {
+            start_order.add_operators(ready.channels_to_operators);
+            start_order.run_ready_operators()?;
+            // The handle may have been dropped while waiting.
+            let _ = ready.reply_tx.send(Ok(ready.operator_ids));
+        }
+        Ok(())
+    }
+
+    /// Handles a request of the [`NodeHandle`].
+    fn handle_request(
+        &mut self,
+        request: HandleRequest,
+        cluster_info_requests: &mut ClusterInfoRequests,
+        quiescence: &mut QuiescenceDetector,
+    ) -> Result<(), String> {
+        let msg = match request {
+            HandleRequest::ClusterInfo(reply_tx) => {
+                if !cluster_info_requests.add_request(self.id, reply_tx) {
+                    return Ok(());
+                }
+                ControlMessage::NodeInfoRequest(self.id)
+            }
+            HandleRequest::SetKnob(name, value) => ControlMessage::SetKnob(name, value, self.id),
+            HandleRequest::AwaitQuiescent(deadline, reply_tx) => {
+                return match quiescence.add_request(
+                    self.id,
+                    &self.context.counters,
+                    deadline,
+                    reply_tx,
+                ) {
+                    Some(token) => self.pass_quiescence_token(token, quiescence),
+                    None => Ok(()),
+                };
+            }
+            HandleRequest::ProbeQuiescence => {
+                return match quiescence.start_round(self.id, &self.context.counters) {
+                    Some(token) => self.pass_quiescence_token(token, quiescence),
+                    None => Ok(()),
+                };
+            }
+            // Handled by the caller, as setting up the channels of the operators is
+            // asynchronous.
+            HandleRequest::AddOperators(..) => unreachable!(),
+        };
+        self.control_handler
+            .broadcast_to_nodes(msg)
+            .map_err(|e| format!("Error broadcasting control message: {:?}", e))
+    }
+
+    /// Passes a quiescence token, to which the current node added its counters, to the next
+    /// node in the ring.
+    fn pass_quiescence_token(
+        &mut self,
+        token: QuiescenceToken,
+        quiescence: &mut QuiescenceDetector,
+    ) -> Result<(), String> {
+        let next_node = (self.id + 1) % self.config.data_addresses.len();
+        if next_node == self.id {
+            // The current node is the only node.
+            self.complete_quiescence_round(token, quiescence);
+            return Ok(());
+        }
+        self.control_handler
+            .send_to_node(next_node, ControlMessage::QuiescenceToken(token))
+            .map_err(|e| format!("Error sending quiescence token: {:?}", e))
+    }
+
+    /// Checks whether the dataflow is quiescent once the token of a round started by the
+    /// current node visited all nodes, and otherwise starts another round shortly after.
+    fn complete_quiescence_round(
+        &mut self,
+        token: QuiescenceToken,
+        quiescence: &mut QuiescenceDetector,
+    ) {
+        if quiescence.complete_round(token) {
+            let handle_tx = self.handle_tx.clone();
+            tokio::spawn(async move {
+                tokio::time::delay_for(quiescence::PROBE_INTERVAL).await;
+                let _ = handle_tx.send(HandleRequest::ProbeQuiescence);
+            });
+        }
+    }
+
+    /// Applies a subscription change requested on the current process or on another node, and
+    /// propagates it to the other nodes unless it was already applied.
+    fn apply_stream_demand(&mut self, change: DemandChange) -> Result<(), String> {
+        if !stream::demand::apply(&change) {
+            return Ok(());
+        }
+        self.control_handler
+            .broadcast_to_nodes(ControlMessage::StreamDemand(change))
+            .map_err(|e| format!("Error broadcasting control message: {:?}", e))
+    }
+
+    /// Forwards a user-defined control message sent on the current process to the other nodes,
+    /// or delivers a message received from another node to the current process.
+    fn route_user_control_message(
+        &mut self,
+        bytes: Vec<u8>,
+        type_tag: String,
+        origin: NodeId,
+    ) -> Result<(), String> {
+        if origin != self.id {
+            // Messages forwarded by another node on the current process were already delivered
+            // when they were sent.
+            if !control::is_local_node(origin) {
+                control::deliver(&type_tag, &bytes);
+            }
+            return Ok(());
+        }
+        self.control_handler
+            .broadcast_to_nodes(ControlMessage::User(bytes, type_tag, origin))
+            .map_err(|e| format!("Error broadcasting control message: {:?}", e))
+    }
+
+    /// Forwards a dictionary trained by a local data sender to the receiving node, or a
+    /// dictionary received from another node to the corresponding data receiver.
+    fn route_stream_dictionary(&mut self, dictionary: StreamDictionary) -> Result<(), String> {
+        let result = if dictionary.to_node == self.id {
+            let from_node = dictionary.from_node;
+            self.control_handler
+                .send_to_data_receiver(from_node, ControlMessage::StreamDictionary(dictionary))
+        } else {
+            let to_node = dictionary.to_node;
+            self.control_handler
+                .send_to_node(to_node, ControlMessage::StreamDictionary(dictionary))
+        };
+        result.map_err(|e| format!("Error forwarding stream dictionary: {:?}", e))
+    }
+
+    /// Runs the data receivers, each on the next core if the node runs on cores with core
+    /// affinity, so that the operators which read the streams they receive are placed on the
+    /// same cores.
+    fn run_data_receivers(
+        &mut self,
+        mut receivers: Vec<DataReceiver>,
+    ) -> future::LocalBoxFuture<'static, Result<(), communication::CommunicationError>> {
+        let core_pool = match self.core_pool.as_mut() {
+            Some(core_pool) if self.config.core_affinity => core_pool,
+            _ => return receivers::run_receivers(receivers).boxed_local(),
+        };
+        let placement = &self.placement;
+        receivers.sort_by_key(|receiver| receiver.node_id());
+        let results: Vec<_> = receivers
+            .into_iter()
+            .map(|mut receiver| {
+                let core = core_pool.next_core();
+                placement.place_receiver(receiver.node_id(), core);
+                let (result_tx, result_rx) = oneshot::channel();
+                let _ = core_pool.spawn_on(core, async move {
+                    let _ = result_tx.send(receiver.run().await);
+                });
+                // Receivers cancelled by stopping the cores return no error.
+                result_rx.map(|result| result.unwrap_or(Ok(())))
+            })
+            .collect();
+        future::try_join_all(results).map_ok(|_| ()).boxed_local()
+    }
+
+    /// Returns the maximum size of the messages received from other nodes once decompressed,
+    /// which are only limited like the messages sent if they are not split into chunks.
+    fn max_received_message_size(&self) -> Option<usize> {
+        match self.config.max_chunk_size {
+            Some(_) => None,
+            None => self.config.max_message_size,
+        }
+    }
+
+    /// Returns the decompressor of the messages which a data receiver receives from another
+    /// node.
+    fn stream_decompressor(&self) -> StreamDecompressor {
+        StreamDecompressor::with_max_message_size(self.max_received_message_size())
+            .with_codecs(self.stream_codecs.clone())
+    }
+
+    /// Returns the settings with which the node sets up the channels of its streams.
+    fn channel_settings(&self) -> ChannelSettings {
+        ChannelSettings {
+            node_id: self.id,
+            channels_to_receivers: Arc::clone(&self.channels_to_receivers),
+            channels_to_senders: Arc::clone(&self.channels_to_senders),
+            // Oversized messages are split into chunks unless chunking is disabled.
+            max_message_size: match self.config.max_chunk_size {
+                Some(_) => None,
+                None => self.config.max_message_size,
+            },
+            run_id: self.run_id.clone(),
+            conflated_streams: self.conflated_streams.clone(),
+            bounded_streams: self.bounded_streams.clone(),
+            buffer_allocators: self.buffer_allocators.clone(),
+            endpoint_lists: Arc::clone(&self.endpoint_lists),
+            context: Arc::clone(&self.context),
+        }
+    }
+
+    /// Launches a local operator as a separate async task.
+    ///
+    /// Returns the channel on which the operator is told to run, and a future which completes
+    /// once the operator finished running.
+    fn spawn_operator(
+        &mut self,
+        operator_info: OperatorMetadata,
+        graph: &Graph,
+        channel_manager: &Arc<std::sync::Mutex<ChannelManager>>,
+        operator_tx: &UnboundedSender<ControlMessage>,
+    ) -> (
+        UnboundedSender<ControlMessage>,
+        future::BoxFuture<'static, ()>,
+    ) {
+        let name = operator_info
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("{}", operator_info.id));
+        slog::debug!(
+            self.config.logger,
+            "Node {}: starting operator {}",
+            self.id,
+            name
+        );
+        let channel_manager_copy = Arc::clone(channel_manager);
+        let operator_tx_copy = operator_tx.clone();
+        let activity = self.health.add_operator(&name);
+        let context = Arc::clone(&self.context);
+        let slow_callback_threshold = self.config.slow_callback_threshold;
+        let (tx, rx) = mpsc::unbounded_channel();
+        let operator_id = operator_info.id;
+        let source_nodes: Vec<_> = operator_info
+            .read_stream_ids
+            .iter()
+            .filter_map(|stream_id| graph.get_source_node(*stream_id))
+            .filter(|node_id| *node_id != self.id)
+            .collect();
+        let operator_fut = async move {
+            let mut operator_executor =
+                (operator_info.runner)(channel_manager_copy, operator_tx_copy, rx);
+            operator_executor.set_activity(activity);
+            operator_executor.set_context(context);
+            operator_executor.set_default_slow_callback_threshold(slow_callback_threshold);
+            operator_executor.execute().await;
+        };
+        let join_handle = match self.core_pool.as_mut() {
+            Some(core_pool) => {
+                let placement = &self.placement;
+                let core = placement
+                    .place_operator(operator_id, name, &source_nodes, || core_pool.next_core());
+                core_pool.spawn_on(core, operator_fut).boxed()
+            }
+            None => tokio::spawn(operator_fut).map(|_| ()).boxed(),
+        };
+        (tx, join_handle)
+    }
+
+    async fn run_operators(&mut self) -> Result<(), String> {
+        let phase_start = Instant::now();
+        let graph = self.scheduled_graph().clone();
+        if let Some(filename) = &self.config.graph_filename {
+            graph.export(filename.as_str()).map_err(|e| e.to_string())?;
+        }
+
+        // Set up the channels while the senders and receivers initialize, as the channels only
+        // need them to be registered.
+        let settings = self.channel_settings();
+        let mut receiver_pushers = HashMap::new();
+        let channel_manager_fut = ChannelManager::new(&graph, &settings, &mut receiver_pushers);
+        let (channel_manager, result) = future::join(
+            channel_manager_fut,
+            self.wait_for_communication_layer_initialized(),
+        )
+        .await;
+        result?;
+        let channel_manager = channel_manager?;
+        self.receiver_pushers = receiver_pushers;
+        self.startup.record("channels", phase_start);
+        // Execute operators scheduled on the current node.
         let channel_manager = Arc::new(std::sync::Mutex::new(channel_manager));
         let local_operators: Vec<_> = graph
             .get_operators()
@@ -405,68 +1780,191 @@ impl Node {
             .filter(|op| op.node_id == self.id)
             .collect();
 
-        let (operator_tx, rx_from_operators) = mpsc::unbounded_channel();
+        let (operator_tx, mut rx_from_operators) = mpsc::unbounded_channel();
         let mut channels_to_operators = HashMap::new();
 
         let num_local_operators = local_operators.len();
+        let setup_timeouts: Vec<_> = local_operators
+            .iter()
+            .map(|op| {
+                let name = op.name.clone().unwrap_or_else(|| format!("{}", op.id));
+                let timeout = op.setup_timeout.unwrap_or(self.config.setup_timeout);
+                (op.id, name, timeout)
+            })
+            .collect();
 
-        let mut join_handles = Vec::with_capacity(num_local_operators);
+        let mut join_handles: Vec<future::BoxFuture<'static, ()>> =
+            Vec::with_capacity(num_local_operators);
         for operator_info in local_operators {
-            let name = operator_info
-                .name
-                .clone()
-                .unwrap_or_else(|| format!("{}", operator_info.id));
-            slog::debug!(
-                self.config.logger,
-                "Node {}: starting operator {}",
-                self.id,
-                name
-            );
-            let channel_manager_copy = Arc::clone(&channel_manager);
-            let operator_tx_copy = operator_tx.clone();
-            let (tx, rx) = mpsc::unbounded_channel();
-            channels_to_operators.insert(operator_info.id, tx);
-            // Launch the operator as a separate async task.
-            let join_handle = tokio::spawn(async move {
-                let mut operator_executor =
-                    (operator_info.runner)(channel_manager_copy, operator_tx_copy, rx);
-                operator_executor.execute().await;
-            });
+            let operator_id = operator_info.id;
+            let (tx, join_handle) =
+                self.spawn_operator(operator_info, &graph, &channel_manager, &operator_tx);
+            channels_to_operators.insert(operator_id, tx);
             join_handles.push(join_handle);
         }
+        let mut start_order =
+            StartOrder::new(&graph.get_operators(), self.id, channels_to_operators)?;
+
+        self.operator_tx = Some(operator_tx);
+
+        // Apply the subscriptions to streams requested by the driver and the operators.
+        stream::demand::register_node(self.control_handler.get_channel_to_handler());
+        barrier::register_node();
+        control::register_node(self.id, self.control_handler.get_channel_to_handler());
 
         // Wait for all operators to finish setting up.
-        self.wait_for_local_operators_initialized(rx_from_operators, num_local_operators)
-            .await;
+        let phase_start = Instant::now();
+        self.wait_for_local_operators_initialized(&mut rx_from_operators, setup_timeouts)
+            .await?;
+        self.startup.record("operator setup", phase_start);
         // Setup driver on the current node.
+        let phase_start = Instant::now();
         if let Some(driver) = graph.get_driver(self.id) {
-            for setup_hook in driver.setup_hooks {
-                (setup_hook)(Arc::clone(&channel_manager));
+            for section in driver.sections {
+                slog::debug!(
+                    self.config.logger,
+                    "Node {}: setting up driver section {}",
+                    self.id,
+                    section.name
+                );
+                for setup_hook in section.setup_hooks.iter() {
+                    (setup_hook)(Arc::clone(&channel_manager));
+                }
+                self.driver_sections.push(section);
             }
         }
+        self.startup.record("driver setup", phase_start);
         // Broadcast all operators initialized on current node.
+        let phase_start = Instant::now();
         self.broadcast_local_operators_initialized().await?;
         // Wait for all other nodes to finish setting up.
         self.wait_for_all_operators_initialized().await?;
+        self.startup.record("cluster setup", phase_start);
+        // Tag the logs of the node with the agreed run ID.
+        self.config.logger = self
+            .config
+            .logger
+            .new(slog::o!("run_id" => self.run_id.get().to_string()));
+        slog::info!(
+            self.config.logger,
+            "Node {}: running with run ID {}",
+            self.id,
+            self.run_id.get()
+        );
+        let mut handle_rx = self.handle_rx.take().unwrap();
         // Tell driver to run.
         self.set_node_initialized();
-        // Tell all operators to run.
-        for (op_id, tx) in channels_to_operators {
-            tx.send(ControlMessage::RunOperator(op_id))
-                .map_err(|e| format!("Error telling operator to run: {}", e))?;
+        // Tell all operators without pending dependencies to run.
+        start_order.run_ready_operators()?;
+        // Notify process supervisors while the node is live, until the dataflow fails.
+        let watchdog = Watchdog::new(&self.config, self.health.clone());
+        let watchdog_fut = async move {
+            match watchdog {
+                Some(watchdog) => watchdog.run().await,
+                None => future::pending().await,
+            }
+        };
+        // Run the actions of the triggers until the dataflow completes.
+        let triggers_fut = triggers::run(Arc::clone(&self.context), self.knobs.clone(), self.id);
+        // Wait for all operators to finish running, or for an operator to fail the dataflow.
+        tokio::select! {
+            _ = future::join_all(join_handles) => Ok(()),
+            _ = watchdog_fut => Ok(()),
+            _ = triggers_fut => Ok(()),
+            result = self.wait_for_dataflow_failure(
+                &mut rx_from_operators,
+                &mut handle_rx,
+                &mut start_order,
+            ) => result,
         }
-        // Wait for all operators to finish running.
-        future::join_all(join_handles).await;
-        Ok(())
     }
 
-    async fn async_run(&mut self) {
+    pub(crate) async fn async_run(&mut self) {
+        self.startup.start();
         // Assign values used later to avoid lifetime errors.
         let num_nodes = self.config.data_addresses.len();
         let logger = self.config.logger.clone();
+        let health = self.health.clone();
+
+        // Fail before connecting to other nodes if the operators cannot be placed.
+        let graph = self
+            .dataflow_graph
+            .as_ref()
+            .unwrap_or_else(|| panic!("Node {}: dataflow graph must be set.", self.id));
+        match scheduler::schedule(
+            graph,
+            self.config.scheduler.as_deref(),
+            self.partitioner.as_ref(),
+            &self.config.node_labels,
+        ) {
+            Ok(scheduled_graph) => self.scheduled_graph = Some(scheduled_graph),
+            Err(e) => {
+                slog::error!(logger, "Node {}: {}", self.id, e);
+                return;
+            }
+        }
+
+        if self.config.admin_address.is_some() || self.config.metrics_address.is_some() {
+            stream_metrics::enable();
+        }
+        if let Some(backend) = &self.config.checkpoint_backend {
+            checkpoint::set_backend(Arc::clone(backend));
+        }
+        if let Some(metrics_address) = self.config.metrics_address {
+            tokio::spawn(crate::node::admin::serve_metrics(
+                metrics_address,
+                Arc::clone(&self.context),
+            ));
+        }
+        // Serve health checks while the node sets up.
+        if let Some(admin_address) = self.config.admin_address {
+            let (knobs, handle_tx, id) = (self.knobs.clone(), self.handle_tx.clone(), self.id);
+            let shutdown_tx = self.shutdown_tx.clone();
+            let admin = AdminInterface::new(
+                health.clone(),
+                self.config.admin_tokens.clone(),
+                move |name, value| {
+                    knobs.set(name, value, id)?;
+                    handle_tx
+                        .send(HandleRequest::SetKnob(name.to_string(), value.to_string()))
+                        .map_err(|_| "The node is not running".to_string())
+                },
+                move || {
+                    // Error indicates node is already shutting down.
+                    shutdown_tx.clone().try_send(()).ok();
+                },
+            );
+            tokio::spawn(crate::node::admin::serve(admin_address, admin));
+        }
+
+        #[cfg(feature = "zenoh_zerocopy_transport")]
+        if let Some(shm_directory) = &self.config.shm_directory {
+            // The transport sends serialized copies if the shared memory is unavailable.
+            if let Err(e) = communication::set_shm_directory(shm_directory) {
+                slog::warn!(
+                    logger,
+                    "Node {}: unable to use {:?} for shared memory: {:?}",
+                    self.id,
+                    shm_directory,
+                    e
+                );
+            }
+        }
+        #[cfg(feature = "zenoh_zerocopy_transport")]
+        {
+            let num_removed = communication::remove_stale_shm_segments();
+            if num_removed > 0 {
+                slog::info!(
+                    logger,
+                    "Node {}: removed {} shared memory segments of exited processes",
+                    self.id,
+                    num_removed
+                );
+            }
+        }
 
         #[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
-        let zconfig = zenoh::net::config::peer();
+        let zconfig = self.config.zenoh_config();
 
         #[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
         let zsession = Arc::new(zenoh::net::open(zconfig).await.unwrap());
@@ -485,35 +1983,82 @@ impl Node {
         #[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
         zrx.recv().await;
 
+        // Listen for shutdown message.
+        let mut shutdown_rx = self.shutdown_rx.take().unwrap();
+
         // Wait zenoh scouting
         #[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
-        wait_zenoh_nodes_discovered(num_nodes, self.id, zsession.clone())
-            .await
-            .unwrap();
-
-        // Create TCPStreams between all node pairs.
-        #[cfg(feature = "tcp_transport")]
-        let control_streams = communication::create_tcp_streams(
-            self.config.control_addresses.clone(),
-            self.id,
-            &self.config.logger,
-        )
-        .await;
-
-        #[cfg(feature = "tcp_transport")]
-        let data_streams = communication::create_tcp_streams(
-            self.config.data_addresses.clone(),
-            self.id,
-            &self.config.logger,
-        )
-        .await;
+        {
+            let phase_start = Instant::now();
+            let discovery_fut = wait_zenoh_nodes_discovered(
+                num_nodes,
+                self.id,
+                zsession.clone(),
+                self.config.discovery_timeout,
+                logger.clone(),
+            );
+            tokio::select! {
+                result = discovery_fut => if let Err(e) = result {
+                    slog::error!(logger, "Node {}: {}", self.id, e);
+                    return;
+                },
+                _ = shutdown_rx.recv() => {
+                    slog::debug!(logger, "Node {}: shutting down during discovery", self.id);
+                    return;
+                }
+            }
+            self.startup.record("discovery", phase_start);
+        }
 
-        #[cfg(feature = "tcp_transport")]
-        let (control_senders, control_receivers) =
-            self.split_control_streams(control_streams).await;
+        // Only connect the data planes of nodes which share channels.
+        let phase_start = Instant::now();
+        self.data_peers = self.get_data_peers();
 
+        // The data socket paths replace the data addresses only on dedicated data connections.
+        #[cfg(feature = "uds_transport")]
+        assert!(
+            self.config.data_socket_paths.is_none()
+                || !(self.config.multiplex_connections || self.config.lazy_connections),
+            "Node {}: data socket paths do not support multiplexed or lazy connections",
+            self.id
+        );
+        // Create TCPStreams between all node pairs.
         #[cfg(feature = "tcp_transport")]
-        let (senders, receivers) = self.split_data_streams(data_streams).await;
+        let (control_senders, control_receivers, senders, receivers) =
+            if self.config.multiplex_connections {
+                // Both planes share the connections to the data addresses.
+                let streams = communication::create_tcp_streams(
+                    self.config.data_addresses.clone(),
+                    self.id,
+                    self.config.authenticator.as_deref(),
+                    &self.config.logger,
+                )
+                .await;
+                self.split_multiplexed_streams(streams).await
+            } else {
+                let control_streams_fut = communication::create_tcp_streams(
+                    self.config.control_addresses.clone(),
+                    self.id,
+                    self.config.authenticator.as_deref(),
+                    &self.config.logger,
+                );
+                let (control_streams, data_streams) = if self.config.lazy_connections {
+                    (control_streams_fut.await, None)
+                } else {
+                    // Connect the control and the data planes in parallel.
+                    let data_streams_fut = self.create_data_streams();
+                    let (control_streams, data_streams) =
+                        future::join(control_streams_fut, data_streams_fut).await;
+                    (control_streams, Some(data_streams))
+                };
+                let (control_senders, control_receivers) =
+                    self.split_control_streams(control_streams).await;
+                let (senders, receivers) = match data_streams {
+                    Some(data_streams) => self.split_data_streams(data_streams).await,
+                    None => self.split_lazy_data_streams().await,
+                };
+                (control_senders, control_receivers, senders, receivers)
+            };
 
         #[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
         let (control_senders, control_receivers) = self
@@ -522,20 +2067,52 @@ impl Node {
 
         #[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
         let (senders, receivers) = self
-            .get_data_streams(zsession.clone(), get_nodes_ids(num_nodes, self.id))
+            .get_data_streams(zsession.clone(), self.data_peers.clone())
             .await;
+        self.startup.record("connections", phase_start);
+
+        // Create the cores before running the receivers, which run on the cores with core
+        // affinity.
+        if self.config.executor == ExecutorModel::ThreadPerCore {
+            let core_pool = CorePool::new(
+                self.id,
+                self.config.worker_threads(),
+                self.config.core_affinity,
+            );
+            self.placement
+                .start(core_pool.num_cores(), core_pool.is_pinned());
+            self.core_pool = Some(core_pool);
+        }
 
-        // Listen for shutdown message.
-        let mut shutdown_rx = self.shutdown_rx.take().unwrap();
         let shutdown_fut = shutdown_rx.recv();
         // Execute threads that send data to other nodes.
-        let control_senders_fut = senders::run_control_senders(control_senders);
-        let senders_fut = senders::run_senders(senders);
+        let control_senders_fut = health.watch(
+            "Control senders",
+            senders::run_control_senders(control_senders),
+        );
+        let senders_fut = health.watch("Data senders", senders::run_senders(senders));
         // Execute threads that receive data from other nodes.
-        let control_recvs_fut = receivers::run_control_receivers(control_receivers);
-        let recvs_fut = receivers::run_receivers(receivers);
+        let control_recvs_fut = health.watch(
+            "Control receivers",
+            receivers::run_control_receivers(control_receivers),
+        );
+        let recvs_fut = health.watch("Data receivers", self.run_data_receivers(receivers));
+        // Detect failed peers, which are reported to the control handler like the nodes which
+        // stop sending heartbeats.
+        #[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
+        tokio::spawn(monitor_zenoh_peers(
+            get_nodes_ids(num_nodes, self.id),
+            zsession.clone(),
+            PeerChecks {
+                interval: self.config.peer_check_interval,
+                timeout: self.config.peer_check_timeout,
+                misses: self.config.peer_check_misses,
+            },
+            self.control_handler.get_channel_to_handler(),
+            logger.clone(),
+        ));
         // Execute operators.
-        let ops_fut = self.run_operators();
+        let ops_fut = health.watch("Dataflow", self.run_operators());
         // These threads only complete when a failure happens.
         if num_nodes <= 1 {
             // Senders and Receivers should return if there's only 1 node.
@@ -604,6 +2181,24 @@ impl Node {
                 _ = z_handler_fut => slog::debug!(logger, "Node {}: shutting down Zenoh Query Handler", self.id),
             }
         }
+        // Cancel the tasks which still run on the cores.
+        self.core_pool = None;
+        self.teardown_driver();
+    }
+
+    /// Runs the teardown hooks of the driver sections in the reverse order of their setup.
+    fn teardown_driver(&mut self) {
+        while let Some(section) = self.driver_sections.pop() {
+            slog::debug!(
+                self.config.logger,
+                "Node {}: tearing down driver section {}",
+                self.id,
+                section.name
+            );
+            for teardown_hook in section.teardown_hooks.iter() {
+                (teardown_hook)();
+            }
+        }
     }
 }
 
@@ -615,30 +2210,58 @@ async fn query_handler(zsession: Arc<zenoh::net::Session>, id: NodeId, mut tx: S
         .declare_queryable(&path.clone().into(), zenoh::net::queryable::EVAL)
         .await
         .unwrap();
+    // Replies with the bincode-serialized `NodeInfo` of the node.
+    let build_info_path = format!("/{}/build_info", id);
+    let build_info = bincode::serialize(&NodeInfo::current(id)).unwrap();
+    let mut build_info_queryable = zsession
+        .declare_queryable(&build_info_path.clone().into(), zenoh::net::queryable::EVAL)
+        .await
+        .unwrap();
     tx.send(()).await.unwrap();
 
-    while let Some(zquery) = queryable.stream().next().await {
+    loop {
+        let (zquery, res_name, payload) = tokio::select! {
+            Some(zquery) = queryable.stream().next() => (zquery, &path, value.as_bytes()),
+            Some(zquery) = build_info_queryable.stream().next() => {
+                (zquery, &build_info_path, build_info.as_slice())
+            }
+            else => break,
+        };
         zquery
             .reply(zenoh::net::Sample {
-                res_name: path.clone(),
-                payload: value.as_bytes().into(),
+                res_name: res_name.clone(),
+                payload: payload.into(),
                 data_info: None,
             })
             .await;
     }
 }
 
+/// Waits until the queryables of all other nodes are discovered, retrying with exponential
+/// backoff and logging the nodes which are still missing.
+///
+/// Returns an error if not all nodes are discovered within the timeout.
 #[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
 async fn wait_zenoh_nodes_discovered(
     total_nodes: usize,
     node_id: NodeId,
     zsession: Arc<zenoh::net::Session>,
-) -> Result<Vec<NodeId>, communication::CommunicationError> {
-    let mut nodes = vec![];
-    let mut n = 0;
-    while nodes.len() < (total_nodes - 1) {
-        if n != node_id {
-            let path = format!("/{}/info", n);
+    timeout: std::time::Duration,
+    logger: slog::Logger,
+) -> Result<Vec<NodeId>, String> {
+    const INITIAL_BACKOFF: std::time::Duration = std::time::Duration::from_millis(10);
+    const MAX_BACKOFF: std::time::Duration = std::time::Duration::from_secs(1);
+    const LOG_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+    let start = std::time::Instant::now();
+    let mut last_log = start;
+    let mut backoff = INITIAL_BACKOFF;
+    let mut missing_nodes: std::collections::BTreeSet<NodeId> =
+        get_nodes_ids(total_nodes, node_id).into_iter().collect();
+    let mut nodes = Vec::with_capacity(missing_nodes.len());
+    loop {
+        for &missing_node in missing_nodes.clone().iter() {
+            let path = format!("/{}/info", missing_node);
             let mut replies = zsession
                 .query(
                     &path.into(),
@@ -647,23 +2270,116 @@ async fn wait_zenoh_nodes_discovered(
                     zenoh::net::protocol::core::QueryConsolidation::default(),
                 )
                 .await
-                .map_err(communication::CommunicationError::from)?;
+                .map_err(|e| format!("Unable to query node {}: {}", missing_node, e))?;
             if let Some(reply) = replies.next().await {
                 let z_data = reply.data.payload.to_vec();
-                let s_id = String::from_utf8_lossy(&z_data);
-                let id = s_id
-                    .parse::<usize>()
-                    .map_err(|_| communication::CommunicationError::DeserializeNotImplemented)?;
-                nodes.push(id);
-                n += 1;
-            } else {
-                std::hint::spin_loop();
+                match String::from_utf8_lossy(&z_data).parse::<NodeId>() {
+                    Ok(id) if id == missing_node => {
+                        slog::debug!(logger, "Node {}: discovered node {}", node_id, id);
+                        missing_nodes.remove(&id);
+                        nodes.push(id);
+                    }
+                    _ => slog::warn!(
+                        logger,
+                        "Node {}: received an invalid reply from node {}",
+                        node_id,
+                        missing_node
+                    ),
+                }
+            }
+        }
+        if missing_nodes.is_empty() {
+            return Ok(nodes);
+        }
+        if start.elapsed() >= timeout {
+            return Err(format!(
+                "Nodes {:?} were not discovered within {:?}",
+                missing_nodes, timeout
+            ));
+        }
+        if last_log.elapsed() >= LOG_INTERVAL {
+            slog::info!(
+                logger,
+                "Node {}: still waiting for nodes {:?}",
+                node_id,
+                missing_nodes
+            );
+            last_log = std::time::Instant::now();
+        }
+        tokio::time::delay_for(backoff).await;
+        backoff = std::cmp::min(backoff * 2, MAX_BACKOFF);
+    }
+}
+
+/// Configures the liveliness checks of the other nodes with the Zenoh transports.
+#[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
+struct PeerChecks {
+    interval: std::time::Duration,
+    timeout: std::time::Duration,
+    misses: usize,
+}
+
+/// Checks periodically that the other nodes are alive, and reports a node which misses
+/// consecutive checks to the control handler as
+/// [`PeerUnreachable`](ControlMessage::PeerUnreachable). Returns once the control handler is
+/// dropped.
+///
+/// Zenoh removes the queryables of a session as soon as the session closes, so queries for the
+/// `/{id}/info` queryable of a failed node complete without replies.
+#[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
+async fn monitor_zenoh_peers(
+    node_ids: Vec<NodeId>,
+    zsession: Arc<zenoh::net::Session>,
+    checks: PeerChecks,
+    control_tx: UnboundedSender<ControlMessage>,
+    logger: slog::Logger,
+) {
+    let mut misses: HashMap<NodeId, usize> = HashMap::new();
+    loop {
+        tokio::time::delay_for(checks.interval).await;
+        for &node_id in node_ids.iter() {
+            let path = format!("/{}/info", node_id);
+            let replied = match zsession
+                .query(
+                    &path.into(),
+                    "",
+                    zenoh::net::protocol::core::QueryTarget::default(),
+                    zenoh::net::protocol::core::QueryConsolidation::default(),
+                )
+                .await
+            {
+                Ok(mut replies) => matches!(
+                    tokio::time::timeout(checks.timeout, replies.next()).await,
+                    Ok(Some(_))
+                ),
+                Err(e) => {
+                    slog::warn!(logger, "Unable to query node {}: {:?}", node_id, e);
+                    false
+                }
+            };
+            let node_misses = misses.entry(node_id).or_default();
+            if replied {
+                *node_misses = 0;
+                continue;
+            }
+            *node_misses += 1;
+            slog::warn!(
+                logger,
+                "Node {} did not reply within {:?} ({} of {} checks)",
+                node_id,
+                checks.timeout,
+                node_misses,
+                checks.misses
+            );
+            if *node_misses >= checks.misses
+                && control_tx
+                    .send(ControlMessage::PeerUnreachable(node_id))
+                    .is_err()
+            {
+                return;
             }
-        } else {
-            n += 1;
         }
     }
-    Ok(nodes)
 }
 
 #[cfg(any(feature = "zenoh_transport", feature = "zenoh_zerocopy_transport"))]
@@ -677,10 +2393,38 @@ fn get_nodes_ids(total_nodes: usize, node_id: NodeId) -> Vec<NodeId> {
     nodes
 }
 
+/// Blocks until the node finished setting up, and panics if the thread running the node, which
+/// holds the sender of `running_rx`, stops before.
+pub(crate) fn wait_until_initialized(
+    id: NodeId,
+    initialized: &(std::sync::Mutex<bool>, std::sync::Condvar),
+    running_rx: &std::sync::mpsc::Receiver<()>,
+) {
+    let (lock, cvar) = initialized;
+    let mut started = lock.lock().unwrap();
+    while !*started {
+        if let Err(std::sync::mpsc::TryRecvError::Disconnected) = running_rx.try_recv() {
+            panic!("Node {} stopped before it finished setting up", id);
+        }
+        started = cvar
+            .wait_timeout(started, std::time::Duration::from_millis(100))
+            .unwrap()
+            .0;
+    }
+}
+
 /// Handle to a [`Node`] running asynchronously.
 pub struct NodeHandle {
     thread_handle: thread::JoinHandle<()>,
     shutdown_tx: Sender<()>,
+    handle_tx: UnboundedSender<HandleRequest>,
+    knobs: RuntimeKnobs,
+    startup: StartupProfiler,
+    startup_state: Arc<std::sync::Mutex<NodeStartupStateMachine>>,
+    placement: Placement,
+    run_id: RunId,
+    context: Arc<NodeContext>,
+    id: NodeId,
 }
 
 // TODO: distinguish between shutting down the dataflow and shutting down the node.
@@ -689,6 +2433,134 @@ impl NodeHandle {
     pub fn join(self) -> Result<(), String> {
         self.thread_handle.join().map_err(|e| format!("{:?}", e))
     }
+    /// Returns the build and platform information of all nodes, sorted by node id.
+    ///
+    /// Blocks until every node replied.
+    pub fn cluster_info(&self) -> Result<Vec<NodeInfo>, String> {
+        let (reply_tx, reply_rx) = std::sync::mpsc::channel();
+        self.handle_tx
+            .send(HandleRequest::ClusterInfo(reply_tx))
+            .map_err(|_| "The node is not running".to_string())?;
+        reply_rx
+            .recv()
+            .map_err(|_| "The node shut down before all nodes replied".to_string())
+    }
+
+    /// Runs the operators which the driver connected since the node started running, and
+    /// connects them to the existing streams. Blocks until the operators finished setting up on
+    /// all nodes, and returns their IDs.
+    ///
+    /// The drivers of all nodes must connect the same operators and call the method, as the
+    /// nodes set up the operators placed on them. The dataflow must only add operators and
+    /// streams, i.e. its diff must be [additive](GraphDiff::is_additive), and the added operators
+    /// must only exchange messages with nodes to which the data planes are already connected. The
+    /// dependencies of added operators are ignored, and the nodes do not wait for the added
+    /// operators to finish running when they shut down.
+    pub fn add_operators(&self) -> Result<Vec<OperatorId>, String> {
+        let (reply_tx, reply_rx) = std::sync::mpsc::channel();
+        self.handle_tx
+            .send(HandleRequest::AddOperators(
+                default_graph::clone(),
+                reply_tx,
+            ))
+            .map_err(|_| "The node is not running".to_string())?;
+        reply_rx
+            .recv()
+            .map_err(|_| "The node shut down before the operators were added".to_string())?
+    }
+
+    /// Blocks until the dataflow is quiescent, i.e. until no messages are in flight and all
+    /// operators on all nodes finished processing the messages they received.
+    ///
+    /// Messages which are never read, e.g. on an
+    /// [`ExtractStream`](crate::dataflow::stream::ExtractStream) which the driver does not read,
+    /// count as in flight. Returns an error if the dataflow is not quiescent within the timeout.
+    pub fn await_quiescent(&self, timeout: Duration) -> Result<(), String> {
+        let (reply_tx, reply_rx) = std::sync::mpsc::channel();
+        self.handle_tx
+            .send(HandleRequest::AwaitQuiescent(
+                Instant::now() + timeout,
+                reply_tx,
+            ))
+            .map_err(|_| "The node is not running".to_string())?;
+        reply_rx
+            .recv_timeout(timeout)
+            .map_err(|_| format!("The dataflow did not become quiescent within {:?}", timeout))
+    }
+
+    /// Updates a [runtime knob](RuntimeKnobs) on all nodes.
+    ///
+    /// Returns an error if the knob is unknown or the value is invalid on the current node. Other
+    /// nodes validate the value again, and log if they reject it.
+    pub fn set_knob(&self, name: &str, value: &str) -> Result<(), String> {
+        self.knobs.set(name, value, self.id)?;
+        self.handle_tx
+            .send(HandleRequest::SetKnob(name.to_string(), value.to_string()))
+            .map_err(|_| "The node is not running".to_string())
+    }
+
+    /// Returns the ID of the run of the dataflow, which is the same on all nodes and is included
+    /// in the messages sent between nodes and in the logs of the node.
+    pub fn run_id(&self) -> Uuid {
+        self.run_id.get()
+    }
+
+    /// Captures the messages sent around the current time on the streams captured with
+    /// [`CapturePolicy::AroundTrigger`](crate::node::CapturePolicy::AroundTrigger) on the
+    /// current node, e.g. when the driver detects an incident. Slow callbacks trigger the
+    /// capture as well.
+    pub fn trigger_capture(&self) {
+        self.context.captures.trigger();
+    }
+
+    /// Returns the average latency of the messages of a stream tracked with
+    /// [`Node::track_latency`], split into the time spent in each hop to the operators on the
+    /// current node. Returns `None` if no message of the stream was received yet.
+    pub fn latency_breakdown(&self, stream_id: StreamId) -> Option<LatencyBreakdown> {
+        latency::breakdown(stream_id)
+    }
+
+    /// Returns the messages from which the messages of the stream with the timestamp were
+    /// computed if provenance is tracked with [`Node::track_provenance`], closest first, e.g. to
+    /// find which sensor readings led to a decision of the planner.
+    ///
+    /// Watermark callbacks are attributed the timestamp of the watermark on the stream which
+    /// invoked them. Returns `None` if the messages were not sent by an operator, or were sent
+    /// or received on another node, or if they are older than the last 10000 messages whose
+    /// provenance was recorded.
+    pub fn provenance(&self, stream_id: StreamId, timestamp: &Timestamp) -> Option<Vec<Origin>> {
+        self.context.provenance.origins(stream_id, timestamp)
+    }
+
+    /// Reconstructs the messages and the operators which contributed to the messages of the
+    /// stream with the timestamp from the provenance recorded with [`Node::track_provenance`],
+    /// e.g. to export it with [`Lineage::to_dot`] when analyzing an incident.
+    ///
+    /// The lineage follows the inputs of the callbacks which sent the messages on the current
+    /// node. Returns `None` under the same conditions as [`NodeHandle::provenance`].
+    pub fn lineage(&self, stream_id: StreamId, timestamp: &Timestamp) -> Option<Lineage> {
+        Lineage::new(&self.context.provenance, stream_id, timestamp)
+    }
+
+    /// Returns how long each phase of the startup of the [`Node`] took, e.g. connecting to the
+    /// other nodes or waiting for them to set up their operators.
+    pub fn startup_report(&self) -> StartupReport {
+        self.startup.report()
+    }
+
+    /// Returns the phase of the startup of each other node, as seen by the [`Node`] from the
+    /// control messages it received, e.g. to find out which node a stuck startup waits for.
+    pub fn peer_phases(&self) -> BTreeMap<NodeId, PeerPhase> {
+        self.startup_state.lock().unwrap().peer_phases()
+    }
+
+    /// Returns the cores on which the [`Node`] runs its operators and data receivers, or `None`
+    /// if the node does not use the [`ThreadPerCore`](crate::ExecutorModel::ThreadPerCore)
+    /// executor or is still setting up.
+    pub fn placement_report(&self) -> Option<PlacementReport> {
+        self.placement.report()
+    }
+
     /// Blocks until the [`Node`] shuts down.
     pub fn shutdown(mut self) -> Result<(), String> {
         // Error indicates node is already shutting down.
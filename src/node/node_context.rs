@@ -0,0 +1,30 @@
+use crate::{
+    communication::bounded_channel::CreditCounters,
+    node::{
+        capture::Captures, provenance::ProvenanceRecords, quiescence::MessageCounters,
+        triggers::Triggers, watermark_progress::WatermarkProgress,
+    },
+};
+
+/// The state which a [`Node`](crate::node::Node) shares with its streams, operators, and data
+/// receivers.
+///
+/// Kept by each node, so that nodes which run in the same process, e.g. with the
+/// [`MultiNodeRunner`](crate::node::MultiNodeRunner), do not count, capture, or report each
+/// other's messages.
+#[derive(Default)]
+pub(crate) struct NodeContext {
+    /// Counts the messages sent and processed on the node for the quiescence detection.
+    pub counters: MessageCounters,
+    /// Counts the messages of the blocking streams received from other nodes which the
+    /// operators of the node read.
+    pub credits: CreditCounters,
+    /// The streams whose messages the node captures to disk.
+    pub captures: Captures,
+    /// The triggers registered on the node, and the messages they observed.
+    pub triggers: Triggers,
+    /// The origins of the messages sent and received by the node.
+    pub provenance: ProvenanceRecords,
+    /// The watermarks of the watched streams, sent on the node or reported by other nodes.
+    pub watermark_progress: WatermarkProgress,
+}
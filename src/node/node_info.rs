@@ -0,0 +1,161 @@
+use std::{fmt, sync::mpsc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::node::{NodeId, ResourceLimits};
+
+/// Build and platform information of a [`Node`](crate::node::Node).
+///
+/// Nodes report their information to each other via the control plane, which helps diagnose
+/// deployments where nodes run different builds of ERDOS.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeInfo {
+    pub node_id: NodeId,
+    /// Version of the erdos crate.
+    pub crate_version: String,
+    /// Commit from which erdos was built, if it was built from a git checkout.
+    pub git_hash: Option<String>,
+    /// Cargo features with which erdos was built.
+    pub features: Vec<String>,
+    /// Transport used to communicate with other nodes.
+    pub transport: String,
+    pub os: String,
+    pub arch: String,
+    /// CPUs and memory available to the node, e.g. the limits of its container.
+    pub resources: ResourceLimits,
+}
+
+impl NodeInfo {
+    /// Returns the information of the current build for the node.
+    pub fn current(node_id: NodeId) -> Self {
+        let mut features = Vec::new();
+        if cfg!(feature = "python") {
+            features.push("python".to_string());
+        }
+        if cfg!(feature = "tcp_transport") {
+            features.push("tcp_transport".to_string());
+        }
+        if cfg!(feature = "uds_transport") {
+            features.push("uds_transport".to_string());
+        }
+        if cfg!(feature = "zenoh_transport") {
+            features.push("zenoh_transport".to_string());
+        }
+        if cfg!(feature = "zenoh_zerocopy_transport") {
+            features.push("zenoh_zerocopy_transport".to_string());
+        }
+
+        let transport = if cfg!(feature = "tcp_transport") {
+            "tcp"
+        } else if cfg!(feature = "zenoh_zerocopy_transport") {
+            "zenoh_zerocopy"
+        } else {
+            "zenoh"
+        };
+
+        Self {
+            node_id,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            git_hash: option_env!("ERDOS_GIT_HASH").map(String::from),
+            features,
+            transport: transport.to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            resources: ResourceLimits::detect(),
+        }
+    }
+}
+
+impl fmt::Display for NodeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "node {}: erdos {} ({}), transport {}, features [{}], {}/{}, {}",
+            self.node_id,
+            self.crate_version,
+            self.git_hash.as_deref().unwrap_or("unknown commit"),
+            self.transport,
+            self.features.join(", "),
+            self.os,
+            self.arch,
+            self.resources
+        )
+    }
+}
+
+/// Aggregates the [`NodeInfo`] replies of all nodes for the pending
+/// [`NodeHandle::cluster_info`](crate::node::NodeHandle::cluster_info) requests.
+pub(crate) struct ClusterInfoRequests {
+    num_nodes: usize,
+    replies: Vec<NodeInfo>,
+    waiting: Vec<mpsc::Sender<Vec<NodeInfo>>>,
+}
+
+impl ClusterInfoRequests {
+    pub fn new(num_nodes: usize) -> Self {
+        Self {
+            num_nodes,
+            replies: Vec::new(),
+            waiting: Vec::new(),
+        }
+    }
+
+    /// Adds a request for the information of all nodes.
+    ///
+    /// Returns true if the other nodes must be asked for their information, i.e. if no request
+    /// is already waiting for their replies.
+    pub fn add_request(&mut self, node_id: NodeId, reply_tx: mpsc::Sender<Vec<NodeInfo>>) -> bool {
+        self.waiting.push(reply_tx);
+        if self.waiting.len() > 1 {
+            return false;
+        }
+        self.replies = vec![NodeInfo::current(node_id)];
+        self.try_complete();
+        !self.waiting.is_empty()
+    }
+
+    /// Adds the information received from another node.
+    pub fn add_reply(&mut self, info: NodeInfo) {
+        if self.waiting.is_empty() || self.replies.iter().any(|i| i.node_id == info.node_id) {
+            return;
+        }
+        self.replies.push(info);
+        self.try_complete();
+    }
+
+    fn try_complete(&mut self) {
+        if self.replies.len() < self.num_nodes {
+            return;
+        }
+        self.replies.sort_by_key(|info| info.node_id);
+        for tx in self.waiting.drain(..) {
+            // The handle may have been dropped while waiting.
+            let _ = tx.send(self.replies.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that requests are answered once all nodes replied, in the order of the node ids.
+    #[test]
+    fn test_cluster_info_requests() {
+        let mut requests = ClusterInfoRequests::new(3);
+        let (tx, rx) = mpsc::channel();
+        assert!(requests.add_request(1, tx.clone()));
+        assert!(!requests.add_request(1, tx));
+
+        requests.add_reply(NodeInfo::current(2));
+        requests.add_reply(NodeInfo::current(2));
+        assert!(rx.try_recv().is_err());
+        requests.add_reply(NodeInfo::current(0));
+
+        for _ in 0..2 {
+            let infos = rx.try_recv().unwrap();
+            let node_ids: Vec<_> = infos.iter().map(|info| info.node_id).collect();
+            assert_eq!(node_ids, vec![0, 1, 2]);
+        }
+    }
+}
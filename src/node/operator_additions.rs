@@ -0,0 +1,133 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::mpsc,
+};
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{communication::ControlMessage, node::NodeId, OperatorId};
+
+/// Operators added to the running dataflow by the same call to
+/// [`NodeHandle::add_operators`](crate::node::NodeHandle::add_operators) on each node.
+#[derive(Default)]
+struct Addition {
+    /// The local operators which have not finished setting up.
+    pending_operators: HashSet<OperatorId>,
+    /// Channels to the local operators, which are told to run once the operators finished
+    /// setting up on all nodes.
+    channels_to_operators: HashMap<OperatorId, UnboundedSender<ControlMessage>>,
+    /// The nodes on which the operators finished setting up.
+    ready_nodes: HashSet<NodeId>,
+    /// Set once the operators are added on the current node. Other nodes may finish setting up
+    /// the operators before.
+    reply_tx: Option<mpsc::Sender<Result<Vec<OperatorId>, String>>>,
+}
+
+/// Operators added to the running dataflow which finished setting up on all nodes.
+pub(crate) struct ReadyOperators {
+    pub operator_ids: Vec<OperatorId>,
+    pub channels_to_operators: HashMap<OperatorId, UnboundedSender<ControlMessage>>,
+    pub reply_tx: mpsc::Sender<Result<Vec<OperatorId>, String>>,
+}
+
+/// Tracks the operators added to the running dataflow, which run once they finished setting up
+/// on all nodes, so that no operator sends messages to an operator which is not set up yet.
+pub(crate) struct OperatorAdditions {
+    num_nodes: usize,
+    /// Keyed by the sorted IDs of the added operators, on which the nodes agree because the IDs
+    /// are generated deterministically.
+    additions: HashMap<Vec<OperatorId>, Addition>,
+}
+
+impl OperatorAdditions {
+    pub fn new(num_nodes: usize) -> Self {
+        Self {
+            num_nodes,
+            additions: HashMap::new(),
+        }
+    }
+
+    /// Adds operators to the running dataflow, of which the local operators are set up and
+    /// told to run with `channels_to_operators`.
+    ///
+    /// Returns true if the local operators are already set up, i.e. if none run on the current
+    /// node.
+    pub fn add(
+        &mut self,
+        operator_ids: Vec<OperatorId>,
+        channels_to_operators: HashMap<OperatorId, UnboundedSender<ControlMessage>>,
+        reply_tx: mpsc::Sender<Result<Vec<OperatorId>, String>>,
+    ) -> bool {
+        let addition = self.additions.entry(operator_ids).or_default();
+        addition.pending_operators = channels_to_operators.keys().cloned().collect();
+        addition.channels_to_operators = channels_to_operators;
+        addition.reply_tx = Some(reply_tx);
+        addition.pending_operators.is_empty()
+    }
+
+    /// Records that a local operator finished setting up.
+    ///
+    /// Returns the IDs of the operators added with it once all of them are set up on the
+    /// current node.
+    pub fn operator_initialized(&mut self, operator_id: OperatorId) -> Option<Vec<OperatorId>> {
+        self.additions
+            .iter_mut()
+            .find(|(_, addition)| addition.pending_operators.remove(&operator_id))
+            .filter(|(_, addition)| addition.pending_operators.is_empty())
+            .map(|(operator_ids, _)| operator_ids.clone())
+    }
+
+    /// Records that the operators finished setting up on the node.
+    ///
+    /// Returns the operators once they finished setting up on all nodes.
+    pub fn node_ready(
+        &mut self,
+        node_id: NodeId,
+        operator_ids: Vec<OperatorId>,
+    ) -> Option<ReadyOperators> {
+        let addition = self.additions.entry(operator_ids.clone()).or_default();
+        addition.ready_nodes.insert(node_id);
+        if addition.ready_nodes.len() < self.num_nodes || addition.reply_tx.is_none() {
+            return None;
+        }
+        let addition = self.additions.remove(&operator_ids)?;
+        Some(ReadyOperators {
+            operator_ids,
+            channels_to_operators: addition.channels_to_operators,
+            reply_tx: addition.reply_tx?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    /// Test that added operators are ready once they finished setting up on all nodes, including
+    /// nodes which finished before the operators were added on the current node.
+    #[test]
+    fn test_operators_ready_on_all_nodes() {
+        let (local, remote) = (OperatorId::new_v4(), OperatorId::new_v4());
+        let mut operator_ids = vec![local, remote];
+        operator_ids.sort();
+        let mut additions = OperatorAdditions::new(2);
+
+        assert!(additions.node_ready(1, operator_ids.clone()).is_none());
+        let (tx, _rx) = unbounded_channel();
+        let mut channels_to_operators = HashMap::new();
+        channels_to_operators.insert(local, tx);
+        let (reply_tx, _reply_rx) = mpsc::channel();
+        assert!(!additions.add(operator_ids.clone(), channels_to_operators, reply_tx));
+        assert_eq!(additions.operator_initialized(remote), None);
+        assert_eq!(
+            additions.operator_initialized(local),
+            Some(operator_ids.clone())
+        );
+
+        let ready = additions.node_ready(0, operator_ids.clone()).unwrap();
+        assert_eq!(ready.operator_ids, operator_ids);
+        assert!(ready.channels_to_operators.contains_key(&local));
+        assert!(additions.additions.is_empty());
+    }
+}
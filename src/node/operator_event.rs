@@ -1,6 +1,10 @@
 use std::{cmp::Ordering, collections::HashSet, fmt};
 
-use crate::{dataflow::Timestamp, Uuid};
+use crate::{
+    dataflow::{CallbackResult, OperatorError, Timestamp},
+    node::{latency::LatencyProbe, provenance::Origin},
+    Uuid,
+};
 
 /// `OperatorEvent` is a structure that encapsulates a particular invocation of the
 /// callback in response to a message or watermark. These events are processed according to the
@@ -26,21 +30,29 @@ pub struct OperatorEvent {
     /// the same priority can run concurrently.
     pub priority: i8,
     /// The callback invoked when the event is processed.
-    pub callback: Box<dyn FnOnce()>,
+    pub callback: Box<dyn FnOnce() -> Result<(), OperatorError>>,
     /// IDs of items the event requires read access to.
     pub read_ids: HashSet<Uuid>,
     /// IDs of items the event requires write access to.
     pub write_ids: HashSet<Uuid>,
+    /// Set if the event processes a message of a stream whose latency is tracked.
+    pub latency_probe: Option<LatencyProbe>,
+    /// Set if provenance is tracked: the message, or the timestamp of the watermark, of the
+    /// stream to which the messages sent by the callback are attributed.
+    pub provenance: Option<Origin>,
+    /// Size of the message counted against the memory limit of the operator until the event is
+    /// processed, if the memory of the operator is limited.
+    pub buffered_bytes: u64,
 }
 
 impl OperatorEvent {
-    pub fn new(
+    pub fn new<R: CallbackResult + 'static>(
         t: Timestamp,
         is_watermark_callback: bool,
         priority: i8,
         read_ids: HashSet<Uuid>,
         write_ids: HashSet<Uuid>,
-        callback: impl FnOnce() + 'static,
+        callback: impl FnOnce() -> R + 'static,
     ) -> Self {
         Self {
             priority,
@@ -48,7 +60,10 @@ impl OperatorEvent {
             is_watermark_callback,
             read_ids,
             write_ids,
-            callback: Box::new(callback),
+            callback: Box::new(move || callback().into_result()),
+            latency_probe: None,
+            provenance: None,
+            buffered_bytes: 0,
         }
     }
 }
@@ -1,16 +1,20 @@
 use std::{
     cell::RefCell,
     collections::HashMap,
+    future::Future,
+    mem,
     pin::Pin,
     rc::Rc,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
     task::{Context, Poll},
+    time::{Duration, Instant},
 };
 
 use futures::future;
+use serde::Deserialize;
 use tokio::{
     self,
     stream::{Stream, StreamExt},
@@ -18,16 +22,33 @@ use tokio::{
 };
 
 use crate::{
-    communication::{ControlMessage, RecvEndpoint},
+    communication::{bounded_channel, ControlMessage, RecvEndpoint},
     dataflow::{
-        operator::{Operator, OperatorConfig},
-        stream::{InternalReadStream, StreamId},
-        Data, EventMakerT, Message, ReadStream,
+        operator::{
+            ErrorPolicy, MemoryLimitPolicy, Operator, OperatorConfig, OperatorError,
+            OperatorErrorReport, OperatorT, RunContext,
+        },
+        stream::{InternalReadStream, StreamId, WriteStreamT},
+        Data, EventMakerT, Message, ReadStream, Timestamp, WriteStream,
     },
+    node::checkpoint,
+    node::health::OperatorActivity,
+    node::latency,
     node::lattice::ExecutionLattice,
+    node::memory_limit::{self, MemoryTracker},
+    node::metrics::{Counter, Gauge, Histogram, OperatorMetrics},
     node::operator_event::OperatorEvent,
+    node::thread_per_core,
+    node::{NodeContext, NodeId},
+    OperatorId,
 };
 
+/// Duration after which a callback is slow if neither the operator nor the node set a threshold.
+const DEFAULT_SLOW_CALLBACK_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// Gauge of the events of an operator which wait for their callbacks to run.
+const QUEUED_EVENTS_METRIC: &str = "erdos_operator_queued_events";
+
 #[derive(Clone, Debug, PartialEq)]
 enum EventRunnerMessage {
     AddedEvents,
@@ -37,13 +58,41 @@ enum EventRunnerMessage {
 pub trait OperatorExecutorStreamT: Send + Stream<Item = Vec<OperatorEvent>> {
     fn get_id(&self) -> StreamId;
     fn get_closed_ref(&self) -> Arc<AtomicBool>;
+    /// Sends the callbacks of the messages the operator receives in its pull loop to `tx`.
+    fn set_pulled_events_tx(&self, tx: mpsc::UnboundedSender<Vec<OperatorEvent>>);
+    /// Counts the messages of the stream against the memory limit of the operator.
+    fn set_memory_tracker(&mut self, tracker: Arc<MemoryTracker>);
     fn to_pinned_stream(self: Box<Self>) -> Pin<Box<dyn Send + Stream<Item = Vec<OperatorEvent>>>>;
 }
 
+/// A write stream of an operator, which the executor closes if the operator fails.
+pub trait OperatorExecutorWriteStreamT {
+    /// Sends the top watermark on the stream, so that the downstream operators complete.
+    fn close_on_failure(&mut self);
+}
+
+impl<D> OperatorExecutorWriteStreamT for WriteStream<D>
+where
+    for<'a> D: Data + Deserialize<'a>,
+{
+    fn close_on_failure(&mut self) {
+        if let Err(e) = self.release_holds_and_close() {
+            slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "Unable to close the write stream {} (ID: {}) of a failed operator: {:?}",
+                self.get_name(),
+                self.get_id(),
+                e
+            );
+        }
+    }
+}
+
 pub struct OperatorExecutorStream<D: Data> {
     stream: Rc<RefCell<InternalReadStream<D>>>,
     recv_endpoint: Option<RecvEndpoint<Arc<Message<D>>>>,
     closed: Arc<AtomicBool>,
+    memory: Option<Arc<MemoryTracker>>,
 }
 
 impl<D: Data> OperatorExecutorStreamT for OperatorExecutorStream<D> {
@@ -55,6 +104,14 @@ impl<D: Data> OperatorExecutorStreamT for OperatorExecutorStream<D> {
         self.closed.clone()
     }
 
+    fn set_pulled_events_tx(&self, tx: mpsc::UnboundedSender<Vec<OperatorEvent>>) {
+        self.stream.borrow_mut().set_pulled_events_tx(tx);
+    }
+
+    fn set_memory_tracker(&mut self, tracker: Arc<MemoryTracker>) {
+        self.memory = Some(tracker);
+    }
+
     fn to_pinned_stream(self: Box<Self>) -> Pin<Box<dyn Send + Stream<Item = Vec<OperatorEvent>>>> {
         Box::into_pin(self as Box<dyn Send + Stream<Item = Vec<OperatorEvent>>>)
     }
@@ -77,24 +134,95 @@ impl<D: Data> Stream for OperatorExecutorStream<D> {
             mut_self.recv_endpoint = endpoint;
         }
         match mut_self.recv_endpoint.as_mut() {
-            Some(RecvEndpoint::InterThread(rx)) => match rx.poll_recv(cx) {
+            Some(endpoint) => match endpoint.poll_recv(cx) {
                 Poll::Ready(Some(msg)) => {
                     if msg.is_top_watermark() {
                         self.closed.store(true, Ordering::SeqCst);
                         self.recv_endpoint = None;
                     }
-                    Poll::Ready(Some(self.stream.borrow().make_events(msg)))
+                    let buffered_bytes = match &self.memory {
+                        Some(memory) if msg.data().is_some() => {
+                            if memory.policy() == MemoryLimitPolicy::DropMessages
+                                && memory.is_exceeded()
+                            {
+                                memory.message_dropped();
+                                return Poll::Ready(Some(Vec::new()));
+                            }
+                            // Messages sent within the node are not serialized, and count
+                            // with the size of their value.
+                            let frame_size = match &*msg {
+                                Message::TimestampedData(msg) => msg.frame_size,
+                                Message::Watermark(_) => None,
+                            };
+                            frame_size.unwrap_or_else(|| mem::size_of_val(&*msg)) as u64
+                        }
+                        _ => 0,
+                    };
+                    let mut events = self.stream.borrow().make_events(msg);
+                    if let (Some(memory), Some(event)) = (&self.memory, events.first_mut()) {
+                        event.buffered_bytes = buffered_bytes;
+                        memory.buffer(buffered_bytes);
+                    }
+                    Poll::Ready(Some(events))
                 }
                 Poll::Ready(None) => Poll::Ready(None),
                 Poll::Pending => Poll::Pending,
             },
-            None => Poll::Ready(None),
+            None => {
+                // The operator may have received the top watermark in its pull loop.
+                if mut_self.stream.borrow().is_closed() {
+                    mut_self.closed.store(true, Ordering::SeqCst);
+                }
+                Poll::Ready(None)
+            }
         }
     }
 }
 
 unsafe impl<D: Data> Send for OperatorExecutorStream<D> {}
 
+/// The pull loop of an operator, [`Operator::run_async`].
+///
+/// Like the operator's streams, the loop is only polled by the operator's executor.
+struct PullLoop<'a> {
+    future: Pin<Box<dyn Future<Output = ()> + 'a>>,
+    /// Waits for the full channels on which the loop sent messages before polling it again.
+    blocked: Option<Pin<Box<dyn Future<Output = ()>>>>,
+}
+
+unsafe impl Send for PullLoop<'_> {}
+
+impl<'a> PullLoop<'a> {
+    fn new(future: Pin<Box<dyn Future<Output = ()> + 'a>>) -> Self {
+        Self {
+            future,
+            blocked: None,
+        }
+    }
+}
+
+impl Future for PullLoop<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(blocked) = self.blocked.as_mut() {
+            if blocked.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            self.blocked = None;
+        }
+        let future = &mut self.future;
+        let (poll, blocked) = bounded_channel::defer_blocking(|| future.as_mut().poll(cx));
+        if poll.is_pending() && !blocked.is_empty() {
+            let mut blocked: Pin<Box<dyn Future<Output = ()>>> = Box::pin(blocked.wait());
+            if blocked.as_mut().poll(cx).is_pending() {
+                self.blocked = Some(blocked);
+            }
+        }
+        poll
+    }
+}
+
 impl<D: Data> From<Rc<RefCell<InternalReadStream<D>>>> for OperatorExecutorStream<D> {
     fn from(stream: Rc<RefCell<InternalReadStream<D>>>) -> Self {
         Self::new(stream)
@@ -115,6 +243,230 @@ impl<D: Data> OperatorExecutorStream<D> {
             stream,
             recv_endpoint: None,
             closed,
+            memory: None,
+        }
+    }
+}
+
+/// Handles the errors returned by an operator's callbacks according to its [`ErrorPolicy`].
+struct CallbackErrorHandler {
+    operator_id: OperatorId,
+    operator_name: Option<String>,
+    node_id: NodeId,
+    policy: ErrorPolicy,
+    /// Stream on which error reports are sent if the policy is
+    /// [`ErrorPolicy::SendToErrorStream`].
+    error_stream: Option<Mutex<WriteStream<OperatorErrorReport>>>,
+    /// Used to notify the node that the dataflow must be torn down.
+    control_tx: mpsc::UnboundedSender<ControlMessage>,
+    /// Used to notify the executor that the operator failed.
+    failure_tx: mpsc::UnboundedSender<()>,
+    failed: AtomicBool,
+    /// Tracks the memory used by the operator if its memory is limited.
+    memory: Option<Arc<MemoryTracker>>,
+}
+
+impl CallbackErrorHandler {
+    fn handle(&self, timestamp: &Timestamp, error: OperatorError) {
+        self.handle_with_policy(self.policy, timestamp, error);
+    }
+
+    fn handle_with_policy(&self, policy: ErrorPolicy, timestamp: &Timestamp, error: OperatorError) {
+        let name = self
+            .operator_name
+            .clone()
+            .unwrap_or_else(|| format!("{}", self.operator_id));
+        match policy {
+            ErrorPolicy::SkipMessage => slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "Node {}: operator {} skipped message with timestamp {:?}: {}",
+                self.node_id,
+                name,
+                timestamp,
+                error
+            ),
+            ErrorPolicy::FailOperator => {
+                slog::error!(
+                    crate::TERMINAL_LOGGER,
+                    "Node {}: operator {} failed at timestamp {:?}: {}",
+                    self.node_id,
+                    name,
+                    timestamp,
+                    error
+                );
+                self.fail();
+            }
+            ErrorPolicy::FailDataflow => {
+                slog::error!(
+                    crate::TERMINAL_LOGGER,
+                    "Node {}: operator {} failed at timestamp {:?}, shutting down the dataflow: {}",
+                    self.node_id,
+                    name,
+                    timestamp,
+                    error
+                );
+                if let Err(e) = self.control_tx.send(ControlMessage::OperatorFailed(
+                    self.operator_id,
+                    error.to_string(),
+                )) {
+                    slog::error!(
+                        crate::TERMINAL_LOGGER,
+                        "Node {}: error sending OperatorFailed message for operator {}: {:?}",
+                        self.node_id,
+                        name,
+                        e
+                    );
+                }
+                self.fail();
+            }
+            ErrorPolicy::SendToErrorStream => {
+                let report = OperatorErrorReport {
+                    operator_id: self.operator_id,
+                    operator_name: self.operator_name.clone(),
+                    node_id: self.node_id,
+                    timestamp: timestamp.clone(),
+                    error,
+                };
+                match &self.error_stream {
+                    Some(error_stream) => {
+                        let msg = Message::new_message(timestamp.clone(), report);
+                        if let Err(e) = error_stream.lock().unwrap().send(msg) {
+                            slog::error!(
+                                crate::TERMINAL_LOGGER,
+                                "Node {}: operator {} failed to send on its error stream: {:?}",
+                                self.node_id,
+                                name,
+                                e
+                            );
+                        }
+                    }
+                    None => slog::error!(
+                        crate::TERMINAL_LOGGER,
+                        "Node {}: operator {} has no error stream, dropping error report {:?}",
+                        self.node_id,
+                        name,
+                        report
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Applies the memory limit policy of the operator if its memory usage went over its limit.
+    fn check_memory_limit(&self, timestamp: &Timestamp) {
+        let memory = match &self.memory {
+            Some(memory) => memory,
+            None => return,
+        };
+        let usage = match memory.newly_exceeded() {
+            Some(usage) => usage,
+            None => return,
+        };
+        let error = OperatorError::new(format!(
+            "uses {} bytes of memory, over its limit of {} bytes",
+            usage,
+            memory.limit()
+        ));
+        match memory.policy() {
+            MemoryLimitPolicy::FailOperator => {
+                self.handle_with_policy(ErrorPolicy::FailOperator, timestamp, error)
+            }
+            MemoryLimitPolicy::FailDataflow => {
+                self.handle_with_policy(ErrorPolicy::FailDataflow, timestamp, error)
+            }
+            MemoryLimitPolicy::Backpressure | MemoryLimitPolicy::DropMessages => slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "Node {}: operator {} {}",
+                self.node_id,
+                self.operator_name
+                    .clone()
+                    .unwrap_or_else(|| format!("{}", self.operator_id)),
+                error
+            ),
+        }
+    }
+
+    fn fail(&self) {
+        if !self.failed.swap(true, Ordering::SeqCst) {
+            // The receiver is dropped if the executor already stopped processing events.
+            self.failure_tx.send(()).ok();
+        }
+    }
+
+    fn has_failed(&self) -> bool {
+        self.failed.load(Ordering::SeqCst)
+    }
+
+    /// Closes the error stream once the operator completes.
+    fn close_error_stream(&self) {
+        if let Some(error_stream) = &self.error_stream {
+            let mut error_stream = error_stream.lock().unwrap();
+            if !error_stream.is_closed() {
+                error_stream
+                    .send(Message::new_watermark(Timestamp::top()))
+                    .ok();
+            }
+        }
+    }
+}
+
+/// Records the durations of an operator's callbacks and the number of events waiting for their
+/// callbacks, and warns about the callbacks which run for longer than the slow callback
+/// threshold, which triggers the captures of the node.
+struct CallbackTimer {
+    operator_name: String,
+    node_id: NodeId,
+    threshold: Duration,
+    context: Arc<NodeContext>,
+    message_durations: Histogram,
+    watermark_durations: Histogram,
+    slow_callbacks: Counter,
+    queued_events: Gauge,
+}
+
+impl CallbackTimer {
+    fn new(
+        operator_name: &str,
+        node_id: NodeId,
+        threshold: Duration,
+        context: Arc<NodeContext>,
+    ) -> Self {
+        let metrics = OperatorMetrics::new(operator_name);
+        Self {
+            operator_name: operator_name.to_string(),
+            node_id,
+            threshold,
+            context,
+            message_durations: metrics.histogram("erdos_message_callback_duration_seconds"),
+            watermark_durations: metrics.histogram("erdos_watermark_callback_duration_seconds"),
+            slow_callbacks: metrics.counter("erdos_slow_callbacks_total"),
+            queued_events: metrics.gauge(QUEUED_EVENTS_METRIC),
+        }
+    }
+
+    fn record(&self, timestamp: &Timestamp, is_watermark_callback: bool, duration: Duration) {
+        let durations = if is_watermark_callback {
+            &self.watermark_durations
+        } else {
+            &self.message_durations
+        };
+        durations.observe(duration.as_secs_f64());
+        if duration > self.threshold {
+            self.slow_callbacks.increment();
+            self.context.captures.trigger();
+            slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "Node {}: operator {} ran a slow {} callback for timestamp {:?} in {:?}",
+                self.node_id,
+                self.operator_name,
+                if is_watermark_callback {
+                    "watermark"
+                } else {
+                    "message"
+                },
+                timestamp,
+                duration
+            );
         }
     }
 }
@@ -129,7 +481,7 @@ impl<D: Data> OperatorExecutorStream<D> {
 /// events.
 pub struct OperatorExecutor {
     /// The instance of the operator that needs to be executed.
-    operator: Box<dyn Operator>,
+    operator: Box<dyn OperatorT>,
     /// The configuration with which the operator was instantiated, without the argument.
     config: OperatorConfig<()>,
     /// A merged stream of all the input streams of the operator. This is used to retrieve events
@@ -141,6 +493,19 @@ pub struct OperatorExecutor {
     lattice: Arc<ExecutionLattice>,
     /// Receives control messages regarding the operator.
     control_rx: mpsc::UnboundedReceiver<ControlMessage>,
+    /// Applies the operator's error policy to errors returned by callbacks.
+    error_handler: Arc<CallbackErrorHandler>,
+    /// Notified when the operator fails due to an error returned by a callback.
+    failure_rx: mpsc::UnboundedReceiver<()>,
+    /// Tracks the running callbacks, used to detect stalled operators.
+    activity: Arc<OperatorActivity>,
+    /// The state of the node on which the operator runs, on which its callbacks are counted and
+    /// traced.
+    context: Arc<NodeContext>,
+    /// Receives the callbacks of the messages the operator receives in its pull loop.
+    pulled_events_rx: mpsc::UnboundedReceiver<Vec<OperatorEvent>>,
+    /// Clones of the operator's write streams, which are closed if the operator fails.
+    write_streams: Vec<Box<dyn OperatorExecutorWriteStreamT>>,
 }
 
 impl OperatorExecutor {
@@ -149,12 +514,30 @@ impl OperatorExecutor {
         operator: T,
         config: OperatorConfig<U>,
         mut operator_streams: Vec<Box<dyn OperatorExecutorStreamT>>,
+        control_tx: mpsc::UnboundedSender<ControlMessage>,
         control_rx: mpsc::UnboundedReceiver<ControlMessage>,
+        error_stream: Option<WriteStream<OperatorErrorReport>>,
     ) -> Self {
         let streams_closed: HashMap<_, _> = operator_streams
             .iter()
             .map(|s| (s.get_id(), s.get_closed_ref()))
             .collect();
+        let (pulled_events_tx, pulled_events_rx) = mpsc::unbounded_channel();
+        for stream in operator_streams.iter() {
+            stream.set_pulled_events_tx(pulled_events_tx.clone());
+        }
+        let memory = config.memory_limit.map(|limit| {
+            let name = config
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("{}", config.id));
+            memory_limit::register(config.id, &name, limit, config.memory_limit_policy)
+        });
+        if let Some(memory) = &memory {
+            for stream in operator_streams.iter_mut() {
+                stream.set_memory_tracker(Arc::clone(memory));
+            }
+        }
         let event_stream = operator_streams.pop().map(|first| {
             operator_streams
                 .into_iter()
@@ -162,6 +545,18 @@ impl OperatorExecutor {
                     Box::pin(StreamExt::merge(x, y.to_pinned_stream()))
                 })
         });
+        let (failure_tx, failure_rx) = mpsc::unbounded_channel();
+        let error_handler = Arc::new(CallbackErrorHandler {
+            operator_id: config.id,
+            operator_name: config.name.clone(),
+            node_id: config.node_id,
+            policy: config.error_policy,
+            error_stream: error_stream.map(Mutex::new),
+            control_tx,
+            failure_tx,
+            failed: AtomicBool::new(false),
+            memory,
+        });
         Self {
             operator: Box::new(operator),
             config: config.drop_arg(),
@@ -169,9 +564,36 @@ impl OperatorExecutor {
             streams_closed,
             lattice: Arc::new(ExecutionLattice::new()),
             control_rx,
+            error_handler,
+            failure_rx,
+            activity: Arc::new(OperatorActivity::default()),
+            context: Arc::new(NodeContext::default()),
+            pulled_events_rx,
+            write_streams: Vec::new(),
         }
     }
 
+    /// Sets the write streams of the operator, which are closed if the operator fails so that
+    /// the downstream operators complete.
+    pub fn set_write_streams(&mut self, write_streams: Vec<Box<dyn OperatorExecutorWriteStreamT>>) {
+        self.write_streams = write_streams;
+    }
+
+    /// Reports the callbacks run by the operator to the node's health checks.
+    pub(crate) fn set_activity(&mut self, activity: Arc<OperatorActivity>) {
+        self.activity = activity;
+    }
+
+    /// Counts and traces the callbacks run by the operator on the state of the node.
+    pub(crate) fn set_context(&mut self, context: Arc<NodeContext>) {
+        self.context = context;
+    }
+
+    /// Sets the slow callback threshold of the node, unless the operator sets its own threshold.
+    pub(crate) fn set_default_slow_callback_threshold(&mut self, threshold: Duration) {
+        self.config.slow_callback_threshold.get_or_insert(threshold);
+    }
+
     /// Whether all input streams have been closed.
     ///
     /// Returns true if there are no input streams.
@@ -182,10 +604,11 @@ impl OperatorExecutor {
     }
 
     /// A high-level execute function that first waits for a [`ControlMessage::RunOperator`] message
-    /// and executes [`Operator::run`].
-    /// Once [`Operator::run`] completes, the function runs callbacks by retrieving events from the
-    /// input streams, adding them to the lattice maintained by the executor and notifying the
-    /// `event_runner` invocations to process the received events.
+    /// and executes [`Operator::run`], followed by [`Operator::run_async`]. While the latter runs,
+    /// the callbacks of the messages it receives are processed.
+    /// Once [`Operator::run_async`] completes, the function runs callbacks by retrieving events
+    /// from the input streams, adding them to the lattice maintained by the executor and notifying
+    /// the `event_runner` invocations to process the received events.
     pub async fn execute(&mut self) {
         loop {
             if let Some(ControlMessage::RunOperator(id)) = self.control_rx.recv().await {
@@ -207,29 +630,145 @@ impl OperatorExecutor {
             name
         );
 
+        let mut last_checkpoint_id = 0;
+        if checkpoint::is_enabled() {
+            let operator_id = self.config.id;
+            if let Some(operator) = self.operator.as_stateful() {
+                match thread_per_core::block_in_place(|| checkpoint::restore(operator_id, operator))
+                {
+                    Ok(Some(checkpoint_id)) => {
+                        slog::info!(
+                            crate::TERMINAL_LOGGER,
+                            "Node {}: restored operator {} from checkpoint {}",
+                            self.config.node_id,
+                            name,
+                            checkpoint_id
+                        );
+                        last_checkpoint_id = checkpoint_id;
+                    }
+                    Ok(None) => (),
+                    Err(e) => self
+                        .error_handler
+                        .handle(&Timestamp::bottom(), OperatorError::new(e)),
+                }
+            }
+        }
+
         // Callbacks are not invoked while the operator is running.
-        tokio::task::block_in_place(|| self.operator.run());
+        let mut ctx = RunContext::new(self.config.clone(), self.error_handler.control_tx.clone());
+        let ((), blocked) = bounded_channel::defer_blocking(|| {
+            thread_per_core::block_in_place(|| self.operator.run_with_context(&mut ctx))
+        });
+        blocked.wait().await;
+        // Operators which depend on this operator may now run, unless it let them run earlier.
+        ctx.ready();
 
-        if let Some(mut event_stream) = self.event_stream.take() {
-            // Launch consumers
-            // TODO: use CondVar instead of watch.
-            // TODO: adjust number of event runners. based on size of event lattice.
-            let (notifier_tx, notifier_rx) = watch::channel(EventRunnerMessage::AddedEvents);
-            let mut event_runner_handles = Vec::new();
-            for _ in 0..self.config.num_event_runners {
-                let event_runner_fut =
-                    Self::event_runner(Arc::clone(&self.lattice), notifier_rx.clone());
+        // Launch consumers
+        // TODO: use CondVar instead of watch.
+        // TODO: adjust number of event runners. based on size of event lattice.
+        let (notifier_tx, notifier_rx) = watch::channel(EventRunnerMessage::AddedEvents);
+        let mut event_runner_handles = Vec::new();
+        if self.event_stream.is_some() {
+            let callback_timer = Arc::new(CallbackTimer::new(
+                &name,
+                self.config.node_id,
+                self.config
+                    .slow_callback_threshold
+                    .unwrap_or(DEFAULT_SLOW_CALLBACK_THRESHOLD),
+                Arc::clone(&self.context),
+            ));
+            for i in 0..self.config.num_event_runners {
+                let event_runner_fut = Self::event_runner(
+                    Arc::clone(&self.lattice),
+                    notifier_rx.clone(),
+                    Arc::clone(&self.error_handler),
+                    i,
+                    Arc::clone(&self.activity),
+                    Arc::clone(&callback_timer),
+                    self.config.id,
+                );
                 event_runner_handles.push(tokio::spawn(event_runner_fut));
             }
-            while let Some(events) = event_stream.next().await {
-                {
-                    // Add all the received events to the lattice.
+        }
+
+        let queued_events = OperatorMetrics::new(&name).gauge(QUEUED_EVENTS_METRIC);
+        let mut pull_loop = PullLoop::new(self.operator.run_async(&mut ctx));
+        loop {
+            tokio::select! {
+                _ = &mut pull_loop => break,
+                Some(events) = self.pulled_events_rx.recv() => {
+                    queued_events.add(events.len() as f64);
                     self.lattice.add_events(events).await;
-                    // Notify receivers that new events were added.
                     notifier_tx
                         .broadcast(EventRunnerMessage::AddedEvents)
                         .unwrap();
                 }
+                // Stop the pull loop if a callback failed the operator.
+                Some(_) = self.failure_rx.recv() => break,
+            }
+        }
+        drop(pull_loop);
+        while let Ok(events) = self.pulled_events_rx.try_recv() {
+            queued_events.add(events.len() as f64);
+            self.lattice.add_events(events).await;
+            notifier_tx
+                .broadcast(EventRunnerMessage::AddedEvents)
+                .unwrap();
+        }
+
+        let checkpointing = checkpoint::is_enabled() && self.operator.as_stateful().is_some();
+        let mut checkpoint_requests = checkpoint::requests();
+        if let Some(mut event_stream) = self.event_stream.take() {
+            if checkpointing {
+                self.activity.start_checkpoints(last_checkpoint_id);
+            }
+            while !self.error_handler.has_failed() {
+                // Stop reading messages until the callbacks of the buffered messages run.
+                if let Some(memory) = &self.error_handler.memory {
+                    while memory.policy() == MemoryLimitPolicy::Backpressure
+                        && memory.is_exceeded()
+                        && memory.buffered_bytes() > 0
+                        && !self.error_handler.has_failed()
+                    {
+                        tokio::select! {
+                            _ = memory.wait_for_release() => (),
+                            Some(_) = self.failure_rx.recv() => break,
+                        }
+                    }
+                }
+                tokio::select! {
+                    events = event_stream.next() => match events {
+                        Some(events) => {
+                            self.context.counters.message_read_into_events(events.len());
+                            let timestamp = events.first().map(|event| event.timestamp.clone());
+                            queued_events.add(events.len() as f64);
+                            // Add all the received events to the lattice.
+                            self.lattice.add_events(events).await;
+                            if let Some(timestamp) = timestamp {
+                                self.error_handler.check_memory_limit(&timestamp);
+                            }
+                            // Notify receivers that new events were added.
+                            notifier_tx
+                                .broadcast(EventRunnerMessage::AddedEvents)
+                                .unwrap();
+                        }
+                        None => break,
+                    },
+                    Some(checkpoint_id) = checkpoint_requests.recv(), if checkpointing => {
+                        if checkpoint_id > last_checkpoint_id {
+                            last_checkpoint_id = checkpoint_id;
+                            Self::take_checkpoint(
+                                self.operator.as_mut(),
+                                &self.config,
+                                &self.activity,
+                                &name,
+                                checkpoint_id,
+                            );
+                        }
+                    }
+                    // Stop processing events if a callback failed the operator.
+                    Some(_) = self.failure_rx.recv() => break,
+                }
             }
             // Wait for event runners to finish.
             notifier_tx
@@ -237,9 +776,24 @@ impl OperatorExecutor {
                 .unwrap();
             // Handle errors?
             future::join_all(event_runner_handles).await;
+            self.activity.stop_checkpoints();
+        }
+        self.error_handler.close_error_stream();
+        if self.error_handler.memory.is_some() {
+            memory_limit::unregister(self.config.id);
         }
 
-        if self.all_streams_closed() {
+        if self.error_handler.has_failed() {
+            slog::error!(
+                crate::TERMINAL_LOGGER,
+                "Node {}: operator {} failed, skipping destroy",
+                self.config.node_id,
+                name,
+            );
+            for write_stream in self.write_streams.iter_mut() {
+                write_stream.close_on_failure();
+            }
+        } else if self.all_streams_closed() {
             slog::debug!(
                 crate::TERMINAL_LOGGER,
                 "Node {}: destroying operator {}",
@@ -250,18 +804,96 @@ impl OperatorExecutor {
         }
     }
 
+    /// Saves the state of the operator in the checkpoint, and logs the errors, which do not fail
+    /// the operator as it is restored from an older checkpoint. Checkpoints which are not taken
+    /// are reported as lagging to the `activity` tracker.
+    fn take_checkpoint(
+        operator: &mut dyn OperatorT,
+        config: &OperatorConfig<()>,
+        activity: &OperatorActivity,
+        name: &str,
+        checkpoint_id: u64,
+    ) {
+        let operator = match operator.as_stateful() {
+            Some(operator) => operator,
+            None => return,
+        };
+        let result = thread_per_core::block_in_place(|| {
+            checkpoint::take(config.id, operator, checkpoint_id)
+        });
+        match result {
+            Ok(()) => {
+                activity.finish_checkpoint(checkpoint_id);
+                slog::debug!(
+                    crate::TERMINAL_LOGGER,
+                    "Node {}: operator {} took checkpoint {}",
+                    config.node_id,
+                    name,
+                    checkpoint_id
+                );
+            }
+            Err(e) => slog::warn!(
+                crate::TERMINAL_LOGGER,
+                "Node {}: operator {} {}",
+                config.node_id,
+                name,
+                e
+            ),
+        }
+    }
+
     /// An `event_runner` invocation is in charge of executing callbacks associated with an event.
     /// Upon receipt of an `AddedEvents` notification, it queries the lattice for events that are
     /// ready to run, executes them, and notifies the lattice of their completion.
+    /// Errors returned by callbacks are passed to the `error_handler`, running callbacks are
+    /// reported to the `activity` tracker, and their durations to the `callback_timer`, which
+    /// also holds the context of the node on which they are counted. The messages sent by the
+    /// callbacks are attributed to the operator `operator_id`.
     async fn event_runner(
         lattice: Arc<ExecutionLattice>,
         mut notifier_rx: watch::Receiver<EventRunnerMessage>,
+        error_handler: Arc<CallbackErrorHandler>,
+        event_runner_id: usize,
+        activity: Arc<OperatorActivity>,
+        callback_timer: Arc<CallbackTimer>,
+        operator_id: OperatorId,
     ) {
+        let context = Arc::clone(&callback_timer.context);
         // Wait for notification for events added.
         while let Some(control_msg) = notifier_rx.recv().await {
             while let Some((event, event_id)) = lattice.get_event().await {
-                (event.callback)();
+                activity.start_callback(event_runner_id);
+                if context.provenance.is_tracked() {
+                    context
+                        .provenance
+                        .enter_callback(operator_id, event.provenance.as_ref());
+                }
+                let buffered_bytes = event.buffered_bytes;
+                let start = Instant::now();
+                // Messages sent on full channels are queued, and the runner waits for the
+                // channels to have room before running the next callback.
+                let (result, blocked) = bounded_channel::defer_blocking(event.callback);
+                let duration = start.elapsed();
+                callback_timer.record(&event.timestamp, event.is_watermark_callback, duration);
+                callback_timer.queued_events.add(-1.0);
+                if let Some(probe) = &event.latency_probe {
+                    latency::record_callback(probe, start, duration);
+                }
+                activity.finish_callback(event_runner_id);
+                if let Err(error) = result {
+                    error_handler.handle(&event.timestamp, error);
+                }
+                if let Some(memory) = &error_handler.memory {
+                    memory.release(buffered_bytes);
+                    // The callback may have reported a larger state.
+                    error_handler.check_memory_limit(&event.timestamp);
+                }
+                blocked.wait().await;
                 lattice.mark_as_completed(event_id).await;
+                context.counters.event_completed();
+                if error_handler.has_failed() {
+                    return;
+                }
             }
             if EventRunnerMessage::DestroyOperator == control_msg {
                 break;
@@ -0,0 +1,159 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use crate::{node::NodeId, OperatorId};
+
+/// The core on which an operator runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperatorPlacement {
+    pub id: OperatorId,
+    pub name: String,
+    pub core: usize,
+}
+
+/// The core on which the data receiver of the messages from another node runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiverPlacement {
+    pub from_node: NodeId,
+    pub core: usize,
+}
+
+/// The cores on which a [`Node`](crate::node::Node) with the
+/// [`ThreadPerCore`](crate::ExecutorModel::ThreadPerCore) executor runs its operators and data
+/// receivers, e.g. to check which operators share a core.
+///
+/// Returned by [`NodeHandle::placement_report`](crate::node::NodeHandle::placement_report).
+/// Data receivers are only listed if they run on the cores, i.e. if
+/// [`core_affinity`](crate::Configuration::core_affinity) is set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacementReport {
+    pub node_id: NodeId,
+    pub num_cores: usize,
+    /// Whether the cores are pinned to CPUs.
+    pub pinned: bool,
+    pub receivers: Vec<ReceiverPlacement>,
+    pub operators: Vec<OperatorPlacement>,
+}
+
+impl fmt::Display for PlacementReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "node {} runs on {} {}cores",
+            self.node_id,
+            self.num_cores,
+            if self.pinned { "pinned " } else { "" }
+        )?;
+        for core in 0..self.num_cores {
+            let receivers: Vec<_> = self
+                .receivers
+                .iter()
+                .filter(|receiver| receiver.core == core)
+                .map(|receiver| format!("receiver from node {}", receiver.from_node))
+                .collect();
+            let operators: Vec<_> = self
+                .operators
+                .iter()
+                .filter(|operator| operator.core == core)
+                .map(|operator| operator.name.clone())
+                .collect();
+            writeln!(
+                f,
+                "  core {}: {}",
+                core,
+                [receivers, operators].concat().join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Records the cores to which a node assigns its data receivers and operators.
+#[derive(Clone)]
+pub(crate) struct Placement {
+    node_id: NodeId,
+    report: Arc<Mutex<Option<PlacementReport>>>,
+}
+
+impl Placement {
+    pub fn new(node_id: NodeId) -> Self {
+        Self {
+            node_id,
+            report: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Starts a new report when the node creates its cores.
+    pub fn start(&self, num_cores: usize, pinned: bool) {
+        *self.report.lock().unwrap() = Some(PlacementReport {
+            node_id: self.node_id,
+            num_cores,
+            pinned,
+            receivers: Vec::new(),
+            operators: Vec::new(),
+        });
+    }
+
+    pub fn place_receiver(&self, from_node: NodeId, core: usize) {
+        if let Some(report) = self.report.lock().unwrap().as_mut() {
+            report.receivers.push(ReceiverPlacement { from_node, core });
+        }
+    }
+
+    /// Places an operator on the core of the receiver of the first node from which it reads
+    /// streams, so that it reads payloads on the core which received them, or otherwise on
+    /// `next_core`.
+    pub fn place_operator(
+        &self,
+        id: OperatorId,
+        name: String,
+        source_nodes: &[NodeId],
+        next_core: impl FnOnce() -> usize,
+    ) -> usize {
+        let mut report = self.report.lock().unwrap();
+        let receiver_cores: HashMap<_, _> = report
+            .iter()
+            .flat_map(|report| report.receivers.iter())
+            .map(|receiver| (receiver.from_node, receiver.core))
+            .collect();
+        let core = source_nodes
+            .iter()
+            .find_map(|node_id| receiver_cores.get(node_id).cloned())
+            .unwrap_or_else(next_core);
+        if let Some(report) = report.as_mut() {
+            report.operators.push(OperatorPlacement { id, name, core });
+        }
+        core
+    }
+
+    /// Returns the report, or `None` if the node does not run on cores.
+    pub fn report(&self) -> Option<PlacementReport> {
+        self.report.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that operators are placed on the cores of the receivers of the streams they read.
+    #[test]
+    fn test_placement() {
+        let placement = Placement::new(0);
+        placement.start(2, false);
+        placement.place_receiver(1, 1);
+        let reader = placement.place_operator(OperatorId::new_v4(), "reader".into(), &[0, 1], || 0);
+        let other = placement.place_operator(OperatorId::new_v4(), "other".into(), &[], || 0);
+        assert_eq!((reader, other), (1, 0));
+
+        let report = placement.report().unwrap();
+        assert_eq!(report.operators.len(), 2);
+        assert_eq!(
+            report.to_string(),
+            "node 0 runs on 2 cores\n  core 0: other\n  core 1: receiver from node 1, reader\n"
+        );
+    }
+}
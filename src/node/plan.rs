@@ -0,0 +1,219 @@
+use std::fmt;
+
+use crate::{
+    dataflow::{
+        graph::{json_string, Channel, Graph, Vertex},
+        stream::StreamId,
+    },
+    node::NodeId,
+    OperatorId,
+};
+
+/// The node on which an operator runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedOperator {
+    pub id: OperatorId,
+    pub name: String,
+    pub node_id: NodeId,
+}
+
+/// A stream whose messages are sent to other nodes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedStream {
+    pub id: StreamId,
+    pub name: String,
+    pub source_node: NodeId,
+    /// The other nodes which receive the messages, sorted by ID.
+    pub sink_nodes: Vec<NodeId>,
+}
+
+/// The placement of a dataflow on nodes, returned by [`Node::plan`](crate::node::Node::plan)
+/// without running the dataflow, e.g. to inspect which streams cross nodes before deploying.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataflowPlan {
+    /// The operators, sorted by node and name.
+    pub operators: Vec<PlannedOperator>,
+    /// The streams between nodes, sorted by name.
+    pub inter_node_streams: Vec<PlannedStream>,
+}
+
+impl DataflowPlan {
+    /// Describes a scheduled graph.
+    pub(crate) fn new(graph: &Graph) -> Self {
+        let mut operators: Vec<_> = graph
+            .get_operators()
+            .into_iter()
+            .map(|operator| PlannedOperator {
+                id: operator.id,
+                name: operator.name.unwrap_or_else(|| operator.id.to_string()),
+                node_id: operator.node_id,
+            })
+            .collect();
+        operators.sort_by(|a, b| (a.node_id, &a.name).cmp(&(b.node_id, &b.name)));
+
+        let node_of = |vertex: &Vertex| match vertex {
+            Vertex::Driver(node_id) => *node_id,
+            Vertex::Operator(operator_id) => graph.get_operator(*operator_id).unwrap().node_id,
+        };
+        let mut inter_node_streams = Vec::new();
+        for stream in graph.get_streams() {
+            let mut sink_nodes: Vec<NodeId> = stream
+                .get_channels()
+                .into_iter()
+                .filter_map(|channel| match channel {
+                    Channel::InterNode(cm) => Some(node_of(&cm.sink)),
+                    _ => None,
+                })
+                .collect();
+            if sink_nodes.is_empty() {
+                continue;
+            }
+            sink_nodes.sort_unstable();
+            sink_nodes.dedup();
+            let id = stream.get_id();
+            inter_node_streams.push(PlannedStream {
+                id,
+                name: id.name().unwrap_or_else(|| id.to_string()),
+                source_node: node_of(&stream.get_source()),
+                sink_nodes,
+            });
+        }
+        inter_node_streams.sort_by(|a, b| a.name.cmp(&b.name));
+        Self {
+            operators,
+            inter_node_streams,
+        }
+    }
+
+    /// Returns the plan as a JSON document.
+    pub fn to_json(&self) -> String {
+        let operators: Vec<String> = self
+            .operators
+            .iter()
+            .map(|operator| {
+                format!(
+                    "{{\"id\": \"{}\", \"name\": {}, \"node\": {}}}",
+                    operator.id,
+                    json_string(&operator.name),
+                    operator.node_id
+                )
+            })
+            .collect();
+        let streams: Vec<String> = self
+            .inter_node_streams
+            .iter()
+            .map(|stream| {
+                let sink_nodes: Vec<String> = stream
+                    .sink_nodes
+                    .iter()
+                    .map(|node_id| node_id.to_string())
+                    .collect();
+                format!(
+                    "{{\"id\": \"{}\", \"name\": {}, \"source_node\": {}, \"sink_nodes\": [{}]}}",
+                    stream.id,
+                    json_string(&stream.name),
+                    stream.source_node,
+                    sink_nodes.join(", ")
+                )
+            })
+            .collect();
+        format!(
+            "{{\n  \"operators\": [\n    {}\n  ],\n  \"inter_node_streams\": [\n    {}\n  ]\n}}\n",
+            operators.join(",\n    "),
+            streams.join(",\n    ")
+        )
+    }
+}
+
+impl fmt::Display for DataflowPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut node_id = None;
+        for operator in self.operators.iter() {
+            if node_id != Some(operator.node_id) {
+                writeln!(f, "node {}:", operator.node_id)?;
+                node_id = Some(operator.node_id);
+            }
+            writeln!(f, "  {}", operator.name)?;
+        }
+        if !self.inter_node_streams.is_empty() {
+            writeln!(f, "streams between nodes:")?;
+        }
+        for stream in self.inter_node_streams.iter() {
+            let sink_nodes: Vec<String> = stream
+                .sink_nodes
+                .iter()
+                .map(|node_id| node_id.to_string())
+                .collect();
+            writeln!(
+                f,
+                "  {}: node {} -> {}",
+                stream.name,
+                stream.source_node,
+                sink_nodes.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+    use super::*;
+
+    use crate::{
+        communication::ControlMessage, dataflow::WriteStream,
+        node::operator_executor::OperatorExecutor, scheduler,
+        scheduler::channel_manager::ChannelManager,
+    };
+
+    fn runner(
+        _: Arc<Mutex<ChannelManager>>,
+        _: UnboundedSender<ControlMessage>,
+        _: UnboundedReceiver<ControlMessage>,
+    ) -> OperatorExecutor {
+        unreachable!()
+    }
+
+    /// Test that the plan lists the operators of each node and the streams between nodes.
+    #[test]
+    fn test_dataflow_plan() {
+        let (source, sink) = (
+            OperatorId::new_deterministic(),
+            OperatorId::new_deterministic(),
+        );
+        let stream: WriteStream<u32> = WriteStream::new_with_name("frames");
+        let mut graph = Graph::new();
+        graph.add_operator(
+            source,
+            Some("Camera".into()),
+            0,
+            vec![],
+            vec![stream.get_id()],
+            runner,
+        );
+        graph.add_operator_stream(source, &stream);
+        graph.add_operator(
+            sink,
+            Some("Detector \"v2\"".into()),
+            1,
+            vec![stream.get_id()],
+            vec![],
+            runner,
+        );
+        let graph = scheduler::schedule(&graph, None, None, &[]).unwrap();
+
+        let plan = DataflowPlan::new(&graph);
+        assert_eq!(
+            plan.to_string(),
+            "node 0:\n  Camera\nnode 1:\n  Detector \"v2\"\nstreams between nodes:\n  \
+             frames: node 0 -> 1\n"
+        );
+        let json = plan.to_json();
+        assert!(json.contains("\"name\": \"Detector \\\"v2\\\"\", \"node\": 1"));
+        assert!(json.contains("\"name\": \"frames\", \"source_node\": 0, \"sink_nodes\": [1]"));
+    }
+}
@@ -0,0 +1,272 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    communication::MessageMetadata,
+    dataflow::{stream::StreamId, Timestamp},
+    OperatorId,
+};
+
+/// Maximum number of origins recorded for a message. The origins closest to the message are
+/// kept.
+pub(crate) const MAX_ORIGINS: usize = 32;
+/// Maximum number of messages whose provenance is recorded on a node. The provenance of the
+/// oldest messages is forgotten first.
+pub(crate) const MAX_RECORDS: usize = 10_000;
+
+thread_local! {
+    /// The callback running on the thread.
+    static CURRENT: RefCell<Option<Callback>> = RefCell::new(None);
+    /// The provenance of the message being sent on the thread, which is attached to the message
+    /// if it is sent to another node.
+    static SENT: RefCell<Option<(StreamId, Provenance)>> = RefCell::new(None);
+}
+
+/// A message which contributed to another message: a message of `stream_id` with `timestamp`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Origin {
+    pub stream_id: StreamId,
+    pub timestamp: Timestamp,
+}
+
+/// The origins of the messages of a stream with a timestamp, which are sent to other nodes with
+/// the messages.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub timestamp: Timestamp,
+    /// The operator which sent the messages.
+    pub operator: Option<OperatorId>,
+    /// The messages read by the callbacks which sent the messages.
+    pub inputs: Vec<Origin>,
+    /// All messages which contributed to the messages, closest first.
+    pub origins: Vec<Origin>,
+}
+
+/// The message processed by a callback, to which the messages sent by the callback are
+/// attributed.
+struct Callback {
+    operator: OperatorId,
+    input: Origin,
+    /// The input and its origins.
+    origins: Vec<Origin>,
+}
+
+/// Provenance of the messages sent or received on a node, keyed by the stream and the timestamp
+/// of the messages.
+#[derive(Default)]
+struct Records {
+    records: HashMap<(StreamId, Timestamp), Provenance>,
+    /// Order in which the messages were first recorded, in order to forget the oldest ones.
+    order: VecDeque<(StreamId, Timestamp)>,
+}
+
+impl Records {
+    fn get(&self, stream_id: StreamId, timestamp: &Timestamp) -> Option<&Provenance> {
+        self.records.get(&(stream_id, timestamp.clone()))
+    }
+
+    /// Adds the provenance of a message to the messages of the stream with the same timestamp,
+    /// and returns the provenance of all of them.
+    fn merge(&mut self, stream_id: StreamId, provenance: &Provenance) -> Provenance {
+        let key = (stream_id, provenance.timestamp.clone());
+        if !self.records.contains_key(&key) {
+            if self.order.len() >= MAX_RECORDS {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.records.remove(&oldest);
+                }
+            }
+            self.order.push_back(key.clone());
+        }
+        let record = self.records.entry(key).or_insert_with(|| Provenance {
+            timestamp: provenance.timestamp.clone(),
+            operator: provenance.operator,
+            inputs: Vec::new(),
+            origins: Vec::new(),
+        });
+        extend_bounded(&mut record.inputs, &provenance.inputs);
+        extend_bounded(&mut record.origins, &provenance.origins);
+        record.clone()
+    }
+}
+
+fn extend_bounded(origins: &mut Vec<Origin>, new_origins: &[Origin]) {
+    for origin in new_origins {
+        if origins.len() >= MAX_ORIGINS {
+            break;
+        }
+        if !origins.contains(origin) {
+            origins.push(origin.clone());
+        }
+    }
+}
+
+/// The provenance recorded on a node.
+#[derive(Default)]
+pub(crate) struct ProvenanceRecords {
+    records: Mutex<Records>,
+    /// Avoids looking up the provenance of every message if provenance is not tracked.
+    tracking: AtomicBool,
+}
+
+impl ProvenanceRecords {
+    pub fn track(&self) {
+        self.tracking.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_tracked(&self) -> bool {
+        self.tracking.load(Ordering::Relaxed)
+    }
+
+    /// Returns the provenance of the messages of the stream with the timestamp, or `None` if it
+    /// was not recorded on the node.
+    pub fn provenance(&self, stream_id: StreamId, timestamp: &Timestamp) -> Option<Provenance> {
+        self.records
+            .lock()
+            .unwrap()
+            .get(stream_id, timestamp)
+            .cloned()
+    }
+
+    /// Returns the messages which contributed to the messages of the stream with the timestamp,
+    /// the messages read by the callbacks which sent them first, or `None` if their provenance
+    /// was not recorded on the node.
+    pub fn origins(&self, stream_id: StreamId, timestamp: &Timestamp) -> Option<Vec<Origin>> {
+        self.provenance(stream_id, timestamp)
+            .map(|provenance| provenance.origins)
+    }
+
+    /// Sets the message processed by the callbacks of the operator which run on the thread
+    /// until the next call, which contributes to the messages sent by the callbacks.
+    pub fn enter_callback(&self, operator: OperatorId, input: Option<&Origin>) {
+        let current = input.map(|input| {
+            let mut origins = vec![input.clone()];
+            if let Some(ancestors) = self
+                .records
+                .lock()
+                .unwrap()
+                .get(input.stream_id, &input.timestamp)
+            {
+                extend_bounded(&mut origins, &ancestors.origins);
+            }
+            Callback {
+                operator,
+                input: input.clone(),
+                origins,
+            }
+        });
+        CURRENT.with(|c| *c.borrow_mut() = current);
+    }
+
+    /// Records the provenance of a message sent on the stream, whose timestamp is `None` if the
+    /// message is a watermark.
+    pub fn record_sent(&self, stream_id: StreamId, timestamp: Option<&Timestamp>) {
+        let sent = timestamp.and_then(|timestamp| {
+            CURRENT.with(|c| {
+                c.borrow().as_ref().map(|callback| {
+                    let provenance = Provenance {
+                        timestamp: timestamp.clone(),
+                        operator: Some(callback.operator),
+                        inputs: vec![callback.input.clone()],
+                        origins: callback.origins.clone(),
+                    };
+                    (
+                        stream_id,
+                        self.records.lock().unwrap().merge(stream_id, &provenance),
+                    )
+                })
+            })
+        });
+        SENT.with(|s| *s.borrow_mut() = sent);
+    }
+
+    /// Returns the provenance of the message being sent on the stream, in order to send it to
+    /// another node with the message.
+    pub fn sent_provenance(&self, stream_id: StreamId) -> Option<Provenance> {
+        if !self.is_tracked() {
+            return None;
+        }
+        SENT.with(|s| match s.borrow().as_ref() {
+            Some((sent_stream_id, provenance)) if *sent_stream_id == stream_id => {
+                Some(provenance.clone())
+            }
+            _ => None,
+        })
+    }
+
+    /// Records the provenance of a message received from another node.
+    pub fn record_received(&self, metadata: &MessageMetadata) {
+        if let Some(provenance) = &metadata.provenance {
+            self.records
+                .lock()
+                .unwrap()
+                .merge(metadata.stream_id, provenance);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the origins of a message include the origins of the messages it was computed
+    /// from, and that the records are bounded.
+    #[test]
+    fn test_provenance_chain() {
+        let (camera, detections, plan) =
+            (StreamId::new_v4(), StreamId::new_v4(), StreamId::new_v4());
+        let timestamp = Timestamp::new(vec![1]);
+        let origin = |stream_id| Origin {
+            stream_id,
+            timestamp: timestamp.clone(),
+        };
+
+        // A detector reads the camera and sends detections, which a planner reads.
+        let provenance_records = ProvenanceRecords::default();
+        let (detector, planner) = (OperatorId::new_v4(), OperatorId::new_v4());
+        provenance_records.enter_callback(detector, Some(&origin(camera)));
+        provenance_records.record_sent(detections, Some(&timestamp));
+        provenance_records.enter_callback(planner, Some(&origin(detections)));
+        provenance_records.record_sent(plan, Some(&timestamp));
+        provenance_records.enter_callback(planner, None);
+        assert_eq!(
+            provenance_records.origins(plan, &timestamp),
+            Some(vec![origin(detections), origin(camera)])
+        );
+        // Nodes which run in the same process record their provenance separately.
+        assert!(ProvenanceRecords::default()
+            .origins(plan, &timestamp)
+            .is_none());
+        let plan_provenance = provenance_records.provenance(plan, &timestamp).unwrap();
+        assert_eq!(plan_provenance.operator, Some(planner));
+        assert_eq!(plan_provenance.inputs, vec![origin(detections)]);
+        let sent = || SENT.with(|s| s.borrow().clone());
+        assert_eq!(sent(), Some((plan, plan_provenance)));
+        // Watermarks and messages sent outside callbacks have no provenance.
+        provenance_records.record_sent(plan, None);
+        assert_eq!(sent(), None);
+
+        let mut records = Records::default();
+        for time in 0..MAX_RECORDS as u64 + 1 {
+            let provenance = Provenance {
+                timestamp: Timestamp::new(vec![time]),
+                operator: Some(planner),
+                inputs: Vec::new(),
+                origins: (0..MAX_ORIGINS + 1)
+                    .map(|_| origin(StreamId::new_v4()))
+                    .collect(),
+            };
+            let merged = records.merge(plan, &provenance);
+            assert_eq!(merged.origins.len(), MAX_ORIGINS);
+        }
+        assert_eq!(records.records.len(), MAX_RECORDS);
+        assert!(records.get(plan, &Timestamp::new(vec![0])).is_none());
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::node::NodeId;
+
+/// Time between the rounds of the quiescence detection.
+pub(crate) const PROBE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Counts the messages sent and processed on a node.
+#[derive(Default)]
+pub(crate) struct MessageCounters {
+    /// Messages sent to operators and drivers on the node which they did not read yet, and
+    /// callbacks which did not complete yet.
+    in_flight: AtomicI64,
+    /// Number of messages sent to operators and drivers on any node.
+    activity: AtomicU64,
+    sent_to_nodes: AtomicU64,
+    received_from_nodes: AtomicU64,
+}
+
+impl MessageCounters {
+    /// A message was sent to an operator or a driver on the node.
+    pub fn message_sent(&self) {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        self.activity.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// A message was sent to another node.
+    pub fn message_sent_to_node(&self) {
+        self.sent_to_nodes.fetch_add(1, Ordering::SeqCst);
+        self.activity.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// A message was received from another node.
+    pub fn message_received_from_node(&self) {
+        self.received_from_nodes.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// An operator or a driver read a message.
+    pub fn message_read(&self) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// An operator turned a message into callbacks.
+    pub fn message_read_into_events(&self, num_events: usize) {
+        self.in_flight
+            .fetch_add(num_events as i64 - 1, Ordering::SeqCst);
+    }
+
+    /// An operator turned a message which it already read into callbacks.
+    pub fn events_added(&self, num_events: usize) {
+        self.in_flight
+            .fetch_add(num_events as i64, Ordering::SeqCst);
+    }
+
+    /// A callback completed.
+    pub fn event_completed(&self) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub fn local_counters(&self) -> LocalCounters {
+        LocalCounters {
+            sent_to_nodes: self.sent_to_nodes.load(Ordering::SeqCst),
+            received_from_nodes: self.received_from_nodes.load(Ordering::SeqCst),
+            in_flight: self.in_flight.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// Snapshot of the counters of a node, exported as runtime metrics.
+pub(crate) struct LocalCounters {
+    pub sent_to_nodes: u64,
+    pub received_from_nodes: u64,
+    pub in_flight: i64,
+}
+
+/// Token passed along the ring of nodes to detect whether the dataflow is quiescent.
+///
+/// Each node adds its counters to the token. The dataflow is quiescent if, in two consecutive
+/// rounds, all nodes were idle, all messages sent between nodes were received, and no messages
+/// were sent in between.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuiescenceToken {
+    initiator: NodeId,
+    sent: u64,
+    received: u64,
+    activity: u64,
+    idle: bool,
+}
+
+impl QuiescenceToken {
+    fn new(initiator: NodeId) -> Self {
+        Self {
+            initiator,
+            sent: 0,
+            received: 0,
+            activity: 0,
+            idle: true,
+        }
+    }
+
+    pub fn initiator(&self) -> NodeId {
+        self.initiator
+    }
+
+    /// Adds the counters of the current node.
+    pub(crate) fn add_local_counters(&mut self, counters: &MessageCounters) {
+        self.sent += counters.sent_to_nodes.load(Ordering::SeqCst);
+        self.received += counters.received_from_nodes.load(Ordering::SeqCst);
+        self.activity += counters.activity.load(Ordering::SeqCst);
+        self.idle &= counters.in_flight.load(Ordering::SeqCst) == 0;
+    }
+
+    fn totals(&self) -> (u64, u64, u64) {
+        (self.sent, self.received, self.activity)
+    }
+}
+
+/// Answers the requests to wait until the dataflow is quiescent, by passing tokens along the
+/// ring of nodes until two consecutive rounds observe the same quiescent state.
+pub(crate) struct QuiescenceDetector {
+    waiting: Vec<(Instant, mpsc::Sender<()>)>,
+    /// The totals of the previous round, if it observed a quiescent state.
+    previous: Option<(u64, u64, u64)>,
+    round_in_progress: bool,
+}
+
+impl QuiescenceDetector {
+    pub fn new() -> Self {
+        Self {
+            waiting: Vec::new(),
+            previous: None,
+            round_in_progress: false,
+        }
+    }
+
+    /// Adds a request which waits until the deadline at most.
+    ///
+    /// Returns the token with which the current node starts a round, unless a round is already
+    /// in progress.
+    pub fn add_request(
+        &mut self,
+        node_id: NodeId,
+        counters: &MessageCounters,
+        deadline: Instant,
+        reply_tx: mpsc::Sender<()>,
+    ) -> Option<QuiescenceToken> {
+        self.waiting.push((deadline, reply_tx));
+        self.start_round(node_id, counters)
+    }
+
+    /// Returns the token with which the current node starts a round, unless a round is already
+    /// in progress or no request is waiting anymore.
+    pub fn start_round(
+        &mut self,
+        node_id: NodeId,
+        counters: &MessageCounters,
+    ) -> Option<QuiescenceToken> {
+        if self.round_in_progress {
+            return None;
+        }
+        let now = Instant::now();
+        self.waiting.retain(|(deadline, _)| *deadline > now);
+        if self.waiting.is_empty() {
+            self.round_in_progress = false;
+            self.previous = None;
+            return None;
+        }
+        self.round_in_progress = true;
+        let mut token = QuiescenceToken::new(node_id);
+        token.add_local_counters(counters);
+        Some(token)
+    }
+
+    /// Completes a round once the token visited all nodes.
+    ///
+    /// Returns true if another round must be started.
+    pub fn complete_round(&mut self, token: QuiescenceToken) -> bool {
+        self.round_in_progress = false;
+        if !token.idle || token.sent != token.received {
+            self.previous = None;
+            return true;
+        }
+        if self.previous != Some(token.totals()) {
+            self.previous = Some(token.totals());
+            return true;
+        }
+        self.previous = None;
+        for (_, tx) in self.waiting.drain(..) {
+            // The handle may have stopped waiting.
+            let _ = tx.send(());
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that requests are answered after two identical quiescent rounds.
+    #[test]
+    fn test_quiescence_detector() {
+        let mut detector = QuiescenceDetector::new();
+        let counters = MessageCounters::default();
+        let (tx, rx) = mpsc::channel();
+        let deadline = Instant::now() + Duration::from_secs(60);
+        assert!(detector
+            .add_request(0, &counters, deadline, tx.clone())
+            .is_some());
+        assert!(detector.add_request(0, &counters, deadline, tx).is_none());
+
+        let token = QuiescenceToken::new(0);
+        let mut busy_token = token.clone();
+        busy_token.idle = false;
+        assert!(detector.complete_round(busy_token));
+        assert!(detector.complete_round(token.clone()));
+        assert!(rx.try_recv().is_err());
+        assert!(detector.start_round(0, &counters).is_some());
+        assert!(!detector.complete_round(token));
+        assert!(rx.try_recv().is_ok());
+        assert!(rx.try_recv().is_ok());
+    }
+}
@@ -0,0 +1,199 @@
+use std::{fmt, fs, thread};
+
+use serde::{Deserialize, Serialize};
+
+/// Root of the cgroup filesystem.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Memory limits of cgroup v1 above which the memory is unlimited, as the kernel reports
+/// unlimited memory as the largest multiple of the page size.
+const UNLIMITED_MEMORY: u64 = 1 << 62;
+
+/// The CPUs and memory available to the process.
+///
+/// On Linux, the limits of the cgroup of the process apply, e.g. the limits of the container
+/// in which the process runs, instead of the resources of the whole machine. Nodes use the
+/// limits to size their worker threads and the shared memory of the zero-copy transport, and
+/// report them in their [`NodeInfo`](crate::node::NodeInfo).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// Number of CPUs the process may use, which is fractional with CPU quotas.
+    pub cpus: f64,
+    /// Memory in bytes the process may use, or `None` if it is unknown.
+    pub memory_bytes: Option<u64>,
+    /// Whether the limits of a cgroup are lower than the resources of the machine.
+    pub cgroup_limited: bool,
+}
+
+impl ResourceLimits {
+    /// Detects the resources available to the current process.
+    pub fn detect() -> Self {
+        let machine_cpus = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1) as f64;
+        let machine_memory = fs::read_to_string("/proc/meminfo")
+            .ok()
+            .and_then(|meminfo| parse_meminfo(&meminfo));
+        Self::new(
+            machine_cpus,
+            machine_memory,
+            cgroup_cpus(),
+            cgroup_memory_bytes(),
+        )
+    }
+
+    fn new(
+        machine_cpus: f64,
+        machine_memory: Option<u64>,
+        cgroup_cpus: Option<f64>,
+        cgroup_memory: Option<u64>,
+    ) -> Self {
+        let cpus = cgroup_cpus.map_or(machine_cpus, |cpus| cpus.min(machine_cpus));
+        let memory_bytes = match (machine_memory, cgroup_memory) {
+            (Some(machine), Some(cgroup)) => Some(machine.min(cgroup)),
+            (machine, cgroup) => cgroup.or(machine),
+        };
+        let memory_limited = match (machine_memory, cgroup_memory) {
+            (Some(machine), Some(cgroup)) => cgroup < machine,
+            (None, cgroup) => cgroup.is_some(),
+            (Some(_), None) => false,
+        };
+        Self {
+            cpus,
+            memory_bytes,
+            cgroup_limited: cpus < machine_cpus || memory_limited,
+        }
+    }
+
+    /// Returns the number of worker threads which use the available CPUs, which is at least 1.
+    pub fn worker_threads(&self) -> usize {
+        (self.cpus.ceil() as usize).max(1)
+    }
+}
+
+impl fmt::Display for ResourceLimits {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} cpus", self.cpus)?;
+        match self.memory_bytes {
+            Some(bytes) => write!(f, ", {} MiB of memory", bytes / (1024 * 1024))?,
+            None => write!(f, ", unknown memory")?,
+        }
+        if self.cgroup_limited {
+            write!(f, " (cgroup limits)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the directories of the cgroups of the process, with the cgroup v2 hierarchy first.
+fn cgroup_dirs(controller: &str) -> Vec<String> {
+    let mut dirs = Vec::new();
+    if let Ok(cgroups) = fs::read_to_string("/proc/self/cgroup") {
+        // Lines have the format `hierarchy-id:controllers:path`, and the controllers of the
+        // cgroup v2 hierarchy are empty.
+        for line in cgroups.lines() {
+            let mut fields = line.splitn(3, ':');
+            let (controllers, path) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(_), Some(controllers), Some(path)) => (controllers, path),
+                _ => continue,
+            };
+            if controllers.is_empty() {
+                dirs.insert(0, format!("{}{}", CGROUP_ROOT, path));
+            } else if controllers.split(',').any(|c| c == controller) {
+                dirs.push(format!("{}/{}{}", CGROUP_ROOT, controller, path));
+            }
+        }
+    }
+    // Containers mount their own cgroup at the root.
+    dirs.push(CGROUP_ROOT.to_string());
+    dirs.push(format!("{}/{}", CGROUP_ROOT, controller));
+    dirs
+}
+
+fn cgroup_cpus() -> Option<f64> {
+    cgroup_dirs("cpu").iter().find_map(|dir| {
+        if let Ok(cpu_max) = fs::read_to_string(format!("{}/cpu.max", dir)) {
+            return Some(parse_cpu_max(&cpu_max));
+        }
+        let quota = fs::read_to_string(format!("{}/cpu.cfs_quota_us", dir)).ok()?;
+        let period = fs::read_to_string(format!("{}/cpu.cfs_period_us", dir)).ok()?;
+        Some(parse_cfs_quota(&quota, &period))
+    })?
+}
+
+fn cgroup_memory_bytes() -> Option<u64> {
+    cgroup_dirs("memory").iter().find_map(|dir| {
+        fs::read_to_string(format!("{}/memory.max", dir))
+            .or_else(|_| fs::read_to_string(format!("{}/memory.limit_in_bytes", dir)))
+            .ok()
+            .map(|limit| parse_memory_limit(&limit))
+    })?
+}
+
+/// Parses the `cpu.max` file of cgroup v2, e.g. `150000 100000` for 1.5 CPUs or `max 100000`.
+fn parse_cpu_max(cpu_max: &str) -> Option<f64> {
+    let mut fields = cpu_max.split_whitespace();
+    let quota = fields.next()?;
+    let period = fields.next().unwrap_or("100000");
+    parse_cfs_quota(quota, period)
+}
+
+/// Parses the CPU quota and period of cgroup v1, where a quota of -1 is unlimited.
+fn parse_cfs_quota(quota: &str, period: &str) -> Option<f64> {
+    let quota: f64 = quota.trim().parse().ok()?;
+    let period: f64 = period.trim().parse().ok()?;
+    if quota <= 0.0 || period <= 0.0 {
+        None
+    } else {
+        Some(quota / period)
+    }
+}
+
+/// Parses the `memory.max` file of cgroup v2 or the `memory.limit_in_bytes` file of cgroup v1.
+fn parse_memory_limit(limit: &str) -> Option<u64> {
+    limit
+        .trim()
+        .parse()
+        .ok()
+        .filter(|&bytes| bytes < UNLIMITED_MEMORY)
+}
+
+/// Parses the total memory of the machine from `/proc/meminfo`.
+fn parse_meminfo(meminfo: &str) -> Option<u64> {
+    let line = meminfo.lines().find(|line| line.starts_with("MemTotal:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the cgroup files are parsed, and that the cgroup limits apply only if they are
+    /// lower than the resources of the machine.
+    #[test]
+    fn test_resource_limits() {
+        assert_eq!(parse_cpu_max("150000 100000\n"), Some(1.5));
+        assert_eq!(parse_cpu_max("max 100000\n"), None);
+        assert_eq!(parse_cfs_quota("-1\n", "100000\n"), None);
+        assert_eq!(parse_cfs_quota("200000\n", "100000\n"), Some(2.0));
+        assert_eq!(parse_memory_limit("max\n"), None);
+        assert_eq!(parse_memory_limit("9223372036854771712\n"), None);
+        assert_eq!(parse_memory_limit("1073741824\n"), Some(1 << 30));
+        assert_eq!(
+            parse_meminfo("MemTotal:       16384000 kB\nMemFree: 1 kB\n"),
+            Some(16384000 * 1024)
+        );
+
+        let limits = ResourceLimits::new(8.0, Some(16 << 30), Some(1.5), Some(1 << 30));
+        assert_eq!(limits.cpus, 1.5);
+        assert_eq!(limits.memory_bytes, Some(1 << 30));
+        assert!(limits.cgroup_limited);
+        assert_eq!(limits.worker_threads(), 2);
+
+        let limits = ResourceLimits::new(4.0, Some(16 << 30), Some(8.0), None);
+        assert_eq!(limits.cpus, 4.0);
+        assert_eq!(limits.memory_bytes, Some(16 << 30));
+        assert!(!limits.cgroup_limited);
+    }
+}
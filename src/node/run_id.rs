@@ -0,0 +1,47 @@
+use std::sync::{Arc, RwLock};
+
+use crate::{node::NodeId, Uuid};
+
+/// The ID of a run of the dataflow, which distinguishes the messages and logs of different runs,
+/// e.g. when aggregating the logs of a fleet.
+///
+/// Each node proposes a random ID when it is created, and all nodes adopt the ID proposed by
+/// node 0 while they wait for each other to set up their operators. No messages are sent between
+/// nodes before, so all messages carry the agreed ID.
+#[derive(Clone)]
+pub(crate) struct RunId(Arc<RwLock<Uuid>>);
+
+impl RunId {
+    pub fn new() -> Self {
+        Self(Arc::new(RwLock::new(Uuid::new_v4())))
+    }
+
+    pub fn get(&self) -> Uuid {
+        *self.0.read().unwrap()
+    }
+
+    /// Adopts the ID proposed by a node if the node decides the ID.
+    pub fn propose(&self, node_id: NodeId, run_id: Uuid) {
+        if node_id == 0 {
+            *self.0.write().unwrap() = run_id;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that only the ID proposed by node 0 is adopted.
+    #[test]
+    fn test_propose_run_id() {
+        let run_id = RunId::new();
+        let proposed = run_id.get();
+        let other_run_id = RunId::new();
+        run_id.propose(1, other_run_id.get());
+        assert_eq!(run_id.get(), proposed);
+        run_id.propose(0, other_run_id.get());
+        assert_eq!(run_id.get(), other_run_id.get());
+        assert_eq!(run_id.clone().get(), other_run_id.get());
+    }
+}
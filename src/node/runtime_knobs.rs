@@ -0,0 +1,228 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+
+use slog::{Drain, Level, OwnedKVList, Record};
+
+use crate::node::NodeId;
+
+/// Name of the built-in knob which sets the minimum level of the messages logged by ERDOS.
+pub const LOG_LEVEL_KNOB: &str = "log_level";
+
+/// Minimum level of logged messages, as returned by [`Level::as_usize`]. Trace by default.
+static LOG_LEVEL: AtomicUsize = AtomicUsize::new(6);
+
+/// Drain which discards the messages below the level set by the [`LOG_LEVEL_KNOB`].
+pub(crate) struct RuntimeLevelFilter<D: Drain>(pub D);
+
+impl<D: Drain> Drain for RuntimeLevelFilter<D> {
+    type Ok = Option<D::Ok>;
+    type Err = D::Err;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        let level = Level::from_usize(LOG_LEVEL.load(Ordering::Relaxed)).unwrap_or(Level::Trace);
+        if record.level().is_at_least(level) {
+            self.0.log(record, values).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Drain which writes each message as a plain line to stderr, for builds without the
+/// `term_logger` feature. Lines are formatted in a buffer reused by the thread, so that logging
+/// does not allocate once the buffer has grown.
+#[cfg(not(feature = "term_logger"))]
+pub(crate) struct PlainDrain;
+
+#[cfg(not(feature = "term_logger"))]
+impl Drain for PlainDrain {
+    type Ok = ();
+    type Err = std::io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), std::io::Error> {
+        use slog::KV;
+        use std::{cell::RefCell, fmt::Write as _, io::Write as _};
+
+        thread_local! {
+            static LINE: RefCell<String> = RefCell::new(String::new());
+        }
+
+        /// Appends the key-value pairs of a record to the line.
+        struct LineSerializer<'a>(&'a mut String);
+
+        impl slog::Serializer for LineSerializer<'_> {
+            fn emit_arguments(&mut self, key: slog::Key, val: &fmt::Arguments) -> slog::Result {
+                write!(self.0, ", {}: {}", key, val).map_err(slog::Error::from)
+            }
+        }
+
+        LINE.with(|line| {
+            let mut line = line.borrow_mut();
+            line.clear();
+            let _ = write!(line, "{} {}", record.level().as_short_str(), record.msg());
+            let mut serializer = LineSerializer(&mut line);
+            let _ = record.kv().serialize(record, &mut serializer);
+            let _ = values.serialize(record, &mut serializer);
+            line.push('\n');
+            std::io::stderr().write_all(line.as_bytes())
+        })
+    }
+}
+
+type UpdateFn = dyn Fn(&str) -> Result<(), String> + Send + Sync;
+
+struct Knob {
+    value: String,
+    on_update: Box<UpdateFn>,
+}
+
+/// A change of a runtime knob, recorded in the audit log of the [`RuntimeKnobs`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnobChange {
+    pub name: String,
+    pub old_value: String,
+    pub new_value: String,
+    /// The node on which the change was requested.
+    pub origin: NodeId,
+    pub time: SystemTime,
+}
+
+impl fmt::Display for KnobChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {} (requested by node {})",
+            self.name, self.old_value, self.new_value, self.origin
+        )
+    }
+}
+
+/// Configuration values which can be updated while the dataflow runs, e.g. to tune a live robot
+/// without redeploying it.
+///
+/// Knobs are registered before running the [`Node`](crate::node::Node), and updated with
+/// [`NodeHandle::set_knob`](crate::node::NodeHandle::set_knob), which propagates the change to
+/// all nodes via the control plane. Operators read the current values from a clone of the
+/// [`RuntimeKnobs`], e.g. passed as an argument of their
+/// [`OperatorConfig`](crate::dataflow::OperatorConfig).
+///
+/// The [`LOG_LEVEL_KNOB`] is always registered.
+#[derive(Clone)]
+pub struct RuntimeKnobs {
+    knobs: Arc<Mutex<HashMap<String, Knob>>>,
+    audit_log: Arc<Mutex<Vec<KnobChange>>>,
+}
+
+impl RuntimeKnobs {
+    pub(crate) fn new() -> Self {
+        let knobs = Self {
+            knobs: Arc::new(Mutex::new(HashMap::new())),
+            audit_log: Arc::new(Mutex::new(Vec::new())),
+        };
+        knobs.register(LOG_LEVEL_KNOB, "trace", |value: &str| {
+            let level: Level = value
+                .parse()
+                .map_err(|_| format!("Unknown log level {}", value))?;
+            LOG_LEVEL.store(level.as_usize(), Ordering::Relaxed);
+            Ok(())
+        });
+        knobs
+    }
+
+    /// Registers a knob with its initial value.
+    ///
+    /// `on_update` validates new values and applies them if needed. Updates for which it returns
+    /// an error are rejected.
+    pub fn register<F>(&self, name: &str, initial_value: &str, on_update: F)
+    where
+        F: 'static + Fn(&str) -> Result<(), String> + Send + Sync,
+    {
+        let knob = Knob {
+            value: initial_value.to_string(),
+            on_update: Box::new(on_update),
+        };
+        if self
+            .knobs
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), knob)
+            .is_some()
+        {
+            slog::warn!(crate::TERMINAL_LOGGER, "Overwrote runtime knob {}", name);
+        }
+    }
+
+    /// Returns the current value of the knob.
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.knobs
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(|knob| knob.value.clone())
+    }
+
+    /// Returns the current value of the knob parsed as `T`.
+    pub fn get_parsed<T: std::str::FromStr>(&self, name: &str) -> Option<T> {
+        self.get(name).and_then(|value| value.parse().ok())
+    }
+
+    /// Returns all changes of the knobs, in the order in which they were applied.
+    pub fn audit_log(&self) -> Vec<KnobChange> {
+        self.audit_log.lock().unwrap().clone()
+    }
+
+    /// Validates and applies a new value of the knob, and records the change in the audit log.
+    pub(crate) fn set(&self, name: &str, value: &str, origin: NodeId) -> Result<(), String> {
+        let mut knobs = self.knobs.lock().unwrap();
+        let knob = knobs
+            .get_mut(name)
+            .ok_or_else(|| format!("Unknown runtime knob {}", name))?;
+        (knob.on_update)(value).map_err(|e| format!("Invalid value for {}: {}", name, e))?;
+        let change = KnobChange {
+            name: name.to_string(),
+            old_value: std::mem::replace(&mut knob.value, value.to_string()),
+            new_value: value.to_string(),
+            origin,
+            time: SystemTime::now(),
+        };
+        slog::info!(crate::TERMINAL_LOGGER, "Updated runtime knob {}", change);
+        self.audit_log.lock().unwrap().push(change);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that invalid values are rejected, and that valid values are recorded in the audit log.
+    #[test]
+    fn test_set_knob() {
+        let knobs = RuntimeKnobs::new();
+        knobs.register("sampling_rate", "1.0", |value: &str| {
+            match value.parse::<f64>() {
+                Ok(rate) if rate > 0.0 && rate <= 1.0 => Ok(()),
+                _ => Err("The sampling rate must be in (0, 1]".to_string()),
+            }
+        });
+
+        assert!(knobs.set("sampling_rate", "2.0", 0).is_err());
+        assert!(knobs.set("batching_interval", "10", 0).is_err());
+        assert_eq!(knobs.get_parsed::<f64>("sampling_rate"), Some(1.0));
+
+        knobs.set("sampling_rate", "0.5", 1).unwrap();
+        assert_eq!(knobs.get_parsed::<f64>("sampling_rate"), Some(0.5));
+        let audit_log = knobs.audit_log();
+        assert_eq!(audit_log.len(), 1);
+        assert_eq!(audit_log[0].old_value, "1.0");
+        assert_eq!(audit_log[0].new_value, "0.5");
+        assert_eq!(audit_log[0].origin, 1);
+    }
+}
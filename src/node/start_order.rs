@@ -0,0 +1,146 @@
+use std::collections::{HashMap, HashSet};
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    communication::ControlMessage, dataflow::graph::OperatorMetadata, node::NodeId, OperatorId,
+};
+
+/// Tells the operators of a node to run once the operators they depend on finished running.
+///
+/// Dependencies are set with
+/// [`OperatorConfig::depends_on`](crate::dataflow::OperatorConfig::depends_on), and may run on
+/// other nodes.
+pub(crate) struct StartOrder {
+    /// Channels to the local operators which have not been told to run yet.
+    channels_to_operators: HashMap<OperatorId, UnboundedSender<ControlMessage>>,
+    /// The dependencies of the local operators which have not finished running.
+    pending_dependencies: HashMap<OperatorId, HashSet<OperatorId>>,
+}
+
+impl StartOrder {
+    /// Returns an error if a dependency does not exist or the dependencies contain a cycle.
+    pub fn new(
+        operators: &[OperatorMetadata],
+        node_id: NodeId,
+        channels_to_operators: HashMap<OperatorId, UnboundedSender<ControlMessage>>,
+    ) -> Result<Self, String> {
+        let mut ids_by_name: HashMap<&str, Vec<OperatorId>> = HashMap::new();
+        for operator in operators {
+            if let Some(name) = &operator.name {
+                ids_by_name.entry(name).or_default().push(operator.id);
+            }
+        }
+        let mut dependencies = HashMap::new();
+        for operator in operators {
+            let mut operator_dependencies = HashSet::new();
+            for name in &operator.dependencies {
+                let ids = ids_by_name.get(name.as_str()).ok_or_else(|| {
+                    format!(
+                        "Operator {:?} depends on unknown operator {}",
+                        operator.id, name
+                    )
+                })?;
+                operator_dependencies.extend(ids.iter().cloned());
+            }
+            dependencies.insert(operator.id, operator_dependencies);
+        }
+        check_acyclic(&dependencies)?;
+
+        let pending_dependencies = operators
+            .iter()
+            .filter(|operator| operator.node_id == node_id)
+            .map(|operator| (operator.id, dependencies.remove(&operator.id).unwrap()))
+            .collect();
+        Ok(Self {
+            channels_to_operators,
+            pending_dependencies,
+        })
+    }
+
+    /// Adds operators without dependencies, e.g. operators added to the running dataflow.
+    pub fn add_operators(
+        &mut self,
+        channels_to_operators: HashMap<OperatorId, UnboundedSender<ControlMessage>>,
+    ) {
+        for (operator_id, tx) in channels_to_operators {
+            self.pending_dependencies
+                .insert(operator_id, HashSet::new());
+            self.channels_to_operators.insert(operator_id, tx);
+        }
+    }
+
+    /// Records that the operator finished running, and runs the operators that depend on it.
+    pub fn operator_ran(&mut self, operator_id: OperatorId) -> Result<(), String> {
+        for dependencies in self.pending_dependencies.values_mut() {
+            dependencies.remove(&operator_id);
+        }
+        self.run_ready_operators()
+    }
+
+    /// Tells the local operators whose dependencies finished running to run.
+    pub fn run_ready_operators(&mut self) -> Result<(), String> {
+        let ready: Vec<OperatorId> = self
+            .pending_dependencies
+            .iter()
+            .filter(|(_, dependencies)| dependencies.is_empty())
+            .map(|(operator_id, _)| *operator_id)
+            .collect();
+        for operator_id in ready {
+            self.pending_dependencies.remove(&operator_id);
+            if let Some(tx) = self.channels_to_operators.remove(&operator_id) {
+                tx.send(ControlMessage::RunOperator(operator_id))
+                    .map_err(|e| format!("Error telling operator to run: {}", e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns an error if the dependencies contain a cycle.
+fn check_acyclic(dependencies: &HashMap<OperatorId, HashSet<OperatorId>>) -> Result<(), String> {
+    // Repeatedly remove operators whose dependencies have all been removed.
+    let mut remaining: HashMap<_, _> = dependencies.clone();
+    while !remaining.is_empty() {
+        let removable: Vec<OperatorId> = remaining
+            .iter()
+            .filter(|(_, deps)| deps.iter().all(|dep| !remaining.contains_key(dep)))
+            .map(|(operator_id, _)| *operator_id)
+            .collect();
+        if removable.is_empty() {
+            let mut cycle: Vec<String> = remaining.keys().map(|id| id.to_string()).collect();
+            cycle.sort();
+            return Err(format!(
+                "The dependencies of operators [{}] contain a cycle",
+                cycle.join(", ")
+            ));
+        }
+        for operator_id in removable {
+            remaining.remove(&operator_id);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that operators in a dependency cycle are rejected.
+    #[test]
+    fn test_check_acyclic() {
+        let (a, b, c) = (
+            OperatorId::new_deterministic(),
+            OperatorId::new_deterministic(),
+            OperatorId::new_deterministic(),
+        );
+        let mut dependencies = HashMap::new();
+        dependencies.insert(a, HashSet::new());
+        dependencies.insert(b, vec![a].into_iter().collect());
+        dependencies.insert(c, vec![a, b].into_iter().collect());
+        assert!(check_acyclic(&dependencies).is_ok());
+
+        dependencies.insert(a, vec![c].into_iter().collect());
+        assert!(check_acyclic(&dependencies).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::node::NodeId;
+
+/// Time a [`Node`](crate::node::Node) spent in one phase of its startup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StartupPhase {
+    pub name: String,
+    pub duration: Duration,
+}
+
+/// Durations of the phases of the startup of a [`Node`](crate::node::Node), in the order in
+/// which they completed.
+///
+/// Returned by [`NodeHandle::startup_report`](crate::node::NodeHandle::startup_report).
+#[derive(Debug, Clone, PartialEq)]
+pub struct StartupReport {
+    pub node_id: NodeId,
+    pub phases: Vec<StartupPhase>,
+    /// Time from the start of the node until it finished setting up, or `None` if it is still
+    /// setting up.
+    pub total: Option<Duration>,
+}
+
+impl StartupReport {
+    /// Returns the duration of a phase, if it completed.
+    pub fn phase(&self, name: &str) -> Option<Duration> {
+        self.phases
+            .iter()
+            .find(|phase| phase.name == name)
+            .map(|phase| phase.duration)
+    }
+}
+
+impl fmt::Display for StartupReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.total {
+            Some(total) => writeln!(f, "node {} started in {:?}", self.node_id, total)?,
+            None => writeln!(f, "node {} is starting", self.node_id)?,
+        }
+        for phase in self.phases.iter() {
+            writeln!(f, "  {}: {:?}", phase.name, phase.duration)?;
+        }
+        Ok(())
+    }
+}
+
+/// Records the phases of the startup of a node, and logs them as they complete.
+#[derive(Clone)]
+pub(crate) struct StartupProfiler {
+    start: Arc<Mutex<Instant>>,
+    report: Arc<Mutex<StartupReport>>,
+    logger: slog::Logger,
+}
+
+impl StartupProfiler {
+    pub fn new(node_id: NodeId, logger: slog::Logger) -> Self {
+        Self {
+            start: Arc::new(Mutex::new(Instant::now())),
+            report: Arc::new(Mutex::new(StartupReport {
+                node_id,
+                phases: Vec::new(),
+                total: None,
+            })),
+            logger,
+        }
+    }
+
+    /// Restarts the profile when the node starts running.
+    pub fn start(&self) {
+        *self.start.lock().unwrap() = Instant::now();
+        let mut report = self.report.lock().unwrap();
+        report.phases.clear();
+        report.total = None;
+    }
+
+    /// Records that a phase which began at `phase_start` completed.
+    pub fn record(&self, name: &str, phase_start: Instant) {
+        let duration = phase_start.elapsed();
+        let mut report = self.report.lock().unwrap();
+        slog::info!(
+            self.logger,
+            "Node {}: startup phase {} took {:?}",
+            report.node_id,
+            name,
+            duration
+        );
+        report.phases.push(StartupPhase {
+            name: name.to_string(),
+            duration,
+        });
+    }
+
+    /// Records that the node finished setting up.
+    pub fn finish(&self) {
+        let total = self.start.lock().unwrap().elapsed();
+        let mut report = self.report.lock().unwrap();
+        slog::info!(
+            self.logger,
+            "Node {}: finished setting up in {:?}",
+            report.node_id,
+            total
+        );
+        report.total = Some(total);
+    }
+
+    pub fn report(&self) -> StartupReport {
+        self.report.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that phases are reported in the order in which they completed.
+    #[test]
+    fn test_startup_profiler() {
+        let profiler = StartupProfiler::new(0, crate::get_terminal_logger());
+        profiler.start();
+        let start = Instant::now();
+        profiler.record("connections", start);
+        profiler.record("operator setup", Instant::now());
+        assert_eq!(profiler.report().total, None);
+        profiler.finish();
+
+        let report = profiler.report();
+        let names: Vec<_> = report
+            .phases
+            .iter()
+            .map(|phase| phase.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["connections", "operator setup"]);
+        assert!(report.phase("connections").is_some());
+        assert!(report.phase("discovery").is_none());
+        assert!(report.total.unwrap() >= report.phase("connections").unwrap());
+    }
+}
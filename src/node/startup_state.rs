@@ -0,0 +1,233 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::{communication::ControlMessage, node::NodeId};
+
+/// The phases through which the nodes go while they set up the dataflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PeerPhase {
+    /// The control and data connections between the nodes are being established.
+    Connecting,
+    /// The connections are established, and the nodes set up their operators.
+    SettingUpOperators,
+    /// All nodes set up their operators.
+    Ready,
+}
+
+impl fmt::Display for PeerPhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PeerPhase::Connecting => write!(f, "connecting"),
+            PeerPhase::SettingUpOperators => write!(f, "setting up operators"),
+            PeerPhase::Ready => write!(f, "ready"),
+        }
+    }
+}
+
+/// The startup progress of a peer, as reported by the messages received from it.
+#[derive(Debug, Clone, Default)]
+struct PeerStartup {
+    /// Whether the node shares data channels with the peer.
+    shares_data: bool,
+    control_sender: bool,
+    control_receiver: bool,
+    data_sender: bool,
+    data_receiver: bool,
+    operators_initialized: bool,
+}
+
+impl PeerStartup {
+    /// Returns the connections to the peer which are not established.
+    fn pending_connections(&self) -> Vec<&'static str> {
+        let mut pending = Vec::new();
+        if !self.control_sender {
+            pending.push("control sender");
+        }
+        if !self.control_receiver {
+            pending.push("control receiver");
+        }
+        if self.shares_data && !self.data_sender {
+            pending.push("data sender");
+        }
+        if self.shares_data && !self.data_receiver {
+            pending.push("data receiver");
+        }
+        pending
+    }
+
+    fn phase(&self) -> PeerPhase {
+        if !self.pending_connections().is_empty() {
+            PeerPhase::Connecting
+        } else if !self.operators_initialized {
+            PeerPhase::SettingUpOperators
+        } else {
+            PeerPhase::Ready
+        }
+    }
+}
+
+/// Tracks the startup phase of each peer of a node from the control messages the node
+/// receives.
+///
+/// Messages are idempotent, so duplicate messages and messages for a phase which the peer has
+/// already completed are ignored.
+#[derive(Debug, Clone)]
+pub(crate) struct NodeStartupStateMachine {
+    node_id: NodeId,
+    peers: BTreeMap<NodeId, PeerStartup>,
+}
+
+impl NodeStartupStateMachine {
+    pub fn new(node_id: NodeId, num_nodes: usize) -> Self {
+        let peers = (0..num_nodes)
+            .filter(|&peer| peer != node_id)
+            .map(|peer| (peer, PeerStartup::default()))
+            .collect();
+        Self { node_id, peers }
+    }
+
+    /// Sets the peers with which the node shares data channels, whose data senders and receivers
+    /// must be initialized.
+    pub fn set_data_peers(&mut self, data_peers: &[NodeId]) {
+        for (peer, startup) in self.peers.iter_mut() {
+            startup.shares_data = data_peers.contains(peer);
+        }
+    }
+
+    /// Advances the state of the peer which the message is about.
+    ///
+    /// Returns the message if it does not concern the startup of a peer, and an error if a peer
+    /// failed to set up.
+    pub fn handle(&mut self, msg: ControlMessage) -> Result<Option<ControlMessage>, String> {
+        let (peer, update): (NodeId, fn(&mut PeerStartup)) = match msg {
+            ControlMessage::ControlSenderInitialized(peer) => (peer, |s| s.control_sender = true),
+            ControlMessage::ControlReceiverInitialized(peer) => {
+                (peer, |s| s.control_receiver = true)
+            }
+            ControlMessage::DataSenderInitialized(peer) => (peer, |s| s.data_sender = true),
+            ControlMessage::DataReceiverInitialized(peer) => (peer, |s| s.data_receiver = true),
+            ControlMessage::AllOperatorsInitializedOnNode(peer, _) => {
+                (peer, |s| s.operators_initialized = true)
+            }
+            ControlMessage::NodeSetupFailed(peer, reason) => {
+                return Err(format!(
+                    "Node {} failed to set up while {}: {}",
+                    peer,
+                    self.peer_phase(peer).unwrap_or(PeerPhase::Connecting),
+                    reason
+                ));
+            }
+            msg => return Ok(Some(msg)),
+        };
+        // Messages about the node itself or unknown nodes do not change the state.
+        if let Some(startup) = self.peers.get_mut(&peer) {
+            update(startup);
+        }
+        Ok(None)
+    }
+
+    /// Returns the phase of the peer, or `None` if the node is not a peer.
+    pub fn peer_phase(&self, peer: NodeId) -> Option<PeerPhase> {
+        self.peers.get(&peer).map(PeerStartup::phase)
+    }
+
+    pub fn peer_phases(&self) -> BTreeMap<NodeId, PeerPhase> {
+        self.peers
+            .iter()
+            .map(|(&peer, startup)| (peer, startup.phase()))
+            .collect()
+    }
+
+    /// Returns the earliest phase of the peers, i.e. the phase of the cluster as seen by the
+    /// node.
+    pub fn phase(&self) -> PeerPhase {
+        self.peers
+            .values()
+            .map(PeerStartup::phase)
+            .min()
+            .unwrap_or(PeerPhase::Ready)
+    }
+
+    /// Returns whether the connections to all peers are established.
+    pub fn is_connected(&self) -> bool {
+        self.phase() > PeerPhase::Connecting
+    }
+
+    /// Describes which peers the node waits for in which phase.
+    pub fn describe_pending(&self) -> String {
+        let pending: Vec<String> = self
+            .peers
+            .iter()
+            .filter_map(|(peer, startup)| match startup.phase() {
+                PeerPhase::Connecting => Some(format!(
+                    "node {} is connecting (waiting for its {})",
+                    peer,
+                    startup.pending_connections().join(", ")
+                )),
+                PeerPhase::SettingUpOperators => {
+                    Some(format!("node {} is setting up operators", peer))
+                }
+                PeerPhase::Ready => None,
+            })
+            .collect();
+        if pending.is_empty() {
+            format!("node {} waits for no peers", self.node_id)
+        } else {
+            format!("node {} waits until {}", self.node_id, pending.join("; "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Uuid;
+
+    /// Test that the phase of each peer advances with the messages received from it.
+    #[test]
+    fn test_startup_state_machine() {
+        let mut state = NodeStartupStateMachine::new(0, 3);
+        state.set_data_peers(&[1]);
+        assert_eq!(state.phase(), PeerPhase::Connecting);
+
+        for peer in 1..3 {
+            let msg = ControlMessage::ControlSenderInitialized(peer);
+            assert!(state.handle(msg).unwrap().is_none());
+            state
+                .handle(ControlMessage::ControlReceiverInitialized(peer))
+                .unwrap();
+        }
+        // Duplicate messages are ignored.
+        state
+            .handle(ControlMessage::ControlSenderInitialized(2))
+            .unwrap();
+        assert_eq!(state.peer_phase(2), Some(PeerPhase::SettingUpOperators));
+        assert_eq!(state.peer_phase(1), Some(PeerPhase::Connecting));
+        assert_eq!(
+            state.describe_pending(),
+            "node 0 waits until node 1 is connecting (waiting for its data sender, data \
+             receiver); node 2 is setting up operators"
+        );
+
+        // Messages about later phases may arrive early.
+        let initialized = ControlMessage::AllOperatorsInitializedOnNode(1, Uuid::nil());
+        state.handle(initialized).unwrap();
+        state
+            .handle(ControlMessage::DataSenderInitialized(1))
+            .unwrap();
+        state
+            .handle(ControlMessage::DataReceiverInitialized(1))
+            .unwrap();
+        assert!(state.is_connected());
+        assert_eq!(state.peer_phase(1), Some(PeerPhase::Ready));
+        assert_eq!(state.phase(), PeerPhase::SettingUpOperators);
+
+        let msg = ControlMessage::OperatorRan(crate::OperatorId::nil());
+        assert!(state.handle(msg).unwrap().is_some());
+        let failed = ControlMessage::NodeSetupFailed(2, "timed out".to_string());
+        assert_eq!(
+            state.handle(failed),
+            Err("Node 2 failed to set up while setting up operators: timed out".to_string())
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        RwLock,
+    },
+    time::Instant,
+};
+
+use lazy_static::lazy_static;
+
+use crate::{
+    communication::{to_serialized_bytes, CodecError, InterProcessMessage},
+    dataflow::stream::StreamId,
+    node::metrics::{Counter, Histogram, OperatorMetrics},
+};
+
+/// Upper bounds in seconds of the buckets of the serialization durations, which are shorter
+/// than the durations of callbacks.
+const SERIALIZATION_BUCKETS: [f64; 8] = [1e-5, 5e-5, 1e-4, 5e-4, 0.001, 0.005, 0.01, 0.1];
+
+lazy_static! {
+    /// The metrics of the streams for which the current process sends or receives messages.
+    static ref STREAMS: RwLock<HashMap<StreamId, StreamMetrics>> = RwLock::new(HashMap::new());
+}
+
+/// Whether the streams are instrumented, which avoids the lookups if no node exports metrics.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The metrics of a stream, which are exported with a label for the ID of the stream.
+#[derive(Clone)]
+struct StreamMetrics {
+    sent: Counter,
+    received: Counter,
+    serialization_durations: Histogram,
+}
+
+impl StreamMetrics {
+    fn new(stream_id: StreamId) -> Self {
+        let metrics = OperatorMetrics::with_label("stream", &stream_id.to_string());
+        Self {
+            sent: metrics.counter("erdos_stream_messages_sent_total"),
+            received: metrics.counter("erdos_stream_messages_received_total"),
+            serialization_durations: metrics.histogram_with_buckets(
+                "erdos_stream_serialization_duration_seconds",
+                &SERIALIZATION_BUCKETS,
+            ),
+        }
+    }
+}
+
+/// Instruments the streams registered from now on.
+pub(crate) fn enable() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Registers the metrics of a stream which the node sends to or receives from other nodes.
+pub(crate) fn register(stream_id: StreamId) {
+    if ENABLED.load(Ordering::Relaxed) {
+        STREAMS
+            .write()
+            .unwrap()
+            .entry(stream_id)
+            .or_insert_with(|| StreamMetrics::new(stream_id));
+    }
+}
+
+fn get(stream_id: StreamId) -> Option<StreamMetrics> {
+    if ENABLED.load(Ordering::Relaxed) {
+        STREAMS.read().unwrap().get(&stream_id).cloned()
+    } else {
+        None
+    }
+}
+
+/// Counts a message which a data sender sends to another node, and serializes it, unless it is
+/// already serialized, in order to record the time spent serializing it.
+pub(crate) fn message_sent(msg: InterProcessMessage) -> Result<InterProcessMessage, CodecError> {
+    let metrics = match get(msg.metadata().stream_id) {
+        Some(metrics) => metrics,
+        None => return Ok(msg),
+    };
+    metrics.sent.increment();
+    match msg {
+        InterProcessMessage::Deserialized { metadata, data }
+            if metadata.watermark_frame().is_none() =>
+        {
+            let start = Instant::now();
+            let bytes = to_serialized_bytes(&data.encode_into_vec()?);
+            metrics
+                .serialization_durations
+                .observe(start.elapsed().as_secs_f64());
+            Ok(InterProcessMessage::new_serialized(bytes, metadata))
+        }
+        msg => Ok(msg),
+    }
+}
+
+/// Counts a message which a data receiver received from another node.
+pub(crate) fn message_received(stream_id: StreamId) {
+    if let Some(metrics) = get(stream_id) {
+        metrics.received.increment();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::{metrics, quiescence::MessageCounters};
+
+    /// Test that the messages of the registered streams are counted with a label for the stream.
+    #[test]
+    fn test_stream_metrics() {
+        let stream_id = StreamId::new_deterministic();
+        message_received(stream_id);
+        enable();
+        register(stream_id);
+        message_received(stream_id);
+        message_received(stream_id);
+        message_received(StreamId::new_deterministic());
+
+        let text = metrics::render(&MessageCounters::default());
+        let line = format!(
+            "erdos_stream_messages_received_total{{stream=\"{}\"}} 2\n",
+            stream_id
+        );
+        assert!(text.contains(&line));
+    }
+}
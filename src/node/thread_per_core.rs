@@ -0,0 +1,186 @@
+use std::{cell::Cell, future::Future, pin::Pin, thread};
+
+use futures::FutureExt;
+use tokio::{
+    runtime::Builder,
+    sync::{mpsc, oneshot},
+};
+
+use crate::{communication::bounded_channel, node::NodeId};
+
+type Task = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+thread_local! {
+    /// Whether the current thread is a core thread of a [`CorePool`].
+    static ON_CORE: Cell<bool> = Cell::new(false);
+}
+
+/// Runs the blocking function. On the threaded runtime, the other tasks of the worker thread move
+/// to another thread in the meantime, so the function may block on full bounded channels. On a
+/// core thread, whose single-threaded runtime does not support moving its tasks, the function is
+/// called directly and the core waits for it.
+pub(crate) fn block_in_place<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    if ON_CORE.with(Cell::get) {
+        f()
+    } else {
+        tokio::task::block_in_place(|| {
+            let deferring = bounded_channel::set_deferring(false);
+            let result = f();
+            bounded_channel::set_deferring(deferring);
+            result
+        })
+    }
+}
+
+/// Threads which each run a single-threaded runtime, to which operators are assigned in turn
+/// so that an operator always runs on the same thread.
+///
+/// The pool only runs the operators. Operators on different cores send messages to each other
+/// on the channels which the [`ChannelManager`](crate::scheduler::channel_manager::ChannelManager)
+/// sets up for every executor model.
+///
+/// Dropping the pool cancels the operators which are still running.
+pub(crate) struct CorePool {
+    cores: Vec<mpsc::UnboundedSender<Task>>,
+    next_core: usize,
+    pinned: bool,
+}
+
+impl CorePool {
+    /// Creates the threads, which are pinned to the CPUs in turn if `pin` is set and pinning is
+    /// supported.
+    pub fn new(node_id: NodeId, num_cores: usize, pin: bool) -> Self {
+        let pinned = pin && cfg!(all(target_os = "linux", feature = "core_affinity"));
+        let cores = (0..num_cores.max(1))
+            .map(|core| {
+                let (tx, rx) = mpsc::unbounded_channel();
+                thread::Builder::new()
+                    .name(format!("node-{}-core-{}", node_id, core))
+                    .spawn(move || {
+                        if pinned {
+                            pin_to_cpu(core);
+                        }
+                        Self::run_core(rx)
+                    })
+                    .expect("Unable to spawn a core thread");
+                tx
+            })
+            .collect();
+        Self {
+            cores,
+            next_core: 0,
+            pinned,
+        }
+    }
+
+    pub fn num_cores(&self) -> usize {
+        self.cores.len()
+    }
+
+    /// Returns whether the threads are pinned to CPUs.
+    pub fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+
+    /// Returns the next core in turn.
+    pub fn next_core(&mut self) -> usize {
+        let core = self.next_core;
+        self.next_core = (self.next_core + 1) % self.cores.len();
+        core
+    }
+
+    fn run_core(mut rx: mpsc::UnboundedReceiver<Task>) {
+        ON_CORE.with(|on_core| on_core.set(true));
+        // Tasks on the core must not block it while waiting for the channels to other tasks.
+        bounded_channel::set_deferring(true);
+        let mut runtime = Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()
+            .unwrap();
+        // The tasks only run while the runtime is blocked on the receiver.
+        runtime.block_on(async move {
+            while let Some(task) = rx.recv().await {
+                tokio::spawn(task);
+            }
+        });
+    }
+
+    /// Runs the task on the core, and returns a future which completes with the task.
+    pub fn spawn_on<F>(&self, core: usize, task: F) -> impl Future<Output = ()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let (done_tx, done_rx) = oneshot::channel();
+        let core = core % self.cores.len();
+        let task = async move {
+            task.await;
+            let _ = done_tx.send(());
+        };
+        // The task is dropped if the core stopped, which completes the returned future.
+        let _ = self.cores[core].send(Box::pin(task));
+        done_rx.map(|_| ())
+    }
+}
+
+/// Pins the current thread to a CPU, wrapping around if there are more cores than CPUs.
+#[cfg(all(target_os = "linux", feature = "core_affinity"))]
+fn pin_to_cpu(core: usize) {
+    unsafe {
+        let num_cpus = libc::sysconf(libc::_SC_NPROCESSORS_ONLN).max(1) as usize;
+        let mut cpu_set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core % num_cpus, &mut cpu_set);
+        // The thread keeps running unpinned if the CPU is unavailable, e.g. in a container.
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &cpu_set);
+    }
+}
+
+#[cfg(not(all(target_os = "linux", feature = "core_affinity")))]
+fn pin_to_cpu(_core: usize) {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Test that blocking functions run on the cores.
+    #[test]
+    fn test_block_in_place_on_core() {
+        let pool = CorePool::new(0, 1, false);
+        let result = Arc::new(Mutex::new(None));
+        let result_copy = Arc::clone(&result);
+        let task = pool.spawn_on(0, async move {
+            *result_copy.lock().unwrap() = Some(block_in_place(|| 42));
+        });
+        futures::executor::block_on(task);
+        assert_eq!(*result.lock().unwrap(), Some(42));
+    }
+
+    /// Test that tasks are assigned to the cores in turn.
+    #[test]
+    fn test_core_pool() {
+        let mut pool = CorePool::new(0, 2, false);
+        let threads = Arc::new(Mutex::new(Vec::new()));
+        let tasks: Vec<_> = (0..3)
+            .map(|_| {
+                let threads = Arc::clone(&threads);
+                let core = pool.next_core();
+                pool.spawn_on(core, async move {
+                    let name = thread::current().name().map(String::from);
+                    threads.lock().unwrap().push(name.unwrap());
+                })
+            })
+            .collect();
+        futures::executor::block_on(futures::future::join_all(tasks));
+        let mut threads = threads.lock().unwrap().clone();
+        threads.sort();
+        assert_eq!(
+            threads,
+            vec!["node-0-core-0", "node-0-core-0", "node-0-core-1"]
+        );
+    }
+}
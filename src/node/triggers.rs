@@ -0,0 +1,437 @@
+use std::{
+    any::Any,
+    fmt,
+    io::{self, Write},
+    net::TcpStream,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use slog::Level;
+
+use crate::{
+    dataflow::{graph::json_string, stream::StreamId, Data, Message},
+    node::{metrics, NodeContext, NodeId, RuntimeKnobs, LOG_LEVEL_KNOB},
+};
+
+/// Interval at which the conditions of the triggers are checked.
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time after which sending a webhook request fails.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+type MessagePredicate = dyn Fn(&dyn Any) -> bool + Send + Sync;
+type MetricPredicate = dyn Fn(f64) -> bool + Send + Sync;
+type TriggerCallback = dyn Fn(&TriggerEvent) + Send + Sync;
+
+#[derive(Clone)]
+enum Condition {
+    /// Holds from a message which matches the predicate until a message which does not.
+    Message {
+        stream_id: StreamId,
+        predicate: Arc<MessagePredicate>,
+    },
+    /// Holds from the last message sent on the stream.
+    Silence { stream_id: StreamId },
+    /// Holds while the value of the metric matches the predicate.
+    Metric {
+        name: String,
+        predicate: Arc<MetricPredicate>,
+    },
+}
+
+/// What a [`Trigger`] does when it fires.
+#[derive(Clone)]
+pub enum TriggerAction {
+    /// Captures the messages sent around the current time on the streams captured with
+    /// [`CapturePolicy::AroundTrigger`](crate::node::CapturePolicy::AroundTrigger).
+    Capture,
+    /// Logs the [`TriggerEvent`] as an error.
+    Alert,
+    /// Posts the [`TriggerEvent`] as JSON to the `http://` URL, e.g. to page the person on call.
+    Webhook(String),
+    /// Sets the minimum level of the messages logged by ERDOS on the current node, e.g. to debug
+    /// the incident which fired the trigger.
+    SetLogLevel(Level),
+    /// Calls the function with the [`TriggerEvent`].
+    Callback(Arc<TriggerCallback>),
+}
+
+impl fmt::Debug for TriggerAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TriggerAction::Capture => write!(f, "Capture"),
+            TriggerAction::Alert => write!(f, "Alert"),
+            TriggerAction::Webhook(url) => write!(f, "Webhook({:?})", url),
+            TriggerAction::SetLogLevel(level) => write!(f, "SetLogLevel({:?})", level),
+            TriggerAction::Callback(_) => write!(f, "Callback"),
+        }
+    }
+}
+
+/// A firing of a [`Trigger`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriggerEvent {
+    /// The name of the trigger.
+    pub trigger: String,
+    /// The node on which the trigger fired.
+    pub node_id: NodeId,
+    pub time: SystemTime,
+}
+
+impl TriggerEvent {
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"trigger\":{},\"node_id\":{},\"time_micros\":{}}}",
+            json_string(&self.trigger),
+            self.node_id,
+            self.time
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_micros()
+        )
+    }
+}
+
+/// A predicate over the messages of a stream or a metric which fires actions once it has held
+/// for a duration, registered with [`Node::add_trigger`](crate::node::Node::add_trigger), e.g.
+/// to capture the inputs of a detector which has not detected anything for a second.
+///
+/// A trigger fires once when its condition has held for the duration, and fires again once the
+/// condition stopped holding and held again. Message predicates are evaluated on the nodes which
+/// send the stream, and metrics are the metrics registered by the operators of the node.
+#[derive(Clone)]
+pub struct Trigger {
+    name: String,
+    condition: Condition,
+    duration: Duration,
+    actions: Vec<TriggerAction>,
+}
+
+impl Trigger {
+    /// Fires when a message sent on the stream matches the predicate, until a message does not.
+    pub fn on_message<D, F>(name: &str, stream_id: StreamId, predicate: F) -> Self
+    where
+        D: Data,
+        F: 'static + Fn(&Message<D>) -> bool + Send + Sync,
+    {
+        let predicate =
+            move |msg: &dyn Any| msg.downcast_ref::<Message<D>>().map_or(false, &predicate);
+        Self::new(
+            name,
+            Condition::Message {
+                stream_id,
+                predicate: Arc::new(predicate),
+            },
+            Duration::from_secs(0),
+        )
+    }
+
+    /// Fires when no message was sent on the stream for the duration.
+    pub fn on_silence(name: &str, stream_id: StreamId, duration: Duration) -> Self {
+        Self::new(name, Condition::Silence { stream_id }, duration)
+    }
+
+    /// Fires when the sum of the values of the metric registered by the operators matches the
+    /// predicate. The value of a histogram is its number of observations.
+    pub fn on_metric<F>(name: &str, metric: &str, predicate: F) -> Self
+    where
+        F: 'static + Fn(f64) -> bool + Send + Sync,
+    {
+        Self::new(
+            name,
+            Condition::Metric {
+                name: metric.to_string(),
+                predicate: Arc::new(predicate),
+            },
+            Duration::from_secs(0),
+        )
+    }
+
+    fn new(name: &str, condition: Condition, duration: Duration) -> Self {
+        Self {
+            name: name.to_string(),
+            condition,
+            duration,
+            actions: Vec::new(),
+        }
+    }
+
+    /// Fires only once the condition has held for the duration, e.g. once the detector sent
+    /// empty detections for 1 second.
+    pub fn sustained(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Adds an action run when the trigger fires.
+    pub fn action(mut self, action: TriggerAction) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+struct TriggerState {
+    trigger: Trigger,
+    /// Time since which the condition holds.
+    holds_since: Option<Instant>,
+    /// Whether the trigger fired since the condition started holding.
+    fired: bool,
+}
+
+impl TriggerState {
+    fn new(trigger: Trigger, now: Instant) -> Self {
+        let holds_since = match trigger.condition {
+            Condition::Silence { .. } => Some(now),
+            _ => None,
+        };
+        Self {
+            trigger,
+            holds_since,
+            fired: false,
+        }
+    }
+
+    fn set_holds(&mut self, holds: bool, now: Instant) {
+        if !holds {
+            self.holds_since = None;
+            self.fired = false;
+        } else if self.holds_since.is_none() {
+            self.holds_since = Some(now);
+        }
+    }
+
+    fn message_sent(&mut self, stream_id: StreamId, msg: &dyn Any, now: Instant) {
+        match &self.trigger.condition {
+            Condition::Message {
+                stream_id: id,
+                predicate,
+            } if *id == stream_id => {
+                let holds = predicate(msg);
+                self.set_holds(holds, now);
+            }
+            Condition::Silence { stream_id: id } if *id == stream_id => {
+                // The silence starts again.
+                self.holds_since = Some(now);
+                self.fired = false;
+            }
+            _ => (),
+        }
+    }
+
+    /// Returns whether the trigger fires.
+    fn check<F: Fn(&str) -> Option<f64>>(&mut self, now: Instant, metric_value: F) -> bool {
+        if let Condition::Metric { name, predicate } = &self.trigger.condition {
+            let holds = metric_value(name).map_or(false, |value| predicate(value));
+            self.set_holds(holds, now);
+        }
+        match self.holds_since {
+            Some(since) if !self.fired && now.duration_since(since) >= self.trigger.duration => {
+                self.fired = true;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The triggers registered on a node.
+#[derive(Default)]
+pub(crate) struct Triggers {
+    triggers: Mutex<Vec<TriggerState>>,
+    /// Avoids locking the triggers for every message if no trigger is registered.
+    watching: AtomicBool,
+}
+
+impl Triggers {
+    /// Registers a trigger on the node.
+    pub fn add(&self, trigger: Trigger) {
+        self.triggers
+            .lock()
+            .unwrap()
+            .push(TriggerState::new(trigger, Instant::now()));
+        self.watching.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_watching(&self) -> bool {
+        self.watching.load(Ordering::Relaxed)
+    }
+
+    /// Evaluates the triggers on the message sent on the stream.
+    pub fn message_sent<D: Data>(&self, stream_id: StreamId, msg: &Message<D>) {
+        let now = Instant::now();
+        for state in self.triggers.lock().unwrap().iter_mut() {
+            state.message_sent(stream_id, msg, now);
+        }
+    }
+}
+
+/// Checks the conditions of the triggers of the node periodically, and runs the actions of the
+/// triggers which fire. Never returns.
+pub(crate) async fn run(context: Arc<NodeContext>, knobs: RuntimeKnobs, node_id: NodeId) {
+    loop {
+        tokio::time::delay_for(CHECK_INTERVAL).await;
+        if !context.triggers.is_watching() {
+            continue;
+        }
+        let now = Instant::now();
+        let fired: Vec<Trigger> = context
+            .triggers
+            .triggers
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .filter_map(|state| {
+                if state.check(now, metrics::value) {
+                    Some(state.trigger.clone())
+                } else {
+                    None
+                }
+            })
+            .collect();
+        for trigger in fired {
+            let event = TriggerEvent {
+                trigger: trigger.name.clone(),
+                node_id,
+                time: SystemTime::now(),
+            };
+            for action in trigger.actions.iter() {
+                run_action(action, &event, &context, &knobs);
+            }
+        }
+    }
+}
+
+fn run_action(
+    action: &TriggerAction,
+    event: &TriggerEvent,
+    context: &NodeContext,
+    knobs: &RuntimeKnobs,
+) {
+    match action {
+        TriggerAction::Capture => context.captures.trigger(),
+        TriggerAction::Alert => slog::error!(
+            crate::TERMINAL_LOGGER,
+            "Node {}: trigger {} fired",
+            event.node_id,
+            event.trigger
+        ),
+        TriggerAction::Webhook(url) => {
+            let (url, body) = (url.clone(), event.to_json());
+            // Avoid blocking the node on slow webhooks.
+            thread::spawn(move || {
+                if let Err(e) = post(&url, &body) {
+                    slog::warn!(
+                        crate::TERMINAL_LOGGER,
+                        "Unable to post the trigger event to {}: {}",
+                        url,
+                        e
+                    );
+                }
+            });
+        }
+        TriggerAction::SetLogLevel(level) => {
+            let level = level.as_str().to_lowercase();
+            if let Err(e) = knobs.set(LOG_LEVEL_KNOB, &level, event.node_id) {
+                slog::warn!(crate::TERMINAL_LOGGER, "Trigger {}: {}", event.trigger, e);
+            }
+        }
+        TriggerAction::Callback(callback) => callback(event),
+    }
+}
+
+/// Posts the JSON body to the `http://host[:port][/path]` URL.
+fn post(url: &str, body: &str) -> io::Result<()> {
+    let invalid_url = || io::Error::new(io::ErrorKind::InvalidInput, "expected an http:// URL");
+    let rest = url.strip_prefix("http://").ok_or_else(invalid_url)?;
+    let (host, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, "/"),
+    };
+    if host.is_empty() {
+        return Err(invalid_url());
+    }
+    let address = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_write_timeout(Some(WEBHOOK_TIMEOUT))?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        path,
+        host,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataflow::Timestamp;
+
+    /// Test that triggers fire once their condition has held for the duration, and fire again
+    /// once the condition held again.
+    #[test]
+    fn test_trigger_conditions() {
+        let stream_id = StreamId::new_v4();
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let no_metric = |_: &str| None;
+        let detections = |count: usize| Message::new_message(Timestamp::new(vec![0]), count);
+
+        let trigger = Trigger::on_message("no-detections", stream_id, |msg: &Message<usize>| {
+            msg.data() == Some(&0)
+        })
+        .sustained(Duration::from_secs(1));
+        let mut state = TriggerState::new(trigger, start);
+        state.message_sent(stream_id, &detections(0), at(0));
+        assert!(!state.check(at(500), no_metric));
+        assert!(state.check(at(1000), no_metric));
+        assert!(!state.check(at(2000), no_metric));
+        state.message_sent(stream_id, &detections(3), at(2100));
+        state.message_sent(stream_id, &detections(0), at(2200));
+        assert!(!state.check(at(3000), no_metric));
+        assert!(state.check(at(3200), no_metric));
+
+        let mut silence = TriggerState::new(
+            Trigger::on_silence("silence", stream_id, Duration::from_secs(1)),
+            start,
+        );
+        silence.message_sent(stream_id, &detections(1), at(500));
+        assert!(!silence.check(at(1000), no_metric));
+        assert!(silence.check(at(1500), no_metric));
+
+        let mut metric = TriggerState::new(
+            Trigger::on_metric("queue", "queue_size", |size| size > 10.0),
+            start,
+        );
+        assert!(!metric.check(at(0), |_| Some(5.0)));
+        assert!(metric.check(at(100), |_| Some(20.0)));
+        assert!(!metric.check(at(200), |_| Some(20.0)));
+        assert!(!metric.check(at(300), |_| None));
+        assert!(metric.check(at(400), |_| Some(20.0)));
+
+        let event = TriggerEvent {
+            trigger: "queue".to_string(),
+            node_id: 1,
+            time: UNIX_EPOCH + Duration::from_micros(42),
+        };
+        assert_eq!(
+            event.to_json(),
+            "{\"trigger\":\"queue\",\"node_id\":1,\"time_micros\":42}"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    env, fs,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+
+use crate::{node::NodeHealth, Configuration};
+
+/// Signals process supervisors that the node is alive.
+///
+/// The watchdog periodically notifies systemd (`WATCHDOG=1`) if the node runs as a systemd
+/// service, and updates the liveness file if one is configured. It skips the notifications while
+/// the liveness check of the [`NodeHealth`] fails, e.g. while a callback is stalled, and stops
+/// once an operator fails the dataflow, so the supervisor can restart the node.
+pub(crate) struct Watchdog {
+    interval: Duration,
+    health: NodeHealth,
+    liveness_file: Option<PathBuf>,
+    /// Path of the systemd notification socket, set via `NOTIFY_SOCKET`.
+    notify_socket: Option<String>,
+}
+
+impl Watchdog {
+    /// Returns `None` if neither systemd nor a liveness file need to be notified.
+    pub fn new(config: &Configuration, health: NodeHealth) -> Option<Self> {
+        let liveness_file = config.liveness_file.as_ref().map(PathBuf::from);
+        let notify_socket = env::var("NOTIFY_SOCKET").ok().filter(|socket_path| {
+            // Abstract socket addresses start with '@', which the standard library cannot bind.
+            let is_abstract = socket_path.starts_with('@');
+            if is_abstract {
+                slog::warn!(
+                    crate::TERMINAL_LOGGER,
+                    "Unable to notify systemd: abstract socket {} is not supported",
+                    socket_path
+                );
+            }
+            !is_abstract
+        });
+        if liveness_file.is_none() && notify_socket.is_none() {
+            return None;
+        }
+        // systemd recommends notifying at half of the watchdog timeout.
+        let systemd_interval = env::var("WATCHDOG_USEC")
+            .ok()
+            .and_then(|usec| usec.parse().ok())
+            .map(|usec: u64| Duration::from_micros(usec / 2));
+        let interval = match systemd_interval {
+            Some(systemd_interval) => std::cmp::min(config.watchdog_interval, systemd_interval),
+            None => config.watchdog_interval,
+        };
+        Some(Self {
+            interval,
+            health,
+            liveness_file,
+            notify_socket,
+        })
+    }
+
+    /// Notifies the supervisors periodically while the node is live. Never returns.
+    pub async fn run(self) {
+        self.sd_notify("READY=1");
+        loop {
+            match self.health.check_liveness() {
+                Ok(()) => {
+                    self.sd_notify("WATCHDOG=1");
+                    self.touch_liveness_file();
+                }
+                Err(reason) => slog::warn!(
+                    crate::TERMINAL_LOGGER,
+                    "Skipping the watchdog notification: {}",
+                    reason
+                ),
+            }
+            tokio::time::delay_for(self.interval).await;
+        }
+    }
+
+    fn touch_liveness_file(&self) {
+        if let Some(path) = &self.liveness_file {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            if let Err(e) = fs::write(path, now.to_string()) {
+                slog::warn!(
+                    crate::TERMINAL_LOGGER,
+                    "Unable to update liveness file {:?}: {}",
+                    path,
+                    e
+                );
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn sd_notify(&self, state: &str) {
+        if let Some(socket_path) = &self.notify_socket {
+            let result = UnixDatagram::unbound()
+                .and_then(|socket| socket.send_to(state.as_bytes(), socket_path));
+            if let Err(e) = result {
+                slog::warn!(crate::TERMINAL_LOGGER, "Unable to notify systemd: {}", e);
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn sd_notify(&self, _state: &str) {}
+}
@@ -0,0 +1,226 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::dataflow::{stream::StreamId, Timestamp};
+
+#[derive(Default)]
+struct ProgressState {
+    watermark: Option<Timestamp>,
+    /// Whether the watermark advanced on the current node since it was last reported to the
+    /// other nodes.
+    unreported: bool,
+}
+
+/// The highest watermark of a watched stream.
+#[derive(Default)]
+struct Progress {
+    state: Mutex<ProgressState>,
+    advanced: Condvar,
+}
+
+impl Progress {
+    /// Sets the watermark if it is higher than the current watermark.
+    fn advance(&self, watermark: &Timestamp, local: bool) {
+        let mut state = self.state.lock().unwrap();
+        if state.watermark.as_ref().map_or(true, |w| w < watermark) {
+            state.watermark = Some(watermark.clone());
+            state.unreported |= local;
+            self.advanced.notify_all();
+        }
+    }
+}
+
+/// Observes the watermarks of a stream from the driver, without reading its messages, e.g. to
+/// show the progress of a dataflow or to wait until it processed an input.
+///
+/// Created by [`Node::watch_watermarks`](crate::node::Node::watch_watermarks). The nodes
+/// periodically send the watermarks of the watched streams to the other nodes, so that the
+/// watcher observes the stream on any node. Watermarks sent in quick succession are coalesced,
+/// so the watcher may skip watermarks.
+#[derive(Clone)]
+pub struct WatermarkWatcher {
+    stream_id: StreamId,
+    progress: Arc<Progress>,
+    /// Last watermark returned by [`WatermarkWatcher::next_watermark`].
+    last_seen: Option<Timestamp>,
+}
+
+impl WatermarkWatcher {
+    pub fn stream_id(&self) -> StreamId {
+        self.stream_id
+    }
+
+    /// Returns the highest watermark sent on the stream, if any.
+    pub fn watermark(&self) -> Option<Timestamp> {
+        self.progress.state.lock().unwrap().watermark.clone()
+    }
+
+    /// Waits until the watermark of the stream reaches the timestamp. Returns `false` if it does
+    /// not within the timeout.
+    pub fn wait_for(&self, timestamp: &Timestamp, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.progress.state.lock().unwrap();
+        while state.watermark.as_ref().map_or(true, |w| w < timestamp) {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            state = self
+                .progress
+                .advanced
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+        true
+    }
+
+    /// Waits until the watermark of the stream is higher than the watermark last returned, and
+    /// returns it. Returns `None` if it does not advance within the timeout.
+    pub fn next_watermark(&mut self, timeout: Duration) -> Option<Timestamp> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.progress.state.lock().unwrap();
+        loop {
+            if let Some(watermark) = &state.watermark {
+                if self
+                    .last_seen
+                    .as_ref()
+                    .map_or(true, |seen| seen < watermark)
+                {
+                    self.last_seen = Some(watermark.clone());
+                    return self.last_seen.clone();
+                }
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            state = self
+                .progress
+                .advanced
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+}
+
+/// The progress of the streams watched on a node.
+#[derive(Default)]
+pub(crate) struct WatermarkProgress {
+    streams: RwLock<HashMap<StreamId, Arc<Progress>>>,
+    /// Avoids locking the watched streams for every watermark if no stream is watched.
+    watching: AtomicBool,
+}
+
+impl WatermarkProgress {
+    /// Watches the watermarks of the stream on the node.
+    pub fn watch(&self, stream_id: StreamId) -> WatermarkWatcher {
+        let progress = Arc::clone(
+            self.streams
+                .write()
+                .unwrap()
+                .entry(stream_id)
+                .or_insert_with(Default::default),
+        );
+        self.watching.store(true, Ordering::SeqCst);
+        WatermarkWatcher {
+            stream_id,
+            progress,
+            last_seen: None,
+        }
+    }
+
+    pub fn is_watching(&self) -> bool {
+        self.watching.load(Ordering::Relaxed)
+    }
+
+    fn get(&self, stream_id: StreamId) -> Option<Arc<Progress>> {
+        self.streams.read().unwrap().get(&stream_id).cloned()
+    }
+
+    /// Records a watermark sent on the stream by an operator of the node.
+    pub fn watermark_sent(&self, stream_id: StreamId, watermark: &Timestamp) {
+        if let Some(progress) = self.get(stream_id) {
+            progress.advance(watermark, true);
+        }
+    }
+
+    /// Returns the watermarks which advanced on the node since the last call, at most one per
+    /// stream.
+    pub fn take_unreported(&self) -> Vec<(StreamId, Timestamp)> {
+        let streams = self.streams.read().unwrap();
+        let mut updates = Vec::new();
+        for (&stream_id, progress) in streams.iter() {
+            let mut state = progress.state.lock().unwrap();
+            if state.unreported {
+                state.unreported = false;
+                if let Some(watermark) = &state.watermark {
+                    updates.push((stream_id, watermark.clone()));
+                }
+            }
+        }
+        updates
+    }
+
+    /// Records the watermarks reported by another node.
+    pub fn apply_reported(&self, updates: Vec<(StreamId, Timestamp)>) {
+        for (stream_id, watermark) in updates {
+            if let Some(progress) = self.get(stream_id) {
+                progress.advance(&watermark, false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    /// Test that the watchers observe the highest watermark, and that the watermarks are reported
+    /// once, coalesced per stream.
+    #[test]
+    fn test_watermark_watcher() {
+        let stream_id = StreamId::new_deterministic();
+        let progress = WatermarkProgress::default();
+        let mut watcher = progress.watch(stream_id);
+        assert_eq!(watcher.watermark(), None);
+        assert_eq!(watcher.next_watermark(Duration::from_millis(1)), None);
+
+        progress.watermark_sent(stream_id, &Timestamp::new(vec![1]));
+        progress.watermark_sent(stream_id, &Timestamp::new(vec![3]));
+        progress.watermark_sent(stream_id, &Timestamp::new(vec![2]));
+        assert_eq!(watcher.watermark(), Some(Timestamp::new(vec![3])));
+        assert_eq!(
+            watcher.next_watermark(Duration::from_millis(1)),
+            Some(Timestamp::new(vec![3]))
+        );
+        assert_eq!(watcher.next_watermark(Duration::from_millis(1)), None);
+        assert_eq!(
+            progress.take_unreported(),
+            vec![(stream_id, Timestamp::new(vec![3]))]
+        );
+        assert!(progress.take_unreported().is_empty());
+        // Nodes which run in the same process watch their streams separately.
+        let other_node_watcher = WatermarkProgress::default().watch(stream_id);
+        assert_eq!(other_node_watcher.watermark(), None);
+
+        let waiting = watcher.clone();
+        let handle = thread::spawn(move || {
+            waiting.wait_for(&Timestamp::new(vec![5]), Duration::from_secs(10))
+        });
+        progress.apply_reported(vec![(stream_id, Timestamp::new(vec![5]))]);
+        assert!(handle.join().unwrap());
+        assert!(!watcher.wait_for(&Timestamp::new(vec![6]), Duration::from_millis(1)));
+        // Reported watermarks are not reported again.
+        assert!(progress.take_unreported().is_empty());
+    }
+}
@@ -8,7 +8,7 @@ use crate::{
     communication::ControlMessage,
     dataflow::{
         graph::default_graph,
-        stream::{InternalReadStream, WriteStreamT},
+        stream::{InternalReadStream, StreamId, WriteStreamT},
         Message, Operator, OperatorConfig, ReadStream, WriteStream,
     },
     node::{
@@ -16,7 +16,7 @@ use crate::{
         Node, NodeHandle, NodeId,
     },
     scheduler::channel_manager::ChannelManager,
-    Configuration, Uuid,
+    Configuration,
 };
 
 // Private submodules
@@ -102,14 +102,14 @@ fn internal(_py: Python, m: &PyModule) -> PyResult<()> {
         }
 
         // Get the IDs of the read streams.
-        let read_stream_ids: Vec<Uuid> = connect_read_streams
+        let read_stream_ids: Vec<StreamId> = connect_read_streams
             .iter()
             .map(|rs| rs.read_stream.get_id())
             .collect();
         let read_stream_ids_clone = read_stream_ids.clone();
 
         // Get the IDs of the write streams.
-        let write_stream_ids: Vec<Uuid> = connect_write_streams
+        let write_stream_ids: Vec<StreamId> = connect_write_streams
             .iter()
             .map(|ws| ws.write_stream.get_id())
             .collect();
@@ -143,12 +143,13 @@ fn internal(_py: Python, m: &PyModule) -> PyResult<()> {
                 let py_write_streams: Vec<PyWriteStream> = write_stream_ids_clone
                     .iter()
                     .map(|&id| {
-                        let send_endpoints = channel_manager
-                            .lock()
-                            .unwrap()
-                            .get_send_endpoints(id)
-                            .unwrap();
-                        PyWriteStream::from(WriteStream::from_endpoints(send_endpoints, id))
+                        PyWriteStream::from(
+                            channel_manager
+                                .lock()
+                                .unwrap()
+                                .get_write_stream(id)
+                                .unwrap(),
+                        )
                     })
                     .collect();
                 slog::debug!(
@@ -304,7 +305,9 @@ operator.__init__(*read_streams, *write_streams, *args, **kwargs)
                     },
                     config,
                     op_ex_streams,
+                    control_sender,
                     control_receiver,
+                    None,
                 )
             };
 
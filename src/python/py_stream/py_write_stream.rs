@@ -38,6 +38,10 @@ impl PyWriteStream {
                 WriteStreamError::TimestampError => TimestampError::py_err(error_str),
                 WriteStreamError::Closed => ClosedError::py_err(error_str),
                 WriteStreamError::IOError => IOError::py_err(error_str),
+                WriteStreamError::MessageTooLarge { size, limit } => IOError::py_err(format!(
+                    "{}: message of {} bytes exceeds the limit of {} bytes",
+                    error_str, size, limit
+                )),
                 WriteStreamError::SerializationError => SerializationError::py_err(error_str),
             }
         })
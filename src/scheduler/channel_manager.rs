@@ -4,13 +4,16 @@ use std::{any::Any, collections::HashMap, sync::Arc};
 use tokio::sync::{mpsc, Mutex};
 
 use crate::{
-    communication::{Pusher, PusherT, RecvEndpoint, SendEndpoint},
+    communication::{
+        bounded_channel, ChannelBound, ConflationKey, Pusher, PusherT, RecvEndpoint, SendEndpoint,
+    },
     dataflow::{
+        buffer::BufferAllocator,
         graph::{Channel, Graph, Vertex},
-        stream::StreamId,
+        stream::{endpoint_lists::EndpointLists, StreamId, WriteStream},
         Data, Message,
     },
-    node::NodeId,
+    node::{run_id::RunId, stream_metrics, NodeContext, NodeId},
     scheduler::endpoints_manager::{ChannelsToReceivers, ChannelsToSenders},
 };
 
@@ -20,23 +23,42 @@ pub trait StreamEndpointsT: Send {
 
     /// Creates a new inter-thread channel for the stream.
     ///
-    /// It creates a `mpsc::Channel` and adds the sender and receiver to the
-    /// corresponding endpoints.
-    fn add_inter_thread_channel(&mut self);
+    /// It creates a `mpsc::Channel`, or a bounded channel if the stream is bounded, and adds the
+    /// sender and receiver to the corresponding endpoints, which count their messages on the
+    /// `context` of the node.
+    fn add_inter_thread_channel(&mut self, bound: Option<ChannelBound>, context: &Arc<NodeContext>);
 
     /// Adds a `SendEndpoint` to the other node.
     ///
     /// Assumes that `channels_to_senders` already stores a `mpsc::Sender` to the
-    /// network sender to the other node.
+    /// network sender to the other node. Messages larger than `max_message_size`
+    /// are rejected by the endpoint, and messages are stamped with the `run_id`. If the stream
+    /// is conflated, `conflation_key` is the [`ConflationKey`] of its messages.
     async fn add_inter_node_send_endpoint(
         &mut self,
         other_node_id: NodeId,
         channels_to_senders: Arc<Mutex<ChannelsToSenders>>,
+        max_message_size: Option<usize>,
+        run_id: RunId,
+        conflation_key: Option<Arc<dyn Any + Send + Sync>>,
+        context: &Arc<NodeContext>,
     ) -> Result<(), String>;
+    /// Adds a `RecvEndpoint` fed by the pusher of the stream, whose messages' buffers are
+    /// allocated by `allocator` if set. If the stream is bounded, the endpoint is fed by a
+    /// bounded channel which returns credits to `source_node_id`.
     fn add_inter_node_recv_endpoint(
         &mut self,
         receiver_pushers: &mut HashMap<StreamId, Box<dyn PusherT>>,
+        allocator: Option<Arc<dyn BufferAllocator>>,
+        bound: Option<ChannelBound>,
+        source_node_id: NodeId,
+        context: &Arc<NodeContext>,
     ) -> Result<(), String>;
+
+    /// Adds the send endpoints of the stream to the endpoints of its running
+    /// [`WriteStream`](crate::dataflow::WriteStream), which loads them before it sends its next
+    /// message.
+    fn forward_send_endpoints(&mut self, endpoint_lists: &mut EndpointLists) -> Result<(), String>;
 }
 
 pub struct StreamEndpoints<D>
@@ -96,20 +118,54 @@ where
         self
     }
 
-    fn add_inter_thread_channel(&mut self) {
+    fn add_inter_thread_channel(
+        &mut self,
+        bound: Option<ChannelBound>,
+        context: &Arc<NodeContext>,
+    ) {
+        if let Some(bound) = bound {
+            let (tx, rx) = bounded_channel::channel(bound);
+            self.add_send_endpoint(SendEndpoint::Bounded(tx, Arc::clone(context)));
+            self.add_recv_endpoint(RecvEndpoint::Bounded(rx, Arc::clone(context)));
+            return;
+        }
         let (tx, rx) = mpsc::unbounded_channel();
-        self.add_send_endpoint(SendEndpoint::InterThread(tx));
-        self.add_recv_endpoint(RecvEndpoint::InterThread(rx));
+        self.add_send_endpoint(SendEndpoint::InterThread(tx, Arc::clone(context)));
+        self.add_recv_endpoint(RecvEndpoint::InterThread(rx, Arc::clone(context)));
     }
 
     async fn add_inter_node_send_endpoint(
         &mut self,
         other_node_id: NodeId,
         channels_to_senders: Arc<Mutex<ChannelsToSenders>>,
+        max_message_size: Option<usize>,
+        run_id: RunId,
+        conflation_key: Option<Arc<dyn Any + Send + Sync>>,
+        context: &Arc<NodeContext>,
     ) -> Result<(), String> {
+        let conflation_key = match conflation_key {
+            Some(key) => Some(
+                key.downcast_ref::<ConflationKey<Arc<Message<D>>>>()
+                    .cloned()
+                    .ok_or_else(|| {
+                        format!(
+                            "The conflation key of stream {} does not match the type of its data",
+                            self.stream_id
+                        )
+                    })?,
+            ),
+            None => None,
+        };
         let channels_to_senders = channels_to_senders.lock().await;
         if let Some(tx) = channels_to_senders.clone_channel(other_node_id) {
-            self.add_send_endpoint(SendEndpoint::InterProcess(self.stream_id, tx));
+            self.add_send_endpoint(SendEndpoint::InterProcess(
+                self.stream_id,
+                tx,
+                max_message_size,
+                run_id,
+                conflation_key,
+                Arc::clone(context),
+            ));
             Ok(())
         } else {
             Err(format!("Unable to clone channel to node {}", other_node_id))
@@ -119,14 +175,35 @@ where
     fn add_inter_node_recv_endpoint(
         &mut self,
         receiver_pushers: &mut HashMap<StreamId, Box<dyn PusherT>>,
+        allocator: Option<Arc<dyn BufferAllocator>>,
+        bound: Option<ChannelBound>,
+        source_node_id: NodeId,
+        context: &Arc<NodeContext>,
     ) -> Result<(), String> {
         let pusher: &mut Box<dyn PusherT> = receiver_pushers
             .entry(self.stream_id)
             .or_insert_with(|| Box::new(Pusher::<Arc<Message<D>>>::new()));
         if let Some(pusher) = pusher.as_any().downcast_mut::<Pusher<Arc<Message<D>>>>() {
-            let (tx, rx) = mpsc::unbounded_channel();
-            pusher.add_endpoint(SendEndpoint::InterThread(tx));
-            self.add_recv_endpoint(RecvEndpoint::InterThread(rx));
+            if let Some(allocator) = allocator {
+                pusher.set_allocator(allocator);
+            }
+            match bound {
+                Some(bound) => {
+                    let (tx, rx) = bounded_channel::channel_from_node(
+                        bound,
+                        source_node_id,
+                        self.stream_id,
+                        &context.credits,
+                    );
+                    pusher.add_endpoint(SendEndpoint::Bounded(tx, Arc::clone(context)));
+                    self.add_recv_endpoint(RecvEndpoint::Bounded(rx, Arc::clone(context)));
+                }
+                None => {
+                    let (tx, rx) = mpsc::unbounded_channel();
+                    pusher.add_endpoint(SendEndpoint::InterThread(tx, Arc::clone(context)));
+                    self.add_recv_endpoint(RecvEndpoint::InterThread(rx, Arc::clone(context)));
+                }
+            }
             Ok(())
         } else {
             Err(format!(
@@ -135,10 +212,43 @@ where
             ))
         }
     }
+
+    fn forward_send_endpoints(&mut self, endpoint_lists: &mut EndpointLists) -> Result<(), String> {
+        endpoint_lists.add(self.stream_id, std::mem::take(&mut self.send_endpoints))
+    }
+}
+
+/// Settings with which a node sets up the transport channels of its streams.
+#[derive(Clone)]
+pub(crate) struct ChannelSettings {
+    pub node_id: NodeId,
+    /// Passes the pushers of the streams received from other nodes to the data receivers.
+    pub channels_to_receivers: Arc<Mutex<ChannelsToReceivers>>,
+    pub channels_to_senders: Arc<Mutex<ChannelsToSenders>>,
+    /// Messages larger than the size are rejected by the endpoints to other nodes.
+    pub max_message_size: Option<usize>,
+    /// Stamped on the messages sent to other nodes.
+    pub run_id: RunId,
+    /// The [`ConflationKey`] of each conflated stream.
+    pub conflated_streams: HashMap<StreamId, Arc<dyn Any + Send + Sync>>,
+    /// The [`BufferAllocator`] of each stream whose buffers are allocated by the receiving
+    /// operators.
+    pub buffer_allocators: HashMap<StreamId, Arc<dyn BufferAllocator>>,
+    /// The [`ChannelBound`] of each stream whose messages are queued in bounded channels.
+    pub bounded_streams: HashMap<StreamId, ChannelBound>,
+    /// The send endpoints of the streams written on the node, shared by their write streams.
+    pub endpoint_lists: Arc<std::sync::Mutex<EndpointLists>>,
+    /// The state which the node shares with the endpoints and write streams.
+    pub context: Arc<NodeContext>,
 }
 
 /// Data structure that stores information needed to set up dataflow channels
 /// by constructing individual transport channels.
+///
+/// The channel manager is only locked while operators and drivers set up their streams. The
+/// [`WriteStream`]s it returns share the list of endpoints of their stream through a handle,
+/// which only locks the list when the channels of the stream are reconfigured, so that messages
+/// are sent without locking.
 pub struct ChannelManager {
     /// The node to which the [`ChannelManager`] belongs.
     node_id: NodeId,
@@ -146,6 +256,10 @@ pub struct ChannelManager {
     graph: Graph,
     /// Stores a `StreamEndpoints` for each stream id.
     stream_entries: HashMap<StreamId, Box<dyn StreamEndpointsT>>,
+    /// The send endpoints of the streams written on the node, shared by their write streams.
+    endpoint_lists: Arc<std::sync::Mutex<EndpointLists>>,
+    /// The state which the node shares with its write streams.
+    context: Arc<NodeContext>,
 }
 
 impl ChannelManager {
@@ -153,28 +267,61 @@ impl ChannelManager {
     /// for operators with streams containing dataflow channels to other nodes, and transport
     /// channels from TCP receivers to operators that are connected to streams originating on
     /// other nodes.
-    pub async fn new(
+    ///
+    /// The pushers sent to the receivers are added to `receiver_pushers`, so that the channels
+    /// of operators added later are added to them.
+    pub(crate) async fn new(
         graph: &Graph,
-        node_id: NodeId,
-        channels_to_receivers: Arc<Mutex<ChannelsToReceivers>>,
-        channels_to_senders: Arc<Mutex<ChannelsToSenders>>,
-    ) -> Self {
+        settings: &ChannelSettings,
+        receiver_pushers: &mut HashMap<StreamId, Box<dyn PusherT>>,
+    ) -> Result<Self, String> {
+        Self::for_added_channels(&Graph::new(), graph, settings, receiver_pushers).await
+    }
+
+    /// Creates the transport channels of `graph` which `old_graph` does not have, e.g. when
+    /// operators are added to a running dataflow.
+    ///
+    /// The send endpoints of the new channels of the streams in `old_graph` are passed to the
+    /// [`WriteStream`](crate::dataflow::WriteStream)s of the streams, which already run, and
+    /// the pushers of the streams received from other nodes are updated in `receiver_pushers`
+    /// and sent to the receivers.
+    pub(crate) async fn for_added_channels(
+        old_graph: &Graph,
+        graph: &Graph,
+        settings: &ChannelSettings,
+        receiver_pushers: &mut HashMap<StreamId, Box<dyn PusherT>>,
+    ) -> Result<Self, String> {
         let mut channel_manager = Self {
-            node_id,
+            node_id: settings.node_id,
             graph: graph.clone(),
             stream_entries: HashMap::new(),
+            endpoint_lists: Arc::clone(&settings.endpoint_lists),
+            context: Arc::clone(&settings.context),
         };
+        let mut updated_pushers = Vec::new();
 
-        let mut receiver_pushers: HashMap<StreamId, Box<dyn PusherT>> = HashMap::new();
-
-        let node_vertices = graph.get_vertices_on(node_id);
+        let node_vertices = graph.get_vertices_on(settings.node_id);
         for stream_metadata in graph.get_streams() {
+            let stream_id = stream_metadata.get_id();
+            let old_stream = old_graph.get_stream(stream_id);
+            let old_channels = old_stream
+                .as_ref()
+                .map(|stream| stream.get_channels())
+                .unwrap_or_default();
+            let channels: Vec<_> = stream_metadata
+                .get_channels()
+                .into_iter()
+                .filter(|channel| !old_channels.contains(channel))
+                .collect();
+            if channels.is_empty() {
+                continue;
+            }
             if node_vertices.contains(&stream_metadata.get_source()) {
                 let stream_endpoint_t = channel_manager
                     .stream_entries
-                    .entry(stream_metadata.get_id())
+                    .entry(stream_id)
                     .or_insert_with(|| stream_metadata.to_stream_endpoints_t());
-                for channel in stream_metadata.get_channels() {
+                for channel in channels {
                     match channel {
                         Channel::InterNode(channel_metadata) => {
                             let other_node_id = match channel_metadata.sink {
@@ -183,31 +330,54 @@ impl ChannelManager {
                                     graph.get_operator(op_id).unwrap().node_id
                                 }
                             };
+                            stream_metrics::register(stream_id);
                             stream_endpoint_t
                                 .add_inter_node_send_endpoint(
                                     other_node_id,
-                                    Arc::clone(&channels_to_senders),
+                                    Arc::clone(&settings.channels_to_senders),
+                                    settings.max_message_size,
+                                    settings.run_id.clone(),
+                                    settings.conflated_streams.get(&stream_id).cloned(),
+                                    &settings.context,
                                 )
-                                .await
-                                .unwrap();
+                                .await?;
                         }
                         Channel::InterThread(_) => {
-                            stream_endpoint_t.add_inter_thread_channel();
+                            stream_endpoint_t.add_inter_thread_channel(
+                                settings.bounded_streams.get(&stream_id).copied(),
+                                &settings.context,
+                            );
                         }
                         Channel::Unscheduled(cm) => eprintln!("Unscheduled channel: {:?}", cm),
                     }
                 }
+                if old_stream.is_some() {
+                    stream_endpoint_t
+                        .forward_send_endpoints(&mut settings.endpoint_lists.lock().unwrap())?;
+                }
             } else {
-                for channel in stream_metadata.get_channels() {
+                for channel in channels {
                     if let Channel::InterNode(channel_metadata) = channel {
                         if node_vertices.contains(&channel_metadata.sink) {
                             let stream_endpoint_t = channel_manager
                                 .stream_entries
-                                .entry(stream_metadata.get_id())
+                                .entry(stream_id)
                                 .or_insert_with(|| stream_metadata.to_stream_endpoints_t());
-                            stream_endpoint_t
-                                .add_inter_node_recv_endpoint(&mut receiver_pushers)
-                                .unwrap();
+                            stream_metrics::register(stream_id);
+                            let source_node_id = match stream_metadata.get_source() {
+                                Vertex::Driver(id) => id,
+                                Vertex::Operator(op_id) => {
+                                    graph.get_operator(op_id).unwrap().node_id
+                                }
+                            };
+                            stream_endpoint_t.add_inter_node_recv_endpoint(
+                                receiver_pushers,
+                                settings.buffer_allocators.get(&stream_id).cloned(),
+                                settings.bounded_streams.get(&stream_id).copied(),
+                                source_node_id,
+                                &settings.context,
+                            )?;
+                            updated_pushers.push(stream_id);
                         }
                     }
                 }
@@ -216,10 +386,15 @@ impl ChannelManager {
 
         // Send pushers to the DataReceiver which publishes received messages from TCP
         // on the proper transport channel.
-        for (k, v) in receiver_pushers.into_iter() {
-            channels_to_receivers.lock().await.send(k, v);
+        updated_pushers.dedup();
+        for stream_id in updated_pushers {
+            settings
+                .channels_to_receivers
+                .lock()
+                .await
+                .send(stream_id, receiver_pushers[&stream_id].clone());
         }
-        channel_manager
+        Ok(channel_manager)
     }
 
     pub fn node_id(&self) -> NodeId {
@@ -257,6 +432,24 @@ impl ChannelManager {
         }
     }
 
+    /// Returns the [`WriteStream`] of a given stream, which sends on its `SendEndpoint`s and on
+    /// the endpoints to the operators added later.
+    ///
+    /// The endpoints are added to the list of endpoints of the stream, which the returned stream
+    /// and its clones load before they send their first message.
+    pub fn get_write_stream<D>(&mut self, stream_id: StreamId) -> Result<WriteStream<D>, String>
+    where
+        for<'a> D: Data + Deserialize<'a>,
+    {
+        let send_endpoints = self.get_send_endpoints(stream_id)?;
+        let mut endpoint_lists = self.endpoint_lists.lock().unwrap();
+        endpoint_lists.add::<D>(stream_id, send_endpoints)?;
+        let handle = endpoint_lists.handle(stream_id)?;
+        Ok(WriteStream::new_with_id(stream_id)
+            .with_endpoint_list(handle)
+            .with_context(Arc::clone(&self.context)))
+    }
+
     /// Returns a cloned vector of the `SendEndpoint`s for a given stream.
     pub fn get_send_endpoints<D>(
         &mut self,
@@ -0,0 +1,238 @@
+use std::{collections::HashMap, error::Error, fmt};
+
+use crate::{
+    dataflow::graph::{Graph, OperatorMetadata},
+    node::NodeId,
+    OperatorId,
+};
+
+use super::strategies::SchedulableOperator;
+
+/// Error raised when the operators of a dataflow graph cannot be placed on nodes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchedulingError {
+    /// A constraint refers to an operator name which no operator has.
+    UnknownOperator(String),
+    /// The operators cannot be placed so that all co-location and separation constraints and
+    /// the capacity of the nodes hold.
+    Infeasible(String),
+}
+
+impl fmt::Display for SchedulingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchedulingError::UnknownOperator(name) => {
+                write!(
+                    f,
+                    "a placement constraint refers to unknown operator {}",
+                    name
+                )
+            }
+            SchedulingError::Infeasible(reason) => {
+                write!(f, "unable to place the operators: {}", reason)
+            }
+        }
+    }
+}
+
+impl Error for SchedulingError {}
+
+/// The fields of an operator to which its co-location and separation constraints refer.
+pub(crate) trait ConstrainedOperator {
+    fn id(&self) -> OperatorId;
+    fn name(&self) -> Option<&String>;
+    fn colocated_with(&self) -> &[String];
+    fn separated_from(&self) -> &[String];
+}
+
+impl ConstrainedOperator for OperatorMetadata {
+    fn id(&self) -> OperatorId {
+        self.id
+    }
+
+    fn name(&self) -> Option<&String> {
+        self.name.as_ref()
+    }
+
+    fn colocated_with(&self) -> &[String] {
+        &self.colocated_with
+    }
+
+    fn separated_from(&self) -> &[String] {
+        &self.separated_from
+    }
+}
+
+impl ConstrainedOperator for SchedulableOperator {
+    fn id(&self) -> OperatorId {
+        self.id
+    }
+
+    fn name(&self) -> Option<&String> {
+        self.name.as_ref()
+    }
+
+    fn colocated_with(&self) -> &[String] {
+        &self.colocated_with
+    }
+
+    fn separated_from(&self) -> &[String] {
+        &self.separated_from
+    }
+}
+
+/// The co-location and separation constraints between operators, which refer to the operators
+/// by their index in a slice of operators.
+pub(crate) struct Constraints {
+    /// The index of the group of co-located operators to which each operator belongs.
+    pub groups: Vec<usize>,
+    pub num_groups: usize,
+    /// Pairs of groups which must not run on the same node.
+    pub separated: Vec<(usize, usize)>,
+}
+
+impl Constraints {
+    pub fn new<O: ConstrainedOperator>(operators: &[O]) -> Result<Self, SchedulingError> {
+        let mut indices_by_name: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (index, operator) in operators.iter().enumerate() {
+            if let Some(name) = operator.name() {
+                indices_by_name.entry(name).or_default().push(index);
+            }
+        }
+        let resolve = |name: &String| {
+            indices_by_name
+                .get(name.as_str())
+                .ok_or_else(|| SchedulingError::UnknownOperator(name.clone()))
+        };
+
+        // Merges the groups of co-located operators with union-find.
+        let mut parents: Vec<usize> = (0..operators.len()).collect();
+        fn find(parents: &mut [usize], index: usize) -> usize {
+            let mut root = index;
+            while parents[root] != root {
+                root = parents[root];
+            }
+            parents[index] = root;
+            root
+        }
+        for (index, operator) in operators.iter().enumerate() {
+            for name in operator.colocated_with() {
+                for &other in resolve(name)? {
+                    let (root, other_root) = (find(&mut parents, index), find(&mut parents, other));
+                    // Keep the lower index as the root, so that groups are numbered in order.
+                    parents[root.max(other_root)] = root.min(other_root);
+                }
+            }
+        }
+        let mut group_of_root = HashMap::new();
+        let mut groups = Vec::with_capacity(operators.len());
+        for index in 0..operators.len() {
+            let root = find(&mut parents, index);
+            let num_groups = group_of_root.len();
+            groups.push(*group_of_root.entry(root).or_insert(num_groups));
+        }
+
+        let mut separated = Vec::new();
+        for (index, operator) in operators.iter().enumerate() {
+            for name in operator.separated_from() {
+                for &other in resolve(name)? {
+                    if groups[index] == groups[other] {
+                        return Err(SchedulingError::Infeasible(format!(
+                            "operator {:?} must be both co-located with and separated from {:?}",
+                            operator.id(),
+                            operators[other].id()
+                        )));
+                    }
+                    separated.push((groups[index], groups[other]));
+                }
+            }
+        }
+        Ok(Self {
+            groups,
+            num_groups: group_of_root.len(),
+            separated,
+        })
+    }
+}
+
+/// Checks that the nodes of the operators in a graph satisfy their co-location and separation
+/// constraints.
+pub(crate) fn check(graph: &Graph) -> Result<(), SchedulingError> {
+    let mut operators = graph.get_operators();
+    operators.sort_by_key(|operator| operator.id);
+    let constraints = Constraints::new(&operators)?;
+    let mut group_nodes: Vec<Option<(usize, NodeId)>> = vec![None; constraints.num_groups];
+    for (index, operator) in operators.iter().enumerate() {
+        match group_nodes[constraints.groups[index]] {
+            Some((other, node_id)) if node_id != operator.node_id => {
+                return Err(SchedulingError::Infeasible(format!(
+                    "operator {:?} on node {} must be co-located with operator {:?} on node {}",
+                    operator.id, operator.node_id, operators[other].id, node_id
+                )));
+            }
+            Some(_) => (),
+            None => group_nodes[constraints.groups[index]] = Some((index, operator.node_id)),
+        }
+    }
+    for (group, other_group) in constraints.separated {
+        let (index, node_id) = group_nodes[group].unwrap();
+        let (other, other_node_id) = group_nodes[other_group].unwrap();
+        if node_id == other_node_id {
+            return Err(SchedulingError::Infeasible(format!(
+                "operator {:?} must be separated from operator {:?}, but both run on node {}",
+                operators[index].id, operators[other].id, node_id
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+    use super::*;
+
+    use crate::{
+        communication::ControlMessage, node::operator_executor::OperatorExecutor,
+        scheduler::channel_manager::ChannelManager, OperatorId,
+    };
+
+    fn runner(
+        _: Arc<Mutex<ChannelManager>>,
+        _: UnboundedSender<ControlMessage>,
+        _: UnboundedReceiver<ControlMessage>,
+    ) -> OperatorExecutor {
+        unreachable!()
+    }
+
+    /// Test that placements which violate co-location or separation constraints are rejected.
+    #[test]
+    fn test_placement_constraints() {
+        let (reader, writer, replica) = (
+            OperatorId::new_deterministic(),
+            OperatorId::new_deterministic(),
+            OperatorId::new_deterministic(),
+        );
+        let mut graph = Graph::new();
+        graph.add_operator(reader, Some("reader".into()), 0, vec![], vec![], runner);
+        graph.add_operator(writer, Some("writer".into()), 0, vec![], vec![], runner);
+        graph.add_operator(replica, Some("replica".into()), 1, vec![], vec![], runner);
+        graph.set_operator_constraints(reader, vec!["writer".into()], vec!["replica".into()]);
+        assert_eq!(check(&graph), Ok(()));
+
+        graph.set_operator_node(replica, 0);
+        assert!(matches!(check(&graph), Err(SchedulingError::Infeasible(_))));
+        graph.set_operator_node(replica, 1);
+        graph.set_operator_node(writer, 1);
+        assert!(matches!(check(&graph), Err(SchedulingError::Infeasible(_))));
+
+        graph.set_operator_constraints(reader, vec!["unknown".into()], vec![]);
+        assert_eq!(
+            check(&graph),
+            Err(SchedulingError::UnknownOperator("unknown".to_string()))
+        );
+    }
+}
@@ -1,14 +1,49 @@
+use std::collections::HashMap;
+
 use crate::dataflow::graph::{Channel, Graph, Vertex};
 
 // Crate-wide visible submodules
 pub(crate) mod endpoints_manager;
 
+// Private submodules
+mod constraints;
+mod partitioning;
+mod selector;
+mod strategies;
+
 // Public exports
 pub mod channel_manager;
 
+pub use constraints::SchedulingError;
+pub use partitioning::{PartitioningScheduler, StreamTraffic};
+pub use selector::NodeSelector;
+pub use strategies::{
+    ConstraintScheduler, LoadAwareScheduler, RoundRobinScheduler, SchedulableOperator, Scheduler,
+};
+
+pub(crate) use selector::parse_labels;
+
 /// Schedules a dataflow graph. Assigns operators to nodes and updates channels.
 /// After running this method, there should be no unscheduled channels remaining.
-pub(crate) fn schedule(graph: &Graph) -> Graph {
+///
+/// Operators with node selectors run on the nodes whose labels match, and movable operators are
+/// placed by the scheduler and then by the partitioner, if any. Fails if the nodes of the
+/// operators violate their co-location or separation constraints.
+pub(crate) fn schedule(
+    graph: &Graph,
+    scheduler: Option<&dyn Scheduler>,
+    partitioner: Option<&PartitioningScheduler>,
+    node_labels: &[HashMap<String, String>],
+) -> Result<Graph, SchedulingError> {
+    let mut placed_graph = selector::place(graph, node_labels)?;
+    if let Some(scheduler) = scheduler {
+        placed_graph = strategies::place(scheduler, &placed_graph, node_labels)?;
+    }
+    if let Some(partitioner) = partitioner {
+        placed_graph = partitioner.place(&placed_graph, node_labels)?;
+    }
+    let graph = &placed_graph;
+    constraints::check(graph)?;
     let mut scheduled_graph = graph.clone();
     for stream in scheduled_graph.get_streams_ref_mut() {
         let source_node_id = match stream.get_source() {
@@ -37,5 +72,5 @@ pub(crate) fn schedule(graph: &Graph) -> Graph {
         }
         stream.set_channels(channels);
     }
-    scheduled_graph
+    Ok(scheduled_graph)
 }
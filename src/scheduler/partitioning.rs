@@ -0,0 +1,488 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::Path,
+};
+
+use crate::{
+    dataflow::{
+        graph::{Channel, Graph, OperatorMetadata, Vertex},
+        stream::StreamId,
+    },
+    node::NodeId,
+    OperatorId,
+};
+
+use super::{constraints::Constraints, NodeSelector, SchedulingError};
+
+/// Weight of the streams whose traffic is not estimated, so that operators are still placed next
+/// to the operators with which they share streams.
+const DEFAULT_TRAFFIC: f64 = 1.0;
+/// Maximum number of refinement passes over the movable operators.
+const MAX_PASSES: usize = 8;
+
+/// Estimated traffic of a stream, e.g. measured while profiling the dataflow.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamTraffic {
+    pub messages_per_second: f64,
+    /// Average size of the serialized messages in bytes.
+    pub message_size: usize,
+}
+
+impl StreamTraffic {
+    pub fn new(messages_per_second: f64, message_size: usize) -> Self {
+        Self {
+            messages_per_second,
+            message_size,
+        }
+    }
+
+    /// Returns the estimated number of bytes sent per second.
+    pub fn bytes_per_second(&self) -> f64 {
+        self.messages_per_second * self.message_size as f64
+    }
+}
+
+/// A vertex at the other end of a stream.
+#[derive(Clone, Copy)]
+enum Neighbor {
+    /// A driver, which stays on its node.
+    Node(NodeId),
+    /// The group of co-located operators with the index.
+    Group(usize),
+}
+
+/// Places the operators which are [movable](crate::dataflow::OperatorConfig::movable) on nodes
+/// so that the traffic of the streams between nodes is minimized, while the
+/// [estimated cores](crate::dataflow::OperatorConfig::cpu_demand) used by the operators on each
+/// node stay within the capacity of the node.
+///
+/// The traffic of a stream is estimated from [annotations](PartitioningScheduler::stream_traffic)
+/// or [profiles](PartitioningScheduler::load_profile). Operators are first placed greedily, those
+/// with the most traffic first, on the node with which they exchange the most traffic. Like
+/// Kernighan-Lin and METIS, refinement passes then move operators to other nodes as long as the
+/// moves reduce the traffic between nodes.
+///
+/// Operators which must be [co-located](crate::dataflow::OperatorConfig::colocate_with) are
+/// placed together, and operators which must be
+/// [separated](crate::dataflow::OperatorConfig::separate_from) are placed on different nodes.
+/// Operators with [node selectors](crate::dataflow::OperatorConfig::with_node_selector) are
+/// moved among the nodes whose labels match.
+///
+/// Every node schedules the dataflow graph itself, so every node must use the same scheduler.
+#[derive(Debug, Clone, Default)]
+pub struct PartitioningScheduler {
+    node_capacities: BTreeMap<NodeId, f64>,
+    stream_traffic: HashMap<StreamId, StreamTraffic>,
+    profile: HashMap<String, StreamTraffic>,
+}
+
+impl PartitioningScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of cores the operators may use on the node. Operators are only moved to
+    /// nodes whose capacity is set.
+    pub fn node_capacity(mut self, node_id: NodeId, cores: f64) -> Self {
+        self.node_capacities.insert(node_id, cores);
+        self
+    }
+
+    /// Sets the estimated traffic of the stream, which takes precedence over profiles.
+    pub fn stream_traffic(mut self, stream_id: StreamId, traffic: StreamTraffic) -> Self {
+        self.stream_traffic.insert(stream_id, traffic);
+        self
+    }
+
+    /// Loads the traffic of streams measured in a previous run from a file in which each line
+    /// contains the name or ID of a stream, its messages per second, and the average size of its
+    /// messages in bytes, separated by whitespace. Lines starting with `#` are ignored.
+    pub fn load_profile<P: AsRef<Path>>(mut self, path: P) -> io::Result<Self> {
+        for (index, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid stream profile on line {}: {}", index + 1, line),
+                )
+            };
+            // Names of streams may contain whitespace.
+            let mut fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 3 {
+                return Err(invalid());
+            }
+            let message_size = fields.pop().unwrap().parse().map_err(|_| invalid())?;
+            let messages_per_second = fields.pop().unwrap().parse().map_err(|_| invalid())?;
+            self.profile.insert(
+                fields.join(" "),
+                StreamTraffic::new(messages_per_second, message_size),
+            );
+        }
+        Ok(self)
+    }
+
+    /// Returns the estimated bytes per second sent on the stream.
+    fn traffic(&self, stream_id: StreamId) -> f64 {
+        self.stream_traffic
+            .get(&stream_id)
+            .or_else(|| {
+                stream_id
+                    .name()
+                    .and_then(|name| self.profile.get(&name))
+                    .or_else(|| self.profile.get(&stream_id.to_string()))
+            })
+            .map_or(DEFAULT_TRAFFIC, StreamTraffic::bytes_per_second)
+    }
+
+    /// Returns a copy of the graph in which the movable operators are placed on nodes.
+    pub(crate) fn place(
+        &self,
+        graph: &Graph,
+        node_labels: &[HashMap<String, String>],
+    ) -> Result<Graph, SchedulingError> {
+        let mut placed_graph = graph.clone();
+        for (operator_id, node_id) in self.partition(graph, node_labels)? {
+            placed_graph.set_operator_node(operator_id, node_id);
+        }
+        Ok(placed_graph)
+    }
+
+    /// Returns the nodes of all operators. Only operates on sorted IDs, so that every node
+    /// computes the same placement.
+    ///
+    /// Operators with node selectors are movable among the nodes whose labels match.
+    fn partition(
+        &self,
+        graph: &Graph,
+        node_labels: &[HashMap<String, String>],
+    ) -> Result<HashMap<OperatorId, NodeId>, SchedulingError> {
+        let mut operators = graph.get_operators();
+        operators.sort_by_key(|operator| operator.id);
+        // Co-located operators are placed together as a group.
+        let constraints = Constraints::new(&operators)?;
+        let groups = &constraints.groups;
+        let num_groups = constraints.num_groups;
+        let group_of: HashMap<OperatorId, usize> = operators
+            .iter()
+            .enumerate()
+            .map(|(index, operator)| (operator.id, groups[index]))
+            .collect();
+        let neighbor = |vertex: &Vertex| match vertex {
+            Vertex::Driver(node_id) => Neighbor::Node(*node_id),
+            Vertex::Operator(operator_id) => Neighbor::Group(group_of[operator_id]),
+        };
+
+        let mut streams = graph.get_streams();
+        streams.sort_by_key(|stream| stream.get_id());
+        let mut neighbors: Vec<Vec<(Neighbor, f64)>> = vec![Vec::new(); num_groups];
+        for stream in streams {
+            let traffic = self.traffic(stream.get_id());
+            let source = neighbor(&stream.get_source());
+            for channel in stream.get_channels() {
+                let sink = match channel {
+                    Channel::InterThread(cm)
+                    | Channel::InterNode(cm)
+                    | Channel::Unscheduled(cm) => neighbor(&cm.sink),
+                };
+                match (source, sink) {
+                    (Neighbor::Group(a), Neighbor::Group(b)) if a == b => continue,
+                    _ => (),
+                }
+                if let Neighbor::Group(group) = source {
+                    neighbors[group].push((sink, traffic));
+                }
+                if let Neighbor::Group(group) = sink {
+                    neighbors[group].push((source, traffic));
+                }
+            }
+        }
+        let mut separated: Vec<Vec<usize>> = vec![Vec::new(); num_groups];
+        for &(a, b) in constraints.separated.iter() {
+            separated[a].push(b);
+            separated[b].push(a);
+        }
+
+        // Groups with an operator which is not movable stay on its node. Without capacities, the
+        // movable operators join the first operator of their group.
+        let mut assignment: Vec<Option<NodeId>> = vec![None; num_groups];
+        let mut first_operators = vec![None; num_groups];
+        let mut cpu_demands = vec![0.0; num_groups];
+        let mut selectors: Vec<Vec<&NodeSelector>> = vec![Vec::new(); num_groups];
+        let placeable =
+            |operator: &OperatorMetadata| operator.movable || operator.node_selector.is_some();
+        for (index, operator) in operators.iter().enumerate() {
+            first_operators[groups[index]].get_or_insert(operator.id);
+            cpu_demands[groups[index]] += operator.cpu_demand;
+            selectors[groups[index]].extend(operator.node_selector.iter());
+            if !placeable(operator) {
+                assignment[groups[index]].get_or_insert(operator.node_id);
+            }
+        }
+        if self.node_capacities.is_empty() {
+            for (index, operator) in operators.iter().enumerate() {
+                assignment[groups[index]].get_or_insert(operator.node_id);
+            }
+        }
+        let mut loads: BTreeMap<NodeId, f64> = BTreeMap::new();
+        for (group, node_id) in assignment.iter().enumerate() {
+            if let Some(node_id) = node_id {
+                *loads.entry(*node_id).or_insert(0.0) += cpu_demands[group];
+            }
+        }
+        let connectivity = |group: usize, assignment: &[Option<NodeId>]| {
+            let mut connectivity: BTreeMap<NodeId, f64> = BTreeMap::new();
+            for (neighbor, traffic) in neighbors[group].iter() {
+                let node_id = match neighbor {
+                    Neighbor::Node(node_id) => Some(*node_id),
+                    Neighbor::Group(other) => assignment[*other],
+                };
+                if let Some(node_id) = node_id {
+                    *connectivity.entry(node_id).or_insert(0.0) += traffic;
+                }
+            }
+            connectivity
+        };
+        let fits = |loads: &BTreeMap<NodeId, f64>, node_id: NodeId, cpu_demand: f64| {
+            let load = loads.get(&node_id).cloned().unwrap_or(0.0);
+            load + cpu_demand <= self.node_capacities[&node_id]
+        };
+        let no_labels = HashMap::new();
+        let allowed = |assignment: &[Option<NodeId>], group: usize, node_id: NodeId| {
+            let labels = node_labels.get(node_id).unwrap_or(&no_labels);
+            selectors[group]
+                .iter()
+                .all(|selector| selector.matches(labels))
+                && separated[group]
+                    .iter()
+                    .all(|&other| assignment[other] != Some(node_id))
+        };
+
+        // Place the groups with the most traffic first, as their placement matters most.
+        let mut movable: Vec<usize> = (0..num_groups)
+            .filter(|&group| assignment[group].is_none())
+            .collect();
+        let total_traffic =
+            |group: usize| -> f64 { neighbors[group].iter().map(|(_, traffic)| traffic).sum() };
+        movable.sort_by(|&a, &b| {
+            total_traffic(b)
+                .partial_cmp(&total_traffic(a))
+                .unwrap_or(Ordering::Equal)
+        });
+        for &group in movable.iter() {
+            let cpu_demand = cpu_demands[group];
+            let traffic_to = connectivity(group, &assignment);
+            let spare = |node_id: &NodeId| {
+                self.node_capacities[node_id] - loads.get(node_id).cloned().unwrap_or(0.0)
+            };
+            let candidates = || {
+                self.node_capacities
+                    .keys()
+                    .filter(|&&node_id| allowed(&assignment, group, node_id))
+            };
+            let best = candidates()
+                .filter(|&&node_id| fits(&loads, node_id, cpu_demand))
+                .max_by(|a, b| {
+                    let traffic_a = traffic_to.get(a).cloned().unwrap_or(0.0);
+                    let traffic_b = traffic_to.get(b).cloned().unwrap_or(0.0);
+                    traffic_a
+                        .partial_cmp(&traffic_b)
+                        .unwrap_or(Ordering::Equal)
+                        .then(spare(a).partial_cmp(&spare(b)).unwrap_or(Ordering::Equal))
+                        // Prefer the lower node ID on ties.
+                        .then(b.cmp(a))
+                })
+                // Overload the node with the most spare capacity if the operators fit nowhere.
+                .or_else(|| {
+                    candidates().max_by(|a, b| {
+                        spare(a)
+                            .partial_cmp(&spare(b))
+                            .unwrap_or(Ordering::Equal)
+                            .then(b.cmp(a))
+                    })
+                })
+                .cloned()
+                .ok_or_else(|| {
+                    SchedulingError::Infeasible(format!(
+                        "no node matches the node selectors and separation constraints of {:?}",
+                        first_operators[group].unwrap()
+                    ))
+                })?;
+            assignment[group] = Some(best);
+            *loads.entry(best).or_insert(0.0) += cpu_demand;
+        }
+
+        // Move groups to the node with the largest reduction of traffic between nodes. Every
+        // move strictly reduces that traffic, so the passes terminate.
+        for _ in 0..MAX_PASSES {
+            let mut improved = false;
+            for &group in movable.iter() {
+                let cpu_demand = cpu_demands[group];
+                let current = assignment[group].unwrap();
+                let traffic_to = connectivity(group, &assignment);
+                let current_traffic = traffic_to.get(&current).cloned().unwrap_or(0.0);
+                let mut best: Option<(NodeId, f64)> = None;
+                for &node_id in self.node_capacities.keys() {
+                    if node_id == current
+                        || !fits(&loads, node_id, cpu_demand)
+                        || !allowed(&assignment, group, node_id)
+                    {
+                        continue;
+                    }
+                    let gain = traffic_to.get(&node_id).cloned().unwrap_or(0.0) - current_traffic;
+                    if gain > f64::EPSILON && best.map_or(true, |(_, best_gain)| gain > best_gain) {
+                        best = Some((node_id, gain));
+                    }
+                }
+                if let Some((node_id, _)) = best {
+                    *loads.get_mut(&current).unwrap() -= cpu_demand;
+                    *loads.entry(node_id).or_insert(0.0) += cpu_demand;
+                    assignment[group] = Some(node_id);
+                    improved = true;
+                }
+            }
+            if !improved {
+                break;
+            }
+        }
+
+        // Operators which are not movable keep their node, even if it conflicts with their
+        // group, which fails the check of the schedule.
+        Ok(operators
+            .iter()
+            .enumerate()
+            .map(|(index, operator)| {
+                let node_id = if placeable(operator) {
+                    assignment[groups[index]].unwrap()
+                } else {
+                    operator.node_id
+                };
+                (operator.id, node_id)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+    use super::*;
+
+    use crate::{
+        communication::ControlMessage, dataflow::WriteStream,
+        node::operator_executor::OperatorExecutor, scheduler::channel_manager::ChannelManager,
+    };
+
+    fn runner(
+        _: Arc<Mutex<ChannelManager>>,
+        _: UnboundedSender<ControlMessage>,
+        _: UnboundedReceiver<ControlMessage>,
+    ) -> OperatorExecutor {
+        unreachable!()
+    }
+
+    /// Test that movable operators are placed next to their heaviest streams within the
+    /// capacity of the nodes.
+    #[test]
+    fn test_partitioning_scheduler() {
+        let ids: Vec<OperatorId> = (0..4).map(|_| OperatorId::new_deterministic()).collect();
+        let (camera, detector, planner, actuator) = (ids[0], ids[1], ids[2], ids[3]);
+        let images: WriteStream<u32> = WriteStream::new();
+        let detections: WriteStream<u32> = WriteStream::new();
+        let controls: WriteStream<u32> = WriteStream::new();
+
+        let mut graph = Graph::new();
+        graph.add_operator(
+            camera,
+            Some("camera".into()),
+            0,
+            vec![],
+            vec![images.get_id()],
+            runner,
+        );
+        graph.add_operator_stream(camera, &images);
+        let detector_streams = (vec![images.get_id()], vec![detections.get_id()]);
+        graph.add_operator(
+            detector,
+            None,
+            1,
+            detector_streams.0,
+            detector_streams.1,
+            runner,
+        );
+        graph.add_operator_stream(detector, &detections);
+        graph.set_operator_partitioning(detector, true, 2.0);
+        let planner_streams = (vec![detections.get_id()], vec![controls.get_id()]);
+        graph.add_operator(
+            planner,
+            None,
+            0,
+            planner_streams.0,
+            planner_streams.1,
+            runner,
+        );
+        graph.add_operator_stream(planner, &controls);
+        graph.set_operator_partitioning(planner, true, 1.0);
+        graph.add_operator(
+            actuator,
+            Some("actuator".into()),
+            1,
+            vec![controls.get_id()],
+            vec![],
+            runner,
+        );
+
+        let scheduler = PartitioningScheduler::new()
+            .stream_traffic(images.get_id(), StreamTraffic::new(30.0, 1 << 20))
+            .stream_traffic(detections.get_id(), StreamTraffic::new(10.0, 1024))
+            .stream_traffic(controls.get_id(), StreamTraffic::new(10.0, 100));
+        // The planner would rather run next to the detector, but does not fit on node 0.
+        let placement = scheduler
+            .clone()
+            .node_capacity(0, 3.0)
+            .node_capacity(1, 4.0);
+        let placement = placement.partition(&graph, &[]).unwrap();
+        assert_eq!(placement[&camera], 0);
+        assert_eq!(placement[&detector], 0);
+        assert_eq!(placement[&planner], 1);
+        assert_eq!(placement[&actuator], 1);
+
+        // The detector does not fit next to the camera.
+        let placement = scheduler
+            .clone()
+            .node_capacity(0, 2.0)
+            .node_capacity(1, 4.0);
+        let placement = placement.partition(&graph, &[]).unwrap();
+        assert_eq!(placement[&detector], 1);
+        assert_eq!(placement[&planner], 1);
+        assert_eq!(placement[&camera], 0);
+
+        // The co-located detector and planner only fit on node 1 together.
+        graph.set_operator_constraints(planner, vec!["detector".to_string()], vec![]);
+        let placement = scheduler
+            .clone()
+            .node_capacity(0, 3.0)
+            .node_capacity(1, 4.0);
+        let placement = placement.partition(&graph, &[]).unwrap();
+        assert_eq!((placement[&detector], placement[&planner]), (1, 1));
+
+        // The planner must not run next to the actuator or the camera.
+        graph.set_operator_constraints(
+            planner,
+            vec![],
+            vec!["actuator".to_string(), "camera".to_string()],
+        );
+        let placement = scheduler.node_capacity(0, 4.0).node_capacity(1, 4.0);
+        assert!(matches!(
+            placement.partition(&graph, &[]),
+            Err(SchedulingError::Infeasible(_))
+        ));
+    }
+}
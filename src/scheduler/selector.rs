@@ -0,0 +1,206 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crate::{dataflow::graph::Graph, node::NodeId};
+
+use super::SchedulingError;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Requirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    Exists(String),
+    NotExists(String),
+}
+
+impl Requirement {
+    fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        match self {
+            Requirement::Equals(key, value) => labels.get(key) == Some(value),
+            Requirement::NotEquals(key, value) => labels.get(key) != Some(value),
+            Requirement::Exists(key) => labels.contains_key(key),
+            Requirement::NotExists(key) => !labels.contains_key(key),
+        }
+    }
+}
+
+/// Selects the nodes on which an operator may run by their
+/// [labels](crate::Configuration::node_labels), e.g. `gpu=true,zone=vehicle`.
+///
+/// A selector is a comma-separated list of requirements which a node must all satisfy:
+/// `key=value`, `key!=value`, `key` if the node has the label, and `!key` if it does not.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeSelector {
+    expression: String,
+    requirements: Vec<Requirement>,
+}
+
+impl NodeSelector {
+    /// Returns whether a node with the labels satisfies the selector.
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        self.requirements
+            .iter()
+            .all(|requirement| requirement.matches(labels))
+    }
+}
+
+impl FromStr for NodeSelector {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, String> {
+        let is_key = |key: &str| {
+            !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.' || c == '/')
+        };
+        let mut requirements = Vec::new();
+        for requirement in expression.split(',').map(str::trim) {
+            let invalid = || format!("Invalid requirement {:?} in node selector", requirement);
+            let requirement = if let Some(index) = requirement.find("!=") {
+                let (key, value) = (&requirement[..index], &requirement[index + 2..]);
+                Requirement::NotEquals(key.trim().to_string(), value.trim().to_string())
+            } else if let Some(index) = requirement.find('=') {
+                let (key, value) = (&requirement[..index], &requirement[index + 1..]);
+                Requirement::Equals(key.trim().to_string(), value.trim().to_string())
+            } else if requirement.starts_with('!') {
+                Requirement::NotExists(requirement[1..].trim().to_string())
+            } else {
+                Requirement::Exists(requirement.to_string())
+            };
+            let key = match &requirement {
+                Requirement::Equals(key, _)
+                | Requirement::NotEquals(key, _)
+                | Requirement::Exists(key)
+                | Requirement::NotExists(key) => key,
+            };
+            if !is_key(key) {
+                return Err(invalid());
+            }
+            requirements.push(requirement);
+        }
+        Ok(Self {
+            expression: expression.to_string(),
+            requirements,
+        })
+    }
+}
+
+impl fmt::Display for NodeSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+/// Parses the labels of a node, e.g. `gpu=true,zone=vehicle`. A label without a value has an
+/// empty value.
+pub(crate) fn parse_labels(labels: &str) -> HashMap<String, String> {
+    labels
+        .split(',')
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(|label| match label.find('=') {
+            Some(index) => (
+                label[..index].trim().to_string(),
+                label[index + 1..].trim().to_string(),
+            ),
+            None => (label.to_string(), String::new()),
+        })
+        .collect()
+}
+
+/// Returns a copy of the graph in which every operator with a node selector runs on the least
+/// loaded node which matches the selector, with the lowest ID on ties.
+pub(crate) fn place(
+    graph: &Graph,
+    node_labels: &[HashMap<String, String>],
+) -> Result<Graph, SchedulingError> {
+    let mut operators = graph.get_operators();
+    operators.sort_by_key(|operator| operator.id);
+    let mut loads: HashMap<NodeId, f64> = HashMap::new();
+    for operator in operators.iter() {
+        if operator.node_selector.is_none() {
+            *loads.entry(operator.node_id).or_insert(0.0) += operator.cpu_demand;
+        }
+    }
+    let mut placed_graph = graph.clone();
+    for operator in operators.iter() {
+        let selector = match &operator.node_selector {
+            Some(selector) => selector,
+            None => continue,
+        };
+        let load = |node_id: &NodeId| loads.get(node_id).cloned().unwrap_or(0.0);
+        let node_id = (0..node_labels.len())
+            .filter(|&node_id| selector.matches(&node_labels[node_id]))
+            .min_by(|a, b| {
+                load(a)
+                    .partial_cmp(&load(b))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then(a.cmp(b))
+            })
+            .ok_or_else(|| {
+                SchedulingError::Infeasible(format!(
+                    "no node matches the selector {} of operator {:?}",
+                    selector, operator.id
+                ))
+            })?;
+        *loads.entry(node_id).or_insert(0.0) += operator.cpu_demand;
+        placed_graph.set_operator_node(operator.id, node_id);
+    }
+    Ok(placed_graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+    use super::*;
+
+    use crate::{
+        communication::ControlMessage, node::operator_executor::OperatorExecutor,
+        scheduler::channel_manager::ChannelManager, OperatorId,
+    };
+
+    fn runner(
+        _: Arc<Mutex<ChannelManager>>,
+        _: UnboundedSender<ControlMessage>,
+        _: UnboundedReceiver<ControlMessage>,
+    ) -> OperatorExecutor {
+        unreachable!()
+    }
+
+    /// Test that operators with node selectors are spread over the nodes whose labels match.
+    #[test]
+    fn test_node_selector() {
+        let node_labels = vec![
+            parse_labels("zone=cloud"),
+            parse_labels("gpu=true, zone=vehicle"),
+            parse_labels("gpu=true,zone=vehicle,debug"),
+        ];
+        let selector: NodeSelector = "gpu=true, zone!=cloud, !debug".parse().unwrap();
+        assert!(selector.matches(&node_labels[1]));
+        assert!(!selector.matches(&node_labels[0]) && !selector.matches(&node_labels[2]));
+        assert!("gpu=true,=false".parse::<NodeSelector>().is_err());
+
+        let mut ids: Vec<OperatorId> = (0..3).map(|_| OperatorId::new_deterministic()).collect();
+        ids.sort();
+        let mut graph = Graph::new();
+        for &id in ids.iter() {
+            graph.add_operator(id, None, 0, vec![], vec![], runner);
+            graph.set_operator_node_selector(id, "gpu=true".parse().unwrap());
+        }
+        let placed_graph = place(&graph, &node_labels).unwrap();
+        let nodes: Vec<NodeId> = ids
+            .iter()
+            .map(|&id| placed_graph.get_operator(id).unwrap().node_id)
+            .collect();
+        assert_eq!(nodes, vec![1, 2, 1]);
+
+        graph.set_operator_node_selector(ids[0], "tpu".parse().unwrap());
+        assert!(matches!(
+            place(&graph, &node_labels),
+            Err(SchedulingError::Infeasible(_))
+        ));
+    }
+}
@@ -0,0 +1,352 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+};
+
+use crate::{dataflow::graph::Graph, node::NodeId, OperatorId};
+
+use super::{constraints::Constraints, SchedulingError};
+
+/// An operator of a dataflow graph, as placed by a [`Scheduler`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchedulableOperator {
+    pub id: OperatorId,
+    pub name: Option<String>,
+    /// The node on which the operator runs unless the scheduler moves it.
+    pub node_id: NodeId,
+    /// Whether the scheduler may move the operator to another node, i.e. whether the operator
+    /// is [movable](crate::dataflow::OperatorConfig::movable) or has a
+    /// [node selector](crate::dataflow::OperatorConfig::with_node_selector).
+    pub movable: bool,
+    /// Estimated number of cores the operator uses.
+    pub cpu_demand: f64,
+    /// The nodes whose labels match the node selector of the operator, or all nodes.
+    pub eligible_nodes: Vec<NodeId>,
+    /// The nodes on which the operator prefers to run, in order of preference.
+    pub node_affinity: Vec<NodeId>,
+    /// The names of the operators which must run on the same node.
+    pub colocated_with: Vec<String>,
+    /// The names of the operators which must not run on the same node.
+    pub separated_from: Vec<String>,
+}
+
+/// Places the movable operators of a dataflow graph on nodes, selected with
+/// [`Configuration::scheduler`](crate::Configuration::scheduler).
+///
+/// Every node schedules the dataflow graph itself, so every node must use the same scheduler,
+/// and the placement must only depend on the operators. The co-location and separation
+/// constraints of the operators are checked after the operators are placed.
+pub trait Scheduler: Send + Sync {
+    /// Returns the nodes of the movable operators, which are sorted by ID and placed on one of
+    /// `num_nodes` nodes. Operators which are missing from the placement stay on their node.
+    fn place(
+        &self,
+        operators: &[SchedulableOperator],
+        num_nodes: usize,
+    ) -> Result<HashMap<OperatorId, NodeId>, SchedulingError>;
+}
+
+/// Places the movable operators on their eligible nodes in turn, in the order of their IDs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RoundRobinScheduler;
+
+impl Scheduler for RoundRobinScheduler {
+    fn place(
+        &self,
+        operators: &[SchedulableOperator],
+        num_nodes: usize,
+    ) -> Result<HashMap<OperatorId, NodeId>, SchedulingError> {
+        let mut placement = HashMap::new();
+        let mut next_node = 0;
+        for operator in operators.iter().filter(|operator| operator.movable) {
+            let node_id = (0..num_nodes)
+                .map(|offset| (next_node + offset) % num_nodes)
+                .find(|node_id| operator.eligible_nodes.contains(node_id))
+                .ok_or_else(|| no_eligible_node(operator))?;
+            placement.insert(operator.id, node_id);
+            next_node = node_id + 1;
+        }
+        Ok(placement)
+    }
+}
+
+/// Places the movable operators, those with the highest
+/// [CPU demand](crate::dataflow::OperatorConfig::cpu_demand) first, on the eligible node whose
+/// cores are the least used, counting the operators which stay on their node.
+///
+/// Nodes have 1 core unless their capacity is set, in which case operators which fit nowhere
+/// are placed on the least used node.
+#[derive(Debug, Clone, Default)]
+pub struct LoadAwareScheduler {
+    node_capacities: BTreeMap<NodeId, f64>,
+}
+
+impl LoadAwareScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of cores the operators may use on the node.
+    pub fn node_capacity(mut self, node_id: NodeId, cores: f64) -> Self {
+        self.node_capacities.insert(node_id, cores);
+        self
+    }
+
+    fn capacity(&self, node_id: NodeId) -> f64 {
+        self.node_capacities.get(&node_id).cloned().unwrap_or(1.0)
+    }
+}
+
+impl Scheduler for LoadAwareScheduler {
+    fn place(
+        &self,
+        operators: &[SchedulableOperator],
+        _num_nodes: usize,
+    ) -> Result<HashMap<OperatorId, NodeId>, SchedulingError> {
+        let mut loads: HashMap<NodeId, f64> = HashMap::new();
+        for operator in operators.iter().filter(|operator| !operator.movable) {
+            *loads.entry(operator.node_id).or_insert(0.0) += operator.cpu_demand;
+        }
+        let mut movable: Vec<&SchedulableOperator> = operators
+            .iter()
+            .filter(|operator| operator.movable)
+            .collect();
+        movable.sort_by(|a, b| {
+            b.cpu_demand
+                .partial_cmp(&a.cpu_demand)
+                .unwrap_or(Ordering::Equal)
+                .then(a.id.cmp(&b.id))
+        });
+        let mut placement = HashMap::new();
+        for operator in movable {
+            let utilization = |node_id: &NodeId| {
+                let load = loads.get(node_id).cloned().unwrap_or(0.0);
+                (load + operator.cpu_demand) / self.capacity(*node_id)
+            };
+            let node_id = operator
+                .eligible_nodes
+                .iter()
+                .min_by(|a, b| {
+                    utilization(a)
+                        .partial_cmp(&utilization(b))
+                        .unwrap_or(Ordering::Equal)
+                        .then(a.cmp(b))
+                })
+                .cloned()
+                .ok_or_else(|| no_eligible_node(operator))?;
+            *loads.entry(node_id).or_insert(0.0) += operator.cpu_demand;
+            placement.insert(operator.id, node_id);
+        }
+        Ok(placement)
+    }
+}
+
+/// Places the movable operators on the nodes of their
+/// [node affinity](crate::dataflow::OperatorConfig::with_node_affinity) so that their
+/// co-location and separation constraints hold.
+///
+/// Co-located operators are placed together, in the order of their IDs, on the first node of
+/// their affinities which is eligible for all of them and on which no operator they must be
+/// separated from runs. Operators without affinity are placed on the eligible node with the
+/// fewest operators.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConstraintScheduler;
+
+impl Scheduler for ConstraintScheduler {
+    fn place(
+        &self,
+        operators: &[SchedulableOperator],
+        num_nodes: usize,
+    ) -> Result<HashMap<OperatorId, NodeId>, SchedulingError> {
+        let constraints = Constraints::new(operators)?;
+        let num_groups = constraints.num_groups;
+        let mut separated: Vec<Vec<usize>> = vec![Vec::new(); num_groups];
+        for &(a, b) in constraints.separated.iter() {
+            separated[a].push(b);
+            separated[b].push(a);
+        }
+        let groups = &constraints.groups;
+        let members = |group: usize| {
+            operators
+                .iter()
+                .enumerate()
+                .filter(move |(index, _)| groups[*index] == group)
+                .map(|(_, operator)| operator)
+        };
+
+        // Groups with an operator which is not movable stay on its node.
+        let mut assignment: Vec<Option<NodeId>> = vec![None; num_groups];
+        let mut num_operators = vec![0; num_nodes];
+        for (index, operator) in operators.iter().enumerate() {
+            if !operator.movable {
+                assignment[groups[index]].get_or_insert(operator.node_id);
+                if let Some(count) = num_operators.get_mut(operator.node_id) {
+                    *count += 1;
+                }
+            }
+        }
+        let mut placement = HashMap::new();
+        for group in 0..num_groups {
+            let node_id = match assignment[group] {
+                Some(node_id) => node_id,
+                None => {
+                    let allowed = |node_id: &NodeId| {
+                        members(group).all(|operator| operator.eligible_nodes.contains(node_id))
+                            && separated[group]
+                                .iter()
+                                .all(|&other| assignment[other] != Some(*node_id))
+                    };
+                    let preferred = members(group)
+                        .flat_map(|operator| operator.node_affinity.iter())
+                        .find(|node_id| allowed(*node_id))
+                        .cloned();
+                    let node_id = preferred
+                        .or_else(|| {
+                            (0..num_nodes)
+                                .filter(allowed)
+                                .min_by_key(|&node_id| (num_operators[node_id], node_id))
+                        })
+                        .ok_or_else(|| {
+                            SchedulingError::Infeasible(format!(
+                                "no node matches the node selectors and separation constraints \
+                                 of {:?}",
+                                members(group).next().unwrap().id
+                            ))
+                        })?;
+                    assignment[group] = Some(node_id);
+                    node_id
+                }
+            };
+            for member in members(group).filter(|operator| operator.movable) {
+                placement.insert(member.id, node_id);
+                if let Some(count) = num_operators.get_mut(node_id) {
+                    *count += 1;
+                }
+            }
+        }
+        Ok(placement)
+    }
+}
+
+fn no_eligible_node(operator: &SchedulableOperator) -> SchedulingError {
+    SchedulingError::Infeasible(format!(
+        "no node matches the node selector of operator {:?}",
+        operator.id
+    ))
+}
+
+/// Returns a copy of the graph in which the movable operators are placed on nodes by the
+/// scheduler.
+pub(crate) fn place(
+    scheduler: &dyn Scheduler,
+    graph: &Graph,
+    node_labels: &[HashMap<String, String>],
+) -> Result<Graph, SchedulingError> {
+    let mut metadata = graph.get_operators();
+    metadata.sort_by_key(|operator| operator.id);
+    let num_nodes = node_labels.len();
+    let operators: Vec<SchedulableOperator> = metadata
+        .into_iter()
+        .map(|operator| SchedulableOperator {
+            id: operator.id,
+            movable: operator.movable || operator.node_selector.is_some(),
+            eligible_nodes: (0..num_nodes)
+                .filter(|&node_id| match &operator.node_selector {
+                    Some(selector) => selector.matches(&node_labels[node_id]),
+                    None => true,
+                })
+                .collect(),
+            name: operator.name,
+            node_id: operator.node_id,
+            cpu_demand: operator.cpu_demand,
+            node_affinity: operator.node_affinity,
+            colocated_with: operator.colocated_with,
+            separated_from: operator.separated_from,
+        })
+        .collect();
+    let mut placed_graph = graph.clone();
+    for (operator_id, node_id) in scheduler.place(&operators, num_nodes)? {
+        let operator = match operators.iter().find(|operator| operator.id == operator_id) {
+            Some(operator) if operator.movable => operator,
+            _ => continue,
+        };
+        if !operator.eligible_nodes.contains(&node_id) {
+            return Err(SchedulingError::Infeasible(format!(
+                "the scheduler placed operator {:?} on node {}, which is not eligible",
+                operator_id, node_id
+            )));
+        }
+        placed_graph.set_operator_node(operator_id, node_id);
+    }
+    Ok(placed_graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn operator(name: &str, node_id: NodeId, movable: bool) -> SchedulableOperator {
+        SchedulableOperator {
+            id: OperatorId::new_deterministic(),
+            name: Some(name.to_string()),
+            node_id,
+            movable,
+            cpu_demand: 1.0,
+            eligible_nodes: vec![0, 1, 2],
+            node_affinity: Vec::new(),
+            colocated_with: Vec::new(),
+            separated_from: Vec::new(),
+        }
+    }
+
+    /// Test that the schedulers place the movable operators on eligible nodes, according to
+    /// their strategies.
+    #[test]
+    fn test_schedulers() {
+        let pinned = operator("pinned", 0, false);
+        let (mut a, mut b, mut c) = (
+            operator("a", 0, true),
+            operator("b", 0, true),
+            operator("c", 0, true),
+        );
+        c.eligible_nodes = vec![0];
+        let sorted = |mut operators: Vec<SchedulableOperator>| {
+            operators.sort_by_key(|operator| operator.id);
+            operators
+        };
+        let operators = sorted(vec![pinned.clone(), a.clone(), b.clone(), c.clone()]);
+
+        let placement = RoundRobinScheduler.place(&operators, 3).unwrap();
+        assert_eq!(placement[&c.id], 0);
+        assert_ne!(placement[&a.id], placement[&b.id]);
+        assert!(!placement.contains_key(&pinned.id));
+
+        // The pinned operator loads node 0, and node 2 has the most cores.
+        let placement = LoadAwareScheduler::new()
+            .node_capacity(2, 4.0)
+            .place(&operators, 3)
+            .unwrap();
+        assert_eq!(
+            (placement[&a.id], placement[&b.id], placement[&c.id]),
+            (2, 2, 0)
+        );
+
+        a.node_affinity = vec![1];
+        b.colocated_with = vec!["a".to_string()];
+        c.eligible_nodes = vec![0, 1];
+        c.separated_from = vec!["pinned".to_string()];
+        let operators = sorted(vec![pinned.clone(), a.clone(), b.clone(), c.clone()]);
+        let placement = ConstraintScheduler.place(&operators, 3).unwrap();
+        assert_eq!(
+            (placement[&a.id], placement[&b.id], placement[&c.id]),
+            (1, 1, 1)
+        );
+
+        c.eligible_nodes = vec![0];
+        let operators = sorted(vec![pinned, a, b, c]);
+        assert!(matches!(
+            ConstraintScheduler.place(&operators, 3),
+            Err(SchedulingError::Infeasible(_))
+        ));
+    }
+}
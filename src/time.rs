@@ -0,0 +1,165 @@
+//! Sources of time for operators.
+//!
+//! Operators read the time from the [`Clock`] set in their
+//! [`OperatorConfig`](crate::dataflow::OperatorConfig) instead of calling `Instant::now()` or
+//! `SystemTime::now()`, so that they also run on virtual time in tests and on dilated time in
+//! simulations.
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone)]
+enum Source {
+    Wallclock,
+    /// Time which runs `rate` times as fast as the wallclock, starting at `start_time`.
+    Dilated {
+        rate: f64,
+        start_time: Duration,
+        start_instant: Instant,
+    },
+    Virtual(Arc<VirtualTime>),
+}
+
+/// Time which only moves forward when [`Clock::advance`] is called.
+struct VirtualTime {
+    now: Mutex<Duration>,
+    advanced: Condvar,
+}
+
+/// A handle to a source of time.
+///
+/// Clones of a [`Clock`] share the same source of time. Defaults to the wallclock.
+#[derive(Clone)]
+pub struct Clock {
+    source: Source,
+}
+
+impl Clock {
+    /// Returns a clock which reads the time of the system.
+    pub fn wallclock() -> Self {
+        Self {
+            source: Source::Wallclock,
+        }
+    }
+
+    /// Returns a clock which starts at the time of the system, and runs `rate` times as fast as
+    /// the wallclock.
+    pub fn dilated(rate: f64) -> Self {
+        assert!(rate > 0.0, "The rate of a dilated clock must be positive.");
+        Self {
+            source: Source::Dilated {
+                rate,
+                start_time: system_time(),
+                start_instant: Instant::now(),
+            },
+        }
+    }
+
+    /// Returns a clock which starts at `start_time` since the UNIX epoch, and is moved forward
+    /// with [`Clock::advance`].
+    pub fn new_virtual(start_time: Duration) -> Self {
+        Self {
+            source: Source::Virtual(Arc::new(VirtualTime {
+                now: Mutex::new(start_time),
+                advanced: Condvar::new(),
+            })),
+        }
+    }
+
+    /// Returns the current time since the UNIX epoch.
+    pub fn now(&self) -> Duration {
+        match &self.source {
+            Source::Wallclock => system_time(),
+            Source::Dilated {
+                rate,
+                start_time,
+                start_instant,
+            } => *start_time + start_instant.elapsed().mul_f64(*rate),
+            Source::Virtual(time) => *time.now.lock().unwrap(),
+        }
+    }
+
+    /// Returns the time elapsed since `earlier`, which was returned by [`Clock::now`].
+    pub fn elapsed(&self, earlier: Duration) -> Duration {
+        self.now().checked_sub(earlier).unwrap_or_default()
+    }
+
+    /// Blocks the current thread until the clock moved forward by `duration`.
+    pub fn sleep(&self, duration: Duration) {
+        match &self.source {
+            Source::Wallclock => thread::sleep(duration),
+            Source::Dilated { rate, .. } => thread::sleep(duration.div_f64(*rate)),
+            Source::Virtual(time) => {
+                let mut now = time.now.lock().unwrap();
+                let wake_time = *now + duration;
+                while *now < wake_time {
+                    now = time.advanced.wait(now).unwrap();
+                }
+            }
+        }
+    }
+
+    /// Moves a virtual clock forward, and wakes up the threads sleeping until then.
+    ///
+    /// Panics if the clock is not virtual.
+    pub fn advance(&self, duration: Duration) {
+        match &self.source {
+            Source::Virtual(time) => {
+                *time.now.lock().unwrap() += duration;
+                time.advanced.notify_all();
+            }
+            _ => panic!("Only virtual clocks can be advanced."),
+        }
+    }
+
+    /// Returns true if the clock is moved forward with [`Clock::advance`].
+    pub fn is_virtual(&self) -> bool {
+        matches!(self.source, Source::Virtual(_))
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::wallclock()
+    }
+}
+
+fn system_time() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that sleeping on a virtual clock returns once the clock is advanced.
+    #[test]
+    fn test_virtual_clock() {
+        let clock = Clock::new_virtual(Duration::from_secs(10));
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(
+            clock.elapsed(Duration::from_secs(10)),
+            Duration::from_secs(3)
+        );
+
+        let sleeping_clock = clock.clone();
+        let (tx, rx) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            let start_time = sleeping_clock.now();
+            sleeping_clock.sleep(Duration::from_secs(5));
+            tx.send(sleeping_clock.elapsed(start_time)).unwrap();
+        });
+        // The sleeper may start at any time, so advance the clock until it wakes up.
+        let slept = loop {
+            if let Ok(slept) = rx.recv_timeout(Duration::from_millis(10)) {
+                break slept;
+            }
+            clock.advance(Duration::from_secs(1));
+        };
+        assert!(slept >= Duration::from_secs(5));
+    }
+}
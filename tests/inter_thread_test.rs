@@ -1,16 +1,22 @@
-use std::thread;
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
 
+use futures::{SinkExt, StreamExt};
 use slog;
 
 use erdos::{
     self,
+    dataflow::graph::default_graph,
     dataflow::{
         message::*,
         stream::{
             errors::{ReadError, TryReadError, WriteStreamError},
             ExtractStream, IngestStream, WriteStreamT,
         },
-        Operator, OperatorConfig, ReadStream, WriteStream,
+        ErrorPolicy, Operator, OperatorConfig, OperatorError, ReadStream, WriteStream,
     },
     node::Node,
     *,
@@ -63,6 +69,20 @@ impl RecvOperator {
 
 impl Operator for RecvOperator {}
 
+/// Takes the duration passed as argument to set up.
+pub struct SlowSetupOperator {}
+
+impl SlowSetupOperator {
+    pub fn new(config: OperatorConfig<Duration>) -> Self {
+        thread::sleep(config.arg.unwrap());
+        Self {}
+    }
+
+    pub fn connect() {}
+}
+
+impl Operator for SlowSetupOperator {}
+
 pub struct SquareOperator {}
 
 impl SquareOperator {
@@ -115,6 +135,30 @@ impl Operator for DestroyOperator {
     }
 }
 
+/// Fails upon the first message it receives.
+pub struct FailingOperator {}
+
+impl FailingOperator {
+    pub fn new(
+        _config: OperatorConfig<()>,
+        read_stream: ReadStream<usize>,
+        write_stream: WriteStream<usize>,
+    ) -> Self {
+        read_stream.add_state(write_stream).add_callback(
+            |_t: &Timestamp, _data: &usize, _write_stream: &mut WriteStream<usize>| {
+                Err::<(), _>(OperatorError::new("failed on purpose"))
+            },
+        );
+        Self {}
+    }
+
+    pub fn connect(_read_stream: &ReadStream<usize>) -> WriteStream<usize> {
+        WriteStream::new()
+    }
+}
+
+impl Operator for FailingOperator {}
+
 /// Noop operator which may flow watermarks.
 pub struct TwoStreamDestroyOperator {}
 
@@ -167,6 +211,79 @@ fn test_shutdown() {
     node_handle.shutdown().unwrap();
 }
 
+#[test]
+fn test_startup_report() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let s = connect_1_write!(SendOperator, OperatorConfig::new().name("SendOperator"));
+    connect_0_write!(RecvOperator, OperatorConfig::new().name("RecvOperator"), s);
+
+    let node_handle = node.run_async();
+    let report = node_handle.startup_report();
+    assert!(report.total.is_some());
+    for phase in ["connections", "channels", "operator setup", "cluster setup"].iter() {
+        assert!(report.phase(phase).is_some(), "Missing phase {}", phase);
+    }
+    node_handle.shutdown().unwrap();
+}
+
+#[test]
+#[should_panic(expected = "stopped before it finished setting up")]
+fn test_setup_timeout() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let config = OperatorConfig::new()
+        .name("SlowSetupOperator")
+        .arg(Duration::from_secs(2))
+        .setup_timeout(Duration::from_millis(100));
+    connect_0_write!(SlowSetupOperator, config);
+
+    node.run_async();
+}
+
+#[test]
+fn test_driver_sections() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    for &section in ["camera", "logging"].iter() {
+        let events_copy = Arc::clone(&events);
+        default_graph::with_driver_section(section, || {
+            let setup_events = Arc::clone(&events_copy);
+            default_graph::add_driver_setup_hook(0, move |_| {
+                setup_events
+                    .lock()
+                    .unwrap()
+                    .push(format!("setup {}", section));
+            });
+            let teardown_events = Arc::clone(&events_copy);
+            default_graph::add_driver_teardown_hook(0, move || {
+                teardown_events
+                    .lock()
+                    .unwrap()
+                    .push(format!("teardown {}", section));
+            });
+        });
+    }
+    let s = connect_1_write!(SendOperator, OperatorConfig::new().name("SendOperator"));
+    connect_0_write!(RecvOperator, OperatorConfig::new().name("RecvOperator"), s);
+
+    let node_handle = node.run_async();
+    node_handle.shutdown().unwrap();
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            "setup camera",
+            "setup logging",
+            "teardown logging",
+            "teardown camera"
+        ]
+    );
+}
+
 #[test]
 fn test_ingest() {
     let config = utils::make_default_config();
@@ -214,6 +331,29 @@ fn test_extract() {
     }
 }
 
+#[test]
+fn test_extract_async() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let s = connect_1_write!(SendOperator, OperatorConfig::new().name("SendOperator"));
+    let mut extract_stream = ExtractStream::new(0, &s);
+
+    node.run_async();
+
+    futures::executor::block_on(async {
+        for count in 0..5 {
+            assert_eq!(
+                extract_stream.next().await,
+                Some(Message::new_message(
+                    Timestamp::new(vec![count as u64]),
+                    count as usize
+                ))
+            );
+        }
+    });
+}
+
 #[test]
 fn test_ingest_extract() {
     let config = utils::make_default_config();
@@ -240,6 +380,130 @@ fn test_ingest_extract() {
     }
 }
 
+#[test]
+fn test_thread_per_core() {
+    let config = utils::make_default_config().with_executor(ExecutorModel::ThreadPerCore);
+    let node = Node::new(config);
+
+    let s = connect_1_write!(SendOperator, OperatorConfig::new().name("SendOperator"));
+    let square_stream = connect_1_write!(
+        SquareOperator,
+        OperatorConfig::new().name("SquareOperator"),
+        s
+    );
+    let mut extract_stream = ExtractStream::new(0, &square_stream);
+
+    node.run_async();
+
+    for count in 0..5 {
+        let msg = extract_stream.read().unwrap();
+        assert_eq!(msg.data(), Some(&(count * count)));
+    }
+}
+
+#[test]
+fn test_ingest_sink() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let mut ingest_stream = IngestStream::new(0);
+    let square_stream = connect_1_write!(
+        SquareOperator,
+        OperatorConfig::new().name("SquareOperator"),
+        ingest_stream
+    );
+    let mut extract_stream = ExtractStream::new(0, &square_stream);
+
+    node.run_async();
+
+    futures::executor::block_on(async {
+        for count in 0..5 {
+            let msg = Message::new_message(Timestamp::new(vec![count as u64]), count);
+            SinkExt::send(&mut ingest_stream, msg).await.unwrap();
+            assert_eq!(
+                extract_stream
+                    .next()
+                    .await
+                    .and_then(|msg| msg.data().cloned()),
+                Some(count * count)
+            );
+        }
+        ingest_stream.close().await.unwrap();
+    });
+    assert!(ingest_stream.is_closed());
+}
+
+#[test]
+fn test_extract_read_until_watermark() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let mut ingest_stream = IngestStream::new(0);
+    let square_stream = connect_1_write!(
+        SquareOperator,
+        OperatorConfig::new().name("SquareOperator"),
+        ingest_stream
+    );
+    let mut extract_stream = ExtractStream::new(0, &square_stream);
+    let watermarks = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let watermarks_copy = std::sync::Arc::clone(&watermarks);
+    extract_stream.add_watermark_callback(move |t| watermarks_copy.lock().unwrap().push(t.clone()));
+
+    node.run_async();
+
+    for count in 1..4 {
+        let msg = Message::new_message(Timestamp::new(vec![1]), count);
+        ingest_stream.send(msg).unwrap();
+    }
+    ingest_stream
+        .send(Message::new_watermark(Timestamp::new(vec![1])))
+        .unwrap();
+    let squares: Vec<usize> = extract_stream
+        .read_until_watermark(&Timestamp::new(vec![1]))
+        .unwrap()
+        .iter()
+        .filter_map(|msg| msg.data().cloned())
+        .collect();
+    assert_eq!(squares, vec![1, 4, 9]);
+    assert_eq!(*watermarks.lock().unwrap(), vec![Timestamp::new(vec![1])]);
+    assert_eq!(
+        extract_stream.get_low_watermark(),
+        Some(&Timestamp::new(vec![1]))
+    );
+    // The watermark was already read.
+    assert!(extract_stream
+        .read_until_watermark(&Timestamp::new(vec![0]))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_channel_streams() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let (input_tx, input_rx) = tokio::sync::mpsc::unbounded_channel();
+    let (output_tx, output_rx) = tokio::sync::mpsc::unbounded_channel();
+    let input_stream = erdos::dataflow::stream::stream_from_channel(0, input_rx, |count| {
+        Timestamp::new(vec![*count as u64])
+    });
+    let square_stream = connect_1_write!(
+        SquareOperator,
+        OperatorConfig::new().name("SquareOperator"),
+        input_stream
+    );
+    erdos::dataflow::stream::stream_to_channel(0, &square_stream, output_tx);
+
+    node.run_async();
+
+    for count in 0..5usize {
+        input_tx.send(count).unwrap();
+    }
+    drop(input_tx);
+    let squares: Vec<usize> = futures::executor::block_on(output_rx.collect());
+    assert_eq!(squares, vec![0, 1, 4, 9, 16]);
+}
+
 #[test]
 fn test_destroy() {
     let config = utils::make_default_config();
@@ -356,3 +620,29 @@ fn test_close_write_streams_on_destroy() {
     assert_eq!(extract_stream.try_read(), Err(TryReadError::Closed));
     assert!(extract_stream.is_closed());
 }
+
+/// Test that the write streams of an operator which fails are closed.
+#[test]
+fn test_close_write_streams_on_failure() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let mut ingest_stream = IngestStream::new(0);
+    let s = connect_1_write!(
+        FailingOperator,
+        OperatorConfig::new().error_policy(ErrorPolicy::FailOperator),
+        ingest_stream
+    );
+    let mut extract_stream = ExtractStream::new(0, &s);
+
+    node.run_async();
+
+    ingest_stream
+        .send(Message::new_message(Timestamp::new(vec![1]), 1))
+        .unwrap();
+
+    // Receive the top watermark sent once the operator failed.
+    let msg = extract_stream.read().unwrap();
+    assert_eq!(msg, Message::new_watermark(Timestamp::top()));
+    assert!(extract_stream.is_closed());
+}
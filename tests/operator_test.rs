@@ -1,9 +1,24 @@
 extern crate erdos;
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
 use erdos::dataflow::{
     operators::JoinOperator,
     operators::MapOperator,
-    stream::{ExtractStream, WriteStreamT},
-    Message, Operator, OperatorConfig, Timestamp, WriteStream,
+    operators::{connect_align, AlignConfig, AlignedMessages},
+    operators::{connect_async_map_fn, connect_filter_fn, connect_map_fn},
+    operators::{CircuitBreakerConfig, CircuitBreakerOperator, CircuitState},
+    operators::{DeltaDecodeOperator, DeltaEncodeConfig, DeltaEncodeOperator, RetimestampOperator},
+    operators::{Deployment, SwitchConfig, SwitchOperator},
+    operators::{DivergenceReport, ShadowCompareConfig, ShadowCompareOperator},
+    operators::{ResampleConfig, ResampleOperator},
+    operators::{RetryConfig, RetryOperator},
+    stream::{ExtractStream, IngestStream, WriteStreamT},
+    Message, Operator, OperatorConfig, OperatorError, ReadStream, RunContext, TimeDomain,
+    Timestamp, WriteStream,
 };
 use erdos::node::Node;
 use erdos::*;
@@ -70,6 +85,680 @@ fn test_input_receiver_map() {
     }
 }
 
+// Operator Dependency Tests.
+static CALIBRATION_LOADED: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+pub struct CalibrationLoaderOp {}
+
+impl CalibrationLoaderOp {
+    pub fn new(_config: OperatorConfig<()>) -> Self {
+        Self {}
+    }
+
+    pub fn connect() {}
+}
+
+impl Operator for CalibrationLoaderOp {
+    fn run(&mut self) {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        CALIBRATION_LOADED.store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+pub struct PerceptionOp {
+    output_stream: WriteStream<bool>,
+}
+
+impl PerceptionOp {
+    pub fn new(_config: OperatorConfig<()>, output_stream: WriteStream<bool>) -> Self {
+        Self { output_stream }
+    }
+
+    pub fn connect() -> WriteStream<bool> {
+        WriteStream::new()
+    }
+}
+
+impl Operator for PerceptionOp {
+    fn run(&mut self) {
+        let loaded = CALIBRATION_LOADED.load(std::sync::atomic::Ordering::SeqCst);
+        self.output_stream
+            .send(Message::new_message(Timestamp::new(vec![0]), loaded))
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_operator_depends_on() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    connect_0_write!(
+        CalibrationLoaderOp,
+        OperatorConfig::new().name("CalibrationLoader")
+    );
+    let s = connect_1_write!(
+        PerceptionOp,
+        OperatorConfig::new()
+            .name("Perception")
+            .depends_on("CalibrationLoader")
+    );
+    let mut extract_stream = ExtractStream::new(0, &s);
+
+    node.run_async();
+
+    let msg = extract_stream.read().unwrap();
+    if let Message::TimestampedData(data) = msg {
+        assert!(
+            data.data,
+            "The operator ran before the operator it depends on."
+        );
+    } else {
+        panic!("Expected a data message");
+    }
+}
+
+static SOURCE_INITIALIZED: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+/// Runs until the dependent operator ran, after telling it to run once initialized.
+pub struct StreamingSourceOp {}
+
+impl StreamingSourceOp {
+    pub fn new(_config: OperatorConfig<()>) -> Self {
+        Self {}
+    }
+
+    pub fn connect() {}
+}
+
+impl Operator for StreamingSourceOp {
+    fn run_with_context(&mut self, ctx: &mut RunContext) {
+        SOURCE_INITIALIZED.store(true, std::sync::atomic::Ordering::SeqCst);
+        ctx.ready();
+        while SOURCE_INITIALIZED.load(std::sync::atomic::Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+pub struct SourceDependentOp {
+    output_stream: WriteStream<bool>,
+}
+
+impl SourceDependentOp {
+    pub fn new(_config: OperatorConfig<()>, output_stream: WriteStream<bool>) -> Self {
+        Self { output_stream }
+    }
+
+    pub fn connect() -> WriteStream<bool> {
+        WriteStream::new()
+    }
+}
+
+impl Operator for SourceDependentOp {
+    fn run(&mut self) {
+        let initialized = SOURCE_INITIALIZED.swap(false, std::sync::atomic::Ordering::SeqCst);
+        self.output_stream
+            .send(Message::new_message(Timestamp::new(vec![0]), initialized))
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_operator_depends_on_ready() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    connect_0_write!(
+        StreamingSourceOp,
+        OperatorConfig::new().name("StreamingSource")
+    );
+    let s = connect_1_write!(
+        SourceDependentOp,
+        OperatorConfig::new()
+            .name("SourceDependent")
+            .depends_on("StreamingSource")
+    );
+    let mut extract_stream = ExtractStream::new(0, &s);
+
+    node.run_async();
+
+    let msg = extract_stream.read().unwrap();
+    assert_eq!(
+        msg.data(),
+        Some(&true),
+        "The operator did not run after the operator it depends on was ready."
+    );
+}
+
+// Pull Operator Tests.
+pub struct PullDoubleOp {
+    read_stream: ReadStream<u32>,
+    write_stream: WriteStream<u32>,
+}
+
+impl PullDoubleOp {
+    pub fn new(
+        _config: OperatorConfig<()>,
+        read_stream: ReadStream<u32>,
+        write_stream: WriteStream<u32>,
+    ) -> Self {
+        Self {
+            read_stream,
+            write_stream,
+        }
+    }
+
+    pub fn connect(_read_stream: &ReadStream<u32>) -> WriteStream<u32> {
+        WriteStream::new()
+    }
+}
+
+#[erdos::async_trait(?Send)]
+impl Operator for PullDoubleOp {
+    async fn run_async(&mut self, _ctx: &mut RunContext) {
+        while let Ok(msg) = self.read_stream.recv().await {
+            if let Some(data) = msg.data() {
+                self.write_stream
+                    .send(Message::new_message(msg.timestamp().clone(), 2 * data))
+                    .unwrap();
+            }
+        }
+    }
+}
+
+#[test]
+fn test_pull_operator_flows_watermarks() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let mut ingest_stream = IngestStream::new(0);
+    let s = connect_1_write!(
+        PullDoubleOp,
+        OperatorConfig::new().name("PullDoubleOp"),
+        ingest_stream
+    );
+    let mut extract_stream = ExtractStream::new(0, &s);
+
+    node.run_async();
+
+    for t in 1..3 {
+        let timestamp = Timestamp::new(vec![t]);
+        ingest_stream
+            .send(Message::new_message(timestamp.clone(), t as u32))
+            .unwrap();
+        ingest_stream
+            .send(Message::new_watermark(timestamp.clone()))
+            .unwrap();
+        assert_eq!(
+            extract_stream.read().unwrap(),
+            Message::new_message(timestamp.clone(), 2 * t as u32)
+        );
+        // The watermark callbacks run for the messages received in the pull loop.
+        assert_eq!(
+            extract_stream.read().unwrap(),
+            Message::new_watermark(timestamp)
+        );
+    }
+}
+
+// Lazy Activation Tests.
+pub struct OnDemandSourceOp {
+    output_stream: WriteStream<u32>,
+}
+
+impl OnDemandSourceOp {
+    pub fn new(_config: OperatorConfig<()>, output_stream: WriteStream<u32>) -> Self {
+        Self { output_stream }
+    }
+
+    pub fn connect() -> WriteStream<u32> {
+        WriteStream::new()
+    }
+}
+
+impl Operator for OnDemandSourceOp {
+    fn run(&mut self) {
+        self.output_stream.wait_for_demand();
+        self.output_stream
+            .send(Message::new_message(Timestamp::new(vec![0]), 1))
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_source_waits_for_demand() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let s = connect_1_write!(
+        OnDemandSourceOp,
+        OperatorConfig::new().name("OnDemandSource")
+    );
+    let mut extract_stream = ExtractStream::new(0, &s);
+
+    node.run_async();
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    assert!(
+        extract_stream.try_read().is_err(),
+        "The source produced a message without demand."
+    );
+    let _subscription = extract_stream.subscribe();
+    let msg = extract_stream.read().unwrap();
+    assert!(matches!(msg, Message::TimestampedData(_)));
+}
+
+// Retimestamp Operator Tests.
+#[test]
+fn test_retimestamp_ingestion_time() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let clock = erdos::time::Clock::new_virtual(std::time::Duration::from_millis(100));
+    let mut ingest_stream = IngestStream::new(0);
+    ingest_stream.set_clock(clock.clone());
+    let s = connect_1_write!(
+        RetimestampOperator<u32>,
+        OperatorConfig::new()
+            .name("RetimestampOperator")
+            .flow_watermarks(false)
+            .arg(TimeDomain::IngestionTime),
+        ingest_stream
+    );
+    let mut extract_stream = ExtractStream::new(0, &s);
+
+    node.run_async();
+
+    ingest_stream
+        .send(Message::new_message(Timestamp::new(vec![7]), 7))
+        .unwrap();
+    ingest_stream
+        .send(Message::new_watermark(Timestamp::new(vec![7])))
+        .unwrap();
+    match extract_stream.read().unwrap() {
+        Message::TimestampedData(td) => {
+            assert_eq!(td.timestamp, Timestamp::new(vec![100]));
+            assert_eq!(td.time(TimeDomain::EventTime), &Timestamp::new(vec![7]));
+        }
+        msg => panic!("Expected a data message, received {:?}", msg),
+    }
+    assert_eq!(
+        extract_stream.read().unwrap(),
+        Message::new_watermark(Timestamp::new(vec![100]))
+    );
+}
+
+// Function Operator Tests.
+#[test]
+fn test_fn_operators() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let mut ingest_stream = IngestStream::new(0);
+    let doubled = connect_map_fn(
+        OperatorConfig::new().name("Double"),
+        &ingest_stream,
+        |data: &u32| 2 * data,
+    );
+    let filtered = connect_filter_fn(
+        OperatorConfig::new().name("MultipleOfFour"),
+        &doubled,
+        |data: &u32| data % 4 == 0,
+    );
+    let incremented = connect_async_map_fn(
+        OperatorConfig::new().name("AsyncIncrement"),
+        &filtered,
+        |data: u32| async move { data as u64 + 1 },
+    );
+    let mut extract_stream = ExtractStream::new(0, &incremented);
+
+    node.run_async();
+
+    for t in 1..5 {
+        let timestamp = Timestamp::new(vec![t]);
+        ingest_stream
+            .send(Message::new_message(timestamp.clone(), t as u32))
+            .unwrap();
+        ingest_stream
+            .send(Message::new_watermark(timestamp.clone()))
+            .unwrap();
+        if t % 2 == 0 {
+            assert_eq!(
+                extract_stream.read().unwrap(),
+                Message::new_message(timestamp.clone(), 2 * t + 1)
+            );
+        }
+        // Watermarks are forwarded after the messages before them.
+        assert_eq!(
+            extract_stream.read().unwrap(),
+            Message::new_watermark(timestamp)
+        );
+    }
+}
+
+// Retry Operator Tests.
+/// Test that the calls which fail are retried, and that the fallback value and a failure report
+/// are sent for the calls which fail on every attempt.
+#[test]
+fn test_retry_operator() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let mut ingest_stream = IngestStream::new(0);
+    // Fails on the first attempt for each message, and on every attempt for 0.
+    let attempted = Arc::new(Mutex::new(HashSet::new()));
+    let flaky_call = move |data: u32| {
+        let first_attempt = attempted.lock().unwrap().insert(data);
+        async move {
+            if first_attempt || data == 0 {
+                Err(OperatorError::new("service unavailable"))
+            } else {
+                Ok(data as u64 * 10)
+            }
+        }
+    };
+    let retry_config = RetryConfig::new(flaky_call)
+        .max_attempts(3)
+        .timeout(Duration::from_secs(1))
+        .backoff(Duration::from_millis(1))
+        .fallback(u64::MAX);
+    let (values, failures) = connect_2_write!(
+        RetryOperator<u32, u64>,
+        OperatorConfig::new()
+            .name("RetryOperator")
+            .flow_watermarks(false)
+            .arg(retry_config),
+        ingest_stream
+    );
+    let mut values_extract = ExtractStream::new(0, &values);
+    let mut failures_extract = ExtractStream::new(0, &failures);
+
+    node.run_async();
+
+    for t in 0..3 {
+        let timestamp = Timestamp::new(vec![t]);
+        ingest_stream
+            .send(Message::new_message(timestamp.clone(), t as u32))
+            .unwrap();
+        ingest_stream
+            .send(Message::new_watermark(timestamp.clone()))
+            .unwrap();
+        let expected = if t == 0 { u64::MAX } else { t * 10 };
+        assert_eq!(
+            values_extract.read().unwrap(),
+            Message::new_message(timestamp.clone(), expected)
+        );
+        assert_eq!(
+            values_extract.read().unwrap(),
+            Message::new_watermark(timestamp.clone())
+        );
+        if t == 0 {
+            match failures_extract.read().unwrap() {
+                Message::TimestampedData(msg) => {
+                    assert_eq!(msg.timestamp, timestamp);
+                    assert!(msg.data.error.reason().contains("failed after 3 attempts"));
+                }
+                msg => panic!("Expected a failure report, received {:?}", msg),
+            }
+        }
+        assert_eq!(
+            failures_extract.read().unwrap(),
+            Message::new_watermark(timestamp)
+        );
+    }
+}
+
+#[test]
+fn test_align_operator() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let mut camera_stream = IngestStream::new(0);
+    let mut lidar_stream = IngestStream::new(0);
+    let mut radar_stream = IngestStream::new(0);
+    let aligned = connect_align(
+        OperatorConfig::new()
+            .name("AlignOperator")
+            .arg(AlignConfig::new(10)),
+        vec![&camera_stream, &lidar_stream, &radar_stream],
+    );
+    let mut extract_stream = ExtractStream::new(0, &aligned);
+
+    node.run_async();
+
+    for (stream, times) in vec![
+        (&mut camera_stream, vec![100, 200]),
+        (&mut lidar_stream, vec![95, 108, 190]),
+        (&mut radar_stream, vec![150]),
+    ] {
+        for time in times {
+            stream
+                .send(Message::new_message(
+                    Timestamp::new(vec![time]),
+                    time as u32,
+                ))
+                .unwrap();
+        }
+        stream
+            .send(Message::new_watermark(Timestamp::new(vec![210])))
+            .unwrap();
+    }
+
+    // The radar message is too far in time from both camera messages.
+    let aligned_message = |time: u64, lidar_time: u64| {
+        let message = |time: u64| Some((Timestamp::new(vec![time]), time as u32));
+        Message::new_message(
+            Timestamp::new(vec![time]),
+            AlignedMessages {
+                messages: vec![message(time), message(lidar_time), None],
+            },
+        )
+    };
+    assert_eq!(extract_stream.read().unwrap(), aligned_message(100, 95));
+    assert_eq!(extract_stream.read().unwrap(), aligned_message(200, 190));
+    assert_eq!(
+        extract_stream.read().unwrap(),
+        Message::new_watermark(Timestamp::new(vec![210]))
+    );
+}
+
+#[test]
+fn test_resample_operator() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let mut ingest_stream = IngestStream::new(0);
+    let resample_config = ResampleConfig::new(8, |before: &f64, after: &f64, fraction: f64| {
+        before + (after - before) * fraction
+    });
+    let resampled = connect_1_write!(
+        ResampleOperator<f64>,
+        OperatorConfig::new()
+            .name("ResampleOperator")
+            .flow_watermarks(false)
+            .arg(resample_config),
+        ingest_stream
+    );
+    let mut extract_stream = ExtractStream::new(0, &resampled);
+
+    node.run_async();
+
+    let send_sample = |ingest_stream: &mut IngestStream<f64>, time: u64| {
+        ingest_stream
+            .send(Message::new_message(
+                Timestamp::new(vec![time]),
+                time as f64,
+            ))
+            .unwrap();
+    };
+    let expected = |time: u64| Message::new_message(Timestamp::new(vec![time]), time as f64);
+    for &time in &[0, 16, 32] {
+        send_sample(&mut ingest_stream, time);
+    }
+    ingest_stream
+        .send(Message::new_watermark(Timestamp::new(vec![32])))
+        .unwrap();
+    for &time in &[0, 8, 16, 24, 32] {
+        assert_eq!(extract_stream.read().unwrap(), expected(time));
+    }
+    assert_eq!(
+        extract_stream.read().unwrap(),
+        Message::new_watermark(Timestamp::new(vec![32]))
+    );
+
+    // The sample at time 32 arrives after the output for 32 was sent, and is dropped. The output
+    // for 40 is interpolated once the stream closes.
+    ingest_stream
+        .send(Message::new_message(Timestamp::new(vec![32, 1]), 0.0))
+        .unwrap();
+    send_sample(&mut ingest_stream, 48);
+    ingest_stream
+        .send(Message::new_watermark(Timestamp::top()))
+        .unwrap();
+    for &time in &[40, 48] {
+        assert_eq!(extract_stream.read().unwrap(), expected(time));
+    }
+    assert_eq!(
+        extract_stream.read().unwrap(),
+        Message::new_watermark(Timestamp::top())
+    );
+}
+
+#[test]
+fn test_circuit_breaker_operator() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let mut ingest_stream = IngestStream::new(0);
+    let clock = erdos::time::Clock::new_virtual(Duration::from_secs(1));
+    // The service is down for the first 3 messages.
+    let call = |data: u32| async move {
+        if data < 3 {
+            Err(OperatorError::new("service unavailable"))
+        } else {
+            Ok(data as u64 * 10)
+        }
+    };
+    let breaker_config = CircuitBreakerConfig::new(call)
+        .failure_rate_threshold(1.0)
+        .window(3)
+        .min_calls(3)
+        .open_duration(Duration::from_secs(10))
+        .fallback(u64::MAX);
+    let (values, transitions) = connect_2_write!(
+        CircuitBreakerOperator<u32, u64>,
+        OperatorConfig::new()
+            .name("CircuitBreakerOperator")
+            .flow_watermarks(false)
+            .clock(clock.clone())
+            .arg(breaker_config),
+        ingest_stream
+    );
+    let mut values_extract = ExtractStream::new(0, &values);
+    let mut transitions_extract = ExtractStream::new(0, &transitions);
+
+    node.run_async();
+
+    // The call for 3 is short-circuited, and the call for 4 is the trial once the circuit
+    // stayed open for the open duration.
+    for (t, expected) in [u64::MAX, u64::MAX, u64::MAX, u64::MAX, 40]
+        .iter()
+        .enumerate()
+    {
+        if t == 4 {
+            clock.advance(Duration::from_secs(10));
+        }
+        let timestamp = Timestamp::new(vec![t as u64]);
+        ingest_stream
+            .send(Message::new_message(timestamp.clone(), t as u32))
+            .unwrap();
+        ingest_stream
+            .send(Message::new_watermark(timestamp.clone()))
+            .unwrap();
+        assert_eq!(
+            values_extract.read().unwrap(),
+            Message::new_message(timestamp.clone(), *expected)
+        );
+        assert_eq!(
+            values_extract.read().unwrap(),
+            Message::new_watermark(timestamp)
+        );
+    }
+
+    let mut changes = Vec::new();
+    for _ in 0..8 {
+        if let Message::TimestampedData(msg) = transitions_extract.read().unwrap() {
+            changes.push((msg.data.timestamp, msg.data.from, msg.data.to));
+        }
+    }
+    assert_eq!(
+        changes,
+        vec![
+            (
+                Timestamp::new(vec![2]),
+                CircuitState::Closed,
+                CircuitState::Open
+            ),
+            (
+                Timestamp::new(vec![4]),
+                CircuitState::Open,
+                CircuitState::HalfOpen
+            ),
+            (
+                Timestamp::new(vec![4]),
+                CircuitState::HalfOpen,
+                CircuitState::Closed
+            ),
+        ]
+    );
+}
+
+// Delta Operator Tests.
+#[test]
+fn test_input_receiver_delta() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let s1 = connect_1_write!(InputGenOp, OperatorConfig::new().name("InputOperator"));
+    // Send the difference between consecutive messages, with a keyframe every 3 messages.
+    let s2 = connect_1_write!(
+        DeltaEncodeOperator<u32, i64>,
+        OperatorConfig::new()
+            .name("DeltaEncodeOperator")
+            .arg(DeltaEncodeConfig::new(
+                |previous: &u32, current: &u32| -> i64 { *current as i64 - *previous as i64 },
+                3
+            )),
+        s1
+    );
+    let s3 = connect_1_write!(
+        DeltaDecodeOperator<u32, i64>,
+        OperatorConfig::new()
+            .name("DeltaDecodeOperator")
+            .arg(|previous: &u32, delta: &i64| -> u32 { (*previous as i64 + delta) as u32 }),
+        s2
+    );
+    let mut extract_stream = ExtractStream::new(0, &s3);
+
+    node.run_async();
+
+    let mut i = 0;
+    while i < 10 {
+        let msg = extract_stream.read();
+        if let Message::TimestampedData(data) = msg.unwrap() {
+            assert_eq!(
+                data.data, i,
+                "The returned value ({}) was different than expected ({}).",
+                data.data, i
+            );
+            i += 1;
+        }
+    }
+}
+
 // Join Operator Tests.
 #[test]
 fn test_input_receiver_join() {
@@ -110,3 +799,119 @@ fn test_input_receiver_join() {
         }
     }
 }
+
+// Switch Operator Tests.
+#[test]
+fn test_switch_deployment() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let mut blue_stream = IngestStream::new(0);
+    let mut green_stream = IngestStream::new(0);
+    let s = connect_1_write!(
+        SwitchOperator<u32>,
+        OperatorConfig::new()
+            .name("SwitchOperator")
+            .arg(SwitchConfig::register(
+                &node.runtime_knobs(),
+                "deployment",
+                Deployment::Blue
+            )),
+        blue_stream,
+        green_stream
+    );
+    let mut extract_stream = ExtractStream::new(0, &s);
+
+    let node_handle = node.run_async();
+
+    // Switch to the green deployment after the first timestamp.
+    for (t, deployment) in &[(1, Deployment::Blue), (2, Deployment::Green)] {
+        node_handle
+            .set_knob("deployment", &deployment.to_string())
+            .unwrap();
+        let timestamp = Timestamp::new(vec![*t]);
+        blue_stream
+            .send(Message::new_message(timestamp.clone(), 10 * *t as u32))
+            .unwrap();
+        blue_stream
+            .send(Message::new_watermark(timestamp.clone()))
+            .unwrap();
+        green_stream
+            .send(Message::new_message(timestamp.clone(), 20 * *t as u32))
+            .unwrap();
+        green_stream
+            .send(Message::new_watermark(timestamp.clone()))
+            .unwrap();
+        let expected_data = match deployment {
+            Deployment::Blue => 10 * *t as u32,
+            Deployment::Green => 20 * *t as u32,
+        };
+        assert_eq!(
+            extract_stream.read().unwrap(),
+            Message::new_message(timestamp.clone(), expected_data)
+        );
+        assert_eq!(
+            extract_stream.read().unwrap(),
+            Message::new_watermark(timestamp)
+        );
+    }
+}
+
+// Shadow Compare Operator Tests.
+#[test]
+fn test_shadow_compare() {
+    let config = utils::make_default_config();
+    let node = Node::new(config);
+
+    let mut primary_stream = IngestStream::new(0);
+    let mut candidate_stream = IngestStream::new(0);
+    let s = connect_1_write!(
+        ShadowCompareOperator<u32>,
+        OperatorConfig::new()
+            .name("ShadowCompareOperator")
+            .arg(ShadowCompareConfig::new(
+                |primary: &[u32], candidate: &[u32]| -> f64 {
+                    (primary.iter().sum::<u32>() as f64 - candidate.iter().sum::<u32>() as f64)
+                        .abs()
+                },
+                1.0
+            )),
+        primary_stream,
+        candidate_stream
+    );
+    let mut extract_stream = ExtractStream::new(0, &s);
+
+    node.run_async();
+
+    // Only the second timestamp diverges by more than the threshold.
+    for (t, primary, candidate) in &[(1, 10, 11), (2, 10, 15)] {
+        let timestamp = Timestamp::new(vec![*t]);
+        primary_stream
+            .send(Message::new_message(timestamp.clone(), *primary))
+            .unwrap();
+        primary_stream
+            .send(Message::new_watermark(timestamp.clone()))
+            .unwrap();
+        candidate_stream
+            .send(Message::new_message(timestamp.clone(), *candidate))
+            .unwrap();
+        candidate_stream
+            .send(Message::new_watermark(timestamp))
+            .unwrap();
+    }
+    assert_eq!(
+        extract_stream.read().unwrap(),
+        Message::new_watermark(Timestamp::new(vec![1]))
+    );
+    assert_eq!(
+        extract_stream.read().unwrap(),
+        Message::new_message(
+            Timestamp::new(vec![2]),
+            DivergenceReport {
+                divergence: 5.0,
+                primary: vec![10],
+                candidate: vec![15],
+            }
+        )
+    );
+}
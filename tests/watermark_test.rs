@@ -8,7 +8,7 @@ use erdos::{
     node::Node,
     *,
 };
-use std::thread;
+use std::{thread, time::Duration};
 
 mod utils;
 
@@ -38,6 +38,35 @@ impl Operator for SendOperator {
     }
 }
 
+/// Sends watermarks up to the timestamp passed as argument.
+pub struct SendUntilOperator {
+    last_watermark: u64,
+    write_stream: WriteStream<usize>,
+}
+
+impl SendUntilOperator {
+    pub fn new(config: OperatorConfig<u64>, write_stream: WriteStream<usize>) -> Self {
+        Self {
+            last_watermark: config.arg.unwrap(),
+            write_stream,
+        }
+    }
+
+    pub fn connect() -> WriteStream<usize> {
+        WriteStream::new()
+    }
+}
+
+impl Operator for SendUntilOperator {
+    fn run(&mut self) {
+        for count in 0..=self.last_watermark {
+            self.write_stream
+                .send(Message::new_watermark(Timestamp::new(vec![count])))
+                .unwrap();
+        }
+    }
+}
+
 pub struct MultiStreamCallbackOperator {}
 
 impl MultiStreamCallbackOperator {
@@ -178,3 +207,36 @@ fn test_multi_stream_watermark_callbacks() {
         );
     }
 }
+
+/// Test that nodes which run in the same process and share stream IDs only observe their own
+/// watermarks.
+#[test]
+fn test_isolated_nodes_watermarks() {
+    erdos::reset();
+    let mut first_node = Node::new(utils::make_default_config());
+    let s1 = connect_1_write!(
+        SendUntilOperator,
+        OperatorConfig::new().name("SendUntilOperator").arg(4)
+    );
+    let first_watcher = first_node.watch_watermarks(s1.get_id());
+    let first_handle = first_node.run_async();
+
+    // Connects the same operator, which writes to a stream with the same ID.
+    erdos::reset();
+    let mut second_node = Node::new(utils::make_default_config());
+    let s2 = connect_1_write!(
+        SendUntilOperator,
+        OperatorConfig::new().name("SendUntilOperator").arg(1)
+    );
+    assert_eq!(s1.get_id(), s2.get_id());
+    let second_watcher = second_node.watch_watermarks(s2.get_id());
+    let second_handle = second_node.run_async();
+
+    let timeout = Duration::from_secs(5);
+    assert!(first_watcher.wait_for(&Timestamp::new(vec![4]), timeout));
+    assert!(second_watcher.wait_for(&Timestamp::new(vec![1]), timeout));
+    thread::sleep(Duration::from_millis(500));
+    assert_eq!(second_watcher.watermark(), Some(Timestamp::new(vec![1])));
+    first_handle.shutdown().unwrap();
+    second_handle.shutdown().unwrap();
+}